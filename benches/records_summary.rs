@@ -0,0 +1,120 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Compares the throughput of `ntfs::scan::records_summary` against collecting the same information
+// via the individual convenience functions of `NtfsFile`.
+//
+// Run via `cargo bench --bench records_summary`.
+
+use std::fs::File;
+use std::io::{Cursor, Read, Seek};
+use std::time::{Duration, Instant};
+
+use ntfs::scan::records_summary;
+use ntfs::structured_values::NtfsFileNamespace;
+use ntfs::Ntfs;
+
+/// Number of passes over the MFT of the test filesystem.
+/// This simulates the scan of a large MFT with several hundred thousand File Records.
+const PASSES: usize = 500;
+
+/// (File Record Number, name, parent File Record Number, modification time, data size)
+type Row = (u64, Option<String>, Option<u64>, Option<u64>, Option<u64>);
+
+fn scan_with_summaries<T: Read + Seek>(ntfs: &Ntfs, fs: &mut T) -> Vec<Row> {
+    records_summary(ntfs, fs)
+        .unwrap()
+        .map(|summary| {
+            let summary = summary.unwrap();
+            (
+                summary.file_record_number(),
+                summary
+                    .name()
+                    .map(|file_name| file_name.name().to_string_lossy()),
+                summary
+                    .parent_directory_reference()
+                    .map(|reference| reference.file_record_number()),
+                summary
+                    .info()
+                    .map(|info| info.modification_time().nt_timestamp()),
+                summary.data_size(),
+            )
+        })
+        .collect()
+}
+
+fn scan_with_accessors<T: Read + Seek>(
+    ntfs: &Ntfs,
+    fs: &mut T,
+    file_record_numbers: &[u64],
+) -> Vec<Row> {
+    file_record_numbers
+        .iter()
+        .map(|&file_record_number| {
+            let file = ntfs.file(fs, file_record_number).unwrap();
+
+            let file_name = [
+                NtfsFileNamespace::Win32,
+                NtfsFileNamespace::Win32AndDos,
+                NtfsFileNamespace::Posix,
+                NtfsFileNamespace::Dos,
+            ]
+            .into_iter()
+            .find_map(|namespace| file.name(fs, Some(namespace), None))
+            .map(|file_name| file_name.unwrap());
+            let info = file.info().ok();
+            let data_size = file
+                .data(fs, "")
+                .map(|data_item| data_item.unwrap().to_attribute().unwrap().value_length());
+
+            (
+                file_record_number,
+                file_name
+                    .as_ref()
+                    .map(|file_name| file_name.name().to_string_lossy()),
+                file_name
+                    .map(|file_name| file_name.parent_directory_reference().file_record_number()),
+                info.map(|info| info.modification_time().nt_timestamp()),
+                data_size,
+            )
+        })
+        .collect()
+}
+
+fn measure<F: FnMut() -> usize>(mut f: F) -> (Duration, usize) {
+    let start = Instant::now();
+    let mut records = 0;
+
+    for _ in 0..PASSES {
+        records += f();
+    }
+
+    (start.elapsed(), records)
+}
+
+fn main() {
+    let mut buffer = Vec::new();
+    File::open("testdata/testfs1")
+        .unwrap()
+        .read_to_end(&mut buffer)
+        .unwrap();
+    let mut fs = Cursor::new(buffer);
+    let ntfs = Ntfs::new(&mut fs).unwrap();
+
+    // Both approaches must yield identical values.
+    let summary_rows = scan_with_summaries(&ntfs, &mut fs);
+    let file_record_numbers = summary_rows.iter().map(|row| row.0).collect::<Vec<_>>();
+    let accessor_rows = scan_with_accessors(&ntfs, &mut fs, &file_record_numbers);
+    assert_eq!(summary_rows, accessor_rows);
+
+    let (summary_time, summary_records) = measure(|| scan_with_summaries(&ntfs, &mut fs).len());
+    let (accessor_time, accessor_records) =
+        measure(|| scan_with_accessors(&ntfs, &mut fs, &file_record_numbers).len());
+
+    let summary_throughput = summary_records as f64 / summary_time.as_secs_f64();
+    let accessor_throughput = accessor_records as f64 / accessor_time.as_secs_f64();
+
+    println!("records_summary: {summary_records} records in {summary_time:?} ({summary_throughput:.0} records/s)");
+    println!("convenience functions: {accessor_records} records in {accessor_time:?} ({accessor_throughput:.0} records/s)");
+    println!("speedup: {:.2}x", summary_throughput / accessor_throughput);
+}
@@ -0,0 +1,162 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Measures reading and seeking through a large sparse region of a non-resident value.
+// The sparse fixture is generated on the fly by replacing the Data Runs of "sparse-file" in the test
+// filesystem with a single sparse Data Run of `SPARSE_SIZE` bytes.
+//
+// Run via `cargo bench --bench sparse_read`.
+
+use std::fs::File;
+use std::io::{Cursor, Read, SeekFrom};
+use std::time::{Duration, Instant};
+
+use ntfs::attribute_value::NtfsReadSegment;
+use ntfs::indexes::NtfsFileNameIndex;
+use ntfs::{Ntfs, NtfsReadSeek};
+
+/// Size of the generated sparse region, in bytes.
+const SPARSE_SIZE: u64 = 4 << 30;
+
+/// Sizes of the buffers passed to every read call, in bytes.
+const READ_SIZES: [usize; 3] = [4096, 65536, 1 << 20];
+
+/// Sector size used for the Update Sequence Array of File Records.
+const SECTOR_SIZE: usize = 512;
+
+/// Applies `f` to the File Record at `position`, undoing and redoing the Update Sequence Array fixups around it.
+fn patch_file_record<F>(image: &mut [u8], position: usize, file_record_size: usize, f: F)
+where
+    F: FnOnce(&mut [u8]),
+{
+    let record = &mut image[position..position + file_record_size];
+    let usa_offset = u16::from_le_bytes([record[4], record[5]]) as usize;
+    let usa_count = u16::from_le_bytes([record[6], record[7]]) as usize;
+
+    for i in 1..usa_count {
+        let sector_end = i * SECTOR_SIZE - 2;
+        let usa_entry = usa_offset + i * 2;
+        record.copy_within(usa_entry..usa_entry + 2, sector_end);
+    }
+
+    f(record);
+
+    for i in 1..usa_count {
+        let sector_end = i * SECTOR_SIZE - 2;
+        let usa_entry = usa_offset + i * 2;
+        record.copy_within(sector_end..sector_end + 2, usa_entry);
+        record.copy_within(usa_offset..usa_offset + 2, sector_end);
+    }
+}
+
+/// Returns the test filesystem with "sparse-file" turned into a `SPARSE_SIZE` hole, along with its File Record Number.
+fn sparse_fixture() -> (Cursor<Vec<u8>>, u64) {
+    let mut buffer = Vec::new();
+    File::open("testdata/testfs1")
+        .unwrap()
+        .read_to_end(&mut buffer)
+        .unwrap();
+    let mut fs = Cursor::new(buffer);
+
+    let mut ntfs = Ntfs::new(&mut fs).unwrap();
+    ntfs.read_upcase_table(&mut fs).unwrap();
+    let root_dir = ntfs.root_directory(&mut fs).unwrap();
+    let root_dir_index = root_dir.directory_index(&mut fs).unwrap();
+    let mut root_dir_finder = root_dir_index.finder();
+    let entry = NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut fs, "sparse-file")
+        .unwrap()
+        .unwrap();
+    let file_record_number = entry.file_reference().file_record_number();
+    let file = entry.to_file(&ntfs, &mut fs).unwrap();
+    let data_item = file.data(&mut fs, "").unwrap().unwrap();
+    let data_attribute = data_item.to_attribute().unwrap();
+
+    let file_position = file.position().value().unwrap().get() as usize;
+    let attribute_offset =
+        data_attribute.position().value().unwrap().get() as usize - file_position;
+    let file_record_size = ntfs.file_record_size() as usize;
+    let cluster_count = SPARSE_SIZE / ntfs.cluster_size() as u64;
+
+    patch_file_record(fs.get_mut(), file_position, file_record_size, |record| {
+        let attribute = &mut record[attribute_offset..];
+
+        // Highest VCN, allocated size, data size, and initialized size.
+        attribute[0x18..0x20].copy_from_slice(&(cluster_count - 1).to_le_bytes());
+        attribute[0x28..0x30].copy_from_slice(&SPARSE_SIZE.to_le_bytes());
+        attribute[0x30..0x38].copy_from_slice(&SPARSE_SIZE.to_le_bytes());
+        attribute[0x38..0x40].copy_from_slice(&SPARSE_SIZE.to_le_bytes());
+
+        // A single sparse Data Run (no offset, 4-byte cluster count), followed by the terminator.
+        let data_runs_offset = u16::from_le_bytes([attribute[0x20], attribute[0x21]]) as usize;
+        let data_runs = &mut attribute[data_runs_offset..];
+        data_runs[0] = 0x04;
+        data_runs[1..5].copy_from_slice(&(cluster_count as u32).to_le_bytes());
+        data_runs[5] = 0;
+    });
+
+    (fs, file_record_number)
+}
+
+fn measure<F: FnMut() -> u64>(mut f: F) -> (Duration, u64) {
+    let start = Instant::now();
+    let bytes = f();
+    (start.elapsed(), bytes)
+}
+
+fn print_throughput(name: &str, (time, bytes): (Duration, u64)) {
+    let throughput = bytes as f64 / time.as_secs_f64() / (1 << 30) as f64;
+    println!("{name}: {bytes} bytes in {time:?} ({throughput:.2} GiB/s)");
+}
+
+fn main() {
+    let (mut fs, file_record_number) = sparse_fixture();
+    let ntfs = Ntfs::new(&mut fs).unwrap();
+    let file = ntfs.file(&mut fs, file_record_number).unwrap();
+    let data_item = file.data(&mut fs, "").unwrap().unwrap();
+    let data_attribute = data_item.to_attribute().unwrap();
+    assert_eq!(data_attribute.value_length(), SPARSE_SIZE);
+
+    for read_size in READ_SIZES {
+        let mut value = data_attribute.value(&mut fs).unwrap();
+        let mut buf = vec![0u8; read_size];
+
+        let result = measure(|| {
+            let mut total = 0;
+            loop {
+                let bytes_read = value.read(&mut fs, &mut buf).unwrap();
+                if bytes_read == 0 {
+                    break total;
+                }
+
+                total += bytes_read as u64;
+            }
+        });
+        assert_eq!(result.1, SPARSE_SIZE);
+        print_throughput(&format!("read in {read_size} byte chunks"), result);
+    }
+
+    let mut value = data_attribute.value(&mut fs).unwrap();
+    let result = measure(|| {
+        let mut total = 0;
+        while total < SPARSE_SIZE {
+            total = value.seek(&mut fs, SeekFrom::Current(4096)).unwrap();
+        }
+        total
+    });
+    print_throughput("seek in 4096 byte steps", result);
+
+    let mut value = data_attribute.value(&mut fs).unwrap();
+    let mut buf = vec![0u8; 4096];
+    let result = measure(|| {
+        let mut total = 0;
+        loop {
+            match value.read_segment(&mut fs, &mut buf).unwrap() {
+                NtfsReadSegment::Data(0) => break total,
+                NtfsReadSegment::Data(n) => total += n as u64,
+                NtfsReadSegment::Hole(n) => total += n,
+            }
+        }
+    });
+    assert_eq!(result.1, SPARSE_SIZE);
+    print_throughput("read_segment", result);
+}
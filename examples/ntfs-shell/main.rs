@@ -1,26 +1,25 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
-
-mod sector_reader;
-
 use std::env;
+use std::fs;
 use std::fs::{File, OpenOptions};
 use std::io;
-use std::io::{BufReader, Read, Seek, Write};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
 
 use anyhow::{anyhow, bail, Context, Result};
-use ntfs::attribute_value::NtfsAttributeValue;
-use ntfs::indexes::NtfsFileNameIndex;
+use ntfs::attribute_value::{NtfsAttributeValue, NtfsReadSegment};
+use ntfs::indexes::{NtfsFileNameFilter, NtfsFileNameIndex};
+use ntfs::io_ext::SectorReader;
 use ntfs::structured_values::{
     NtfsAttributeList, NtfsFileName, NtfsFileNamespace, NtfsStandardInformation,
 };
-use ntfs::{Ntfs, NtfsAttribute, NtfsAttributeType, NtfsFile, NtfsReadSeek};
+use ntfs::walk::{NtfsTreeEntry, NtfsTreeWalker};
+use ntfs::{Ntfs, NtfsAttribute, NtfsAttributeType, NtfsFile, NtfsStringExt};
 use time::format_description::FormatItem;
 use time::macros::format_description;
 use time::OffsetDateTime;
 
-use sector_reader::SectorReader;
-
 struct CommandInfo<'n, T>
 where
     T: Read + Seek,
@@ -84,9 +83,11 @@ fn main() -> Result<()> {
             "attr" => attr(false, arg, &mut info),
             "attr_runs" => attr(true, arg, &mut info),
             "cd" => cd(arg, &mut info),
+            "copyall" => copyall(arg, &mut info),
             "dir" => dir(&mut info),
             "exit" | "quit" => break,
             "fileinfo" => fileinfo(arg, &mut info),
+            "find" => find(arg, &mut info),
             "fsinfo" => fsinfo(&mut info),
             "get" => get(arg, &mut info),
             "help" => help(arg),
@@ -179,7 +180,7 @@ where
     let resident = attribute.is_resident();
     let start = attribute.position();
     let length = attribute.value_length();
-    let name = attribute.name()?.to_string_lossy();
+    let name = attribute.name()?.display_sanitized().to_string();
 
     println!(
         "{:<10} | {:<20} | {:<8} | {:>#13x} | {:>#18x} | {:>13} | \"{}\"",
@@ -289,7 +290,7 @@ where
         if !info.current_directory_string.is_empty() {
             info.current_directory_string += "\\";
         }
-        info.current_directory_string += &file_name.name().to_string_lossy();
+        info.current_directory_string += &file_name.name().display_sanitized().to_string();
 
         info.current_directory.push(file);
     }
@@ -297,6 +298,149 @@ where
     Ok(())
 }
 
+fn copyall<T>(arg: &str, info: &mut CommandInfo<T>) -> Result<()>
+where
+    T: Read + Seek,
+{
+    // The target directory is the last argument, so that the source directory may contain spaces.
+    let (dir_arg, target) = arg
+        .rsplit_once(' ')
+        .ok_or_else(|| anyhow!("Missing target directory argument!"))?;
+    let dir_arg = dir_arg.trim_end();
+
+    let dir = parse_file_arg(dir_arg, info)?;
+    if !dir.is_directory() {
+        bail!("\"{}\" is not a directory.", dir_arg);
+    }
+
+    let target = Path::new(target);
+    fs::create_dir_all(target)
+        .with_context(|| format!("Tried to create \"{}\"", target.display()))?;
+
+    let mut walker = NtfsTreeWalker::new(dir);
+    let mut components = Vec::<String>::new();
+    let mut copied_files = 0;
+    let mut created_directories = 0;
+    let mut errors = 0;
+
+    // Report and skip errors of individual entries instead of aborting the entire run.
+    while let Some(entry) = walker.next(&mut info.fs) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                errors += 1;
+                continue;
+            }
+        };
+
+        // Build the output path from the file names of the entry and its parents instead of splitting
+        // the backslash-separated path, because a POSIX file name may contain a backslash itself.
+        // The walk is depth-first, so the parents are the last `depth - 1` directories we have seen.
+        components.truncate(entry.depth() - 1);
+        components.push(entry.file_name().name().to_string_lossy());
+
+        let output_path = match copyall_output_path(target, &components) {
+            Ok(output_path) => output_path,
+            Err(e) => {
+                eprintln!("Error copying \"{}\": {e}", entry.path());
+                errors += 1;
+                continue;
+            }
+        };
+
+        let result = if entry.is_directory() {
+            fs::create_dir_all(&output_path)
+                .with_context(|| format!("Tried to create \"{}\"", output_path.display()))
+                .map(|()| created_directories += 1)
+        } else {
+            copyall_file(info, &entry, &output_path).map(|()| copied_files += 1)
+        };
+
+        if let Err(e) = result {
+            eprintln!("Error copying \"{}\": {e:?}", entry.path());
+            errors += 1;
+        }
+    }
+
+    println!(
+        "Copied {copied_files} files and created {created_directories} directories, {errors} errors."
+    );
+
+    Ok(())
+}
+
+fn copyall_file<T>(
+    info: &mut CommandInfo<T>,
+    entry: &NtfsTreeEntry,
+    output_path: &Path,
+) -> Result<()>
+where
+    T: Read + Seek,
+{
+    let file = entry.to_file(info.ntfs, &mut info.fs)?;
+
+    // Save the unnamed data stream under the file name, and each alternate data stream as "name:stream".
+    for stream in file.data_stream_sizes(&mut info.fs)? {
+        let data_stream_name = stream.name();
+        let mut output_file_name = output_path.as_os_str().to_os_string();
+        if !data_stream_name.is_empty() {
+            output_file_name.push(":");
+            output_file_name.push(data_stream_name);
+        }
+
+        let mut output_file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&output_file_name)
+            .with_context(|| format!("Tried to open {output_file_name:?} for writing"))?;
+
+        let data_item = file
+            .data(&mut info.fs, data_stream_name)
+            .ok_or_else(|| anyhow!("Found no \"{}\" $DATA attribute", data_stream_name))??;
+        let data_attribute = data_item.to_attribute()?;
+        let mut data_value = data_attribute.value(&mut info.fs)?;
+        save_data(info, &mut data_value, &mut output_file)?;
+    }
+
+    Ok(())
+}
+
+/// Joins the file names in `components` to a path below `target`.
+///
+/// File names come from the (untrusted) filesystem, so every name that is not a plain single path component
+/// on the local system is rejected, as it could otherwise escape `target`.
+fn copyall_output_path(target: &Path, components: &[String]) -> Result<PathBuf> {
+    let mut output_path = target.to_path_buf();
+
+    for component in components {
+        let mut path_components = Path::new(component).components();
+        let is_plain = matches!(
+            (path_components.next(), path_components.next()),
+            (Some(Component::Normal(_)), None)
+        );
+
+        if !is_plain
+            || component == "."
+            || component == ".."
+            || component.contains(|c| matches!(c, '/' | '\\' | ':' | '\0'))
+        {
+            bail!("Refusing to copy a file named \"{}\"", component);
+        }
+
+        output_path.push(component);
+    }
+
+    if !output_path.starts_with(target) {
+        bail!(
+            "\"{}\" is outside the target directory",
+            output_path.display()
+        );
+    }
+
+    Ok(output_path)
+}
+
 fn dir<T>(info: &mut CommandInfo<T>) -> Result<()>
 where
     T: Read + Seek,
@@ -306,7 +450,8 @@ where
         .last()
         .unwrap()
         .directory_index(&mut info.fs)?;
-    let mut iter = index.entries();
+    // Like Windows, list each file only under its long name and not additionally under its DOS name.
+    let mut iter = index.entries_deduplicated(NtfsFileNameFilter::SkipDosNames);
 
     while let Some(entry) = iter.next(&mut info.fs) {
         let entry = entry?;
@@ -319,7 +464,7 @@ where
         } else {
             ""
         };
-        println!("{:5}  {}", prefix, file_name.name());
+        println!("{:5}  {}", prefix, file_name.name().display_sanitized());
     }
 
     Ok(())
@@ -367,16 +512,16 @@ fn fileinfo_std(attribute: NtfsAttribute) -> Result<()> {
 
     println!("{:34}{}", "Attributes:", std_info.file_attributes());
 
-    let atime = OffsetDateTime::from(std_info.access_time())
+    let atime = OffsetDateTime::try_from(std_info.access_time())?
         .format(TIME_FORMAT)
         .unwrap();
-    let ctime = OffsetDateTime::from(std_info.creation_time())
+    let ctime = OffsetDateTime::try_from(std_info.creation_time())?
         .format(TIME_FORMAT)
         .unwrap();
-    let mtime = OffsetDateTime::from(std_info.modification_time())
+    let mtime = OffsetDateTime::try_from(std_info.modification_time())?
         .format(TIME_FORMAT)
         .unwrap();
-    let mmtime = OffsetDateTime::from(std_info.mft_record_modification_time())
+    let mmtime = OffsetDateTime::try_from(std_info.mft_record_modification_time())?
         .format(TIME_FORMAT)
         .unwrap();
     println!("{:34}{}", "Access Time:", atime);
@@ -433,7 +578,7 @@ where
 
     let file_name = attribute.structured_value::<_, NtfsFileName>(&mut info.fs)?;
 
-    println!("{:34}\"{}\"", "Name:", file_name.name().to_string_lossy());
+    println!("{:34}\"{}\"", "Name:", file_name.name().display_sanitized());
     println!("{:34}{:?}", "Namespace:", file_name.namespace());
     println!(
         "{:34}{:#x}",
@@ -448,12 +593,64 @@ fn fileinfo_data(attribute: NtfsAttribute) -> Result<()> {
     println!();
     println!("{:=^72}", " DATA STREAM ");
 
-    println!("{:34}\"{}\"", "Name:", attribute.name()?.to_string_lossy());
+    println!(
+        "{:34}\"{}\"",
+        "Name:",
+        attribute.name()?.display_sanitized()
+    );
     println!("{:34}{}", "Size:", attribute.value_length());
 
     Ok(())
 }
 
+fn find<T>(arg: &str, info: &mut CommandInfo<T>) -> Result<()>
+where
+    T: Read + Seek,
+{
+    if arg.is_empty() {
+        bail!("Missing argument!");
+    }
+
+    // Like Windows, match file names case-insensitively.
+    let pattern = arg.to_uppercase().chars().collect::<Vec<char>>();
+    let dir = info.current_directory.last().unwrap().clone();
+    let mut walker = NtfsTreeWalker::new(dir);
+
+    while let Some(entry) = walker.next(&mut info.fs) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                continue;
+            }
+        };
+
+        let name = entry.file_name().name().to_string_lossy().to_uppercase();
+        if find_matches(&pattern, &name.chars().collect::<Vec<char>>()) {
+            if info.current_directory_string.is_empty() {
+                println!("\\{}", entry.path());
+            } else {
+                println!("\\{}\\{}", info.current_directory_string, entry.path());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Matches `name` against `pattern`, which may contain the wildcards `*` (any sequence of characters)
+/// and `?` (any single character).
+fn find_matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| find_matches(rest, &name[skip..])),
+        Some((&c, rest)) => match name.split_first() {
+            Some((&n, name_rest)) if c == '?' || c == n => find_matches(rest, name_rest),
+            _ => false,
+        },
+    }
+}
+
 fn fsinfo<T>(info: &mut CommandInfo<T>) -> Result<()>
 where
     T: Read + Seek,
@@ -475,7 +672,7 @@ where
     println!("{:20}{}", "Size:", info.ntfs.size());
 
     let volume_name = if let Some(Ok(volume_name)) = info.ntfs.volume_name(&mut info.fs) {
-        format!("\"{}\"", volume_name.name())
+        format!("\"{}\"", volume_name.name().display_sanitized())
     } else {
         "<NONE>".to_string()
     };
@@ -525,18 +722,7 @@ where
         data_value.len(),
         output_file_name
     );
-    let mut buf = [0u8; 4096];
-
-    loop {
-        let bytes_read = data_value.read(&mut info.fs, &mut buf)?;
-        if bytes_read == 0 {
-            break;
-        }
-
-        output_file.write_all(&buf[..bytes_read])?;
-    }
-
-    Ok(())
+    save_data(info, &mut data_value, &mut output_file)
 }
 
 fn help(arg: &str) -> Result<()> {
@@ -562,10 +748,24 @@ fn help(arg: &str) -> Result<()> {
             println!("This implementation of \"cd\" only supports subdirectories of the current directory.");
             println!("\"cd ..\" moves back into the parent directory.");
         }
+        "copyall" => {
+            println!("Usage: copyall DIRECTORY TARGET");
+            println!();
+            println!("Recursively copies all files and subdirectories of DIRECTORY to the directory TARGET of your local filesystem.");
+            println!("Alternate data streams are saved as separate files named \"FILE:STREAM\".");
+            println!("Files that cannot be copied are reported and skipped.");
+            println!();
+            println!("TARGET must not contain spaces. It is created if it doesn't exist yet.");
+            println!("Existing files in TARGET are not overwritten.");
+            println!();
+            println!("DIRECTORY can be a subdirectory of the current directory or a File Record Number (like \"/5\"),");
+            println!("see \"help get\" for details.");
+        }
         "dir" => {
             println!("Usage: dir");
             println!();
             println!("Lists filenames in the current directory (like \"ls\" on UNIX systems).");
+            println!("Files with a separate DOS name (like \"PROGRA~1\") are only listed under their long name.");
             println!("No additional parameters are supported.");
             println!("Try \"fileinfo\" to get additional information about a single file.");
         }
@@ -575,6 +775,17 @@ fn help(arg: &str) -> Result<()> {
             println!("Shows information about a single file (by parsing its NTFS attributes).");
             help_file("fileinfo");
         }
+        "find" => {
+            println!("Usage: find PATTERN");
+            println!();
+            println!("Recursively searches the current directory and its subdirectories for files matching PATTERN");
+            println!("and prints their paths.");
+            println!("PATTERN is matched case-insensitively against the file names and may contain the wildcards");
+            println!("\"*\" (any sequence of characters) and \"?\" (any single character).");
+            println!();
+            println!("Example:");
+            println!("  find *.exe");
+        }
         "get" => {
             println!("Usage:");
             println!("  get FILE");
@@ -591,9 +802,11 @@ fn help(arg: &str) -> Result<()> {
             println!("  attr      - Show structure of NTFS attributes of a particular file");
             println!("  attr_runs - Show structure of NTFS attributes of a particular file, including data runs");
             println!("  cd        - Change the current directory");
+            println!("  copyall   - Recursively copy a directory from the NTFS filesystem");
             println!("  dir       - Show files of the current directory");
             println!("  exit      - Quit ntfs-shell");
             println!("  fileinfo  - Show information about a particular file");
+            println!("  find      - Find files by name in the current directory tree");
             println!("  fsinfo    - Show general filesystem information");
             println!("  get       - Copy a file from the NTFS filesystem");
             println!("  help      - Show this help");
@@ -664,3 +877,33 @@ where
         }
     }
 }
+
+fn save_data<T>(
+    info: &mut CommandInfo<T>,
+    data_value: &mut NtfsAttributeValue,
+    output_file: &mut File,
+) -> Result<()>
+where
+    T: Read + Seek,
+{
+    // Read whole clusters, but at least 4 KiB at once.
+    let buf_size = data_value.recommended_read_size(info.ntfs).max(4096);
+    let mut buf = vec![0u8; buf_size];
+
+    // Skip sparse holes instead of writing zeros, so that the output file stays sparse
+    // on filesystems supporting that.
+    loop {
+        match data_value.read_segment(&mut info.fs, &mut buf)? {
+            NtfsReadSegment::Data(0) => break,
+            NtfsReadSegment::Data(bytes_read) => output_file.write_all(&buf[..bytes_read])?,
+            NtfsReadSegment::Hole(length) => {
+                output_file.seek(SeekFrom::Current(length as i64))?;
+            }
+        }
+    }
+
+    // Set the final length in case the data ends with a hole.
+    output_file.set_len(data_value.len())?;
+
+    Ok(())
+}
@@ -0,0 +1,14 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(entry) = ntfs::fuzz::attribute_list_entry(data) {
+        let _ = entry.name();
+        let _ = entry.base_file_reference();
+        let _ = entry.lowest_vcn();
+    }
+});
@@ -0,0 +1,21 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ntfs::indexes::NtfsFileNameIndex;
+
+fuzz_target!(|data: &[u8]| {
+    for entry in ntfs::fuzz::index_node_entries::<NtfsFileNameIndex>(data) {
+        let Ok(entry) = entry else {
+            break;
+        };
+
+        if let Some(Ok(key)) = entry.key() {
+            let _ = key.name().to_string_lossy();
+        }
+
+        let _ = entry.subnode_vcn();
+    }
+});
@@ -0,0 +1,97 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+//! Uses the `ntfs` crate like a `no_std` consumer with an allocator (e.g. a UEFI application) would.
+//!
+//! This crate depends on `ntfs` without default features and is built for `x86_64-unknown-uefi` in CI.
+//! Its tests mount an in-memory filesystem image, list the root directory, and read a file.
+//! A global allocator is provided by the final binary (or by `std` when running the tests).
+
+#![cfg_attr(not(test), no_std)]
+#![forbid(unsafe_code)]
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use ntfs::indexes::NtfsFileNameIndex;
+use ntfs::io::Cursor;
+use ntfs::{Ntfs, NtfsReadSeek, Result};
+
+/// Returns the names of all files and directories in the root directory of the given filesystem image.
+pub fn root_directory_names(image: &[u8]) -> Result<Vec<String>> {
+    let mut fs = Cursor::new(image);
+    let ntfs = Ntfs::new(&mut fs)?;
+    let root_directory = ntfs.root_directory(&mut fs)?;
+    let index = root_directory.directory_index(&mut fs)?;
+    let mut entries = index.entries();
+    let mut names = Vec::new();
+
+    while let Some(entry) = entries.next(&mut fs) {
+        let entry = entry?;
+        let file_name = entry
+            .key()
+            .expect("every entry of a filename index has a key")?;
+        names.push(file_name.name().to_string_lossy());
+    }
+
+    Ok(names)
+}
+
+/// Reads the unnamed $DATA stream of a file in the root directory of the given filesystem image.
+///
+/// Returns `None` if the file does not exist or has no unnamed $DATA stream.
+pub fn read_root_file(image: &[u8], name: &str) -> Result<Option<Vec<u8>>> {
+    let mut fs = Cursor::new(image);
+    let mut ntfs = Ntfs::new(&mut fs)?;
+    ntfs.read_upcase_table(&mut fs)?;
+
+    let root_directory = ntfs.root_directory(&mut fs)?;
+    let index = root_directory.directory_index(&mut fs)?;
+    let mut finder = index.finder();
+    let entry = match NtfsFileNameIndex::find(&mut finder, &ntfs, &mut fs, name) {
+        Some(entry) => entry?,
+        None => return Ok(None),
+    };
+
+    let file = entry.to_file(&ntfs, &mut fs)?;
+    let data_item = match file.data(&mut fs, "") {
+        Some(data_item) => data_item?,
+        None => return Ok(None),
+    };
+    let data_attribute = data_item.to_attribute()?;
+    let mut data_value = data_attribute.value(&mut fs)?;
+
+    let mut data = Vec::new();
+    data_value.read_to_end(&mut fs, &mut data, data_value.len() as usize)?;
+
+    Ok(Some(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TESTFS1: &[u8] = include_bytes!("../../testdata/testfs1");
+
+    #[test]
+    fn test_root_directory_names() {
+        let names = root_directory_names(TESTFS1).unwrap();
+        assert!(names.iter().any(|name| name == "$MFT"));
+        assert!(names.iter().any(|name| name == "1000-bytes-file"));
+        assert!(names.iter().any(|name| name == "many_subdirs"));
+    }
+
+    #[test]
+    fn test_read_root_file() {
+        let data = read_root_file(TESTFS1, "file-with-12345").unwrap().unwrap();
+        assert_eq!(data, b"12345");
+
+        let data = read_root_file(TESTFS1, "1000-bytes-file").unwrap().unwrap();
+        assert_eq!(data.len(), 1000);
+        assert!(data.chunks(5).all(|chunk| chunk == b"12345"));
+
+        assert!(read_root_file(TESTFS1, "does-not-exist").unwrap().is_none());
+    }
+}
@@ -0,0 +1,330 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+//! Support for readers with asynchronous I/O (`async` feature).
+//!
+//! All parsing code of this crate works on a synchronous reader.
+//! The async entry points run that code on an [`AsyncBlockCache`], which serves reads from blocks that have
+//! already been fetched from the asynchronous reader.
+//! If a read hits a block that hasn't been fetched yet, the synchronous code fails, the missing blocks are
+//! fetched asynchronously, and the synchronous code is run again.
+//! As all reading functions of this crate are deterministic, every run gets further than the previous one,
+//! and the synchronous code path doesn't need to know anything about async.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use futures_io::{AsyncRead, AsyncSeek};
+
+use crate::error::Result;
+use crate::io::{Error, ErrorKind, Read, Seek, SeekFrom};
+
+/// Size of the blocks fetched from the asynchronous reader, in bytes.
+const BLOCK_SIZE: u64 = 4096;
+
+/// Trait for asynchronous readers of an NTFS filesystem, as accepted by the `*_async` functions of this crate.
+///
+/// It is implemented for every type implementing [`AsyncRead`], [`AsyncSeek`], and [`Unpin`] of the
+/// `futures-io` crate.
+/// Readers of other async runtimes can be adapted, e.g. via `tokio_util::compat` for Tokio.
+pub trait AsyncNtfsReadSeek: AsyncRead + AsyncSeek + Unpin {}
+
+impl<T> AsyncNtfsReadSeek for T where T: AsyncRead + AsyncSeek + Unpin {}
+
+/// Cache of blocks fetched from an asynchronous reader, which runs synchronous reading code on them.
+pub(crate) struct AsyncBlockCache<'a, T>
+where
+    T: AsyncNtfsReadSeek,
+{
+    fs: &'a mut T,
+    blocks: BTreeMap<u64, Vec<u8>>,
+    len: u64,
+}
+
+impl<'a, T> AsyncBlockCache<'a, T>
+where
+    T: AsyncNtfsReadSeek,
+{
+    pub(crate) async fn new(fs: &'a mut T) -> Result<Self> {
+        let len = poll_fn(|cx| Pin::new(&mut *fs).poll_seek(cx, SeekFrom::End(0))).await?;
+        let blocks = BTreeMap::new();
+
+        Ok(Self { fs, blocks, len })
+    }
+
+    /// Fetches the given blocks from the asynchronous reader, reading adjacent blocks at once.
+    async fn fetch(&mut self, missing_blocks: BTreeSet<u64>) -> Result<()> {
+        let mut missing_blocks = missing_blocks.into_iter().peekable();
+
+        while let Some(first_block) = missing_blocks.next() {
+            let mut block_count = 1;
+            while missing_blocks.peek() == Some(&(first_block + block_count)) {
+                missing_blocks.next();
+                block_count += 1;
+            }
+
+            let start = first_block * BLOCK_SIZE;
+            let end = u64::min((first_block + block_count) * BLOCK_SIZE, self.len);
+            let mut buf = vec![0u8; end.saturating_sub(start) as usize];
+
+            let fs = &mut *self.fs;
+            poll_fn(|cx| Pin::new(&mut *fs).poll_seek(cx, SeekFrom::Start(start))).await?;
+
+            let mut bytes_read = 0;
+            while bytes_read < buf.len() {
+                let n =
+                    poll_fn(|cx| Pin::new(&mut *fs).poll_read(cx, &mut buf[bytes_read..])).await?;
+                if n == 0 {
+                    break;
+                }
+
+                bytes_read += n;
+            }
+            buf.truncate(bytes_read);
+
+            // A block is stored even if the reader returned less data than expected.
+            // The synchronous code then sees the end of the data instead of asking for the block again.
+            for (i, chunk) in buf.chunks(BLOCK_SIZE as usize).enumerate() {
+                self.blocks.insert(first_block + i as u64, chunk.to_vec());
+            }
+            for block in first_block + buf.len() as u64 / BLOCK_SIZE..first_block + block_count {
+                self.blocks.entry(block).or_default();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns a synchronous reader over the blocks fetched so far.
+    pub(crate) fn reader(&self) -> CachedBlockReader<'_> {
+        CachedBlockReader {
+            blocks: &self.blocks,
+            len: self.len,
+            position: 0,
+            missing_blocks: BTreeSet::new(),
+        }
+    }
+
+    /// Runs `f` on a synchronous reader over the fetched blocks until it doesn't need any further blocks,
+    /// and returns its result.
+    ///
+    /// `f` may be run multiple times, so it must not modify any state on failure.
+    pub(crate) async fn run<R, F>(&mut self, mut f: F) -> Result<R>
+    where
+        F: FnMut(&mut CachedBlockReader<'_>) -> Result<R>,
+    {
+        loop {
+            let mut reader = self.reader();
+            let result = f(&mut reader);
+
+            // Any result computed while blocks were missing is discarded, even if the error has been swallowed.
+            if reader.missing_blocks.is_empty() {
+                return result;
+            }
+
+            let missing_blocks = reader.missing_blocks;
+            self.fetch(missing_blocks).await?;
+        }
+    }
+}
+
+/// Synchronous reader over the blocks of an [`AsyncBlockCache`].
+///
+/// Reading a block that hasn't been fetched yet fails with [`ErrorKind::WouldBlock`] and records the block
+/// for fetching.
+pub(crate) struct CachedBlockReader<'c> {
+    blocks: &'c BTreeMap<u64, Vec<u8>>,
+    len: u64,
+    position: u64,
+    missing_blocks: BTreeSet<u64>,
+}
+
+impl<'c> Read for CachedBlockReader<'c> {
+    fn read(&mut self, buf: &mut [u8]) -> crate::io::Result<usize> {
+        if buf.is_empty() || self.position >= self.len {
+            return Ok(0);
+        }
+
+        // Record all missing blocks covered by this read, so that they are fetched at once.
+        let first_block = self.position / BLOCK_SIZE;
+        let end = u64::min(self.position.saturating_add(buf.len() as u64), self.len);
+        let last_block = (end - 1) / BLOCK_SIZE;
+        let missing_blocks = (first_block..=last_block)
+            .filter(|block| !self.blocks.contains_key(block))
+            .collect::<Vec<u64>>();
+
+        if !missing_blocks.is_empty() {
+            self.missing_blocks.extend(missing_blocks);
+            return Err(Error::new(
+                ErrorKind::WouldBlock,
+                "the data has not been fetched from the asynchronous reader yet",
+            ));
+        }
+
+        let block = &self.blocks[&first_block];
+        let offset_in_block = (self.position % BLOCK_SIZE) as usize;
+        let available = block.get(offset_in_block..).unwrap_or_default();
+        let bytes_to_read = usize::min(buf.len(), available.len());
+
+        buf[..bytes_to_read].copy_from_slice(&available[..bytes_to_read]);
+        self.position += bytes_to_read as u64;
+        Ok(bytes_to_read)
+    }
+}
+
+impl<'c> Seek for CachedBlockReader<'c> {
+    fn seek(&mut self, pos: SeekFrom) -> crate::io::Result<u64> {
+        let new_position = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => (self.len as i64).checked_add(n).map(|n| n as u64),
+            SeekFrom::Current(n) => (self.position as i64).checked_add(n).map(|n| n as u64),
+        };
+
+        self.position = new_position
+            .filter(|n| (*n as i64) >= 0)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid seek position"))?;
+        Ok(self.position)
+    }
+}
+
+/// Future that calls a closure on every poll, as returned by [`poll_fn`].
+struct PollFn<F> {
+    f: F,
+}
+
+// The closure is never pinned, so `PollFn` can be moved even after it has been polled.
+impl<F> Unpin for PollFn<F> {}
+
+impl<F, R> Future for PollFn<F>
+where
+    F: FnMut(&mut Context<'_>) -> Poll<R>,
+{
+    type Output = R;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<R> {
+        (self.f)(cx)
+    }
+}
+
+/// Returns a future that wraps the given polling closure.
+///
+/// This is a replacement for `core::future::poll_fn`, which requires a newer Rust version than our MSRV.
+fn poll_fn<F, R>(f: F) -> PollFn<F>
+where
+    F: FnMut(&mut Context<'_>) -> Poll<R>,
+{
+    PollFn { f }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::{Context, Poll};
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::indexes::NtfsFileNameIndex;
+    use crate::ntfs::Ntfs;
+
+    /// Asynchronous in-memory reader.
+    struct AsyncCursor(Cursor<Vec<u8>>);
+
+    impl AsyncRead for AsyncCursor {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<std::io::Result<usize>> {
+            Poll::Ready(std::io::Read::read(&mut self.0, buf))
+        }
+    }
+
+    impl AsyncSeek for AsyncCursor {
+        fn poll_seek(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            pos: SeekFrom,
+        ) -> Poll<std::io::Result<u64>> {
+            Poll::Ready(std::io::Seek::seek(&mut self.0, pos))
+        }
+    }
+
+    fn async_testfs1() -> AsyncCursor {
+        AsyncCursor(crate::helpers::tests::testfs1())
+    }
+
+    #[tokio::test]
+    async fn test_block_cache() {
+        let mut fs = async_testfs1();
+        let mut cache = AsyncBlockCache::new(&mut fs).await.unwrap();
+
+        // Reading across a block boundary fetches both blocks at once and then succeeds.
+        let mut runs = 0;
+        let buf = cache
+            .run(|reader| {
+                runs += 1;
+                let mut buf = [0u8; 16];
+                reader.seek(SeekFrom::Start(BLOCK_SIZE - 8))?;
+                reader.read_exact(&mut buf)?;
+                Ok(buf)
+            })
+            .await
+            .unwrap();
+        assert_eq!(runs, 2);
+
+        let testfs1 = crate::helpers::tests::testfs1().into_inner();
+        let start = BLOCK_SIZE as usize - 8;
+        assert_eq!(buf, testfs1[start..start + 16]);
+
+        // Reading at the end of the data returns nothing instead of asking for more blocks.
+        let len = testfs1.len() as u64;
+        let n = cache
+            .run(|reader| {
+                reader.seek(SeekFrom::Start(len))?;
+                Ok(reader.read(&mut [0u8; 16])?)
+            })
+            .await
+            .unwrap();
+        assert_eq!(n, 0);
+    }
+
+    #[tokio::test]
+    async fn test_async_entry_points() {
+        let mut fs = async_testfs1();
+
+        let mut ntfs = Ntfs::new_async(&mut fs).await.unwrap();
+        ntfs.read_upcase_table_async(&mut fs).await.unwrap();
+        let root_dir = ntfs
+            .file_async(
+                &mut fs,
+                crate::KnownNtfsFileRecordNumber::RootDirectory as u64,
+            )
+            .await
+            .unwrap();
+
+        // Look up "file-with-12345" in the root directory.
+        let root_dir_index = root_dir.directory_index_async(&mut fs).await.unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find_async(&mut root_dir_finder, &ntfs, &mut fs, "file-with-12345")
+                .await
+                .unwrap()
+                .unwrap();
+        let file_record_number = entry.file_reference().file_record_number();
+        assert_eq!(file_record_number, 65);
+
+        // Read its data.
+        let file = ntfs.file_async(&mut fs, file_record_number).await.unwrap();
+        let data_item = file.data_async(&mut fs, "").await.unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value_async(&mut fs).await.unwrap();
+
+        let mut buf = [0u8; 5];
+        assert_eq!(data_value.read_async(&mut fs, &mut buf).await.unwrap(), 5);
+        assert_eq!(&buf, b"12345");
+        assert_eq!(data_value.read_async(&mut fs, &mut buf).await.unwrap(), 0);
+    }
+}
@@ -5,7 +5,9 @@ use core::iter::FusedIterator;
 use core::ops::Range;
 use core::{fmt, mem};
 
-use binrw::io::{Read, Seek};
+use crate::io::{Read, Seek};
+use alloc::string::String;
+use alloc::vec::Vec;
 use bitflags::bitflags;
 use byteorder::{ByteOrder, LittleEndian};
 use enumn::N;
@@ -13,21 +15,32 @@ use memoffset::offset_of;
 use nt_string::u16strle::U16StrLe;
 use strum_macros::Display;
 
+#[cfg(feature = "async")]
+use crate::async_io::{AsyncBlockCache, AsyncNtfsReadSeek};
+use crate::attribute_definition::{NtfsAttributeDefinition, NtfsAttributeDefinitionFlags};
 use crate::attribute_value::{
-    NtfsAttributeListNonResidentAttributeValue, NtfsAttributeValue, NtfsNonResidentAttributeValue,
-    NtfsResidentAttributeValue,
+    NtfsAttributeListNonResidentAttributeValue, NtfsAttributeValue, NtfsDataRun,
+    NtfsNonResidentAttributeValue, NtfsResidentAttributeValue,
 };
 use crate::error::{NtfsError, Result};
 use crate::file::NtfsFile;
+use crate::ntfs::Ntfs;
 use crate::structured_values::{
-    NtfsAttributeList, NtfsAttributeListEntries, NtfsStructuredValue,
+    NtfsAttributeList, NtfsAttributeListEntries, NtfsAttributeListEntry, NtfsStructuredValue,
     NtfsStructuredValueFromResidentAttributeValue,
 };
+use crate::traits::NtfsReadSeek;
 use crate::types::{NtfsPosition, Vcn};
 
 /// Size of all [`NtfsAttributeHeader`] fields.
 const ATTRIBUTE_HEADER_SIZE: usize = 16;
 
+/// Size of all [`NtfsResidentAttributeHeader`] fields, plus a reserved byte.
+const RESIDENT_ATTRIBUTE_HEADER_SIZE: usize = 24;
+
+/// Size of all [`NtfsNonResidentAttributeHeader`] fields.
+const NON_RESIDENT_ATTRIBUTE_HEADER_SIZE: usize = 64;
+
 /// On-disk structure of the generic header of an NTFS Attribute.
 #[repr(C, packed)]
 struct NtfsAttributeHeader {
@@ -50,6 +63,7 @@ struct NtfsAttributeHeader {
 bitflags! {
     /// Flags returned by [`NtfsAttribute::flags`].
     #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
     pub struct NtfsAttributeFlags: u16 {
         /// The attribute value is compressed.
         const COMPRESSED = 0x0001;
@@ -113,6 +127,7 @@ struct NtfsNonResidentAttributeHeader {
 ///
 /// Reference: <https://flatcap.github.io/linux-ntfs/ntfs/attributes/index.html>
 #[derive(Clone, Copy, Debug, Display, Eq, N, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[repr(u32)]
 pub enum NtfsAttributeType {
     /// $STANDARD_INFORMATION, see [`NtfsStandardInformation`].
@@ -201,6 +216,23 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         Ok(attribute)
     }
 
+    /// Returns the space allocated for the value of this NTFS Attribute, in bytes.
+    ///
+    /// For non-resident attributes, this is read from the attribute header and a multiple of the cluster size.
+    /// It may be smaller than [`NtfsAttribute::value_length`] if the value is compressed or stored sparsely.
+    /// For resident attributes, this is the value length rounded up to the next multiple of 8,
+    /// which is the alignment of attributes within a File Record.
+    ///
+    /// If the value is split over multiple connected attributes, only the first one reports this size.
+    pub fn allocated_size(&self) -> u64 {
+        if self.is_resident() {
+            (self.resident_value_length() as u64 + 7) & !7
+        } else {
+            let start = self.offset + offset_of!(NtfsNonResidentAttributeHeader, allocated_size);
+            LittleEndian::read_u64(&self.file.record_data()[start..])
+        }
+    }
+
     /// Returns the length of this NTFS Attribute, in bytes.
     ///
     /// This denotes the length of the attribute structure on disk.
@@ -238,6 +270,34 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         LittleEndian::read_u16(&self.file.record_data()[start..])
     }
 
+    /// Returns the size of the initialized part of the value of this NTFS Attribute, in bytes.
+    ///
+    /// This is usually the same as [`NtfsAttribute::value_length`].
+    /// Windows may defer zeroing the clusters of a preallocated file, in which case everything beyond
+    /// the initialized size reads as zeros.
+    /// Resident values are always fully initialized.
+    ///
+    /// If the value is split over multiple connected attributes, only the first one reports this size.
+    pub fn initialized_size(&self) -> u64 {
+        if self.is_resident() {
+            self.resident_value_length() as u64
+        } else {
+            let start = self.offset + offset_of!(NtfsNonResidentAttributeHeader, initialized_size);
+            LittleEndian::read_u64(&self.file.record_data()[start..])
+        }
+    }
+
+    /// Returns `true` if this is a continuation of a non-resident value split over multiple connected attributes,
+    /// i.e. an attribute whose value doesn't start at VCN 0.
+    ///
+    /// Such attributes are stored in extension records and referenced by an Attribute List.
+    /// [`NtfsAttributes`] merges them into the value of the first attribute and never returns them on their own,
+    /// but [`NtfsAttributesRaw`] (e.g. over an extension record) does.
+    /// Their header only describes their own VCN range, and sizes are only reported by the first attribute.
+    pub fn is_continuation(&self) -> bool {
+        !self.is_resident() && self.non_resident_value_lowest_vcn().value() != 0
+    }
+
     /// Returns `true` if this is a resident attribute, i.e. one where its value
     /// is part of the attribute structure.
     pub fn is_resident(&self) -> bool {
@@ -256,12 +316,24 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         }
 
         self.validate_name_sizes()?;
+        self.validate_field_layout()?;
+
+        // `validate_name_sizes` has checked the name against the attribute length, and `validate_attribute_length`
+        // has checked the attribute against the record size.
+        // Still use checked arithmetic here, so that no inconsistency can ever lead to a panic.
+        let data = self.file.record_data();
+        let slice = self
+            .offset
+            .checked_add(self.name_offset() as usize)
+            .and_then(|start| Some(start..start.checked_add(self.name_length())?))
+            .and_then(|range| data.get(range))
+            .ok_or(NtfsError::InvalidAttributeNameLength {
+                position: self.position(),
+                expected: self.name_offset() as usize + self.name_length(),
+                actual: self.attribute_length(),
+            })?;
 
-        let start = self.offset + self.name_offset() as usize;
-        let end = start + self.name_length();
-        let string = U16StrLe(&self.file.record_data()[start..end]);
-
-        Ok(string)
+        Ok(U16StrLe(slice))
     }
 
     fn name_offset(&self) -> u16 {
@@ -281,20 +353,15 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
     }
 
     pub(crate) fn non_resident_value(&self) -> Result<NtfsNonResidentAttributeValue<'n, 'f>> {
-        let (data, position) = self.non_resident_value_data_and_position()?;
-
-        NtfsNonResidentAttributeValue::new(
-            self.file.ntfs(),
-            data,
-            position,
-            self.non_resident_value_data_size(),
-        )
+        NtfsNonResidentAttributeValue::new(self)
     }
 
     pub(crate) fn non_resident_value_data_and_position(&self) -> Result<(&'f [u8], NtfsPosition)> {
         debug_assert!(!self.is_resident());
-        let start = self.offset + self.non_resident_value_data_runs_offset() as usize;
-        let end = self.offset + self.attribute_length() as usize;
+        let start = self
+            .offset
+            .saturating_add(self.non_resident_value_data_runs_offset() as usize);
+        let end = self.offset.saturating_add(self.attribute_length() as usize);
         let position = self.file.position() + start;
         let data = &self.file.record_data().get(start..end).ok_or(
             NtfsError::InvalidNonResidentValueDataRange {
@@ -306,7 +373,19 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         Ok((data, position))
     }
 
-    fn non_resident_value_data_size(&self) -> u64 {
+    pub(crate) fn non_resident_value_highest_vcn(&self) -> Vcn {
+        debug_assert!(!self.is_resident());
+        let start = self.offset + offset_of!(NtfsNonResidentAttributeHeader, highest_vcn);
+        Vcn::from(LittleEndian::read_i64(&self.file.record_data()[start..]))
+    }
+
+    pub(crate) fn non_resident_value_lowest_vcn(&self) -> Vcn {
+        debug_assert!(!self.is_resident());
+        let start = self.offset + offset_of!(NtfsNonResidentAttributeHeader, lowest_vcn);
+        Vcn::from(LittleEndian::read_i64(&self.file.record_data()[start..]))
+    }
+
+    pub(crate) fn non_resident_value_data_size(&self) -> u64 {
         debug_assert!(!self.is_resident());
         let start = self.offset + offset_of!(NtfsNonResidentAttributeHeader, data_size);
         LittleEndian::read_u64(&self.file.record_data()[start..])
@@ -318,6 +397,20 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         LittleEndian::read_u16(&self.file.record_data()[start..])
     }
 
+    pub(crate) fn ntfs(&self) -> &'n Ntfs {
+        self.file.ntfs()
+    }
+
+    /// Returns the File Record Number of the file this NTFS Attribute belongs to.
+    /// For an attribute stored in an extension record, this is the number of the base File Record.
+    pub(crate) fn owning_file_record_number(&self) -> u64 {
+        if self.file.is_base_record() {
+            self.file.file_record_number()
+        } else {
+            self.file.base_file_record().file_record_number()
+        }
+    }
+
     pub(crate) fn offset(&self) -> usize {
         self.offset
     }
@@ -353,14 +446,54 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
     }
 
     pub(crate) fn resident_value(&self) -> Result<NtfsResidentAttributeValue<'f>> {
+        let range = self.resident_value_range()?;
+        let data = &self.file.record_data()[range];
+
+        Ok(NtfsResidentAttributeValue::new(data, self.position()))
+    }
+
+    /// Returns the byte range of the resident value of this attribute within the data of its File Record.
+    pub(crate) fn resident_value_range(&self) -> Result<Range<usize>> {
         debug_assert!(self.is_resident());
         self.validate_resident_value_sizes()?;
+        self.validate_field_layout()?;
 
-        let start = self.offset + self.resident_value_offset() as usize;
-        let end = start + self.resident_value_length() as usize;
-        let data = &self.file.record_data()[start..end];
+        // See `NtfsAttribute::name` for why this uses checked arithmetic after validation.
+        let record_data = self.file.record_data();
+        self.offset
+            .checked_add(self.resident_value_offset() as usize)
+            .and_then(|start| {
+                let length = usize::try_from(self.resident_value_length()).ok()?;
+                Some(start..start.checked_add(length)?)
+            })
+            .filter(|range| range.end <= record_data.len())
+            .ok_or(NtfsError::InvalidResidentAttributeValueLength {
+                position: self.position(),
+                length: self.resident_value_length(),
+                offset: self.resident_value_offset(),
+                actual: self.attribute_length(),
+            })
+    }
 
-        Ok(NtfsResidentAttributeValue::new(data, self.position()))
+    /// Returns the byte position of the value length field of this resident attribute within the data of its
+    /// File Record.
+    #[cfg(feature = "write")]
+    pub(crate) fn resident_value_length_offset(&self) -> usize {
+        debug_assert!(self.is_resident());
+        self.offset + offset_of!(NtfsResidentAttributeHeader, value_length)
+    }
+
+    /// Returns the byte range within the data of its File Record that the resident value of this attribute
+    /// may occupy without moving any other attribute.
+    ///
+    /// This range starts at the value and ends at the end of the attribute.
+    #[cfg(feature = "write")]
+    pub(crate) fn resident_value_capacity_range(&self) -> Result<Range<usize>> {
+        let value_range = self.resident_value_range()?;
+
+        // `resident_value_range` has validated that the value ends within the attribute.
+        let end = self.offset + self.attribute_length() as usize;
+        Ok(value_range.start..end)
     }
 
     fn resident_value_length(&self) -> u32 {
@@ -390,11 +523,41 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         S::from_attribute_value(fs, value)
     }
 
+    /// Returns an [`NtfsAttributeMetadata`] snapshot of the header fields of this NTFS Attribute.
+    ///
+    /// Contrary to [`NtfsAttribute`], the snapshot doesn't borrow the [`NtfsFile`], so the metadata of multiple
+    /// attributes can be collected while iterating over them.
+    ///
+    /// The Data Runs are those stored in this attribute.
+    /// If the value is split over multiple connected attributes, they only cover the part of the value
+    /// stored in this attribute, while the sizes are reported for the entire value.
+    pub fn to_metadata(&self) -> Result<NtfsAttributeMetadata> {
+        let data_runs = if self.is_resident() {
+            Vec::new()
+        } else {
+            self.non_resident_value()?
+                .data_runs()
+                .collect::<Result<Vec<NtfsDataRun>>>()?
+        };
+
+        Ok(NtfsAttributeMetadata {
+            ty: self.ty()?,
+            name: self.name()?.to_string_lossy(),
+            instance: self.instance(),
+            flags: self.flags(),
+            is_resident: self.is_resident(),
+            value_length: self.value_length(),
+            allocated_size: self.allocated_size(),
+            initialized_size: self.initialized_size(),
+            position: self.position(),
+            data_runs,
+        })
+    }
+
     /// Returns the type of this NTFS Attribute, or [`NtfsError::UnsupportedAttributeType`]
     /// if it's an unknown type.
     pub fn ty(&self) -> Result<NtfsAttributeType> {
-        let start = self.offset + offset_of!(NtfsAttributeHeader, ty);
-        let ty = LittleEndian::read_u32(&self.file.record_data()[start..]);
+        let ty = self.ty_raw();
 
         NtfsAttributeType::n(ty).ok_or(NtfsError::UnsupportedAttributeType {
             position: self.position(),
@@ -402,6 +565,57 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         })
     }
 
+    /// Returns the raw type of this NTFS Attribute, which may also be an unknown type.
+    pub(crate) fn ty_raw(&self) -> u32 {
+        let start = self.offset + offset_of!(NtfsAttributeHeader, ty);
+        LittleEndian::read_u32(&self.file.record_data()[start..])
+    }
+
+    /// Checks this NTFS Attribute against the attribute definitions of the filesystem
+    /// (see [`Ntfs::attribute_definitions`]).
+    ///
+    /// The attribute type must be defined, the value length must be within the defined bounds,
+    /// and the attribute must be resident if its definition requires that.
+    ///
+    /// [`Ntfs::attribute_definitions`]: crate::Ntfs::attribute_definitions
+    pub fn validate_against_definitions(
+        &self,
+        definitions: &[NtfsAttributeDefinition],
+    ) -> Result<()> {
+        let ty = self.ty()?;
+        let definition = definitions
+            .iter()
+            .find(|definition| definition.type_code() == ty as u32)
+            .ok_or(NtfsError::AttributeTypeNotDefined {
+                position: self.position(),
+                ty,
+            })?;
+
+        let value_length = self.value_length();
+        if value_length < definition.minimum_size() || value_length > definition.maximum_size() {
+            return Err(NtfsError::InvalidAttributeValueLength {
+                position: self.position(),
+                ty,
+                minimum: definition.minimum_size(),
+                maximum: definition.maximum_size(),
+                actual: value_length,
+            });
+        }
+
+        if !self.is_resident()
+            && definition
+                .flags()
+                .contains(NtfsAttributeDefinitionFlags::RESIDENT)
+        {
+            return Err(NtfsError::AttributeMustBeResident {
+                position: self.position(),
+                ty,
+            });
+        }
+
+        Ok(())
+    }
+
     fn validate_attribute_length(&self) -> Result<()> {
         let start = self.offset;
         let end = self.file.record_data().len();
@@ -435,6 +649,57 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         Ok(())
     }
 
+    /// Checks that the name and the value (or the Data Runs of a non-resident attribute) both begin after
+    /// the fixed header and don't overlap each other.
+    ///
+    /// Empty fields are not checked, because they don't occupy any space.
+    /// Whether a field lies within the attribute is not checked here, callers validate the sizes of
+    /// the fields they access themselves.
+    fn validate_field_layout(&self) -> Result<()> {
+        let (header_end, value_start, value_end) = if self.is_resident() {
+            let start = self.resident_value_offset() as usize;
+            let length = self.resident_value_length();
+            let end = usize::try_from(length)
+                .ok()
+                .and_then(|length| start.checked_add(length))
+                .ok_or(NtfsError::InvalidResidentAttributeValueLength {
+                    position: self.position(),
+                    length,
+                    offset: self.resident_value_offset(),
+                    actual: self.attribute_length(),
+                })?;
+            (RESIDENT_ATTRIBUTE_HEADER_SIZE, start, end)
+        } else {
+            let start = self.non_resident_value_data_runs_offset() as usize;
+            let end = self.attribute_length() as usize;
+            (NON_RESIDENT_ATTRIBUTE_HEADER_SIZE, start, end)
+        };
+
+        // Like in `NtfsAttribute::name`, a name at offset zero is considered empty.
+        let name_start = self.name_offset() as usize;
+        let name_end = if name_start == 0 {
+            name_start
+        } else {
+            name_start + self.name_length()
+        };
+
+        let name_is_empty = name_start == name_end;
+        let value_is_empty = value_start >= value_end;
+
+        let name_in_header = !name_is_empty && name_start < header_end;
+        let value_in_header = !value_is_empty && value_start < header_end;
+        let overlapping =
+            !name_is_empty && !value_is_empty && name_start < value_end && value_start < name_end;
+
+        if name_in_header || value_in_header || overlapping {
+            return Err(NtfsError::OverlappingAttributeFields {
+                position: self.position(),
+            });
+        }
+
+        Ok(())
+    }
+
     fn validate_name_sizes(&self) -> Result<()> {
         let start = self.name_offset();
         if start as u32 >= self.attribute_length() {
@@ -509,9 +774,10 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
                 self.file.ntfs(),
                 fs,
                 list_entries.clone(),
-                self.instance(),
-                self.ty()?,
                 data_size,
+                self.allocated_size(),
+                self.owning_file_record_number(),
+                self.ty_raw(),
             )?;
             Ok(NtfsAttributeValue::AttributeListNonResident(value))
         } else if self.is_resident() {
@@ -523,6 +789,102 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         }
     }
 
+    /// Streams the logical content of the value of this NTFS Attribute through `f`, one chunk at a time.
+    ///
+    /// This applies all rules for reading the content of a file the way Windows does:
+    ///   * The content ends at [`NtfsAttribute::value_length`], even if more clusters are allocated.
+    ///   * Sparse holes are passed as zeros.
+    ///   * Everything beyond [`NtfsAttribute::initialized_size`] is passed as zeros, without reading the
+    ///     clusters on the filesystem (which may contain stale data).
+    ///
+    /// `buf` is used for every chunk, so a single buffer can be reused for many values.
+    /// All chunks except for the last one fill the entire buffer.
+    /// Iteration stops at the first error, be it from reading the value or returned by `f`.
+    ///
+    /// This is useful for hashing file contents:
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use ntfs::indexes::NtfsFileNameIndex;
+    /// # use ntfs::{Ntfs, NtfsError};
+    /// # fn main() -> Result<(), NtfsError> {
+    /// # let mut fs = File::open("testdata/testfs1").unwrap();
+    /// # let mut ntfs = Ntfs::new(&mut fs)?;
+    /// # ntfs.read_upcase_table(&mut fs)?;
+    /// # let root_dir = ntfs.root_directory(&mut fs)?;
+    /// # let index = root_dir.directory_index(&mut fs)?;
+    /// # let mut finder = index.finder();
+    /// # let entry = NtfsFileNameIndex::find(&mut finder, &ntfs, &mut fs, "file-with-12345").unwrap()?;
+    /// # let file = entry.to_file(&ntfs, &mut fs)?;
+    /// let data_item = file.data(&mut fs, "").unwrap()?;
+    /// let data_attribute = data_item.to_attribute()?;
+    ///
+    /// let mut buf = vec![0u8; 65536];
+    /// let mut checksum = 0u32;
+    /// data_attribute.for_each_value_chunk(&mut fs, &mut buf, |chunk| {
+    ///     checksum = chunk.iter().fold(checksum, |sum, byte| sum.wrapping_add(*byte as u32));
+    ///     Ok::<(), NtfsError>(())
+    /// })?;
+    /// assert_eq!(checksum, 5 * b'3' as u32);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn for_each_value_chunk<T, F, E>(
+        &self,
+        fs: &mut T,
+        buf: &mut [u8],
+        mut f: F,
+    ) -> core::result::Result<(), E>
+    where
+        T: Read + Seek,
+        F: FnMut(&[u8]) -> core::result::Result<(), E>,
+        E: From<NtfsError>,
+    {
+        if buf.is_empty() {
+            return Err(NtfsError::BufferTooSmall {
+                expected: 1,
+                actual: 0,
+            }
+            .into());
+        }
+
+        let mut value = self.value(fs)?;
+        let value_length = value.len();
+        let initialized_size = u64::min(self.initialized_size(), value_length);
+        let mut offset = 0;
+
+        while offset < initialized_size {
+            let chunk_size = u64::min(buf.len() as u64, initialized_size - offset) as usize;
+            let chunk = &mut buf[..chunk_size];
+            value.read_exact(fs, chunk)?;
+            f(chunk)?;
+            offset += chunk_size as u64;
+        }
+
+        if offset < value_length {
+            buf.fill(0);
+
+            while offset < value_length {
+                let chunk_size = u64::min(buf.len() as u64, value_length - offset) as usize;
+                f(&buf[..chunk_size])?;
+                offset += chunk_size as u64;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Async variant of [`NtfsAttribute::value`] for readers implementing [`AsyncNtfsReadSeek`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn value_async<T>(&self, fs: &mut T) -> Result<NtfsAttributeValue<'n, 'f>>
+    where
+        T: AsyncNtfsReadSeek,
+    {
+        let mut cache = AsyncBlockCache::new(fs).await?;
+        cache.run(|fs| self.value(fs)).await
+    }
+
     /// Returns the length of the value data of this NTFS Attribute, in bytes.
     pub fn value_length(&self) -> u64 {
         if self.is_resident() {
@@ -533,6 +895,79 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
     }
 }
 
+/// Owned snapshot of the header fields of an [`NtfsAttribute`], returned by [`NtfsAttribute::to_metadata`].
+///
+/// Each getter returns what the equally named function of [`NtfsAttribute`] returns.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsAttributeMetadata {
+    ty: NtfsAttributeType,
+    name: String,
+    instance: u16,
+    flags: NtfsAttributeFlags,
+    is_resident: bool,
+    value_length: u64,
+    allocated_size: u64,
+    initialized_size: u64,
+    position: NtfsPosition,
+    data_runs: Vec<NtfsDataRun>,
+}
+
+impl NtfsAttributeMetadata {
+    /// See [`NtfsAttribute::allocated_size`].
+    pub fn allocated_size(&self) -> u64 {
+        self.allocated_size
+    }
+
+    /// Returns the Data Runs stored in the attribute, or an empty slice for a resident attribute.
+    ///
+    /// See [`NtfsNonResidentAttributeValue::data_runs`].
+    pub fn data_runs(&self) -> &[NtfsDataRun] {
+        &self.data_runs
+    }
+
+    /// See [`NtfsAttribute::flags`].
+    pub fn flags(&self) -> NtfsAttributeFlags {
+        self.flags
+    }
+
+    /// See [`NtfsAttribute::initialized_size`].
+    pub fn initialized_size(&self) -> u64 {
+        self.initialized_size
+    }
+
+    /// See [`NtfsAttribute::instance`].
+    pub fn instance(&self) -> u16 {
+        self.instance
+    }
+
+    /// See [`NtfsAttribute::is_resident`].
+    pub fn is_resident(&self) -> bool {
+        self.is_resident
+    }
+
+    /// Returns the name of the attribute.
+    ///
+    /// Any unpaired UTF-16 surrogates are replaced by U+FFFD REPLACEMENT CHARACTER.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// See [`NtfsAttribute::position`].
+    pub fn position(&self) -> NtfsPosition {
+        self.position
+    }
+
+    /// See [`NtfsAttribute::ty`].
+    pub fn ty(&self) -> NtfsAttributeType {
+        self.ty
+    }
+
+    /// See [`NtfsAttribute::value_length`].
+    pub fn value_length(&self) -> u64 {
+        self.value_length
+    }
+}
+
 /// Iterator over
 ///   all attributes of an [`NtfsFile`],
 ///   returning an [`NtfsAttributeItem`] for each entry.
@@ -541,13 +976,40 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
 /// It provides a flattened "data-centric" view of the attributes and abstracts away the filesystem details
 /// to deal with many or large attributes (Attribute Lists and connected attributes).
 ///
+/// Attribute List entries are considered to be fragments of the same connected attribute if they directly
+/// follow each other, share type and name, and have strictly increasing lowest VCNs.
+/// Instance numbers are not taken into account, because they are only unique within a single File Record.
+///
+/// By default, the attributes of the base File Record are returned in their on-disk order.
+/// When the $ATTRIBUTE_LIST attribute is reached, the attributes of the extension records are returned in its
+/// place, in the order of the Attribute List, and iteration then continues with the remaining attributes of
+/// the base File Record.
+/// This is the canonical NTFS order (ascending attribute type, then name, then lowest VCN) only for files
+/// without an Attribute List.
+/// Call [`NtfsAttributes::in_attribute_list_order`] (or use [`NtfsFile::attributes_sorted`]) to get the
+/// canonical order for all files.
+///
 /// Check [`NtfsAttributesRaw`] if you want to iterate over the plain attributes on the filesystem.
 /// See [`NtfsAttributesAttached`] for an iterator that implements [`Iterator`] and [`FusedIterator`].
 #[derive(Clone, Debug)]
 pub struct NtfsAttributes<'n, 'f> {
     raw_iter: NtfsAttributesRaw<'n, 'f>,
     list_entries: Option<NtfsAttributeListEntries<'n, 'f>>,
-    list_skip_info: Option<(u16, NtfsAttributeType)>,
+    /// Last fragment of the connected attribute returned before, whose further fragments need to be skipped.
+    list_skip_entry: Option<NtfsAttributeListEntry>,
+    order: AttributeOrder,
+}
+
+/// Order in which [`NtfsAttributes`] returns the attributes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum AttributeOrder {
+    /// Attributes of the base File Record in their on-disk order, with Attribute List entries of
+    /// extension records returned in place of the $ATTRIBUTE_LIST attribute.
+    Raw,
+    /// Attribute List order has been requested, but we haven't looked for an Attribute List yet.
+    ListRequested,
+    /// All attributes are returned in the order of the Attribute List, including those of the base File Record.
+    List,
 }
 
 impl<'n, 'f> NtfsAttributes<'n, 'f> {
@@ -555,7 +1017,8 @@ impl<'n, 'f> NtfsAttributes<'n, 'f> {
         Self {
             raw_iter: NtfsAttributesRaw::new(file),
             list_entries: None,
-            list_skip_info: None,
+            list_skip_entry: None,
+            order: AttributeOrder::Raw,
         }
     }
 
@@ -589,44 +1052,74 @@ impl<'n, 'f> NtfsAttributes<'n, 'f> {
                         Some(Err(e)) => return Some(Err(e)),
                         None => break,
                     };
-                    let entry_instance = entry.instance();
                     let entry_record_number = entry.base_file_reference().file_record_number();
-                    let entry_ty = iter_try!(entry.ty());
+                    let file = self.raw_iter.file;
+
+                    // Every Attribute List entry of the base File Record must refer to one of its attributes.
+                    // Otherwise, that attribute would silently vanish from the flattened view.
+                    let mut base_record_attribute = None;
+                    if entry_record_number == file.file_record_number() {
+                        let attribute = iter_try!(base_record_attribute_for_entry(file, &entry));
 
-                    // Ignore all Attribute List entries that just repeat attributes of the raw iterator.
-                    if entry_record_number == self.raw_iter.file.file_record_number() {
-                        continue;
+                        // Ignore all Attribute List entries that just repeat attributes of the raw iterator.
+                        if self.order == AttributeOrder::Raw {
+                            continue;
+                        }
+
+                        base_record_attribute = Some(attribute);
                     }
 
                     // Ignore all Attribute List entries that are connected attributes of a previous one.
-                    if let Some((skip_instance, skip_ty)) = self.list_skip_info {
-                        if entry_instance == skip_instance && entry_ty == skip_ty {
+                    // They have already been returned as part of the value of that attribute.
+                    if let Some(skip_entry) = &self.list_skip_entry {
+                        if entry.is_connected_to(skip_entry) {
+                            self.list_skip_entry = Some(entry);
                             continue;
                         }
                     }
 
                     // We found an attribute that we want to return.
-                    self.list_skip_info = None;
-
-                    let ntfs = self.raw_iter.file.ntfs();
-                    let entry_file = iter_try!(entry.to_file(ntfs, fs));
-                    let entry_attribute = iter_try!(entry.to_attribute(&entry_file));
-                    let attribute_offset = entry_attribute.offset();
-
+                    self.list_skip_entry = None;
+
+                    let (attribute_value_file, attribute_offset, is_resident) =
+                        if let Some(attribute) = base_record_attribute {
+                            (None, attribute.offset(), attribute.is_resident())
+                        } else {
+                            let ntfs = file.ntfs();
+                            let entry_file = iter_try!(entry.to_file(ntfs, fs));
+                            let entry_attribute = iter_try!(entry.to_attribute(&entry_file));
+                            let attribute_offset = entry_attribute.offset();
+                            let is_resident = entry_attribute.is_resident();
+                            (Some(entry_file), attribute_offset, is_resident)
+                        };
+
+                    // Resident attributes are never split and can be read from their extension record
+                    // like any other resident attribute.
+                    // Only non-resident ones may be continued by further connected attributes.
                     let mut list_entries = None;
-                    if !entry_attribute.is_resident() {
+                    if !is_resident {
                         list_entries = Some(attribute_list_entries_clone);
-                        self.list_skip_info = Some((entry_instance, entry_ty));
+                        self.list_skip_entry = Some(entry);
                     }
 
                     let item = NtfsAttributeItem {
-                        attribute_file: self.raw_iter.file,
-                        attribute_value_file: Some(entry_file),
+                        attribute_file: file,
+                        attribute_value_file,
                         attribute_offset,
                         list_entries,
                     };
                     return Some(Ok(item));
                 }
+
+                // In Attribute List order, the Attribute List has already covered all attributes.
+                if self.order == AttributeOrder::List {
+                    return None;
+                }
+            }
+
+            if self.order == AttributeOrder::ListRequested {
+                iter_try!(self.find_attribute_list(fs));
+                continue;
             }
 
             let attribute = iter_try!(self.raw_iter.next()?);
@@ -645,6 +1138,62 @@ impl<'n, 'f> NtfsAttributes<'n, 'f> {
             }
         }
     }
+
+    /// Looks for an $ATTRIBUTE_LIST attribute in the base File Record and switches to Attribute List order
+    /// if there is one.
+    /// Otherwise, the attributes are returned in the order of the raw iterator.
+    fn find_attribute_list<T>(&mut self, fs: &mut T) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        self.order = AttributeOrder::Raw;
+
+        for attribute in self.raw_iter.clone() {
+            let attribute = attribute?;
+
+            if let Ok(NtfsAttributeType::AttributeList) = attribute.ty() {
+                let attribute_list = attribute.structured_value::<T, NtfsAttributeList>(fs)?;
+                self.list_entries = Some(attribute_list.entries());
+                self.order = AttributeOrder::List;
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Makes this iterator return all attributes in the order of the Attribute List, if the file has one.
+    ///
+    /// The Attribute List is sorted by attribute type, then by name (using upcase collation), then by lowest VCN,
+    /// which is the canonical NTFS order.
+    /// Attributes of the base File Record and of extension records are therefore interleaved as required
+    /// by that order.
+    /// By default, the attributes of the base File Record are returned in their on-disk order instead,
+    /// with the attributes of extension records returned in place of the $ATTRIBUTE_LIST attribute.
+    /// The $ATTRIBUTE_LIST attribute itself is not returned in Attribute List order, because it is not part
+    /// of the Attribute List.
+    ///
+    /// Files without an Attribute List are not affected.
+    pub fn in_attribute_list_order(mut self) -> Self {
+        self.order = AttributeOrder::ListRequested;
+        self
+    }
+}
+
+/// Returns the attribute of the base File Record that an Attribute List entry refers to (by type and instance).
+///
+/// Returns [`NtfsError::AttributeNotFound`] with the position of the entry if there is no such attribute.
+fn base_record_attribute_for_entry<'n, 'f>(
+    file: &'f NtfsFile<'n>,
+    entry: &NtfsAttributeListEntry,
+) -> Result<NtfsAttribute<'n, 'f>> {
+    entry.to_attribute(file).map_err(|e| match e {
+        NtfsError::AttributeNotFound { ty, .. } => NtfsError::AttributeNotFound {
+            position: entry.position(),
+            ty,
+        },
+        e => e,
+    })
 }
 
 /// Iterator over
@@ -706,6 +1255,23 @@ pub struct NtfsAttributeItem<'n, 'f> {
 }
 
 impl<'n, 'f> NtfsAttributeItem<'n, 'f> {
+    /// Returns the [`NtfsAttribute`] structure for this NTFS Attribute with the lifetime of the original
+    /// [`NtfsFile`], or `None` if the attribute is stored in another [`NtfsFile`] held by this item.
+    ///
+    /// The returned attribute doesn't know about any Attribute List entries, so this is only suitable for
+    /// reading resident values.
+    pub(crate) fn to_base_file_attribute(&self) -> Option<Result<NtfsAttribute<'n, 'f>>> {
+        if self.attribute_value_file.is_some() {
+            return None;
+        }
+
+        Some(NtfsAttribute::new(
+            self.attribute_file,
+            self.attribute_offset,
+            None,
+        ))
+    }
+
     /// Returns the actual [`NtfsAttribute`] structure for this NTFS Attribute.
     pub fn to_attribute<'i>(&'i self) -> Result<NtfsAttribute<'n, 'i>> {
         if let Some(file) = &self.attribute_value_file {
@@ -773,9 +1339,1333 @@ impl<'n, 'f> FusedIterator for NtfsAttributesRaw<'n, 'f> {}
 
 #[cfg(test)]
 mod tests {
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use super::*;
+    use crate::attribute_value::{NtfsPhysicalRange, NtfsReadSegment};
+    use crate::file::{KnownNtfsFileRecordNumber, NtfsFileFlags};
+    use crate::helpers::tests::{
+        attribute_list_entry, attribute_offset, encode_data_runs, file_reference,
+        first_attribute_offset, insert_attribute, make_extension_record, patch_file_record_number,
+        take_attribute_instance, AttributeBuilder, FailingReader,
+    };
     use crate::indexes::NtfsFileNameIndex;
-    use crate::ntfs::Ntfs;
-    use crate::traits::NtfsReadSeek;
+    use crate::structured_values::NtfsFileName;
+
+    /// Name of the resident $FILE_NAME attribute in an extension record of [`testfs1_with_attribute_list`].
+    const EXTENSION_RECORD_NAME: &str = "extra-name";
+
+    /// A non-resident $DATA attribute fragment of the fixture built by [`testfs1_with_attribute_list`].
+    struct DataFragment {
+        name: &'static str,
+        extension_record: u64,
+        instance: u16,
+        lowest_vcn: u64,
+        /// Offset of the single cluster of this fragment from the first cluster of "1000-bytes-file".
+        cluster_offset: u64,
+        /// Size of the entire stream (only stored in the first fragment).
+        data_size: u64,
+    }
+
+    /// Adds an $ATTRIBUTE_LIST to the File Record of "file-with-12345" in testfs1, which references
+    /// non-resident $DATA streams in the extension records 100 to 102 (which are overwritten for that purpose).
+    ///
+    /// The streams reuse the clusters of "1000-bytes-file" (which contains "12345" repeatedly):
+    ///   * "frag" is split into two fragments in records 100 and 101 and has a size of 1000 bytes.
+    ///   * "one" is stored in record 100, with a size of 5 bytes.
+    ///   * "two" is stored in record 102, with a size of 3 bytes starting at byte 512.
+    ///
+    /// The instance numbers are chosen to collide across records:
+    /// The two fragments of "frag" have different instance numbers, whereas the adjacent
+    /// "one" and "two" streams have equal ones.
+    ///
+    /// Additionally, record 101 holds a resident $FILE_NAME attribute with the name [`EXTENSION_RECORD_NAME`],
+    /// which is a copy of the $FILE_NAME attribute of the base File Record otherwise.
+    fn testfs1_with_attribute_list() -> std::io::Cursor<Vec<u8>> {
+        testfs1_with_attribute_list_entries(false)
+    }
+
+    /// Like [`testfs1_with_attribute_list`], but optionally also adds Attribute List entries for the
+    /// attributes of the base File Record, like Windows does.
+    fn testfs1_with_attribute_list_entries(base_record_entries: bool) -> std::io::Cursor<Vec<u8>> {
+        let fragments = [
+            DataFragment {
+                name: "frag",
+                extension_record: 100,
+                instance: 2,
+                lowest_vcn: 0,
+                cluster_offset: 0,
+                data_size: 1000,
+            },
+            DataFragment {
+                name: "frag",
+                extension_record: 101,
+                instance: 0,
+                lowest_vcn: 1,
+                cluster_offset: 1,
+                data_size: 0,
+            },
+            DataFragment {
+                name: "one",
+                extension_record: 100,
+                instance: 0,
+                lowest_vcn: 0,
+                cluster_offset: 0,
+                data_size: 5,
+            },
+            DataFragment {
+                name: "two",
+                extension_record: 102,
+                instance: 0,
+                lowest_vcn: 0,
+                cluster_offset: 1,
+                data_size: 3,
+            },
+        ];
+
+        testfs1_with_data_fragments(base_record_entries, &fragments)
+    }
+
+    /// Builds the fixture of [`testfs1_with_attribute_list_entries`] with the given $DATA attribute fragments
+    /// in the extension records 100 to 102.
+    fn testfs1_with_data_fragments(
+        base_record_entries: bool,
+        fragments: &[DataFragment],
+    ) -> std::io::Cursor<Vec<u8>> {
+        const CLUSTER_SIZE: u64 = 512;
+        const FILE_NAME_RECORD: u64 = 101;
+
+        let mut testfs1 = crate::helpers::tests::testfs1();
+
+        let (
+            base_reference,
+            extension_references,
+            first_lcn,
+            file_name_value,
+            base_record_attributes,
+        ) = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            assert_eq!(ntfs.cluster_size() as u64, CLUSTER_SIZE);
+
+            let base_file = ntfs.file(&mut testfs1, 65).unwrap();
+            let base_reference = file_reference(65, base_file.sequence_number());
+
+            // Type, name, and instance of all attributes of the base File Record.
+            let base_record_attributes = base_file
+                .attributes_raw()
+                .map(|attribute| {
+                    let attribute = attribute.unwrap();
+                    (
+                        attribute.ty().unwrap(),
+                        attribute.name().unwrap().to_string_lossy(),
+                        attribute.instance(),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            let extension_references = (100..=102)
+                .map(|file_record_number| {
+                    let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+                    (
+                        file_record_number,
+                        file_reference(file_record_number, file.sequence_number()),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            let data_file = ntfs.file(&mut testfs1, 66).unwrap();
+            let data_item = data_file.data(&mut testfs1, "").unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+            let data_run = match data_attribute.value(&mut testfs1).unwrap() {
+                NtfsAttributeValue::NonResident(value) => {
+                    value.data_runs().next().unwrap().unwrap()
+                }
+                _ => panic!("1000-bytes-file should be non-resident"),
+            };
+            let first_lcn = data_run.data_position().value().unwrap().get() / CLUSTER_SIZE;
+
+            // Copy the $FILE_NAME value of the base File Record, replacing the name.
+            let file_name_attribute = base_file
+                .attributes_raw()
+                .map(Result::unwrap)
+                .find(|attribute| attribute.ty().unwrap() == NtfsAttributeType::FileName)
+                .unwrap();
+            let mut file_name_value = match file_name_attribute.value(&mut testfs1).unwrap() {
+                NtfsAttributeValue::Resident(value) => value.data()[..0x42].to_vec(),
+                _ => panic!("$FILE_NAME attributes are always resident"),
+            };
+            file_name_value[0x40] = EXTENSION_RECORD_NAME.len() as u8;
+            file_name_value.extend(
+                EXTENSION_RECORD_NAME
+                    .encode_utf16()
+                    .flat_map(|c| c.to_le_bytes()),
+            );
+
+            (
+                base_reference,
+                extension_references,
+                first_lcn,
+                file_name_value,
+                base_record_attributes,
+            )
+        };
+
+        let extension_reference = |file_record_number: u64| {
+            let (_, reference) = extension_references
+                .iter()
+                .find(|(n, _)| *n == file_record_number)
+                .unwrap();
+            *reference
+        };
+
+        // Build the extension records.
+        for (file_record_number, _) in &extension_references {
+            let mut attributes = Vec::new();
+            let mut next_instance = 0;
+
+            for fragment in fragments
+                .iter()
+                .filter(|fragment| fragment.extension_record == *file_record_number)
+            {
+                // A single Data Run of 1 cluster.
+                let data_runs = encode_data_runs(&[(1, Some(first_lcn + fragment.cluster_offset))]);
+                let mut attribute = AttributeBuilder::non_resident(
+                    NtfsAttributeType::Data,
+                    fragment.lowest_vcn,
+                    fragment.lowest_vcn,
+                    &data_runs,
+                )
+                .name(fragment.name)
+                .instance(fragment.instance);
+                if fragment.lowest_vcn == 0 {
+                    let allocated_size =
+                        (fragment.data_size + CLUSTER_SIZE - 1) & !(CLUSTER_SIZE - 1);
+                    attribute =
+                        attribute.sizes(allocated_size, fragment.data_size, fragment.data_size);
+                }
+
+                attributes.extend(attribute.build());
+                next_instance = next_instance.max(fragment.instance + 1);
+            }
+
+            if *file_record_number == FILE_NAME_RECORD {
+                let attribute =
+                    AttributeBuilder::resident(NtfsAttributeType::FileName, &file_name_value)
+                        .indexed()
+                        .instance(next_instance);
+                attributes.extend(attribute.build());
+                next_instance += 1;
+            }
+
+            patch_file_record_number(&mut testfs1, *file_record_number, |record| {
+                make_extension_record(record, base_reference, &attributes, next_instance);
+            });
+        }
+
+        // Build the $ATTRIBUTE_LIST attribute, ordered by type, name, and lowest VCN.
+        // The $FILE_NAME attribute comes before the $DATA attributes, because its type is lower.
+        // Entries of the base File Record come first among entries of the same type and name.
+        let mut entries = Vec::new();
+        if base_record_entries {
+            for (ty, name, instance) in &base_record_attributes {
+                entries.push((*ty, name.clone(), 0, base_reference, *instance));
+            }
+        }
+        entries.push((
+            NtfsAttributeType::FileName,
+            String::new(),
+            0,
+            extension_reference(FILE_NAME_RECORD),
+            1,
+        ));
+        for fragment in fragments {
+            entries.push((
+                NtfsAttributeType::Data,
+                fragment.name.to_string(),
+                fragment.lowest_vcn,
+                extension_reference(fragment.extension_record),
+                fragment.instance,
+            ));
+        }
+        entries.sort_by(|(ty1, name1, vcn1, ..), (ty2, name2, vcn2, ..)| {
+            (*ty1 as u32, name1, vcn1).cmp(&(*ty2 as u32, name2, vcn2))
+        });
+
+        let list = entries
+            .iter()
+            .flat_map(|(ty, name, lowest_vcn, reference, instance)| {
+                attribute_list_entry(*ty, name, *lowest_vcn, *reference, *instance)
+            })
+            .collect::<Vec<u8>>();
+
+        patch_file_record_number(&mut testfs1, 65, |record| {
+            // The $ATTRIBUTE_LIST comes right after the $STANDARD_INFORMATION attribute.
+            let first_attribute_offset = first_attribute_offset(record);
+            assert_eq!(
+                attribute_offset(record, NtfsAttributeType::StandardInformation),
+                first_attribute_offset
+            );
+            let insert_offset = first_attribute_offset
+                + LittleEndian::read_u32(&record[first_attribute_offset + 4..]) as usize;
+
+            let attribute = AttributeBuilder::resident(NtfsAttributeType::AttributeList, &list)
+                .instance(take_attribute_instance(record))
+                .build();
+            insert_attribute(record, insert_offset, &attribute);
+        });
+
+        testfs1
+    }
+
+    /// Returns the first attribute ($STANDARD_INFORMATION) of the MFT File Record of testfs1
+    /// after letting `f` modify it.
+    fn patched_mft_first_attribute<F, R>(f: F, check: R)
+    where
+        F: FnOnce(&mut [u8]),
+        R: FnOnce(&NtfsAttribute),
+    {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let (position, file_record_size) = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            (
+                ntfs.mft_position().value().unwrap().get() as usize,
+                ntfs.file_record_size() as usize,
+            )
+        };
+        crate::helpers::tests::patch_file_record(
+            &mut testfs1,
+            position,
+            file_record_size,
+            |record| {
+                let attribute_offset = LittleEndian::read_u16(&record[20..]) as usize;
+                f(&mut record[attribute_offset..]);
+            },
+        );
+
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let mft = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::MFT as u64)
+            .unwrap();
+        let attribute = mft.attributes_raw().next().unwrap().unwrap();
+        assert_eq!(
+            attribute.ty().unwrap(),
+            NtfsAttributeType::StandardInformation
+        );
+        check(&attribute);
+    }
+
+    #[test]
+    fn test_overlapping_attribute_fields() {
+        // A name pointing into the resident value.
+        patched_mft_first_attribute(
+            |attribute| {
+                attribute[9] = 2;
+                LittleEndian::write_u16(&mut attribute[10..], 0x20);
+            },
+            |attribute| {
+                assert!(matches!(
+                    attribute.name(),
+                    Err(NtfsError::OverlappingAttributeFields { .. })
+                ));
+                assert!(matches!(
+                    attribute.resident_value(),
+                    Err(NtfsError::OverlappingAttributeFields { .. })
+                ));
+            },
+        );
+
+        // A name pointing into the attribute header.
+        patched_mft_first_attribute(
+            |attribute| {
+                attribute[9] = 1;
+                LittleEndian::write_u16(&mut attribute[10..], 0x10);
+            },
+            |attribute| {
+                assert!(matches!(
+                    attribute.name(),
+                    Err(NtfsError::OverlappingAttributeFields { .. })
+                ));
+            },
+        );
+
+        // A resident value pointing into the attribute header.
+        patched_mft_first_attribute(
+            |attribute| LittleEndian::write_u16(&mut attribute[20..], 0x10),
+            |attribute| {
+                assert!(matches!(
+                    attribute.resident_value(),
+                    Err(NtfsError::OverlappingAttributeFields { .. })
+                ));
+            },
+        );
+
+        // A name directly following the header and a value directly following the name are fine.
+        patched_mft_first_attribute(
+            |attribute| {
+                let value_length = LittleEndian::read_u32(&attribute[16..]);
+                LittleEndian::write_u32(&mut attribute[16..], value_length - 8);
+                LittleEndian::write_u16(&mut attribute[20..], 0x20);
+                attribute[9] = 4;
+                LittleEndian::write_u16(&mut attribute[10..], 0x18);
+            },
+            |attribute| {
+                assert!(attribute.name().is_ok());
+                assert_eq!(attribute.name_length(), 8);
+                assert!(attribute.resident_value().is_ok());
+            },
+        );
+    }
+
+    #[test]
+    fn test_adversarial_offsets() {
+        // A name offset at the upper u16 boundary.
+        patched_mft_first_attribute(
+            |attribute| {
+                attribute[9] = u8::MAX;
+                LittleEndian::write_u16(&mut attribute[10..], u16::MAX);
+            },
+            |attribute| {
+                assert!(matches!(
+                    attribute.name(),
+                    Err(NtfsError::InvalidAttributeNameOffset {
+                        expected: u16::MAX,
+                        ..
+                    })
+                ));
+            },
+        );
+
+        // A name that ends just beyond the attribute.
+        patched_mft_first_attribute(
+            |attribute| {
+                let attribute_length = LittleEndian::read_u32(&attribute[4..]) as u16;
+                attribute[9] = 1;
+                LittleEndian::write_u16(&mut attribute[10..], attribute_length - 1);
+            },
+            |attribute| {
+                assert!(matches!(
+                    attribute.name(),
+                    Err(NtfsError::InvalidAttributeNameLength { .. })
+                ));
+            },
+        );
+
+        // A resident value offset at the upper u16 boundary.
+        patched_mft_first_attribute(
+            |attribute| LittleEndian::write_u16(&mut attribute[20..], u16::MAX),
+            |attribute| {
+                assert!(matches!(
+                    attribute.resident_value(),
+                    Err(NtfsError::InvalidResidentAttributeValueOffset {
+                        expected: u16::MAX,
+                        ..
+                    })
+                ));
+            },
+        );
+
+        // Resident value lengths whose end overflows u32 or exceeds the attribute.
+        for length in [u32::MAX, u32::MAX - 0x17, 0x1_0000] {
+            patched_mft_first_attribute(
+                |attribute| LittleEndian::write_u32(&mut attribute[16..], length),
+                |attribute| {
+                    assert!(matches!(
+                        attribute.resident_value(),
+                        Err(NtfsError::InvalidResidentAttributeValueLength { length: l, .. })
+                            if l == length
+                    ));
+                    assert_eq!(attribute.value_length(), length as u64);
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn test_connected_attributes_across_extension_records() {
+        let mut testfs1 = testfs1_with_attribute_list();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+
+        // Every stream must be returned exactly once, even though instance numbers collide across records.
+        let mut data_stream_names = Vec::new();
+        let mut iter = file.attributes();
+        while let Some(item) = iter.next(&mut testfs1) {
+            let item = item.unwrap();
+            let attribute = item.to_attribute().unwrap();
+
+            if attribute.ty().unwrap() == NtfsAttributeType::Data {
+                data_stream_names.push(attribute.name().unwrap().to_string_lossy());
+            }
+        }
+        assert_eq!(data_stream_names, ["frag", "one", "two", ""]);
+
+        // The fragmented stream must be read across both extension records.
+        for (name, expected_data) in [
+            ("frag", b"12345".repeat(200)),
+            ("one", b"12345".to_vec()),
+            ("two", b"345".to_vec()),
+            ("", b"12345".to_vec()),
+        ] {
+            let data_item = file.data(&mut testfs1, name).unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+            let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+            assert_eq!(data_value.len(), expected_data.len() as u64);
+
+            let mut data = vec![0u8; expected_data.len() + 1];
+            let bytes_read = data_value.read(&mut testfs1, &mut data).unwrap();
+            assert_eq!(&data[..bytes_read], expected_data.as_slice(), "{name}");
+        }
+
+        // Reading at an offset works across the boundary of two fragments.
+        let data_item = file.data(&mut testfs1, "frag").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+        let mut data = [0u8; 5];
+        data_value.read_at(&mut testfs1, 510, &mut data).unwrap();
+        assert_eq!(&data, b"12345");
+        assert_eq!(data_value.stream_position(), 0);
+
+        // The sizes of a fragmented stream are only taken from its first fragment.
+        let stream_sizes = file
+            .data_stream_sizes(&mut testfs1)
+            .unwrap()
+            .into_iter()
+            .map(|sizes| {
+                (
+                    sizes.name().to_string(),
+                    sizes.data_size(),
+                    sizes.allocated_size(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stream_sizes,
+            [
+                ("frag".to_string(), 1000, 1024),
+                ("one".to_string(), 5, 512),
+                ("two".to_string(), 3, 512),
+                (String::new(), 5, 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_connected_attributes_continuations() {
+        // "three" is split into three fragments in the extension records 100 to 102.
+        // The third fragment reuses the first cluster of "1000-bytes-file".
+        let fragments = [
+            DataFragment {
+                name: "three",
+                extension_record: 100,
+                instance: 0,
+                lowest_vcn: 0,
+                cluster_offset: 0,
+                data_size: 1500,
+            },
+            DataFragment {
+                name: "three",
+                extension_record: 101,
+                instance: 0,
+                lowest_vcn: 1,
+                cluster_offset: 1,
+                data_size: 0,
+            },
+            DataFragment {
+                name: "three",
+                extension_record: 102,
+                instance: 0,
+                lowest_vcn: 2,
+                cluster_offset: 0,
+                data_size: 0,
+            },
+        ];
+        let mut testfs1 = testfs1_with_data_fragments(false, &fragments);
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+
+        // The fragments are merged into a single item, which is not a continuation.
+        let mut data_items = Vec::new();
+        let mut attributes = file.attributes();
+        while let Some(item) = attributes.next(&mut testfs1) {
+            let item = item.unwrap();
+            let attribute = item.to_attribute().unwrap();
+            assert!(!attribute.is_continuation());
+
+            if attribute.ty().unwrap() == NtfsAttributeType::Data {
+                let mut data = Vec::new();
+                let mut value = attribute.value(&mut testfs1).unwrap();
+                value
+                    .read_to_end(&mut testfs1, &mut data, value.len() as usize)
+                    .unwrap();
+                data_items.push((attribute.name().unwrap().to_string_lossy(), data));
+            }
+        }
+
+        // The value consists of the two clusters of "1000-bytes-file" (including the unused end of the second one),
+        // followed by the first cluster again.
+        let file_data = b"12345".repeat(200);
+        assert_eq!(data_items.len(), 2);
+        let (name, data) = &data_items[0];
+        assert_eq!(name, "three");
+        assert_eq!(data.len(), 1500);
+        assert_eq!(data[..1000], file_data[..]);
+        assert_eq!(data[1024..], file_data[..476]);
+        assert_eq!(data_items[1].0, "");
+
+        // Iterating each extension record on its own also returns the second and third fragment,
+        // which are continuations.
+        for (file_record_number, is_continuation) in [(100, false), (101, true), (102, true)] {
+            let extension_file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+            let data_attribute = extension_file
+                .attributes_raw()
+                .map(Result::unwrap)
+                .find(|attribute| attribute.ty().unwrap() == NtfsAttributeType::Data)
+                .unwrap();
+            assert_eq!(
+                data_attribute.is_continuation(),
+                is_continuation,
+                "{file_record_number}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_connected_attributes_failed_read_keeps_position() {
+        let mut testfs1 = FailingReader {
+            inner: testfs1_with_attribute_list(),
+            fail_range: None,
+        };
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        let data_item = file.data(&mut testfs1, "frag").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+        assert!(matches!(
+            data_value,
+            NtfsAttributeValue::AttributeListNonResident(_)
+        ));
+
+        let mut expected = vec![0u8; data_value.len() as usize];
+        data_value.read_exact(&mut testfs1, &mut expected).unwrap();
+
+        // Get the position of a byte in the second fragment, which is stored in another extension record.
+        data_value
+            .seek(&mut testfs1, crate::io::SeekFrom::Start(600))
+            .unwrap();
+        let fail_position = data_value.data_position().value().unwrap().get();
+        data_value
+            .seek(&mut testfs1, crate::io::SeekFrom::Start(100))
+            .unwrap();
+
+        // Fail in the middle of the second fragment, after the first one has been read successfully.
+        testfs1.fail_range = Some(fail_position..fail_position + 1);
+        let mut buf = vec![0u8; expected.len() - 100];
+        let error = data_value.read(&mut testfs1, &mut buf).unwrap_err();
+        assert!(matches!(error.root_cause(), NtfsError::Io(_)));
+        assert_eq!(data_value.stream_position(), 100);
+
+        // Retrying the read at the same position returns the identical content.
+        testfs1.fail_range = None;
+        data_value.read_exact(&mut testfs1, &mut buf).unwrap();
+        assert_eq!(buf, expected[100..]);
+        assert_eq!(data_value.stream_position(), expected.len() as u64);
+    }
+
+    #[test]
+    fn test_connected_attributes_read_up_to_attribute_boundary() {
+        let mut testfs1 = FailingReader {
+            inner: testfs1_with_attribute_list(),
+            fail_range: None,
+        };
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        let data_item = file.data(&mut testfs1, "frag").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+
+        let mut expected = vec![0u8; data_value.len() as usize];
+        data_value.read_exact(&mut testfs1, &mut expected).unwrap();
+        data_value
+            .seek(&mut testfs1, crate::io::SeekFrom::Start(0))
+            .unwrap();
+
+        // Make the extension record with the second fragment unreadable.
+        let record_position = ntfs.file(&mut testfs1, 101).unwrap().position().value();
+        let record_position = record_position.unwrap().get();
+        testfs1.fail_range =
+            Some(record_position..record_position + ntfs.file_record_size() as u64);
+
+        // Reading the entire first fragment succeeds, because the next connected attribute is only
+        // read by the next read.
+        let mut buf = vec![0u8; 512];
+        assert_eq!(data_value.read(&mut testfs1, &mut buf).unwrap(), 512);
+        assert_eq!(buf, expected[..512]);
+        assert_eq!(data_value.stream_position(), 512);
+
+        let error = data_value.read(&mut testfs1, &mut buf).unwrap_err();
+        assert!(matches!(error.root_cause(), NtfsError::Io(_)));
+        assert_eq!(data_value.stream_position(), 512);
+
+        testfs1.fail_range = None;
+        let mut buf = vec![0u8; expected.len() - 512];
+        data_value.read_exact(&mut testfs1, &mut buf).unwrap();
+        assert_eq!(buf, expected[512..]);
+    }
+
+    #[test]
+    fn test_connected_attributes_read_segment_error_context() {
+        let mut testfs1 = FailingReader {
+            inner: testfs1_with_attribute_list(),
+            fail_range: None,
+        };
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        let data_item = file.data(&mut testfs1, "frag").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+
+        // Make the extension record with the second fragment unreadable.
+        let record_position = ntfs.file(&mut testfs1, 101).unwrap().position().value();
+        let record_position = record_position.unwrap().get();
+        testfs1.fail_range =
+            Some(record_position..record_position + ntfs.file_record_size() as u64);
+
+        let mut buf = vec![0u8; 1024];
+        assert_eq!(
+            data_value.read_segment(&mut testfs1, &mut buf).unwrap(),
+            NtfsReadSegment::Data(512)
+        );
+
+        let error = data_value.read_segment(&mut testfs1, &mut buf).unwrap_err();
+        assert!(matches!(error.root_cause(), NtfsError::Io(_)));
+        assert!(matches!(
+            error,
+            NtfsError::ValueReadContext {
+                file_record_number: 65,
+                ty,
+                ..
+            } if ty == NtfsAttributeType::Data as u32
+        ));
+    }
+
+    #[test]
+    fn test_connected_attributes_byte_ranges() {
+        // The ranges of a value must match the positions returned after seeking to the same offsets.
+        fn position_at(
+            value: &mut NtfsAttributeValue,
+            fs: &mut std::io::Cursor<Vec<u8>>,
+            offset: u64,
+        ) -> NtfsPosition {
+            value.seek(fs, crate::io::SeekFrom::Start(offset)).unwrap();
+            value.data_position()
+        }
+
+        let mut testfs1 = testfs1_with_attribute_list();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+
+        // "frag" is split over two connected attributes with one cluster each.
+        let data_item = file.data(&mut testfs1, "frag").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+        let ranges = data_value.byte_ranges(&mut testfs1, 0..u64::MAX).unwrap();
+        assert_eq!(
+            ranges,
+            [
+                NtfsPhysicalRange::Data {
+                    logical_offset: 0,
+                    position: position_at(&mut data_value, &mut testfs1, 0),
+                    length: 512,
+                },
+                NtfsPhysicalRange::Data {
+                    logical_offset: 512,
+                    position: position_at(&mut data_value, &mut testfs1, 512),
+                    length: 488,
+                },
+            ]
+        );
+
+        // The unnamed $DATA attribute is resident in the base File Record.
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+        let ranges = data_value.byte_ranges(&mut testfs1, 1..5).unwrap();
+        assert_eq!(
+            ranges,
+            [NtfsPhysicalRange::Data {
+                logical_offset: 1,
+                position: position_at(&mut data_value, &mut testfs1, 1),
+                length: 4,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_connected_attributes_read_after_byte_ranges() {
+        let mut testfs1 = testfs1_with_attribute_list();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        let data_item = file.data(&mut testfs1, "frag").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = match data_attribute.value(&mut testfs1).unwrap() {
+            NtfsAttributeValue::AttributeListNonResident(value) => value,
+            _ => panic!("\"frag\" should be a non-resident value in an Attribute List"),
+        };
+
+        let mut expected = vec![0u8; data_value.len() as usize];
+        data_value
+            .clone()
+            .read_exact(&mut testfs1, &mut expected)
+            .unwrap();
+
+        // Reading the File Records of the connected attributes in between moves the filesystem reader,
+        // which the next read must notice.
+        let mut buf = [0u8; 100];
+        data_value.read_exact(&mut testfs1, &mut buf).unwrap();
+        assert_eq!(buf, expected[..100]);
+
+        data_value.byte_ranges(&mut testfs1, 0..u64::MAX).unwrap();
+        data_value.read_exact(&mut testfs1, &mut buf).unwrap();
+        assert_eq!(buf, expected[100..200]);
+
+        assert!(data_value.is_consistent(&mut testfs1).unwrap());
+        data_value.read_exact(&mut testfs1, &mut buf).unwrap();
+        assert_eq!(buf, expected[200..300]);
+    }
+
+    #[test]
+    fn test_connected_attributes_consistency() {
+        fn is_consistent(testfs1: &mut std::io::Cursor<Vec<u8>>, name: &str) -> bool {
+            let mut ntfs = Ntfs::new(testfs1).unwrap();
+            ntfs.read_upcase_table(testfs1).unwrap();
+            let file = ntfs.file(testfs1, 65).unwrap();
+            let data_item = file.data(testfs1, name).unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+
+            match data_attribute.value(testfs1).unwrap() {
+                NtfsAttributeValue::AttributeListNonResident(value) => {
+                    value.is_consistent(testfs1).unwrap()
+                }
+                _ => panic!("\"{name}\" should be a non-resident value in an Attribute List"),
+            }
+        }
+
+        let mut testfs1 = testfs1_with_attribute_list();
+        for name in ["frag", "one", "two"] {
+            assert!(is_consistent(&mut testfs1, name), "{name}");
+        }
+
+        // Let the second fragment of "frag" begin at VCN 2 (and also end there),
+        // leaving a gap after the first fragment, which ends at VCN 0.
+        let position = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let file = ntfs.file(&mut testfs1, 101).unwrap();
+            let attribute = file
+                .attributes_raw()
+                .map(Result::unwrap)
+                .find(|attribute| attribute.ty().unwrap() == NtfsAttributeType::Data)
+                .unwrap();
+            attribute.position().value().unwrap().get() as usize
+        };
+        assert!(position % 512 + 32 <= 510);
+        LittleEndian::write_u64(&mut testfs1.get_mut()[position + 16..], 2);
+        LittleEndian::write_u64(&mut testfs1.get_mut()[position + 24..], 2);
+
+        assert!(!is_consistent(&mut testfs1, "frag"));
+        assert!(is_consistent(&mut testfs1, "one"));
+    }
+
+    /// Returns type and name of all attributes returned by `iter`.
+    fn attribute_types_and_names<T>(
+        mut iter: NtfsAttributes,
+        fs: &mut T,
+    ) -> Vec<(NtfsAttributeType, String)>
+    where
+        T: Read + Seek,
+    {
+        let mut attributes = Vec::new();
+        while let Some(item) = iter.next(fs) {
+            let item = item.unwrap();
+            let attribute = item.to_attribute().unwrap();
+            attributes.push((
+                attribute.ty().unwrap(),
+                attribute.name().unwrap().to_string_lossy(),
+            ));
+        }
+        attributes
+    }
+
+    #[test]
+    fn test_attribute_list_order() {
+        let owned = |attributes: &[(NtfsAttributeType, &str)]| {
+            attributes
+                .iter()
+                .map(|(ty, name)| (*ty, name.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let raw_order = owned(&[
+            (NtfsAttributeType::StandardInformation, ""),
+            (NtfsAttributeType::FileName, ""),
+            (NtfsAttributeType::Data, "frag"),
+            (NtfsAttributeType::Data, "one"),
+            (NtfsAttributeType::Data, "two"),
+            (NtfsAttributeType::FileName, ""),
+            (NtfsAttributeType::SecurityDescriptor, ""),
+            (NtfsAttributeType::Data, ""),
+        ]);
+
+        for base_record_entries in [false, true] {
+            let mut testfs1 = testfs1_with_attribute_list_entries(base_record_entries);
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let file = ntfs.file(&mut testfs1, 65).unwrap();
+
+            // By default, the extension record attributes are returned in place of the $ATTRIBUTE_LIST,
+            // no matter whether the Attribute List also references the base File Record.
+            assert_eq!(
+                attribute_types_and_names(file.attributes(), &mut testfs1),
+                raw_order
+            );
+
+            // In Attribute List order, only the attributes referenced by the Attribute List are returned.
+            let list_order = if base_record_entries {
+                owned(&[
+                    (NtfsAttributeType::StandardInformation, ""),
+                    (NtfsAttributeType::FileName, ""),
+                    (NtfsAttributeType::FileName, ""),
+                    (NtfsAttributeType::SecurityDescriptor, ""),
+                    (NtfsAttributeType::Data, ""),
+                    (NtfsAttributeType::Data, "frag"),
+                    (NtfsAttributeType::Data, "one"),
+                    (NtfsAttributeType::Data, "two"),
+                ])
+            } else {
+                owned(&[
+                    (NtfsAttributeType::FileName, ""),
+                    (NtfsAttributeType::Data, "frag"),
+                    (NtfsAttributeType::Data, "one"),
+                    (NtfsAttributeType::Data, "two"),
+                ])
+            };
+            assert_eq!(
+                attribute_types_and_names(
+                    file.attributes().in_attribute_list_order(),
+                    &mut testfs1
+                ),
+                list_order
+            );
+            assert_eq!(
+                attribute_types_and_names(file.attributes_sorted(), &mut testfs1),
+                list_order
+            );
+
+            if base_record_entries {
+                // The sorted order interleaves attributes of the base File Record and the extension records.
+                let base_position = file.position().value().unwrap().get();
+                let base_range = base_position..base_position + ntfs.file_record_size() as u64;
+                let mut iter = file.attributes_sorted();
+                let mut in_base_record = Vec::new();
+                while let Some(item) = iter.next(&mut testfs1) {
+                    let item = item.unwrap();
+                    let attribute = item.to_attribute().unwrap();
+                    let position = attribute.position().value().unwrap().get();
+                    in_base_record.push(base_range.contains(&position));
+                }
+                assert_eq!(
+                    in_base_record,
+                    [true, true, false, true, true, false, false, false]
+                );
+            }
+        }
+
+        // Files without an Attribute List are not affected by the order.
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let mft = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::MFT as u64)
+            .unwrap();
+        assert_eq!(
+            attribute_types_and_names(mft.attributes().in_attribute_list_order(), &mut testfs1),
+            attribute_types_and_names(mft.attributes(), &mut testfs1)
+        );
+    }
+
+    #[test]
+    fn test_attribute_list_entry_without_base_record_attribute() {
+        let mut testfs1 = testfs1_with_attribute_list_entries(true);
+
+        // Let the Attribute List entry of the base File Record's $STANDARD_INFORMATION attribute
+        // (the first entry) refer to a nonexistent instance.
+        let (position, file_record_size, attribute_offset, entry_position) = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let file = ntfs.file(&mut testfs1, 65).unwrap();
+            let attribute = file
+                .find_resident_attribute(NtfsAttributeType::AttributeList, None, None)
+                .unwrap();
+            let attribute_list = attribute
+                .structured_value::<_, NtfsAttributeList>(&mut testfs1)
+                .unwrap();
+            let entry = attribute_list
+                .entries()
+                .next(&mut testfs1)
+                .unwrap()
+                .unwrap();
+            assert_eq!(entry.ty().unwrap(), NtfsAttributeType::StandardInformation);
+            (
+                file.position().value().unwrap().get() as usize,
+                ntfs.file_record_size() as usize,
+                attribute.offset(),
+                entry.position(),
+            )
+        };
+
+        crate::helpers::tests::patch_file_record(
+            &mut testfs1,
+            position,
+            file_record_size,
+            |record| {
+                let value_offset =
+                    LittleEndian::read_u16(&record[attribute_offset + 20..]) as usize;
+                let entry_offset = attribute_offset + value_offset;
+                assert_eq!(
+                    LittleEndian::read_u32(&record[entry_offset..]),
+                    NtfsAttributeType::StandardInformation as u32
+                );
+                LittleEndian::write_u16(&mut record[entry_offset + 24..], 0x1234);
+            },
+        );
+
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+
+        // The inconsistency must be reported in both orders instead of silently skipping the attribute.
+        for iter in [
+            file.attributes(),
+            file.attributes().in_attribute_list_order(),
+        ] {
+            let error = iter.attach(&mut testfs1).find_map(Result::err).unwrap();
+            assert!(matches!(
+                error,
+                NtfsError::AttributeNotFound {
+                    ty: NtfsAttributeType::StandardInformation,
+                    ..
+                }
+            ));
+            assert_eq!(error.position(), Some(entry_position));
+        }
+    }
+
+    #[test]
+    fn test_attribute_list_entries_attached() {
+        let mut testfs1 = testfs1_with_attribute_list();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        let attribute = file
+            .find_resident_attribute(NtfsAttributeType::AttributeList, None, None)
+            .unwrap();
+        let attribute_list = attribute
+            .structured_value::<_, NtfsAttributeList>(&mut testfs1)
+            .unwrap();
+
+        let entries = attribute_list
+            .entries()
+            .attach(&mut testfs1)
+            .map(|entry| {
+                entry.map(|entry| {
+                    (
+                        entry.ty().unwrap(),
+                        entry.name().to_string_lossy(),
+                        entry.base_file_reference().file_record_number(),
+                        entry.lowest_vcn(),
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            entries,
+            [
+                (
+                    NtfsAttributeType::FileName,
+                    String::new(),
+                    101,
+                    Vcn::from(0)
+                ),
+                (
+                    NtfsAttributeType::Data,
+                    "frag".to_string(),
+                    100,
+                    Vcn::from(0)
+                ),
+                (
+                    NtfsAttributeType::Data,
+                    "frag".to_string(),
+                    101,
+                    Vcn::from(1)
+                ),
+                (
+                    NtfsAttributeType::Data,
+                    "one".to_string(),
+                    100,
+                    Vcn::from(0)
+                ),
+                (
+                    NtfsAttributeType::Data,
+                    "two".to_string(),
+                    102,
+                    Vcn::from(0)
+                ),
+            ]
+        );
+
+        // Detaching returns an iterator that continues where the attached one stopped.
+        let mut attached = attribute_list.entries().attach(&mut testfs1);
+        attached.next().unwrap().unwrap();
+        let mut detached = attached.detach();
+        let second_entry = detached.next(&mut testfs1).unwrap().unwrap();
+        assert_eq!(second_entry.ty().unwrap(), NtfsAttributeType::Data);
+        assert_eq!(second_entry.name(), "frag");
+    }
+
+    #[test]
+    fn test_data_stream_name_case() {
+        let mut testfs1 = testfs1_with_attribute_list();
+
+        // Rename the "two" stream in record 102 to "TwO".
+        let (position, file_record_size) = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let file = ntfs.file(&mut testfs1, 102).unwrap();
+            (
+                file.position().value().unwrap().get() as usize,
+                ntfs.file_record_size() as usize,
+            )
+        };
+        crate::helpers::tests::patch_file_record(
+            &mut testfs1,
+            position,
+            file_record_size,
+            |record| {
+                let attribute_offset = LittleEndian::read_u16(&record[20..]) as usize;
+                let name_offset = attribute_offset + 64;
+                let name = "TwO"
+                    .encode_utf16()
+                    .flat_map(|c| c.to_le_bytes())
+                    .collect::<Vec<u8>>();
+                record[name_offset..name_offset + name.len()].copy_from_slice(&name);
+            },
+        );
+
+        // Without the $UpCase table, stream names are compared exactly.
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        assert!(file.data(&mut testfs1, "two").is_none());
+        assert!(file.data(&mut testfs1, "TwO").is_some());
+
+        // With the $UpCase table, they are compared case-insensitively like on Windows.
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        for name in ["two", "TWO", "TwO"] {
+            let data_item = file.data(&mut testfs1, name).unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+            assert_eq!(data_attribute.name().unwrap(), "TwO");
+
+            let mut data = [0u8; 3];
+            data_attribute
+                .value(&mut testfs1)
+                .unwrap()
+                .read_exact(&mut testfs1, &mut data)
+                .unwrap();
+            assert_eq!(&data, b"345");
+        }
+    }
+
+    #[test]
+    fn test_data_position_after_full_read() {
+        let mut testfs1 = testfs1_with_attribute_list();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+
+        // Resident, non-resident, and Attribute List non-resident values must all point
+        // directly after their last byte when they have been read to the end.
+        // The two fragments of "frag" are stored in adjacent clusters, so its end is also
+        // 1000 bytes after its start.
+        for (name, expect_resident) in [("", true), ("one", false), ("frag", false)] {
+            let data_item = file.data(&mut testfs1, name).unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+            assert_eq!(data_attribute.is_resident(), expect_resident, "{name}");
+
+            let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+            let start = data_value.data_position().get().unwrap();
+
+            let mut data = Vec::new();
+            data_value
+                .read_to_end(&mut testfs1, &mut data, data_value.len() as usize)
+                .unwrap();
+            assert_eq!(data.len() as u64, data_value.len(), "{name}");
+            assert_eq!(
+                data_value.data_position().get(),
+                Some(start + data_value.len()),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_extension_records() {
+        let mut testfs1 = testfs1_with_attribute_list();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        let base_file = ntfs.file(&mut testfs1, 65).unwrap();
+        assert!(base_file.is_base_record());
+
+        // Each extension record references the base File Record and continues
+        // the instance numbering after its own attributes.
+        for (file_record_number, next_attribute_instance) in [(100, 3), (101, 2), (102, 1)] {
+            let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+            assert!(!file.is_base_record());
+            assert_eq!(file.base_file_record().file_record_number(), 65);
+            assert_eq!(
+                file.base_file_record().sequence_number(),
+                base_file.sequence_number()
+            );
+            assert_eq!(file.next_attribute_instance(), next_attribute_instance);
+        }
+    }
+
+    /// Wraps a reader and records the position of every read.
+    struct ReadPositionRecorder<R> {
+        inner: R,
+        read_positions: Vec<u64>,
+    }
+
+    impl<R: Read + Seek> Read for ReadPositionRecorder<R> {
+        fn read(&mut self, buf: &mut [u8]) -> crate::io::Result<usize> {
+            let position = self.inner.stream_position()?;
+            self.read_positions.push(position);
+            self.inner.read(buf)
+        }
+    }
+
+    impl<R: Seek> Seek for ReadPositionRecorder<R> {
+        fn seek(&mut self, pos: crate::io::SeekFrom) -> crate::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_file_record_cache() {
+        let mut testfs1 = testfs1_with_attribute_list_entries(true);
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let extension_record_positions = (100..=102)
+            .map(|file_record_number| {
+                let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+                file.position().value().unwrap().get()
+            })
+            .collect::<Vec<u64>>();
+
+        let mut fs = ReadPositionRecorder {
+            inner: testfs1,
+            read_positions: Vec::new(),
+        };
+
+        // Iterates all attributes of "file-with-12345" and returns how often each extension record has been read.
+        let mut extension_record_reads = |ntfs: &Ntfs| {
+            fs.read_positions.clear();
+
+            let file = ntfs.file(&mut fs, 65).unwrap();
+            let mut attributes = file.attributes();
+            while let Some(item) = attributes.next(&mut fs) {
+                item.unwrap().to_attribute().unwrap();
+            }
+
+            extension_record_positions
+                .iter()
+                .map(|position| {
+                    fs.read_positions
+                        .iter()
+                        .filter(|read_position| *read_position == position)
+                        .count()
+                })
+                .collect::<Vec<usize>>()
+        };
+
+        // Without a cache, record 100 is read for both attributes it contains.
+        assert_eq!(extension_record_reads(&ntfs), [2, 1, 1]);
+
+        // With a cache, every extension record is read only once, and not at all when iterating again.
+        ntfs.set_file_record_cache_capacity(10);
+        assert_eq!(extension_record_reads(&ntfs), [1, 1, 1]);
+        assert_eq!(extension_record_reads(&ntfs), [0, 0, 0]);
+
+        // Clearing the cache reads them again.
+        ntfs.clear_file_record_cache();
+        assert_eq!(extension_record_reads(&ntfs), [1, 1, 1]);
+    }
+
+    #[test]
+    fn test_read_segment_across_extension_records() {
+        let mut testfs1 = testfs1_with_attribute_list();
+
+        // Turn the Data Run of the second "frag" fragment (in record 101) into a sparse one.
+        let (position, file_record_size) = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let file = ntfs.file(&mut testfs1, 101).unwrap();
+            (
+                file.position().value().unwrap().get() as usize,
+                ntfs.file_record_size() as usize,
+            )
+        };
+        crate::helpers::tests::patch_file_record(
+            &mut testfs1,
+            position,
+            file_record_size,
+            |record| {
+                let attribute_offset = LittleEndian::read_u16(&record[20..]) as usize;
+                let data_runs_offset = attribute_offset
+                    + LittleEndian::read_u16(&record[attribute_offset + 32..]) as usize;
+
+                // A single Data Run of 1 cluster without an LCN, followed by the terminator.
+                record[data_runs_offset] = 0x01;
+                record[data_runs_offset + 2] = 0;
+            },
+        );
+
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        let data_item = file.data(&mut testfs1, "frag").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+        assert!(matches!(
+            data_value,
+            NtfsAttributeValue::AttributeListNonResident(_)
+        ));
+
+        // The first fragment is data, the second one is a hole up to the data size of 1000 bytes.
+        let mut buf = [0u8; 1024];
+        assert_eq!(
+            data_value.read_segment(&mut testfs1, &mut buf).unwrap(),
+            NtfsReadSegment::Data(512)
+        );
+        assert_eq!(&buf[..5], b"12345");
+
+        buf.fill(0xff);
+        assert_eq!(
+            data_value.read_segment(&mut testfs1, &mut buf).unwrap(),
+            NtfsReadSegment::Hole(488)
+        );
+        assert!(buf.iter().all(|byte| *byte == 0xff));
+        assert_eq!(data_value.stream_position(), 1000);
+
+        assert_eq!(
+            data_value.read_segment(&mut testfs1, &mut buf).unwrap(),
+            NtfsReadSegment::Data(0)
+        );
+    }
 
     #[test]
     fn test_empty_data_attribute() {
@@ -804,4 +2694,372 @@ mod tests {
         let bytes_read = data_attribute_value.read(&mut testfs1, &mut buf).unwrap();
         assert_eq!(bytes_read, 0);
     }
+
+    #[test]
+    fn test_non_resident_value_of_unknown_attribute_type() {
+        const UNKNOWN_TYPE: u32 = 0x1000;
+
+        // Change the type of the non-resident $DATA attribute of "1000-bytes-file" to an unknown one.
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        patch_file_record_number(&mut testfs1, 66, |record| {
+            let offset = attribute_offset(record, NtfsAttributeType::Data);
+            LittleEndian::write_u32(&mut record[offset..], UNKNOWN_TYPE);
+        });
+
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 66).unwrap();
+        let attribute = file
+            .attributes_raw()
+            .map(Result::unwrap)
+            .find(|attribute| attribute.ty().is_err())
+            .unwrap();
+        assert!(!attribute.is_resident());
+        assert_eq!(attribute.ty_raw(), UNKNOWN_TYPE);
+
+        // The value can still be read.
+        let mut value = attribute.value(&mut testfs1).unwrap();
+        assert_eq!(value.len(), 1000);
+        let mut buf = [0u8; 5];
+        value.read_exact(&mut testfs1, &mut buf).unwrap();
+        assert_eq!(&buf, b"12345");
+    }
+
+    #[test]
+    fn test_for_each_value_chunk() {
+        use sha2::{Digest, Sha256};
+
+        fn collect_chunks<F>(
+            testfs1: &mut std::io::Cursor<Vec<u8>>,
+            file_record_number: u64,
+            buf_size: usize,
+            mut f: F,
+        ) -> Result<()>
+        where
+            F: FnMut(&[u8]) -> Result<()>,
+        {
+            let ntfs = Ntfs::new(testfs1).unwrap();
+            let file = ntfs.file(testfs1, file_record_number).unwrap();
+            let data_item = file.data(testfs1, "").unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+
+            let mut buf = vec![0u8; buf_size];
+            data_attribute.for_each_value_chunk(testfs1, &mut buf, |chunk| {
+                assert!(chunk.len() <= buf_size);
+                f(chunk)
+            })
+        }
+
+        fn sha256(
+            testfs1: &mut std::io::Cursor<Vec<u8>>,
+            file_record_number: u64,
+            buf_size: usize,
+        ) -> String {
+            let mut hasher = Sha256::new();
+            collect_chunks(testfs1, file_record_number, buf_size, |chunk| {
+                hasher.update(chunk);
+                Ok(())
+            })
+            .unwrap();
+
+            format!("{:x}", hasher.finalize())
+        }
+
+        // "1000-bytes-file" (File Record 66) and "sparse-file" (File Record 67), whose allocated sizes exceed
+        // their data sizes.
+        // The expected hashes have been computed independently from the raw bytes of testfs1.
+        const HASH_1000_BYTES_FILE: &str =
+            "e987ddba8f237d56608b83db03b7e80e2ffdf2970cd4498910c8d20dc4d59bb1";
+        const HASH_SPARSE_FILE: &str =
+            "e044906d742cb7611c72106cc5efc09955a4acf71af581a8b795af8823e7ec3b";
+
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        for buf_size in [1, 512, 1000, 4096, 65536] {
+            assert_eq!(sha256(&mut testfs1, 66, buf_size), HASH_1000_BYTES_FILE);
+            assert_eq!(sha256(&mut testfs1, 67, buf_size), HASH_SPARSE_FILE);
+        }
+
+        // An empty buffer is rejected.
+        assert!(matches!(
+            collect_chunks(&mut testfs1, 66, 0, |_| Ok(())),
+            Err(NtfsError::BufferTooSmall { .. })
+        ));
+
+        // Errors returned by the closure stop the iteration.
+        let mut calls = 0;
+        let result = collect_chunks(&mut testfs1, 67, 4096, |_| {
+            calls += 1;
+            Err(NtfsError::InvalidTime)
+        });
+        assert!(matches!(result, Err(NtfsError::InvalidTime)));
+        assert_eq!(calls, 1);
+
+        // Reduce the initialized size of "1000-bytes-file" to 600 bytes.
+        // The remaining 400 bytes must be zeros, even though the clusters still contain the original data.
+        let (record_position, attribute_offset, file_record_size) = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let file = ntfs.file(&mut testfs1, 66).unwrap();
+            let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+            let record_position = file.position().value().unwrap().get() as usize;
+
+            (
+                record_position,
+                data_attribute.position().value().unwrap().get() as usize - record_position,
+                ntfs.file_record_size() as usize,
+            )
+        };
+        crate::helpers::tests::patch_file_record(
+            &mut testfs1,
+            record_position,
+            file_record_size,
+            |record| {
+                let start =
+                    attribute_offset + offset_of!(NtfsNonResidentAttributeHeader, initialized_size);
+                LittleEndian::write_u64(&mut record[start..], 600);
+            },
+        );
+
+        let mut content = Vec::new();
+        collect_chunks(&mut testfs1, 66, 256, |chunk| {
+            content.extend_from_slice(chunk);
+            Ok(())
+        })
+        .unwrap();
+
+        let mut expected = b"12345".repeat(120);
+        expected.resize(1000, 0);
+        assert_eq!(content, expected);
+    }
+
+    #[test]
+    fn test_resident_attribute_in_extension_record() {
+        let mut testfs1 = testfs1_with_attribute_list();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        let base_file_name = file
+            .attributes_raw()
+            .map(Result::unwrap)
+            .find(|attribute| attribute.ty().unwrap() == NtfsAttributeType::FileName)
+            .unwrap()
+            .structured_value::<_, NtfsFileName>(&mut testfs1)
+            .unwrap();
+
+        // The resident $FILE_NAME attribute in record 101 is returned as a regular resident attribute.
+        let mut file_names = Vec::new();
+        let mut iter = file.attributes();
+        while let Some(item) = iter.next(&mut testfs1) {
+            let item = item.unwrap();
+            let attribute = item.to_attribute().unwrap();
+
+            if attribute.ty().unwrap() == NtfsAttributeType::FileName {
+                assert!(attribute.is_resident());
+                assert!(matches!(
+                    attribute.value(&mut testfs1).unwrap(),
+                    NtfsAttributeValue::Resident(_)
+                ));
+
+                let file_name = attribute
+                    .structured_value::<_, NtfsFileName>(&mut testfs1)
+                    .unwrap();
+                assert_eq!(
+                    file_name.parent_directory_reference().file_record_number(),
+                    base_file_name
+                        .parent_directory_reference()
+                        .file_record_number()
+                );
+                file_names.push(file_name.name().to_string_lossy());
+            }
+        }
+        // The Attribute List comes before the $FILE_NAME attribute of the base File Record.
+        assert_eq!(
+            file_names,
+            [
+                EXTENSION_RECORD_NAME.to_string(),
+                base_file_name.name().to_string_lossy()
+            ]
+        );
+
+        // It is also returned by the iterator over all names of the file.
+        let names = file
+            .names()
+            .attach(&mut testfs1)
+            .map(|file_name| file_name.unwrap().name().to_string_lossy())
+            .collect::<Vec<_>>();
+        assert!(names.contains(&EXTENSION_RECORD_NAME.to_string()));
+    }
+
+    #[test]
+    fn test_to_metadata() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "sparse-file")
+                .unwrap()
+                .unwrap();
+        let file = entry.to_file(&ntfs, &mut testfs1).unwrap();
+
+        // The metadata can be collected while the iterator still needs the filesystem.
+        let mut metadata = Vec::new();
+        let mut attributes = file.attributes();
+        while let Some(item) = attributes.next(&mut testfs1) {
+            let item = item.unwrap();
+            metadata.push(item.to_attribute().unwrap().to_metadata().unwrap());
+        }
+
+        // It matches what the accessors return.
+        let attributes = file.attributes_raw().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(metadata.len(), attributes.len());
+
+        for (metadata, attribute) in metadata.iter().zip(&attributes) {
+            assert_eq!(metadata.ty(), attribute.ty().unwrap());
+            assert_eq!(metadata.name(), attribute.name().unwrap());
+            assert_eq!(metadata.instance(), attribute.instance());
+            assert_eq!(metadata.flags(), attribute.flags());
+            assert_eq!(metadata.is_resident(), attribute.is_resident());
+            assert_eq!(metadata.value_length(), attribute.value_length());
+            assert_eq!(metadata.allocated_size(), attribute.allocated_size());
+            assert_eq!(metadata.initialized_size(), attribute.initialized_size());
+            assert_eq!(metadata.position(), attribute.position());
+        }
+
+        // Resident attributes have no Data Runs.
+        let info = &metadata[0];
+        assert_eq!(info.ty(), NtfsAttributeType::StandardInformation);
+        assert!(info.is_resident());
+        assert!(info.data_runs().is_empty());
+
+        // The non-resident $DATA attribute has a sparse Data Run between two Data Runs with data.
+        let data = metadata
+            .iter()
+            .find(|metadata| metadata.ty() == NtfsAttributeType::Data)
+            .unwrap();
+        assert!(!data.is_resident());
+        assert_eq!(data.value_length(), 500005);
+        assert_eq!(data.allocated_size(), 500224);
+
+        let data_attribute = attributes
+            .iter()
+            .find(|attribute| attribute.ty().unwrap() == NtfsAttributeType::Data)
+            .unwrap();
+        let data_runs = data_attribute
+            .non_resident_value()
+            .unwrap()
+            .data_runs()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(data.data_runs(), data_runs.as_slice());
+
+        let data_runs = data
+            .data_runs()
+            .iter()
+            .map(|data_run| (data_run.is_sparse(), data_run.allocated_size()))
+            .collect::<Vec<_>>();
+        assert_eq!(data_runs[..2], [(false, 512), (true, 499200)]);
+        assert!(data_runs[2..].iter().all(|(is_sparse, _)| !is_sparse));
+        assert_eq!(data_runs.iter().map(|(_, size)| size).sum::<u64>(), 500224);
+    }
+
+    #[test]
+    fn test_validate_against_definitions() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let definitions = ntfs.attribute_definitions(&mut testfs1).unwrap();
+
+        // All attributes of the system files and our test files adhere to the definitions.
+        for file_record_number in (0..16).chain(64..69) {
+            let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+            for attribute in file.attributes_raw() {
+                let attribute = attribute.unwrap();
+                attribute
+                    .validate_against_definitions(&definitions)
+                    .unwrap();
+            }
+        }
+
+        let file = ntfs.file(&mut testfs1, 66).unwrap();
+        let standard_information = file
+            .find_resident_attribute(NtfsAttributeType::StandardInformation, None, None)
+            .unwrap();
+        let data = file
+            .find_resident_attribute(NtfsAttributeType::Data, None, None)
+            .unwrap();
+        assert!(!data.is_resident());
+
+        // An attribute type missing from the definitions.
+        let definitions_without_data = definitions
+            .iter()
+            .filter(|definition| definition.ty().unwrap() != NtfsAttributeType::Data)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            data.validate_against_definitions(&definitions_without_data),
+            Err(NtfsError::AttributeTypeNotDefined {
+                ty: NtfsAttributeType::Data,
+                ..
+            })
+        ));
+
+        // Lower the maximum size of $STANDARD_INFORMATION (at offset 152 of its definition) to 40 bytes.
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let position = definitions[0].position().value().unwrap().get() as usize;
+        LittleEndian::write_u64(&mut testfs1.get_mut()[position + 152..], 40);
+        let definitions = ntfs.attribute_definitions(&mut testfs1).unwrap();
+        assert!(matches!(
+            standard_information.validate_against_definitions(&definitions),
+            Err(NtfsError::InvalidAttributeValueLength {
+                ty: NtfsAttributeType::StandardInformation,
+                maximum: 40,
+                actual,
+                ..
+            }) if actual == standard_information.value_length()
+        ));
+
+        // Require $DATA to be resident via the flags (at offset 140 of its definition).
+        let data_definition = definitions
+            .iter()
+            .find(|definition| definition.ty().unwrap() == NtfsAttributeType::Data)
+            .unwrap();
+        let position = data_definition.position().value().unwrap().get() as usize;
+        let flags = LittleEndian::read_u32(&testfs1.get_ref()[position + 140..]);
+        LittleEndian::write_u32(
+            &mut testfs1.get_mut()[position + 140..],
+            flags | NtfsAttributeDefinitionFlags::RESIDENT.bits(),
+        );
+        let definitions = ntfs.attribute_definitions(&mut testfs1).unwrap();
+        assert!(matches!(
+            data.validate_against_definitions(&definitions),
+            Err(NtfsError::AttributeMustBeResident {
+                ty: NtfsAttributeType::Data,
+                ..
+            })
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let json = serde_json::to_string(&NtfsAttributeType::StandardInformation).unwrap();
+        assert_eq!(json, r#""StandardInformation""#);
+        assert_eq!(
+            serde_json::from_str::<NtfsAttributeType>(&json).unwrap(),
+            NtfsAttributeType::StandardInformation
+        );
+
+        let flags = NtfsAttributeFlags::COMPRESSED | NtfsAttributeFlags::SPARSE;
+        let json = serde_json::to_string(&flags).unwrap();
+        assert_eq!(json, r#""COMPRESSED | SPARSE""#);
+        assert_eq!(
+            serde_json::from_str::<NtfsAttributeFlags>(&json).unwrap(),
+            flags
+        );
+
+        let flags = NtfsFileFlags::IN_USE | NtfsFileFlags::IS_DIRECTORY;
+        let json = serde_json::to_string(&flags).unwrap();
+        assert_eq!(json, r#""IN_USE | IS_DIRECTORY""#);
+        assert_eq!(serde_json::from_str::<NtfsFileFlags>(&json).unwrap(), flags);
+    }
 }
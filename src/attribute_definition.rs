@@ -0,0 +1,217 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::fmt;
+
+use crate::io::{Cursor, Read, Seek};
+use alloc::vec::Vec;
+use binrw::{BinRead, BinReaderExt};
+use bitflags::bitflags;
+use nt_string::u16strle::U16StrLe;
+
+use crate::attribute::NtfsAttributeType;
+use crate::error::{NtfsError, Result};
+use crate::file::KnownNtfsFileRecordNumber;
+use crate::ntfs::Ntfs;
+use crate::traits::NtfsReadSeek;
+use crate::types::NtfsPosition;
+
+/// Size of the name field of an [`AttributeDefinitionData`] entry, in bytes (64 UTF-16 characters).
+const ATTRIBUTE_DEFINITION_NAME_SIZE: usize = 128;
+
+/// Size of all [`AttributeDefinitionData`] fields.
+const ATTRIBUTE_DEFINITION_SIZE: usize = 160;
+
+#[derive(BinRead, Clone, Debug)]
+struct AttributeDefinitionData {
+    name: [u8; ATTRIBUTE_DEFINITION_NAME_SIZE],
+    ty: u32,
+    display_rule: u32,
+    collation_rule: u32,
+    flags: u32,
+    minimum_size: u64,
+    maximum_size: u64,
+}
+
+bitflags! {
+    /// Flags returned by [`NtfsAttributeDefinition::flags`].
+    #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    pub struct NtfsAttributeDefinitionFlags: u32 {
+        /// The attribute can be indexed.
+        const INDEXABLE = 0x0002;
+        /// A file may have multiple attributes of this type.
+        const MULTIPLE = 0x0004;
+        /// The attribute value must contain at least one non-zero byte.
+        const NOT_ZERO = 0x0008;
+        /// The attribute must be indexed and its value must be unique.
+        const INDEXED_UNIQUE = 0x0010;
+        /// The attribute must be named and its name must be unique.
+        const NAMED_UNIQUE = 0x0020;
+        /// The attribute must always be resident.
+        const RESIDENT = 0x0040;
+        /// Changes to the attribute are always logged, even if it is non-resident.
+        const ALWAYS_LOG = 0x0080;
+    }
+}
+
+impl fmt::Display for NtfsAttributeDefinitionFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// A single entry of the $AttrDef file, which defines an attribute type supported by the filesystem.
+///
+/// You can get all entries via [`Ntfs::attribute_definitions`] and check an attribute against them
+/// via [`NtfsAttribute::validate_against_definitions`].
+///
+/// [`NtfsAttribute::validate_against_definitions`]: crate::NtfsAttribute::validate_against_definitions
+///
+/// Reference: <https://flatcap.github.io/linux-ntfs/ntfs/files/attrdef.html>
+#[derive(Clone, Debug)]
+pub struct NtfsAttributeDefinition {
+    data: AttributeDefinitionData,
+    position: NtfsPosition,
+}
+
+impl NtfsAttributeDefinition {
+    /// Reads all entries of the $AttrDef file.
+    /// The list ends at the first entry with a type code of zero or at the end of the file.
+    pub(crate) fn read_all<T>(ntfs: &Ntfs, fs: &mut T) -> Result<Vec<Self>>
+    where
+        T: Read + Seek,
+    {
+        let attr_def_file = ntfs.file(fs, KnownNtfsFileRecordNumber::AttrDef as u64)?;
+        let data_item = attr_def_file
+            .data(fs, "")
+            .ok_or(NtfsError::AttributeNotFound {
+                position: attr_def_file.position(),
+                ty: NtfsAttributeType::Data,
+            })??;
+        let data_attribute = data_item.to_attribute()?;
+        let mut value = data_attribute.value(fs)?;
+
+        let mut definitions = Vec::new();
+        let mut buf = [0u8; ATTRIBUTE_DEFINITION_SIZE];
+
+        while value.len() - value.stream_position() >= ATTRIBUTE_DEFINITION_SIZE as u64 {
+            let position = value.data_position();
+            value.read_exact(fs, &mut buf)?;
+
+            let mut cursor = Cursor::new(&buf[..]);
+            let data = cursor.read_le::<AttributeDefinitionData>()?;
+            if data.ty == 0 {
+                break;
+            }
+
+            definitions.push(Self { data, position });
+        }
+
+        Ok(definitions)
+    }
+
+    /// Returns the collation rule used for indexing attributes of this type.
+    pub fn collation_rule(&self) -> u32 {
+        self.data.collation_rule
+    }
+
+    /// Returns the default display rule of this attribute type (unused by Windows and usually zero).
+    pub fn display_rule(&self) -> u32 {
+        self.data.display_rule
+    }
+
+    /// Returns flags describing how attributes of this type may be used.
+    pub fn flags(&self) -> NtfsAttributeDefinitionFlags {
+        NtfsAttributeDefinitionFlags::from_bits_truncate(self.data.flags)
+    }
+
+    /// Returns the maximum length of an attribute value of this type, in bytes.
+    ///
+    /// `u64::MAX` means that the length is unlimited.
+    pub fn maximum_size(&self) -> u64 {
+        self.data.maximum_size
+    }
+
+    /// Returns the minimum length of an attribute value of this type, in bytes.
+    pub fn minimum_size(&self) -> u64 {
+        self.data.minimum_size
+    }
+
+    /// Returns the name of this attribute type (like "$STANDARD_INFORMATION").
+    pub fn name(&self) -> U16StrLe<'_> {
+        let name_length = self
+            .data
+            .name
+            .chunks_exact(2)
+            .position(|character| character == [0, 0])
+            .unwrap_or(ATTRIBUTE_DEFINITION_NAME_SIZE / 2);
+
+        U16StrLe(&self.data.name[..name_length * 2])
+    }
+
+    /// Returns the absolute position of this entry within the filesystem, in bytes.
+    pub fn position(&self) -> NtfsPosition {
+        self.position
+    }
+
+    /// Returns the attribute type defined by this entry, or [`NtfsError::UnsupportedAttributeType`]
+    /// if it's an unknown type.
+    pub fn ty(&self) -> Result<NtfsAttributeType> {
+        NtfsAttributeType::n(self.data.ty).ok_or(NtfsError::UnsupportedAttributeType {
+            position: self.position,
+            actual: self.data.ty,
+        })
+    }
+
+    /// Returns the raw type code of the attribute type defined by this entry.
+    pub fn type_code(&self) -> u32 {
+        self.data.ty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribute_definitions() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let definitions = ntfs.attribute_definitions(&mut testfs1).unwrap();
+
+        // mkntfs writes the same 15 attribute definitions as Windows XP and later (without $PROPERTY_SET).
+        assert_eq!(definitions.len(), 15);
+        assert!(definitions.iter().all(|definition| definition.ty().is_ok()));
+
+        let standard_information = &definitions[0];
+        assert_eq!(
+            standard_information.ty().unwrap(),
+            NtfsAttributeType::StandardInformation
+        );
+        assert_eq!(standard_information.name(), "$STANDARD_INFORMATION");
+        assert_eq!(standard_information.minimum_size(), 48);
+        assert_eq!(standard_information.maximum_size(), 72);
+        assert!(standard_information
+            .flags()
+            .contains(NtfsAttributeDefinitionFlags::RESIDENT));
+
+        let file_name = definitions
+            .iter()
+            .find(|definition| definition.ty().unwrap() == NtfsAttributeType::FileName)
+            .unwrap();
+        assert_eq!(file_name.name(), "$FILE_NAME");
+        assert_eq!(file_name.minimum_size(), 68);
+        assert_eq!(file_name.maximum_size(), 578);
+        assert!(file_name.flags().contains(
+            NtfsAttributeDefinitionFlags::INDEXABLE | NtfsAttributeDefinitionFlags::RESIDENT
+        ));
+
+        let data = definitions
+            .iter()
+            .find(|definition| definition.ty().unwrap() == NtfsAttributeType::Data)
+            .unwrap();
+        assert_eq!(data.name(), "$DATA");
+        assert_eq!(data.minimum_size(), 0);
+        assert_eq!(data.maximum_size(), u64::MAX);
+    }
+}
@@ -6,10 +6,16 @@
 // Connected attributes are stored in a way that the first attribute reports the entire data size and all further attributes report a zero value length.
 // We have to go down to the Data Run level to get trustable lengths again, and this is what `NtfsAttributeListNonResidentAttributeValue` does here.
 
-use binrw::io::{Read, Seek, SeekFrom};
+use core::ops::Range;
 
-use super::{DataRunsState, NtfsDataRuns, StreamState};
-use crate::attribute::{NtfsAttribute, NtfsAttributeType};
+use crate::io::{Read, Seek, SeekFrom};
+use alloc::vec::Vec;
+
+use super::{
+    DataRunsState, NtfsDataRuns, NtfsPhysicalRange, NtfsReadSegment, PhysicalRangeCollector,
+    StreamState,
+};
+use crate::attribute::NtfsAttribute;
 use crate::error::{NtfsError, Result};
 use crate::file::NtfsFile;
 use crate::ntfs::Ntfs;
@@ -32,10 +38,18 @@ pub struct NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
     connected_entries: AttributeListConnectedEntries<'n, 'f>,
     /// Total length of the value data, in bytes.
     data_size: u64,
+    /// Total allocated size of the value, in bytes.
+    allocated_size: u64,
     /// File, location, and data runs iteration state of the current attribute.
     attribute_state: Option<AttributeState<'n>>,
     /// Iteration state of the current Data Run.
     stream_state: StreamState,
+    /// File Record Number of the file this value belongs to, reported in [`NtfsError::ValueReadContext`].
+    file_record_number: u64,
+    /// Raw type of the attribute this value belongs to, reported in [`NtfsError::ValueReadContext`].
+    ///
+    /// This is kept raw, because values of unknown attribute types can be read as well.
+    ty: u32,
 }
 
 impl<'n, 'f> NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
@@ -43,15 +57,15 @@ impl<'n, 'f> NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
         ntfs: &'n Ntfs,
         fs: &mut T,
         attribute_list_entries: NtfsAttributeListEntries<'n, 'f>,
-        instance: u16,
-        ty: NtfsAttributeType,
         data_size: u64,
+        allocated_size: u64,
+        file_record_number: u64,
+        ty: u32,
     ) -> Result<Self>
     where
         T: Read + Seek,
     {
-        let connected_entries =
-            AttributeListConnectedEntries::new(attribute_list_entries.clone(), instance, ty);
+        let connected_entries = AttributeListConnectedEntries::new(attribute_list_entries.clone());
         let stream_state = StreamState::new(data_size);
 
         let mut value = Self {
@@ -59,22 +73,121 @@ impl<'n, 'f> NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
             initial_attribute_list_entries: attribute_list_entries,
             connected_entries,
             data_size,
+            allocated_size,
             attribute_state: None,
             stream_state,
+            file_record_number,
+            ty,
         };
         value.next_attribute(fs)?;
 
         Ok(value)
     }
 
+    /// Returns the locations on the filesystem that store the bytes in `logical_range` of this value.
+    ///
+    /// This reads the File Records of the connected attributes until `logical_range` is covered.
+    /// See [`NtfsAttributeValue::byte_ranges`].
+    ///
+    /// [`NtfsAttributeValue::byte_ranges`]: crate::attribute_value::NtfsAttributeValue::byte_ranges
+    pub fn byte_ranges<T>(
+        &self,
+        fs: &mut T,
+        logical_range: Range<u64>,
+    ) -> Result<Vec<NtfsPhysicalRange>>
+    where
+        T: Read + Seek,
+    {
+        let mut collector = PhysicalRangeCollector::new(logical_range, self.data_size);
+        let mut connected_entries =
+            AttributeListConnectedEntries::new(self.initial_attribute_list_entries.clone());
+
+        while collector.is_incomplete() {
+            let entry = match connected_entries.next(fs) {
+                Some(entry) => entry?,
+                None => break,
+            };
+            let file = entry.to_file(self.ntfs, fs)?;
+            let attribute = entry.to_attribute(&file)?;
+            if attribute.is_resident() {
+                return Err(NtfsError::UnexpectedResidentAttribute {
+                    position: attribute.position(),
+                });
+            }
+
+            for data_run in attribute.non_resident_value()?.data_runs() {
+                let data_run = data_run?;
+
+                if !collector.add_data_run(&data_run) {
+                    break;
+                }
+            }
+        }
+
+        Ok(collector.into_ranges())
+    }
+
     /// Returns the absolute current data seek position within the filesystem, in bytes.
     /// This may be `None` if:
     ///   * The current seek position is outside the valid range, or
     ///   * The current Data Run is a "sparse" Data Run.
+    ///
+    /// If the current Data Run has been read or seeked up to its end, this is the start position of the
+    /// next Data Run of the current attribute, where the next read continues.
+    /// At the end of the last Data Run of a connected attribute, this is still the position one byte past
+    /// that Data Run, because the next connected attribute is only read from the filesystem by the next read or seek.
     pub fn data_position(&self) -> NtfsPosition {
         self.stream_state.data_position()
     }
 
+    /// Returns the number of bytes from the current seek position up to the end of the Data Run they are
+    /// stored in.
+    ///
+    /// This many bytes can be read from consecutive positions on the filesystem.
+    /// At the boundary between two connected attributes, this returns zero.
+    pub(crate) fn contiguous_len(&self) -> u64 {
+        self.stream_state.data_run_remaining_len()
+    }
+
+    /// Returns whether the Data Runs of all connected attributes are consistent with their attribute headers.
+    ///
+    /// This reads the File Records of all connected attributes and checks each of them like
+    /// [`NtfsNonResidentAttributeValue::is_consistent`].
+    /// Additionally, the VCN ranges of the connected attributes must directly follow each other, and
+    /// their Data Runs must add up to the allocated size of the value.
+    /// An inconsistent value is usually caused by a truncated or corrupted Data Run list.
+    /// Reading it returns less data than [`Self::len`] suggests.
+    ///
+    /// [`NtfsNonResidentAttributeValue::is_consistent`]: crate::attribute_value::NtfsNonResidentAttributeValue::is_consistent
+    pub fn is_consistent<T>(&self, fs: &mut T) -> Result<bool>
+    where
+        T: Read + Seek,
+    {
+        let cluster_size = self.ntfs.cluster_size() as u64;
+        let mut connected_entries =
+            AttributeListConnectedEntries::new(self.initial_attribute_list_entries.clone());
+        let mut next_vcn = 0i64;
+
+        while let Some(entry) = connected_entries.next(fs) {
+            let entry = entry?;
+            let file = entry.to_file(self.ntfs, fs)?;
+            let attribute = entry.to_attribute(&file)?;
+            if attribute.is_resident() {
+                return Ok(false);
+            }
+
+            let value = attribute.non_resident_value()?;
+            if !value.is_consistent() || value.lowest_vcn().value() != next_vcn {
+                return Ok(false);
+            }
+
+            next_vcn = value.highest_vcn().value().saturating_add(1);
+        }
+
+        let run_size = (next_vcn as u64).saturating_mul(cluster_size);
+        Ok(run_size == self.allocated_size && self.data_size <= self.allocated_size)
+    }
+
     /// Returns `true` if the non-resident attribute value contains no data.
     pub fn is_empty(&self) -> bool {
         self.len() == 0
@@ -94,7 +207,9 @@ impl<'n, 'f> NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
         };
 
         // Get the state of the `NtfsDataRuns` iterator of that attribute.
-        let data_runs_state = match attribute_state.data_runs_state.take() {
+        // It is only updated once we got another Data Run, so that a Data Run that fails to decode is
+        // decoded (and reported) again by the next call.
+        let data_runs_state = match attribute_state.data_runs_state.clone() {
             Some(data_runs_state) => data_runs_state,
             None => return Ok(false),
         };
@@ -112,18 +227,34 @@ impl<'n, 'f> NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
         // Do we have a next Data Run? Save that.
         let stream_data_run = match stream_data_runs.next() {
             Some(stream_data_run) => stream_data_run,
-            None => return Ok(false),
+            None => {
+                // We have fully iterated this iterator (and hence also the attribute and file).
+                attribute_state.data_runs_state = None;
+                return Ok(false);
+            }
         };
         let stream_data_run = stream_data_run?;
         self.stream_state.set_stream_data_run(Some(stream_data_run));
 
         // We got another Data Run, so serialize the updated `NtfsDataRuns` state for the next iteration.
-        // This step is skipped when we got no Data Run, because it means we have fully iterated this iterator (and hence also the attribute and file).
         attribute_state.data_runs_state = Some(stream_data_runs.into_state());
 
         Ok(true)
     }
 
+    /// Moves past the current Data Run if it has been read or seeked up to its end and the current attribute
+    /// has another Data Run.
+    ///
+    /// This lets [`Self::data_position`] return the start position of the next Data Run, where the next read continues.
+    /// The next connected attribute is only read from the filesystem by the next read or seek.
+    fn next_data_run_if_exhausted(&mut self) -> Result<()> {
+        if self.stream_state.is_data_run_exhausted() {
+            self.next_data_run()?;
+        }
+
+        Ok(())
+    }
+
     /// Advances to the next attribute and returns whether we got another connected attribute.
     fn next_attribute<T>(&mut self, fs: &mut T) -> Result<bool>
     where
@@ -176,22 +307,49 @@ impl<'n, 'f> NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
         self.ntfs
     }
 
-    /// Rewinds this value reader to the very beginning.
-    fn rewind<T>(&mut self, fs: &mut T) -> Result<()>
+    /// Reads the next segment of the value, which is either data or a sparse hole.
+    ///
+    /// See [`NtfsAttributeValue::read_segment`] for details.
+    ///
+    /// [`NtfsAttributeValue::read_segment`]: crate::attribute_value::NtfsAttributeValue::read_segment
+    pub fn read_segment<T>(&mut self, fs: &mut T, buf: &mut [u8]) -> Result<NtfsReadSegment>
     where
         T: Read + Seek,
     {
-        self.connected_entries.attribute_list_entries =
-            Some(self.initial_attribute_list_entries.clone());
-        self.stream_state = StreamState::new(self.len());
-        self.next_attribute(fs)?;
+        self.read_data_run_segments(fs, buf)
+            .map_err(|e| e.with_value_context(self.file_record_number, self.ty))
+    }
 
-        Ok(())
+    /// Reads the next segment from the Data Runs (see [`Self::read_segment`]).
+    fn read_data_run_segments<T>(&mut self, fs: &mut T, buf: &mut [u8]) -> Result<NtfsReadSegment>
+    where
+        T: Read + Seek,
+    {
+        loop {
+            // Read from the current Data Run if there is one.
+            if let Some(segment) = self.stream_state.read_data_run_segment(fs, buf)? {
+                // The segment has been read successfully, so an undecodable next Data Run is reported
+                // by the next read.
+                let _ = self.next_data_run_if_exhausted();
+                return Ok(segment);
+            }
+
+            // Move to the next Data Run of the current attribute.
+            if self.next_data_run()? {
+                continue;
+            }
+
+            // Move to the first Data Run of the next connected attribute.
+            if !self.next_attribute(fs)? {
+                // We read everything we could.
+                return Ok(NtfsReadSegment::Data(0));
+            }
+        }
     }
-}
 
-impl<'n, 'f> NtfsReadSeek for NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
-    fn read<T>(&mut self, fs: &mut T, buf: &mut [u8]) -> Result<usize>
+    /// Reads from the Data Runs into `buf` without restoring the previous position on error
+    /// (see [`NtfsReadSeek::read`]).
+    fn read_data_runs<T>(&mut self, fs: &mut T, buf: &mut [u8]) -> Result<usize>
     where
         T: Read + Seek,
     {
@@ -199,9 +357,19 @@ impl<'n, 'f> NtfsReadSeek for NtfsAttributeListNonResidentAttributeValue<'n, 'f>
 
         while bytes_read < buf.len() {
             // Read from the current Data Run if there is one.
-            if self.stream_state.read_data_run(fs, buf, &mut bytes_read)? {
-                // We read something, so check the loop condition again if we need to read more.
-                continue;
+            match self.stream_state.read_data_run(fs, buf, &mut bytes_read) {
+                Ok(true) => {
+                    // We read something, so check the loop condition again if we need to read more.
+                    continue;
+                }
+                Ok(false) => (),
+                Err(NtfsError::BadClusterRead { .. } | NtfsError::BeyondBackingStorage { .. })
+                    if bytes_read > 0 =>
+                {
+                    // Return what we have read so far and report the error on the next read.
+                    break;
+                }
+                Err(e) => return Err(e),
             }
 
             // Move to the next Data Run of the current attribute.
@@ -220,10 +388,14 @@ impl<'n, 'f> NtfsReadSeek for NtfsAttributeListNonResidentAttributeValue<'n, 'f>
             }
         }
 
+        // The data has been read successfully, so an undecodable next Data Run is reported by the next read.
+        let _ = self.next_data_run_if_exhausted();
+
         Ok(bytes_read)
     }
 
-    fn seek<T>(&mut self, fs: &mut T, pos: SeekFrom) -> Result<u64>
+    /// Seeks within the Data Runs of all connected attributes (see [`NtfsReadSeek::seek`]).
+    fn seek_data_runs<T>(&mut self, fs: &mut T, pos: SeekFrom) -> Result<u64>
     where
         T: Read + Seek,
     {
@@ -273,31 +445,77 @@ impl<'n, 'f> NtfsReadSeek for NtfsAttributeListNonResidentAttributeValue<'n, 'f>
             _ => unreachable!(),
         }
 
+        // A seek exactly to the end of a Data Run continues at the start of the next one.
+        self.next_data_run_if_exhausted()?;
+
         Ok(self.stream_position())
     }
 
+    /// Rewinds this value reader to the very beginning.
+    fn rewind<T>(&mut self, fs: &mut T) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        self.connected_entries.attribute_list_entries =
+            Some(self.initial_attribute_list_entries.clone());
+        self.stream_state = StreamState::new(self.len());
+        self.next_attribute(fs)?;
+
+        Ok(())
+    }
+}
+
+impl<'n, 'f> NtfsReadSeek for NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
+    fn read<T>(&mut self, fs: &mut T, buf: &mut [u8]) -> Result<usize>
+    where
+        T: Read + Seek,
+    {
+        // A failed read must not move the stream position, so that it can be retried.
+        // Cloning the state of the current attribute would copy its entire File Record on every read,
+        // so we only remember the position and seek back to it in the rare case of an error.
+        let stream_position = self.stream_position();
+
+        let result = self.read_data_runs(fs, buf);
+        if result.is_err() {
+            // Rewinding first discards any state the failed read may have left behind.
+            // The original error is reported, even if seeking back fails as well.
+            let _ = self
+                .rewind(fs)
+                .and_then(|()| self.seek_data_runs(fs, SeekFrom::Start(stream_position)));
+        }
+
+        result.map_err(|e| e.with_value_context(self.file_record_number, self.ty))
+    }
+
+    fn seek<T>(&mut self, fs: &mut T, pos: SeekFrom) -> Result<u64>
+    where
+        T: Read + Seek,
+    {
+        self.seek_data_runs(fs, pos)
+            .map_err(|e| e.with_value_context(self.file_record_number, self.ty))
+    }
+
     fn stream_position(&self) -> u64 {
         self.stream_state.stream_position()
     }
 }
 
+/// Iterator over all fragments of a connected attribute.
+///
+/// It expects an `NtfsAttributeListEntries` iterator whose next entry is the first fragment.
+/// All following entries that continue the connected attribute (see `NtfsAttributeListEntry::is_connected_to`)
+/// are returned as well.
 #[derive(Clone, Debug)]
 struct AttributeListConnectedEntries<'n, 'f> {
     attribute_list_entries: Option<NtfsAttributeListEntries<'n, 'f>>,
-    instance: u16,
-    ty: NtfsAttributeType,
+    previous_entry: Option<NtfsAttributeListEntry>,
 }
 
 impl<'n, 'f> AttributeListConnectedEntries<'n, 'f> {
-    fn new(
-        attribute_list_entries: NtfsAttributeListEntries<'n, 'f>,
-        instance: u16,
-        ty: NtfsAttributeType,
-    ) -> Self {
+    fn new(attribute_list_entries: NtfsAttributeListEntries<'n, 'f>) -> Self {
         Self {
             attribute_list_entries: Some(attribute_list_entries),
-            instance,
-            ty,
+            previous_entry: None,
         }
     }
 
@@ -308,7 +526,13 @@ impl<'n, 'f> AttributeListConnectedEntries<'n, 'f> {
         let attribute_list_entries = self.attribute_list_entries.as_mut()?;
 
         let entry = iter_try!(attribute_list_entries.next(fs)?);
-        if entry.instance() == self.instance && iter_try!(entry.ty()) == self.ty {
+        let is_connected = match &self.previous_entry {
+            Some(previous_entry) => entry.is_connected_to(previous_entry),
+            None => true,
+        };
+
+        if is_connected {
+            self.previous_entry = Some(entry.clone());
             Some(Ok(entry))
         } else {
             self.attribute_list_entries = None;
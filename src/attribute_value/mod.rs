@@ -11,10 +11,16 @@ pub use attribute_list_non_resident::*;
 pub use non_resident::*;
 pub use resident::*;
 
-use binrw::io;
-use binrw::io::{Read, Seek, SeekFrom};
+use core::ops::Range;
 
+use crate::io;
+use crate::io::{Read, Seek, SeekFrom};
+use alloc::vec::Vec;
+
+#[cfg(feature = "async")]
+use crate::async_io::{AsyncBlockCache, AsyncNtfsReadSeek};
 use crate::error::{NtfsError, Result};
+use crate::ntfs::Ntfs;
 use crate::traits::NtfsReadSeek;
 use crate::types::NtfsPosition;
 
@@ -42,6 +48,57 @@ impl<'n, 'f> NtfsAttributeValue<'n, 'f> {
         NtfsAttributeValueAttached::new(fs, self)
     }
 
+    /// Async variant of [`NtfsReadSeek::read`] for readers implementing [`AsyncNtfsReadSeek`].
+    ///
+    /// The stream position of this value is only advanced once the read has succeeded.
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn read_async<T>(&mut self, fs: &mut T, buf: &mut [u8]) -> Result<usize>
+    where
+        T: AsyncNtfsReadSeek,
+    {
+        let mut cache = AsyncBlockCache::new(fs).await?;
+
+        // Every attempt starts from the original state, because a failed one may have partially advanced it.
+        let this = &*self;
+        let (value, bytes_read) = cache
+            .run(|fs| {
+                let mut value = this.clone();
+                let bytes_read = value.read(fs, buf)?;
+                Ok((value, bytes_read))
+            })
+            .await?;
+
+        *self = value;
+        Ok(bytes_read)
+    }
+
+    /// Returns the locations on the filesystem that store the bytes in `logical_range` of this value,
+    /// sorted by their offset within the value.
+    ///
+    /// `logical_range` is clipped to the length of the value.
+    /// Each Data Run overlapping it results in an [`NtfsPhysicalRange`], with sparse Data Runs being
+    /// returned as [`NtfsPhysicalRange::Hole`].
+    /// A resident value results in a single [`NtfsPhysicalRange::Data`] within its File Record.
+    ///
+    /// The Data Runs are walked only once, which is much faster than seeking to every offset of interest
+    /// and querying [`NtfsAttributeValue::data_position`].
+    /// The stream position of this value is not changed.
+    pub fn byte_ranges<T>(
+        &self,
+        fs: &mut T,
+        logical_range: Range<u64>,
+    ) -> Result<Vec<NtfsPhysicalRange>>
+    where
+        T: Read + Seek,
+    {
+        match self {
+            Self::Resident(inner) => Ok(inner.byte_ranges(logical_range)),
+            Self::NonResident(inner) => inner.byte_ranges(logical_range),
+            Self::AttributeListNonResident(inner) => inner.byte_ranges(fs, logical_range),
+        }
+    }
+
     /// Returns the absolute current data seek position within the filesystem, in bytes.
     /// This may be `None` if:
     ///   * The current seek position is outside the valid range, or
@@ -55,6 +112,93 @@ impl<'n, 'f> NtfsAttributeValue<'n, 'f> {
         }
     }
 
+    /// Returns the number of bytes from the current seek position that are stored at consecutive positions
+    /// on the filesystem.
+    ///
+    /// If a read of this many bytes starts at [`NtfsAttributeValue::data_position`], all of them are located
+    /// right after each other.
+    pub(crate) fn contiguous_len(&self) -> u64 {
+        match self {
+            Self::Resident(inner) => inner.remaining_len(),
+            Self::NonResident(inner) => inner.contiguous_len(),
+            Self::AttributeListNonResident(inner) => inner.contiguous_len(),
+        }
+    }
+
+    /// Returns the absolute byte position within the filesystem of the byte at `offset` of this value.
+    ///
+    /// Contrary to [`NtfsAttributeValue::data_position`], this works for any offset and doesn't change the
+    /// stream position of this value.
+    /// This makes it possible to locate every part of a read that spans multiple Data Runs or even multiple
+    /// connected attributes (see [`NtfsAttributeListNonResidentAttributeValue`]).
+    ///
+    /// The returned position is `None` if `offset` lies in a sparse Data Run or beyond the length of the value.
+    pub fn map_position<T>(&self, fs: &mut T, offset: u64) -> Result<NtfsPosition>
+    where
+        T: Read + Seek,
+    {
+        let end = offset.saturating_add(1);
+        let position = match self.byte_ranges(fs, offset..end)?.first() {
+            Some(NtfsPhysicalRange::Data {
+                logical_offset,
+                position,
+                ..
+            }) => *position + (offset - logical_offset),
+            _ => NtfsPosition::none(),
+        };
+
+        Ok(position)
+    }
+
+    /// Returns a variant of this reader that implements [`Read`] and [`Seek`]
+    /// by taking ownership of the filesystem reader.
+    ///
+    /// Contrary to [`NtfsAttributeValue::attach`], the returned reader doesn't borrow the filesystem reader.
+    /// This allows you to store it in a struct or to have multiple readers at the same time
+    /// (each with its own filesystem reader, e.g. a `&File` or a cloned file handle).
+    /// Call [`NtfsOwnedValueReader::into_inner`] to get the filesystem reader back.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use std::io;
+    /// # use ntfs::indexes::NtfsFileNameIndex;
+    /// # use ntfs::Ntfs;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut fs = File::open("testdata/testfs1")?;
+    /// let mut ntfs = Ntfs::new(&mut fs)?;
+    /// ntfs.read_upcase_table(&mut fs)?;
+    ///
+    /// let root_dir = ntfs.root_directory(&mut fs)?;
+    /// let index = root_dir.directory_index(&mut fs)?;
+    /// let mut finder = index.finder();
+    /// let entry = NtfsFileNameIndex::find(&mut finder, &ntfs, &mut fs, "file-with-12345").unwrap()?;
+    /// let file = entry.to_file(&ntfs, &mut fs)?;
+    ///
+    /// let data_item = file.data(&mut fs, "").unwrap()?;
+    /// let data_attribute = data_item.to_attribute()?;
+    /// let data_value = data_attribute.value(&mut fs)?;
+    ///
+    /// // The reader takes ownership of `fs`, so it can be passed around freely.
+    /// let mut reader = data_value.into_reader(fs);
+    /// let mut output = Vec::new();
+    /// io::copy(&mut reader, &mut output)?;
+    /// assert_eq!(output, b"12345");
+    ///
+    /// // Get the filesystem reader back for further operations.
+    /// let mut fs = reader.into_inner();
+    /// # let _ = ntfs.root_directory(&mut fs)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_reader<T>(self, fs: T) -> NtfsOwnedValueReader<'n, 'f, T>
+    where
+        T: Read + Seek,
+    {
+        NtfsOwnedValueReader::new(fs, self)
+    }
+
     /// Returns `true` if the attribute value contains no data.
     pub fn is_empty(&self) -> bool {
         self.len() == 0
@@ -68,6 +212,147 @@ impl<'n, 'f> NtfsAttributeValue<'n, 'f> {
             Self::AttributeListNonResident(inner) => inner.len(),
         }
     }
+
+    /// Reads the next segment of the value, which is either data or a sparse hole.
+    ///
+    /// Contrary to [`NtfsReadSeek::read`], this doesn't fill sparse Data Runs with zeros.
+    /// Instead, it returns [`NtfsReadSegment::Hole`] with the length of the hole and leaves `buf` untouched.
+    /// This allows backup tools to preserve the sparseness of a file.
+    /// The stream position is advanced in both cases.
+    ///
+    /// Data is read from a single Data Run at a time, so [`NtfsReadSegment::Data`] may report fewer bytes than
+    /// the buffer could hold.
+    /// Holes are cluster-aligned, because sparse Data Runs consist of entire clusters.
+    /// `NtfsReadSegment::Data(0)` is returned at the end of the value (or for an empty buffer).
+    pub fn read_segment<T>(&mut self, fs: &mut T, buf: &mut [u8]) -> Result<NtfsReadSegment>
+    where
+        T: Read + Seek,
+    {
+        match self {
+            Self::Resident(inner) => inner.read(fs, buf).map(NtfsReadSegment::Data),
+            Self::NonResident(inner) => inner.read_segment(fs, buf),
+            Self::AttributeListNonResident(inner) => inner.read_segment(fs, buf),
+        }
+    }
+
+    /// Returns a recommended buffer size for reading this value, in bytes.
+    ///
+    /// For non-resident values, this is the cluster size of the filesystem.
+    /// Buffers of a multiple of this size make every read start at a cluster boundary.
+    /// For resident values, this is the length of the value, which can be read at once.
+    pub fn recommended_read_size(&self, ntfs: &Ntfs) -> usize {
+        match self {
+            Self::Resident(inner) => inner.len() as usize,
+            Self::NonResident(_) | Self::AttributeListNonResident(_) => {
+                ntfs.cluster_size() as usize
+            }
+        }
+    }
+}
+
+/// A contiguous range of an attribute value and its location on the filesystem,
+/// as returned by [`NtfsAttributeValue::byte_ranges`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NtfsPhysicalRange {
+    /// `length` bytes at `logical_offset` within the value are stored at the absolute byte position
+    /// `position` of the filesystem.
+    Data {
+        logical_offset: u64,
+        position: NtfsPosition,
+        length: u64,
+    },
+    /// `length` bytes at `logical_offset` within the value are a sparse hole, which reads as zeros
+    /// and is not stored on the filesystem.
+    Hole { logical_offset: u64, length: u64 },
+}
+
+impl NtfsPhysicalRange {
+    /// Returns the byte range of the value covered by this range.
+    pub fn logical_range(&self) -> Range<u64> {
+        match self {
+            Self::Data {
+                logical_offset,
+                length,
+                ..
+            }
+            | Self::Hole {
+                logical_offset,
+                length,
+            } => *logical_offset..*logical_offset + *length,
+        }
+    }
+}
+
+/// Collects the [`NtfsPhysicalRange`]s of consecutive Data Runs that overlap a byte range of a value.
+pub(crate) struct PhysicalRangeCollector {
+    /// Byte range of the value to collect, already clipped to the length of the value.
+    logical_range: Range<u64>,
+    /// Offset of the next Data Run within the value, in bytes.
+    offset: u64,
+    ranges: Vec<NtfsPhysicalRange>,
+}
+
+impl PhysicalRangeCollector {
+    pub(crate) fn new(logical_range: Range<u64>, data_size: u64) -> Self {
+        let end = u64::min(logical_range.end, data_size);
+
+        Self {
+            logical_range: logical_range.start..end,
+            offset: 0,
+            ranges: Vec::new(),
+        }
+    }
+
+    /// Adds the part of `data_run` that overlaps the byte range to collect.
+    ///
+    /// Returns `false` when all following Data Runs lie beyond that range.
+    pub(crate) fn add_data_run(&mut self, data_run: &NtfsDataRun) -> bool {
+        let data_run_start = self.offset;
+        let data_run_end = data_run_start.saturating_add(data_run.allocated_size());
+        self.offset = data_run_end;
+
+        let start = u64::max(data_run_start, self.logical_range.start);
+        let end = u64::min(data_run_end, self.logical_range.end);
+
+        if start < end {
+            let logical_offset = start;
+            let length = end - start;
+
+            let range = if data_run.is_sparse() {
+                NtfsPhysicalRange::Hole {
+                    logical_offset,
+                    length,
+                }
+            } else {
+                NtfsPhysicalRange::Data {
+                    logical_offset,
+                    position: data_run.data_position() + (start - data_run_start),
+                    length,
+                }
+            };
+            self.ranges.push(range);
+        }
+
+        self.is_incomplete()
+    }
+
+    pub(crate) fn into_ranges(self) -> Vec<NtfsPhysicalRange> {
+        self.ranges
+    }
+
+    /// Returns whether further Data Runs may still overlap the byte range to collect.
+    pub(crate) fn is_incomplete(&self) -> bool {
+        self.offset < self.logical_range.end
+    }
+}
+
+/// A segment of an attribute value, as returned by [`NtfsAttributeValue::read_segment`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NtfsReadSegment {
+    /// The given number of bytes have been read into the buffer.
+    Data(usize),
+    /// The given number of bytes are a sparse hole (reading as zeros) and have been skipped.
+    Hole(u64),
 }
 
 impl<'n, 'f> NtfsReadSeek for NtfsAttributeValue<'n, 'f> {
@@ -161,6 +446,72 @@ where
     }
 }
 
+/// A variant of [`NtfsAttributeValue`] that implements [`Read`] and [`Seek`]
+/// by taking ownership of the filesystem reader.
+///
+/// This reader is returned from the [`NtfsAttributeValue::into_reader`] function.
+#[derive(Debug)]
+pub struct NtfsOwnedValueReader<'n, 'f, T: Read + Seek> {
+    fs: T,
+    value: NtfsAttributeValue<'n, 'f>,
+}
+
+impl<'n, 'f, T> NtfsOwnedValueReader<'n, 'f, T>
+where
+    T: Read + Seek,
+{
+    fn new(fs: T, value: NtfsAttributeValue<'n, 'f>) -> Self {
+        Self { fs, value }
+    }
+
+    /// Returns the absolute current data seek position within the filesystem, in bytes.
+    /// This may be `None` if:
+    ///   * The current seek position is outside the valid range, or
+    ///   * The attribute does not have a Data Run, or
+    ///   * The current Data Run is a "sparse" Data Run.
+    pub fn data_position(&self) -> NtfsPosition {
+        self.value.data_position()
+    }
+
+    /// Consumes this reader and returns the filesystem reader.
+    pub fn into_inner(self) -> T {
+        self.fs
+    }
+
+    /// Consumes this reader and returns the inner [`NtfsAttributeValue`] along with the filesystem reader.
+    pub fn into_parts(self) -> (NtfsAttributeValue<'n, 'f>, T) {
+        (self.value, self.fs)
+    }
+
+    /// Returns `true` if the attribute value contains no data.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the total length of the attribute value, in bytes.
+    pub fn len(&self) -> u64 {
+        self.value.len()
+    }
+}
+
+impl<'n, 'f, T> Read for NtfsOwnedValueReader<'n, 'f, T>
+where
+    T: Read + Seek,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.value.read(&mut self.fs, buf).map_err(io::Error::from)
+    }
+}
+
+impl<'n, 'f, T> Seek for NtfsOwnedValueReader<'n, 'f, T>
+where
+    T: Read + Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.value.seek(&mut self.fs, pos).map_err(io::Error::from)
+    }
+}
+
 pub(crate) fn seek_contiguous(
     stream_position: &mut u64,
     length: u64,
@@ -194,3 +545,96 @@ pub(crate) fn seek_contiguous(
         ))),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexes::NtfsFileNameIndex;
+    use crate::ntfs::Ntfs;
+
+    #[test]
+    fn test_owned_value_readers() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+
+        let mut files = Vec::new();
+        for name in ["file-with-12345", "1000-bytes-file"] {
+            let mut root_dir_finder = root_dir_index.finder();
+            let entry = NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, name)
+                .unwrap()
+                .unwrap();
+            files.push(entry.to_file(&ntfs, &mut testfs1).unwrap());
+        }
+
+        let data_items = files
+            .iter()
+            .map(|file| file.data(&mut testfs1, "").unwrap().unwrap())
+            .collect::<Vec<_>>();
+        let data_attributes = data_items
+            .iter()
+            .map(|data_item| data_item.to_attribute().unwrap())
+            .collect::<Vec<_>>();
+
+        // Hold two readers with their own filesystem readers at the same time and read in alternation.
+        let mut readers = data_attributes
+            .iter()
+            .map(|data_attribute| {
+                let value = data_attribute.value(&mut testfs1).unwrap();
+                value.into_reader(testfs1.clone())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(readers[0].len(), 5);
+        assert_eq!(readers[1].len(), 1000);
+
+        let mut buf = [0u8; 3];
+        readers[0].read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"123");
+        readers[1].read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"123");
+        readers[0].read_exact(&mut buf[..2]).unwrap();
+        assert_eq!(&buf[..2], b"45");
+
+        readers[1].seek(SeekFrom::End(-1)).unwrap();
+        readers[1].read_exact(&mut buf[..1]).unwrap();
+        assert_eq!(buf[0], b'5');
+
+        // The filesystem reader can be taken back.
+        let (value, fs) = readers.pop().unwrap().into_parts();
+        assert_eq!(value.stream_position(), 1000);
+        assert_eq!(fs.get_ref().len(), testfs1.get_ref().len());
+    }
+
+    #[test]
+    fn test_map_position() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let mft = ntfs.file(&mut testfs1, 0).unwrap();
+        let mft_data_item = mft.data(&mut testfs1, "").unwrap().unwrap();
+        let mft_data_attribute = mft_data_item.to_attribute().unwrap();
+        let mut mft_data_value = mft_data_attribute.value(&mut testfs1).unwrap();
+
+        // Every offset maps to the same position that `data_position` reports after seeking there.
+        let len = mft_data_value.len();
+        for offset in [0, 1, 1023, 1024, len / 2, len - 1] {
+            let position = mft_data_value.map_position(&mut testfs1, offset).unwrap();
+            assert_eq!(mft_data_value.stream_position(), 0);
+
+            mft_data_value
+                .seek(&mut testfs1, SeekFrom::Start(offset))
+                .unwrap();
+            assert_eq!(position, mft_data_value.data_position(), "{offset}");
+            mft_data_value
+                .seek(&mut testfs1, SeekFrom::Start(0))
+                .unwrap();
+        }
+
+        // Offsets beyond the value have no position.
+        assert_eq!(
+            mft_data_value.map_position(&mut testfs1, len).unwrap(),
+            NtfsPosition::none()
+        );
+    }
+}
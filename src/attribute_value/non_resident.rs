@@ -7,15 +7,18 @@
 
 use core::iter::FusedIterator;
 use core::mem;
+use core::ops::Range;
 
-use binrw::io;
-use binrw::io::Cursor;
-use binrw::io::{Read, Seek, SeekFrom};
+use crate::io;
+use crate::io::Cursor;
+use crate::io::{Read, Seek, SeekFrom};
+use alloc::vec::Vec;
 use binrw::BinRead;
 
-use super::seek_contiguous;
+use super::{seek_contiguous, NtfsPhysicalRange, NtfsReadSegment, PhysicalRangeCollector};
+use crate::attribute::NtfsAttribute;
 use crate::error::{NtfsError, Result};
-use crate::ntfs::Ntfs;
+use crate::ntfs::{Ntfs, NtfsBadClusterPolicy};
 use crate::traits::NtfsReadSeek;
 use crate::types::{Lcn, NtfsPosition, Vcn};
 
@@ -32,17 +35,26 @@ pub struct NtfsNonResidentAttributeValue<'n, 'f> {
     stream_data_runs: NtfsDataRuns<'n, 'f>,
     /// Iteration state of the current Data Run.
     stream_state: StreamState,
+    /// Allocated size reported by the attribute header, in bytes.
+    allocated_size: u64,
+    /// Lowest VCN reported by the attribute header.
+    lowest_vcn: Vcn,
+    /// Highest VCN reported by the attribute header.
+    highest_vcn: Vcn,
+    /// File Record Number of the file this value belongs to, reported in [`NtfsError::ValueReadContext`].
+    file_record_number: u64,
+    /// Raw type of the attribute this value belongs to, reported in [`NtfsError::ValueReadContext`].
+    ///
+    /// This is kept raw, because values of unknown attribute types can be read as well.
+    ty: u32,
 }
 
 impl<'n, 'f> NtfsNonResidentAttributeValue<'n, 'f> {
-    pub(crate) fn new(
-        ntfs: &'n Ntfs,
-        data: &'f [u8],
-        position: NtfsPosition,
-        data_size: u64,
-    ) -> Result<Self> {
+    pub(crate) fn new(attribute: &NtfsAttribute<'n, 'f>) -> Result<Self> {
+        let ntfs = attribute.ntfs();
+        let (data, position) = attribute.non_resident_value_data_and_position()?;
         let stream_data_runs = NtfsDataRuns::new(ntfs, data, position);
-        let stream_state = StreamState::new(data_size);
+        let stream_state = StreamState::new(attribute.non_resident_value_data_size());
 
         let mut value = Self {
             ntfs,
@@ -50,6 +62,11 @@ impl<'n, 'f> NtfsNonResidentAttributeValue<'n, 'f> {
             position,
             stream_data_runs,
             stream_state,
+            allocated_size: attribute.allocated_size(),
+            lowest_vcn: attribute.non_resident_value_lowest_vcn(),
+            highest_vcn: attribute.non_resident_value_highest_vcn(),
+            file_record_number: attribute.owning_file_record_number(),
+            ty: attribute.ty_raw(),
         };
         value.next_data_run()?;
 
@@ -68,20 +85,107 @@ impl<'n, 'f> NtfsNonResidentAttributeValue<'n, 'f> {
         NtfsNonResidentAttributeValueAttached::new(fs, self)
     }
 
+    /// Returns the locations on the filesystem that store the bytes in `logical_range` of this value.
+    ///
+    /// See [`NtfsAttributeValue::byte_ranges`].
+    ///
+    /// [`NtfsAttributeValue::byte_ranges`]: crate::attribute_value::NtfsAttributeValue::byte_ranges
+    pub fn byte_ranges(&self, logical_range: Range<u64>) -> Result<Vec<NtfsPhysicalRange>> {
+        let mut collector = PhysicalRangeCollector::new(logical_range, self.len());
+
+        for data_run in self.data_runs() {
+            let data_run = data_run?;
+
+            if !collector.add_data_run(&data_run) {
+                break;
+            }
+        }
+
+        Ok(collector.into_ranges())
+    }
+
     /// Returns the absolute current data seek position within the filesystem, in bytes.
     /// This may be `None` if:
     ///   * The current seek position is outside the valid range, or
     ///   * The attribute does not have a Data Run, or
     ///   * The current Data Run is a "sparse" Data Run
+    ///
+    /// If the current Data Run has been read or seeked up to its end, this is the start position of the
+    /// next Data Run, where the next read continues.
     pub fn data_position(&self) -> NtfsPosition {
         self.stream_state.data_position()
     }
 
+    /// Returns the number of bytes from the current seek position up to the end of the Data Run they are
+    /// stored in.
+    ///
+    /// This many bytes can be read from consecutive positions on the filesystem.
+    pub(crate) fn contiguous_len(&self) -> u64 {
+        self.stream_state.data_run_remaining_len()
+    }
+
     /// Returns an iterator over all data runs of this non-resident attribute.
     pub fn data_runs(&self) -> NtfsDataRuns<'n, 'f> {
         NtfsDataRuns::new(self.ntfs, self.data, self.position)
     }
 
+    pub(crate) fn highest_vcn(&self) -> Vcn {
+        self.highest_vcn
+    }
+
+    /// Returns whether the Data Runs of this value are consistent with the attribute header.
+    ///
+    /// This is the case if the Data Runs cover exactly the range between the lowest and highest VCN of
+    /// the attribute, and the allocated size of the attribute is large enough for the Data Runs and the data.
+    /// Only the first attribute of a value split over multiple connected attributes reports sizes,
+    /// so the latter is only checked if the lowest VCN is zero.
+    ///
+    /// An inconsistent value is usually caused by a truncated or corrupted Data Run list.
+    /// Reading it returns less data than [`Self::len`] suggests.
+    /// Undecodable Data Runs are also considered inconsistent.
+    pub fn is_consistent(&self) -> bool {
+        let cluster_size = self.ntfs.cluster_size() as u64;
+        let mut run_clusters = 0u64;
+
+        for data_run in self.data_runs() {
+            match data_run {
+                Ok(data_run) => {
+                    run_clusters =
+                        run_clusters.saturating_add(data_run.allocated_size() / cluster_size)
+                }
+                Err(_) => return false,
+            }
+        }
+
+        let lowest_vcn = self.lowest_vcn.value();
+        let highest_vcn = self.highest_vcn.value();
+
+        // A zero-length value has no Data Runs.
+        // Its highest VCN is zero or even -1 according to NTFS-3G.
+        let is_empty =
+            run_clusters == 0 && lowest_vcn == 0 && (highest_vcn == 0 || highest_vcn == -1);
+        if !is_empty {
+            if lowest_vcn < 0 || highest_vcn < lowest_vcn {
+                return false;
+            }
+
+            let vcn_clusters = (highest_vcn - lowest_vcn) as u64 + 1;
+            if run_clusters != vcn_clusters {
+                return false;
+            }
+        }
+
+        if lowest_vcn == 0 {
+            let run_size = run_clusters.saturating_mul(cluster_size);
+
+            if run_size > self.allocated_size || self.len() > self.allocated_size {
+                return false;
+            }
+        }
+
+        true
+    }
+
     /// Returns `true` if the non-resident attribute value contains no data.
     pub fn is_empty(&self) -> bool {
         self.len() == 0
@@ -92,6 +196,10 @@ impl<'n, 'f> NtfsNonResidentAttributeValue<'n, 'f> {
         self.stream_state.data_size()
     }
 
+    pub(crate) fn lowest_vcn(&self) -> Vcn {
+        self.lowest_vcn
+    }
+
     /// Returns whether we got another Data Run.
     fn next_data_run(&mut self) -> Result<bool> {
         let stream_data_run = match self.stream_data_runs.next() {
@@ -104,23 +212,60 @@ impl<'n, 'f> NtfsNonResidentAttributeValue<'n, 'f> {
         Ok(true)
     }
 
+    /// Moves past the current Data Run if it has been read or seeked up to its end.
+    ///
+    /// This lets [`Self::data_position`] return the start position of the next Data Run, where the next read continues.
+    fn next_data_run_if_exhausted(&mut self) -> Result<()> {
+        if self.stream_state.is_data_run_exhausted() {
+            self.next_data_run()?;
+        }
+
+        Ok(())
+    }
+
     /// Returns the [`Ntfs`] object reference associated to this value.
     pub fn ntfs(&self) -> &'n Ntfs {
         self.ntfs
     }
 
-    /// Rewinds this value reader to the very beginning.
-    fn rewind(&mut self) -> Result<()> {
-        self.stream_data_runs = self.data_runs();
-        self.stream_state = StreamState::new(self.len());
-        self.next_data_run()?;
+    /// Reads the next segment of the value, which is either data or a sparse hole.
+    ///
+    /// See [`NtfsAttributeValue::read_segment`] for details.
+    ///
+    /// [`NtfsAttributeValue::read_segment`]: crate::attribute_value::NtfsAttributeValue::read_segment
+    pub fn read_segment<T>(&mut self, fs: &mut T, buf: &mut [u8]) -> Result<NtfsReadSegment>
+    where
+        T: Read + Seek,
+    {
+        self.read_data_run_segments(fs, buf)
+            .map_err(|e| e.with_value_context(self.file_record_number, self.ty))
+    }
 
-        Ok(())
+    /// Reads the next segment from the Data Runs (see [`Self::read_segment`]).
+    fn read_data_run_segments<T>(&mut self, fs: &mut T, buf: &mut [u8]) -> Result<NtfsReadSegment>
+    where
+        T: Read + Seek,
+    {
+        loop {
+            // Read from the current Data Run if there is one.
+            if let Some(segment) = self.stream_state.read_data_run_segment(fs, buf)? {
+                // The segment has been read successfully, so an undecodable next Data Run is reported
+                // by the next read.
+                let _ = self.next_data_run_if_exhausted();
+                return Ok(segment);
+            }
+
+            // Move to the next Data Run.
+            if !self.next_data_run()? {
+                // We read everything we could.
+                return Ok(NtfsReadSegment::Data(0));
+            }
+        }
     }
-}
 
-impl<'n, 'f> NtfsReadSeek for NtfsNonResidentAttributeValue<'n, 'f> {
-    fn read<T>(&mut self, fs: &mut T, buf: &mut [u8]) -> Result<usize>
+    /// Reads from the Data Runs into `buf` without restoring the previous position on error
+    /// (see [`NtfsReadSeek::read`]).
+    fn read_data_runs<T>(&mut self, fs: &mut T, buf: &mut [u8]) -> Result<usize>
     where
         T: Read + Seek,
     {
@@ -128,9 +273,19 @@ impl<'n, 'f> NtfsReadSeek for NtfsNonResidentAttributeValue<'n, 'f> {
 
         while bytes_read < buf.len() {
             // Read from the current Data Run if there is one.
-            if self.stream_state.read_data_run(fs, buf, &mut bytes_read)? {
-                // We read something, so check the loop condition again if we need to read more.
-                continue;
+            match self.stream_state.read_data_run(fs, buf, &mut bytes_read) {
+                Ok(true) => {
+                    // We read something, so check the loop condition again if we need to read more.
+                    continue;
+                }
+                Ok(false) => (),
+                Err(NtfsError::BadClusterRead { .. } | NtfsError::BeyondBackingStorage { .. })
+                    if bytes_read > 0 =>
+                {
+                    // Return what we have read so far and report the error on the next read.
+                    break;
+                }
+                Err(e) => return Err(e),
             }
 
             // Move to the next Data Run.
@@ -143,10 +298,14 @@ impl<'n, 'f> NtfsReadSeek for NtfsNonResidentAttributeValue<'n, 'f> {
             }
         }
 
+        // The data has been read successfully, so an undecodable next Data Run is reported by the next read.
+        let _ = self.next_data_run_if_exhausted();
+
         Ok(bytes_read)
     }
 
-    fn seek<T>(&mut self, fs: &mut T, pos: SeekFrom) -> Result<u64>
+    /// Seeks within the Data Runs (see [`NtfsReadSeek::seek`]).
+    fn seek_data_runs<T>(&mut self, fs: &mut T, pos: SeekFrom) -> Result<u64>
     where
         T: Read + Seek,
     {
@@ -190,9 +349,48 @@ impl<'n, 'f> NtfsReadSeek for NtfsNonResidentAttributeValue<'n, 'f> {
             _ => unreachable!(),
         }
 
+        // A seek exactly to the end of a Data Run continues at the start of the next one.
+        self.next_data_run_if_exhausted()?;
+
         Ok(self.stream_position())
     }
 
+    /// Rewinds this value reader to the very beginning.
+    fn rewind(&mut self) -> Result<()> {
+        self.stream_data_runs = self.data_runs();
+        self.stream_state = StreamState::new(self.len());
+        self.next_data_run()?;
+
+        Ok(())
+    }
+}
+
+impl<'n, 'f> NtfsReadSeek for NtfsNonResidentAttributeValue<'n, 'f> {
+    fn read<T>(&mut self, fs: &mut T, buf: &mut [u8]) -> Result<usize>
+    where
+        T: Read + Seek,
+    {
+        // A failed read must not move the stream position, so that it can be retried.
+        let stream_data_runs = self.stream_data_runs.clone();
+        let stream_state = self.stream_state.clone();
+
+        let result = self.read_data_runs(fs, buf);
+        if result.is_err() {
+            self.stream_data_runs = stream_data_runs;
+            self.stream_state = stream_state;
+        }
+
+        result.map_err(|e| e.with_value_context(self.file_record_number, self.ty))
+    }
+
+    fn seek<T>(&mut self, fs: &mut T, pos: SeekFrom) -> Result<u64>
+    where
+        T: Read + Seek,
+    {
+        self.seek_data_runs(fs, pos)
+            .map_err(|e| e.with_value_context(self.file_record_number, self.ty))
+    }
+
     fn stream_position(&self) -> u64 {
         self.stream_state.stream_position()
     }
@@ -361,70 +559,93 @@ impl<'n, 'f> Iterator for NtfsDataRuns<'n, 'f> {
     type Item = Result<NtfsDataRun>;
 
     fn next(&mut self) -> Option<Result<NtfsDataRun>> {
-        if self.state.offset >= self.data.len() {
-            return None;
-        }
-
-        // Read the single header byte.
-        let mut cursor = Cursor::new(&self.data[self.state.offset..]);
-        let header = iter_try!(u8::read(&mut cursor));
+        loop {
+            if self.state.offset >= self.data.len() {
+                return None;
+            }
 
-        // A zero byte marks the end of the data runs.
-        if header == 0 {
-            // Ensure that any further call uses the fast path above.
-            self.state.offset = self.data.len();
-            return None;
-        }
+            // Read the single header byte.
+            let mut cursor = Cursor::new(&self.data[self.state.offset..]);
+            let header = iter_try!(u8::read(&mut cursor));
 
-        // The lower nibble indicates the length of the following cluster count variable length integer.
-        let cluster_count_byte_count = header & 0x0f;
-        let cluster_count = iter_try!(
-            self.read_variable_length_unsigned_integer(&mut cursor, cluster_count_byte_count)
-        );
-        if cluster_count == 0 {
-            return Some(Err(NtfsError::InvalidClusterCountInDataRunHeader {
-                position: NtfsDataRuns::position(self),
-                cluster_count,
-            }));
-        }
-        let allocated_size = iter_try!(cluster_count
-            .checked_mul(self.ntfs.cluster_size() as u64)
-            .ok_or_else(|| NtfsError::InvalidClusterCountInDataRunHeader {
-                position: NtfsDataRuns::position(self),
-                cluster_count,
-            }));
-
-        // The upper nibble indicates the length of the following VCN variable length integer.
-        let vcn_byte_count = (header & 0xf0) >> 4;
-        let vcn = Vcn::from(iter_try!(
-            self.read_variable_length_signed_integer(&mut cursor, vcn_byte_count)
-        ));
+            // A zero byte marks the end of the data runs.
+            if header == 0 {
+                // Ensure that any further call uses the fast path above.
+                self.state.offset = self.data.len();
+                return None;
+            }
 
-        // The VCN may either indicate "real" data or a sparse Data Run.
-        let position = if vcn.value() != 0 {
-            // This Data Run contains "real" data.
-            // Turn the read VCN into an absolute LCN.
-            let new_lcn = iter_try!(self.state.previous_lcn.checked_add(vcn).ok_or(
-                NtfsError::InvalidVcnInDataRunHeader {
+            // The lower nibble indicates the length of the following cluster count variable length integer.
+            let cluster_count_byte_count = header & 0x0f;
+            let cluster_count =
+                iter_try!(self
+                    .read_variable_length_unsigned_integer(&mut cursor, cluster_count_byte_count));
+            if cluster_count == 0 {
+                // Some writers emit zero-length Data Runs in the middle of a run list.
+                // Windows skips them, and so do we in lenient mode.
+                iter_try!(self
+                    .ntfs
+                    .tolerate(NtfsError::InvalidClusterCountInDataRunHeader {
+                        position: NtfsDataRuns::position(self),
+                        cluster_count,
+                    }));
+            }
+            let allocated_size = iter_try!(cluster_count
+                .checked_mul(self.ntfs.cluster_size() as u64)
+                .ok_or_else(|| NtfsError::InvalidClusterCountInDataRunHeader {
                     position: NtfsDataRuns::position(self),
-                    vcn,
-                    previous_lcn: self.state.previous_lcn,
-                }
+                    cluster_count,
+                }));
+
+            // The upper nibble indicates the length of the following VCN variable length integer.
+            let vcn_byte_count = (header & 0xf0) >> 4;
+            let vcn = Vcn::from(iter_try!(
+                self.read_variable_length_signed_integer(&mut cursor, vcn_byte_count)
             ));
-            self.state.previous_lcn = new_lcn;
-            iter_try!(new_lcn.position(self.ntfs))
-        } else {
-            // This is a sparse Data Run.
-            NtfsPosition::none()
-        };
 
-        // Only advance after having checked for success.
-        // In case of an error, a subsequent call shall output the same error again.
-        let bytes_to_advance = cursor.stream_position().unwrap() as usize;
-        self.state.offset += bytes_to_advance;
+            // The VCN may either indicate "real" data or a sparse Data Run.
+            // Even a skipped zero-length Data Run contributes to the LCN, because the LCN of each
+            // Data Run is stored relative to the previous one.
+            let mut previous_lcn = self.state.previous_lcn;
+            let position = if vcn.value() != 0 {
+                // This Data Run contains "real" data.
+                // Turn the read VCN into an absolute LCN.
+                previous_lcn = iter_try!(previous_lcn.checked_add(vcn).ok_or(
+                    NtfsError::InvalidVcnInDataRunHeader {
+                        position: NtfsDataRuns::position(self),
+                        vcn,
+                        previous_lcn,
+                    }
+                ));
+                let position = iter_try!(previous_lcn.position(self.ntfs));
 
-        let data_run = NtfsDataRun::new(position, allocated_size);
-        Some(Ok(data_run))
+                // Reject Data Runs whose end lies beyond the addressable range,
+                // so that reads within them can never overflow.
+                if position.checked_add(allocated_size).value().is_none() {
+                    return Some(Err(NtfsError::LcnTooBig { lcn: previous_lcn }));
+                }
+
+                position
+            } else {
+                // This is a sparse Data Run.
+                NtfsPosition::none()
+            };
+
+            // Only advance after having checked for success.
+            // In case of an error, a subsequent call shall output the same error again.
+            let bytes_to_advance = cursor.position() as usize;
+            self.state.offset += bytes_to_advance;
+            self.state.previous_lcn = previous_lcn;
+
+            if cluster_count == 0 {
+                continue;
+            }
+
+            let data_run = NtfsDataRun::new(position, allocated_size)
+                .with_bad_clusters(self.ntfs)
+                .with_backing_size(self.ntfs);
+            return Some(Ok(data_run));
+        }
     }
 }
 
@@ -451,6 +672,10 @@ pub struct NtfsDataRun {
     allocated_size: u64,
     /// Current relative position within the Data Run value, in bytes.
     stream_position: u64,
+    /// Clusters of this Data Run marked as bad (see [`Ntfs::read_bad_clusters`]), if any.
+    bad_clusters: Option<DataRunBadClusters>,
+    /// Length of the reader, if this Data Run extends beyond it (see [`Ntfs::check_backing_size`]).
+    backing_size: Option<u64>,
 }
 
 impl NtfsDataRun {
@@ -459,9 +684,64 @@ impl NtfsDataRun {
             position,
             allocated_size,
             stream_position: 0,
+            bad_clusters: None,
+            backing_size: None,
         }
     }
 
+    /// Records the length of the reader if this Data Run extends beyond it.
+    pub(crate) fn with_backing_size(mut self, ntfs: &Ntfs) -> Self {
+        if let (Some(position), Some(backing_size)) = (self.position.value(), ntfs.backing_size()) {
+            if position.get() + self.allocated_size > backing_size {
+                self.backing_size = Some(backing_size);
+            }
+        }
+
+        self
+    }
+
+    /// Records the clusters of this Data Run that are marked as bad in the given [`Ntfs`] object.
+    pub(crate) fn with_bad_clusters(mut self, ntfs: &Ntfs) -> Self {
+        let position = match self.position.value() {
+            Some(position) => position.get(),
+            None => return self,
+        };
+
+        let bad_clusters = ntfs.bad_clusters();
+        if bad_clusters.is_empty() {
+            return self;
+        }
+
+        // Data Runs are always cluster-aligned.
+        let cluster_size = ntfs.cluster_size() as u64;
+        let partition_offset = ntfs.partition_offset();
+        let start_lcn = (position - partition_offset) / cluster_size;
+        let end_lcn = start_lcn + self.allocated_size / cluster_size;
+
+        // `bad_clusters` is sorted and merged, so skip all ranges ending before this Data Run.
+        let first_index = bad_clusters.partition_point(|range| range.end.value() <= start_lcn);
+        let ranges = bad_clusters[first_index..]
+            .iter()
+            .take_while(|range| range.start.value() < end_lcn)
+            .map(|range| {
+                let start = u64::max(range.start.value(), start_lcn) - start_lcn;
+                let end = u64::min(range.end.value(), end_lcn) - start_lcn;
+                start * cluster_size..end * cluster_size
+            })
+            .collect::<Vec<_>>();
+
+        if !ranges.is_empty() {
+            self.bad_clusters = Some(DataRunBadClusters {
+                cluster_size,
+                partition_offset,
+                policy: ntfs.bad_cluster_policy(),
+                ranges,
+            });
+        }
+
+        self
+    }
+
     /// Returns the allocated size of the Data Run, in bytes.
     pub fn allocated_size(&self) -> u64 {
         self.allocated_size
@@ -479,6 +759,11 @@ impl NtfsDataRun {
         }
     }
 
+    /// Returns `true` if this is a "sparse" Data Run, which has no clusters on the filesystem and reads as zeros.
+    pub fn is_sparse(&self) -> bool {
+        self.position.value().is_none()
+    }
+
     pub(crate) fn remaining_len(&self) -> u64 {
         self.allocated_size().saturating_sub(self.stream_position)
     }
@@ -494,11 +779,62 @@ impl NtfsReadSeek for NtfsDataRun {
         }
 
         let bytes_to_read = usize::min(buf.len(), self.remaining_len() as usize);
-        let work_slice = &mut buf[..bytes_to_read];
+        let mut work_slice = &mut buf[..bytes_to_read];
+
+        if let Some(bad_clusters) = &self.bad_clusters {
+            let read_range = self.stream_position..self.stream_position + bytes_to_read as u64;
+
+            if let Some(bad_range) = bad_clusters.first_overlap(read_range) {
+                if bad_range.start == self.stream_position {
+                    // The read starts in a bad cluster.
+                    match bad_clusters.policy {
+                        NtfsBadClusterPolicy::Error => {
+                            let position = self.position + self.stream_position;
+                            let lcn = Lcn::from(
+                                (position.value().unwrap().get() - bad_clusters.partition_offset)
+                                    / bad_clusters.cluster_size,
+                            );
+                            return Err(NtfsError::BadClusterRead { lcn, position });
+                        }
+                        NtfsBadClusterPolicy::ZeroFill => {
+                            let bytes_read = (bad_range.end - bad_range.start) as usize;
+                            work_slice[..bytes_read].fill(0);
+                            self.stream_position += bytes_read as u64;
+                            return Ok(bytes_read);
+                        }
+                    }
+                } else {
+                    // Only read up to the bad cluster and handle it in the next call.
+                    let bytes_to_read = (bad_range.start - self.stream_position) as usize;
+                    work_slice = &mut work_slice[..bytes_to_read];
+                }
+            }
+        }
 
         let bytes_read = if let Some(position) = self.position.value() {
             // This Data Run contains "real" data.
-            fs.seek(SeekFrom::Start(position.get() + self.stream_position))?;
+            let data_position = position.get() + self.stream_position;
+
+            if let Some(backing_size) = self.backing_size {
+                // Only read up to the end of the reader and fail if nothing is left.
+                let available = backing_size.saturating_sub(data_position);
+                if available == 0 {
+                    return Err(NtfsError::BeyondBackingStorage {
+                        position: self.position + self.stream_position,
+                        available: backing_size,
+                    });
+                }
+
+                let bytes_to_read = u64::min(work_slice.len() as u64, available) as usize;
+                work_slice = &mut work_slice[..bytes_to_read];
+            }
+
+            // Only seek if the reader isn't already at the right position (e.g. after a previous read).
+            // Seeking may be expensive, e.g. it discards the buffer of a `BufReader`.
+            if fs.stream_position()? != data_position {
+                fs.seek(SeekFrom::Start(data_position))?;
+            }
+
             fs.read(work_slice)?
         } else {
             // This is a sparse Data Run.
@@ -523,6 +859,35 @@ impl NtfsReadSeek for NtfsDataRun {
     }
 }
 
+/// Clusters of an [`NtfsDataRun`] marked as bad.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct DataRunBadClusters {
+    /// The size of a single cluster, in bytes.
+    cluster_size: u64,
+    /// The partition offset of the filesystem (see [`Ntfs::partition_offset`]).
+    partition_offset: u64,
+    /// How reads from the bad clusters are handled.
+    policy: NtfsBadClusterPolicy,
+    /// Byte ranges of the bad clusters relative to the start of the Data Run, sorted by their start.
+    ranges: Vec<Range<u64>>,
+}
+
+impl DataRunBadClusters {
+    /// Returns the part of the first bad cluster range that overlaps `range`, if any.
+    fn first_overlap(&self, range: Range<u64>) -> Option<Range<u64>> {
+        let index = self
+            .ranges
+            .partition_point(|bad_range| bad_range.end <= range.start);
+        let bad_range = self.ranges.get(index)?;
+
+        if bad_range.start < range.end {
+            Some(u64::max(bad_range.start, range.start)..u64::min(bad_range.end, range.end))
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct StreamState {
     /// Current Data Run we are reading from.
@@ -555,11 +920,27 @@ impl StreamState {
         }
     }
 
+    /// Returns the number of bytes left in the current Data Run, or zero if there is none.
+    pub(crate) fn data_run_remaining_len(&self) -> u64 {
+        self.stream_data_run
+            .as_ref()
+            .map(|data_run| data_run.remaining_len())
+            .unwrap_or(0)
+    }
+
     /// Returns the total (used) data size of the value, in bytes.
     pub(crate) fn data_size(&self) -> u64 {
         self.data_size
     }
 
+    /// Returns whether the current Data Run has been read or seeked up to its very end.
+    ///
+    /// [`Self::data_position`] then returns a position one byte past the current Data Run.
+    /// Callers should move to the next Data Run if there is one.
+    pub(crate) fn is_data_run_exhausted(&self) -> bool {
+        matches!(&self.stream_data_run, Some(data_run) if data_run.remaining_len() == 0)
+    }
+
     pub(crate) fn optimize_seek(&self, pos: SeekFrom, data_size: u64) -> Result<SeekFrom> {
         let mut pos = self.simplify_seek(pos, data_size)?;
 
@@ -636,7 +1017,8 @@ impl StreamState {
         };
 
         // Have we already seeked past the size of the Data Run?
-        if data_run.stream_position() >= data_run.allocated_size() {
+        let remaining_data_run_len = data_run.remaining_len();
+        if remaining_data_run_len == 0 {
             return Ok(false);
         }
 
@@ -649,11 +1031,20 @@ impl StreamState {
 
         // Read up to the buffer length or up to the (used) data size, whatever comes first.
         let start = *bytes_read;
-        let remaining_buf_len = buf.len() - start;
-        let end = start + usize::min(remaining_buf_len, remaining_data_size as usize);
-
-        // Perform the actual read.
-        let bytes_read_in_data_run = data_run.read(fs, &mut buf[start..end])?;
+        let remaining_buf_len = (buf.len() - start) as u64;
+        let end = start + u64::min(remaining_buf_len, remaining_data_size) as usize;
+
+        let bytes_read_in_data_run = if data_run.is_sparse() {
+            // Fast path for sparse Data Runs: They have no clusters on the filesystem (and therefore no bad clusters),
+            // so fill as much of the buffer as the Data Run covers in one step and never touch `fs`.
+            let bytes_to_fill = u64::min((end - start) as u64, remaining_data_run_len) as usize;
+            buf[start..start + bytes_to_fill].fill(0);
+            data_run.stream_position += bytes_to_fill as u64;
+            bytes_to_fill
+        } else {
+            // Perform the actual read.
+            data_run.read(fs, &mut buf[start..end])?
+        };
         if bytes_read_in_data_run == 0 {
             return Ok(false);
         }
@@ -663,6 +1054,50 @@ impl StreamState {
         Ok(true)
     }
 
+    /// Reads data from the current Data Run or skips the rest of it if it's a sparse Data Run.
+    ///
+    /// Returns `None` if the caller should move to the next Data Run.
+    pub(crate) fn read_data_run_segment<T>(
+        &mut self,
+        fs: &mut T,
+        buf: &mut [u8],
+    ) -> Result<Option<NtfsReadSegment>>
+    where
+        T: Read + Seek,
+    {
+        // Is there a Data Run to read from?
+        let data_run = match &mut self.stream_data_run {
+            Some(data_run) => data_run,
+            None => return Ok(None),
+        };
+
+        // Have we already seeked past the size of the Data Run?
+        if data_run.remaining_len() == 0 {
+            return Ok(None);
+        }
+
+        // We must not go past the (used) data size of the entire value.
+        let remaining_data_size = self.data_size.saturating_sub(self.stream_position);
+        if remaining_data_size == 0 {
+            return Ok(Some(NtfsReadSegment::Data(0)));
+        }
+
+        if data_run.is_sparse() {
+            // Skip the rest of the sparse Data Run up to the (used) data size.
+            let hole_length = u64::min(data_run.remaining_len(), remaining_data_size);
+            let new_position = data_run.stream_position() + hole_length;
+            data_run.seek(fs, SeekFrom::Start(new_position))?;
+            self.stream_position += hole_length;
+
+            Ok(Some(NtfsReadSegment::Hole(hole_length)))
+        } else {
+            let mut bytes_read = 0;
+            self.read_data_run(fs, buf, &mut bytes_read)?;
+
+            Ok(Some(NtfsReadSegment::Data(bytes_read)))
+        }
+    }
+
     /// Returns whether we have reached the final seek position within this Data Run and can therefore stop seeking.
     ///
     /// In all other cases, the caller should move to the next Data Run and seek again.
@@ -681,8 +1116,10 @@ impl StreamState {
             None => return Ok(false),
         };
 
-        if *bytes_left_to_seek < data_run.remaining_len() {
+        if *bytes_left_to_seek <= data_run.remaining_len() {
             // We have found the right Data Run, now we have to seek inside the Data Run.
+            // A seek to the very end of this Data Run also stays here, because moving to the next
+            // Data Run (possibly reading another File Record) is left to the next read or seek.
             //
             // If we were called to seek from the very beginning, we can be sure that this
             // Data Run is also seeked from the beginning.
@@ -723,12 +1160,144 @@ impl StreamState {
 
 #[cfg(test)]
 mod tests {
-    use binrw::io::SeekFrom;
-
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::io::SeekFrom;
+
+    use super::*;
+    use crate::attribute::NtfsAttributeType;
+    use crate::attribute_value::{NtfsPhysicalRange, NtfsReadSegment};
+    use crate::file::KnownNtfsFileRecordNumber;
+    use crate::helpers::tests::{
+        attribute_offset, encode_data_runs, insert_attribute, patch_file_record_number,
+        remove_attribute, take_attribute_instance, AttributeBuilder, FailingReader,
+    };
     use crate::indexes::NtfsFileNameIndex;
-    use crate::ntfs::Ntfs;
+    use crate::ntfs::{Ntfs, NtfsValidationMode};
     use crate::traits::NtfsReadSeek;
 
+    #[test]
+    fn test_zero_length_data_run() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // 2 clusters at LCN 16, followed by a zero-length Data Run moving the LCN by 4,
+        // followed by 3 clusters at LCN 22 (relative to the skipped Data Run).
+        let data = [
+            0x21, 0x02, 0x10, 0x00, //
+            0x11, 0x00, 0x04, //
+            0x11, 0x03, 0x02, //
+            0x00,
+        ];
+        let position = NtfsPosition::new(0x1000);
+
+        // Strict mode rejects the zero-length Data Run, and keeps doing so on subsequent calls.
+        let mut data_runs = NtfsDataRuns::new(&ntfs, &data, position);
+        let first_data_run = data_runs.next().unwrap().unwrap();
+        assert_eq!(first_data_run.data_position(), NtfsPosition::new(16 * 512));
+        for _ in 0..2 {
+            assert!(matches!(
+                data_runs.next(),
+                Some(Err(NtfsError::InvalidClusterCountInDataRunHeader {
+                    cluster_count: 0,
+                    ..
+                }))
+            ));
+        }
+
+        // Lenient mode skips it with a warning and continues with the following Data Runs.
+        let warnings = Arc::new(AtomicUsize::new(0));
+        let warnings_in_handler = Arc::clone(&warnings);
+        ntfs.set_validation_mode(NtfsValidationMode::Lenient);
+        ntfs.set_warning_handler(move |error| {
+            assert!(matches!(
+                error,
+                NtfsError::InvalidClusterCountInDataRunHeader { position, .. }
+                    if *position == NtfsPosition::new(0x1004)
+            ));
+            warnings_in_handler.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let data_runs = NtfsDataRuns::new(&ntfs, &data, position)
+            .map(|data_run| {
+                let data_run = data_run.unwrap();
+                (data_run.data_position(), data_run.allocated_size())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            data_runs,
+            [
+                (NtfsPosition::new(16 * 512), 2 * 512),
+                (NtfsPosition::new(22 * 512), 3 * 512),
+            ]
+        );
+        assert_eq!(warnings.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_consistency() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // All non-resident attributes of the first File Records of testfs1 are consistent,
+        // including sparse ones (like $BadClus:$Bad and "sparse-file").
+        let mut non_resident_count = 0;
+        for file_record_number in 0..80 {
+            let file = match ntfs.file(&mut testfs1, file_record_number) {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+
+            for attribute in file.attributes_raw() {
+                let attribute = attribute.unwrap();
+                if !attribute.is_resident() {
+                    assert!(attribute.non_resident_value().unwrap().is_consistent());
+                    non_resident_count += 1;
+                }
+            }
+        }
+        assert!(non_resident_count > 10);
+
+        // Truncate the Data Run list of "sparse-file" after its first Data Run.
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "sparse-file")
+                .unwrap()
+                .unwrap();
+        let file_record_number = entry.file_reference().file_record_number();
+        let file = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        let data_attribute_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_attribute_item.to_attribute().unwrap();
+        let (data, position) = data_attribute
+            .non_resident_value_data_and_position()
+            .unwrap();
+        let first_data_run_length = 1 + (data[0] & 0x0f) as usize + (data[0] >> 4) as usize;
+        let terminator_position = position.value().unwrap().get() as usize + first_data_run_length;
+        assert!(terminator_position % 512 < 510);
+
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        testfs1.get_mut()[terminator_position] = 0;
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+        let data_attribute_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_attribute_item.to_attribute().unwrap();
+        let mut data_attribute_value = data_attribute.non_resident_value().unwrap();
+
+        // The header still reports the entire VCN range, but the remaining Data Run only covers a single cluster.
+        assert_eq!(data_attribute_value.data_runs().count(), 1);
+        assert!(!data_attribute_value.is_consistent());
+
+        // Reading returns less data than the value length suggests.
+        let mut buf = vec![0u8; 500005];
+        let bytes_read = data_attribute_value.read(&mut testfs1, &mut buf).unwrap();
+        assert_eq!(data_attribute_value.len(), 500005);
+        assert_eq!(bytes_read, 512);
+    }
+
     #[test]
     fn test_read_and_seek() {
         let mut testfs1 = crate::helpers::tests::testfs1();
@@ -855,4 +1424,540 @@ mod tests {
         assert_eq!(buf[5..500000], [0u8].repeat(499995));
         assert_eq!(buf[500000..500005], [b'1', b'1', b'1', b'1', b'1']);
     }
+
+    #[test]
+    fn test_byte_ranges() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "sparse-file")
+                .unwrap()
+                .unwrap();
+        let file = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        let data_attribute_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_attribute_item.to_attribute().unwrap();
+        let data_attribute_value = data_attribute.value(&mut testfs1).unwrap();
+
+        let data_runs = data_attribute
+            .non_resident_value()
+            .unwrap()
+            .data_runs()
+            .map(|data_run| data_run.unwrap().data_position())
+            .collect::<Vec<_>>();
+        let first_position = data_runs[0];
+        let third_position = data_runs[2];
+
+        // The entire value consists of the data of the first and third Data Run, with a hole in between.
+        // The third Data Run is clipped to the length of the value.
+        assert_eq!(
+            data_attribute_value
+                .byte_ranges(&mut testfs1, 0..500005)
+                .unwrap(),
+            [
+                NtfsPhysicalRange::Data {
+                    logical_offset: 0,
+                    position: first_position,
+                    length: 512,
+                },
+                NtfsPhysicalRange::Hole {
+                    logical_offset: 512,
+                    length: 499200,
+                },
+                NtfsPhysicalRange::Data {
+                    logical_offset: 499712,
+                    position: third_position,
+                    length: 293,
+                },
+            ]
+        );
+
+        // Ranges within a Data Run start at the corresponding position.
+        let ranges = data_attribute_value
+            .byte_ranges(&mut testfs1, 100..600)
+            .unwrap();
+        assert_eq!(
+            ranges,
+            [
+                NtfsPhysicalRange::Data {
+                    logical_offset: 100,
+                    position: first_position + 100u64,
+                    length: 412,
+                },
+                NtfsPhysicalRange::Hole {
+                    logical_offset: 512,
+                    length: 88,
+                },
+            ]
+        );
+        assert_eq!(ranges[1].logical_range(), 512..600);
+
+        // Nothing is returned beyond the length of the value.
+        assert!(data_attribute_value
+            .byte_ranges(&mut testfs1, 500005..600000)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_data_position_at_data_run_boundaries() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // Find the "sparse-file".
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "sparse-file")
+                .unwrap()
+                .unwrap();
+        let file = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        let data_attribute_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_attribute_item.to_attribute().unwrap();
+        let mut data_attribute_value = data_attribute.non_resident_value().unwrap();
+
+        // A 512-byte Data Run with data, a 499200-byte sparse Data Run, and another Data Run with data.
+        let mut data_runs = data_attribute_value.data_runs();
+        let first_data_run = data_runs.next().unwrap().unwrap();
+        let _second_data_run = data_runs.next().unwrap().unwrap();
+        let third_data_run = data_runs.next().unwrap().unwrap();
+        assert_eq!(first_data_run.allocated_size(), 512);
+        let second_boundary = 512 + 499200;
+
+        // Seeking exactly to the end of a Data Run reports the start of the next Data Run.
+        data_attribute_value
+            .seek(&mut testfs1, SeekFrom::Start(second_boundary))
+            .unwrap();
+        assert_eq!(
+            data_attribute_value.data_position(),
+            third_data_run.data_position()
+        );
+
+        data_attribute_value
+            .seek(&mut testfs1, SeekFrom::Start(512))
+            .unwrap();
+        assert_eq!(data_attribute_value.data_position(), NtfsPosition::none());
+
+        // The same must hold when the boundary is reached by reading.
+        data_attribute_value
+            .seek(&mut testfs1, SeekFrom::Start(0))
+            .unwrap();
+        let mut buf = [0u8; 512];
+        assert_eq!(
+            data_attribute_value.read(&mut testfs1, &mut buf).unwrap(),
+            512
+        );
+        assert_eq!(data_attribute_value.data_position(), NtfsPosition::none());
+
+        // Seeking by zero bytes from a boundary doesn't change anything.
+        data_attribute_value
+            .seek(&mut testfs1, SeekFrom::Current(0))
+            .unwrap();
+        assert_eq!(data_attribute_value.stream_position(), 512);
+        assert_eq!(data_attribute_value.data_position(), NtfsPosition::none());
+
+        data_attribute_value
+            .seek(&mut testfs1, SeekFrom::Start(second_boundary - 1))
+            .unwrap();
+        assert_eq!(
+            data_attribute_value
+                .read(&mut testfs1, &mut buf[..1])
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            data_attribute_value.data_position(),
+            third_data_run.data_position()
+        );
+
+        // Reading continues correctly in the next Data Run.
+        assert_eq!(
+            data_attribute_value.read(&mut testfs1, &mut buf).unwrap(),
+            293
+        );
+        assert_eq!(buf[288..293], [b'1', b'1', b'1', b'1', b'1']);
+    }
+
+    #[test]
+    fn test_zero_length_data_run_at_data_run_boundary() {
+        // Replace the unnamed resident $DATA attribute of File Record 65 by a non-resident one with
+        // a zero-length Data Run between its two Data Runs (like in `test_zero_length_data_run`).
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let data_runs = encode_data_runs(&[(1, Some(16)), (0, Some(20)), (1, Some(22))]);
+        patch_file_record_number(&mut testfs1, 65, |record| {
+            let offset = attribute_offset(record, NtfsAttributeType::Data);
+            remove_attribute(record, offset);
+
+            let attribute =
+                AttributeBuilder::non_resident(NtfsAttributeType::Data, 0, 1, &data_runs)
+                    .sizes(1024, 1024, 1024)
+                    .instance(take_attribute_instance(record))
+                    .build();
+            insert_attribute(record, offset, &attribute);
+        });
+        let second_cluster = testfs1.get_ref()[22 * 512..23 * 512].to_vec();
+
+        // Lenient mode skips the zero-length Data Run once when seeking to the end of the first Data Run.
+        // The getters don't decode it again.
+        let warnings = Arc::new(AtomicUsize::new(0));
+        let warnings_in_handler = Arc::clone(&warnings);
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.set_validation_mode(NtfsValidationMode::Lenient);
+        ntfs.set_warning_handler(move |_| {
+            warnings_in_handler.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        let data_attribute_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_attribute_item.to_attribute().unwrap();
+        let mut data_attribute_value = data_attribute.non_resident_value().unwrap();
+
+        data_attribute_value
+            .seek(&mut testfs1, SeekFrom::Start(512))
+            .unwrap();
+        for _ in 0..2 {
+            assert_eq!(
+                data_attribute_value.data_position(),
+                NtfsPosition::new(22 * 512)
+            );
+            assert_eq!(data_attribute_value.contiguous_len(), 512);
+        }
+        assert_eq!(warnings.load(Ordering::SeqCst), 1);
+
+        let mut buf = [0u8; 512];
+        data_attribute_value
+            .read_exact(&mut testfs1, &mut buf)
+            .unwrap();
+        assert_eq!(buf[..], second_cluster[..]);
+        assert_eq!(warnings.load(Ordering::SeqCst), 1);
+
+        // Strict mode reports the zero-length Data Run when seeking to the end of the first Data Run.
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        let data_attribute_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_attribute_item.to_attribute().unwrap();
+        let mut data_attribute_value = data_attribute.non_resident_value().unwrap();
+        let error = data_attribute_value
+            .seek(&mut testfs1, SeekFrom::Start(512))
+            .unwrap_err();
+        assert!(matches!(
+            error.root_cause(),
+            NtfsError::InvalidClusterCountInDataRunHeader {
+                cluster_count: 0,
+                ..
+            }
+        ));
+        assert_eq!(data_attribute_value.stream_position(), 512);
+
+        // Reading up to there succeeds, and the next read reports it.
+        data_attribute_value
+            .seek(&mut testfs1, SeekFrom::Start(0))
+            .unwrap();
+        assert_eq!(
+            data_attribute_value.read(&mut testfs1, &mut buf).unwrap(),
+            512
+        );
+        assert_eq!(data_attribute_value.stream_position(), 512);
+
+        let error = data_attribute_value
+            .read(&mut testfs1, &mut buf)
+            .unwrap_err();
+        assert!(matches!(
+            error.root_cause(),
+            NtfsError::InvalidClusterCountInDataRunHeader {
+                cluster_count: 0,
+                ..
+            }
+        ));
+        assert_eq!(data_attribute_value.stream_position(), 512);
+    }
+
+    #[test]
+    fn test_read_segment() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // Find the "sparse-file".
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "sparse-file")
+                .unwrap()
+                .unwrap();
+        let file = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        let data_attribute_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_attribute_item.to_attribute().unwrap();
+        let mut data_attribute_value = data_attribute.value(&mut testfs1).unwrap();
+
+        // Collect all segments as (offset, length, is_hole).
+        // The data bytes are only "12345" at offset 0 and "11111" at offset 500000, but holes consist of
+        // entire clusters. Hence, the zeros in the first and last cluster are actual data.
+        let mut segments = Vec::new();
+        let mut data = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let offset = data_attribute_value.stream_position();
+
+            match data_attribute_value
+                .read_segment(&mut testfs1, &mut buf)
+                .unwrap()
+            {
+                NtfsReadSegment::Data(0) => break,
+                NtfsReadSegment::Data(bytes_read) => {
+                    segments.push((offset, bytes_read as u64, false));
+                    data.extend_from_slice(&buf[..bytes_read]);
+                }
+                NtfsReadSegment::Hole(length) => {
+                    segments.push((offset, length, true));
+                    data.resize(data.len() + length as usize, 0);
+                }
+            }
+        }
+
+        assert_eq!(
+            segments,
+            [(0, 512, false), (512, 499200, true), (499712, 293, false)]
+        );
+        assert_eq!(data_attribute_value.stream_position(), 500005);
+
+        // The result must be identical to a regular read.
+        data_attribute_value
+            .seek(&mut testfs1, SeekFrom::Start(0))
+            .unwrap();
+        let mut expected_data = Vec::new();
+        data_attribute_value
+            .read_to_end(&mut testfs1, &mut expected_data, usize::MAX)
+            .unwrap();
+        assert_eq!(data, expected_data);
+
+        // Reading a segment from the middle of a hole only returns the rest of the hole.
+        data_attribute_value
+            .seek(&mut testfs1, SeekFrom::Start(1000))
+            .unwrap();
+        assert_eq!(
+            data_attribute_value
+                .read_segment(&mut testfs1, &mut buf)
+                .unwrap(),
+            NtfsReadSegment::Hole(498712)
+        );
+    }
+
+    /// Wraps a reader and counts the seeks that actually change its position, as well as all accesses.
+    struct SeekCountingReader<R> {
+        inner: R,
+        seek_count: usize,
+        access_count: usize,
+    }
+
+    impl<R: Read> Read for SeekCountingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.access_count += 1;
+            self.inner.read(buf)
+        }
+    }
+
+    impl<R: Seek> Seek for SeekCountingReader<R> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.access_count += 1;
+            if pos != SeekFrom::Current(0) {
+                self.seek_count += 1;
+            }
+
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_contiguous_read_seeks_once() {
+        let mut testfs1 = SeekCountingReader {
+            inner: crate::helpers::tests::testfs1(),
+            seek_count: 0,
+            access_count: 0,
+        };
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // The $UpCase file of testfs1 has a single Data Run of 128 KiB.
+        let upcase_file = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::UpCase as u64)
+            .unwrap();
+        let data_item = upcase_file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+        assert_eq!(data_value.len(), 131072);
+
+        let read_size = data_value.recommended_read_size(&ntfs);
+        assert_eq!(read_size, ntfs.cluster_size() as usize);
+
+        // Reading it cluster by cluster must only seek once to the beginning of the Data Run.
+        testfs1.seek_count = 0;
+        let mut buf = vec![0u8; read_size];
+        let mut data = Vec::new();
+        let mut read_count = 0;
+        loop {
+            let bytes_read = data_value.read(&mut testfs1, &mut buf).unwrap();
+            if bytes_read == 0 {
+                break;
+            }
+
+            data.extend_from_slice(&buf[..bytes_read]);
+            read_count += 1;
+        }
+        assert_eq!(read_count, 256);
+        assert_eq!(testfs1.seek_count, 1);
+
+        // Moving the filesystem reader elsewhere between two reads must still be detected.
+        data_value
+            .seek(&mut testfs1, SeekFrom::Start(read_size as u64))
+            .unwrap();
+        testfs1.seek(SeekFrom::Start(0)).unwrap();
+        data_value.read_exact(&mut testfs1, &mut buf).unwrap();
+        assert_eq!(buf, data[read_size..2 * read_size]);
+
+        // So must reading another value in between.
+        let mut other_value = data_value.clone();
+        other_value.seek(&mut testfs1, SeekFrom::Start(0)).unwrap();
+        for i in 2..4 {
+            data_value.read_exact(&mut testfs1, &mut buf).unwrap();
+            assert_eq!(buf, data[i * read_size..(i + 1) * read_size]);
+
+            other_value.read_exact(&mut testfs1, &mut buf).unwrap();
+            assert_eq!(buf, data[(i - 2) * read_size..(i - 1) * read_size]);
+        }
+    }
+
+    #[test]
+    fn test_sparse_read_and_seek_skip_filesystem() {
+        let mut testfs1 = SeekCountingReader {
+            inner: crate::helpers::tests::testfs1(),
+            seek_count: 0,
+            access_count: 0,
+        };
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // Find the "sparse-file", whose second Data Run is a hole from 512 to 499712.
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "sparse-file")
+                .unwrap()
+                .unwrap();
+        let file = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        let data_attribute_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_attribute_item.to_attribute().unwrap();
+        let mut data_attribute_value = data_attribute.value(&mut testfs1).unwrap();
+
+        // Seeking into the hole and reading it in small chunks never accesses the filesystem.
+        testfs1.access_count = 0;
+        data_attribute_value
+            .seek(&mut testfs1, SeekFrom::Start(512))
+            .unwrap();
+
+        let mut buf = [0xffu8; 4096];
+        for i in 0..100 {
+            assert_eq!(
+                data_attribute_value.read(&mut testfs1, &mut buf).unwrap(),
+                4096
+            );
+            assert_eq!(buf, [0u8; 4096]);
+            assert_eq!(data_attribute_value.stream_position(), 512 + (i + 1) * 4096);
+            buf.fill(0xff);
+        }
+
+        data_attribute_value
+            .seek(&mut testfs1, SeekFrom::Current(10000))
+            .unwrap();
+        assert_eq!(testfs1.access_count, 0);
+
+        // The rest of the hole is filled in a single step.
+        let position = data_attribute_value.stream_position();
+        let mut buf = vec![0xffu8; (499712 - position) as usize];
+        assert_eq!(
+            data_attribute_value.read(&mut testfs1, &mut buf).unwrap(),
+            buf.len()
+        );
+        assert!(buf.iter().all(|b| *b == 0));
+        assert_eq!(testfs1.access_count, 0);
+
+        // Data after the hole is still read from the filesystem.
+        data_attribute_value
+            .seek(&mut testfs1, SeekFrom::Start(500000))
+            .unwrap();
+        let mut buf = [0u8; 10];
+        assert_eq!(
+            data_attribute_value.read(&mut testfs1, &mut buf).unwrap(),
+            5
+        );
+        assert_eq!(&buf[..5], b"11111");
+    }
+
+    #[test]
+    fn test_read_segment_error_context() {
+        let mut testfs1 = FailingReader {
+            inner: crate::helpers::tests::testfs1(),
+            fail_range: None,
+        };
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        let file = ntfs.file(&mut testfs1, 67).unwrap();
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+
+        // Make the first Data Run unreadable.
+        testfs1.fail_range = Some(0..u64::MAX);
+        let mut buf = [0u8; 512];
+        let error = data_value.read_segment(&mut testfs1, &mut buf).unwrap_err();
+        assert!(matches!(error.root_cause(), NtfsError::Io(_)));
+        assert!(matches!(
+            error,
+            NtfsError::ValueReadContext {
+                file_record_number: 67,
+                ty,
+                ..
+            } if ty == NtfsAttributeType::Data as u32
+        ));
+    }
+
+    #[test]
+    fn test_failed_read_keeps_position() {
+        let mut testfs1 = FailingReader {
+            inner: crate::helpers::tests::testfs1(),
+            fail_range: None,
+        };
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // "sparse-file" (File Record 67) consists of a Data Run with data, a sparse Data Run,
+        // and another Data Run with data at byte position 1815040.
+        let file = ntfs.file(&mut testfs1, 67).unwrap();
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+
+        let mut expected = vec![0u8; data_value.len() as usize];
+        data_value.read_exact(&mut testfs1, &mut expected).unwrap();
+        data_value.seek(&mut testfs1, SeekFrom::Start(100)).unwrap();
+
+        // Fail in the middle of the last Data Run, after everything before it has been read successfully.
+        testfs1.fail_range = Some(1815040 + 100..u64::MAX);
+        let mut buf = vec![0u8; expected.len() - 100];
+        let error = data_value.read(&mut testfs1, &mut buf).unwrap_err();
+        assert!(matches!(error.root_cause(), NtfsError::Io(_)));
+        assert_eq!(data_value.stream_position(), 100);
+
+        // Retrying the read at the same position returns the identical content.
+        testfs1.fail_range = None;
+        data_value.read_exact(&mut testfs1, &mut buf).unwrap();
+        assert_eq!(buf, expected[100..]);
+        assert_eq!(data_value.stream_position(), expected.len() as u64);
+    }
 }
@@ -7,9 +7,12 @@
 //! are always read into a buffer first and then fixed up in memory.
 //! Further accesses to the record data can then happen via slices.
 
-use binrw::io::{Read, Seek, SeekFrom};
+use core::ops::Range;
 
-use super::seek_contiguous;
+use crate::io::{Read, Seek, SeekFrom};
+use alloc::vec::Vec;
+
+use super::{seek_contiguous, NtfsPhysicalRange};
 use crate::error::Result;
 use crate::traits::NtfsReadSeek;
 use crate::types::NtfsPosition;
@@ -31,6 +34,27 @@ impl<'f> NtfsResidentAttributeValue<'f> {
         }
     }
 
+    /// Returns the location of the bytes in `logical_range` of this value within its File Record.
+    ///
+    /// See [`NtfsAttributeValue::byte_ranges`].
+    ///
+    /// [`NtfsAttributeValue::byte_ranges`]: crate::attribute_value::NtfsAttributeValue::byte_ranges
+    pub fn byte_ranges(&self, logical_range: Range<u64>) -> Vec<NtfsPhysicalRange> {
+        let start = logical_range.start;
+        let end = u64::min(logical_range.end, self.len());
+        let mut ranges = Vec::new();
+
+        if start < end {
+            ranges.push(NtfsPhysicalRange::Data {
+                logical_offset: start,
+                position: self.position + start,
+                length: end - start,
+            });
+        }
+
+        ranges
+    }
+
     /// Returns a slice of the entire value data.
     ///
     /// Remember that a resident attribute fits entirely inside the NTFS File Record
@@ -61,7 +85,7 @@ impl<'f> NtfsResidentAttributeValue<'f> {
         self.data.len() as u64
     }
 
-    fn remaining_len(&self) -> u64 {
+    pub(crate) fn remaining_len(&self) -> u64 {
         self.len().saturating_sub(self.stream_position)
     }
 }
@@ -101,7 +125,7 @@ impl<'f> NtfsReadSeek for NtfsResidentAttributeValue<'f> {
 
 #[cfg(test)]
 mod tests {
-    use binrw::io::SeekFrom;
+    use crate::io::SeekFrom;
 
     use crate::indexes::NtfsFileNameIndex;
     use crate::ntfs::Ntfs;
@@ -9,6 +9,16 @@ use memoffset::offset_of;
 use crate::error::{NtfsError, Result};
 use crate::types::{Lcn, NtfsPosition};
 
+/// Byte positions of boot sector fields, which are reported in errors.
+const OEM_NAME_POSITION: u64 = 0x03;
+const ZEROS_1_POSITION: u64 = 0x0e;
+const MEDIA_POSITION: u64 = 0x15;
+const ZEROS_2_POSITION: u64 = 0x16;
+const ZEROS_3_POSITION: u64 = 0x20;
+
+/// OEM name of every boot sector written by Windows and other NTFS implementations.
+const NTFS_OEM_NAME: &[u8] = b"NTFS    ";
+
 // Sources:
 // - https://en.wikipedia.org/wiki/NTFS#Partition_Boot_Sector_(VBR)
 // - https://en.wikipedia.org/wiki/BIOS_parameter_block#NTFS
@@ -68,7 +78,12 @@ impl BiosParameterBlock {
     }
 
     pub(crate) fn file_record_size(&self) -> Result<u32> {
-        self.record_size(self.file_record_size_info)
+        /// Windows never creates File Records larger than 4 KiB.
+        /// Allow a small factor above that, but deny anything larger to prevent allocating huge buffers
+        /// for every File Record we read.
+        const MAX_FILE_RECORD_SIZE: u32 = 16384;
+
+        self.record_size(self.file_record_size_info, MAX_FILE_RECORD_SIZE)
     }
 
     /// Returns the Logical Cluster Number (LCN) to the beginning of the Master File Table (MFT).
@@ -80,8 +95,19 @@ impl BiosParameterBlock {
         }
     }
 
+    /// Returns the Logical Cluster Number (LCN) to the beginning of the mirror copy of the Master File Table (MFT).
+    pub(crate) fn mft_mirror_lcn(&self) -> Result<Lcn> {
+        if self.mft_mirror_lcn.value() > 0 && self.mft_mirror_lcn != self.mft_lcn {
+            Ok(self.mft_mirror_lcn)
+        } else {
+            Err(NtfsError::InvalidMftMirrorLcn)
+        }
+    }
+
     /// Source: https://en.wikipedia.org/wiki/NTFS#Partition_Boot_Sector_(VBR)
-    fn record_size(&self, size_info: i8) -> Result<u32> {
+    ///
+    /// Sizes denoted by a cluster count must not exceed `max_size`.
+    fn record_size(&self, size_info: i8, max_size: u32) -> Result<u32> {
         // The usual exponent of `BiosParameterBlock::file_record_size_info` is 10 (2^10 = 1024 bytes).
         // For index records, it's usually 12 (2^12 = 4096 bytes).
 
@@ -98,8 +124,10 @@ impl BiosParameterBlock {
 
         if size_info > 0 {
             // The size field denotes a cluster count.
+            // With large clusters, even small counts would result in huge sizes.
             cluster_size
                 .checked_mul(size_info as u32)
+                .filter(|size| *size <= max_size)
                 .ok_or(NtfsError::InvalidRecordSizeInfo {
                     size_info,
                     cluster_size,
@@ -185,6 +213,37 @@ impl BiosParameterBlock {
     pub(crate) fn total_sectors(&self) -> u64 {
         self.total_sectors
     }
+
+    fn validate(&self) -> Result<()> {
+        // These fields are only used by FAT and must be zero for NTFS.
+        // This is the same set of fields that Windows and NTFS-3G check.
+        let zero_fields = [
+            (ZEROS_1_POSITION, self.zeros_1.iter().all(|b| *b == 0)),
+            (ZEROS_2_POSITION, self.zeros_2.iter().all(|b| *b == 0)),
+            (ZEROS_3_POSITION, self.zeros_3 == 0),
+        ];
+        for (position, is_zero) in zero_fields {
+            if !is_zero {
+                return Err(NtfsError::InvalidBootSectorReservedField {
+                    position: NtfsPosition::new(position),
+                });
+            }
+        }
+
+        // Valid media descriptors are 0xF0 and 0xF8 to 0xFF (NTFS always uses 0xF8 for hard disks).
+        if self.media != 0xf0 && self.media < 0xf8 {
+            return Err(NtfsError::InvalidMediaDescriptor {
+                position: NtfsPosition::new(MEDIA_POSITION),
+                actual: self.media,
+            });
+        }
+
+        if self.total_sectors == 0 {
+            return Err(NtfsError::InvalidTotalSectors);
+        }
+
+        Ok(())
+    }
 }
 
 #[allow(unused)]
@@ -213,6 +272,15 @@ impl BootSector {
             });
         }
 
-        Ok(())
+        // A boot sector with a valid signature may still belong to another filesystem.
+        if self.oem_name != NTFS_OEM_NAME {
+            return Err(NtfsError::InvalidBootSectorOemName {
+                position: NtfsPosition::new(OEM_NAME_POSITION),
+                expected: NTFS_OEM_NAME,
+                actual: self.oem_name,
+            });
+        }
+
+        self.bpb.validate()
     }
 }
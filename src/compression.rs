@@ -0,0 +1,303 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+//! Decompressors for the compression formats used by Windows Overlay Filter (WOF) compressed files.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, LittleEndian};
+
+/// Number of symbols of the XPRESS Huffman alphabet (256 literals and 256 match headers).
+const XPRESS_HUFFMAN_SYMBOL_COUNT: usize = 512;
+
+/// Size of the table of 4-bit code lengths that begins every XPRESS Huffman block.
+const XPRESS_HUFFMAN_TABLE_SIZE: usize = XPRESS_HUFFMAN_SYMBOL_COUNT / 2;
+
+/// Maximum length of an XPRESS Huffman code, in bits.
+const XPRESS_HUFFMAN_MAX_CODE_LENGTH: u32 = 15;
+
+/// Maximum number of bytes an XPRESS Huffman block can decompress to.
+pub(crate) const XPRESS_HUFFMAN_MAX_BLOCK_SIZE: usize = 65536;
+
+/// Lookup table for decoding canonical Huffman codes of up to [`XPRESS_HUFFMAN_MAX_CODE_LENGTH`] bits.
+///
+/// The next 15 bits of the input are used as index.
+/// Each entry stores the decoded symbol in the upper 12 bits and the length of its code in the lower 4 bits.
+/// A code length of zero marks bit sequences that are not assigned to any symbol.
+struct HuffmanDecoder {
+    table: Vec<u16>,
+}
+
+impl HuffmanDecoder {
+    fn new(code_lengths: &[u8]) -> Result<Self, &'static str> {
+        let mut table = vec![0u16; 1 << XPRESS_HUFFMAN_MAX_CODE_LENGTH];
+        let mut next_entry = 0;
+
+        // Canonical Huffman codes are assigned in the order of their lengths and then their symbols.
+        // Filling the table in the same order yields the correct entry for every code.
+        for code_length in 1..=XPRESS_HUFFMAN_MAX_CODE_LENGTH {
+            for (symbol, _) in code_lengths
+                .iter()
+                .enumerate()
+                .filter(|(_, length)| **length as u32 == code_length)
+            {
+                let entry_count = 1 << (XPRESS_HUFFMAN_MAX_CODE_LENGTH - code_length);
+                let entries = table
+                    .get_mut(next_entry..next_entry + entry_count)
+                    .ok_or("the Huffman code lengths are oversubscribed")?;
+                entries.fill((symbol as u16) << 4 | code_length as u16);
+                next_entry += entry_count;
+            }
+        }
+
+        if next_entry == 0 {
+            return Err("the Huffman code has no symbols");
+        }
+
+        Ok(Self { table })
+    }
+
+    /// Decodes the symbol at the beginning of the given 32 bits and returns it along with its code length.
+    fn decode(&self, bits: u32) -> Result<(u16, u32), &'static str> {
+        let entry = self.table[(bits >> (32 - XPRESS_HUFFMAN_MAX_CODE_LENGTH)) as usize];
+        let code_length = (entry & 0xf) as u32;
+        if code_length == 0 {
+            return Err("invalid Huffman code");
+        }
+
+        Ok((entry >> 4, code_length))
+    }
+}
+
+/// Reader for the XPRESS Huffman bitstream, which interleaves 16-bit little-endian words of bits
+/// with whole bytes (for long match lengths).
+struct BitReader<'a> {
+    input: &'a [u8],
+    position: usize,
+    /// The next bits of the input, starting at the most significant bit.
+    next_bits: u32,
+    /// Number of valid bits in `next_bits` beyond the first 16.
+    extra_bit_count: i32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(input: &'a [u8], position: usize) -> Self {
+        let mut reader = Self {
+            input,
+            position,
+            next_bits: 0,
+            extra_bit_count: 16,
+        };
+        reader.next_bits = reader.read_word() << 16;
+        reader.next_bits |= reader.read_word();
+        reader
+    }
+
+    fn consume(&mut self, bit_count: u32) {
+        if bit_count == 0 {
+            return;
+        }
+
+        self.next_bits <<= bit_count;
+        self.extra_bit_count -= bit_count as i32;
+
+        if self.extra_bit_count < 0 {
+            self.next_bits |= self.read_word() << -self.extra_bit_count;
+            self.extra_bit_count += 16;
+        }
+    }
+
+    /// Returns the next `bit_count` bits (at most 16) without consuming them.
+    fn peek(&self, bit_count: u32) -> u32 {
+        if bit_count == 0 {
+            0
+        } else {
+            self.next_bits >> (32 - bit_count)
+        }
+    }
+
+    fn read_byte(&mut self) -> Result<u8, &'static str> {
+        let byte = *self
+            .input
+            .get(self.position)
+            .ok_or("the compressed data ends within a match length")?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    /// Reads the next 16-bit word of bits.
+    ///
+    /// Compressors may end the bitstream early when all remaining bits are unused.
+    /// Therefore, reading beyond the end of the input yields zeros.
+    fn read_word(&mut self) -> u32 {
+        let word = match self.input.get(self.position..self.position + 2) {
+            Some(bytes) => LittleEndian::read_u16(bytes) as u32,
+            None => 0,
+        };
+        self.position += 2;
+        word
+    }
+
+    fn read_u16(&mut self) -> Result<u16, &'static str> {
+        let bytes = self
+            .input
+            .get(self.position..self.position + 2)
+            .ok_or("the compressed data ends within a match length")?;
+        self.position += 2;
+        Ok(LittleEndian::read_u16(bytes))
+    }
+}
+
+/// Decompresses a single XPRESS Huffman block (called "LZ77+Huffman" in [MS-XCA]) from `input` into `output`.
+///
+/// `output` must have the exact size of the uncompressed data, which must not exceed
+/// [`XPRESS_HUFFMAN_MAX_BLOCK_SIZE`].
+/// On error, a reason is returned for the caller to wrap into an [`NtfsError`].
+///
+/// Reference: <https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-xca/a8b7cb0a-92a6-4187-a23b-5e14273b96f8>
+///
+/// [MS-XCA]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-xca
+/// [`NtfsError`]: crate::NtfsError
+pub(crate) fn decompress_xpress_huffman(
+    input: &[u8],
+    output: &mut [u8],
+) -> Result<(), &'static str> {
+    debug_assert!(output.len() <= XPRESS_HUFFMAN_MAX_BLOCK_SIZE);
+
+    if output.is_empty() {
+        return Ok(());
+    }
+
+    let table = input
+        .get(..XPRESS_HUFFMAN_TABLE_SIZE)
+        .ok_or("the compressed data ends within the Huffman table")?;
+
+    // Every byte stores the 4-bit code lengths of two consecutive symbols, the lower nibble coming first.
+    let code_lengths = table
+        .iter()
+        .flat_map(|byte| [byte & 0xf, byte >> 4])
+        .collect::<Vec<u8>>();
+    let decoder = HuffmanDecoder::new(&code_lengths)?;
+
+    let mut reader = BitReader::new(input, XPRESS_HUFFMAN_TABLE_SIZE);
+    let mut output_position = 0;
+
+    while output_position < output.len() {
+        let (symbol, code_length) = decoder.decode(reader.next_bits)?;
+        reader.consume(code_length);
+
+        if symbol < 256 {
+            output[output_position] = symbol as u8;
+            output_position += 1;
+            continue;
+        }
+
+        // A match header stores the number of offset bits in the upper and the match length in the lower 4 bits.
+        // The end-of-stream symbol 256 never needs to be decoded, because we know the uncompressed size.
+        let match_header = symbol - 256;
+        let offset_bit_count = (match_header >> 4) as u32;
+        let mut match_length = (match_header & 0xf) as usize;
+
+        if match_length == 15 {
+            match_length = reader.read_byte()? as usize;
+
+            if match_length == 255 {
+                match_length = reader.read_u16()? as usize;
+                match_length = match_length
+                    .checked_sub(15)
+                    .ok_or("invalid long match length")?;
+            }
+
+            match_length += 15;
+        }
+
+        match_length += 3;
+
+        let match_offset = (reader.peek(offset_bit_count) | 1 << offset_bit_count) as usize;
+        reader.consume(offset_bit_count);
+
+        if match_offset > output_position {
+            return Err("the match offset points before the beginning of the data");
+        }
+
+        // Matches may overlap the bytes they produce, so this needs to be copied byte by byte.
+        let match_end = usize::min(output_position + match_length, output.len());
+        while output_position < match_end {
+            output[output_position] = output[output_position - match_offset];
+            output_position += 1;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    use std::fs::File;
+    use std::io::Read;
+
+    /// The first 4096 bytes of the repeated sentence "The quick brown fox jumps over the lazy dog. ".
+    pub(crate) fn fox_data() -> Vec<u8> {
+        let mut data = b"The quick brown fox jumps over the lazy dog. ".repeat(100);
+        data.truncate(4096);
+        data
+    }
+
+    /// [`fox_data`] compressed with XPRESS Huffman.
+    ///
+    /// This exercises literals with codes of different lengths and a long match whose length is stored
+    /// in an additional 16-bit word.
+    pub(crate) fn fox_data_compressed() -> Vec<u8> {
+        let mut buffer = Vec::new();
+        File::open("testdata/xpress4k_fox")
+            .unwrap()
+            .read_to_end(&mut buffer)
+            .unwrap();
+        buffer
+    }
+
+    #[test]
+    fn test_xpress_huffman() {
+        let compressed = fox_data_compressed();
+        let mut output = vec![0u8; 4096];
+        decompress_xpress_huffman(&compressed, &mut output).unwrap();
+        assert_eq!(output, fox_data());
+
+        // Decompressing just a part of the data also works.
+        let mut output = vec![0u8; 100];
+        decompress_xpress_huffman(&compressed, &mut output).unwrap();
+        assert_eq!(output, fox_data()[..100]);
+    }
+
+    #[test]
+    fn test_xpress_huffman_invalid() {
+        let compressed = fox_data_compressed();
+        let mut output = vec![0u8; 4096];
+
+        // The Huffman table is truncated.
+        assert!(decompress_xpress_huffman(&compressed[..200], &mut output).is_err());
+
+        // The Huffman table has no symbols.
+        assert!(decompress_xpress_huffman(&[0u8; 260], &mut output).is_err());
+
+        // The Huffman table is oversubscribed (512 codes with a length of 1 bit).
+        assert!(decompress_xpress_huffman(&[0x11u8; 260], &mut output).is_err());
+
+        // All 512 symbols get 9-bit codes (equal to the symbol), and the first symbol is a match
+        // with offset 1, which points before the beginning of the data.
+        let mut input = vec![0x99u8; XPRESS_HUFFMAN_TABLE_SIZE];
+        input.extend_from_slice(&(256u16 << 7).to_le_bytes());
+        input.extend_from_slice(&[0u8; 2]);
+        assert!(decompress_xpress_huffman(&input, &mut output).is_err());
+
+        // The same with a literal first, so that the match is valid.
+        let mut input = vec![0x99u8; XPRESS_HUFFMAN_TABLE_SIZE];
+        input.extend_from_slice(&((b'a' as u16) << 7 | 256 >> 2).to_le_bytes());
+        input.extend_from_slice(&[0u8; 2]);
+        let mut output = vec![0u8; 4];
+        decompress_xpress_huffman(&input, &mut output).unwrap();
+        assert_eq!(&output, b"aaaa");
+    }
+}
@@ -1,11 +1,13 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use alloc::boxed::Box;
 use core::ops::Range;
 
 use displaydoc::Display;
 
 use crate::attribute::NtfsAttributeType;
+use crate::structured_values::NtfsCollationRule;
 use crate::types::NtfsPosition;
 use crate::types::{Lcn, Vcn};
 
@@ -16,6 +18,16 @@ pub type Result<T, E = NtfsError> = core::result::Result<T, E>;
 #[derive(Debug, Display)]
 #[non_exhaustive]
 pub enum NtfsError {
+    /// The NTFS file at byte position {position:#x} has no attribute with instance {instance}
+    AttributeInstanceNotFound {
+        position: NtfsPosition,
+        instance: u16,
+    },
+    /// The NTFS Attribute at byte position {position:#x} has type {ty:?} and is non-resident, but $AttrDef requires it to be resident
+    AttributeMustBeResident {
+        position: NtfsPosition,
+        ty: NtfsAttributeType,
+    },
     /// The NTFS file at byte position {position:#x} has no attribute of type {ty:?}, but it was expected
     AttributeNotFound {
         position: NtfsPosition,
@@ -27,14 +39,56 @@ pub enum NtfsError {
         expected: NtfsAttributeType,
         actual: NtfsAttributeType,
     },
+    /// The NTFS Attribute at byte position {position:#x} has type {ty:?}, which is not defined in $AttrDef
+    AttributeTypeNotDefined {
+        position: NtfsPosition,
+        ty: NtfsAttributeType,
+    },
+    /// The read at byte position {position:#x} covers the Logical Cluster Number (LCN) {lcn}, which is marked as bad in the $BadClus file
+    BadClusterRead { lcn: Lcn, position: NtfsPosition },
+    /// The read at byte position {position:#x} lies beyond the end of the reader, which only has {available} bytes
+    BeyondBackingStorage {
+        position: NtfsPosition,
+        available: u64,
+    },
     /// The given buffer should have at least {expected} bytes, but it only has {actual} bytes
     BufferTooSmall { expected: usize, actual: usize },
+    /// The index at byte position {position:#x} should use the collation rule {expected:?}, but it uses {actual:?}
+    CollationMismatch {
+        position: NtfsPosition,
+        expected: NtfsCollationRule,
+        actual: NtfsCollationRule,
+    },
+    /// The Data Run at byte position {position:#x} ends at byte {end:#x}, which is beyond the volume size of {volume_size:#x} bytes
+    DataRunBeyondVolume {
+        position: NtfsPosition,
+        end: u64,
+        volume_size: u64,
+    },
+    /// The NTFS file at byte position {position:#x} is a directory according to its File Record flags ({file_record_is_directory}), but its $FILE_NAME attribute says {file_name_is_directory}
+    DirectoryFlagMismatch {
+        position: NtfsPosition,
+        file_record_is_directory: bool,
+        file_name_is_directory: bool,
+    },
+    /// The NTFS File Record {file_record_number} is not in use
+    FileRecordNotInUse { file_record_number: u64 },
+    /// The index allocation at byte position {position:#x} references a Virtual Cluster Number (VCN) {vcn}, but the Index Record at that VCN is not marked as in use in the $BITMAP attribute of the index
+    IndexRecordNotAllocated { position: NtfsPosition, vcn: Vcn },
+    /// The ACE at byte offset {offset:#x} of the security descriptor has a size of {size} bytes, which is too small for its contents
+    InvalidAceSize { offset: usize, size: u16 },
     /// The NTFS Attribute at byte position {position:#x} has a length of {expected} bytes, but only {actual} bytes are left in the record
     InvalidAttributeLength {
         position: NtfsPosition,
         expected: usize,
         actual: usize,
     },
+    /// The NTFS Attribute List entry at byte position {position:#x} has a lowest VCN of {lowest_vcn}, but the previous entry of the same attribute already has a lowest VCN of {previous_lowest_vcn}
+    InvalidAttributeListEntryOrder {
+        position: NtfsPosition,
+        lowest_vcn: Vcn,
+        previous_lowest_vcn: Vcn,
+    },
     /// The NTFS Attribute at byte position {position:#x} indicates a name length up to offset {expected}, but the attribute only has a size of {actual} bytes
     InvalidAttributeNameLength {
         position: NtfsPosition,
@@ -47,6 +101,22 @@ pub enum NtfsError {
         expected: u16,
         actual: u32,
     },
+    /// The NTFS Attribute at byte position {position:#x} has type {ty:?} and a value length of {actual} bytes, but $AttrDef only allows {minimum} to {maximum} bytes
+    InvalidAttributeValueLength {
+        position: NtfsPosition,
+        ty: NtfsAttributeType,
+        minimum: u64,
+        maximum: u64,
+        actual: u64,
+    },
+    /// The boot sector should have OEM name {expected:?} at byte position {position:#x}, but it has {actual:?}
+    InvalidBootSectorOemName {
+        position: NtfsPosition,
+        expected: &'static [u8],
+        actual: [u8; 8],
+    },
+    /// The boot sector has a nonzero value in the reserved field at byte position {position:#x}
+    InvalidBootSectorReservedField { position: NtfsPosition },
     /// The NTFS Data Run header at byte position {position:#x} indicates a maximum byte count of {expected}, but {actual} is the limit
     InvalidByteCountInDataRunHeader {
         position: NtfsPosition,
@@ -58,6 +128,11 @@ pub enum NtfsError {
         position: NtfsPosition,
         cluster_count: u64,
     },
+    /// The compressed data at byte position {position:#x} is invalid: {reason}
+    InvalidCompressedData {
+        position: NtfsPosition,
+        reason: &'static str,
+    },
     /// The NTFS File Record at byte position {position:#x} indicates an allocated size of {expected} bytes, but the record only has a size of {actual} bytes
     InvalidFileAllocatedSize {
         position: NtfsPosition,
@@ -66,6 +141,8 @@ pub enum NtfsError {
     },
     /// The requested NTFS File Record Number {file_record_number} is invalid
     InvalidFileRecordNumber { file_record_number: u64 },
+    /// The File Record at byte position {position:#x} does not fit into the filesystem
+    InvalidFileRecordPosition { position: NtfsPosition },
     /// The NTFS File Record at byte position {position:#x} should have signature {expected:?}, but it has signature {actual:?}
     InvalidFileSignature {
         position: NtfsPosition,
@@ -78,7 +155,7 @@ pub enum NtfsError {
         expected: u32,
         actual: u32,
     },
-    /// The NTFS Index Record at byte position {position:#x} indicates an allocated size of {expected} bytes, but the record only has a size of {actual} bytes
+    /// The NTFS Index Record at byte position {position:#x} should have an allocated size of {expected} bytes, but it indicates {actual} bytes
     InvalidIndexAllocatedSize {
         position: NtfsPosition,
         expected: u32,
@@ -96,6 +173,8 @@ pub enum NtfsError {
         expected: u16,
         actual: u16,
     },
+    /// The NTFS index at byte position {position:#x} uses an Index Record size of {size} bytes, which is not a power of two between 512 and 65536
+    InvalidIndexRecordSize { position: NtfsPosition, size: u32 },
     /// The NTFS index root at byte position {position:#x} indicates that its entries start at offset {expected}, but the index root only has a size of {actual} bytes
     InvalidIndexRootEntriesOffset {
         position: NtfsPosition,
@@ -120,14 +199,37 @@ pub enum NtfsError {
         expected: u32,
         actual: u32,
     },
-    /// The MFT LCN in the BIOS Parameter Block of the NTFS filesystem is invalid.
+    /// The $LogFile restart page at byte position {position:#x} references a restart area at offset {offset}, which does not fit into the page size of {page_size} bytes
+    InvalidLogFileRestartAreaOffset {
+        position: NtfsPosition,
+        offset: u16,
+        page_size: u32,
+    },
+    /// The $LogFile restart page at byte position {position:#x} should have signature {expected:?}, but it has signature {actual:?}
+    InvalidLogFileRestartPageSignature {
+        position: NtfsPosition,
+        expected: &'static [u8],
+        actual: [u8; 4],
+    },
+    /// The $LogFile restart page at byte position {position:#x} indicates a page size of {size} bytes, which is not a power of two between 512 and 65536
+    InvalidLogFileRestartPageSize { position: NtfsPosition, size: u32 },
+    /// The boot sector has an invalid media descriptor {actual:#04x} at byte position {position:#x}
+    InvalidMediaDescriptor { position: NtfsPosition, actual: u8 },
+    /// The MFT LCN in the BIOS Parameter Block of the NTFS filesystem is invalid or outside the filesystem.
     InvalidMftLcn,
+    /// The MFT Mirror LCN in the BIOS Parameter Block of the NTFS filesystem is invalid, outside the filesystem, or equal to the MFT LCN.
+    InvalidMftMirrorLcn,
     /// The NTFS Non Resident Value Data at byte position {position:#x} references a data field in the range {range:?}, but the entry only has a size of {size} bytes
     InvalidNonResidentValueDataRange {
         position: NtfsPosition,
         range: Range<usize>,
         size: usize,
     },
+    /// The $FILE_NAME attribute at byte position {position:#x} references File Record {parent_file_record_number} as its parent directory, but that File Record is not an in-use directory
+    InvalidParentDirectoryReference {
+        position: NtfsPosition,
+        parent_file_record_number: u64,
+    },
     /// The resident NTFS Attribute at byte position {position:#x} indicates a value length of {length} starting at offset {offset}, but the attribute only has a size of {actual} bytes
     InvalidResidentAttributeValueLength {
         position: NtfsPosition,
@@ -145,6 +247,10 @@ pub enum NtfsError {
     InvalidRecordSizeInfo { size_info: i8, cluster_size: u32 },
     /// The sectors per cluster field in the BIOS Parameter Block denotes {sectors_per_cluster:#04x}, which is invalid
     InvalidSectorsPerCluster { sectors_per_cluster: u8 },
+    /// A field of the security descriptor references the range {range:?}, but the security descriptor only has a size of {size} bytes
+    InvalidSecurityDescriptorRange { range: Range<usize>, size: usize },
+    /// The SID has {count} sub authorities, but at most 15 are allowed
+    InvalidSidSubAuthorityCount { count: u8 },
     /// The NTFS structured value at byte position {position:#x} of type {ty:?} has {actual} bytes where {expected} bytes were expected
     InvalidStructuredValueSize {
         position: NtfsPosition,
@@ -154,6 +260,8 @@ pub enum NtfsError {
     },
     /// The given time can't be represented as an NtfsTime
     InvalidTime,
+    /// The BIOS Parameter Block of the NTFS filesystem indicates a total sector count of zero.
+    InvalidTotalSectors,
     /// The 2-byte signature field at byte position {position:#x} should contain {expected:?}, but it contains {actual:?}
     InvalidTwoByteSignature {
         position: NtfsPosition,
@@ -180,15 +288,38 @@ pub enum NtfsError {
         previous_lcn: Lcn,
     },
     /// I/O error: {0:?}
-    Io(binrw::io::Error),
+    Io(crate::io::Error),
     /// The Logical Cluster Number (LCN) {lcn} is too big to be multiplied by the cluster size
     LcnTooBig { lcn: Lcn },
     /// The index root at byte position {position:#x} is a large index, but no matching index allocation attribute was provided
     MissingIndexAllocation { position: NtfsPosition },
-    /// The NTFS file at byte position {position:#x} is not a directory
-    NotADirectory { position: NtfsPosition },
+    /// The NTFS file at byte position {position:#x} is not a directory (the referencing Index Entry says {index_entry_is_directory:?})
+    NotADirectory {
+        position: NtfsPosition,
+        index_entry_is_directory: Option<bool>,
+    },
+    /// The NTFS timestamp {nt_timestamp} is out of the range of the requested date/time type
+    NtfsTimeOutOfRange { nt_timestamp: u64 },
+    /// The name and the value of the NTFS attribute at byte position {position:#x} overlap each other or its header
+    OverlappingAttributeFields { position: NtfsPosition },
+    /// An on-disk structure could not be parsed: {reason}
+    ParseError {
+        position: Option<NtfsPosition>,
+        reason: &'static str,
+        source: Option<Box<binrw::error::Error>>,
+    },
+    /// The resident attribute at byte position {position:#x} can hold at most {capacity} bytes, but {actual} bytes were given
+    ResidentValueTooLarge {
+        position: NtfsPosition,
+        capacity: u32,
+        actual: usize,
+    },
+    /// Classifying a position requires the regions of the system files, but they have not been read
+    SystemRegionsNotLoaded,
     /// The total sector count is too big to be multiplied by the sector size
     TotalSectorsTooBig { total_sectors: u64 },
+    /// The NTFS Attribute at byte position {position:#x} has a length of {length} bytes, which is not a multiple of 8
+    UnalignedAttributeLength { position: NtfsPosition, length: u32 },
     /// The NTFS Attribute at byte position {position:#x} should not belong to an Attribute List, but it does
     UnexpectedAttributeListAttribute { position: NtfsPosition },
     /// The NTFS Attribute at byte position {position:#x} should be resident, but it is non-resident
@@ -199,10 +330,22 @@ pub enum NtfsError {
     UnsupportedAttributeType { position: NtfsPosition, actual: u32 },
     /// The cluster size is {actual} bytes, but it needs to be between {min} and {max}
     UnsupportedClusterSize { min: u32, max: u32, actual: u32 },
+    /// The collation rule of the index at byte position {position:#x} is {actual:#x}, which is not supported
+    UnsupportedCollationRule { position: NtfsPosition, actual: u32 },
     /// The namespace of the NTFS file name starting at byte position {position:#x} is {actual}, which is not supported
     UnsupportedFileNamespace { position: NtfsPosition, actual: u8 },
+    /// The File Record size is {actual} bytes, but it needs to be a power of two between {min} and {max}
+    UnsupportedFileRecordSize { min: u32, max: u32, actual: u32 },
     /// The sector size is {actual} bytes, but it needs to be between {min} and {max}
     UnsupportedSectorSize { min: u16, max: u16, actual: u16 },
+    /// The security descriptor has revision {revision}, but only revision 1 is supported
+    UnsupportedSecurityDescriptorRevision { revision: u8 },
+    /// The Windows Overlay Filter (WOF) compression algorithm {actual} of the file at byte position {position:#x} is not supported
+    UnsupportedWofAlgorithm { position: NtfsPosition, actual: u32 },
+    /// The Windows Overlay Filter (WOF) provider {actual} of the file at byte position {position:#x} is not supported
+    UnsupportedWofProvider { position: NtfsPosition, actual: u32 },
+    /// A case-insensitive comparison requires the $UpCase table, but it has not been read
+    UpcaseTableNotLoaded,
     /// The Update Sequence Array (USA) of the record at byte position {position:#x} has entries for {array_count} blocks of 512 bytes, but the record is only {record_size} bytes long
     UpdateSequenceArrayExceedsRecordSize {
         position: NtfsPosition,
@@ -215,6 +358,12 @@ pub enum NtfsError {
         expected: [u8; 2],
         actual: [u8; 2],
     },
+    /// Reading the value of the attribute of type {ty:#x} in File Record {file_record_number} failed: {source}
+    ValueReadContext {
+        file_record_number: u64,
+        ty: u32,
+        source: Box<NtfsError>,
+    },
     /// The index allocation at byte position {position:#x} references a Virtual Cluster Number (VCN) {expected}, but a record with VCN {actual} is found at that offset
     VcnMismatchInIndexAllocation {
         position: NtfsPosition,
@@ -225,37 +374,1190 @@ pub enum NtfsError {
     VcnOutOfBoundsInIndexAllocation { position: NtfsPosition, vcn: Vcn },
     /// The Virtual Cluster Number (VCN) {vcn} is too big to be multiplied by the cluster size
     VcnTooBig { vcn: Vcn },
+    /// The Logical Cluster Number (LCN) {lcn} cannot be accessed, because only a dump of the Master File Table (MFT) is available
+    VolumeDataUnavailable { lcn: Lcn },
+}
+
+/// Category of an [`NtfsError`], returned by [`NtfsError::kind`].
+///
+/// While new [`NtfsError`] variants may be added over time, this list of categories is meant to stay stable.
+/// It allows applications to handle errors by category without matching on individual variants.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NtfsErrorKind {
+    /// An I/O error occurred while accessing the filesystem reader,
+    /// or a read covered a cluster marked as bad in the $BadClus file.
+    Io,
+    /// A File Record, the boot sector, or another record-based on-disk structure is corrupted.
+    CorruptRecord,
+    /// An attribute or attribute value is corrupted, inconsistent, or missing.
+    CorruptAttribute,
+    /// An index structure (Index Root, Index Allocation, Index Record, or Index Entry) is corrupted.
+    CorruptIndex,
+    /// The filesystem uses a feature or parameter that is not supported by this crate.
+    Unsupported,
+    /// An argument passed by the caller is invalid.
+    InvalidArgument,
+}
+
+impl NtfsError {
+    /// Returns the [`NtfsErrorKind`] category of this error.
+    ///
+    /// For a [`NtfsError::ValueReadContext`], this is the category of the wrapped error.
+    pub fn kind(&self) -> NtfsErrorKind {
+        match self {
+            Self::BadClusterRead { .. }
+            | Self::BeyondBackingStorage { .. }
+            | Self::Io(_)
+            | Self::VolumeDataUnavailable { .. } => NtfsErrorKind::Io,
+
+            Self::DirectoryFlagMismatch { .. }
+            | Self::InvalidBootSectorOemName { .. }
+            | Self::InvalidBootSectorReservedField { .. }
+            | Self::InvalidFileAllocatedSize { .. }
+            | Self::InvalidFileSignature { .. }
+            | Self::InvalidFileUsedSize { .. }
+            | Self::InvalidLogFileRestartAreaOffset { .. }
+            | Self::InvalidLogFileRestartPageSignature { .. }
+            | Self::InvalidLogFileRestartPageSize { .. }
+            | Self::InvalidMediaDescriptor { .. }
+            | Self::InvalidMftLcn
+            | Self::InvalidMftMirrorLcn
+            | Self::InvalidRecordSizeInfo { .. }
+            | Self::InvalidSectorsPerCluster { .. }
+            | Self::InvalidTotalSectors
+            | Self::InvalidTwoByteSignature { .. }
+            | Self::InvalidUpdateSequenceCount { .. }
+            | Self::InvalidUpdateSequenceNumberRange { .. }
+            | Self::ParseError { .. }
+            | Self::TotalSectorsTooBig { .. }
+            | Self::UpdateSequenceArrayExceedsRecordSize { .. }
+            | Self::UpdateSequenceNumberMismatch { .. } => NtfsErrorKind::CorruptRecord,
+
+            Self::AttributeMustBeResident { .. }
+            | Self::AttributeNotFound { .. }
+            | Self::AttributeTypeNotDefined { .. }
+            | Self::DataRunBeyondVolume { .. }
+            | Self::InvalidAceSize { .. }
+            | Self::InvalidAttributeLength { .. }
+            | Self::InvalidAttributeListEntryOrder { .. }
+            | Self::InvalidAttributeNameLength { .. }
+            | Self::InvalidAttributeNameOffset { .. }
+            | Self::InvalidAttributeValueLength { .. }
+            | Self::InvalidByteCountInDataRunHeader { .. }
+            | Self::InvalidClusterCountInDataRunHeader { .. }
+            | Self::InvalidCompressedData { .. }
+            | Self::InvalidNonResidentValueDataRange { .. }
+            | Self::InvalidParentDirectoryReference { .. }
+            | Self::InvalidResidentAttributeValueLength { .. }
+            | Self::InvalidResidentAttributeValueOffset { .. }
+            | Self::InvalidSecurityDescriptorRange { .. }
+            | Self::InvalidSidSubAuthorityCount { .. }
+            | Self::InvalidStructuredValueSize { .. }
+            | Self::InvalidUpcaseTableSize { .. }
+            | Self::InvalidVcnInDataRunHeader { .. }
+            | Self::LcnTooBig { .. }
+            | Self::OverlappingAttributeFields { .. }
+            | Self::UnalignedAttributeLength { .. }
+            | Self::UnexpectedAttributeListAttribute { .. }
+            | Self::UnexpectedNonResidentAttribute { .. }
+            | Self::UnexpectedResidentAttribute { .. }
+            | Self::VcnTooBig { .. } => NtfsErrorKind::CorruptAttribute,
+
+            Self::IndexRecordNotAllocated { .. }
+            | Self::InvalidIndexAllocatedSize { .. }
+            | Self::InvalidIndexEntryDataRange { .. }
+            | Self::InvalidIndexEntrySize { .. }
+            | Self::InvalidIndexRecordSize { .. }
+            | Self::InvalidIndexRootEntriesOffset { .. }
+            | Self::InvalidIndexRootUsedSize { .. }
+            | Self::InvalidIndexSignature { .. }
+            | Self::InvalidIndexUsedSize { .. }
+            | Self::MissingIndexAllocation { .. }
+            | Self::VcnMismatchInIndexAllocation { .. }
+            | Self::VcnOutOfBoundsInIndexAllocation { .. } => NtfsErrorKind::CorruptIndex,
+
+            Self::UnsupportedAttributeType { .. }
+            | Self::UnsupportedClusterSize { .. }
+            | Self::UnsupportedCollationRule { .. }
+            | Self::UnsupportedFileNamespace { .. }
+            | Self::UnsupportedFileRecordSize { .. }
+            | Self::UnsupportedSectorSize { .. }
+            | Self::UnsupportedSecurityDescriptorRevision { .. }
+            | Self::UnsupportedWofAlgorithm { .. }
+            | Self::UnsupportedWofProvider { .. } => NtfsErrorKind::Unsupported,
+
+            Self::AttributeInstanceNotFound { .. }
+            | Self::AttributeOfDifferentType { .. }
+            | Self::BufferTooSmall { .. }
+            | Self::CollationMismatch { .. }
+            | Self::FileRecordNotInUse { .. }
+            | Self::InvalidFileRecordNumber { .. }
+            | Self::InvalidFileRecordPosition { .. }
+            | Self::InvalidTime
+            | Self::NotADirectory { .. }
+            | Self::NtfsTimeOutOfRange { .. }
+            | Self::ResidentValueTooLarge { .. }
+            | Self::SystemRegionsNotLoaded
+            | Self::UpcaseTableNotLoaded => NtfsErrorKind::InvalidArgument,
+
+            Self::ValueReadContext { source, .. } => source.kind(),
+        }
+    }
+
+    /// Returns the absolute byte position on the filesystem where this error occurred,
+    /// or `None` if this error variant carries no position.
+    ///
+    /// For a [`NtfsError::ValueReadContext`], this is the position of the wrapped error.
+    pub fn position(&self) -> Option<NtfsPosition> {
+        match self {
+            Self::AttributeInstanceNotFound { position, .. }
+            | Self::AttributeMustBeResident { position, .. }
+            | Self::AttributeNotFound { position, .. }
+            | Self::AttributeOfDifferentType { position, .. }
+            | Self::AttributeTypeNotDefined { position, .. }
+            | Self::BadClusterRead { position, .. }
+            | Self::BeyondBackingStorage { position, .. }
+            | Self::CollationMismatch { position, .. }
+            | Self::DataRunBeyondVolume { position, .. }
+            | Self::DirectoryFlagMismatch { position, .. }
+            | Self::IndexRecordNotAllocated { position, .. }
+            | Self::InvalidAttributeLength { position, .. }
+            | Self::InvalidAttributeListEntryOrder { position, .. }
+            | Self::InvalidAttributeNameLength { position, .. }
+            | Self::InvalidAttributeNameOffset { position, .. }
+            | Self::InvalidAttributeValueLength { position, .. }
+            | Self::InvalidBootSectorOemName { position, .. }
+            | Self::InvalidBootSectorReservedField { position }
+            | Self::InvalidByteCountInDataRunHeader { position, .. }
+            | Self::InvalidClusterCountInDataRunHeader { position, .. }
+            | Self::InvalidCompressedData { position, .. }
+            | Self::InvalidFileAllocatedSize { position, .. }
+            | Self::InvalidFileRecordPosition { position }
+            | Self::InvalidFileSignature { position, .. }
+            | Self::InvalidFileUsedSize { position, .. }
+            | Self::InvalidIndexAllocatedSize { position, .. }
+            | Self::InvalidIndexEntryDataRange { position, .. }
+            | Self::InvalidIndexEntrySize { position, .. }
+            | Self::InvalidIndexRecordSize { position, .. }
+            | Self::InvalidIndexRootEntriesOffset { position, .. }
+            | Self::InvalidIndexRootUsedSize { position, .. }
+            | Self::InvalidIndexSignature { position, .. }
+            | Self::InvalidIndexUsedSize { position, .. }
+            | Self::InvalidLogFileRestartAreaOffset { position, .. }
+            | Self::InvalidLogFileRestartPageSignature { position, .. }
+            | Self::InvalidLogFileRestartPageSize { position, .. }
+            | Self::InvalidMediaDescriptor { position, .. }
+            | Self::InvalidNonResidentValueDataRange { position, .. }
+            | Self::InvalidParentDirectoryReference { position, .. }
+            | Self::InvalidResidentAttributeValueLength { position, .. }
+            | Self::InvalidResidentAttributeValueOffset { position, .. }
+            | Self::InvalidStructuredValueSize { position, .. }
+            | Self::InvalidTwoByteSignature { position, .. }
+            | Self::InvalidUpdateSequenceCount { position, .. }
+            | Self::InvalidUpdateSequenceNumberRange { position, .. }
+            | Self::InvalidVcnInDataRunHeader { position, .. }
+            | Self::MissingIndexAllocation { position }
+            | Self::NotADirectory { position, .. }
+            | Self::OverlappingAttributeFields { position }
+            | Self::ResidentValueTooLarge { position, .. }
+            | Self::UnalignedAttributeLength { position, .. }
+            | Self::UnexpectedAttributeListAttribute { position }
+            | Self::UnexpectedNonResidentAttribute { position }
+            | Self::UnexpectedResidentAttribute { position }
+            | Self::UnsupportedAttributeType { position, .. }
+            | Self::UnsupportedCollationRule { position, .. }
+            | Self::UnsupportedFileNamespace { position, .. }
+            | Self::UnsupportedWofAlgorithm { position, .. }
+            | Self::UnsupportedWofProvider { position, .. }
+            | Self::UpdateSequenceArrayExceedsRecordSize { position, .. }
+            | Self::UpdateSequenceNumberMismatch { position, .. }
+            | Self::VcnMismatchInIndexAllocation { position, .. }
+            | Self::VcnOutOfBoundsInIndexAllocation { position, .. } => Some(*position),
+
+            Self::ParseError { position, .. } => *position,
+
+            Self::BufferTooSmall { .. }
+            | Self::FileRecordNotInUse { .. }
+            | Self::InvalidAceSize { .. }
+            | Self::InvalidFileRecordNumber { .. }
+            | Self::InvalidMftLcn
+            | Self::InvalidMftMirrorLcn
+            | Self::InvalidRecordSizeInfo { .. }
+            | Self::InvalidSectorsPerCluster { .. }
+            | Self::InvalidSecurityDescriptorRange { .. }
+            | Self::InvalidSidSubAuthorityCount { .. }
+            | Self::InvalidTime
+            | Self::InvalidTotalSectors
+            | Self::InvalidUpcaseTableSize { .. }
+            | Self::Io(_)
+            | Self::LcnTooBig { .. }
+            | Self::NtfsTimeOutOfRange { .. }
+            | Self::SystemRegionsNotLoaded
+            | Self::TotalSectorsTooBig { .. }
+            | Self::UnsupportedClusterSize { .. }
+            | Self::UnsupportedFileRecordSize { .. }
+            | Self::UnsupportedSectorSize { .. }
+            | Self::UnsupportedSecurityDescriptorRevision { .. }
+            | Self::UpcaseTableNotLoaded
+            | Self::VcnTooBig { .. }
+            | Self::VolumeDataUnavailable { .. } => None,
+
+            Self::ValueReadContext { source, .. } => source.position(),
+        }
+    }
+
+    /// Returns the innermost error, unwrapping any [`NtfsError::ValueReadContext`].
+    ///
+    /// Match on the returned error to handle specific variants regardless of the context they have
+    /// been reported in.
+    pub fn root_cause(&self) -> &NtfsError {
+        let mut error = self;
+
+        while let Self::ValueReadContext { source, .. } = error {
+            error = &**source;
+        }
+
+        error
+    }
+
+    /// Wraps this error in a [`NtfsError::ValueReadContext`] for the given attribute value,
+    /// unless it already carries such a context.
+    pub(crate) fn with_value_context(self, file_record_number: u64, ty: u32) -> Self {
+        match self {
+            Self::ValueReadContext { .. } => self,
+            _ => Self::ValueReadContext {
+                file_record_number,
+                ty,
+                source: Box::new(self),
+            },
+        }
+    }
 }
 
 impl From<binrw::error::Error> for NtfsError {
     fn from(error: binrw::error::Error) -> Self {
-        if let binrw::error::Error::Io(io_error) = error {
-            Self::Io(io_error)
-        } else {
-            // We don't use any binrw attributes that result in other errors.
-            unreachable!("Got a binrw error of unexpected type: {:?}", error);
+        // We don't use any binrw attributes that result in errors other than I/O errors.
+        // Still map them to an error instead of panicking, as future binrw versions may return more.
+        // binrw only knows the position relative to the reader it has been given, which is not necessarily
+        // an absolute filesystem position.
+        let reason = match error {
+            binrw::error::Error::Io(io_error) => return Self::from(io_error),
+            // binrw wraps errors of nested structures (like an unexpected EOF) with context information.
+            binrw::error::Error::Backtrace(backtrace) => return Self::from(*backtrace.error),
+            binrw::error::Error::BadMagic { .. } => "unexpected magic value",
+            binrw::error::Error::AssertFail { .. } => "failed assertion",
+            binrw::error::Error::NoVariantMatch { .. } | binrw::error::Error::EnumErrors { .. } => {
+                "no matching enum variant"
+            }
+            _ => "unexpected binrw error",
+        };
+
+        Self::ParseError {
+            position: None,
+            reason,
+            source: Some(Box::new(error)),
         }
     }
 }
 
-impl From<binrw::io::Error> for NtfsError {
-    fn from(error: binrw::io::Error) -> Self {
+impl From<crate::io::Error> for NtfsError {
+    fn from(error: crate::io::Error) -> Self {
+        // An NtfsError may have crossed an io::Read/io::Seek boundary (e.g. when reading through
+        // an attached value). Unwrap it again to keep its variant and position.
+        #[cfg(feature = "std")]
+        if error
+            .get_ref()
+            .map_or(false, |inner| inner.is::<NtfsError>())
+        {
+            let inner = error.into_inner().unwrap();
+            return *inner.downcast::<NtfsError>().unwrap();
+        }
+
         Self::Io(error)
     }
 }
 
 // To stay compatible with standardized interfaces (e.g. io::Read, io::Seek),
 // we sometimes need to convert from NtfsError to io::Error.
-impl From<NtfsError> for binrw::io::Error {
+// The NtfsError is preserved as the inner error, so it can be retrieved via `io::Error::get_ref`.
+impl From<NtfsError> for crate::io::Error {
     fn from(error: NtfsError) -> Self {
         if let NtfsError::Io(io_error) = error {
-            io_error
-        } else {
-            binrw::io::Error::new(binrw::io::ErrorKind::Other, error)
+            return io_error;
         }
+
+        let kind = match error.root_cause() {
+            NtfsError::Io(io_error) => io_error.kind(),
+            NtfsError::AttributeInstanceNotFound { .. } | NtfsError::AttributeNotFound { .. } => {
+                crate::io::ErrorKind::NotFound
+            }
+            NtfsError::BeyondBackingStorage { .. } => crate::io::ErrorKind::UnexpectedEof,
+            _ => match error.kind() {
+                NtfsErrorKind::CorruptAttribute
+                | NtfsErrorKind::CorruptIndex
+                | NtfsErrorKind::CorruptRecord => crate::io::ErrorKind::InvalidData,
+                NtfsErrorKind::InvalidArgument => crate::io::ErrorKind::InvalidInput,
+                NtfsErrorKind::Io | NtfsErrorKind::Unsupported => crate::io::ErrorKind::Other,
+            },
+        };
+
+        crate::io::Error::new(kind, error)
     }
 }
 
 #[cfg(feature = "std")]
 #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
-impl std::error::Error for NtfsError {}
+impl std::error::Error for NtfsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(io_error) => Some(io_error),
+            Self::ParseError {
+                source: Some(source),
+                ..
+            } => Some(&**source),
+            Self::ValueReadContext { source, .. } => Some(&**source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position() -> NtfsPosition {
+        NtfsPosition::new(0x1234)
+    }
+
+    /// Returns one instance of every [`NtfsError`] variant along with its expected kind.
+    fn all_variants() -> Vec<(NtfsError, NtfsErrorKind)> {
+        use NtfsErrorKind::*;
+
+        let ty = NtfsAttributeType::Data;
+        let lcn = Lcn::from(1);
+        let vcn = Vcn::from(1);
+
+        vec![
+            (
+                NtfsError::AttributeInstanceNotFound {
+                    position: position(),
+                    instance: 1,
+                },
+                InvalidArgument,
+            ),
+            (
+                NtfsError::AttributeMustBeResident {
+                    position: position(),
+                    ty,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::AttributeNotFound {
+                    position: position(),
+                    ty,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::AttributeOfDifferentType {
+                    position: position(),
+                    expected: ty,
+                    actual: NtfsAttributeType::FileName,
+                },
+                InvalidArgument,
+            ),
+            (
+                NtfsError::AttributeTypeNotDefined {
+                    position: position(),
+                    ty,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::BadClusterRead {
+                    lcn,
+                    position: position(),
+                },
+                Io,
+            ),
+            (
+                NtfsError::BeyondBackingStorage {
+                    position: position(),
+                    available: 1,
+                },
+                Io,
+            ),
+            (
+                NtfsError::BufferTooSmall {
+                    expected: 2,
+                    actual: 1,
+                },
+                InvalidArgument,
+            ),
+            (
+                NtfsError::CollationMismatch {
+                    position: position(),
+                    expected: NtfsCollationRule::FileName,
+                    actual: NtfsCollationRule::NtofsUlong,
+                },
+                InvalidArgument,
+            ),
+            (
+                NtfsError::DataRunBeyondVolume {
+                    position: position(),
+                    end: 0x2000,
+                    volume_size: 0x1000,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::DirectoryFlagMismatch {
+                    position: position(),
+                    file_record_is_directory: true,
+                    file_name_is_directory: false,
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::FileRecordNotInUse {
+                    file_record_number: 1,
+                },
+                InvalidArgument,
+            ),
+            (
+                NtfsError::IndexRecordNotAllocated {
+                    position: position(),
+                    vcn,
+                },
+                CorruptIndex,
+            ),
+            (
+                NtfsError::InvalidAceSize {
+                    offset: 0x14,
+                    size: 2,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::InvalidAttributeLength {
+                    position: position(),
+                    expected: 2,
+                    actual: 1,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::InvalidAttributeListEntryOrder {
+                    position: position(),
+                    lowest_vcn: vcn,
+                    previous_lowest_vcn: vcn,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::InvalidAttributeNameLength {
+                    position: position(),
+                    expected: 2,
+                    actual: 1,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::InvalidAttributeNameOffset {
+                    position: position(),
+                    expected: 2,
+                    actual: 1,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::InvalidAttributeValueLength {
+                    position: position(),
+                    ty,
+                    minimum: 48,
+                    maximum: 72,
+                    actual: 100,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::InvalidBootSectorOemName {
+                    position: position(),
+                    expected: b"NTFS    ",
+                    actual: *b"MSDOS5.0",
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::InvalidBootSectorReservedField {
+                    position: position(),
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::InvalidByteCountInDataRunHeader {
+                    position: position(),
+                    expected: 9,
+                    actual: 8,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::InvalidClusterCountInDataRunHeader {
+                    position: position(),
+                    cluster_count: 0,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::InvalidCompressedData {
+                    position: position(),
+                    reason: "invalid Huffman code",
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::InvalidFileAllocatedSize {
+                    position: position(),
+                    expected: 2,
+                    actual: 1,
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::InvalidFileRecordNumber {
+                    file_record_number: 1,
+                },
+                InvalidArgument,
+            ),
+            (
+                NtfsError::InvalidFileRecordPosition {
+                    position: position(),
+                },
+                InvalidArgument,
+            ),
+            (
+                NtfsError::InvalidFileSignature {
+                    position: position(),
+                    expected: b"FILE",
+                    actual: *b"BAAD",
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::InvalidFileUsedSize {
+                    position: position(),
+                    expected: 2,
+                    actual: 1,
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::InvalidIndexAllocatedSize {
+                    position: position(),
+                    expected: 2,
+                    actual: 1,
+                },
+                CorruptIndex,
+            ),
+            (
+                NtfsError::InvalidIndexEntryDataRange {
+                    position: position(),
+                    range: 0..2,
+                    size: 1,
+                },
+                CorruptIndex,
+            ),
+            (
+                NtfsError::InvalidIndexEntrySize {
+                    position: position(),
+                    expected: 2,
+                    actual: 1,
+                },
+                CorruptIndex,
+            ),
+            (
+                NtfsError::InvalidIndexRecordSize {
+                    position: position(),
+                    size: 0,
+                },
+                CorruptIndex,
+            ),
+            (
+                NtfsError::InvalidIndexRootEntriesOffset {
+                    position: position(),
+                    expected: 2,
+                    actual: 1,
+                },
+                CorruptIndex,
+            ),
+            (
+                NtfsError::InvalidIndexRootUsedSize {
+                    position: position(),
+                    expected: 2,
+                    actual: 1,
+                },
+                CorruptIndex,
+            ),
+            (
+                NtfsError::InvalidIndexSignature {
+                    position: position(),
+                    expected: b"INDX",
+                    actual: *b"BAAD",
+                },
+                CorruptIndex,
+            ),
+            (
+                NtfsError::InvalidIndexUsedSize {
+                    position: position(),
+                    expected: 2,
+                    actual: 1,
+                },
+                CorruptIndex,
+            ),
+            (
+                NtfsError::InvalidLogFileRestartAreaOffset {
+                    position: position(),
+                    offset: 0x2000,
+                    page_size: 4096,
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::InvalidLogFileRestartPageSignature {
+                    position: position(),
+                    expected: b"RSTR",
+                    actual: *b"BAAD",
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::InvalidLogFileRestartPageSize {
+                    position: position(),
+                    size: 1000,
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::InvalidMediaDescriptor {
+                    position: position(),
+                    actual: 0x12,
+                },
+                CorruptRecord,
+            ),
+            (NtfsError::InvalidMftLcn, CorruptRecord),
+            (NtfsError::InvalidMftMirrorLcn, CorruptRecord),
+            (
+                NtfsError::InvalidNonResidentValueDataRange {
+                    position: position(),
+                    range: 0..2,
+                    size: 1,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::InvalidParentDirectoryReference {
+                    position: position(),
+                    parent_file_record_number: 5,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::InvalidResidentAttributeValueLength {
+                    position: position(),
+                    length: 2,
+                    offset: 1,
+                    actual: 1,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::InvalidResidentAttributeValueOffset {
+                    position: position(),
+                    expected: 2,
+                    actual: 1,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::InvalidRecordSizeInfo {
+                    size_info: 0,
+                    cluster_size: 512,
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::InvalidSectorsPerCluster {
+                    sectors_per_cluster: 3,
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::InvalidSecurityDescriptorRange {
+                    range: 0..2,
+                    size: 1,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::InvalidSidSubAuthorityCount { count: 16 },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::InvalidStructuredValueSize {
+                    position: position(),
+                    ty,
+                    expected: 2,
+                    actual: 1,
+                },
+                CorruptAttribute,
+            ),
+            (NtfsError::InvalidTime, InvalidArgument),
+            (NtfsError::InvalidTotalSectors, CorruptRecord),
+            (
+                NtfsError::InvalidTwoByteSignature {
+                    position: position(),
+                    expected: &[0x55, 0xaa],
+                    actual: [0, 0],
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::InvalidUpcaseTableSize {
+                    expected: 2,
+                    actual: 1,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::InvalidUpdateSequenceCount {
+                    position: position(),
+                    update_sequence_count: 0,
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::InvalidUpdateSequenceNumberRange {
+                    position: position(),
+                    range: 0..2,
+                    size: 1,
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::InvalidVcnInDataRunHeader {
+                    position: position(),
+                    vcn,
+                    previous_lcn: lcn,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::Io(crate::io::Error::new(
+                    crate::io::ErrorKind::UnexpectedEof,
+                    "test",
+                )),
+                Io,
+            ),
+            (NtfsError::LcnTooBig { lcn }, CorruptAttribute),
+            (
+                NtfsError::MissingIndexAllocation {
+                    position: position(),
+                },
+                CorruptIndex,
+            ),
+            (
+                NtfsError::NotADirectory {
+                    position: position(),
+                    index_entry_is_directory: None,
+                },
+                InvalidArgument,
+            ),
+            (
+                NtfsError::NtfsTimeOutOfRange { nt_timestamp: 1 },
+                InvalidArgument,
+            ),
+            (
+                NtfsError::OverlappingAttributeFields {
+                    position: position(),
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::ParseError {
+                    position: Some(position()),
+                    reason: "test",
+                    source: None,
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::ResidentValueTooLarge {
+                    position: position(),
+                    capacity: 8,
+                    actual: 9,
+                },
+                InvalidArgument,
+            ),
+            (NtfsError::SystemRegionsNotLoaded, InvalidArgument),
+            (
+                NtfsError::TotalSectorsTooBig { total_sectors: 1 },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::UnalignedAttributeLength {
+                    position: position(),
+                    length: 0x19,
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::UnexpectedAttributeListAttribute {
+                    position: position(),
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::UnexpectedNonResidentAttribute {
+                    position: position(),
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::UnexpectedResidentAttribute {
+                    position: position(),
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::UnsupportedAttributeType {
+                    position: position(),
+                    actual: 0x1234,
+                },
+                Unsupported,
+            ),
+            (
+                NtfsError::UnsupportedClusterSize {
+                    min: 1,
+                    max: 2,
+                    actual: 3,
+                },
+                Unsupported,
+            ),
+            (
+                NtfsError::UnsupportedCollationRule {
+                    position: position(),
+                    actual: 0x42,
+                },
+                Unsupported,
+            ),
+            (
+                NtfsError::UnsupportedFileNamespace {
+                    position: position(),
+                    actual: 7,
+                },
+                Unsupported,
+            ),
+            (
+                NtfsError::UnsupportedFileRecordSize {
+                    min: 1,
+                    max: 2,
+                    actual: 3,
+                },
+                Unsupported,
+            ),
+            (
+                NtfsError::UnsupportedSectorSize {
+                    min: 1,
+                    max: 2,
+                    actual: 3,
+                },
+                Unsupported,
+            ),
+            (
+                NtfsError::UnsupportedSecurityDescriptorRevision { revision: 2 },
+                Unsupported,
+            ),
+            (
+                NtfsError::UnsupportedWofAlgorithm {
+                    position: position(),
+                    actual: 1,
+                },
+                Unsupported,
+            ),
+            (
+                NtfsError::UnsupportedWofProvider {
+                    position: position(),
+                    actual: 1,
+                },
+                Unsupported,
+            ),
+            (NtfsError::UpcaseTableNotLoaded, InvalidArgument),
+            (
+                NtfsError::UpdateSequenceArrayExceedsRecordSize {
+                    position: position(),
+                    array_count: 3,
+                    record_size: 1024,
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::UpdateSequenceNumberMismatch {
+                    position: position(),
+                    expected: [1, 0],
+                    actual: [0, 0],
+                },
+                CorruptRecord,
+            ),
+            (
+                NtfsError::ValueReadContext {
+                    file_record_number: 66,
+                    ty: ty as u32,
+                    source: Box::new(NtfsError::InvalidVcnInDataRunHeader {
+                        position: position(),
+                        vcn,
+                        previous_lcn: lcn,
+                    }),
+                },
+                CorruptAttribute,
+            ),
+            (
+                NtfsError::VcnMismatchInIndexAllocation {
+                    position: position(),
+                    expected: vcn,
+                    actual: Vcn::from(2),
+                },
+                CorruptIndex,
+            ),
+            (
+                NtfsError::VcnOutOfBoundsInIndexAllocation {
+                    position: position(),
+                    vcn,
+                },
+                CorruptIndex,
+            ),
+            (NtfsError::VcnTooBig { vcn }, CorruptAttribute),
+            (NtfsError::VolumeDataUnavailable { lcn }, Io),
+        ]
+    }
+
+    #[test]
+    fn test_kind() {
+        for (error, kind) in all_variants() {
+            assert_eq!(error.kind(), kind, "{error:?}");
+        }
+    }
+
+    #[test]
+    fn test_position() {
+        for (error, _) in all_variants() {
+            if let Some(error_position) = error.position() {
+                assert_eq!(error_position, position(), "{error:?}");
+            }
+        }
+
+        let error = NtfsError::UpdateSequenceNumberMismatch {
+            position: position(),
+            expected: [1, 0],
+            actual: [0, 0],
+        };
+        assert_eq!(error.position(), Some(position()));
+
+        let error = NtfsError::NotADirectory {
+            position: NtfsPosition::none(),
+            index_entry_is_directory: Some(true),
+        };
+        assert_eq!(error.position(), Some(NtfsPosition::none()));
+
+        let error = NtfsError::InvalidFileRecordNumber {
+            file_record_number: 1,
+        };
+        assert_eq!(error.position(), None);
+
+        let error = NtfsError::Io(crate::io::Error::new(
+            crate::io::ErrorKind::UnexpectedEof,
+            "test",
+        ));
+        assert_eq!(error.position(), None);
+    }
+
+    #[test]
+    fn test_value_read_context() {
+        let error = NtfsError::VcnTooBig { vcn: Vcn::from(1) }
+            .with_value_context(66, NtfsAttributeType::Data as u32);
+        assert_eq!(
+            error.to_string(),
+            "Reading the value of the attribute of type 0x80 in File Record 66 failed: \
+             The Virtual Cluster Number (VCN) 1 is too big to be multiplied by the cluster size"
+        );
+        assert_eq!(error.kind(), NtfsErrorKind::CorruptAttribute);
+        assert!(matches!(error.root_cause(), NtfsError::VcnTooBig { .. }));
+
+        // An error that already carries a context is not wrapped again.
+        let error = error.with_value_context(67, NtfsAttributeType::IndexAllocation as u32);
+        match &error {
+            NtfsError::ValueReadContext {
+                file_record_number,
+                ty,
+                source,
+            } => {
+                assert_eq!(*file_record_number, 66);
+                assert_eq!(*ty, NtfsAttributeType::Data as u32);
+                assert!(matches!(**source, NtfsError::VcnTooBig { .. }));
+            }
+            _ => panic!("unexpected error {error:?}"),
+        }
+    }
+
+    #[test]
+    fn test_from_binrw_error() {
+        use binrw::BinReaderExt;
+
+        /// A reader that fails every operation with a custom error kind.
+        struct FailingReader;
+
+        impl crate::io::Read for FailingReader {
+            fn read(&mut self, _buf: &mut [u8]) -> crate::io::Result<usize> {
+                Err(crate::io::Error::new(
+                    crate::io::ErrorKind::ConnectionReset,
+                    "test",
+                ))
+            }
+        }
+
+        impl crate::io::Seek for FailingReader {
+            fn seek(&mut self, _pos: crate::io::SeekFrom) -> crate::io::Result<u64> {
+                Err(crate::io::Error::new(
+                    crate::io::ErrorKind::ConnectionReset,
+                    "test",
+                ))
+            }
+        }
+
+        // I/O errors of the reader are passed through.
+        let error = NtfsError::from(FailingReader.read_le::<u32>().unwrap_err());
+        assert!(
+            matches!(&error, NtfsError::Io(io_error) if io_error.kind() == crate::io::ErrorKind::ConnectionReset)
+        );
+
+        // Nested structures wrap I/O errors in a backtrace.
+        let error = NtfsError::from(FailingReader.read_le::<Lcn>().unwrap_err());
+        assert!(matches!(error, NtfsError::Io(_)));
+
+        // All other binrw errors become parse errors.
+        let error = NtfsError::from(binrw::error::Error::AssertFail {
+            pos: 0,
+            message: "test".into(),
+        });
+        assert!(matches!(
+            error,
+            NtfsError::ParseError {
+                position: None,
+                reason: "failed assertion",
+                source: Some(_),
+            }
+        ));
+        assert_eq!(error.kind(), NtfsErrorKind::CorruptRecord);
+
+        // The binrw error is kept as the source.
+        #[cfg(feature = "std")]
+        {
+            let source = std::error::Error::source(&error).unwrap();
+            assert!(matches!(
+                source.downcast_ref::<binrw::error::Error>(),
+                Some(binrw::error::Error::AssertFail { .. })
+            ));
+        }
+
+        let error = NtfsError::from(binrw::error::Error::NoVariantMatch { pos: 0 });
+        assert!(matches!(error, NtfsError::ParseError { .. }));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_io_error_interop() {
+        use crate::ntfs::Ntfs;
+        use std::error::Error;
+        use std::io::Read;
+
+        // Cut the image after the first data cluster of "1000-bytes-file" (File Record 66)
+        // to provoke an error when reading through the attached value.
+        const CUT: u64 = 1314304 + 512;
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        testfs1.get_mut().truncate(CUT as usize);
+
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.check_backing_size(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 66).unwrap();
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let data_value = data_attribute.value(&mut testfs1).unwrap();
+
+        let mut buf = [0u8; 1000];
+        let io_error = data_value
+            .attach(&mut testfs1)
+            .read_exact(&mut buf)
+            .unwrap_err();
+        assert_eq!(io_error.kind(), crate::io::ErrorKind::UnexpectedEof);
+
+        // The original NtfsError can be retrieved from the io::Error.
+        let inner = io_error.get_ref().unwrap();
+        assert!(matches!(
+            inner.downcast_ref::<NtfsError>().map(NtfsError::root_cause),
+            Some(NtfsError::BeyondBackingStorage { available: CUT, .. })
+        ));
+
+        // Converting back to an NtfsError restores the original variant along with its position.
+        let error = NtfsError::from(io_error);
+        assert!(matches!(
+            error,
+            NtfsError::ValueReadContext {
+                file_record_number: 66,
+                ty,
+                ..
+            } if ty == NtfsAttributeType::Data as u32
+        ));
+        assert!(matches!(
+            error.root_cause(),
+            NtfsError::BeyondBackingStorage { .. }
+        ));
+        assert_eq!(error.position().unwrap().value().unwrap().get(), CUT);
+
+        // The wrapped error is exposed as the error source.
+        let source = error.source().unwrap();
+        assert!(matches!(
+            source.downcast_ref::<NtfsError>(),
+            Some(NtfsError::BeyondBackingStorage { .. })
+        ));
+        assert!(source.source().is_none());
+
+        // Other variants are mapped to an io::ErrorKind according to their category.
+        let io_error = crate::io::Error::from(NtfsError::AttributeNotFound {
+            position: position(),
+            ty: NtfsAttributeType::Data,
+        });
+        assert_eq!(io_error.kind(), crate::io::ErrorKind::NotFound);
+        let io_error = crate::io::Error::from(NtfsError::InvalidMftLcn);
+        assert_eq!(io_error.kind(), crate::io::ErrorKind::InvalidData);
+        let io_error = crate::io::Error::from(NtfsError::InvalidFileRecordNumber {
+            file_record_number: 1,
+        });
+        assert_eq!(io_error.kind(), crate::io::ErrorKind::InvalidInput);
+
+        // I/O errors are passed through in both directions and exposed as the error source.
+        let error = NtfsError::from(crate::io::Error::new(
+            crate::io::ErrorKind::ConnectionReset,
+            "test",
+        ));
+        let source = error.source().unwrap();
+        assert_eq!(
+            source.downcast_ref::<crate::io::Error>().unwrap().kind(),
+            crate::io::ErrorKind::ConnectionReset
+        );
+        assert_eq!(
+            crate::io::Error::from(error).kind(),
+            crate::io::ErrorKind::ConnectionReset
+        );
+    }
+}
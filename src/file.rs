@@ -3,30 +3,44 @@
 
 use core::cmp::Ordering;
 use core::fmt;
+use core::iter::FusedIterator;
+use core::mem;
 use core::num::NonZeroU64;
 
+#[cfg(feature = "write")]
+use crate::io::Write;
+use crate::io::{Read, Seek, SeekFrom};
+use alloc::string::String;
 use alloc::vec;
-use binrw::io::{Read, Seek, SeekFrom};
+use alloc::vec::Vec;
 use bitflags::bitflags;
 use byteorder::{ByteOrder, LittleEndian};
 use memoffset::offset_of;
 use nt_string::u16strle::U16StrLe;
 
+#[cfg(feature = "async")]
+use crate::async_io::{AsyncBlockCache, AsyncNtfsReadSeek};
 use crate::attribute::{
     NtfsAttribute, NtfsAttributeItem, NtfsAttributeType, NtfsAttributes, NtfsAttributesRaw,
 };
 use crate::error::{NtfsError, Result};
 use crate::file_reference::NtfsFileReference;
-use crate::index::NtfsIndex;
-use crate::indexes::NtfsFileNameIndex;
+use crate::index::{NtfsIndex, NtfsOwnedIndex};
+use crate::index_entry::NtfsIndexEntryFlags;
+use crate::indexes::{NtfsCaseSensitivity, NtfsFileNameIndex, NtfsIndexEntryType, NtfsRawIndex};
 use crate::ntfs::Ntfs;
 use crate::record::{Record, RecordHeader};
 use crate::structured_values::{
-    NtfsFileName, NtfsFileNamespace, NtfsIndexRoot, NtfsStandardInformation,
+    NtfsFileName, NtfsFileNamespace, NtfsIndexRoot, NtfsObjectId, NtfsStandardInformation,
     NtfsStructuredValueFromResidentAttributeValue,
 };
+use crate::traits::NtfsReadSeek;
 use crate::types::NtfsPosition;
 use crate::upcase_table::UpcaseOrd;
+use crate::wof::{NtfsResolvedData, NtfsWofInfo, NtfsWofReader, WOF_COMPRESSED_DATA_STREAM_NAME};
+
+#[cfg(feature = "write")]
+use crate::structured_values::NtfsStandardInformationUpdate;
 
 /// A list of standardized NTFS File Record Numbers.
 ///
@@ -83,21 +97,22 @@ pub enum KnownNtfsFileRecordNumber {
 }
 
 #[repr(C, packed)]
-struct FileRecordHeader {
+pub(crate) struct FileRecordHeader {
     record_header: RecordHeader,
-    sequence_number: u16,
-    hard_link_count: u16,
-    first_attribute_offset: u16,
-    flags: u16,
-    data_size: u32,
-    allocated_size: u32,
-    base_file_record: NtfsFileReference,
-    next_attribute_instance: u16,
+    pub(crate) sequence_number: u16,
+    pub(crate) hard_link_count: u16,
+    pub(crate) first_attribute_offset: u16,
+    pub(crate) flags: u16,
+    pub(crate) data_size: u32,
+    pub(crate) allocated_size: u32,
+    pub(crate) base_file_record: NtfsFileReference,
+    pub(crate) next_attribute_instance: u16,
 }
 
 bitflags! {
     /// Flags returned by [`NtfsFile::flags`].
     #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
     pub struct NtfsFileFlags: u16 {
         /// Record is in use.
         const IN_USE = 0x0001;
@@ -128,6 +143,8 @@ pub struct NtfsFile<'n> {
     ntfs: &'n Ntfs,
     record: Record,
     file_record_number: u64,
+    /// Whether the Index Entry this file has been opened from claims that it is a directory (if any).
+    index_entry_is_directory: Option<bool>,
 }
 
 impl<'n> NtfsFile<'n> {
@@ -144,7 +161,20 @@ impl<'n> NtfsFile<'n> {
         fs.seek(SeekFrom::Start(position.get()))?;
         fs.read_exact(&mut data)?;
 
-        let mut record = Record::new(data, position.into());
+        Self::from_data(ntfs, data, position.into(), file_record_number)
+    }
+
+    /// Creates an [`NtfsFile`] from the raw bytes of a File Record that have already been read from
+    /// the filesystem (but not yet fixed up).
+    ///
+    /// `position` is the absolute byte position of the first byte of `data` within the filesystem.
+    pub(crate) fn from_data(
+        ntfs: &'n Ntfs,
+        data: Vec<u8>,
+        position: NtfsPosition,
+        file_record_number: u64,
+    ) -> Result<Self> {
+        let mut record = Record::new(data, position);
         Self::validate_signature(&record)?;
         record.fixup()?;
 
@@ -152,18 +182,142 @@ impl<'n> NtfsFile<'n> {
             ntfs,
             record,
             file_record_number,
+            index_entry_is_directory: None,
         };
         file.validate_sizes()?;
 
         Ok(file)
     }
 
+    /// Creates an [`NtfsFile`] from a File Record that has already been fixed up and validated
+    /// (e.g. a copy taken from the File Record cache of [`Ntfs`]).
+    pub(crate) fn from_record(ntfs: &'n Ntfs, record: Record, file_record_number: u64) -> Self {
+        Self {
+            ntfs,
+            record,
+            file_record_number,
+            index_entry_is_directory: None,
+        }
+    }
+
+    /// Records whether the Index Entry this file has been opened from claims that it is a directory.
+    ///
+    /// This is only used to give more context in [`NtfsError::NotADirectory`].
+    pub(crate) fn with_index_entry_is_directory(
+        mut self,
+        index_entry_is_directory: Option<bool>,
+    ) -> Self {
+        self.index_entry_is_directory = index_entry_is_directory;
+        self
+    }
+
     /// Returns the allocated size of this NTFS File Record, in bytes.
     pub fn allocated_size(&self) -> u32 {
         let start = offset_of!(FileRecordHeader, allocated_size);
         LittleEndian::read_u32(&self.record.data()[start..])
     }
 
+    /// Returns the bytes between the used size and the allocated size of this File Record.
+    ///
+    /// These bytes are not used by the current contents of the File Record, but may still hold
+    /// remnants of data that the record contained before it was shrunk or reused.
+    /// The range is clamped to the actual record buffer, so this never fails even for corrupted size fields.
+    pub fn allocated_slack(&self) -> &[u8] {
+        let data = self.record_data();
+        let end = usize::min(self.allocated_size() as usize, data.len());
+        let start = usize::min(self.data_size() as usize, end);
+
+        &data[start..end]
+    }
+
+    /// Returns a reference to the base File Record if this is an extension record,
+    /// or a zeroed reference if this is a base File Record itself.
+    ///
+    /// Extension records hold attributes of a file that did not fit into its base File Record.
+    /// They are referenced by the Attribute List of the base File Record (see [`NtfsAttributeList`])
+    /// and are usually skipped when enumerating all files of the MFT.
+    /// Use [`NtfsFile::is_base_record`] to distinguish them.
+    ///
+    /// [`NtfsAttributeList`]: crate::structured_values::NtfsAttributeList
+    pub fn base_file_record(&self) -> NtfsFileReference {
+        let start = offset_of!(FileRecordHeader, base_file_record);
+        NtfsFileReference::new(self.record.data()[start..start + 8].try_into().unwrap())
+    }
+
+    /// Finds the top-level attribute with the given instance identifier and returns it.
+    /// Returns [`NtfsError::AttributeInstanceNotFound`] if no such attribute could be found.
+    ///
+    /// Instance identifiers are unique within a single File Record (see [`NtfsAttribute::instance`]).
+    /// Therefore, this function does not traverse Attribute Lists, but only looks at the attributes
+    /// returned by [`NtfsFile::attributes_raw`].
+    ///
+    /// [`NtfsAttribute::instance`]: crate::NtfsAttribute::instance
+    pub fn attribute_by_instance<'f>(&'f self, instance: u16) -> Result<NtfsAttribute<'n, 'f>> {
+        for attribute in self.attributes_raw() {
+            let attribute = attribute?;
+
+            if attribute.instance() == instance {
+                return Ok(attribute);
+            }
+        }
+
+        Err(NtfsError::AttributeInstanceNotFound {
+            position: self.position(),
+            instance,
+        })
+    }
+
+    /// Finds an attribute of a specific type, optionally with a specific name, and returns its [`NtfsAttributeItem`].
+    /// Returns [`NtfsError::AttributeNotFound`] if no such attribute could be found.
+    ///
+    /// This function also traverses Attribute Lists to find the attribute.
+    /// The name is compared as specified by `case_sensitivity`.
+    /// Windows treats attribute names case-insensitively.
+    ///
+    /// Returns [`NtfsError::UpcaseTableNotLoaded`] if a name is given, `case_sensitivity` is
+    /// [`NtfsCaseSensitivity::Insensitive`], and [`read_upcase_table`][Ntfs::read_upcase_table] had not been
+    /// called on the [`Ntfs`] object.
+    ///
+    /// [`NtfsAttributeItem`]: crate::NtfsAttributeItem
+    pub fn attribute_by_ty_and_name<'f, T>(
+        &'f self,
+        fs: &mut T,
+        ty: NtfsAttributeType,
+        match_name: Option<&str>,
+        case_sensitivity: NtfsCaseSensitivity,
+    ) -> Result<NtfsAttributeItem<'n, 'f>>
+    where
+        T: Read + Seek,
+    {
+        if match_name.is_some() && case_sensitivity == NtfsCaseSensitivity::Insensitive {
+            self.ntfs.upcase_table_or_error()?;
+        }
+
+        let mut iter = self.attributes();
+
+        while let Some(item) = iter.next(fs) {
+            let item = item?;
+            let attribute = item.to_attribute()?;
+
+            if attribute.ty()? != ty {
+                continue;
+            }
+
+            if let Some(name) = match_name {
+                if !attribute_name_matches(self.ntfs, &attribute.name()?, name, case_sensitivity)? {
+                    continue;
+                }
+            }
+
+            return Ok(item);
+        }
+
+        Err(NtfsError::AttributeNotFound {
+            position: self.position(),
+            ty,
+        })
+    }
+
     /// Returns an iterator over all attributes of this file.
     ///
     /// This provides a flattened "data-centric" view of the attributes and abstracts away the filesystem details
@@ -172,11 +326,26 @@ impl<'n> NtfsFile<'n> {
     ///
     /// Due to the abstraction, the iterator returns an [`NtfsAttributeItem`] for each entry.
     ///
+    /// The attributes of the base File Record are returned in their on-disk order, with the attributes of
+    /// extension records returned in place of the $ATTRIBUTE_LIST attribute.
+    /// Use [`NtfsFile::attributes_sorted`] if you rely on the canonical NTFS attribute order.
+    ///
     /// [`NtfsAttributeItem`]: crate::NtfsAttributeItem
     pub fn attributes<'f>(&'f self) -> NtfsAttributes<'n, 'f> {
         NtfsAttributes::<'n, 'f>::new(self)
     }
 
+    /// Returns an iterator over all attributes of this file in canonical NTFS order
+    /// (ascending attribute type, then name, then lowest VCN).
+    ///
+    /// This is the same as [`NtfsFile::attributes`], except that the iteration is driven by the Attribute List
+    /// if the file has one (see [`NtfsAttributes::in_attribute_list_order`]).
+    /// Files without an Attribute List always store their attributes in canonical order, so their attributes
+    /// are returned in on-disk order.
+    pub fn attributes_sorted<'f>(&'f self) -> NtfsAttributes<'n, 'f> {
+        self.attributes().in_attribute_list_order()
+    }
+
     /// Returns an iterator over all top-level attributes of this file.
     ///
     /// Contrary to [`NtfsFile::attributes`], it does not traverse $ATTRIBUTE_LIST attributes, but returns
@@ -196,15 +365,11 @@ impl<'n> NtfsFile<'n> {
     /// As NTFS supports multiple data streams per file, you can specify the name of the $DATA attribute
     /// to look up.
     /// Passing an empty string here looks up the default unnamed $DATA attribute (commonly known as the "file data").
-    /// The name is looked up case-insensitively.
+    /// The name is looked up case-insensitively if [`read_upcase_table`][Ntfs::read_upcase_table] has been
+    /// called on the passed [`Ntfs`] object, and compared exactly otherwise.
     ///
     /// If you need more control over which $DATA attribute is available and picked up,
     /// you can use [`NtfsFile::attributes`] to iterate over all attributes of this file.
-    ///
-    /// # Panics
-    ///
-    /// Panics if `data_stream_name` is non-empty and [`read_upcase_table`][Ntfs::read_upcase_table] had not been
-    /// called on the passed [`Ntfs`] object.
     pub fn data<'f, T>(
         &'f self,
         fs: &mut T,
@@ -213,28 +378,106 @@ impl<'n> NtfsFile<'n> {
     where
         T: Read + Seek,
     {
-        let mut iter = self.attributes();
+        self.stream(fs, NtfsAttributeType::Data, data_stream_name)
+    }
 
-        let equal = if data_stream_name.is_empty() {
-            // Use a simpler "comparison" that doesn't require the $UpCase table.
-            |_ntfs: &Ntfs, name: &U16StrLe, _data_stream_name: &str| name.is_empty()
-        } else {
-            |ntfs: &Ntfs, name: &U16StrLe, data_stream_name: &str| {
-                name.upcase_cmp(ntfs, &data_stream_name) == Ordering::Equal
+    /// Async variant of [`NtfsFile::data`] for readers implementing [`AsyncNtfsReadSeek`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn data_async<'f, T>(
+        &'f self,
+        fs: &mut T,
+        data_stream_name: &str,
+    ) -> Option<Result<NtfsAttributeItem<'n, 'f>>>
+    where
+        T: AsyncNtfsReadSeek,
+    {
+        let mut cache = iter_try!(AsyncBlockCache::new(fs).await);
+        cache
+            .run(|fs| self.data(fs, data_stream_name).transpose())
+            .await
+            .transpose()
+    }
+
+    /// Like [`NtfsFile::data`] for the unnamed $DATA attribute, but transparently handles files compressed
+    /// by the Windows Overlay Filter (WOF).
+    ///
+    /// For WOF-compressed files (see [`NtfsFile::wof_info`]), the unnamed $DATA attribute only indicates the
+    /// uncompressed size.
+    /// This function then returns an [`NtfsWofReader`] decompressing the "WofCompressedData" attribute.
+    /// For all other files, the unnamed $DATA attribute is returned as-is.
+    ///
+    /// Returns `None` if this file has no unnamed $DATA attribute.
+    pub fn data_resolved<'f, T>(&'f self, fs: &mut T) -> Option<Result<NtfsResolvedData<'n, 'f>>>
+    where
+        T: Read + Seek,
+    {
+        let data_item = iter_try!(self.data(fs, "")?);
+
+        let wof_info = match self.wof_info(fs) {
+            Some(wof_info) => iter_try!(wof_info),
+            None => return Some(Ok(NtfsResolvedData::Plain(data_item))),
+        };
+
+        let data_size = iter_try!(data_item.to_attribute()).value_length();
+        let compressed_item = match self.data(fs, WOF_COMPRESSED_DATA_STREAM_NAME) {
+            Some(compressed_item) => iter_try!(compressed_item),
+            None => {
+                return Some(Err(NtfsError::AttributeNotFound {
+                    position: self.position(),
+                    ty: NtfsAttributeType::Data,
+                }))
             }
         };
 
+        let reader = iter_try!(NtfsWofReader::new(
+            fs,
+            &wof_info,
+            compressed_item,
+            data_size
+        ));
+        Some(Ok(NtfsResolvedData::WofCompressed(reader)))
+    }
+
+    /// Convenience function to get a named attribute of the given type, like [`NtfsFile::data`] does for
+    /// $DATA attributes.
+    ///
+    /// This is useful for the $LOGGED_UTILITY_STREAM attributes left by Transactional NTFS ("$TXF_DATA",
+    /// see [`NtfsTxfData`]) and EFS ("$EFS").
+    /// Passing an empty string looks up the first unnamed attribute of the given type.
+    /// The name is looked up case-insensitively if [`read_upcase_table`][Ntfs::read_upcase_table] has been
+    /// called on the passed [`Ntfs`] object, and compared exactly otherwise.
+    ///
+    /// Attribute Lists are traversed like in [`NtfsFile::attributes`].
+    ///
+    /// [`NtfsTxfData`]: crate::structured_values::NtfsTxfData
+    pub fn stream<'f, T>(
+        &'f self,
+        fs: &mut T,
+        ty: NtfsAttributeType,
+        name: &str,
+    ) -> Option<Result<NtfsAttributeItem<'n, 'f>>>
+    where
+        T: Read + Seek,
+    {
+        let mut iter = self.attributes();
+        let case_sensitivity = self.default_case_sensitivity();
+
         while let Some(item) = iter.next(fs) {
             let item = iter_try!(item);
             let attribute = iter_try!(item.to_attribute());
 
-            let ty = iter_try!(attribute.ty());
-            if ty != NtfsAttributeType::Data {
+            if iter_try!(attribute.ty()) != ty {
                 continue;
             }
 
-            let name = iter_try!(attribute.name());
-            if !equal(self.ntfs, &name, data_stream_name) {
+            let attribute_name = iter_try!(attribute.name());
+            if !iter_try!(attribute_name_matches(
+                self.ntfs,
+                &attribute_name,
+                name,
+                case_sensitivity
+            )) {
                 continue;
             }
 
@@ -244,6 +487,38 @@ impl<'n> NtfsFile<'n> {
         None
     }
 
+    /// Returns the sizes of all $DATA attributes (data streams) of this file (see [`NtfsStreamSizes`]).
+    ///
+    /// The sizes are taken from the attribute headers, traversing Attribute Lists like [`NtfsFile::attributes`].
+    /// Neither attribute values nor Data Runs are read, which makes this suitable for quickly summing up
+    /// the disk usage of many files.
+    /// If a stream is split over multiple connected attributes, only the sizes of the first one are taken,
+    /// because that one reports the sizes of the entire stream.
+    pub fn data_stream_sizes<T>(&self, fs: &mut T) -> Result<Vec<NtfsStreamSizes>>
+    where
+        T: Read + Seek,
+    {
+        let mut stream_sizes = Vec::new();
+        let mut iter = self.attributes();
+
+        while let Some(item) = iter.next(fs) {
+            let item = item?;
+            let attribute = item.to_attribute()?;
+            if attribute.ty()? != NtfsAttributeType::Data {
+                continue;
+            }
+
+            stream_sizes.push(NtfsStreamSizes {
+                name: attribute.name()?.to_string_lossy(),
+                data_size: attribute.value_length(),
+                allocated_size: attribute.allocated_size(),
+                initialized_size: attribute.initialized_size(),
+            });
+        }
+
+        Ok(stream_sizes)
+    }
+
     /// Returns the size actually used by data of this NTFS File Record, in bytes.
     ///
     /// This is less or equal than [`NtfsFile::allocated_size`].
@@ -252,14 +527,37 @@ impl<'n> NtfsFile<'n> {
         LittleEndian::read_u32(&self.record.data()[start..])
     }
 
+    /// Returns how attribute names are compared when looking them up internally:
+    /// Case-insensitively (like Windows does) if the $UpCase table is available, exactly otherwise.
+    pub(crate) fn default_case_sensitivity(&self) -> NtfsCaseSensitivity {
+        if self.ntfs.upcase_table().is_some() {
+            NtfsCaseSensitivity::Insensitive
+        } else {
+            NtfsCaseSensitivity::Sensitive
+        }
+    }
+
     /// Convenience function to return an [`NtfsIndex`] if this file is a directory.
     /// This structure can be used to iterate over all files of this directory or a find a specific one.
     ///
     /// Apart from any propagated error, this function may return [`NtfsError::NotADirectory`]
     /// if this [`NtfsFile`] is not a directory.
+    /// If this file has been opened via [`NtfsIndexEntry::to_file`], that error also tells whether the
+    /// Index Entry claimed it to be a directory.
     ///
+    /// [`NtfsIndexEntry::to_file`]: crate::NtfsIndexEntry::to_file
+    ///
+    /// The $INDEX_ROOT and $INDEX_ALLOCATION attributes are also found if they are stored in an extension record
+    /// referenced by an Attribute List.
     /// If you need more control over the picked up $INDEX_ROOT and $INDEX_ALLOCATION attributes
     /// you can use [`NtfsFile::attributes`] to iterate over all attributes of this file.
+    ///
+    /// For a large directory, the $BITMAP attribute of the index is read as well (see [`NtfsIndex::read_bitmap`]),
+    /// so that deallocated Index Records are not traversed.
+    /// In [`NtfsValidationMode::Lenient`], a corrupted $BITMAP attribute is passed to the warning handler
+    /// and the Index Records are not checked against it.
+    ///
+    /// [`NtfsValidationMode::Lenient`]: crate::NtfsValidationMode::Lenient
     pub fn directory_index<'f, T>(
         &'f self,
         fs: &mut T,
@@ -270,30 +568,26 @@ impl<'n> NtfsFile<'n> {
         if !self.is_directory() {
             return Err(NtfsError::NotADirectory {
                 position: self.position(),
+                index_entry_is_directory: self.index_entry_is_directory,
             });
         }
 
         // A File Record may contain multiple indexes, so we have to match the name of the directory index.
-        let directory_index_name = "$I30";
-
-        // The IndexRoot attribute is always resident and has to exist for every directory.
-        let index_root_item =
-            self.find_attribute(fs, NtfsAttributeType::IndexRoot, Some(directory_index_name))?;
-        let index_root_attribute = index_root_item.to_attribute()?;
-        let index_root = index_root_attribute.resident_structured_value::<NtfsIndexRoot>()?;
-
-        // The IndexAllocation attribute is only required for "large" indexes.
-        // It is always non-resident and may even be in an Attribute List.
-        let mut index_allocation_item = None;
-        if index_root.is_large_index() {
-            index_allocation_item = Some(self.find_attribute(
-                fs,
-                NtfsAttributeType::IndexAllocation,
-                Some(directory_index_name),
-            )?);
-        }
+        self.index_by_name(fs, "$I30")
+    }
 
-        NtfsIndex::<NtfsFileNameIndex>::new(index_root_item, index_allocation_item)
+    /// Async variant of [`NtfsFile::directory_index`] for readers implementing [`AsyncNtfsReadSeek`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn directory_index_async<'f, T>(
+        &'f self,
+        fs: &mut T,
+    ) -> Result<NtfsIndex<'n, 'f, NtfsFileNameIndex>>
+    where
+        T: AsyncNtfsReadSeek,
+    {
+        let mut cache = AsyncBlockCache::new(fs).await?;
+        cache.run(|fs| self.directory_index(fs)).await
     }
 
     /// Returns the NTFS File Record Number of this file.
@@ -304,44 +598,6 @@ impl<'n> NtfsFile<'n> {
         self.file_record_number
     }
 
-    /// Finds an attribute of a specific type, optionally with a specific name, and returns its [`NtfsAttributeItem`].
-    /// Returns [`NtfsError::AttributeNotFound`] if no such attribute could be found.
-    ///
-    /// This function also traverses Attribute Lists to find the attribute.
-    fn find_attribute<'f, T>(
-        &'f self,
-        fs: &mut T,
-        ty: NtfsAttributeType,
-        match_name: Option<&str>,
-    ) -> Result<NtfsAttributeItem<'n, 'f>>
-    where
-        T: Read + Seek,
-    {
-        let mut iter = self.attributes();
-
-        while let Some(item) = iter.next(fs) {
-            let item = item?;
-            let attribute = item.to_attribute()?;
-
-            if attribute.ty()? != ty {
-                continue;
-            }
-
-            if let Some(name) = match_name {
-                if attribute.name()? != name {
-                    continue;
-                }
-            }
-
-            return Ok(item);
-        }
-
-        Err(NtfsError::AttributeNotFound {
-            position: self.position(),
-            ty,
-        })
-    }
-
     /// Finds a resident attribute of a specific type, optionally with a specific name and/or a specific
     /// instance identifier, and returns it.
     /// Returns [`NtfsError::AttributeNotFound`] if no such resident attribute could be found.
@@ -355,6 +611,8 @@ impl<'n> NtfsFile<'n> {
         match_name: Option<&str>,
         match_instance: Option<u16>,
     ) -> Result<NtfsAttribute<'n, 'f>> {
+        let case_sensitivity = self.default_case_sensitivity();
+
         // Resident attributes are always stored on the top-level (we don't have to dig into Attribute Lists).
         for attribute in self.attributes_raw() {
             let attribute = attribute?;
@@ -370,7 +628,7 @@ impl<'n> NtfsFile<'n> {
             }
 
             if let Some(name) = match_name {
-                if attribute.name()? != name {
+                if !attribute_name_matches(self.ntfs, &attribute.name()?, name, case_sensitivity)? {
                     continue;
                 }
             }
@@ -408,8 +666,16 @@ impl<'n> NtfsFile<'n> {
 
     /// Returns flags set for this file as specified by [`NtfsFileFlags`].
     pub fn flags(&self) -> NtfsFileFlags {
+        Self::flags_from_data(self.record.data())
+    }
+
+    /// Returns the [`NtfsFileFlags`] from the raw bytes of a File Record.
+    ///
+    /// This works before the record has been validated and fixed up, because the flags are always stored
+    /// within the first sector and never affected by the fixup.
+    pub(crate) fn flags_from_data(data: &[u8]) -> NtfsFileFlags {
         let start = offset_of!(FileRecordHeader, flags);
-        NtfsFileFlags::from_bits_truncate(LittleEndian::read_u16(&self.record.data()[start..]))
+        NtfsFileFlags::from_bits_truncate(LittleEndian::read_u16(&data[start..]))
     }
 
     /// Returns the number of hard links to this NTFS File Record.
@@ -418,73 +684,397 @@ impl<'n> NtfsFile<'n> {
         LittleEndian::read_u16(&self.record.data()[start..])
     }
 
-    /// Convenience function to get the $STANDARD_INFORMATION attribute of this file
-    /// (see [`NtfsStandardInformation`]).
-    ///
-    /// This internally calls [`NtfsFile::attributes_raw`] to iterate through the file's
-    /// attributes and pick up the first $STANDARD_INFORMATION attribute.
-    pub fn info(&self) -> Result<NtfsStandardInformation> {
-        self.find_resident_attribute_structured_value::<NtfsStandardInformation>(None)
-    }
-
-    /// Returns whether this NTFS File Record represents a directory.
-    pub fn is_directory(&self) -> bool {
-        self.flags().contains(NtfsFileFlags::IS_DIRECTORY)
-    }
-
-    /// Convenience function to get a $FILE_NAME attribute of this file (see [`NtfsFileName`]).
-    ///
-    /// A file may have multiple $FILE_NAME attributes for each [`NtfsFileNamespace`].
-    /// Files with hard links have further $FILE_NAME attributes for each directory they are in.
-    /// You may optionally filter for a namespace and parent directory via the parameters.
+    /// Returns whether this file has at least one attribute of the given type.
     ///
-    /// This internally calls [`NtfsFile::attributes`] to iterate through the file's
-    /// attributes and pick up the first matching $FILE_NAME attribute.
-    pub fn name<T>(
-        &self,
-        fs: &mut T,
-        match_namespace: Option<NtfsFileNamespace>,
-        match_parent_record_number: Option<u64>,
-    ) -> Option<Result<NtfsFileName>>
+    /// This traverses Attribute Lists like [`NtfsFile::attributes`], stops at the first match,
+    /// and does not read any attribute values.
+    pub fn has_attribute<T>(&self, fs: &mut T, ty: NtfsAttributeType) -> Result<bool>
     where
         T: Read + Seek,
     {
         let mut iter = self.attributes();
 
         while let Some(item) = iter.next(fs) {
-            let item = iter_try!(item);
-            let attribute = iter_try!(item.to_attribute());
-
-            let ty = iter_try!(attribute.ty());
-            if ty != NtfsAttributeType::FileName {
-                continue;
-            }
-
-            let file_name = iter_try!(attribute.structured_value::<_, NtfsFileName>(fs));
-
-            if let Some(namespace) = match_namespace {
-                if file_name.namespace() != namespace {
-                    continue;
-                }
+            let item = item?;
+            if item.to_attribute()?.ty()? == ty {
+                return Ok(true);
             }
+        }
 
-            if let Some(parent_record_number) = match_parent_record_number {
-                if file_name.parent_directory_reference().file_record_number()
-                    != parent_record_number
-                {
-                    continue;
-                }
-            }
+        Ok(false)
+    }
 
-            return Some(Ok(file_name));
+    /// Returns whether this file has a $DATA attribute (data stream) of the given name.
+    ///
+    /// The name is compared like in [`NtfsFile::data`], and passing an empty string checks for the
+    /// default unnamed data stream.
+    /// Attribute values are not read.
+    pub fn has_named_data_stream<T>(&self, fs: &mut T, name: &str) -> Result<bool>
+    where
+        T: Read + Seek,
+    {
+        match self.data(fs, name) {
+            Some(Ok(_)) => Ok(true),
+            Some(Err(e)) => Err(e),
+            None => Ok(false),
         }
-
-        None
     }
 
-    /// Returns the [`Ntfs`] object reference associated to this file.
-    pub fn ntfs(&self) -> &'n Ntfs {
-        self.ntfs
+    /// Returns the $LogFile Sequence Number (LSN) of the last logged change to this NTFS File Record.
+    ///
+    /// This is zero if the File Record has never been changed by a driver that logs to $LogFile.
+    pub fn lsn(&self) -> u64 {
+        self.record.logfile_sequence_number()
+    }
+
+    /// Returns whether this is a base File Record, i.e. not an extension record of another file.
+    ///
+    /// See [`NtfsFile::base_file_record`].
+    pub fn is_base_record(&self) -> bool {
+        self.base_file_record().file_record_number() == 0
+    }
+
+    /// Returns an [`NtfsIndex`] for the index of the given name (like "$I30", "$SII", or "$SDH"),
+    /// which returns keys and data of its entries as raw bytes.
+    /// The name is looked up case-insensitively if [`read_upcase_table`][Ntfs::read_upcase_table] has been
+    /// called on the passed [`Ntfs`] object, and compared exactly otherwise.
+    ///
+    /// Use this function to traverse indexes whose key and data types are not known to this crate.
+    /// For directories, [`NtfsFile::directory_index`] returns an index with typed keys.
+    ///
+    /// Apart from any propagated error, this function returns [`NtfsError::AttributeNotFound`]
+    /// if this file has no $INDEX_ROOT attribute of the given name or a large index has no
+    /// $INDEX_ALLOCATION attribute of the given name.
+    pub fn index<'f, T>(&'f self, fs: &mut T, name: &str) -> Result<NtfsIndex<'n, 'f, NtfsRawIndex>>
+    where
+        T: Read + Seek,
+    {
+        self.index_by_name(fs, name)
+    }
+
+    pub(crate) fn index_by_name<'f, T, E>(
+        &'f self,
+        fs: &mut T,
+        name: &str,
+    ) -> Result<NtfsIndex<'n, 'f, E>>
+    where
+        T: Read + Seek,
+        E: NtfsIndexEntryType,
+    {
+        // The IndexRoot attribute is always resident and has to exist for every index.
+        let case_sensitivity = self.default_case_sensitivity();
+        let index_root_item = self.attribute_by_ty_and_name(
+            fs,
+            NtfsAttributeType::IndexRoot,
+            Some(name),
+            case_sensitivity,
+        )?;
+        let index_root_attribute = index_root_item.to_attribute()?;
+        let index_root = index_root_attribute.resident_structured_value::<NtfsIndexRoot>()?;
+
+        // The IndexAllocation attribute is only required for "large" indexes.
+        // It is always non-resident and may even be in an Attribute List.
+        let is_large_index = index_root.is_large_index();
+        let mut index_allocation_item = None;
+        if is_large_index {
+            index_allocation_item = Some(self.attribute_by_ty_and_name(
+                fs,
+                NtfsAttributeType::IndexAllocation,
+                Some(name),
+                case_sensitivity,
+            )?);
+        }
+
+        let mut index = NtfsIndex::<E>::new(index_root_item, index_allocation_item)?;
+
+        // A large index also comes with a Bitmap attribute of the same name, which marks the Index Records in use.
+        // Tolerate its absence on damaged filesystems and just don't check the Index Records then.
+        // In lenient mode, the same applies to a corrupted Bitmap attribute, while I/O errors are still returned.
+        if is_large_index {
+            match self.attribute_by_ty_and_name(
+                fs,
+                NtfsAttributeType::Bitmap,
+                Some(name),
+                case_sensitivity,
+            ) {
+                Ok(index_bitmap_item) => match index.read_bitmap(fs, &index_bitmap_item) {
+                    Ok(()) => (),
+                    Err(e @ NtfsError::Io(_)) => return Err(e),
+                    Err(e) => self.ntfs.tolerate(e)?,
+                },
+                Err(NtfsError::AttributeNotFound { .. }) => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// Convenience function to get the $STANDARD_INFORMATION attribute of this file
+    /// (see [`NtfsStandardInformation`]).
+    ///
+    /// This internally calls [`NtfsFile::attributes_raw`] to iterate through the file's
+    /// attributes and pick up the first $STANDARD_INFORMATION attribute.
+    /// It therefore only finds the attribute if it is stored in this File Record.
+    /// This is usually the case, but volumes upgraded from older NTFS versions may store it in an extension record.
+    /// Use [`NtfsFile::info_with_fs`] to also find it there.
+    pub fn info(&self) -> Result<NtfsStandardInformation> {
+        self.find_resident_attribute_structured_value::<NtfsStandardInformation>(None)
+    }
+
+    /// Like [`NtfsFile::info`], but falls back to traversing the Attribute List via [`NtfsFile::attributes`]
+    /// if the $STANDARD_INFORMATION attribute is not stored in this File Record.
+    pub fn info_with_fs<T>(&self, fs: &mut T) -> Result<NtfsStandardInformation>
+    where
+        T: Read + Seek,
+    {
+        match self.info() {
+            Err(NtfsError::AttributeNotFound { .. }) => {
+                let item = self.attribute_by_ty_and_name(
+                    fs,
+                    NtfsAttributeType::StandardInformation,
+                    None,
+                    NtfsCaseSensitivity::Sensitive,
+                )?;
+                let attribute = item.to_attribute()?;
+                attribute.resident_structured_value::<NtfsStandardInformation>()
+            }
+            result => result,
+        }
+    }
+
+    /// Like [`NtfsFile::directory_index`], but consumes this [`NtfsFile`] and returns an [`NtfsOwnedIndex`]
+    /// that owns it.
+    ///
+    /// The returned index only borrows the [`Ntfs`] object, so it can be stored next to other objects or
+    /// be returned from a function that has created the [`NtfsFile`] locally.
+    pub fn into_directory_index<T>(
+        self,
+        fs: &mut T,
+    ) -> Result<NtfsOwnedIndex<'n, NtfsFileNameIndex>>
+    where
+        T: Read + Seek,
+    {
+        if !self.is_directory() {
+            return Err(NtfsError::NotADirectory {
+                position: self.position(),
+                index_entry_is_directory: self.index_entry_is_directory,
+            });
+        }
+
+        NtfsOwnedIndex::new(self, fs, "$I30")
+    }
+
+    /// Returns whether this NTFS File Record represents a directory.
+    ///
+    /// This checks the flags of the File Record, whereas [`NtfsFileName::is_directory`] checks the file attributes
+    /// of a $FILE_NAME attribute (like the ones in directory listings).
+    /// Use [`NtfsFile::validate_directory_consistency`] to check that both agree.
+    pub fn is_directory(&self) -> bool {
+        self.flags().contains(NtfsFileFlags::IS_DIRECTORY)
+    }
+
+    /// Returns whether this directory has no entries.
+    ///
+    /// For small directories, this only checks the entries of the $INDEX_ROOT attribute and
+    /// doesn't need to build an [`NtfsIndex`].
+    /// Large directories may still be empty after all their entries have been deleted, so this falls back
+    /// to iterating over the directory index if the $INDEX_ROOT attribute alone doesn't tell.
+    ///
+    /// Apart from any propagated error, this function may return [`NtfsError::NotADirectory`]
+    /// if this [`NtfsFile`] is not a directory.
+    pub fn is_empty_directory<T>(&self, fs: &mut T) -> Result<bool>
+    where
+        T: Read + Seek,
+    {
+        if !self.is_directory() {
+            return Err(NtfsError::NotADirectory {
+                position: self.position(),
+                index_entry_is_directory: self.index_entry_is_directory,
+            });
+        }
+
+        let index_root_item = self.attribute_by_ty_and_name(
+            fs,
+            NtfsAttributeType::IndexRoot,
+            Some("$I30"),
+            self.default_case_sensitivity(),
+        )?;
+        let index_root_attribute = index_root_item.to_attribute()?;
+        let index_root = index_root_attribute.resident_structured_value::<NtfsIndexRoot>()?;
+
+        // An empty index root node only consists of the entry with the LAST_ENTRY flag.
+        // Any other entry in the root node is an actual directory entry.
+        let first_entry = match index_root.entries::<NtfsFileNameIndex>()?.next() {
+            Some(entry) => entry?,
+            None => return Ok(true),
+        };
+        if !first_entry
+            .flags()
+            .contains(NtfsIndexEntryFlags::LAST_ENTRY)
+        {
+            return Ok(false);
+        }
+
+        if !index_root.is_large_index() {
+            return Ok(true);
+        }
+
+        // The LAST_ENTRY of a large index references a subnode, which may or may not have entries.
+        let index = self.directory_index(fs)?;
+        let mut entries = index.entries();
+        match entries.next(fs) {
+            Some(entry) => entry.map(|_| false),
+            None => Ok(true),
+        }
+    }
+
+    /// Convenience function to get a $FILE_NAME attribute of this file (see [`NtfsFileName`]).
+    ///
+    /// A file may have multiple $FILE_NAME attributes for each [`NtfsFileNamespace`].
+    /// Files with hard links have further $FILE_NAME attributes for each directory they are in.
+    /// You may optionally filter for a namespace and parent directory via the parameters.
+    ///
+    /// This internally calls [`NtfsFile::names`] to iterate through the file's
+    /// $FILE_NAME attributes and pick up the first matching one.
+    pub fn name<T>(
+        &self,
+        fs: &mut T,
+        match_namespace: Option<NtfsFileNamespace>,
+        match_parent_record_number: Option<u64>,
+    ) -> Option<Result<NtfsFileName>>
+    where
+        T: Read + Seek,
+    {
+        let mut iter = self.names();
+
+        while let Some(file_name) = iter.next(fs) {
+            let file_name = iter_try!(file_name);
+
+            if let Some(namespace) = match_namespace {
+                if file_name.namespace() != namespace {
+                    continue;
+                }
+            }
+
+            if let Some(parent_record_number) = match_parent_record_number {
+                if file_name.parent_directory_reference().file_record_number()
+                    != parent_record_number
+                {
+                    continue;
+                }
+            }
+
+            return Some(Ok(file_name));
+        }
+
+        None
+    }
+
+    /// Returns an iterator over all $FILE_NAME attributes of this file (see [`NtfsFileName`]).
+    ///
+    /// NTFS creates a $FILE_NAME attribute for every hard link of a file, and an additional one for
+    /// the short name in the [`NtfsFileNamespace::Dos`] namespace (if any).
+    /// Each [`NtfsFileName`] contains a reference to its parent directory, so this iterator can be used
+    /// to enumerate all (parent directory, name) pairs of a file.
+    ///
+    /// Like [`NtfsFile::attributes`], this iterator traverses Attribute Lists.
+    pub fn names<'f>(&'f self) -> NtfsFileNames<'n, 'f> {
+        NtfsFileNames::new(self)
+    }
+
+    /// Returns the instance identifier that will be assigned to the next attribute added to this File Record.
+    ///
+    /// NTFS increments this number whenever an attribute is added, and never reuses instance identifiers
+    /// of removed attributes (see [`NtfsAttribute::instance`]).
+    /// It is reset to zero when the File Record is reused for a new file.
+    pub fn next_attribute_instance(&self) -> u16 {
+        let start = offset_of!(FileRecordHeader, next_attribute_instance);
+        LittleEndian::read_u16(&self.record.data()[start..])
+    }
+
+    /// Returns the [`Ntfs`] object reference associated to this file.
+    pub fn ntfs(&self) -> &'n Ntfs {
+        self.ntfs
+    }
+
+    /// Convenience function to get the $OBJECT_ID attribute of this file (see [`NtfsObjectId`]).
+    ///
+    /// Contrary to [`NtfsFile::info`], this traverses Attribute Lists, because the $OBJECT_ID attribute
+    /// may have been moved to an extension record.
+    /// Returns [`NtfsError::AttributeNotFound`] if this file has no object identifier.
+    pub fn object_id<T>(&self, fs: &mut T) -> Result<NtfsObjectId>
+    where
+        T: Read + Seek,
+    {
+        let item = self.attribute_by_ty_and_name(
+            fs,
+            NtfsAttributeType::ObjectId,
+            None,
+            NtfsCaseSensitivity::Sensitive,
+        )?;
+        let attribute = item.to_attribute()?;
+        attribute.resident_structured_value::<NtfsObjectId>()
+    }
+
+    /// Replaces the value of a resident $DATA attribute of this File Record with `data` and writes the
+    /// entire File Record back to the filesystem.
+    ///
+    /// Like in [`NtfsFile::data`], passing an empty `data_stream_name` selects the unnamed $DATA attribute.
+    /// Only attributes stored in this File Record are considered (Attribute Lists are not traversed).
+    ///
+    /// The length of the attribute stays the same, so no other attribute has to be moved.
+    /// Therefore, `data` must fit into the space already reserved for the value, otherwise
+    /// [`NtfsError::ResidentValueTooLarge`] is returned.
+    /// Non-resident streams are rejected with [`NtfsError::UnexpectedNonResidentAttribute`].
+    ///
+    /// The data size of the File Record (see [`NtfsFile::data_size`]) is not changed, because it covers the
+    /// space occupied by all attributes and not the length of any value.
+    /// Likewise, the file sizes duplicated into $FILE_NAME attributes and directory indexes are not updated.
+    /// See [`NtfsFile::update_standard_information`] for further limitations of writing File Records.
+    #[cfg(feature = "write")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "write")))]
+    pub fn overwrite_resident_data<T>(
+        &mut self,
+        fs: &mut T,
+        data_stream_name: &str,
+        data: &[u8],
+    ) -> Result<()>
+    where
+        T: Read + Write + Seek,
+    {
+        let (capacity_range, length_offset) = {
+            let attribute = self.find_resident_attribute(
+                NtfsAttributeType::Data,
+                Some(data_stream_name),
+                None,
+            )?;
+            if !attribute.is_resident() {
+                return Err(NtfsError::UnexpectedNonResidentAttribute {
+                    position: attribute.position(),
+                });
+            }
+
+            let capacity_range = attribute.resident_value_capacity_range()?;
+            if data.len() > capacity_range.len() {
+                return Err(NtfsError::ResidentValueTooLarge {
+                    position: attribute.position(),
+                    capacity: capacity_range.len() as u32,
+                    actual: data.len(),
+                });
+            }
+
+            (capacity_range, attribute.resident_value_length_offset())
+        };
+
+        self.write_record(fs, |record_data| {
+            // Clear the entire capacity to not leave any bytes of the previous value behind.
+            let value_data = &mut record_data[capacity_range];
+            value_data.fill(0);
+            value_data[..data.len()].copy_from_slice(data);
+
+            LittleEndian::write_u32(&mut record_data[length_offset..], data.len() as u32);
+        })
     }
 
     /// Returns the absolute byte position of this File Record in the NTFS filesystem.
@@ -492,7 +1082,12 @@ impl<'n> NtfsFile<'n> {
         self.record.position()
     }
 
-    pub(crate) fn record_data(&self) -> &[u8] {
+    pub(crate) fn record(&self) -> &Record {
+        &self.record
+    }
+
+    /// Returns the raw bytes of this File Record, after the fixups of the Update Sequence Array have been applied.
+    pub fn record_data(&self) -> &[u8] {
         self.record.data()
     }
 
@@ -506,6 +1101,179 @@ impl<'n> NtfsFile<'n> {
         LittleEndian::read_u16(&self.record.data()[start..])
     }
 
+    /// Returns the bytes between the end marker of the attributes and the used size of this File Record.
+    ///
+    /// These bytes follow the 4-byte end marker and are not part of any attribute.
+    /// They often hold remnants of attributes that were previously stored in this File Record,
+    /// which makes them valuable for recovering deleted filenames.
+    /// The range is clamped to the actual record buffer, so this never fails even for corrupted records.
+    /// See [`NtfsFile::allocated_slack`] for the bytes after the used size.
+    pub fn slack(&self) -> &[u8] {
+        let data = self.record_data();
+        let end = usize::min(self.data_size() as usize, data.len());
+        let start = self
+            .end_marker_offset(end)
+            .saturating_add(mem::size_of::<u32>());
+        let start = usize::min(start, end);
+
+        &data[start..end]
+    }
+
+    /// Convenience function to get the $STANDARD_INFORMATION attribute of this file.
+    ///
+    /// This is an alias of [`NtfsFile::info`].
+    pub fn standard_information(&self) -> Result<NtfsStandardInformation> {
+        self.info()
+    }
+
+    /// Returns the Update Sequence Number (USN) of this NTFS File Record.
+    ///
+    /// The USN is incremented on every write of the File Record and stored at the end of every sector
+    /// to detect torn writes.
+    /// Comparing it between the copies in $MFT and $MFTMirr tells whether they have been written together.
+    pub fn update_sequence_number(&self) -> u16 {
+        self.record.fixed_up_update_sequence_number()
+    }
+
+    /// Modifies the resident $STANDARD_INFORMATION attribute of this File Record in place and writes the
+    /// entire File Record back to the filesystem.
+    ///
+    /// `f` gets an [`NtfsStandardInformationUpdate`] to change timestamps and file attribute flags.
+    /// None of these changes alter the size of any structure, so the layout of the File Record stays intact.
+    /// The Update Sequence Array is recomputed before writing, just like Windows does.
+    ///
+    /// Like [`NtfsFile::info`], this only considers a $STANDARD_INFORMATION attribute stored in this File Record.
+    /// Fixups are always validated when loading a File Record, so every [`NtfsFile`] can be written back.
+    /// This File Record is only updated if writing succeeded.
+    ///
+    /// Note that the copy of this File Record in $MFTMirr (if any) is not updated, and the change is not logged
+    /// to $LogFile.
+    #[cfg(feature = "write")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "write")))]
+    pub fn update_standard_information<T, F>(&mut self, fs: &mut T, f: F) -> Result<()>
+    where
+        T: Read + Write + Seek,
+        F: FnOnce(&mut NtfsStandardInformationUpdate),
+    {
+        let range = {
+            let attribute =
+                self.find_resident_attribute(NtfsAttributeType::StandardInformation, None, None)?;
+            if !attribute.is_resident() {
+                return Err(NtfsError::UnexpectedNonResidentAttribute {
+                    position: attribute.position(),
+                });
+            }
+
+            let range = attribute.resident_value_range()?;
+            NtfsStandardInformationUpdate::validate_size(attribute.position(), range.len())?;
+            range
+        };
+
+        self.write_record(fs, |record_data| {
+            f(&mut NtfsStandardInformationUpdate::new(
+                &mut record_data[range],
+            ))
+        })
+    }
+
+    /// Returns information about the compression of this file by the Windows Overlay Filter (WOF),
+    /// or `None` if this file has no WOF reparse point.
+    ///
+    /// Windows uses WOF for system-compressed files (e.g. via `compact.exe /EXE` or CompactOS).
+    /// Use [`NtfsFile::data_resolved`] to read their uncompressed data.
+    pub fn wof_info<T>(&self, fs: &mut T) -> Option<Result<NtfsWofInfo>>
+    where
+        T: Read + Seek,
+    {
+        let item = match self.attribute_by_ty_and_name(
+            fs,
+            NtfsAttributeType::ReparsePoint,
+            None,
+            NtfsCaseSensitivity::Sensitive,
+        ) {
+            Ok(item) => item,
+            Err(NtfsError::AttributeNotFound { .. }) => return None,
+            Err(e) => return Some(Err(e)),
+        };
+        let attribute = iter_try!(item.to_attribute());
+        let mut value = iter_try!(attribute.value(fs));
+        let position = value.data_position();
+
+        // The WOF reparse data is tiny, so a larger value can't belong to it and only its beginning is read.
+        let mut data = [0u8; 24];
+        let length = usize::min(data.len(), value.len() as usize);
+        iter_try!(value.read_exact(fs, &mut data[..length]));
+
+        NtfsWofInfo::from_reparse_point(&data[..length], position).transpose()
+    }
+
+    /// Applies `f` to a copy of the (fixed up) data of this File Record, recomputes the Update Sequence Array,
+    /// and writes the File Record back to its position on the filesystem.
+    ///
+    /// This File Record is only updated if writing succeeded.
+    #[cfg(feature = "write")]
+    fn write_record<T, F>(&mut self, fs: &mut T, f: F) -> Result<()>
+    where
+        T: Read + Write + Seek,
+        F: FnOnce(&mut [u8]),
+    {
+        let position = self
+            .position()
+            .value()
+            .ok_or(NtfsError::InvalidFileRecordPosition {
+                position: self.position(),
+            })?;
+
+        let mut record = self.record.clone();
+        f(record.data_mut());
+
+        record.unfixup()?;
+        fs.seek(SeekFrom::Start(position.get()))?;
+        fs.write_all(record.data())?;
+        record.fixup()?;
+
+        #[cfg(feature = "std")]
+        self.ntfs.uncache_file_record(self.file_record_number);
+
+        self.record = record;
+        Ok(())
+    }
+
+    /// Returns the offset of the attribute type field that is not followed by another attribute.
+    ///
+    /// This is the offset of the end marker for a valid File Record.
+    /// For a corrupted one, it's the offset of the first attribute header that doesn't fit into `data_size` bytes
+    /// or has a zero length.
+    fn end_marker_offset(&self, data_size: usize) -> usize {
+        let data = self.record_data();
+        let mut offset = self.first_attribute_offset() as usize;
+
+        // `offset` saturates at `usize::MAX` for a bogus attribute length, so the header end must not overflow.
+        while let Some(header_end) = offset.checked_add(2 * mem::size_of::<u32>()) {
+            let header = match data.get(offset..header_end) {
+                Some(header) => header,
+                None => break,
+            };
+            if header_end > data_size {
+                break;
+            }
+
+            let ty = LittleEndian::read_u32(header);
+            if ty == NtfsAttributeType::End as u32 {
+                break;
+            }
+
+            let length = LittleEndian::read_u32(&header[mem::size_of::<u32>()..]) as usize;
+            if length == 0 {
+                break;
+            }
+
+            offset = offset.saturating_add(length);
+        }
+
+        offset
+    }
+
     fn validate_signature(record: &Record) -> Result<()> {
         let signature = &record.signature();
         let expected = b"FILE";
@@ -521,23 +1289,1199 @@ impl<'n> NtfsFile<'n> {
         }
     }
 
+    /// Checks that the File Record flags and all $FILE_NAME attributes of this file agree on whether it is
+    /// a directory.
+    ///
+    /// Partially corrupted filesystems may have a File Record flagged as directory whose $FILE_NAME attributes
+    /// say otherwise (or vice versa).
+    /// Directory listings show the latter, while [`NtfsFile::directory_index`] relies on the former.
+    /// This function returns [`NtfsError::DirectoryFlagMismatch`] for the first $FILE_NAME attribute that
+    /// disagrees with the File Record flags.
+    pub fn validate_directory_consistency<T>(&self, fs: &mut T) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        let file_record_is_directory = self.is_directory();
+        let mut iter = self.names();
+
+        while let Some(file_name) = iter.next(fs) {
+            let file_name_is_directory = file_name?.is_directory();
+
+            if file_name_is_directory != file_record_is_directory {
+                return Err(NtfsError::DirectoryFlagMismatch {
+                    position: self.position(),
+                    file_record_is_directory,
+                    file_name_is_directory,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
     fn validate_sizes(&self) -> Result<()> {
+        // Inconsistent size fields are tolerated in lenient mode as long as the used size fits into
+        // the record, because only the latter is used as a bound for parsing the attributes.
         if self.allocated_size() > self.record.len() {
-            return Err(NtfsError::InvalidFileAllocatedSize {
+            self.ntfs.tolerate(NtfsError::InvalidFileAllocatedSize {
                 position: self.record.position(),
                 expected: self.allocated_size(),
                 actual: self.record.len(),
-            });
+            })?;
         }
 
         if self.data_size() > self.allocated_size() {
-            return Err(NtfsError::InvalidFileUsedSize {
+            self.ntfs.tolerate(NtfsError::InvalidFileUsedSize {
                 position: self.record.position(),
                 expected: self.data_size(),
                 actual: self.allocated_size(),
+            })?;
+        }
+
+        if self.data_size() > self.record.len() {
+            return Err(NtfsError::InvalidFileUsedSize {
+                position: self.record.position(),
+                expected: self.data_size(),
+                actual: self.record.len(),
             });
         }
 
         Ok(())
     }
 }
+
+/// Sizes of a single $DATA attribute (data stream) of a file, returned by [`NtfsFile::data_stream_sizes`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct NtfsStreamSizes {
+    name: String,
+    data_size: u64,
+    allocated_size: u64,
+    initialized_size: u64,
+}
+
+impl NtfsStreamSizes {
+    /// Returns the space allocated for the stream, in bytes (see [`NtfsAttribute::allocated_size`]).
+    pub fn allocated_size(&self) -> u64 {
+        self.allocated_size
+    }
+
+    /// Returns the logical size of the stream, in bytes.
+    pub fn data_size(&self) -> u64 {
+        self.data_size
+    }
+
+    /// Returns the size of the initialized part of the stream, in bytes (see [`NtfsAttribute::initialized_size`]).
+    pub fn initialized_size(&self) -> u64 {
+        self.initialized_size
+    }
+
+    /// Returns the name of the stream, which is empty for the default unnamed data stream.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Iterator over
+///   all $FILE_NAME attributes of an [`NtfsFile`],
+///   returning an [`NtfsFileName`] for each entry.
+///
+/// This iterator is returned from the [`NtfsFile::names`] function.
+/// See [`NtfsFileNamesAttached`] for an iterator that implements [`Iterator`] and [`FusedIterator`].
+#[derive(Clone, Debug)]
+pub struct NtfsFileNames<'n, 'f> {
+    attributes: NtfsAttributes<'n, 'f>,
+}
+
+impl<'n, 'f> NtfsFileNames<'n, 'f> {
+    fn new(file: &'f NtfsFile<'n>) -> Self {
+        Self {
+            attributes: file.attributes(),
+        }
+    }
+
+    /// Returns a variant of this iterator that implements [`Iterator`] and [`FusedIterator`]
+    /// by mutably borrowing the filesystem reader.
+    pub fn attach<'a, T>(self, fs: &'a mut T) -> NtfsFileNamesAttached<'n, 'f, 'a, T>
+    where
+        T: Read + Seek,
+    {
+        NtfsFileNamesAttached::new(fs, self)
+    }
+
+    /// See [`Iterator::next`].
+    pub fn next<T>(&mut self, fs: &mut T) -> Option<Result<NtfsFileName>>
+    where
+        T: Read + Seek,
+    {
+        while let Some(item) = self.attributes.next(fs) {
+            let item = iter_try!(item);
+            let attribute = iter_try!(item.to_attribute());
+
+            let ty = iter_try!(attribute.ty());
+            if ty != NtfsAttributeType::FileName {
+                continue;
+            }
+
+            let file_name = iter_try!(attribute.structured_value::<_, NtfsFileName>(fs));
+            return Some(Ok(file_name));
+        }
+
+        None
+    }
+}
+
+/// Iterator over
+///   all $FILE_NAME attributes of an [`NtfsFile`],
+///   returning an [`NtfsFileName`] for each entry,
+///   implementing [`Iterator`] and [`FusedIterator`].
+///
+/// This iterator is returned from the [`NtfsFileNames::attach`] function.
+/// Conceptually the same as [`NtfsFileNames`], but mutably borrows the filesystem
+/// to implement aforementioned traits.
+#[derive(Debug)]
+pub struct NtfsFileNamesAttached<'n, 'f, 'a, T: Read + Seek> {
+    fs: &'a mut T,
+    names: NtfsFileNames<'n, 'f>,
+}
+
+impl<'n, 'f, 'a, T> NtfsFileNamesAttached<'n, 'f, 'a, T>
+where
+    T: Read + Seek,
+{
+    fn new(fs: &'a mut T, names: NtfsFileNames<'n, 'f>) -> Self {
+        Self { fs, names }
+    }
+
+    /// Consumes this iterator and returns the inner [`NtfsFileNames`].
+    pub fn detach(self) -> NtfsFileNames<'n, 'f> {
+        self.names
+    }
+}
+
+impl<'n, 'f, 'a, T> Iterator for NtfsFileNamesAttached<'n, 'f, 'a, T>
+where
+    T: Read + Seek,
+{
+    type Item = Result<NtfsFileName>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.names.next(self.fs)
+    }
+}
+
+impl<'n, 'f, 'a, T> FusedIterator for NtfsFileNamesAttached<'n, 'f, 'a, T> where T: Read + Seek {}
+
+/// Compares an attribute name to `match_name` as specified by `case_sensitivity`.
+fn attribute_name_matches(
+    ntfs: &Ntfs,
+    name: &U16StrLe,
+    match_name: &str,
+    case_sensitivity: NtfsCaseSensitivity,
+) -> Result<bool> {
+    let matches = match case_sensitivity {
+        NtfsCaseSensitivity::Insensitive => name.upcase_cmp(ntfs, &match_name)? == Ordering::Equal,
+        NtfsCaseSensitivity::Sensitive => *name == match_name,
+    };
+
+    Ok(matches)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::helpers::tests::{
+        add_resident_attribute, attribute_list_entry, attribute_offset, file_reference,
+        insert_attribute, make_extension_record, patch_file_record_number, remove_attribute,
+        take_attribute_instance, AttributeBuilder,
+    };
+    use crate::ntfs::NtfsValidationMode;
+
+    #[test]
+    fn test_attribute_by_instance() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let mft = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::MFT as u64)
+            .unwrap();
+
+        // The MFT File Record of testfs1 has these attribute instances.
+        let expected = [
+            (0, NtfsAttributeType::StandardInformation),
+            (1, NtfsAttributeType::Data),
+            (2, NtfsAttributeType::FileName),
+            (3, NtfsAttributeType::Bitmap),
+        ];
+        for (instance, ty) in expected {
+            let attribute = mft.attribute_by_instance(instance).unwrap();
+            assert_eq!(attribute.instance(), instance);
+            assert_eq!(attribute.ty().unwrap(), ty);
+        }
+
+        let error = mft.attribute_by_instance(4).unwrap_err();
+        assert!(matches!(
+            error,
+            NtfsError::AttributeInstanceNotFound { instance: 4, .. }
+        ));
+        assert_eq!(error.position(), Some(mft.position()));
+    }
+
+    #[test]
+    fn test_base_file_record_and_next_attribute_instance() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let mft = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::MFT as u64)
+            .unwrap();
+
+        // The MFT is a base File Record, whose attributes have the instances 0 to 3.
+        assert!(mft.is_base_record());
+        assert_eq!(mft.base_file_record().file_record_number(), 0);
+        assert_eq!(mft.base_file_record().sequence_number(), 0);
+        assert_eq!(mft.next_attribute_instance(), 4);
+    }
+
+    #[test]
+    fn test_attribute_by_ty_and_name() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_directory = ntfs.root_directory(&mut testfs1).unwrap();
+
+        let item = root_directory
+            .attribute_by_ty_and_name(
+                &mut testfs1,
+                NtfsAttributeType::IndexRoot,
+                Some("$I30"),
+                NtfsCaseSensitivity::Sensitive,
+            )
+            .unwrap();
+        let attribute = item.to_attribute().unwrap();
+        assert_eq!(attribute.ty().unwrap(), NtfsAttributeType::IndexRoot);
+        assert_eq!(attribute.name().unwrap(), "$I30");
+
+        // Without a name, the first attribute of the given type is returned.
+        let item = root_directory
+            .attribute_by_ty_and_name(
+                &mut testfs1,
+                NtfsAttributeType::StandardInformation,
+                None,
+                NtfsCaseSensitivity::Sensitive,
+            )
+            .unwrap();
+        assert_eq!(
+            item.to_attribute().unwrap().ty().unwrap(),
+            NtfsAttributeType::StandardInformation
+        );
+
+        // A case-insensitive lookup finds the index under a differently cased name.
+        let item = root_directory
+            .attribute_by_ty_and_name(
+                &mut testfs1,
+                NtfsAttributeType::IndexRoot,
+                Some("$i30"),
+                NtfsCaseSensitivity::Insensitive,
+            )
+            .unwrap();
+        assert_eq!(item.to_attribute().unwrap().name().unwrap(), "$I30");
+
+        // A case-sensitive lookup doesn't.
+        let error = root_directory
+            .attribute_by_ty_and_name(
+                &mut testfs1,
+                NtfsAttributeType::IndexRoot,
+                Some("$i30"),
+                NtfsCaseSensitivity::Sensitive,
+            )
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            NtfsError::AttributeNotFound {
+                ty: NtfsAttributeType::IndexRoot,
+                ..
+            }
+        ));
+        assert_eq!(error.position(), Some(root_directory.position()));
+    }
+
+    #[test]
+    fn test_attribute_probes() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let root_directory = ntfs.root_directory(&mut testfs1).unwrap();
+        assert!(root_directory
+            .has_attribute(&mut testfs1, NtfsAttributeType::IndexRoot)
+            .unwrap());
+        assert!(!root_directory
+            .has_attribute(&mut testfs1, NtfsAttributeType::Data)
+            .unwrap());
+        assert!(!root_directory
+            .has_attribute(&mut testfs1, NtfsAttributeType::ReparsePoint)
+            .unwrap());
+        assert_eq!(
+            root_directory
+                .standard_information()
+                .unwrap()
+                .file_attributes(),
+            root_directory.info().unwrap().file_attributes()
+        );
+
+        // No file of testfs1 has an object identifier.
+        assert!(!root_directory
+            .has_attribute(&mut testfs1, NtfsAttributeType::ObjectId)
+            .unwrap());
+        assert!(matches!(
+            root_directory.object_id(&mut testfs1),
+            Err(NtfsError::AttributeNotFound {
+                ty: NtfsAttributeType::ObjectId,
+                ..
+            })
+        ));
+
+        // $Secure has the named data stream "$SDS", but no unnamed one.
+        let secure = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::Secure as u64)
+            .unwrap();
+        assert!(secure.has_named_data_stream(&mut testfs1, "$SDS").unwrap());
+        assert!(secure.has_named_data_stream(&mut testfs1, "$sds").unwrap());
+        assert!(!secure.has_named_data_stream(&mut testfs1, "").unwrap());
+        assert!(!secure.has_named_data_stream(&mut testfs1, "$SII").unwrap());
+        assert!(secure
+            .has_attribute(&mut testfs1, NtfsAttributeType::IndexRoot)
+            .unwrap());
+
+        // The MFT only has the unnamed data stream.
+        let mft = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::MFT as u64)
+            .unwrap();
+        assert!(mft.has_named_data_stream(&mut testfs1, "").unwrap());
+        assert!(!mft.has_named_data_stream(&mut testfs1, "$SDS").unwrap());
+    }
+
+    #[test]
+    fn test_data_stream_sizes() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+
+        // "1000-bytes-file" occupies 2 clusters, "sparse-file" has a sparse Data Run, which still counts
+        // towards its allocated size, and "file-with-12345" is resident.
+        for (name, data_size, allocated_size) in [
+            ("1000-bytes-file", 1000, 1024),
+            ("sparse-file", 500005, 500224),
+            ("file-with-12345", 5, 8),
+        ] {
+            let mut finder = root_dir_index.finder();
+            let entry = NtfsFileNameIndex::find(&mut finder, &ntfs, &mut testfs1, name)
+                .unwrap()
+                .unwrap();
+            let file = entry.to_file(&ntfs, &mut testfs1).unwrap();
+
+            let stream_sizes = file.data_stream_sizes(&mut testfs1).unwrap();
+            assert_eq!(stream_sizes.len(), 1, "{name}");
+            assert_eq!(stream_sizes[0].name(), "");
+            assert_eq!(stream_sizes[0].data_size(), data_size, "{name}");
+            assert_eq!(stream_sizes[0].allocated_size(), allocated_size, "{name}");
+            assert_eq!(stream_sizes[0].initialized_size(), data_size, "{name}");
+        }
+
+        // Directories have no data streams.
+        assert!(root_dir.data_stream_sizes(&mut testfs1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_directory_consistency() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // Both the root directory and "file-with-12345" are consistent.
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        root_dir
+            .validate_directory_consistency(&mut testfs1)
+            .unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        file.validate_directory_consistency(&mut testfs1).unwrap();
+
+        // Clear the directory flag in the File Record of the "many_subdirs" directory.
+        // This field is not protected by the Update Sequence Array.
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                .unwrap()
+                .unwrap();
+        let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        let subdir_record_number = subdir.file_record_number();
+        let start =
+            subdir.position().value().unwrap().get() as usize + offset_of!(FileRecordHeader, flags);
+        let flags = subdir.flags() - NtfsFileFlags::IS_DIRECTORY;
+        drop(subdir);
+        testfs1.get_mut()[start..start + 2].copy_from_slice(&flags.bits().to_le_bytes());
+
+        // The Index Entry in the root directory still claims it to be a directory,
+        // which is reported along with the File Record flags.
+        let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        assert!(!subdir.is_directory());
+        assert!(matches!(
+            subdir.directory_index(&mut testfs1),
+            Err(NtfsError::NotADirectory {
+                index_entry_is_directory: Some(true),
+                ..
+            })
+        ));
+        assert!(matches!(
+            subdir.validate_directory_consistency(&mut testfs1),
+            Err(NtfsError::DirectoryFlagMismatch {
+                file_record_is_directory: false,
+                file_name_is_directory: true,
+                ..
+            })
+        ));
+
+        // Without an Index Entry, we can't tell.
+        let subdir = ntfs.file(&mut testfs1, subdir_record_number).unwrap();
+        assert!(matches!(
+            subdir.into_directory_index(&mut testfs1),
+            Err(NtfsError::NotADirectory {
+                index_entry_is_directory: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_index() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let secure = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::Secure as u64)
+            .unwrap();
+
+        // The $SII index of $Secure maps Security IDs to entries of the $SDS stream.
+        let index = secure.index(&mut testfs1, "$SII").unwrap();
+        let mut entries = index.entries();
+        let mut security_ids = Vec::new();
+        while let Some(entry) = entries.next(&mut testfs1) {
+            let entry = entry.unwrap();
+            let key = entry.key().unwrap().unwrap();
+            let data = entry.data().unwrap().unwrap();
+            assert_eq!(key.len(), 4);
+            assert_eq!(data.len(), 20);
+
+            // Each entry stores its Security ID again in the $SDS header.
+            assert_eq!(key[..], data[4..8]);
+            security_ids.push(LittleEndian::read_u32(&key));
+        }
+        assert_eq!(security_ids, [0x100, 0x101]);
+
+        // Entries can be found with a comparison function working on the raw key bytes.
+        // Like for all indexes, it compares the searched key to the key of each entry.
+        let mut finder = index.finder();
+        let entry = finder
+            .find(&mut testfs1, |key| 0x101.cmp(&LittleEndian::read_u32(key)))
+            .unwrap()
+            .unwrap();
+        let data = entry.data().unwrap().unwrap();
+        assert_eq!(LittleEndian::read_u64(&data[8..]), 0x80);
+
+        assert!(matches!(
+            secure.index(&mut testfs1, "$FOO"),
+            Err(NtfsError::AttributeNotFound {
+                ty: NtfsAttributeType::IndexRoot,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_is_empty_directory() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // "many_subdirs" has so many entries that its index needs an $INDEX_ALLOCATION attribute.
+        let many_subdirs = ntfs.file(&mut testfs1, 68).unwrap();
+        let index_root_item = many_subdirs
+            .attribute_by_ty_and_name(
+                &mut testfs1,
+                NtfsAttributeType::IndexRoot,
+                Some("$I30"),
+                NtfsCaseSensitivity::Sensitive,
+            )
+            .unwrap();
+        let index_root_attribute = index_root_item.to_attribute().unwrap();
+        let index_root = index_root_attribute
+            .resident_structured_value::<NtfsIndexRoot>()
+            .unwrap();
+        assert_eq!(
+            index_root.attribute_type(),
+            Some(NtfsAttributeType::FileName)
+        );
+        assert_eq!(
+            index_root.collation_rule().unwrap(),
+            crate::structured_values::NtfsCollationRule::FileName
+        );
+        assert_eq!(index_root.index_record_size(), 4096);
+        assert_eq!(
+            index_root.clusters_per_index_record(),
+            (4096 / ntfs.cluster_size()) as i8
+        );
+        assert!(index_root.is_large_index());
+        assert!(!many_subdirs.is_empty_directory(&mut testfs1).unwrap());
+
+        // All subdirectories of "many_subdirs" are empty and small.
+        let index = many_subdirs.directory_index(&mut testfs1).unwrap();
+        let mut entries = index.entries();
+        let entry = entries.next(&mut testfs1).unwrap().unwrap();
+        let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        assert!(subdir.is_empty_directory(&mut testfs1).unwrap());
+
+        // Files are not directories at all.
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        assert!(matches!(
+            file.is_empty_directory(&mut testfs1),
+            Err(NtfsError::NotADirectory { .. })
+        ));
+    }
+
+    #[test]
+    fn test_lenient_allocated_size() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // Corrupt the allocated size of the File Record of "file-with-12345".
+        // This field is not protected by the Update Sequence Array.
+        let file_record_number = 65;
+        let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+        let start = file.position().value().unwrap().get() as usize
+            + offset_of!(FileRecordHeader, allocated_size);
+        drop(file);
+        testfs1.get_mut()[start..start + 4].copy_from_slice(&0x10000u32.to_le_bytes());
+
+        // Strict mode rejects the File Record.
+        assert!(matches!(
+            ntfs.file(&mut testfs1, file_record_number),
+            Err(NtfsError::InvalidFileAllocatedSize { .. })
+        ));
+
+        // Lenient mode reports a warning, but lets us read the resident $DATA value.
+        let warning_count = Arc::new(AtomicUsize::new(0));
+        let handler_warning_count = warning_count.clone();
+        ntfs.set_validation_mode(NtfsValidationMode::Lenient);
+        ntfs.set_warning_handler(move |error| {
+            assert!(matches!(error, NtfsError::InvalidFileAllocatedSize { .. }));
+            handler_warning_count.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+        assert_eq!(file.allocated_size(), 0x10000);
+        assert_eq!(warning_count.load(Ordering::SeqCst), 1);
+
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        assert!(data_attribute.is_resident());
+
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+        let mut buf = [0u8; 5];
+        data_value.read_exact(&mut testfs1, &mut buf).unwrap();
+        assert_eq!(&buf, b"12345");
+    }
+
+    #[test]
+    fn test_lenient_data_size_exceeding_record() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.set_validation_mode(NtfsValidationMode::Lenient);
+
+        let file_record_number = 65;
+        let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+        let start = file.position().value().unwrap().get() as usize
+            + offset_of!(FileRecordHeader, data_size);
+        drop(file);
+        testfs1.get_mut()[start..start + 4].copy_from_slice(&0x10000u32.to_le_bytes());
+
+        // A used size beyond the record is never tolerated, even in lenient mode.
+        assert!(matches!(
+            ntfs.file(&mut testfs1, file_record_number),
+            Err(NtfsError::InvalidFileUsedSize { .. })
+        ));
+    }
+
+    #[test]
+    fn test_lenient_corrupted_index_bitmap() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+
+        // Let the resident value of the $BITMAP attribute of "many_subdirs" exceed its attribute.
+        let file_record_number = 68;
+        let value_length_offset = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+            let attribute = file
+                .find_resident_attribute(NtfsAttributeType::Bitmap, Some("$I30"), None)
+                .unwrap();
+            let attribute_offset = attribute.position().value().unwrap().get()
+                - file.position().value().unwrap().get();
+            attribute_offset as usize + 16
+        };
+        patch_file_record_number(&mut testfs1, file_record_number, |record| {
+            LittleEndian::write_u32(&mut record[value_length_offset..], 0x10000);
+        });
+
+        // Strict mode doesn't open the directory.
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+        assert!(matches!(
+            file.directory_index(&mut testfs1),
+            Err(NtfsError::InvalidResidentAttributeValueLength { .. })
+        ));
+        drop(file);
+
+        // Lenient mode reports a warning, but opens the directory without checking its Index Records.
+        let warning_count = Arc::new(AtomicUsize::new(0));
+        let handler_warning_count = warning_count.clone();
+        ntfs.set_validation_mode(NtfsValidationMode::Lenient);
+        ntfs.set_warning_handler(move |error| {
+            assert!(matches!(
+                error,
+                NtfsError::InvalidResidentAttributeValueLength { .. }
+            ));
+            handler_warning_count.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+        let index = file.directory_index(&mut testfs1).unwrap();
+        assert!(!index.has_bitmap());
+        assert_eq!(warning_count.load(Ordering::SeqCst), 1);
+
+        let mut entries = index.entries();
+        let mut entry_count = 0;
+        while let Some(entry) = entries.next(&mut testfs1) {
+            entry.unwrap();
+            entry_count += 1;
+        }
+        assert!(entry_count > 0);
+    }
+
+    #[test]
+    fn test_lsn_and_update_sequence_number() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+
+        let (position, file_record_size) = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let mft = ntfs
+                .file(&mut testfs1, KnownNtfsFileRecordNumber::MFT as u64)
+                .unwrap();
+
+            // testfs1 has been created without journaling.
+            assert_eq!(mft.lsn(), 0);
+            assert_eq!(mft.update_sequence_number(), 0x207);
+
+            // $MFT and $MFTMirr have been written together.
+            let mft_mirror = ntfs
+                .file_at_position(&mut testfs1, ntfs.mft_mirror_position(), 0)
+                .unwrap();
+            assert_eq!(
+                mft_mirror.update_sequence_number(),
+                mft.update_sequence_number()
+            );
+
+            (
+                mft.position().value().unwrap().get() as usize,
+                ntfs.file_record_size() as usize,
+            )
+        };
+
+        crate::helpers::tests::patch_file_record(
+            &mut testfs1,
+            position,
+            file_record_size,
+            |record| {
+                // The LSN follows the signature and the Update Sequence Array offset and count.
+                LittleEndian::write_u64(&mut record[8..], 0x1234_5678_9abc);
+            },
+        );
+
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let mft = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::MFT as u64)
+            .unwrap();
+        assert_eq!(mft.lsn(), 0x1234_5678_9abc);
+        assert_eq!(mft.update_sequence_number(), 0x207);
+    }
+
+    #[test]
+    fn test_slack() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file_record_number = 65;
+        let file_record_size = ntfs.file_record_size() as usize;
+
+        let (position, end_marker_offset, allocated_size) = {
+            let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+            let attributes_length = file
+                .attributes_raw()
+                .map(|attribute| attribute.unwrap().attribute_length() as usize)
+                .sum::<usize>();
+
+            (
+                file.position().value().unwrap().get() as usize,
+                file.first_attribute_offset() as usize + attributes_length,
+                file.allocated_size() as usize,
+            )
+        };
+
+        // Put known bytes after the end marker and after the used size.
+        let slack = (1..=16).collect::<Vec<u8>>();
+        let data_size = end_marker_offset + 4 + slack.len();
+        crate::helpers::tests::patch_file_record(
+            &mut testfs1,
+            position,
+            file_record_size,
+            |record| {
+                let start = offset_of!(FileRecordHeader, data_size);
+                record[start..start + 4].copy_from_slice(&(data_size as u32).to_le_bytes());
+                record[end_marker_offset + 4..data_size].copy_from_slice(&slack);
+                record[data_size..allocated_size].fill(0x5a);
+            },
+        );
+
+        let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+        assert_eq!(file.record_data().len(), file_record_size);
+        assert_eq!(file.slack(), slack);
+        assert_eq!(
+            file.allocated_slack(),
+            vec![0x5a; allocated_size - data_size]
+        );
+        drop(file);
+
+        // Corrupt the sizes: The used size ends before the first attribute and the allocated size exceeds the record.
+        crate::helpers::tests::patch_file_record(
+            &mut testfs1,
+            position,
+            file_record_size,
+            |record| {
+                let start = offset_of!(FileRecordHeader, data_size);
+                record[start..start + 4].copy_from_slice(&0x18u32.to_le_bytes());
+                let start = offset_of!(FileRecordHeader, allocated_size);
+                record[start..start + 4].copy_from_slice(&0x10000u32.to_le_bytes());
+            },
+        );
+
+        ntfs.set_validation_mode(NtfsValidationMode::Lenient);
+        let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+        assert!(file.slack().is_empty());
+        assert_eq!(file.allocated_slack().len(), file_record_size - 0x18);
+    }
+
+    /// Adds a hard link "hardlink" in the "many_subdirs" directory and a DOS name "FILE-W~1" to the
+    /// File Record of "file-with-12345" in testfs1.
+    /// The original name is turned into a Win32 name accordingly.
+    fn testfs1_with_hard_link() -> std::io::Cursor<Vec<u8>> {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+
+        let many_subdirs_reference = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let many_subdirs = ntfs.file(&mut testfs1, 68).unwrap();
+            file_reference(68, many_subdirs.sequence_number())
+        };
+
+        patch_file_record_number(&mut testfs1, 65, |record| {
+            const FILE_NAME_HEADER_SIZE: usize = 66;
+
+            // Turn the existing name into a Win32 name and use its value as a template for the new names.
+            let file_name_offset = attribute_offset(record, NtfsAttributeType::FileName);
+            let value_offset = file_name_offset
+                + LittleEndian::read_u16(&record[file_name_offset + 20..]) as usize;
+            record[value_offset + FILE_NAME_HEADER_SIZE - 1] = NtfsFileNamespace::Win32 as u8;
+            let template = record[value_offset..value_offset + FILE_NAME_HEADER_SIZE].to_vec();
+
+            // Insert the new $FILE_NAME attributes after the existing one.
+            let mut insert_offset =
+                file_name_offset + LittleEndian::read_u32(&record[file_name_offset + 4..]) as usize;
+            let new_names = [
+                (None, NtfsFileNamespace::Dos, "FILE-W~1"),
+                (
+                    Some(many_subdirs_reference),
+                    NtfsFileNamespace::Posix,
+                    "hardlink",
+                ),
+            ];
+
+            for (parent_reference, namespace, name) in new_names {
+                let mut value = template.clone();
+                if let Some(parent_reference) = parent_reference {
+                    value[..8].copy_from_slice(&parent_reference);
+                }
+                value[FILE_NAME_HEADER_SIZE - 2] = name.len() as u8;
+                value[FILE_NAME_HEADER_SIZE - 1] = namespace as u8;
+                value.extend(name.encode_utf16().flat_map(|c| c.to_le_bytes()));
+
+                let attribute = AttributeBuilder::resident(NtfsAttributeType::FileName, &value)
+                    .indexed()
+                    .instance(take_attribute_instance(record))
+                    .build();
+                insert_attribute(record, insert_offset, &attribute);
+                insert_offset += attribute.len();
+            }
+
+            // NTFS counts every $FILE_NAME attribute, including the DOS one.
+            let start = offset_of!(FileRecordHeader, hard_link_count);
+            let hard_link_count = LittleEndian::read_u16(&record[start..]) + new_names.len() as u16;
+            LittleEndian::write_u16(&mut record[start..], hard_link_count);
+        });
+
+        testfs1
+    }
+
+    /// Moves the $STANDARD_INFORMATION attribute of "file-with-12345" in testfs1 to the extension record 100
+    /// (which is overwritten for that purpose) and replaces it by an $ATTRIBUTE_LIST referencing it.
+    fn testfs1_with_extension_standard_information() -> std::io::Cursor<Vec<u8>> {
+        const BASE_RECORD: u64 = 65;
+        const EXTENSION_RECORD: u64 = 100;
+
+        let mut testfs1 = crate::helpers::tests::testfs1();
+
+        let (base_reference, extension_reference) = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let base_file = ntfs.file(&mut testfs1, BASE_RECORD).unwrap();
+            let extension_file = ntfs.file(&mut testfs1, EXTENSION_RECORD).unwrap();
+
+            (
+                file_reference(BASE_RECORD, base_file.sequence_number()),
+                file_reference(EXTENSION_RECORD, extension_file.sequence_number()),
+            )
+        };
+
+        // Replace the $STANDARD_INFORMATION attribute by an $ATTRIBUTE_LIST with a single entry.
+        let mut attribute = Vec::new();
+        patch_file_record_number(&mut testfs1, BASE_RECORD, |record| {
+            let offset = attribute_offset(record, NtfsAttributeType::StandardInformation);
+            attribute = remove_attribute(record, offset);
+
+            let instance = LittleEndian::read_u16(&attribute[14..]);
+            let entry = attribute_list_entry(
+                NtfsAttributeType::StandardInformation,
+                "",
+                0,
+                extension_reference,
+                instance,
+            );
+            let list_attribute =
+                AttributeBuilder::resident(NtfsAttributeType::AttributeList, &entry)
+                    .instance(take_attribute_instance(record))
+                    .build();
+            insert_attribute(record, offset, &list_attribute);
+        });
+
+        // Store the $STANDARD_INFORMATION attribute as the only attribute of the extension record.
+        patch_file_record_number(&mut testfs1, EXTENSION_RECORD, |record| {
+            let next_attribute_instance = LittleEndian::read_u16(&attribute[14..]) + 1;
+            make_extension_record(record, base_reference, &attribute, next_attribute_instance);
+        });
+
+        testfs1
+    }
+
+    #[test]
+    fn test_info_with_fs() {
+        let (creation_time, file_attributes) = {
+            let mut testfs1 = crate::helpers::tests::testfs1();
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let file = ntfs.file(&mut testfs1, 65).unwrap();
+            let info = file.info().unwrap();
+            assert_eq!(
+                file.info_with_fs(&mut testfs1).unwrap().creation_time(),
+                info.creation_time()
+            );
+            (info.creation_time(), info.file_attributes())
+        };
+
+        let mut testfs1 = testfs1_with_extension_standard_information();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+
+        assert!(matches!(
+            file.info(),
+            Err(NtfsError::AttributeNotFound {
+                ty: NtfsAttributeType::StandardInformation,
+                ..
+            })
+        ));
+
+        let info = file.info_with_fs(&mut testfs1).unwrap();
+        assert_eq!(info.creation_time(), creation_time);
+        assert_eq!(info.file_attributes(), file_attributes);
+
+        // The remaining attributes of the base File Record are still found.
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+        let mut buf = [0u8; 5];
+        data_value.read_exact(&mut testfs1, &mut buf).unwrap();
+        assert_eq!(&buf, b"12345");
+    }
+
+    #[test]
+    fn test_names() {
+        let (original_hard_link_count, many_subdirs_sequence_number) = {
+            let mut testfs1 = crate::helpers::tests::testfs1();
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let file = ntfs.file(&mut testfs1, 65).unwrap();
+            assert_eq!(file.names().attach(&mut testfs1).count(), 1);
+            let many_subdirs = ntfs.file(&mut testfs1, 68).unwrap();
+
+            (file.hard_link_count(), many_subdirs.sequence_number())
+        };
+        assert_eq!(original_hard_link_count, 1);
+
+        let mut testfs1 = testfs1_with_hard_link();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+
+        let names = file
+            .names()
+            .attach(&mut testfs1)
+            .map(|file_name| {
+                let file_name = file_name.unwrap();
+                (
+                    file_name.parent_directory_reference().file_record_number(),
+                    file_name.namespace(),
+                    file_name.name().to_string_lossy(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                (
+                    KnownNtfsFileRecordNumber::RootDirectory as u64,
+                    NtfsFileNamespace::Win32,
+                    "file-with-12345".to_string()
+                ),
+                (
+                    KnownNtfsFileRecordNumber::RootDirectory as u64,
+                    NtfsFileNamespace::Dos,
+                    "FILE-W~1".to_string()
+                ),
+                (68, NtfsFileNamespace::Posix, "hardlink".to_string()),
+            ]
+        );
+
+        // The hard link count of a File Record counts every $FILE_NAME attribute.
+        assert_eq!(file.hard_link_count(), original_hard_link_count + 2);
+        assert_eq!(file.hard_link_count() as usize, names.len());
+
+        // `name` picks up the first name matching the filters.
+        let file_name = file.name(&mut testfs1, None, None).unwrap().unwrap();
+        assert_eq!(file_name.name(), "file-with-12345");
+        let file_name = file
+            .name(&mut testfs1, Some(NtfsFileNamespace::Dos), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(file_name.name(), "FILE-W~1");
+        let file_name = file.name(&mut testfs1, None, Some(68)).unwrap().unwrap();
+        assert_eq!(file_name.name(), "hardlink");
+
+        // The hard link refers to the actual "many_subdirs" directory.
+        let parent_reference = file_name.parent_directory_reference();
+        assert_eq!(
+            parent_reference.sequence_number(),
+            many_subdirs_sequence_number
+        );
+        let parent = parent_reference.to_file(&ntfs, &mut testfs1).unwrap();
+        assert!(parent.is_directory());
+        assert!(file
+            .name(&mut testfs1, Some(NtfsFileNamespace::Posix), Some(5))
+            .is_none());
+
+        // The file data is still intact.
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        assert_eq!(data_item.to_attribute().unwrap().value_length(), 5);
+    }
+
+    /// Adds a resident $LOGGED_UTILITY_STREAM attribute named "$TXF_DATA" with the given value to the File Record
+    /// of "file-with-12345" in testfs1.
+    fn testfs1_with_txf_data(value: &[u8]) -> std::io::Cursor<Vec<u8>> {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        add_resident_attribute(
+            &mut testfs1,
+            65,
+            NtfsAttributeType::LoggedUtilityStream,
+            "$TXF_DATA",
+            value,
+        );
+        testfs1
+    }
+
+    #[test]
+    fn test_stream() {
+        let mut value = [0u8; 56];
+        value[6] = 0x27;
+        value[22] = 0x42;
+        let mut testfs1 = testfs1_with_txf_data(&value);
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+
+        // Without the upcase table, names are compared exactly.
+        assert!(file
+            .stream(
+                &mut testfs1,
+                NtfsAttributeType::LoggedUtilityStream,
+                "$txf_data"
+            )
+            .is_none());
+        assert!(file
+            .stream(&mut testfs1, NtfsAttributeType::LoggedUtilityStream, "$EFS")
+            .is_none());
+        assert!(file
+            .stream(&mut testfs1, NtfsAttributeType::Data, "$TXF_DATA")
+            .is_none());
+
+        let item = file
+            .stream(
+                &mut testfs1,
+                NtfsAttributeType::LoggedUtilityStream,
+                "$TXF_DATA",
+            )
+            .unwrap()
+            .unwrap();
+        let txf_data = item
+            .to_attribute()
+            .unwrap()
+            .resident_structured_value::<crate::structured_values::NtfsTxfData>()
+            .unwrap();
+        assert_eq!(txf_data.rm_root_file_reference().file_record_number(), 0x27);
+        assert_eq!(txf_data.txid(), 0x42);
+
+        // `data` is `stream` for $DATA attributes.
+        let data_item = file
+            .stream(&mut testfs1, NtfsAttributeType::Data, "")
+            .unwrap()
+            .unwrap();
+        assert_eq!(data_item.to_attribute().unwrap().value_length(), 5);
+
+        drop(file);
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        assert!(file
+            .stream(
+                &mut testfs1,
+                NtfsAttributeType::LoggedUtilityStream,
+                "$txf_data"
+            )
+            .is_some());
+    }
+
+    #[cfg(feature = "write")]
+    #[test]
+    fn test_update_standard_information() {
+        use crate::structured_values::NtfsFileAttributeFlags;
+        use crate::time::NtfsTime;
+
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let new_time = NtfsTime::from(133_000_000_000_000_000);
+        let original_attribute_count;
+
+        let (creation_time, file_attributes) = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let mut file = ntfs.file(&mut testfs1, 65).unwrap();
+            original_attribute_count = file.attributes_raw().count();
+            let info = file.info().unwrap();
+            assert!(!info
+                .file_attributes()
+                .contains(NtfsFileAttributeFlags::READ_ONLY));
+
+            file.update_standard_information(&mut testfs1, |update| {
+                update.set_modification_time(new_time);
+                update.set_file_attributes(
+                    info.file_attributes() | NtfsFileAttributeFlags::READ_ONLY,
+                );
+            })
+            .unwrap();
+
+            // The in-memory File Record has been updated as well.
+            assert_eq!(file.info().unwrap().modification_time(), new_time);
+
+            (info.creation_time(), info.file_attributes())
+        };
+
+        // Re-open the volume and read back the new values.
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        let info = file.info().unwrap();
+        assert_eq!(info.modification_time(), new_time);
+        assert_eq!(info.creation_time(), creation_time);
+        assert_eq!(
+            info.file_attributes(),
+            file_attributes | NtfsFileAttributeFlags::READ_ONLY
+        );
+
+        // All other attributes are still intact.
+        assert_eq!(file.attributes_raw().count(), original_attribute_count);
+    }
+
+    #[cfg(feature = "write")]
+    #[test]
+    fn test_overwrite_resident_data() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+
+        fn read_data(testfs1: &mut std::io::Cursor<Vec<u8>>) -> Vec<u8> {
+            let mut ntfs = Ntfs::new(testfs1).unwrap();
+            ntfs.read_upcase_table(testfs1).unwrap();
+            let file = ntfs.file(testfs1, 65).unwrap();
+
+            let data_item = file.data(testfs1, "").unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+            let mut data_value = data_attribute.value(testfs1).unwrap();
+            let mut data = vec![0u8; data_value.len() as usize];
+            data_value.read_exact(testfs1, &mut data).unwrap();
+            data
+        }
+
+        assert_eq!(read_data(&mut testfs1), b"12345");
+
+        {
+            let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+            ntfs.read_upcase_table(&mut testfs1).unwrap();
+            let mut file = ntfs.file(&mut testfs1, 65).unwrap();
+            let data_size = file.data_size();
+
+            // "file-with-12345" has 8 bytes reserved for its resident value.
+            assert!(matches!(
+                file.overwrite_resident_data(&mut testfs1, "", b"123456789"),
+                Err(NtfsError::ResidentValueTooLarge {
+                    capacity: 8,
+                    actual: 9,
+                    ..
+                })
+            ));
+
+            file.overwrite_resident_data(&mut testfs1, "", b"abcdefgh")
+                .unwrap();
+            assert_eq!(read_data(&mut testfs1), b"abcdefgh");
+
+            file.overwrite_resident_data(&mut testfs1, "", b"xy")
+                .unwrap();
+            assert_eq!(file.data_size(), data_size);
+        }
+
+        assert_eq!(read_data(&mut testfs1), b"xy");
+
+        // Non-resident streams are rejected.
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let mut file = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::MFT as u64)
+            .unwrap();
+        assert!(matches!(
+            file.overwrite_resident_data(&mut testfs1, "", b"xy"),
+            Err(NtfsError::UnexpectedNonResidentAttribute { .. })
+        ));
+    }
+}
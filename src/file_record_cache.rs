@@ -0,0 +1,121 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+//! Least-recently-used cache of File Records, enabled via [`Ntfs::set_file_record_cache_capacity`].
+//!
+//! [`Ntfs::set_file_record_cache_capacity`]: crate::Ntfs::set_file_record_cache_capacity
+
+use alloc::collections::BTreeMap;
+
+use crate::record::Record;
+
+#[derive(Debug)]
+pub(crate) struct FileRecordCache {
+    /// Maximum number of cached File Records.
+    capacity: usize,
+    /// Fixed-up File Records by their File Record Number, along with the tick of their last use.
+    records: BTreeMap<u64, (u64, Record)>,
+    /// File Record Numbers by the tick of their last use, oldest first.
+    last_uses: BTreeMap<u64, u64>,
+    /// Incremented on every use of a File Record.
+    tick: u64,
+}
+
+impl FileRecordCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: BTreeMap::new(),
+            last_uses: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.records.clear();
+        self.last_uses.clear();
+    }
+
+    /// Returns a copy of the cached File Record with the given number (if any) and marks it as most recently used.
+    pub(crate) fn get(&mut self, file_record_number: u64) -> Option<Record> {
+        let tick = self.next_tick();
+        let (last_use, record) = self.records.get_mut(&file_record_number)?;
+
+        self.last_uses.remove(last_use);
+        self.last_uses.insert(tick, file_record_number);
+        *last_use = tick;
+
+        Some(record.clone())
+    }
+
+    /// Caches the given File Record, evicting the least recently used one if the cache is full.
+    pub(crate) fn insert(&mut self, file_record_number: u64, record: Record) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.remove(file_record_number);
+
+        if self.records.len() >= self.capacity {
+            if let Some(oldest) = self.last_uses.values().next().copied() {
+                self.remove(oldest);
+            }
+        }
+
+        let tick = self.next_tick();
+        self.records.insert(file_record_number, (tick, record));
+        self.last_uses.insert(tick, file_record_number);
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    pub(crate) fn remove(&mut self, file_record_number: u64) {
+        if let Some((last_use, _)) = self.records.remove(&file_record_number) {
+            self.last_uses.remove(&last_use);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::types::NtfsPosition;
+
+    fn record(byte: u8) -> Record {
+        Record::new(vec![byte; 4], NtfsPosition::none())
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut cache = FileRecordCache::new(2);
+        cache.insert(1, record(1));
+        cache.insert(2, record(2));
+
+        // Using record 1 makes record 2 the least recently used one, which is evicted next.
+        assert_eq!(cache.get(1).unwrap().data(), [1; 4]);
+        cache.insert(3, record(3));
+        assert!(cache.get(2).is_none());
+        assert_eq!(cache.get(1).unwrap().data(), [1; 4]);
+        assert_eq!(cache.get(3).unwrap().data(), [3; 4]);
+
+        // Inserting an existing record replaces it without evicting another one.
+        cache.insert(3, record(4));
+        assert_eq!(cache.get(3).unwrap().data(), [4; 4]);
+        assert_eq!(cache.get(1).unwrap().data(), [1; 4]);
+
+        cache.remove(1);
+        assert!(cache.get(1).is_none());
+        cache.clear();
+        assert!(cache.get(3).is_none());
+
+        // A capacity of zero caches nothing.
+        let mut cache = FileRecordCache::new(0);
+        cache.insert(1, record(1));
+        assert!(cache.get(1).is_none());
+    }
+}
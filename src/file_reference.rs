@@ -1,17 +1,25 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use binrw::io::{Read, Seek};
+use core::fmt;
+
+use crate::io::{Read, Seek};
 use binrw::BinRead;
 
 use crate::error::Result;
 use crate::file::NtfsFile;
 use crate::ntfs::Ntfs;
 
+/// Mask of the File Record Number (also called segment number) within an [`NtfsFileReference`].
+const FILE_RECORD_NUMBER_MASK: u64 = 0xffff_ffff_ffff;
+
 /// Absolute reference to a File Record on the filesystem, composed out of a File Record Number and a Sequence Number.
 ///
+/// References can be compared and hashed, e.g. to deduplicate hard links while traversing a directory tree.
+/// Two references are equal if both their File Record Number and Sequence Number are equal.
+///
 /// Reference: <https://flatcap.github.io/linux-ntfs/ntfs/concepts/file_reference.html>
-#[derive(BinRead, Clone, Copy, Debug)]
+#[derive(BinRead, Clone, Copy, Eq, Hash, PartialEq)]
 pub struct NtfsFileReference([u8; 8]);
 
 impl NtfsFileReference {
@@ -24,7 +32,15 @@ impl NtfsFileReference {
     /// This can be fed into [`Ntfs::file`] to create an [`NtfsFile`] object for the corresponding File Record
     /// (if you cannot use [`Self::to_file`] for some reason).
     pub fn file_record_number(&self) -> u64 {
-        u64::from_le_bytes(self.0) & 0xffff_ffff_ffff
+        u64::from_le_bytes(self.0) & FILE_RECORD_NUMBER_MASK
+    }
+
+    /// Returns the 48-bit segment number, which is how Microsoft calls the File Record Number.
+    ///
+    /// This is the lower 48 bits of the 64-bit reference, with the Sequence Number in the upper 16 bits masked out.
+    /// It is identical to [`Self::file_record_number`].
+    pub fn segment_number(&self) -> u64 {
+        self.file_record_number()
     }
 
     /// Returns the 16-bit sequence number of the File Record.
@@ -42,3 +58,126 @@ impl NtfsFileReference {
         ntfs.file(fs, self.file_record_number())
     }
 }
+
+impl fmt::Debug for NtfsFileReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NtfsFileReference")
+            .field("file_record_number", &self.file_record_number())
+            .field("sequence_number", &self.sequence_number())
+            .finish()
+    }
+}
+
+impl fmt::Display for NtfsFileReference {
+    /// Formats the reference as "MFT#`file_record_number` seq `sequence_number`", e.g. "MFT#1234 seq 5".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "MFT#{} seq {}",
+            self.file_record_number(),
+            self.sequence_number()
+        )
+    }
+}
+
+impl From<(u64, u16)> for NtfsFileReference {
+    /// Creates a reference from a File Record Number and a Sequence Number.
+    ///
+    /// Only the lower 48 bits of the File Record Number are used, the upper 16 bits are ignored.
+    fn from((file_record_number, sequence_number): (u64, u16)) -> Self {
+        let value = (file_record_number & FILE_RECORD_NUMBER_MASK) | (sequence_number as u64) << 48;
+        Self(value.to_le_bytes())
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<'de> serde::Deserialize<'de> for NtfsFileReference {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(serde::Deserialize)]
+        struct NtfsFileReferenceFields {
+            file_record_number: u64,
+            sequence_number: u16,
+        }
+
+        let fields = NtfsFileReferenceFields::deserialize(deserializer)?;
+        Ok(Self::from((
+            fields.file_record_number,
+            fields.sequence_number,
+        )))
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl serde::Serialize for NtfsFileReference {
+    /// Serializes the reference as a structure with the fields `file_record_number` and `sequence_number`.
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("NtfsFileReference", 2)?;
+        state.serialize_field("file_record_number", &self.file_record_number())?;
+        state.serialize_field("sequence_number", &self.sequence_number())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_file_reference() {
+        // File Record Number 0x1234 with Sequence Number 5, as stored on disk.
+        let bytes = [0x34, 0x12, 0, 0, 0, 0, 0x05, 0];
+        let file_reference = NtfsFileReference::read_le(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(file_reference.file_record_number(), 0x1234);
+        assert_eq!(file_reference.segment_number(), 0x1234);
+        assert_eq!(file_reference.sequence_number(), 5);
+
+        // Constructing the same reference results in the same on-disk layout.
+        let constructed = NtfsFileReference::from((0x1234, 5));
+        assert_eq!(constructed.0, bytes);
+        assert_eq!(constructed, file_reference);
+        assert_ne!(constructed, NtfsFileReference::from((0x1234, 6)));
+
+        // The upper 16 bits of the File Record Number don't leak into the Sequence Number.
+        let truncated = NtfsFileReference::from((0xffff_0000_0000_1234, 5));
+        assert_eq!(truncated, file_reference);
+
+        assert_eq!(file_reference.to_string(), "MFT#4660 seq 5");
+        assert_eq!(
+            format!("{file_reference:?}"),
+            "NtfsFileReference { file_record_number: 4660, sequence_number: 5 }"
+        );
+
+        let set = [
+            file_reference,
+            constructed,
+            NtfsFileReference::from((0x1234, 6)),
+        ]
+        .into_iter()
+        .collect::<HashSet<_>>();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let file_reference = NtfsFileReference::from((0x1234, 5));
+        let json = serde_json::to_string(&file_reference).unwrap();
+        assert_eq!(json, r#"{"file_record_number":4660,"sequence_number":5}"#);
+
+        let file_reference2 = serde_json::from_str::<NtfsFileReference>(&json).unwrap();
+        assert_eq!(file_reference, file_reference2);
+    }
+}
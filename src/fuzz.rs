@@ -0,0 +1,214 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+//! Entry points into crate-internal parsers for structured fuzzing.
+//!
+//! Fuzzing through [`Ntfs::new`] with an entire filesystem image spends most of the time on parsing the boot sector.
+//! The functions of this module let fuzzers feed arbitrary bytes directly into the parsers of
+//! Update Sequence Arrays, Index Entries, and Attribute List entries instead.
+//!
+//! Index Entry keys can already be parsed from arbitrary bytes through the public
+//! [`NtfsIndexEntryKey::key_from_slice`] (e.g. on [`NtfsFileName`]).
+//!
+//! This module is only available with the `fuzz-internals` feature and is not covered by any
+//! semver guarantees.
+//!
+//! [`Ntfs::new`]: crate::Ntfs::new
+//! [`NtfsFileName`]: crate::structured_values::NtfsFileName
+//! [`NtfsIndexEntryKey::key_from_slice`]: crate::indexes::NtfsIndexEntryKey::key_from_slice
+
+use core::mem;
+
+use crate::io::Cursor;
+use alloc::vec::Vec;
+
+use crate::error::Result;
+use crate::index_entry::{NtfsIndexEntry, NtfsIndexNodeEntries};
+use crate::indexes::NtfsIndexEntryType;
+use crate::record::{Record, RecordHeader};
+use crate::structured_values::NtfsAttributeListEntry;
+use crate::types::NtfsPosition;
+
+/// Applies the fixups of the Update Sequence Array to the raw bytes of a File Record or Index Record
+/// and returns the fixed-up bytes.
+///
+/// Returns `None` if `data` doesn't even cover a record header.
+/// The crate itself never creates such short records, because record sizes are validated beforehand.
+pub fn record_fixup(data: Vec<u8>) -> Option<Result<Vec<u8>>> {
+    if data.len() < mem::size_of::<RecordHeader>() {
+        return None;
+    }
+
+    let mut record = Record::new(data, NtfsPosition::none());
+    Some(record.fixup().map(|()| record.into_data()))
+}
+
+/// Parses a single Index Entry from the beginning of `slice`.
+pub fn index_entry<E>(slice: &[u8]) -> Result<NtfsIndexEntry<'_, E>>
+where
+    E: NtfsIndexEntryType,
+{
+    NtfsIndexEntry::new(slice, NtfsPosition::none())
+}
+
+/// Returns an iterator over the Index Entries of an index node, like the one of an $INDEX_ROOT attribute
+/// or an Index Record.
+pub fn index_node_entries<E>(slice: &[u8]) -> NtfsIndexNodeEntries<'_, E>
+where
+    E: NtfsIndexEntryType,
+{
+    NtfsIndexNodeEntries::new(slice, NtfsPosition::none())
+}
+
+/// Parses a single Attribute List entry from the beginning of `slice`.
+///
+/// Use [`NtfsAttributeList::Resident`] to iterate over all entries of an entire Attribute List.
+///
+/// [`NtfsAttributeList::Resident`]: crate::structured_values::NtfsAttributeList::Resident
+pub fn attribute_list_entry(slice: &[u8]) -> Result<NtfsAttributeListEntry> {
+    let mut cursor = Cursor::new(slice);
+    NtfsAttributeListEntry::new(&mut cursor, NtfsPosition::none())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::error::NtfsError;
+    use crate::indexes::NtfsFileNameIndex;
+    use crate::structured_values::NtfsAttributeList;
+
+    /// Returns the inputs checked into `fuzz/corpus/<target>`, which reproduce previously reported crashes.
+    fn corpus(target: &str) -> Vec<Vec<u8>> {
+        let dir = [env!("CARGO_MANIFEST_DIR"), "fuzz", "corpus", target]
+            .iter()
+            .collect::<PathBuf>();
+
+        let mut paths = fs::read_dir(dir)
+            .unwrap()
+            .map(|dir_entry| dir_entry.unwrap().path())
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert!(!paths.is_empty());
+
+        paths.iter().map(|path| fs::read(path).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_record_fixup() {
+        // Inputs not covering a record header are rejected upfront.
+        assert!(record_fixup(Vec::new()).is_none());
+        assert!(record_fixup(vec![0u8; 15]).is_none());
+
+        // An Update Sequence Count of zero doesn't even cover the Update Sequence Number.
+        assert!(matches!(
+            record_fixup(vec![0u8; 16]),
+            Some(Err(NtfsError::InvalidUpdateSequenceCount { .. }))
+        ));
+
+        // The Update Sequence Array must not exceed the record.
+        let mut data = vec![0u8; 512];
+        data[4..6].copy_from_slice(&0xfffeu16.to_le_bytes());
+        data[6..8].copy_from_slice(&3u16.to_le_bytes());
+        assert!(matches!(
+            record_fixup(data),
+            Some(Err(NtfsError::InvalidUpdateSequenceNumberRange { .. }))
+        ));
+
+        let mut data = vec![0u8; 512];
+        data[4..6].copy_from_slice(&0x30u16.to_le_bytes());
+        data[6..8].copy_from_slice(&3u16.to_le_bytes());
+        assert!(matches!(
+            record_fixup(data),
+            Some(Err(NtfsError::UpdateSequenceArrayExceedsRecordSize { .. }))
+        ));
+
+        // A valid single-sector record.
+        let mut data = vec![0u8; 512];
+        data[4..6].copy_from_slice(&0x30u16.to_le_bytes());
+        data[6..8].copy_from_slice(&2u16.to_le_bytes());
+        data[0x30..0x32].copy_from_slice(&[0x12, 0x34]);
+        data[0x32..0x34].copy_from_slice(&[0xab, 0xcd]);
+        data[510..512].copy_from_slice(&[0x12, 0x34]);
+        let data = record_fixup(data).unwrap().unwrap();
+        assert_eq!(&data[510..512], &[0xab, 0xcd]);
+    }
+
+    #[test]
+    fn test_index_entries() {
+        assert!(index_entry::<NtfsFileNameIndex>(&[]).is_err());
+        assert!(index_entry::<NtfsFileNameIndex>(&[0u8; 16]).is_err());
+
+        // A last entry without a key.
+        let mut data = [0u8; 16];
+        data[8..10].copy_from_slice(&16u16.to_le_bytes());
+        data[12] = 2;
+        let entry = index_entry::<NtfsFileNameIndex>(&data).unwrap();
+        assert!(entry.key().is_none());
+
+        // A key length exceeding the entry must not panic.
+        let mut data = [0u8; 24];
+        data[8..10].copy_from_slice(&24u16.to_le_bytes());
+        data[10..12].copy_from_slice(&0xffffu16.to_le_bytes());
+        let entries = index_node_entries::<NtfsFileNameIndex>(&data)
+            .map(|entry| entry.map(|entry| entry.key().map(|key| key.is_ok())))
+            .collect::<Vec<_>>();
+        assert!(!entries.is_empty());
+    }
+
+    #[test]
+    fn test_attribute_list_entries() {
+        // Truncated entries used to end up in an `unreachable!()` for nested binrw errors.
+        assert!(attribute_list_entry(&[]).is_err());
+        assert!(attribute_list_entry(&[0x10u8; 7]).is_err());
+
+        // An entry length of zero would never advance to the next entry.
+        assert!(attribute_list_entry(&[0u8; 26]).is_err());
+
+        let list = NtfsAttributeList::Resident(&[0xffu8; 40], NtfsPosition::none());
+        let mut fs = Cursor::new(Vec::<u8>::new());
+        let entries = list.entries().attach(&mut fs).collect::<Vec<_>>();
+        assert!(entries.iter().any(|entry| entry.is_err()));
+    }
+
+    // The following tests replay the corpus of each fuzz target the same way as the target in `fuzz/fuzz_targets`.
+    // Every input must be processed without a panic or an endless loop.
+
+    #[test]
+    fn test_corpus_record_fixup() {
+        for data in corpus("record_fixup") {
+            let _ = record_fixup(data);
+        }
+    }
+
+    #[test]
+    fn test_corpus_index_node() {
+        for data in corpus("index_node") {
+            for entry in index_node_entries::<NtfsFileNameIndex>(&data) {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(_) => break,
+                };
+
+                if let Some(Ok(key)) = entry.key() {
+                    let _ = key.name().to_string_lossy();
+                }
+
+                let _ = entry.subnode_vcn();
+            }
+        }
+    }
+
+    #[test]
+    fn test_corpus_attribute_list() {
+        for data in corpus("attribute_list") {
+            if let Ok(entry) = attribute_list_entry(&data) {
+                let _ = entry.name();
+                let _ = entry.base_file_reference();
+                let _ = entry.lowest_vcn();
+            }
+        }
+    }
+}
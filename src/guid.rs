@@ -10,6 +10,7 @@ pub(crate) const GUID_SIZE: usize = 16;
 
 /// A Globally Unique Identifier (GUID), used for Object IDs in NTFS.
 #[derive(BinRead, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct NtfsGuid {
     pub data1: u32,
     pub data2: u16,
@@ -52,4 +53,21 @@ mod tests {
         let guid_string = guid.to_string();
         assert_eq!(guid_string, "67C8770B-44F1-410A-AB9A-F9B5446F13EE");
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let guid = NtfsGuid {
+            data1: 0x67c8770b,
+            data2: 0x44f1,
+            data3: 0x410a,
+            data4: [0xab, 0x9a, 0xf9, 0xb5, 0x44, 0x6f, 0x13, 0xee],
+        };
+        let json = serde_json::to_string(&guid).unwrap();
+        assert_eq!(
+            json,
+            r#"{"data1":1741190923,"data2":17649,"data3":16650,"data4":[171,154,249,181,68,111,19,238]}"#
+        );
+        assert_eq!(serde_json::from_str::<NtfsGuid>(&json).unwrap(), guid);
+    }
 }
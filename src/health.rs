@@ -0,0 +1,145 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::error::{NtfsError, Result};
+use crate::io::{Read, Seek};
+use crate::log_file::NtfsLogFileStatus;
+use crate::ntfs::Ntfs;
+use crate::structured_values::NtfsVolumeFlags;
+use crate::time::NtfsTime;
+
+/// Summary of the indicators describing whether an NTFS volume has been cleanly unmounted,
+/// as returned by [`Ntfs::health_summary`].
+///
+/// Every indicator is read independently.
+/// If the structure holding an indicator is corrupted, only that indicator is `None`.
+#[derive(Clone, Debug)]
+pub struct NtfsHealthSummary {
+    log_file_status: Option<NtfsLogFileStatus>,
+    mft_mirror_matches: Option<bool>,
+    volume_creation_time: Option<NtfsTime>,
+    volume_flags: Option<NtfsVolumeFlags>,
+}
+
+impl NtfsHealthSummary {
+    pub(crate) fn read<T>(ntfs: &Ntfs, fs: &mut T) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let log_file_status = degrade(ntfs.log_file_status(fs))?;
+        let mft_mirror_matches = degrade(
+            ntfs.verify_mft_mirror(fs)
+                .map(|mismatches| mismatches.is_empty()),
+        )?;
+        let volume_creation_time = degrade(ntfs.volume_creation_time(fs))?;
+        let volume_flags = degrade(ntfs.volume_info(fs).map(|info| info.flags()))?;
+
+        Ok(Self {
+            log_file_status,
+            mft_mirror_matches,
+            volume_creation_time,
+            volume_flags,
+        })
+    }
+
+    /// Returns whether the volume has been cleanly unmounted, based on all indicators that could be read.
+    ///
+    /// The volume is considered dirty if the [`NtfsVolumeFlags::IS_DIRTY`] flag is set
+    /// or the $LogFile is not clean (see [`NtfsLogFileStatus::is_clean`]).
+    /// Returns `None` if neither indicator could be read.
+    pub fn is_clean(&self) -> Option<bool> {
+        let volume_clean = self
+            .volume_flags
+            .map(|flags| !flags.contains(NtfsVolumeFlags::IS_DIRTY));
+        let log_file_clean = self
+            .log_file_status
+            .as_ref()
+            .map(|status| status.is_clean());
+
+        match (volume_clean, log_file_clean) {
+            (Some(volume_clean), Some(log_file_clean)) => Some(volume_clean && log_file_clean),
+            (volume_clean, log_file_clean) => volume_clean.or(log_file_clean),
+        }
+    }
+
+    /// Returns the state of the $LogFile, or `None` if it could not be read.
+    pub fn log_file_status(&self) -> Option<&NtfsLogFileStatus> {
+        self.log_file_status.as_ref()
+    }
+
+    /// Returns whether the first File Records of the MFT match their copies in $MFTMirr
+    /// (see [`Ntfs::verify_mft_mirror`]), or `None` if they could not be compared.
+    pub fn mft_mirror_matches(&self) -> Option<bool> {
+        self.mft_mirror_matches
+    }
+
+    /// Returns the creation time of the volume (see [`Ntfs::volume_creation_time`]),
+    /// or `None` if it could not be read.
+    pub fn volume_creation_time(&self) -> Option<NtfsTime> {
+        self.volume_creation_time
+    }
+
+    /// Returns the flags of the $VOLUME_INFORMATION attribute, or `None` if they could not be read.
+    ///
+    /// Check [`NtfsVolumeFlags::CHKDSK_UNDERWAY`] and [`NtfsVolumeFlags::MODIFIED_BY_CHKDSK`] to find out
+    /// whether chkdsk has touched the volume.
+    pub fn volume_flags(&self) -> Option<NtfsVolumeFlags> {
+        self.volume_flags
+    }
+}
+
+/// Turns errors caused by corrupted structures into `None`, but keeps I/O errors.
+///
+/// An I/O error affects the entire summary, whereas a corrupted structure only affects a single indicator.
+fn degrade<T>(result: Result<T>) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if matches!(e.root_cause(), NtfsError::Io(_)) => Err(e),
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::KnownNtfsFileRecordNumber;
+
+    #[test]
+    fn test_health_summary() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // testfs1 has been cleanly unmounted by ntfs-3g, which empties the $LogFile.
+        let summary = ntfs.health_summary(&mut testfs1).unwrap();
+        assert_eq!(summary.log_file_status(), Some(&NtfsLogFileStatus::Empty));
+        assert_eq!(summary.mft_mirror_matches(), Some(true));
+        assert_eq!(
+            summary.volume_creation_time(),
+            Some(ntfs.volume_creation_time(&mut testfs1).unwrap())
+        );
+        assert_eq!(summary.volume_flags(), Some(NtfsVolumeFlags::empty()));
+        assert_eq!(summary.is_clean(), Some(true));
+    }
+
+    #[test]
+    fn test_health_summary_degraded() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // Corrupt the signature of the $Volume File Record.
+        // It is one of the mirrored File Records, so this also causes a mismatch with $MFTMirr.
+        let volume_file = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::Volume as u64)
+            .unwrap();
+        let position = volume_file.position().value().unwrap().get() as usize;
+        testfs1.get_mut()[position..position + 4].copy_from_slice(b"BAAD");
+
+        // The $Volume indicators are gone, but the remaining ones are still reported.
+        let summary = ntfs.health_summary(&mut testfs1).unwrap();
+        assert_eq!(summary.log_file_status(), Some(&NtfsLogFileStatus::Empty));
+        assert_eq!(summary.mft_mirror_matches(), Some(false));
+        assert_eq!(summary.volume_creation_time(), None);
+        assert_eq!(summary.volume_flags(), None);
+        assert_eq!(summary.is_clean(), Some(true));
+    }
+}
@@ -13,7 +13,25 @@ macro_rules! iter_try {
 #[cfg(test)]
 pub mod tests {
     use std::fs::File;
-    use std::io::{Cursor, Read};
+    use std::io;
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+    use std::ops::Range;
+
+    use byteorder::{ByteOrder, LittleEndian};
+    use memoffset::offset_of;
+
+    use crate::attribute::NtfsAttributeType;
+    use crate::file::{FileRecordHeader, NtfsFileFlags};
+    use crate::ntfs::Ntfs;
+
+    /// Size of the header of an attribute with a resident value, up to the name.
+    const RESIDENT_ATTRIBUTE_HEADER_SIZE: usize = 24;
+    /// Size of the header of an attribute with a non-resident value, up to the name.
+    const NON_RESIDENT_ATTRIBUTE_HEADER_SIZE: usize = 64;
+    /// Size of the header of an Attribute List entry, up to the name.
+    const ATTRIBUTE_LIST_ENTRY_HEADER_SIZE: usize = 26;
+    /// Size of the end marker terminating the attributes of a File Record.
+    const END_MARKER_SIZE: usize = 8;
 
     pub fn testfs1() -> Cursor<Vec<u8>> {
         let mut buffer = Vec::new();
@@ -23,4 +41,450 @@ pub mod tests {
             .unwrap();
         Cursor::new(buffer)
     }
+
+    /// Wraps a reader and fails all reads starting within `fail_range` (if set).
+    /// A read starting before `fail_range` only returns the bytes up to it.
+    pub struct FailingReader<R> {
+        pub inner: R,
+        pub fail_range: Option<Range<u64>>,
+    }
+
+    impl<R: Read + Seek> Read for FailingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let fail_range = match &self.fail_range {
+                Some(fail_range) => fail_range.clone(),
+                None => return self.inner.read(buf),
+            };
+
+            let position = self.inner.stream_position()?;
+            if fail_range.contains(&position) {
+                return Err(io::Error::new(io::ErrorKind::Other, "injected failure"));
+            }
+
+            let len = match fail_range.start.checked_sub(position) {
+                Some(bytes_to_fail_range) => u64::min(buf.len() as u64, bytes_to_fail_range),
+                None => buf.len() as u64,
+            } as usize;
+            self.inner.read(&mut buf[..len])
+        }
+    }
+
+    impl<R: Seek> Seek for FailingReader<R> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    /// Lets `f` modify the File Record of `file_record_size` bytes at byte position `position` of a test filesystem.
+    ///
+    /// The Update Sequence Array is reverted before calling `f` and reapplied afterwards,
+    /// so `f` gets the same view of the File Record as `NtfsFile` and doesn't need to care about fixups.
+    /// The File Record must be stored contiguously (i.e. not span two Data Runs of the MFT).
+    pub fn patch_file_record<F>(
+        testfs: &mut Cursor<Vec<u8>>,
+        position: usize,
+        file_record_size: usize,
+        f: F,
+    ) where
+        F: FnOnce(&mut [u8]),
+    {
+        const SECTOR_SIZE: usize = 512;
+
+        let record = &mut testfs.get_mut()[position..position + file_record_size];
+        let usa_offset = u16::from_le_bytes([record[4], record[5]]) as usize;
+        let usa_count = u16::from_le_bytes([record[6], record[7]]) as usize;
+
+        for i in 1..usa_count {
+            let sector_end = i * SECTOR_SIZE - 2;
+            let usa_entry = usa_offset + i * 2;
+            record.copy_within(usa_entry..usa_entry + 2, sector_end);
+        }
+
+        f(record);
+
+        for i in 1..usa_count {
+            let sector_end = i * SECTOR_SIZE - 2;
+            let usa_entry = usa_offset + i * 2;
+            record.copy_within(sector_end..sector_end + 2, usa_entry);
+            record.copy_within(usa_offset..usa_offset + 2, sector_end);
+        }
+    }
+
+    /// Like [`patch_file_record`], but looks up the position of File Record `file_record_number` first.
+    pub fn patch_file_record_number<F>(testfs: &mut Cursor<Vec<u8>>, file_record_number: u64, f: F)
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        let (position, file_record_size) = {
+            let ntfs = Ntfs::new(testfs).unwrap();
+            let file = ntfs.file(testfs, file_record_number).unwrap();
+            (
+                file.position().value().unwrap().get() as usize,
+                ntfs.file_record_size() as usize,
+            )
+        };
+
+        patch_file_record(testfs, position, file_record_size, f);
+    }
+
+    /// Adds a resident attribute of the given type, name, and value to the end of the File Record
+    /// `file_record_number` in a test filesystem.
+    pub fn add_resident_attribute(
+        testfs: &mut Cursor<Vec<u8>>,
+        file_record_number: u64,
+        ty: NtfsAttributeType,
+        name: &str,
+        value: &[u8],
+    ) {
+        patch_file_record_number(testfs, file_record_number, |record| {
+            let attribute = AttributeBuilder::resident(ty, value)
+                .name(name)
+                .instance(take_attribute_instance(record))
+                .build();
+            append_attribute(record, &attribute);
+        });
+    }
+
+    /// Returns the on-disk File Reference to File Record `file_record_number` with the given sequence number.
+    pub fn file_reference(file_record_number: u64, sequence_number: u16) -> [u8; 8] {
+        let mut reference = [0u8; 8];
+        reference[..6].copy_from_slice(&file_record_number.to_le_bytes()[..6]);
+        reference[6..].copy_from_slice(&sequence_number.to_le_bytes());
+        reference
+    }
+
+    /// Clears the IN_USE flag of a File Record, like NTFS does when deleting a file.
+    pub fn clear_in_use(record: &mut [u8]) {
+        let start = offset_of!(FileRecordHeader, flags);
+        let flags = LittleEndian::read_u16(&record[start..]) & !NtfsFileFlags::IN_USE.bits();
+        LittleEndian::write_u16(&mut record[start..], flags);
+    }
+
+    /// Returns the byte offset of the first attribute of a File Record.
+    pub fn first_attribute_offset(record: &[u8]) -> usize {
+        LittleEndian::read_u16(&record[offset_of!(FileRecordHeader, first_attribute_offset)..])
+            as usize
+    }
+
+    /// Returns the number of bytes used by a File Record, including the end marker.
+    pub fn data_size(record: &[u8]) -> usize {
+        LittleEndian::read_u32(&record[offset_of!(FileRecordHeader, data_size)..]) as usize
+    }
+
+    /// Returns the byte offset of the first attribute of type `ty` in a File Record.
+    pub fn attribute_offset(record: &[u8], ty: NtfsAttributeType) -> usize {
+        let mut offset = first_attribute_offset(record);
+
+        loop {
+            let attribute_ty = LittleEndian::read_u32(&record[offset..]);
+            assert_ne!(attribute_ty, 0xffff_ffff, "no {ty:?} attribute found");
+
+            if attribute_ty == ty as u32 {
+                return offset;
+            }
+
+            offset += LittleEndian::read_u32(&record[offset + 4..]) as usize;
+        }
+    }
+
+    /// Returns the next attribute instance number of a File Record and increments the stored one.
+    pub fn take_attribute_instance(record: &mut [u8]) -> u16 {
+        let start = offset_of!(FileRecordHeader, next_attribute_instance);
+        let instance = LittleEndian::read_u16(&record[start..]);
+        LittleEndian::write_u16(&mut record[start..], instance + 1);
+        instance
+    }
+
+    /// Inserts the raw `attribute` at byte offset `offset` of a File Record and moves all following
+    /// attributes back.
+    pub fn insert_attribute(record: &mut [u8], offset: usize, attribute: &[u8]) {
+        let old_data_size = data_size(record);
+        let new_data_size = old_data_size + attribute.len();
+        assert!(new_data_size <= record.len(), "File Record is full");
+
+        record.copy_within(offset..old_data_size, offset + attribute.len());
+        record[offset..offset + attribute.len()].copy_from_slice(attribute);
+
+        let start = offset_of!(FileRecordHeader, data_size);
+        LittleEndian::write_u32(&mut record[start..], new_data_size as u32);
+    }
+
+    /// Inserts the raw `attribute` in front of the end marker of a File Record.
+    pub fn append_attribute(record: &mut [u8], attribute: &[u8]) {
+        let offset = data_size(record) - END_MARKER_SIZE;
+        insert_attribute(record, offset, attribute);
+    }
+
+    /// Removes the raw attribute at byte offset `offset` of a File Record and returns it.
+    pub fn remove_attribute(record: &mut [u8], offset: usize) -> Vec<u8> {
+        let old_data_size = data_size(record);
+        let length = LittleEndian::read_u32(&record[offset + 4..]) as usize;
+        let attribute = record[offset..offset + length].to_vec();
+
+        record.copy_within(offset + length..old_data_size, offset);
+        record[old_data_size - length..old_data_size].fill(0);
+
+        let start = offset_of!(FileRecordHeader, data_size);
+        LittleEndian::write_u32(&mut record[start..], (old_data_size - length) as u32);
+
+        attribute
+    }
+
+    /// Turns a File Record into an extension record of the base File Record referenced by `base_reference`.
+    ///
+    /// The given raw `attributes` become its only attributes, and attribute instance numbers continue at
+    /// `next_attribute_instance`.
+    pub fn make_extension_record(
+        record: &mut [u8],
+        base_reference: [u8; 8],
+        attributes: &[u8],
+        next_attribute_instance: u16,
+    ) {
+        let first_attribute_offset = first_attribute_offset(record);
+        let end_marker_offset = first_attribute_offset + attributes.len();
+        let data_size = end_marker_offset + END_MARKER_SIZE;
+
+        let start = offset_of!(FileRecordHeader, hard_link_count);
+        LittleEndian::write_u16(&mut record[start..], 0);
+        let start = offset_of!(FileRecordHeader, flags);
+        LittleEndian::write_u16(&mut record[start..], NtfsFileFlags::IN_USE.bits());
+        let start = offset_of!(FileRecordHeader, data_size);
+        LittleEndian::write_u32(&mut record[start..], data_size as u32);
+        let start = offset_of!(FileRecordHeader, base_file_record);
+        record[start..start + 8].copy_from_slice(&base_reference);
+        let start = offset_of!(FileRecordHeader, next_attribute_instance);
+        LittleEndian::write_u16(&mut record[start..], next_attribute_instance);
+
+        record[first_attribute_offset..].fill(0);
+        record[first_attribute_offset..end_marker_offset].copy_from_slice(attributes);
+        LittleEndian::write_u32(&mut record[end_marker_offset..], 0xffff_ffff);
+    }
+
+    /// Encodes the given Data Runs, terminated by a zero byte.
+    ///
+    /// Each Data Run is given by its length in clusters and its absolute Logical Cluster Number,
+    /// or `None` for a sparse Data Run.
+    pub fn encode_data_runs(data_runs: &[(u64, Option<u64>)]) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        let mut previous_lcn = 0;
+
+        for (cluster_count, lcn) in data_runs {
+            let count_bytes = minimal_signed_le_bytes(*cluster_count as i64);
+            let offset_bytes = match lcn {
+                Some(lcn) => {
+                    let offset = minimal_signed_le_bytes(*lcn as i64 - previous_lcn as i64);
+                    previous_lcn = *lcn;
+                    offset
+                }
+                None => Vec::new(),
+            };
+
+            encoded.push(((offset_bytes.len() as u8) << 4) | count_bytes.len() as u8);
+            encoded.extend(count_bytes);
+            encoded.extend(offset_bytes);
+        }
+
+        encoded.push(0);
+        encoded
+    }
+
+    /// Returns the least number of little-endian bytes that represent `value` as a signed integer.
+    fn minimal_signed_le_bytes(value: i64) -> Vec<u8> {
+        let bytes = value.to_le_bytes();
+        let mut length = bytes.len();
+
+        while length > 1 {
+            let top = bytes[length - 1];
+            let next_is_negative = bytes[length - 2] & 0x80 != 0;
+
+            if (top == 0 && !next_is_negative) || (top == 0xff && next_is_negative) {
+                length -= 1;
+            } else {
+                break;
+            }
+        }
+
+        bytes[..length].to_vec()
+    }
+
+    /// Returns an Attribute List entry referencing the attribute with the given type, name, lowest VCN,
+    /// and instance in the File Record referenced by `reference`.
+    pub fn attribute_list_entry(
+        ty: NtfsAttributeType,
+        name: &str,
+        lowest_vcn: u64,
+        reference: [u8; 8],
+        instance: u16,
+    ) -> Vec<u8> {
+        let name = utf16_le_bytes(name);
+        let entry_length = align8(ATTRIBUTE_LIST_ENTRY_HEADER_SIZE + name.len());
+
+        let mut entry = vec![0u8; entry_length];
+        LittleEndian::write_u32(&mut entry[0..], ty as u32);
+        LittleEndian::write_u16(&mut entry[4..], entry_length as u16);
+        entry[6] = (name.len() / 2) as u8;
+        entry[7] = ATTRIBUTE_LIST_ENTRY_HEADER_SIZE as u8;
+        LittleEndian::write_u64(&mut entry[8..], lowest_vcn);
+        entry[16..24].copy_from_slice(&reference);
+        LittleEndian::write_u16(&mut entry[24..], instance);
+        entry[ATTRIBUTE_LIST_ENTRY_HEADER_SIZE..ATTRIBUTE_LIST_ENTRY_HEADER_SIZE + name.len()]
+            .copy_from_slice(&name);
+
+        entry
+    }
+
+    /// Builder for raw attributes to be put into File Records of a test filesystem.
+    #[derive(Clone, Debug)]
+    pub struct AttributeBuilder {
+        ty: NtfsAttributeType,
+        name: String,
+        instance: u16,
+        form: AttributeForm,
+    }
+
+    #[derive(Clone, Debug)]
+    enum AttributeForm {
+        Resident {
+            value: Vec<u8>,
+            indexed: bool,
+        },
+        NonResident {
+            lowest_vcn: u64,
+            highest_vcn: u64,
+            data_runs: Vec<u8>,
+            sizes: Option<(u64, u64, u64)>,
+        },
+    }
+
+    impl AttributeBuilder {
+        /// Starts an unnamed attribute with the given resident value.
+        pub fn resident(ty: NtfsAttributeType, value: &[u8]) -> Self {
+            Self {
+                ty,
+                name: String::new(),
+                instance: 0,
+                form: AttributeForm::Resident {
+                    value: value.to_vec(),
+                    indexed: false,
+                },
+            }
+        }
+
+        /// Starts an unnamed attribute with a non-resident value, covering the given VCNs with the given
+        /// encoded Data Runs (see [`encode_data_runs`]).
+        ///
+        /// The value sizes are zero unless set via [`AttributeBuilder::sizes`].
+        pub fn non_resident(
+            ty: NtfsAttributeType,
+            lowest_vcn: u64,
+            highest_vcn: u64,
+            data_runs: &[u8],
+        ) -> Self {
+            Self {
+                ty,
+                name: String::new(),
+                instance: 0,
+                form: AttributeForm::NonResident {
+                    lowest_vcn,
+                    highest_vcn,
+                    data_runs: data_runs.to_vec(),
+                    sizes: None,
+                },
+            }
+        }
+
+        /// Returns the raw attribute.
+        pub fn build(&self) -> Vec<u8> {
+            let name = utf16_le_bytes(&self.name);
+            let header_size = match self.form {
+                AttributeForm::Resident { .. } => RESIDENT_ATTRIBUTE_HEADER_SIZE,
+                AttributeForm::NonResident { .. } => NON_RESIDENT_ATTRIBUTE_HEADER_SIZE,
+            };
+            let content_offset = align8(header_size + name.len());
+
+            let mut attribute = vec![0u8; content_offset];
+            LittleEndian::write_u32(&mut attribute[0..], self.ty as u32);
+            attribute[9] = (name.len() / 2) as u8;
+            LittleEndian::write_u16(&mut attribute[10..], header_size as u16);
+            LittleEndian::write_u16(&mut attribute[14..], self.instance);
+            attribute[header_size..header_size + name.len()].copy_from_slice(&name);
+
+            match &self.form {
+                AttributeForm::Resident { value, indexed } => {
+                    LittleEndian::write_u32(&mut attribute[16..], value.len() as u32);
+                    LittleEndian::write_u16(&mut attribute[20..], content_offset as u16);
+                    attribute[22] = *indexed as u8;
+                    attribute.extend_from_slice(value);
+                }
+                AttributeForm::NonResident {
+                    lowest_vcn,
+                    highest_vcn,
+                    data_runs,
+                    sizes,
+                } => {
+                    attribute[8] = 1;
+                    LittleEndian::write_u64(&mut attribute[16..], *lowest_vcn);
+                    LittleEndian::write_u64(&mut attribute[24..], *highest_vcn);
+                    LittleEndian::write_u16(&mut attribute[32..], content_offset as u16);
+                    if let Some((allocated_size, data_size, initialized_size)) = sizes {
+                        LittleEndian::write_u64(&mut attribute[40..], *allocated_size);
+                        LittleEndian::write_u64(&mut attribute[48..], *data_size);
+                        LittleEndian::write_u64(&mut attribute[56..], *initialized_size);
+                    }
+                    attribute.extend_from_slice(data_runs);
+                }
+            }
+
+            attribute.resize(align8(attribute.len()), 0);
+            let attribute_length = attribute.len() as u32;
+            LittleEndian::write_u32(&mut attribute[4..], attribute_length);
+
+            attribute
+        }
+
+        /// Sets the flag that a resident attribute is referenced in an index (like $FILE_NAME attributes).
+        pub fn indexed(mut self) -> Self {
+            match &mut self.form {
+                AttributeForm::Resident { indexed, .. } => *indexed = true,
+                AttributeForm::NonResident { .. } => panic!("only resident attributes are indexed"),
+            }
+            self
+        }
+
+        /// Sets the instance number of the attribute.
+        pub fn instance(mut self, instance: u16) -> Self {
+            self.instance = instance;
+            self
+        }
+
+        /// Sets the name of the attribute.
+        pub fn name(mut self, name: &str) -> Self {
+            self.name = name.to_string();
+            self
+        }
+
+        /// Sets the allocated size, data size, and initialized size of a non-resident value.
+        ///
+        /// NTFS only stores them in the attribute with a lowest VCN of zero.
+        pub fn sizes(mut self, allocated_size: u64, data_size: u64, initialized_size: u64) -> Self {
+            match &mut self.form {
+                AttributeForm::Resident { .. } => panic!("only non-resident attributes have sizes"),
+                AttributeForm::NonResident { sizes, .. } => {
+                    *sizes = Some((allocated_size, data_size, initialized_size))
+                }
+            }
+            self
+        }
+    }
+
+    fn align8(length: usize) -> usize {
+        (length + 7) & !7
+    }
+
+    fn utf16_le_bytes(string: &str) -> Vec<u8> {
+        string
+            .encode_utf16()
+            .flat_map(|c| c.to_le_bytes())
+            .collect()
+    }
 }
@@ -2,20 +2,24 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use core::cmp::Ordering;
+use core::iter::FusedIterator;
 use core::marker::PhantomData;
 
+use crate::io::{Read, Seek};
 use alloc::vec;
 use alloc::vec::Vec;
-use binrw::io::{Read, Seek};
 
 use crate::attribute::{NtfsAttributeItem, NtfsAttributeType};
 use crate::error::{NtfsError, Result};
+use crate::file::NtfsFile;
 use crate::index_entry::{
-    IndexEntryRange, IndexNodeEntryRanges, NtfsIndexEntry, NtfsIndexEntryFlags,
+    IndexEntryRange, IndexNodeEntryRanges, NodeData, NtfsIndexEntry, NtfsIndexEntryFlags,
 };
+use crate::index_record::validate_index_record_size;
 use crate::indexes::NtfsIndexEntryType;
 use crate::structured_values::{NtfsIndexAllocation, NtfsIndexRoot};
-use crate::types::NtfsPosition;
+use crate::traits::NtfsReadSeek;
+use crate::types::{NtfsPosition, Vcn};
 
 /// Helper structure to iterate over all entries of an index or find a specific one.
 ///
@@ -32,9 +36,10 @@ where
     E: NtfsIndexEntryType,
 {
     index_record_size: u32,
-    index_root_entry_ranges: IndexNodeEntryRanges<E>,
+    index_root_entry_ranges: IndexNodeEntryRanges<'f, E>,
     index_root_position: NtfsPosition,
     index_allocation_item: Option<NtfsAttributeItem<'n, 'f>>,
+    index_bitmap: Option<Vec<u8>>,
     entry_type: PhantomData<E>,
 }
 
@@ -49,6 +54,9 @@ where
     /// If you just want to look up files in a directory, check out [`NtfsFile::directory_index`],
     /// which looks up the correct [`NtfsIndexRoot`] and [`NtfsIndexAllocation`] attributes for you.
     ///
+    /// Returns [`NtfsError::CollationMismatch`] if the collation rule of the index doesn't match
+    /// [`NtfsIndexEntryType::COLLATION`] of the entry type `E`.
+    ///
     /// [`NtfsFile::directory_index`]: crate::NtfsFile::directory_index
     pub fn new(
         index_root_item: NtfsAttributeItem<'n, 'f>,
@@ -58,6 +66,17 @@ where
         index_root_attribute.ensure_ty(NtfsAttributeType::IndexRoot)?;
         let index_root = index_root_attribute.resident_structured_value::<NtfsIndexRoot>()?;
 
+        if let Some(expected) = E::COLLATION {
+            let actual = index_root.collation_rule()?;
+            if actual != expected {
+                return Err(NtfsError::CollationMismatch {
+                    position: index_root.position(),
+                    expected,
+                    actual,
+                });
+            }
+        }
+
         if let Some(item) = &index_allocation_item {
             let attribute = item.to_attribute()?;
             attribute.ensure_ty(NtfsAttributeType::IndexAllocation)?;
@@ -68,8 +87,17 @@ where
         }
 
         let index_record_size = index_root.index_record_size();
-        let index_root_entry_ranges = index_root.entry_ranges();
+        let index_root_entry_ranges = match index_root_item.to_base_file_attribute() {
+            // The Index Root is stored in the File Record we borrow, so its entries can be borrowed as well.
+            Some(attribute) => attribute?
+                .resident_structured_value::<NtfsIndexRoot>()?
+                .entry_ranges(),
+            // The Index Root is stored in another File Record owned by `index_root_item`, so its entries
+            // have to be copied.
+            None => index_root.entry_ranges().into_owned(),
+        };
         let index_root_position = index_root.position();
+        let index_bitmap = None;
         let entry_type = PhantomData;
 
         Ok(Self {
@@ -77,18 +105,236 @@ where
             index_root_entry_ranges,
             index_root_position,
             index_allocation_item,
+            index_bitmap,
             entry_type,
         })
     }
 
     /// Returns an [`NtfsIndexEntries`] iterator to perform an in-order traversal of this index.
     pub fn entries<'i>(&'i self) -> NtfsIndexEntries<'n, 'f, 'i, E> {
-        NtfsIndexEntries::new(self)
+        NtfsIndexEntries::new(IndexSource::Borrowed(self))
     }
 
     /// Returns an [`NtfsIndexFinder`] structure to efficiently find an entry in this index.
     pub fn finder<'i>(&'i self) -> NtfsIndexFinder<'n, 'f, 'i, E> {
-        NtfsIndexFinder::new(self)
+        NtfsIndexFinder::new(IndexSource::Borrowed(self))
+    }
+
+    /// Returns `true` if the $BITMAP attribute of this index has been read via [`NtfsIndex::read_bitmap`].
+    pub fn has_bitmap(&self) -> bool {
+        self.index_bitmap.is_some()
+    }
+
+    /// Reads the $BITMAP attribute of this index (contained in an [`NtfsAttributeItem`])
+    /// and stores it in this [`NtfsIndex`] object.
+    ///
+    /// The $BITMAP attribute has the same name as the index and marks the Index Records
+    /// of the [`NtfsIndexAllocation`] attribute that are in use.
+    /// Afterwards, every subnode is checked against it before being read, as determined by the
+    /// [`NtfsIndexBitmapPolicy`] of the traversal.
+    ///
+    /// [`NtfsFile::directory_index`] already does this for you.
+    ///
+    /// [`NtfsFile::directory_index`]: crate::NtfsFile::directory_index
+    pub fn read_bitmap<T>(
+        &mut self,
+        fs: &mut T,
+        index_bitmap_item: &NtfsAttributeItem<'n, '_>,
+    ) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        let index_bitmap_attribute = index_bitmap_item.to_attribute()?;
+        index_bitmap_attribute.ensure_ty(NtfsAttributeType::Bitmap)?;
+        validate_index_record_size(self.index_root_position, self.index_record_size)?;
+
+        // We only need a bit for each Index Record of the $INDEX_ALLOCATION attribute.
+        // The $BITMAP attribute is usually larger, and bytes beyond its initialized size are zero anyway.
+        let index_record_count = match &self.index_allocation_item {
+            Some(item) => item.to_attribute()?.value_length() / self.index_record_size as u64,
+            None => 0,
+        };
+        let mut index_bitmap_value = index_bitmap_attribute.value(fs)?;
+        let length = u64::min(
+            (index_record_count + 7) / 8,
+            u64::min(
+                index_bitmap_attribute.initialized_size(),
+                index_bitmap_value.len(),
+            ),
+        );
+
+        let mut index_bitmap = vec![0; length as usize];
+        index_bitmap_value.read_exact(fs, &mut index_bitmap)?;
+        self.index_bitmap = Some(index_bitmap);
+
+        Ok(())
+    }
+}
+
+/// Variant of [`NtfsIndex`] that owns the [`NtfsFile`] it belongs to, created by [`NtfsFile::into_directory_index`].
+///
+/// Contrary to [`NtfsIndex`], this structure only borrows the [`Ntfs`] object, but not the [`NtfsFile`].
+/// This allows storing it in a structure or returning it from a function that has created the [`NtfsFile`] locally.
+/// The $INDEX_ALLOCATION attribute of a large index is looked up on demand when the index is traversed.
+///
+/// # Example
+/// A cache of directory handles, keyed by File Record Number:
+///
+/// ```ignore
+/// let mut cache = BTreeMap::<u64, NtfsOwnedIndex<NtfsFileNameIndex>>::new();
+///
+/// let dir = ntfs.file(&mut fs, file_record_number)?;
+/// let index = dir.into_directory_index(&mut fs)?;
+/// cache.insert(file_record_number, index);
+///
+/// // Later
+/// let index = cache.get(&file_record_number).unwrap();
+/// let mut finder = index.finder();
+/// let entry = NtfsFileNameIndex::find(&mut finder, &ntfs, &mut fs, "hello.txt");
+/// ```
+///
+/// [`Ntfs`]: crate::Ntfs
+/// [`NtfsFile::into_directory_index`]: crate::NtfsFile::into_directory_index
+#[derive(Clone, Debug)]
+pub struct NtfsOwnedIndex<'n, E>
+where
+    E: NtfsIndexEntryType,
+{
+    file: NtfsFile<'n>,
+    name: &'static str,
+    index_record_size: u32,
+    /// The Index Root entries can't borrow from `file`, because this structure owns it.
+    index_root_entry_ranges: IndexNodeEntryRanges<'static, E>,
+    index_root_position: NtfsPosition,
+    is_large_index: bool,
+    index_bitmap: Option<Vec<u8>>,
+}
+
+impl<'n, E> NtfsOwnedIndex<'n, E>
+where
+    E: NtfsIndexEntryType,
+{
+    /// Creates an [`NtfsOwnedIndex`] for the index named `name` of `file`.
+    pub(crate) fn new<T>(file: NtfsFile<'n>, fs: &mut T, name: &'static str) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        // Copy everything we need out of the borrowing `NtfsIndex`, so that we can move `file` afterwards.
+        let (
+            index_record_size,
+            index_root_entry_ranges,
+            index_root_position,
+            is_large_index,
+            index_bitmap,
+        ) = {
+            let index = file.index_by_name::<T, E>(fs, name)?;
+            (
+                index.index_record_size,
+                index.index_root_entry_ranges.clone().into_owned(),
+                index.index_root_position,
+                index.index_allocation_item.is_some(),
+                index.index_bitmap,
+            )
+        };
+
+        Ok(Self {
+            file,
+            name,
+            index_record_size,
+            index_root_entry_ranges,
+            index_root_position,
+            is_large_index,
+            index_bitmap,
+        })
+    }
+
+    /// Returns an [`NtfsIndexEntries`] iterator to perform an in-order traversal of this index.
+    pub fn entries<'i>(&'i self) -> NtfsIndexEntries<'n, 'i, 'i, E> {
+        NtfsIndexEntries::new(IndexSource::Owned(self))
+    }
+
+    /// Returns the [`NtfsFile`] this index belongs to.
+    pub fn file(&self) -> &NtfsFile<'n> {
+        &self.file
+    }
+
+    /// Returns an [`NtfsIndexFinder`] structure to efficiently find an entry in this index.
+    pub fn finder<'i>(&'i self) -> NtfsIndexFinder<'n, 'i, 'i, E> {
+        NtfsIndexFinder::new(IndexSource::Owned(self))
+    }
+
+    /// Returns the [`NtfsFile`] this index belongs to, consuming the index.
+    pub fn into_file(self) -> NtfsFile<'n> {
+        self.file
+    }
+}
+
+/// The index traversed by an [`NtfsIndexEntries`] iterator.
+#[derive(Clone, Debug)]
+pub(crate) enum IndexSource<'n, 'f, 'i, E>
+where
+    E: NtfsIndexEntryType,
+{
+    Borrowed(&'i NtfsIndex<'n, 'f, E>),
+    Owned(&'f NtfsOwnedIndex<'n, E>),
+}
+
+impl<'n, 'f, 'i, E> IndexSource<'n, 'f, 'i, E>
+where
+    E: NtfsIndexEntryType,
+{
+    fn index_bitmap(&self) -> Option<&[u8]> {
+        match self {
+            Self::Borrowed(index) => index.index_bitmap.as_deref(),
+            Self::Owned(index) => index.index_bitmap.as_deref(),
+        }
+    }
+
+    fn index_record_size(&self) -> u32 {
+        match self {
+            Self::Borrowed(index) => index.index_record_size,
+            Self::Owned(index) => index.index_record_size,
+        }
+    }
+
+    fn index_root_entry_ranges(&self) -> &IndexNodeEntryRanges<'f, E> {
+        match self {
+            Self::Borrowed(index) => &index.index_root_entry_ranges,
+            Self::Owned(index) => &index.index_root_entry_ranges,
+        }
+    }
+
+    fn index_root_position(&self) -> NtfsPosition {
+        match self {
+            Self::Borrowed(index) => index.index_root_position,
+            Self::Owned(index) => index.index_root_position,
+        }
+    }
+}
+
+/// Determines how subnodes are handled whose Index Record is not marked as in use in the $BITMAP attribute
+/// of the index, set via [`NtfsIndexEntries::set_bitmap_policy`] or [`NtfsIndexFinder::set_bitmap_policy`].
+///
+/// Such Index Records have been deallocated, but may still contain stale entries.
+/// This only has an effect if the $BITMAP attribute has been read via [`NtfsIndex::read_bitmap`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NtfsIndexBitmapPolicy {
+    /// Descending into such a subnode returns [`NtfsError::IndexRecordNotAllocated`].
+    ///
+    /// This is the default.
+    Error,
+    /// Such subnodes are skipped along with all their entries.
+    /// The traversal continues with the entry that references the subnode.
+    Skip,
+    /// The $BITMAP attribute is not checked, and all subnodes are read.
+    ///
+    /// This is useful for forensic analysis, as it also returns the stale entries of deallocated Index Records.
+    Ignore,
+}
+
+impl Default for NtfsIndexBitmapPolicy {
+    fn default() -> Self {
+        Self::Error
     }
 }
 
@@ -97,32 +343,66 @@ where
 ///   sorted ascending by the index key,
 ///   returning an [`NtfsIndexEntry`] for each entry.
 ///
-/// This iterator is returned from the [`NtfsIndex::entries`] function.
+/// This iterator is returned from the [`NtfsIndex::entries`] and [`NtfsOwnedIndex::entries`] functions.
+/// See [`NtfsIndexEntriesAttached`] for an iterator that implements [`Iterator`] and [`FusedIterator`].
 #[derive(Clone, Debug)]
 pub struct NtfsIndexEntries<'n, 'f, 'i, E>
 where
     E: NtfsIndexEntryType,
 {
-    index: &'i NtfsIndex<'n, 'f, E>,
-    inner_iterators: Vec<IndexNodeEntryRanges<E>>,
+    index: IndexSource<'n, 'f, 'i, E>,
+    /// $INDEX_ALLOCATION attribute of an [`NtfsOwnedIndex`], looked up when the first subnode is read.
+    owned_index_allocation_item: Option<NtfsAttributeItem<'n, 'f>>,
+    inner_iterators: Vec<IndexNodeEntryRanges<'f, E>>,
     following_entries: Vec<Option<IndexEntryRange<E>>>,
+    filter: fn(&NtfsIndexEntry<E>) -> bool,
+    bitmap_policy: NtfsIndexBitmapPolicy,
 }
 
 impl<'n, 'f, 'i, E> NtfsIndexEntries<'n, 'f, 'i, E>
 where
     E: NtfsIndexEntryType,
 {
-    fn new(index: &'i NtfsIndex<'n, 'f, E>) -> Self {
-        let inner_iterators = vec![index.index_root_entry_ranges.clone()];
+    fn new(index: IndexSource<'n, 'f, 'i, E>) -> Self {
+        Self::with_filter(index, |_| true)
+    }
+
+    /// Creates an iterator that only returns the entries for which `filter` returns `true`.
+    pub(crate) fn with_filter(
+        index: IndexSource<'n, 'f, 'i, E>,
+        filter: fn(&NtfsIndexEntry<E>) -> bool,
+    ) -> Self {
+        let inner_iterators = vec![index.index_root_entry_ranges().clone()];
         let following_entries = Vec::new();
+        let bitmap_policy = NtfsIndexBitmapPolicy::default();
 
         Self {
             index,
+            owned_index_allocation_item: None,
             inner_iterators,
             following_entries,
+            filter,
+            bitmap_policy,
         }
     }
 
+    /// Returns a variant of this iterator that implements [`Iterator`] and [`FusedIterator`]
+    /// by mutably borrowing the filesystem reader.
+    ///
+    /// As the returned entries can no longer borrow from the iterator, each one is copied
+    /// via [`NtfsIndexEntry::into_owned`].
+    pub fn attach<'a, T>(self, fs: &'a mut T) -> NtfsIndexEntriesAttached<'n, 'f, 'i, 'a, T, E>
+    where
+        T: Read + Seek,
+    {
+        NtfsIndexEntriesAttached::new(fs, self)
+    }
+
+    /// Returns the [`NtfsIndexBitmapPolicy`] set via [`NtfsIndexEntries::set_bitmap_policy`].
+    pub fn bitmap_policy(&self) -> NtfsIndexBitmapPolicy {
+        self.bitmap_policy
+    }
+
     /// See [`Iterator::next`].
     pub fn next<'a, T>(&'a mut self, fs: &mut T) -> Option<Result<NtfsIndexEntry<'a, E>>>
     where
@@ -162,26 +442,6 @@ where
                 if let Some(subnode_vcn) = entry.subnode_vcn() {
                     let subnode_vcn = iter_try!(subnode_vcn);
 
-                    // Read the subnode from the filesystem and get an iterator for it.
-                    let index_allocation_item =
-                        iter_try!(self.index.index_allocation_item.as_ref().ok_or(
-                            NtfsError::MissingIndexAllocation {
-                                position: self.index.index_root_position,
-                            }
-                        ));
-                    let index_allocation_attribute =
-                        iter_try!(index_allocation_item.to_attribute());
-                    let index_allocation =
-                        iter_try!(index_allocation_attribute
-                            .structured_value::<_, NtfsIndexAllocation>(fs));
-
-                    let subnode = iter_try!(index_allocation.record_from_vcn(
-                        fs,
-                        self.index.index_record_size,
-                        subnode_vcn
-                    ));
-                    let subnode_iter = subnode.into_entry_ranges();
-
                     let following_entry = if !is_last_entry {
                         // This entry comes after the subnode lexicographically, so save it.
                         // We'll pick it up again after the subnode iterator has been fully iterated.
@@ -192,9 +452,8 @@ where
 
                     // Save this subnode's iterator and any following entry.
                     // We'll pick up the iterator through `self.inner_iterators.last_mut()` in the next loop iteration.
-                    self.inner_iterators.push(subnode_iter);
-                    self.following_entries.push(following_entry);
-                } else if !is_last_entry {
+                    iter_try!(self.push_subnode(fs, subnode_vcn, following_entry));
+                } else if !is_last_entry && (self.filter)(&entry) {
                     // There is no subnode, and this is not the empty "last entry",
                     // so our entry comes next lexicographically.
                     break entry_range;
@@ -216,7 +475,12 @@ where
                 // Otherwise, we can be sure that `inner_iterators.last()` is the matching iterator for converting
                 // `IndexEntryRange` to a (lifetime-bound) `NtfsIndexEntry`.
                 if let Some(entry_range) = self.following_entries.pop()? {
-                    break entry_range;
+                    let iter = self.inner_iterators.last().unwrap();
+                    let entry = iter_try!(entry_range.to_entry(iter.data()));
+
+                    if (self.filter)(&entry) {
+                        break entry_range;
+                    }
                 }
             }
         };
@@ -226,62 +490,240 @@ where
 
         Some(Ok(entry))
     }
+
+    /// Reads the subnode at `subnode_vcn` and makes it the current node level.
+    /// Returns the position of the subnode's Index Record.
+    ///
+    /// `following_entry` is the entry of the parent node that comes after the subnode lexicographically
+    /// (or `None` if the subnode belongs to the empty "last entry").
+    ///
+    /// A subnode skipped due to [`NtfsIndexBitmapPolicy::Skip`] becomes a node level without entries.
+    fn push_subnode<T>(
+        &mut self,
+        fs: &mut T,
+        subnode_vcn: Vcn,
+        following_entry: Option<IndexEntryRange<E>>,
+    ) -> Result<NtfsPosition>
+    where
+        T: Read + Seek,
+    {
+        let index_allocation_item = match self.index {
+            IndexSource::Borrowed(index) => index.index_allocation_item.as_ref(),
+            IndexSource::Owned(index) => {
+                if self.owned_index_allocation_item.is_none() && index.is_large_index {
+                    let file = &index.file;
+                    let item = file.attribute_by_ty_and_name(
+                        fs,
+                        NtfsAttributeType::IndexAllocation,
+                        Some(index.name),
+                        file.default_case_sensitivity(),
+                    )?;
+                    self.owned_index_allocation_item = Some(item);
+                }
+
+                self.owned_index_allocation_item.as_ref()
+            }
+        };
+        let index_allocation_item =
+            index_allocation_item.ok_or(NtfsError::MissingIndexAllocation {
+                position: self.index.index_root_position(),
+            })?;
+        let index_allocation_attribute = index_allocation_item.to_attribute()?;
+        let index_allocation =
+            index_allocation_attribute.structured_value::<_, NtfsIndexAllocation>(fs)?;
+
+        let index_bitmap = match self.bitmap_policy {
+            NtfsIndexBitmapPolicy::Error | NtfsIndexBitmapPolicy::Skip => self.index.index_bitmap(),
+            NtfsIndexBitmapPolicy::Ignore => None,
+        };
+
+        let subnode = index_allocation.record_from_vcn_with_bitmap(
+            fs,
+            self.index.index_record_size(),
+            subnode_vcn,
+            index_bitmap,
+        );
+        let (subnode_position, subnode_entry_ranges) = match subnode {
+            Ok(subnode) => (subnode.position(), subnode.into_entry_ranges()),
+            Err(NtfsError::IndexRecordNotAllocated { position, .. })
+                if self.bitmap_policy == NtfsIndexBitmapPolicy::Skip =>
+            {
+                // Make an empty node level out of the skipped subnode.
+                // This way, the traversal continues with `following_entry` as if the subnode had no entries.
+                let empty_entry_ranges =
+                    IndexNodeEntryRanges::new(NodeData::Borrowed(&[]), 0..0, position);
+                (position, empty_entry_ranges)
+            }
+            Err(e) => return Err(e),
+        };
+
+        self.inner_iterators.push(subnode_entry_ranges);
+        self.following_entries.push(following_entry);
+
+        Ok(subnode_position)
+    }
+
+    /// Sets the [`NtfsIndexBitmapPolicy`] for subnodes whose Index Record is not marked as in use.
+    pub fn set_bitmap_policy(&mut self, bitmap_policy: NtfsIndexBitmapPolicy) {
+        self.bitmap_policy = bitmap_policy;
+    }
+
+    /// Resets the traversal to the first entry of the Index Root.
+    fn reset(&mut self) {
+        self.inner_iterators.clear();
+        self.inner_iterators
+            .push(self.index.index_root_entry_ranges().clone());
+        self.following_entries.clear();
+    }
+}
+
+/// Iterator over
+///   all index entries of an index,
+///   sorted ascending by the index key,
+///   returning an owned [`NtfsIndexEntry`] for each entry,
+///   implementing [`Iterator`] and [`FusedIterator`].
+///
+/// This iterator is returned from the [`NtfsIndexEntries::attach`] function.
+/// Conceptually the same as [`NtfsIndexEntries`], but mutably borrows the filesystem
+/// to implement aforementioned traits.
+#[derive(Debug)]
+pub struct NtfsIndexEntriesAttached<'n, 'f, 'i, 'a, T, E>
+where
+    T: Read + Seek,
+    E: NtfsIndexEntryType,
+{
+    fs: &'a mut T,
+    entries: NtfsIndexEntries<'n, 'f, 'i, E>,
+}
+
+impl<'n, 'f, 'i, 'a, T, E> NtfsIndexEntriesAttached<'n, 'f, 'i, 'a, T, E>
+where
+    T: Read + Seek,
+    E: NtfsIndexEntryType,
+{
+    fn new(fs: &'a mut T, entries: NtfsIndexEntries<'n, 'f, 'i, E>) -> Self {
+        Self { fs, entries }
+    }
+
+    /// Consumes this iterator and returns the inner [`NtfsIndexEntries`].
+    pub fn detach(self) -> NtfsIndexEntries<'n, 'f, 'i, E> {
+        self.entries
+    }
+}
+
+impl<'n, 'f, 'i, 'a, T, E> Iterator for NtfsIndexEntriesAttached<'n, 'f, 'i, 'a, T, E>
+where
+    T: Read + Seek,
+    E: NtfsIndexEntryType,
+{
+    type Item = Result<NtfsIndexEntry<'static, E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next(self.fs)?;
+        Some(entry.map(NtfsIndexEntry::into_owned))
+    }
+}
+
+impl<'n, 'f, 'i, 'a, T, E> FusedIterator for NtfsIndexEntriesAttached<'n, 'f, 'i, 'a, T, E>
+where
+    T: Read + Seek,
+    E: NtfsIndexEntryType,
+{
 }
 
 /// Helper structure to efficiently find an entry in an index, created by [`NtfsIndex::finder`].
 ///
 /// This helper is required, because the returned entry borrows from the iterator it was created from.
 /// The idea is that you copy the field(s) you need from the returned entry and then drop the entry and the finder.
+///
+/// Besides looking up a single entry via [`NtfsIndexFinder::find`], the finder can also position itself in the
+/// index via [`NtfsIndexFinder::seek_to`] and continue from there in sorted order via [`NtfsIndexFinder::next`].
+/// [`NtfsIndexFinder::find_range`] combines both to perform range queries.
 pub struct NtfsIndexFinder<'n, 'f, 'i, E>
 where
     E: NtfsIndexEntryType,
 {
-    index: &'i NtfsIndex<'n, 'f, E>,
-    inner_iterator: IndexNodeEntryRanges<E>,
+    entries: NtfsIndexEntries<'n, 'f, 'i, E>,
 }
 
 impl<'n, 'f, 'i, E> NtfsIndexFinder<'n, 'f, 'i, E>
 where
     E: NtfsIndexEntryType,
 {
-    fn new(index: &'i NtfsIndex<'n, 'f, E>) -> Self {
-        // This is superfluous and done again in `find`, but doesn't justify using an `Option` here.
-        let inner_iterator = index.index_root_entry_ranges.clone();
+    fn new(index: IndexSource<'n, 'f, 'i, E>) -> Self {
+        let entries = NtfsIndexEntries::new(index);
 
-        Self {
-            index,
-            inner_iterator,
-        }
+        Self { entries }
+    }
+
+    /// Returns the [`NtfsIndexBitmapPolicy`] set via [`NtfsIndexFinder::set_bitmap_policy`].
+    pub fn bitmap_policy(&self) -> NtfsIndexBitmapPolicy {
+        self.entries.bitmap_policy()
     }
 
     /// Finds an entry in this index using the given comparison function and returns an [`NtfsIndexEntry`]
     /// (if there is one).
+    ///
+    /// If an entry has been found, subsequent calls to [`NtfsIndexFinder::next`] return the entries following it.
     pub fn find<'a, T, F>(&'a mut self, fs: &mut T, cmp: F) -> Option<Result<NtfsIndexEntry<'a, E>>>
+    where
+        T: Read + Seek,
+        F: Fn(&E::KeyType) -> Ordering,
+    {
+        self.find_internal(fs, cmp, None)
+    }
+
+    fn find_internal<'a, T, F>(
+        &'a mut self,
+        fs: &mut T,
+        cmp: F,
+        mut trace: Option<&mut Vec<NtfsIndexDescentStep>>,
+    ) -> Option<Result<NtfsIndexEntry<'a, E>>>
     where
         T: Read + Seek,
         F: Fn(&E::KeyType) -> Ordering,
     {
         // Always (re)start by iterating through the Index Root entry ranges.
-        self.inner_iterator = self.index.index_root_entry_ranges.clone();
+        self.entries.reset();
+        let mut node_vcn = None;
+        let mut node_position = self.entries.index.index_root_position();
 
         loop {
             // Get the next entry.
             //
             // A textbook B-tree search algorithm would get the middle entry and perform binary search.
             // But we can't do that here, as we are dealing with variable-length entries.
-            let entry_range = iter_try!(self.inner_iterator.next()?);
-            let entry = iter_try!(entry_range.to_entry(self.inner_iterator.data()));
+            let iter = self.entries.inner_iterators.last_mut().unwrap();
+            let entry_range = iter_try!(iter.next()?);
+            let entry = iter_try!(entry_range.to_entry(iter.data()));
+            let is_last_entry = entry.flags().contains(NtfsIndexEntryFlags::LAST_ENTRY);
 
             // Check if this entry has a key.
-            if let Some(key) = entry.key() {
-                // The entry has a key, so compare it using the given function.
-                let key = iter_try!(key);
+            let ordering = match entry.key() {
+                Some(key) => Some(cmp(&iter_try!(key))),
+                None => None,
+            };
 
-                match cmp(&key) {
+            if ordering != Some(Ordering::Greater) {
+                // This entry decides where our search continues, so record it.
+                if let Some(trace) = trace.as_deref_mut() {
+                    trace.push(NtfsIndexDescentStep {
+                        node_vcn,
+                        node_position,
+                        entry_position: entry.position(),
+                        ordering,
+                    });
+                }
+            }
+
+            if let Some(ordering) = ordering {
+                // The entry has a key, so we have compared it using the given function.
+                match ordering {
                     Ordering::Equal => {
                         // We found what we were looking for!
-                        // Recreate `entry` from the last `self.inner_iterator` to please the borrow checker.
-                        let entry = iter_try!(entry_range.to_entry(self.inner_iterator.data()));
+                        // Recreate `entry` from the current node level to please the borrow checker.
+                        let iter = self.entries.inner_iterators.last().unwrap();
+                        let entry = iter_try!(entry_range.to_entry(iter.data()));
                         return Some(Ok(entry));
                     }
                     Ordering::Less => {
@@ -299,32 +741,255 @@ where
             // Either this entry has no key (= is the last one on this subnode level) or
             // it comes lexicographically AFTER what we're looking for.
             // In both cases, we have to continue iterating in the subnode of this entry (if there is any).
+            // Save this entry like `NtfsIndexEntries` does, so that `next` can continue with it.
             let subnode_vcn = iter_try!(entry.subnode_vcn()?);
-            let index_allocation_item = iter_try!(self.index.index_allocation_item.as_ref().ok_or(
-                NtfsError::MissingIndexAllocation {
-                    position: self.index.index_root_position,
+            let following_entry = if !is_last_entry {
+                Some(entry_range)
+            } else {
+                None
+            };
+            node_position = iter_try!(self.entries.push_subnode(fs, subnode_vcn, following_entry));
+            node_vcn = Some(subnode_vcn);
+        }
+    }
+
+    /// Finds an entry in this index like [`NtfsIndexFinder::find`], and additionally records the descent
+    /// through the B-tree in `trace`.
+    ///
+    /// For every visited node, an [`NtfsIndexDescentStep`] is appended to `trace`.
+    /// It records the entry whose comparison decided how the search continues:
+    /// Either that entry has been found, or the search continues in its subnode.
+    /// `trace` is not cleared before, so it's up to the caller whether to reuse it across lookups.
+    ///
+    /// This is useful to log where a lookup result came from, or to debug collation mismatches.
+    pub fn find_traced<'a, T, F>(
+        &'a mut self,
+        fs: &mut T,
+        cmp: F,
+        trace: &mut Vec<NtfsIndexDescentStep>,
+    ) -> Option<Result<NtfsIndexEntry<'a, E>>>
+    where
+        T: Read + Seek,
+        F: Fn(&E::KeyType) -> Ordering,
+    {
+        self.find_internal(fs, cmp, Some(trace))
+    }
+
+    /// Positions this finder at the first entry that is not less than the probe described by the
+    /// given comparison function, and returns an [`NtfsIndexRange`] to iterate from there in sorted order
+    /// as long as `predicate` returns `true` for the entry keys.
+    ///
+    /// This enables range queries like "all entries between `200` and `299`".
+    /// See [`NtfsIndexFinder::seek_to`] for the semantics of `cmp`.
+    pub fn find_range<'a, T, F, P>(
+        &'a mut self,
+        fs: &mut T,
+        cmp: F,
+        predicate: P,
+    ) -> Result<NtfsIndexRange<'n, 'f, 'i, 'a, E, P>>
+    where
+        T: Read + Seek,
+        F: Fn(&E::KeyType) -> Ordering,
+        P: FnMut(&E::KeyType) -> bool,
+    {
+        self.seek_to(fs, cmp)?;
+
+        Ok(NtfsIndexRange {
+            finder: self,
+            predicate,
+            done: false,
+        })
+    }
+
+    /// Returns the next entry in sorted order, continuing after the entry returned by
+    /// [`NtfsIndexFinder::find`] or at the position set by [`NtfsIndexFinder::seek_to`].
+    ///
+    /// Returns `None` when the end of the index has been reached.
+    /// Calling this function on a fresh [`NtfsIndexFinder`] returns the first entry of the index.
+    pub fn next<'a, T>(&'a mut self, fs: &mut T) -> Option<Result<NtfsIndexEntry<'a, E>>>
+    where
+        T: Read + Seek,
+    {
+        self.entries.next(fs)
+    }
+
+    /// Positions this finder at the first entry that is not less than the probe described by the
+    /// given comparison function.
+    /// That entry is returned by the next call to [`NtfsIndexFinder::next`].
+    ///
+    /// Just like for [`NtfsIndexFinder::find`], `cmp` compares the probe to the given entry key:
+    /// It returns [`Ordering::Less`] if the probe comes before the key, [`Ordering::Greater`] if it comes after,
+    /// and [`Ordering::Equal`] if the key matches.
+    /// Contrary to [`NtfsIndexFinder::find`], multiple keys may match (e.g. all keys beginning with a prefix),
+    /// and this function positions at the first of them.
+    ///
+    /// If all entries are less than the probe, the next call to [`NtfsIndexFinder::next`] returns `None`.
+    pub fn seek_to<T, F>(&mut self, fs: &mut T, cmp: F) -> Result<()>
+    where
+        T: Read + Seek,
+        F: Fn(&E::KeyType) -> Ordering,
+    {
+        self.entries.reset();
+
+        loop {
+            let iter = self.entries.inner_iterators.last_mut().unwrap();
+
+            // Save the iterator state, so that we can rewind to the entry we are about to read.
+            let iter_before_entry = iter.clone();
+
+            let entry_range = match iter.next() {
+                Some(entry_range) => entry_range?,
+                None => return Ok(()),
+            };
+            let entry = entry_range.to_entry(iter.data())?;
+            let is_last_entry = entry.flags().contains(NtfsIndexEntryFlags::LAST_ENTRY);
+
+            if !is_last_entry {
+                if let Some(key) = entry.key() {
+                    if cmp(&key?) == Ordering::Greater {
+                        // This entry is less than the probe, so skip it.
+                        continue;
+                    }
                 }
-            ));
-            let index_allocation_attribute = iter_try!(index_allocation_item.to_attribute());
-            let index_allocation = iter_try!(
-                index_allocation_attribute.structured_value::<_, NtfsIndexAllocation>(fs)
-            );
+            }
+
+            // This entry is the first one on this node level that is not less than the probe
+            // (or the empty "last entry").
+            // All entries of its subnode are less than this entry, but some may still be not less than the probe.
+            // Hence, we have to descend into the subnode (if there is any) and look for them.
+            match entry.subnode_vcn() {
+                Some(subnode_vcn) => {
+                    let subnode_vcn = subnode_vcn?;
+                    let following_entry = if !is_last_entry {
+                        Some(entry_range)
+                    } else {
+                        None
+                    };
+                    self.entries
+                        .push_subnode(fs, subnode_vcn, following_entry)?;
+                }
+                None => {
+                    // We are on a leaf node, so this entry is the one to be returned next.
+                    // If all entries of this leaf are less than the probe, `next` continues with the parent entry
+                    // we saved in `following_entries`.
+                    *self.entries.inner_iterators.last_mut().unwrap() = iter_before_entry;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Sets the [`NtfsIndexBitmapPolicy`] for subnodes whose Index Record is not marked as in use.
+    ///
+    /// With [`NtfsIndexBitmapPolicy::Skip`], entries of such subnodes are neither found
+    /// nor returned by [`NtfsIndexFinder::next`].
+    pub fn set_bitmap_policy(&mut self, bitmap_policy: NtfsIndexBitmapPolicy) {
+        self.entries.set_bitmap_policy(bitmap_policy);
+    }
+}
+
+/// A single step of the descent through an index, recorded by [`NtfsIndexFinder::find_traced`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NtfsIndexDescentStep {
+    node_vcn: Option<Vcn>,
+    node_position: NtfsPosition,
+    entry_position: NtfsPosition,
+    ordering: Option<Ordering>,
+}
+
+impl NtfsIndexDescentStep {
+    /// Returns the absolute position of the entry that decided this step, in bytes.
+    pub fn entry_position(&self) -> NtfsPosition {
+        self.entry_position
+    }
+
+    /// Returns the absolute position of the visited node, in bytes.
+    ///
+    /// This is the position of the Index Root attribute or the position of the Index Record.
+    pub fn node_position(&self) -> NtfsPosition {
+        self.node_position
+    }
+
+    /// Returns the Virtual Cluster Number (VCN) of the visited Index Record,
+    /// or `None` if the visited node is the Index Root.
+    pub fn node_vcn(&self) -> Option<Vcn> {
+        self.node_vcn
+    }
+
+    /// Returns the result of comparing the searched key with the key of the entry, as returned by the
+    /// comparison function.
+    ///
+    /// [`Ordering::Equal`] means that the entry has been found.
+    /// [`Ordering::Less`] means that the search continues in the subnode of this entry.
+    /// `None` means that the entry is the empty "last entry" of the node, and the search continues in its
+    /// subnode.
+    pub fn ordering(&self) -> Option<Ordering> {
+        self.ordering
+    }
+}
+
+/// Iterator over
+///   a range of index entries,
+///   sorted ascending by the index key,
+///   returning an [`NtfsIndexEntry`] for each entry.
+///
+/// This iterator is returned from the [`NtfsIndexFinder::find_range`] function.
+/// It stops at the first entry for which the predicate returns `false`.
+pub struct NtfsIndexRange<'n, 'f, 'i, 'a, E, P>
+where
+    E: NtfsIndexEntryType,
+    P: FnMut(&E::KeyType) -> bool,
+{
+    finder: &'a mut NtfsIndexFinder<'n, 'f, 'i, E>,
+    predicate: P,
+    done: bool,
+}
+
+impl<'n, 'f, 'i, 'a, E, P> NtfsIndexRange<'n, 'f, 'i, 'a, E, P>
+where
+    E: NtfsIndexEntryType,
+    P: FnMut(&E::KeyType) -> bool,
+{
+    /// See [`Iterator::next`].
+    pub fn next<'r, T>(&'r mut self, fs: &mut T) -> Option<Result<NtfsIndexEntry<'r, E>>>
+    where
+        T: Read + Seek,
+    {
+        if self.done {
+            return None;
+        }
 
-            let subnode = iter_try!(index_allocation.record_from_vcn(
-                fs,
-                self.index.index_record_size,
-                subnode_vcn
-            ));
-            self.inner_iterator = subnode.into_entry_ranges();
+        let entry = match self.finder.next(fs) {
+            Some(Ok(entry)) => entry,
+            Some(Err(e)) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+            None => {
+                self.done = true;
+                return None;
+            }
+        };
+
+        // Only the empty "last entry" has no key, and `NtfsIndexEntries` never returns that one.
+        let key = iter_try!(entry.key()?);
+        if !(self.predicate)(&key) {
+            self.done = true;
+            return None;
         }
+
+        Some(Ok(entry))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::file::KnownNtfsFileRecordNumber;
     use crate::indexes::NtfsFileNameIndex;
     use crate::ntfs::Ntfs;
+    use crate::structured_values::NtfsCollationRule;
+    use crate::upcase_table::UpcaseOrd;
 
     #[test]
     fn test_index_find() {
@@ -356,6 +1021,76 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_owned_index() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // Create owned indexes from locally created files and store them for later.
+        let mut indexes = Vec::new();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let subdir_record_number = {
+            let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+            let mut root_dir_finder = root_dir_index.finder();
+            let entry =
+                NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                    .unwrap()
+                    .unwrap();
+            entry.file_reference().file_record_number()
+        };
+        indexes.push(root_dir.into_directory_index(&mut testfs1).unwrap());
+
+        let subdir = ntfs.file(&mut testfs1, subdir_record_number).unwrap();
+        indexes.push(subdir.into_directory_index(&mut testfs1).unwrap());
+
+        // Files are no directories.
+        let file = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::MFT as u64)
+            .unwrap();
+        assert!(matches!(
+            file.into_directory_index(&mut testfs1),
+            Err(NtfsError::NotADirectory { .. })
+        ));
+
+        // The owned indexes work just like the borrowing ones.
+        let root_dir_index = &indexes[0];
+        assert_eq!(
+            root_dir_index.file().file_record_number(),
+            KnownNtfsFileRecordNumber::RootDirectory as u64
+        );
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            entry.file_reference().file_record_number(),
+            subdir_record_number
+        );
+
+        // The subdirectory is a large index, so this also reads its $INDEX_ALLOCATION attribute.
+        let subdir_index = &indexes[1];
+        let mut subdir_iter = subdir_index.entries();
+        let mut entry_count = 0;
+        while let Some(entry) = subdir_iter.next(&mut testfs1) {
+            entry.unwrap();
+            entry_count += 1;
+        }
+        assert_eq!(entry_count, 512);
+
+        let mut subdir_finder = subdir_index.finder();
+        for dir_name in ["1", "256", "512"] {
+            let entry = NtfsFileNameIndex::find(&mut subdir_finder, &ntfs, &mut testfs1, dir_name)
+                .unwrap()
+                .unwrap();
+            assert_eq!(entry.key().unwrap().unwrap().name(), dir_name);
+        }
+
+        let subdir = indexes.pop().unwrap().into_file();
+        assert_eq!(subdir.file_record_number(), subdir_record_number);
+    }
+
     #[test]
     fn test_index_iter() {
         let mut testfs1 = crate::helpers::tests::testfs1();
@@ -385,12 +1120,417 @@ mod tests {
         let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
         let mut subdir_iter = subdir_index.entries();
 
-        for dir_name in dir_names {
+        for dir_name in &dir_names {
             let entry = subdir_iter.next(&mut testfs1).unwrap().unwrap();
             let entry_name = entry.key().unwrap().unwrap();
             assert_eq!(entry_name.name(), dir_name.as_str());
         }
 
         assert!(subdir_iter.next(&mut testfs1).is_none());
+
+        // The attached iterator returns the same entries and can be used with iterator adapters.
+        let entries = subdir_index
+            .entries()
+            .attach(&mut testfs1)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        let entry_names = entries
+            .iter()
+            .map(|entry| entry.key().unwrap().unwrap().name().to_string_lossy())
+            .collect::<Vec<_>>();
+        assert_eq!(entry_names, dir_names);
+    }
+
+    #[test]
+    fn test_index_iter_with_undecodable_key() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+
+        // Set an invalid namespace in the key of the 10th entry of the "many_subdirs" subdirectory.
+        let (subdir_record_number, entry_position) = {
+            let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+            ntfs.read_upcase_table(&mut testfs1).unwrap();
+            let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+            let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+            let mut root_dir_finder = root_dir_index.finder();
+            let entry =
+                NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                    .unwrap()
+                    .unwrap();
+            let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+            let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+            let entry = subdir_index
+                .entries()
+                .attach(&mut testfs1)
+                .nth(9)
+                .unwrap()
+                .unwrap();
+
+            (
+                subdir.file_record_number(),
+                entry.position().value().unwrap().get() as usize,
+            )
+        };
+
+        // The namespace is at offset 0x41 of the key, which follows the 16-byte Index Entry header.
+        // It must not be a byte replaced by the Update Sequence Array.
+        let namespace_position = entry_position + 16 + 0x41;
+        assert!(namespace_position % 512 < 510);
+        testfs1.get_mut()[namespace_position] = 7;
+
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let subdir = ntfs.file(&mut testfs1, subdir_record_number).unwrap();
+        let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+        let mut subdir_iter = subdir_index.entries();
+
+        // Only the key of the modified entry fails to decode, and iteration continues after it.
+        let mut entry_count = 0;
+        let mut undecodable_keys = Vec::new();
+        while let Some(entry) = subdir_iter.next(&mut testfs1) {
+            let entry = entry.unwrap();
+            entry_count += 1;
+
+            if let Err(e) = entry.key().unwrap() {
+                assert!(matches!(
+                    e,
+                    NtfsError::UnsupportedFileNamespace { actual: 7, .. }
+                ));
+                undecodable_keys.push(entry.raw_key().to_vec());
+            }
+        }
+        assert_eq!(entry_count, 512);
+        assert_eq!(undecodable_keys.len(), 1);
+
+        // The raw key can still be inspected.
+        let raw_key = &undecodable_keys[0];
+        assert_eq!(raw_key[0x41], 7);
+        assert_eq!(raw_key.len(), 0x42 + 2 * raw_key[0x40] as usize);
+    }
+
+    #[test]
+    fn test_index_find_range() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // Find the "many_subdirs" subdirectory.
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                .unwrap()
+                .unwrap();
+        let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+        let mut subdir_finder = subdir_index.finder();
+
+        // Subdirectories are ordered like "2", "20", "200", "201", ..., "209", "21", "210", ...
+        // Hence, the range from "200" to "299" also contains "21" to "29".
+        let mut expected_names = (1..=512)
+            .map(|i| format!("{i}"))
+            .filter(|name| name.as_str() >= "200" && name.as_str() <= "299")
+            .collect::<Vec<_>>();
+        expected_names.sort_unstable();
+        assert_eq!(expected_names.len(), 100 + 9);
+
+        let mut range = subdir_finder
+            .find_range(
+                &mut testfs1,
+                |file_name| "200".upcase_cmp(&ntfs, &file_name.name()).unwrap(),
+                |file_name| "299".upcase_cmp(&ntfs, &file_name.name()).unwrap() != Ordering::Less,
+            )
+            .unwrap();
+        let mut names = Vec::new();
+
+        while let Some(entry) = range.next(&mut testfs1) {
+            let entry = entry.unwrap();
+            let file_name = entry.key().unwrap().unwrap();
+            names.push(file_name.name().to_string_lossy());
+        }
+
+        assert_eq!(names, expected_names);
+
+        // A probe between two entries positions at the next greater entry.
+        subdir_finder
+            .seek_to(&mut testfs1, |file_name| {
+                "2000".upcase_cmp(&ntfs, &file_name.name()).unwrap()
+            })
+            .unwrap();
+        let entry = subdir_finder.next(&mut testfs1).unwrap().unwrap();
+        assert_eq!(entry.key().unwrap().unwrap().name(), "201");
+
+        // A probe beyond the last entry leaves nothing to iterate.
+        subdir_finder
+            .seek_to(&mut testfs1, |file_name| {
+                "a".upcase_cmp(&ntfs, &file_name.name()).unwrap()
+            })
+            .unwrap();
+        assert!(subdir_finder.next(&mut testfs1).is_none());
+
+        // After a successful `find`, `next` continues with the following entry.
+        NtfsFileNameIndex::find(&mut subdir_finder, &ntfs, &mut testfs1, "299")
+            .unwrap()
+            .unwrap();
+        let entry = subdir_finder.next(&mut testfs1).unwrap().unwrap();
+        assert_eq!(entry.key().unwrap().unwrap().name(), "3");
+    }
+
+    #[test]
+    fn test_index_find_traced() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // Find the "many_subdirs" subdirectory.
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                .unwrap()
+                .unwrap();
+        let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+        let mut subdir_finder = subdir_index.finder();
+
+        // Looking up "299" descends from the Index Root through two levels of Index Records.
+        let mut trace = Vec::new();
+        let entry = subdir_finder
+            .find_traced(
+                &mut testfs1,
+                |file_name| "299".upcase_cmp(&ntfs, &file_name.name()).unwrap(),
+                &mut trace,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.key().unwrap().unwrap().name(), "299");
+        let entry_position = entry.position();
+
+        let expected_trace = [
+            (None, 0x15160, 0x15180, None),
+            (Some(0x28), 0x146400, 0x146740, Some(Ordering::Less)),
+            (Some(0x60), 0xa6e00, 0xa7af8, Some(Ordering::Equal)),
+        ];
+        assert_eq!(trace.len(), expected_trace.len());
+
+        for (step, (node_vcn, node_position, entry_position, ordering)) in
+            trace.iter().zip(expected_trace)
+        {
+            assert_eq!(step.node_vcn(), node_vcn.map(Vcn::from));
+            assert_eq!(step.node_position(), NtfsPosition::new(node_position));
+            assert_eq!(step.entry_position(), NtfsPosition::new(entry_position));
+            assert_eq!(step.ordering(), ordering);
+        }
+
+        // The last step is the returned entry.
+        assert_eq!(trace.last().unwrap().entry_position(), entry_position);
+
+        // A lookup of a missing name records the descent up to the leaf node.
+        trace.clear();
+        assert!(subdir_finder
+            .find_traced(
+                &mut testfs1,
+                |file_name| "2999".upcase_cmp(&ntfs, &file_name.name()).unwrap(),
+                &mut trace,
+            )
+            .is_none());
+        assert_eq!(trace.len(), 3);
+        assert_ne!(trace.last().unwrap().ordering(), Some(Ordering::Equal));
+    }
+
+    #[test]
+    fn test_collation_mismatch() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+
+        let (position, file_record_size, value_start) = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let extend_dir = ntfs
+                .file(&mut testfs1, KnownNtfsFileRecordNumber::Extend as u64)
+                .unwrap();
+            let attribute = extend_dir
+                .find_resident_attribute(NtfsAttributeType::IndexRoot, Some("$I30"), None)
+                .unwrap();
+            let index_root = attribute
+                .resident_structured_value::<NtfsIndexRoot>()
+                .unwrap();
+            assert_eq!(
+                index_root.indexed_attribute_type(),
+                NtfsAttributeType::FileName as u32
+            );
+            assert_eq!(
+                index_root.collation_rule().unwrap(),
+                NtfsCollationRule::FileName
+            );
+
+            (
+                extend_dir.position().value().unwrap().get() as usize,
+                ntfs.file_record_size() as usize,
+                attribute.resident_value_range().unwrap().start,
+            )
+        };
+
+        // Turn the Index Root of "$Extend" into one of a $SII-style index, which doesn't index an attribute
+        // and collates its keys as 32-bit unsigned integers.
+        crate::helpers::tests::patch_file_record(
+            &mut testfs1,
+            position,
+            file_record_size,
+            |record| {
+                record[value_start..value_start + 4].copy_from_slice(&0u32.to_le_bytes());
+                record[value_start + 4..value_start + 8]
+                    .copy_from_slice(&(NtfsCollationRule::NtofsUlong as u32).to_le_bytes());
+            },
+        );
+
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let extend_dir = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::Extend as u64)
+            .unwrap();
+
+        // The filename index type refuses to parse the keys of this index.
+        assert!(matches!(
+            extend_dir.directory_index(&mut testfs1),
+            Err(NtfsError::CollationMismatch {
+                expected: NtfsCollationRule::FileName,
+                actual: NtfsCollationRule::NtofsUlong,
+                ..
+            })
+        ));
+
+        // The raw index type works with any collation rule.
+        let raw_index = extend_dir.index(&mut testfs1, "$I30").unwrap();
+        assert_eq!(raw_index.entries().attach(&mut testfs1).count(), 3);
+    }
+
+    #[test]
+    fn test_index_bitmap() {
+        fn collect_names<T>(
+            entries: NtfsIndexEntries<NtfsFileNameIndex>,
+            fs: &mut T,
+        ) -> Result<Vec<String>>
+        where
+            T: Read + Seek,
+        {
+            entries
+                .attach(fs)
+                .map(|entry| {
+                    entry.map(|entry| entry.key().unwrap().unwrap().name().to_string_lossy())
+                })
+                .collect()
+        }
+
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // Find the "many_subdirs" subdirectory, whose large index comes with a $BITMAP attribute.
+        let (subdir_record_number, all_names, index_record_size) = {
+            let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+            let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+            let mut root_dir_finder = root_dir_index.finder();
+            let entry =
+                NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                    .unwrap()
+                    .unwrap();
+            let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+            let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+            assert!(subdir_index.has_bitmap());
+
+            // All Index Records are in use, so the bitmap doesn't change anything.
+            let all_names = collect_names(subdir_index.entries(), &mut testfs1).unwrap();
+            assert_eq!(all_names.len(), 512);
+
+            (
+                subdir.file_record_number(),
+                all_names,
+                subdir_index.index_record_size,
+            )
+        };
+
+        // "299" is stored in the Index Record at VCN 0x60 (see `test_index_find_traced`).
+        // Mark that Index Record as not in use in the $BITMAP attribute of the index.
+        let vcn = Vcn::from(0x60);
+        let record_number = vcn.offset(&ntfs).unwrap() as usize / index_record_size as usize;
+
+        let (position, file_record_size, value_start) = {
+            let subdir = ntfs.file(&mut testfs1, subdir_record_number).unwrap();
+            let attribute = subdir
+                .find_resident_attribute(NtfsAttributeType::Bitmap, Some("$I30"), None)
+                .unwrap();
+            let value_range = attribute.resident_value_range().unwrap();
+            assert!(value_range.len() > record_number / 8);
+
+            (
+                subdir.position().value().unwrap().get() as usize,
+                ntfs.file_record_size() as usize,
+                value_range.start,
+            )
+        };
+
+        crate::helpers::tests::patch_file_record(
+            &mut testfs1,
+            position,
+            file_record_size,
+            |record| {
+                let byte = &mut record[value_start + record_number / 8];
+                let bit = 1 << (record_number % 8);
+                assert_ne!(*byte & bit, 0);
+                *byte &= !bit;
+            },
+        );
+
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let subdir = ntfs.file(&mut testfs1, subdir_record_number).unwrap();
+        let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+
+        // By default, traversing the index fails at the deallocated Index Record.
+        let error = collect_names(subdir_index.entries(), &mut testfs1).unwrap_err();
+        assert!(matches!(
+            error,
+            NtfsError::IndexRecordNotAllocated { vcn: error_vcn, .. } if error_vcn == vcn
+        ));
+
+        let mut subdir_finder = subdir_index.finder();
+        let error = NtfsFileNameIndex::find(&mut subdir_finder, &ntfs, &mut testfs1, "299")
+            .unwrap()
+            .unwrap_err();
+        assert!(matches!(error, NtfsError::IndexRecordNotAllocated { .. }));
+
+        // Skipping the deallocated Index Record returns all other entries in order.
+        let mut entries = subdir_index.entries();
+        entries.set_bitmap_policy(NtfsIndexBitmapPolicy::Skip);
+        let names = collect_names(entries, &mut testfs1).unwrap();
+        assert!(!names.is_empty() && names.len() < all_names.len());
+        assert!(!names.iter().any(|name| name == "299"));
+
+        let mut all_names_iter = all_names.iter();
+        assert!(names
+            .iter()
+            .all(|name| all_names_iter.any(|all_name| all_name == name)));
+
+        // The finder doesn't find entries of the deallocated Index Record, but all others.
+        let mut subdir_finder = subdir_index.finder();
+        subdir_finder.set_bitmap_policy(NtfsIndexBitmapPolicy::Skip);
+        assert!(NtfsFileNameIndex::find(&mut subdir_finder, &ntfs, &mut testfs1, "299").is_none());
+
+        for dir_name in ["1", "512"] {
+            let entry = NtfsFileNameIndex::find(&mut subdir_finder, &ntfs, &mut testfs1, dir_name)
+                .unwrap()
+                .unwrap();
+            assert_eq!(entry.key().unwrap().unwrap().name(), dir_name);
+        }
+
+        // Ignoring the $BITMAP attribute also returns the stale entries of the deallocated Index Record.
+        let mut entries = subdir_index.entries();
+        entries.set_bitmap_policy(NtfsIndexBitmapPolicy::Ignore);
+        assert_eq!(collect_names(entries, &mut testfs1).unwrap(), all_names);
+
+        // An owned index checks the $BITMAP attribute as well.
+        let owned_subdir_index = subdir.into_directory_index(&mut testfs1).unwrap();
+        assert!(matches!(
+            collect_names(owned_subdir_index.entries(), &mut testfs1),
+            Err(NtfsError::IndexRecordNotAllocated { .. })
+        ));
     }
 }
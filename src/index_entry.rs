@@ -6,8 +6,10 @@ use core::marker::PhantomData;
 use core::ops::Range;
 use core::{fmt, mem};
 
+use crate::io::{Read, Seek};
+use alloc::borrow::Cow;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
-use binrw::io::{Read, Seek};
 use bitflags::bitflags;
 use byteorder::{ByteOrder, LittleEndian};
 use memoffset::offset_of;
@@ -108,7 +110,7 @@ pub struct NtfsIndexEntry<'s, E>
 where
     E: NtfsIndexEntryType,
 {
-    slice: &'s [u8],
+    slice: Cow<'s, [u8]>,
     position: NtfsPosition,
     entry_type: PhantomData<E>,
 }
@@ -121,12 +123,12 @@ where
         let entry_type = PhantomData;
 
         let mut entry = Self {
-            slice,
+            slice: Cow::Borrowed(slice),
             position,
             entry_type,
         };
         entry.validate_size()?;
-        entry.slice = &entry.slice[..entry.index_entry_length() as usize];
+        entry.slice = Cow::Borrowed(&slice[..entry.index_entry_length() as usize]);
 
         Ok(entry)
     }
@@ -205,6 +207,16 @@ where
         LittleEndian::read_u16(&self.slice[start..])
     }
 
+    /// Returns an owned copy of this Index Entry, which is no longer bound to the lifetime of the
+    /// index data it was read from.
+    pub fn into_owned(self) -> NtfsIndexEntry<'static, E> {
+        NtfsIndexEntry {
+            slice: Cow::Owned(self.slice.into_owned()),
+            position: self.position,
+            entry_type: self.entry_type,
+        }
+    }
+
     /// Returns the structured value of the key of this Index Entry,
     /// or `None` if this Index Entry has no key.
     ///
@@ -242,6 +254,22 @@ where
         self.position
     }
 
+    /// Returns the raw bytes of the key of this Index Entry, or an empty slice if this Index Entry has no key.
+    ///
+    /// Contrary to [`NtfsIndexEntry::key`], this doesn't decode the key and therefore never fails.
+    /// This allows inspecting keys that cannot be decoded.
+    /// A key exceeding the Index Entry is truncated to the Index Entry.
+    pub fn raw_key(&self) -> &[u8] {
+        if self.key_length() == 0 || self.flags().contains(NtfsIndexEntryFlags::LAST_ENTRY) {
+            return &[];
+        }
+
+        // `NtfsIndexEntry::new` has already ensured that the header fits into the Index Entry.
+        let start = INDEX_ENTRY_HEADER_SIZE;
+        let end = usize::min(start + self.key_length() as usize, self.slice.len());
+        &self.slice[start..end]
+    }
+
     /// Returns the Virtual Cluster Number (VCN) of the subnode of this Index Entry,
     /// or `None` if this Index Entry has no subnode.
     pub fn subnode_vcn(&self) -> Option<Result<Vcn>> {
@@ -250,8 +278,9 @@ where
         }
 
         // Get the subnode VCN from the very end of the Index Entry, but at least after the header.
+        // The Index Entry length may be smaller than a VCN in a corrupted entry.
         let start = usize::max(
-            self.index_entry_length() as usize - mem::size_of::<Vcn>(),
+            (self.index_entry_length() as usize).saturating_sub(mem::size_of::<Vcn>()),
             INDEX_ENTRY_HEADER_SIZE,
         );
         let end = start + mem::size_of::<Vcn>();
@@ -268,12 +297,27 @@ where
     }
 
     /// Returns an [`NtfsFile`] for the file referenced by this Index Entry.
+    ///
+    /// This fails with [`NtfsError::FileRecordNotInUse`] if the referenced File Record is not in use
+    /// (see [`Ntfs::file_in_use`]), which happens for stale Index Entries of deleted files.
+    ///
+    /// The returned [`NtfsFile`] remembers whether this Index Entry claims it to be a directory.
+    /// [`NtfsFile::directory_index`] includes that in [`NtfsError::NotADirectory`] if the File Record
+    /// disagrees.
     pub fn to_file<'n, T>(&self, ntfs: &'n Ntfs, fs: &mut T) -> Result<NtfsFile<'n>>
     where
         E: NtfsIndexEntryHasFileReference,
         T: Read + Seek,
     {
-        self.file_reference().to_file(ntfs, fs)
+        let file = ntfs.file_in_use(fs, self.file_reference().file_record_number())?;
+
+        // A key that fails to parse just doesn't contribute any information here.
+        let index_entry_is_directory = self
+            .key()
+            .and_then(|key| key.ok())
+            .and_then(|key| E::key_is_directory(&key));
+
+        Ok(file.with_index_entry_is_directory(index_entry_is_directory))
     }
 
     fn validate_size(&self) -> Result<()> {
@@ -285,6 +329,16 @@ where
             });
         }
 
+        // A smaller length would not even cover the header, and a zero length would never advance
+        // to the next Index Entry.
+        if (self.index_entry_length() as usize) < INDEX_ENTRY_HEADER_SIZE {
+            return Err(NtfsError::InvalidIndexEntrySize {
+                position: self.position,
+                expected: INDEX_ENTRY_HEADER_SIZE as u16,
+                actual: self.index_entry_length(),
+            });
+        }
+
         if self.index_entry_length() as usize > self.slice.len() {
             return Err(NtfsError::InvalidIndexEntrySize {
                 position: self.position,
@@ -297,23 +351,49 @@ where
     }
 }
 
+/// Data of a single index node.
+///
+/// The Index Root node is borrowed from the File Record holding the $INDEX_ROOT attribute whenever possible,
+/// so that creating an [`NtfsIndex`] doesn't copy it.
+/// Index Records read from disk are owned and shared between all clones of an [`IndexNodeEntryRanges`]
+/// iterator, so that saving and restoring the iteration state during an index traversal doesn't copy
+/// the entire Index Record.
+/// `Arc` is used instead of `Rc` to keep [`NtfsIndex`] and its iterators `Send` and `Sync`.
+///
+/// [`NtfsIndex`]: crate::NtfsIndex
+#[derive(Clone, Debug)]
+pub(crate) enum NodeData<'f> {
+    Borrowed(&'f [u8]),
+    Owned(Arc<Vec<u8>>),
+}
+
+impl<'f> NodeData<'f> {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Self::Borrowed(data) => data,
+            Self::Owned(data) => data,
+        }
+    }
+}
+
+/// Iterator over the [`IndexEntryRange`]s of a single index node.
 #[derive(Clone, Debug)]
-pub(crate) struct IndexNodeEntryRanges<E>
+pub(crate) struct IndexNodeEntryRanges<'f, E>
 where
     E: NtfsIndexEntryType,
 {
-    data: Vec<u8>,
+    data: NodeData<'f>,
     range: Range<usize>,
     position: NtfsPosition,
     entry_type: PhantomData<E>,
 }
 
-impl<E> IndexNodeEntryRanges<E>
+impl<'f, E> IndexNodeEntryRanges<'f, E>
 where
     E: NtfsIndexEntryType,
 {
-    pub(crate) fn new(data: Vec<u8>, range: Range<usize>, position: NtfsPosition) -> Self {
-        debug_assert!(range.end <= data.len());
+    pub(crate) fn new(data: NodeData<'f>, range: Range<usize>, position: NtfsPosition) -> Self {
+        debug_assert!(range.end <= data.as_slice().len());
         let entry_type = PhantomData;
 
         Self {
@@ -325,11 +405,26 @@ where
     }
 
     pub(crate) fn data(&self) -> &[u8] {
-        &self.data
+        self.data.as_slice()
+    }
+
+    /// Returns an iterator with the same state that owns the node data, copying it if necessary.
+    pub(crate) fn into_owned(self) -> IndexNodeEntryRanges<'static, E> {
+        let data = match self.data {
+            NodeData::Borrowed(data) => Arc::new(data.to_vec()),
+            NodeData::Owned(data) => data,
+        };
+
+        IndexNodeEntryRanges {
+            data: NodeData::Owned(data),
+            range: self.range,
+            position: self.position,
+            entry_type: self.entry_type,
+        }
     }
 }
 
-impl<E> Iterator for IndexNodeEntryRanges<E>
+impl<'f, E> Iterator for IndexNodeEntryRanges<'f, E>
 where
     E: NtfsIndexEntryType,
 {
@@ -343,13 +438,13 @@ where
         // Get the current entry.
         let start = self.range.start;
         let position = self.position;
-        let entry = iter_try!(NtfsIndexEntry::<E>::new(&self.data[start..], position));
+        let entry = iter_try!(NtfsIndexEntry::<E>::new(&self.data()[start..], position));
         let end = start + entry.index_entry_length() as usize;
 
         if entry.flags().contains(NtfsIndexEntryFlags::LAST_ENTRY) {
             // This is the last entry.
             // Ensure that we don't read any other entries by advancing `self.range.start` to the end.
-            self.range.start = self.data.len();
+            self.range.start = self.data().len();
         } else {
             // This is not the last entry.
             // Advance our iterator to the next entry.
@@ -361,7 +456,7 @@ where
     }
 }
 
-impl<E> FusedIterator for IndexNodeEntryRanges<E> where E: NtfsIndexEntryType {}
+impl<'f, E> FusedIterator for IndexNodeEntryRanges<'f, E> where E: NtfsIndexEntryType {}
 
 /// Iterator over
 ///   all index entries of a single index node,
@@ -434,3 +529,77 @@ where
 }
 
 impl<'s, E> FusedIterator for NtfsIndexNodeEntries<'s, E> where E: NtfsIndexEntryType {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexes::NtfsRawIndex;
+
+    /// Returns an Index Entry of `slice_length` bytes with the given header fields.
+    fn index_entry(
+        slice_length: usize,
+        index_entry_length: u16,
+        key_length: u16,
+        flags: NtfsIndexEntryFlags,
+    ) -> Vec<u8> {
+        let mut entry = vec![0u8; slice_length];
+        LittleEndian::write_u16(&mut entry[8..], index_entry_length);
+        LittleEndian::write_u16(&mut entry[10..], key_length);
+        entry[12] = flags.bits();
+        entry
+    }
+
+    #[test]
+    fn test_index_entry_length_below_header_size() {
+        let position = NtfsPosition::new(0x1000);
+
+        for index_entry_length in [0, 7, 15] {
+            let entry = index_entry(32, index_entry_length, 0, NtfsIndexEntryFlags::empty());
+            assert!(matches!(
+                NtfsIndexEntry::<NtfsRawIndex>::new(&entry, position),
+                Err(NtfsError::InvalidIndexEntrySize {
+                    expected: 16,
+                    actual,
+                    ..
+                }) if actual == index_entry_length
+            ));
+
+            // A zero-length entry must not make the iterator loop endlessly over the same entry.
+            let mut entries = NtfsIndexNodeEntries::<NtfsRawIndex>::new(&entry, position);
+            assert!(matches!(entries.next(), Some(Err(_))));
+        }
+    }
+
+    #[test]
+    fn test_index_entry_ranges_near_u16_boundaries() {
+        let position = NtfsPosition::new(0x1000);
+
+        // The key length exceeds the Index Entry.
+        let entry = index_entry(16, 16, u16::MAX, NtfsIndexEntryFlags::empty());
+        let entry = NtfsIndexEntry::<NtfsRawIndex>::new(&entry, position).unwrap();
+        assert!(matches!(
+            entry.key(),
+            Some(Err(NtfsError::InvalidIndexEntryDataRange { range, .. }))
+                if range == (16..16 + u16::MAX as usize)
+        ));
+
+        // The data offset and length are at their maximum.
+        let mut entry = index_entry(16, 16, 0, NtfsIndexEntryFlags::empty());
+        LittleEndian::write_u16(&mut entry[0..], u16::MAX);
+        LittleEndian::write_u16(&mut entry[2..], u16::MAX);
+        let entry = NtfsIndexEntry::<NtfsRawIndex>::new(&entry, position).unwrap();
+        assert!(matches!(
+            entry.data(),
+            Some(Err(NtfsError::InvalidIndexEntryDataRange { range, .. }))
+                if range == (u16::MAX as usize..2 * u16::MAX as usize)
+        ));
+
+        // The subnode VCN doesn't fit into an Index Entry that only consists of the header.
+        let entry = index_entry(16, 16, 0, NtfsIndexEntryFlags::HAS_SUBNODE);
+        let entry = NtfsIndexEntry::<NtfsRawIndex>::new(&entry, position).unwrap();
+        assert!(matches!(
+            entry.subnode_vcn(),
+            Some(Err(NtfsError::InvalidIndexEntryDataRange { range, .. })) if range == (16..24)
+        ));
+    }
+}
@@ -1,19 +1,22 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use core::fmt;
 use core::ops::Range;
 
+use crate::io::{Read, Seek};
+use alloc::sync::Arc;
 use alloc::vec;
-use binrw::io::{Read, Seek};
+use alloc::vec::Vec;
+use bitflags::bitflags;
 use byteorder::{ByteOrder, LittleEndian};
 use memoffset::offset_of;
 
 use crate::attribute_value::NtfsAttributeValue;
 use crate::error::{NtfsError, Result};
-use crate::index_entry::{IndexNodeEntryRanges, NtfsIndexNodeEntries};
+use crate::index_entry::{IndexNodeEntryRanges, NodeData, NtfsIndexNodeEntries};
 use crate::indexes::NtfsIndexEntryType;
-use crate::record::Record;
-use crate::record::RecordHeader;
+use crate::record::{Record, RecordHeader, NTFS_BLOCK_SIZE};
 use crate::traits::NtfsReadSeek;
 use crate::types::{NtfsPosition, Vcn};
 
@@ -26,6 +29,10 @@ struct IndexRecordHeader {
     vcn: i64,
 }
 
+/// Smallest and largest Index Record size that Windows can create.
+const MIN_INDEX_RECORD_SIZE: u32 = 512;
+const MAX_INDEX_RECORD_SIZE: u32 = 65536;
+
 /// Size of all [`IndexNodeHeader`] fields plus some reserved bytes.
 pub(crate) const INDEX_NODE_HEADER_SIZE: usize = 16;
 
@@ -37,6 +44,22 @@ pub(crate) struct IndexNodeHeader {
     pub(crate) flags: u8,
 }
 
+bitflags! {
+    /// Flags returned by [`NtfsIndexRecord::flags`].
+    #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+    pub struct NtfsIndexNodeFlags: u8 {
+        /// The index node has sub-nodes (see [`NtfsIndexRecord::has_subnodes`]).
+        const HAS_SUBNODES = 0x01;
+    }
+}
+
+impl fmt::Display for NtfsIndexNodeFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
 /// A single NTFS Index Record.
 ///
 /// These records are denoted via an `INDX` signature on the filesystem.
@@ -53,8 +76,6 @@ pub struct NtfsIndexRecord {
     record: Record,
 }
 
-const HAS_SUBNODES_FLAG: u8 = 0x01;
-
 impl NtfsIndexRecord {
     pub(crate) fn new<T>(
         fs: &mut T,
@@ -65,11 +86,29 @@ impl NtfsIndexRecord {
         T: Read + Seek,
     {
         let data_position = value.data_position();
+        validate_index_record_size(data_position, index_record_size)?;
 
+        // Only the Data Run at the current position tells whether the entire Index Record is stored contiguously.
+        // The position after reading is no indicator, because the next Data Run may just happen to follow
+        // relocated clusters in the middle of the Index Record.
+        let offset = value.stream_position();
+        let is_contiguous = value.contiguous_len() >= index_record_size as u64;
         let mut data = vec![0; index_record_size as usize];
         value.read_exact(fs, &mut data)?;
 
-        let mut record = Record::new(data, data_position);
+        let mut record = if is_contiguous {
+            Record::new(data, data_position)
+        } else {
+            // The Index Record is not stored contiguously, but straddles two Data Runs
+            // (or even two connected attributes of an Attribute List).
+            // Determine the position of each block to report errors at the right place.
+            let block_positions = (0..index_record_size as u64)
+                .step_by(NTFS_BLOCK_SIZE)
+                .map(|block_offset| value.map_position(fs, offset + block_offset))
+                .collect::<Result<Vec<NtfsPosition>>>()?;
+            Record::new(data, block_positions[0]).with_block_positions(block_positions)
+        };
+
         Self::validate_signature(&record)?;
         record.fixup()?;
 
@@ -82,7 +121,7 @@ impl NtfsIndexRecord {
     /// Returns an iterator over all entries of this Index Record (cf. [`NtfsIndexEntry`]).
     ///
     /// [`NtfsIndexEntry`]: crate::NtfsIndexEntry
-    pub fn entries<E>(&self) -> Result<NtfsIndexNodeEntries<E>>
+    pub fn entries<E>(&self) -> Result<NtfsIndexNodeEntries<'_, E>>
     where
         E: NtfsIndexEntryType,
     {
@@ -95,17 +134,21 @@ impl NtfsIndexRecord {
     fn entries_range_and_position(&self) -> (Range<usize>, NtfsPosition) {
         let start = INDEX_RECORD_HEADER_SIZE as usize + self.index_entries_offset() as usize;
         let end = INDEX_RECORD_HEADER_SIZE as usize + self.index_data_size() as usize;
-        let position = self.record.position() + start;
+        let position = self.record.position_at(start);
 
         (start..end, position)
     }
 
+    /// Returns flags set for the index node of this Index Record as specified by [`NtfsIndexNodeFlags`].
+    pub fn flags(&self) -> NtfsIndexNodeFlags {
+        let start = INDEX_RECORD_HEADER_SIZE as usize + offset_of!(IndexNodeHeader, flags);
+        NtfsIndexNodeFlags::from_bits_truncate(self.record.data()[start])
+    }
+
     /// Returns whether this index node has sub-nodes.
     /// Otherwise, this index node is a leaf node.
     pub fn has_subnodes(&self) -> bool {
-        let start = INDEX_RECORD_HEADER_SIZE as usize + offset_of!(IndexNodeHeader, flags);
-        let flags = self.record.data()[start];
-        (flags & HAS_SUBNODES_FLAG) != 0
+        self.flags().contains(NtfsIndexNodeFlags::HAS_SUBNODES)
     }
 
     /// Returns the allocated size of this NTFS Index Record, in bytes.
@@ -120,17 +163,55 @@ impl NtfsIndexRecord {
         LittleEndian::read_u32(&self.record.data()[start..])
     }
 
+    /// Returns the size actually used by index data within this NTFS Index Record, in bytes.
+    ///
+    /// This is the same as [`NtfsIndexRecord::index_data_size`].
+    pub fn index_used_size(&self) -> u32 {
+        self.index_data_size()
+    }
+
     pub(crate) fn index_entries_offset(&self) -> u32 {
         let start = INDEX_RECORD_HEADER_SIZE as usize + offset_of!(IndexNodeHeader, entries_offset);
         LittleEndian::read_u32(&self.record.data()[start..])
     }
 
-    pub(crate) fn into_entry_ranges<E>(self) -> IndexNodeEntryRanges<E>
+    pub(crate) fn into_entry_ranges<E>(self) -> IndexNodeEntryRanges<'static, E>
     where
         E: NtfsIndexEntryType,
     {
         let (entries_range, position) = self.entries_range_and_position();
-        IndexNodeEntryRanges::new(self.record.into_data(), entries_range, position)
+        let data = NodeData::Owned(Arc::new(self.record.into_data()));
+        IndexNodeEntryRanges::new(data, entries_range, position)
+    }
+
+    /// Returns the $LogFile Sequence Number (LSN) of the last logged change to this NTFS Index Record.
+    pub fn lsn(&self) -> u64 {
+        self.record.logfile_sequence_number()
+    }
+
+    /// Returns the absolute position of this NTFS Index Record within the filesystem, in bytes.
+    pub fn position(&self) -> NtfsPosition {
+        self.record.position()
+    }
+
+    /// Returns the absolute position of the byte at `offset` of this NTFS Index Record within the filesystem.
+    ///
+    /// An Index Record may straddle two Data Runs, in which case its 512-byte blocks are not stored contiguously.
+    /// This function takes that into account, whereas adding `offset` to [`NtfsIndexRecord::position`] doesn't.
+    /// The returned position is `None` if `offset` is beyond the record or lies in a sparse Data Run.
+    pub fn position_at(&self, offset: u32) -> NtfsPosition {
+        self.record.position_at(offset as usize)
+    }
+
+    /// Returns the unused bytes of this NTFS Index Record (after fixup), between the used size
+    /// ([`NtfsIndexRecord::index_data_size`]) and the allocated size ([`NtfsIndexRecord::index_allocated_size`]).
+    ///
+    /// NTFS doesn't clear this area when Index Entries are removed or moved to another Index Record.
+    /// Hence, it may still contain entries of deleted files, which is of interest for forensic analysis.
+    pub fn slack(&self) -> &[u8] {
+        let start = INDEX_RECORD_HEADER_SIZE as usize + self.index_data_size() as usize;
+        let end = INDEX_RECORD_HEADER_SIZE as usize + self.index_allocated_size() as usize;
+        &self.record.data()[start..end]
     }
 
     fn validate_signature(record: &Record) -> Result<()> {
@@ -151,10 +232,11 @@ impl NtfsIndexRecord {
     fn validate_sizes(&self) -> Result<()> {
         let index_record_size = self.record.len();
 
-        // The total size allocated for this Index Record must not be larger than
-        // the size defined for all index records of this index.
+        // The total size allocated for this Index Record must match the size defined for all index records
+        // of this index.
+        // A mismatch means that the record has been written for a different Index Record size.
         let total_allocated_size = INDEX_RECORD_HEADER_SIZE + self.index_allocated_size();
-        if total_allocated_size > index_record_size {
+        if total_allocated_size != index_record_size {
             return Err(NtfsError::InvalidIndexAllocatedSize {
                 position: self.record.position(),
                 expected: index_record_size,
@@ -176,6 +258,15 @@ impl NtfsIndexRecord {
         Ok(())
     }
 
+    /// Returns the Update Sequence Number (USN) of this NTFS Index Record.
+    ///
+    /// See [`NtfsFile::update_sequence_number`] for details.
+    ///
+    /// [`NtfsFile::update_sequence_number`]: crate::NtfsFile::update_sequence_number
+    pub fn update_sequence_number(&self) -> u16 {
+        self.record.fixed_up_update_sequence_number()
+    }
+
     /// Returns the Virtual Cluster Number (VCN) of this Index Record, as reported by the header of this Index Record.
     ///
     /// This can be used to double-check that an Index Record is the actually requested one.
@@ -187,3 +278,22 @@ impl NtfsIndexRecord {
         Vcn::from(LittleEndian::read_i64(&self.record.data()[start..]))
     }
 }
+
+/// Checks that `index_record_size` is a power of two between 512 and 65536 bytes.
+///
+/// This prevents zero-length reads and huge allocations when reading Index Records with a bogus size.
+pub(crate) fn validate_index_record_size(
+    position: NtfsPosition,
+    index_record_size: u32,
+) -> Result<()> {
+    if !index_record_size.is_power_of_two()
+        || !(MIN_INDEX_RECORD_SIZE..=MAX_INDEX_RECORD_SIZE).contains(&index_record_size)
+    {
+        return Err(NtfsError::InvalidIndexRecordSize {
+            position,
+            size: index_record_size,
+        });
+    }
+
+    Ok(())
+}
@@ -1,15 +1,49 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use binrw::io::{Read, Seek};
+use core::cmp::Ordering;
 
+use crate::io::{Read, Seek};
+
+#[cfg(feature = "async")]
+use crate::async_io::{AsyncBlockCache, AsyncNtfsReadSeek};
 use crate::error::Result;
-use crate::index::NtfsIndexFinder;
+use crate::index::{
+    IndexSource, NtfsIndex, NtfsIndexEntries, NtfsIndexFinder, NtfsIndexRange, NtfsOwnedIndex,
+};
 use crate::index_entry::NtfsIndexEntry;
 use crate::indexes::{NtfsIndexEntryHasFileReference, NtfsIndexEntryType};
 use crate::ntfs::Ntfs;
-use crate::structured_values::NtfsFileName;
-use crate::upcase_table::UpcaseOrd;
+use crate::structured_values::{NtfsCollationRule, NtfsFileName, NtfsFileNamespace};
+
+/// Determines how [`NtfsFileNameIndex::find_with_collation`] compares file names.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NtfsCaseSensitivity {
+    /// Names are compared case-insensitively based on the filesystem's $UpCase table.
+    ///
+    /// This is how Windows looks up files by default.
+    Insensitive,
+    /// Names are compared by their UTF-16 code units.
+    ///
+    /// This is required for directories with names that only differ in case, which are created
+    /// by POSIX applications (e.g. in case-sensitive directories of the Windows Subsystem for Linux).
+    Sensitive,
+}
+
+/// Determines which entries [`NtfsIndex::entries_deduplicated`] returns.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NtfsFileNameFilter {
+    /// Return all entries, including a separate entry for the DOS name of a file.
+    All,
+    /// Skip entries of the [`NtfsFileNamespace::Dos`] namespace.
+    ///
+    /// Windows creates a separate entry for the 8.3 short name (e.g. `PROGRA~1`) of each file whose long name
+    /// is not a valid 8.3 name.
+    /// The long name of such a file has the [`NtfsFileNamespace::Win32`] namespace and also has an entry in the index.
+    /// Files whose long name is a valid 8.3 name only have a single entry of the [`NtfsFileNamespace::Win32AndDos`] namespace.
+    /// Hence, skipping all DOS entries returns each name of a file exactly once.
+    SkipDosNames,
+}
 
 /// Defines the [`NtfsIndexEntryType`] for filename indexes (commonly known as "directories").
 #[derive(Clone, Copy, Debug)]
@@ -19,9 +53,14 @@ impl NtfsFileNameIndex {
     /// Finds a file in a filename index by name and returns the [`NtfsIndexEntry`] (if any).
     /// The name is compared case-insensitively based on the filesystem's $UpCase table.
     ///
-    /// # Panics
+    /// If multiple entries match (e.g. the DOS name `FOO.TXT` and the long name `foo.txt`), the first one
+    /// in collation order is returned.
+    /// Use [`NtfsFileNameIndex::find_all`] to get all of them.
+    ///
+    /// This is the same as calling [`NtfsFileNameIndex::find_with_collation`] with [`NtfsCaseSensitivity::Insensitive`].
     ///
-    /// Panics if [`read_upcase_table`][Ntfs::read_upcase_table] had not been called on the passed [`Ntfs`] object.
+    /// Returns [`NtfsError::UpcaseTableNotLoaded`](crate::NtfsError::UpcaseTableNotLoaded) if [`read_upcase_table`][Ntfs::read_upcase_table] had not been
+    /// called on the passed [`Ntfs`] object.
     pub fn find<'a, T>(
         index_finder: &'a mut NtfsIndexFinder<Self>,
         ntfs: &Ntfs,
@@ -31,14 +70,474 @@ impl NtfsFileNameIndex {
     where
         T: Read + Seek,
     {
-        // TODO: This always performs a case-insensitive comparison.
-        // There are some corner cases where NTFS uses case-sensitive filenames. These need to be considered!
-        index_finder.find(fs, |file_name| name.upcase_cmp(ntfs, &file_name.name()))
+        Self::find_with_collation(
+            index_finder,
+            ntfs,
+            fs,
+            name,
+            NtfsCaseSensitivity::Insensitive,
+        )
+    }
+
+    /// Async variant of [`NtfsFileNameIndex::find`] for readers implementing [`AsyncNtfsReadSeek`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn find_async<'a, T>(
+        index_finder: &'a mut NtfsIndexFinder<Self>,
+        ntfs: &Ntfs,
+        fs: &mut T,
+        name: &str,
+    ) -> Option<Result<NtfsIndexEntry<'a, Self>>>
+    where
+        T: AsyncNtfsReadSeek,
+    {
+        let mut cache = iter_try!(AsyncBlockCache::new(fs).await);
+
+        // Fetch all blocks the lookup needs, and then look up the entry once more to return it,
+        // because it borrows the finder.
+        iter_try!(
+            cache
+                .run(|fs| match Self::find(index_finder, ntfs, fs, name) {
+                    Some(Err(e)) => Err(e),
+                    _ => Ok(()),
+                })
+                .await
+        );
+        Self::find(index_finder, ntfs, &mut cache.reader(), name)
+    }
+
+    /// Finds all entries of a filename index whose names are equal to `name` when compared case-insensitively
+    /// based on the filesystem's $UpCase table, and returns an [`NtfsIndexRange`] iterating over them.
+    ///
+    /// Filename indexes are sorted by the NTFS filename collation (see [`NtfsFileNameIndex::find_with_collation`]),
+    /// so all these entries are adjacent in the index and returned in collation order.
+    /// This is useful to tell apart a DOS name and a long name that only differ in case
+    /// (check [`NtfsFileName::namespace`] of each entry), or to find names that have been created
+    /// by POSIX applications and only differ in case.
+    ///
+    /// Returns [`NtfsError::UpcaseTableNotLoaded`](crate::NtfsError::UpcaseTableNotLoaded) if [`read_upcase_table`][Ntfs::read_upcase_table] had not been
+    /// called on the passed [`Ntfs`] object.
+    pub fn find_all<'n, 'f, 'i, 'a, T>(
+        index_finder: &'a mut NtfsIndexFinder<'n, 'f, 'i, Self>,
+        ntfs: &'a Ntfs,
+        fs: &mut T,
+        name: &'a str,
+    ) -> Result<NtfsIndexRange<'n, 'f, 'i, 'a, Self, impl FnMut(&NtfsFileName) -> bool + 'a>>
+    where
+        T: Read + Seek,
+    {
+        let upcase_table = ntfs.upcase_table_or_error()?;
+
+        index_finder.find_range(
+            fs,
+            |file_name| {
+                upcase_table.cmp_code_units(name.encode_utf16(), file_name.name().u16_iter())
+            },
+            move |file_name| {
+                upcase_table.cmp_code_units(name.encode_utf16(), file_name.name().u16_iter())
+                    == Ordering::Equal
+            },
+        )
+    }
+
+    /// Finds a file in a filename index by name and returns the [`NtfsIndexEntry`] (if any).
+    /// The name is compared as specified by `case_sensitivity`.
+    ///
+    /// Filename indexes are sorted by the NTFS filename collation:
+    /// Names are first compared case-insensitively based on the filesystem's $UpCase table.
+    /// Only names that are equal in that comparison are further ordered by their UTF-16 code units.
+    /// Hence, names differing only in case are adjacent entries in the index.
+    ///
+    /// A [`NtfsCaseSensitivity::Insensitive`] lookup returns the first of these entries in collation order.
+    /// A [`NtfsCaseSensitivity::Sensitive`] lookup applies the full collation to find the one entry
+    /// that exactly matches the given name, and returns `None` if only entries differing in case exist.
+    ///
+    /// Returns [`NtfsError::UpcaseTableNotLoaded`](crate::NtfsError::UpcaseTableNotLoaded) if [`read_upcase_table`][Ntfs::read_upcase_table] had not been
+    /// called on the passed [`Ntfs`] object.
+    /// This also applies to case-sensitive lookups, because the index is primarily sorted case-insensitively.
+    pub fn find_with_collation<'a, T>(
+        index_finder: &'a mut NtfsIndexFinder<Self>,
+        ntfs: &Ntfs,
+        fs: &mut T,
+        name: &str,
+        case_sensitivity: NtfsCaseSensitivity,
+    ) -> Option<Result<NtfsIndexEntry<'a, Self>>>
+    where
+        T: Read + Seek,
+    {
+        let upcase_table = iter_try!(ntfs.upcase_table_or_error());
+
+        match case_sensitivity {
+            NtfsCaseSensitivity::Insensitive => {
+                // Multiple entries may match, but a B-tree search may encounter a later one first
+                // (in a parent node of an earlier one).
+                // Hence, position before the first match in collation order and check the entry following it.
+                iter_try!(index_finder.seek_to(fs, |file_name| {
+                    upcase_table.cmp_code_units(name.encode_utf16(), file_name.name().u16_iter())
+                }));
+                let entry = iter_try!(index_finder.next(fs)?);
+                let file_name = iter_try!(entry.key()?);
+
+                if upcase_table.cmp_code_units(name.encode_utf16(), file_name.name().u16_iter())
+                    == Ordering::Equal
+                {
+                    Some(Ok(entry))
+                } else {
+                    None
+                }
+            }
+            NtfsCaseSensitivity::Sensitive => index_finder.find(fs, |file_name| {
+                let file_name = file_name.name();
+
+                upcase_table
+                    .cmp_code_units(name.encode_utf16(), file_name.u16_iter())
+                    .then_with(|| name.encode_utf16().cmp(file_name.u16_iter()))
+            }),
+        }
+    }
+}
+
+/// Filter for [`NtfsFileNameFilter::SkipDosNames`].
+fn skip_dos_names(entry: &NtfsIndexEntry<NtfsFileNameIndex>) -> bool {
+    match entry.key() {
+        Some(Ok(file_name)) => file_name.namespace() != NtfsFileNamespace::Dos,
+        // Let the caller see any errors.
+        _ => true,
+    }
+}
+
+impl<'n, 'f> NtfsIndex<'n, 'f, NtfsFileNameIndex> {
+    /// Returns an [`NtfsIndexEntries`] iterator to perform an in-order traversal of this filename index,
+    /// returning entries as specified by `entries`.
+    ///
+    /// Use [`NtfsFileNameFilter::SkipDosNames`] to get a directory listing like the one of Windows Explorer,
+    /// which doesn't list the DOS names of files separately.
+    pub fn entries_deduplicated<'i>(
+        &'i self,
+        entries: NtfsFileNameFilter,
+    ) -> NtfsIndexEntries<'n, 'f, 'i, NtfsFileNameIndex> {
+        match entries {
+            NtfsFileNameFilter::All => self.entries(),
+            NtfsFileNameFilter::SkipDosNames => {
+                NtfsIndexEntries::with_filter(IndexSource::Borrowed(self), skip_dos_names)
+            }
+        }
+    }
+}
+
+impl<'n> NtfsOwnedIndex<'n, NtfsFileNameIndex> {
+    /// Returns an [`NtfsIndexEntries`] iterator to perform an in-order traversal of this filename index,
+    /// returning entries as specified by `entries`.
+    ///
+    /// See [`NtfsIndex::entries_deduplicated`].
+    pub fn entries_deduplicated<'i>(
+        &'i self,
+        entries: NtfsFileNameFilter,
+    ) -> NtfsIndexEntries<'n, 'i, 'i, NtfsFileNameIndex> {
+        match entries {
+            NtfsFileNameFilter::All => self.entries(),
+            NtfsFileNameFilter::SkipDosNames => {
+                NtfsIndexEntries::with_filter(IndexSource::Owned(self), skip_dos_names)
+            }
+        }
     }
 }
 
 impl NtfsIndexEntryType for NtfsFileNameIndex {
     type KeyType = NtfsFileName;
+
+    const COLLATION: Option<NtfsCollationRule> = Some(NtfsCollationRule::FileName);
+}
+
+impl NtfsIndexEntryHasFileReference for NtfsFileNameIndex {
+    fn key_is_directory(key: &NtfsFileName) -> Option<bool> {
+        Some(key.is_directory())
+    }
 }
 
-impl NtfsIndexEntryHasFileReference for NtfsFileNameIndex {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::NtfsPosition;
+
+    /// Renames the index entries "98" and "99" of the "many_subdirs" directory of testfs1 to "AB" and "ab".
+    ///
+    /// These are the last two entries of that index, so the index stays sorted according to the
+    /// NTFS filename collation.
+    fn testfs1_with_case_differing_names() -> std::io::Cursor<Vec<u8>> {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let name_positions = many_subdirs_entry_positions(&mut testfs1, &["98", "99"])
+            .into_iter()
+            .map(name_position)
+            .collect::<Vec<_>>();
+        let data = testfs1.get_mut();
+
+        for (position, new_name) in name_positions.into_iter().zip(["AB", "ab"]) {
+            // The last two bytes of each sector are replaced by the Update Sequence Number on disk.
+            // Make sure that we don't have to deal with that.
+            assert!(position % 512 + 4 <= 510);
+
+            let bytes = new_name
+                .encode_utf16()
+                .flat_map(|c| c.to_le_bytes())
+                .collect::<Vec<u8>>();
+            data[position..position + 4].copy_from_slice(&bytes);
+        }
+
+        testfs1
+    }
+
+    /// Turns the index entry "98" of the "many_subdirs" directory of testfs1 into a DOS name
+    /// and the index entry "99" into a Win32 name, as if "99" was the long name of the file called "98".
+    fn testfs1_with_dos_name() -> std::io::Cursor<Vec<u8>> {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        set_many_subdirs_namespaces(
+            &mut testfs1,
+            &["98", "99"],
+            &[NtfsFileNamespace::Dos, NtfsFileNamespace::Win32],
+        );
+        testfs1
+    }
+
+    /// Renames the index entries "98" and "99" of the "many_subdirs" directory of testfs1 to "AB" and "ab",
+    /// and turns "AB" into a DOS name and "ab" into a Win32 name, as if "AB" was the short name of the file "ab".
+    fn testfs1_with_colliding_dos_name() -> std::io::Cursor<Vec<u8>> {
+        let mut testfs1 = testfs1_with_case_differing_names();
+        set_many_subdirs_namespaces(
+            &mut testfs1,
+            &["AB", "ab"],
+            &[NtfsFileNamespace::Dos, NtfsFileNamespace::Win32],
+        );
+        testfs1
+    }
+
+    /// Sets the namespaces of the index entries with the given names in the "many_subdirs" directory
+    /// of testfs1, in index order.
+    fn set_many_subdirs_namespaces(
+        testfs1: &mut std::io::Cursor<Vec<u8>>,
+        names: &[&str],
+        namespaces: &[NtfsFileNamespace],
+    ) {
+        let entry_positions = many_subdirs_entry_positions(testfs1, names);
+        let data = testfs1.get_mut();
+
+        for (entry_position, namespace) in entry_positions.into_iter().zip(namespaces) {
+            // The namespace byte directly precedes the name.
+            // It must not be one of the last two bytes of a sector, which are replaced by the
+            // Update Sequence Number on disk.
+            let position = name_position(entry_position) - 1;
+            assert!(position % 512 < 510);
+            data[position] = *namespace as u8;
+        }
+    }
+
+    /// Returns the positions of the index entries with the given names in the "many_subdirs" directory
+    /// of testfs1, in index order.
+    fn many_subdirs_entry_positions(
+        testfs1: &mut std::io::Cursor<Vec<u8>>,
+        names: &[&str],
+    ) -> Vec<NtfsPosition> {
+        let mut entry_positions = Vec::new();
+        {
+            let mut ntfs = Ntfs::new(testfs1).unwrap();
+            ntfs.read_upcase_table(testfs1).unwrap();
+            let root_dir = ntfs.root_directory(testfs1).unwrap();
+            let root_dir_index = root_dir.directory_index(testfs1).unwrap();
+            let mut root_dir_finder = root_dir_index.finder();
+            let entry =
+                NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, testfs1, "many_subdirs")
+                    .unwrap()
+                    .unwrap();
+            let subdir = entry.to_file(&ntfs, testfs1).unwrap();
+            let subdir_index = subdir.directory_index(testfs1).unwrap();
+            let mut subdir_iter = subdir_index.entries();
+
+            while let Some(entry) = subdir_iter.next(testfs1) {
+                let entry = entry.unwrap();
+                let file_name = entry.key().unwrap().unwrap();
+
+                if names.iter().any(|name| file_name.name() == *name) {
+                    entry_positions.push(entry.position());
+                }
+            }
+        }
+
+        assert_eq!(entry_positions.len(), names.len());
+        entry_positions
+    }
+
+    fn name_position(entry_position: NtfsPosition) -> usize {
+        // The key of an index entry follows the 16-byte Index Entry header,
+        // and the name of a $FILE_NAME structure begins at byte 66.
+        entry_position.value().unwrap().get() as usize + 16 + 66
+    }
+
+    #[test]
+    fn test_find_with_collation() {
+        let mut testfs1 = testfs1_with_case_differing_names();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                .unwrap()
+                .unwrap();
+        let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+        let mut subdir_finder = subdir_index.finder();
+
+        // A case-sensitive lookup must return the exact match.
+        for name in ["AB", "ab"] {
+            let entry = NtfsFileNameIndex::find_with_collation(
+                &mut subdir_finder,
+                &ntfs,
+                &mut testfs1,
+                name,
+                NtfsCaseSensitivity::Sensitive,
+            )
+            .unwrap()
+            .unwrap();
+            let file_name = entry.key().unwrap().unwrap();
+            assert_eq!(file_name.name(), name);
+        }
+
+        // There is no case-exact match for "Ab" or "aB".
+        for name in ["Ab", "aB"] {
+            assert!(NtfsFileNameIndex::find_with_collation(
+                &mut subdir_finder,
+                &ntfs,
+                &mut testfs1,
+                name,
+                NtfsCaseSensitivity::Sensitive,
+            )
+            .is_none());
+        }
+
+        // A case-insensitive lookup returns the first of both entries in collation order.
+        let entry = NtfsFileNameIndex::find(&mut subdir_finder, &ntfs, &mut testfs1, "Ab")
+            .unwrap()
+            .unwrap();
+        let file_name = entry.key().unwrap().unwrap();
+        assert_eq!(file_name.name(), "AB");
+
+        // Names without any case differences are found in both modes.
+        for case_sensitivity in [
+            NtfsCaseSensitivity::Insensitive,
+            NtfsCaseSensitivity::Sensitive,
+        ] {
+            let entry = NtfsFileNameIndex::find_with_collation(
+                &mut subdir_finder,
+                &ntfs,
+                &mut testfs1,
+                "97",
+                case_sensitivity,
+            )
+            .unwrap()
+            .unwrap();
+            let file_name = entry.key().unwrap().unwrap();
+            assert_eq!(file_name.name(), "97");
+        }
+    }
+
+    #[test]
+    fn test_entries_deduplicated() {
+        let mut testfs1 = testfs1_with_dos_name();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                .unwrap()
+                .unwrap();
+        let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+
+        let mut collect_names = |filter| {
+            let mut iter = subdir_index.entries_deduplicated(filter);
+            let mut names = Vec::new();
+
+            while let Some(entry) = iter.next(&mut testfs1) {
+                let entry = entry.unwrap();
+                let file_name = entry.key().unwrap().unwrap();
+                names.push((file_name.name().to_string_lossy(), file_name.namespace()));
+            }
+
+            names
+        };
+
+        // All entries are returned by default, including the DOS name "98".
+        let all_names = collect_names(NtfsFileNameFilter::All);
+        assert_eq!(all_names.len(), 512);
+        assert!(all_names.contains(&("98".to_string(), NtfsFileNamespace::Dos)));
+        assert!(all_names.contains(&("99".to_string(), NtfsFileNamespace::Win32)));
+
+        // Skipping DOS names only removes "98".
+        let deduplicated_names = collect_names(NtfsFileNameFilter::SkipDosNames);
+        assert_eq!(deduplicated_names.len(), 511);
+        assert!(!deduplicated_names
+            .iter()
+            .any(|(_, namespace)| *namespace == NtfsFileNamespace::Dos));
+
+        let expected_names = all_names
+            .into_iter()
+            .filter(|(name, _)| name != "98")
+            .collect::<Vec<_>>();
+        assert_eq!(deduplicated_names, expected_names);
+    }
+
+    #[test]
+    fn test_find_all() {
+        let mut testfs1 = testfs1_with_colliding_dos_name();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                .unwrap()
+                .unwrap();
+        let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+        let mut subdir_finder = subdir_index.finder();
+
+        let mut find_all = |name| {
+            let mut range =
+                NtfsFileNameIndex::find_all(&mut subdir_finder, &ntfs, &mut testfs1, name).unwrap();
+            let mut names = Vec::new();
+
+            while let Some(entry) = range.next(&mut testfs1) {
+                let file_name = entry.unwrap().key().unwrap().unwrap();
+                names.push((file_name.name().to_string_lossy(), file_name.namespace()));
+            }
+
+            names
+        };
+
+        // Both the DOS name and the long name are returned in collation order, regardless of the case of the query.
+        let expected_names = [
+            ("AB".to_string(), NtfsFileNamespace::Dos),
+            ("ab".to_string(), NtfsFileNamespace::Win32),
+        ];
+        assert_eq!(find_all("ab"), expected_names);
+        assert_eq!(find_all("aB"), expected_names);
+
+        // Names without any colliding entry only return a single entry.
+        assert_eq!(
+            find_all("97"),
+            [("97".to_string(), NtfsFileNamespace::Posix)]
+        );
+
+        // There are no entries for names that don't exist, even if the neighboring entries share a prefix.
+        assert!(find_all("A").is_empty());
+        assert!(find_all("ABC").is_empty());
+        assert!(find_all("zzz").is_empty());
+    }
+}
@@ -15,12 +15,15 @@
 //! [`NtfsIndexRoot`]: crate::structured_values::NtfsIndexRoot
 
 mod file_name;
+mod raw;
 
 pub use file_name::*;
+pub use raw::*;
 
 use core::fmt;
 
 use crate::error::Result;
+use crate::structured_values::NtfsCollationRule;
 use crate::types::NtfsPosition;
 
 /// Trait implemented by structures that describe Index Entry types.
@@ -32,6 +35,16 @@ use crate::types::NtfsPosition;
 /// [`NtfsIndexEntry`]: crate::NtfsIndexEntry
 pub trait NtfsIndexEntryType: Clone + fmt::Debug {
     type KeyType: NtfsIndexEntryKey;
+
+    /// The collation rule of indexes holding entries of this type.
+    ///
+    /// [`NtfsIndex::new`] checks it against the collation rule of the [`NtfsIndexRoot`] to ensure that keys
+    /// are not parsed as the wrong type.
+    /// `None` skips this check, which is useful for types that work with any index.
+    ///
+    /// [`NtfsIndex::new`]: crate::NtfsIndex::new
+    /// [`NtfsIndexRoot`]: crate::structured_values::NtfsIndexRoot
+    const COLLATION: Option<NtfsCollationRule> = None;
 }
 
 /// Trait implemented by a structure that describes an Index Entry key.
@@ -56,4 +69,16 @@ pub trait NtfsIndexEntryData: fmt::Debug + Sized {
 ///
 /// This trait and [`NtfsIndexEntryHasData`] are mutually exclusive.
 // TODO: Use negative trait bounds of future Rust to enforce mutual exclusion.
-pub trait NtfsIndexEntryHasFileReference: NtfsIndexEntryType {}
+pub trait NtfsIndexEntryHasFileReference: NtfsIndexEntryType {
+    /// Returns whether the given key claims that the referenced file is a directory,
+    /// or `None` if the key has no such information.
+    ///
+    /// [`NtfsIndexEntry::to_file`] records this to tell apart corrupted directory flags from a
+    /// wrong file passed to [`NtfsFile::directory_index`].
+    ///
+    /// [`NtfsFile::directory_index`]: crate::NtfsFile::directory_index
+    /// [`NtfsIndexEntry::to_file`]: crate::NtfsIndexEntry::to_file
+    fn key_is_directory(_key: &Self::KeyType) -> Option<bool> {
+        None
+    }
+}
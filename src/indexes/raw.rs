@@ -0,0 +1,48 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::vec::Vec;
+
+use crate::error::Result;
+use crate::indexes::{
+    NtfsIndexEntryData, NtfsIndexEntryHasData, NtfsIndexEntryKey, NtfsIndexEntryType,
+};
+use crate::types::NtfsPosition;
+
+/// Defines an [`NtfsIndexEntryType`] that returns keys and data of any index as raw bytes.
+///
+/// This is useful for indexes whose key and data types are not known to this crate, e.g. custom
+/// indexes created by third-party drivers.
+/// You can get such an index via [`NtfsFile::index`] and decode keys and data yourself.
+/// To find an entry, pass a comparison function working on the raw key bytes to [`NtfsIndexFinder::find`].
+///
+/// Note that the data of an Index Entry is only meaningful for indexes that store data
+/// (like $SII, $SDH, $O, $Q, or $R).
+/// Entries of filename indexes ($I30) store a file reference instead, which is not returned here.
+/// Use [`NtfsFileNameIndex`] for these indexes.
+///
+/// [`NtfsFile::index`]: crate::NtfsFile::index
+/// [`NtfsFileNameIndex`]: crate::indexes::NtfsFileNameIndex
+/// [`NtfsIndexFinder::find`]: crate::NtfsIndexFinder::find
+#[derive(Clone, Copy, Debug)]
+pub struct NtfsRawIndex;
+
+impl NtfsIndexEntryType for NtfsRawIndex {
+    type KeyType = Vec<u8>;
+}
+
+impl NtfsIndexEntryHasData for NtfsRawIndex {
+    type DataType = Vec<u8>;
+}
+
+impl NtfsIndexEntryKey for Vec<u8> {
+    fn key_from_slice(slice: &[u8], _position: NtfsPosition) -> Result<Self> {
+        Ok(slice.to_vec())
+    }
+}
+
+impl NtfsIndexEntryData for Vec<u8> {
+    fn data_from_slice(slice: &[u8], _position: NtfsPosition) -> Result<Self> {
+        Ok(slice.to_vec())
+    }
+}
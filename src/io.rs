@@ -0,0 +1,77 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+//! I/O traits and types used in the public API of this crate.
+//!
+//! All functions reading from the filesystem take a reader implementing the [`Read`] and [`Seek`] traits
+//! of this module, and all I/O errors are reported as [`Error`] (wrapped in [`NtfsError::Io`]).
+//!
+//! With the `std` feature enabled (the default), these are simply the types of [`std::io`].
+//! Hence, every [`std::io::Read`] + [`std::io::Seek`] implementor (like [`std::fs::File`]) can be passed directly.
+//!
+//! Without the `std` feature, a compatible subset of these types is provided instead.
+//! To use this crate on a custom block device in a `no_std` environment, implement [`Read`] and [`Seek`]
+//! of this module for it.
+//!
+//! [`NtfsError::Io`]: crate::NtfsError::Io
+
+pub use binrw::io::{Cursor, Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ntfs::Ntfs;
+    use crate::traits::NtfsReadSeek;
+
+    /// Minimal reader over a byte slice, implemented only in terms of this module (like a `no_std` block device).
+    struct SliceReader<'a> {
+        data: &'a [u8],
+        position: u64,
+    }
+
+    impl<'a> Read for SliceReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let start = (self.position as usize).min(self.data.len());
+            let remaining = &self.data[start..];
+            let bytes_to_read = buf.len().min(remaining.len());
+
+            buf[..bytes_to_read].copy_from_slice(&remaining[..bytes_to_read]);
+            self.position += bytes_to_read as u64;
+            Ok(bytes_to_read)
+        }
+    }
+
+    impl<'a> Seek for SliceReader<'a> {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+            let new_position = match pos {
+                SeekFrom::Start(n) => Some(n),
+                SeekFrom::End(n) => (self.data.len() as i64).checked_add(n).map(|n| n as u64),
+                SeekFrom::Current(n) => (self.position as i64).checked_add(n).map(|n| n as u64),
+            };
+
+            self.position = new_position
+                .filter(|n| (*n as i64) >= 0)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid seek position"))?;
+            Ok(self.position)
+        }
+    }
+
+    #[test]
+    fn test_custom_reader() {
+        let testfs1 = crate::helpers::tests::testfs1().into_inner();
+        let mut fs = SliceReader {
+            data: &testfs1,
+            position: 0,
+        };
+
+        let ntfs = Ntfs::new(&mut fs).unwrap();
+        let file = ntfs.file(&mut fs, 65).unwrap();
+        let data_item = file.data(&mut fs, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut fs).unwrap();
+
+        let mut buf = [0u8; 5];
+        data_value.read_exact(&mut fs, &mut buf).unwrap();
+        assert_eq!(&buf, b"12345");
+    }
+}
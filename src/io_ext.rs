@@ -0,0 +1,364 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+//! Helpers for readers that need special treatment before they can be passed to this crate.
+
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+
+/// `SectorReader` encapsulates any reader and only performs read and seek operations on it
+/// on boundaries of the given sector size.
+///
+/// This is required for readers that only accept sector-aligned accesses, like a raw partition on Windows
+/// (opened via a path like `\\.\C:`).
+///
+/// `SectorReader` guarantees that:
+///   * Every read from the inner reader starts at a sector boundary and requests a multiple of the sector size.
+///   * Every seek of the inner reader via [`SeekFrom::Start`] goes to a sector boundary.
+///     The inner reader is only seeked when it is not already at the required position.
+///   * Short reads of the inner reader are continued until the requested sectors have been read or the
+///     inner reader reports its end (by returning zero bytes or a partial sector).
+///     Hence, the length of the inner reader doesn't need to be a multiple of the sector size:
+///     Reading its last partial sector returns the remaining bytes and any further read returns zero bytes.
+///
+/// [`SeekFrom::End`] is supported by seeking the inner reader to its end once to determine its length.
+/// This is the only seek of the inner reader that doesn't go to a sector boundary, and it doesn't involve
+/// a read.
+///
+/// This reader does not keep any buffer.
+/// You are advised to encapsulate `SectorReader` in a [`BufReader`](std::io::BufReader),
+/// as unbuffered reads of just a few bytes here and there are highly inefficient.
+/// A `BufReader` with a capacity that is a multiple of the sector size (like the default of 8 KiB)
+/// only performs sector-aligned reads anyway, which `SectorReader` then passes through without copying
+/// more data than necessary.
+///
+/// # Example
+///
+/// ```
+/// # use std::fs::File;
+/// # use std::io::BufReader;
+/// # use ntfs::io_ext::SectorReader;
+/// # use ntfs::Ntfs;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let f = File::open("testdata/testfs1")?;
+/// let sr = SectorReader::new(f, 4096)?;
+/// let mut fs = BufReader::new(sr);
+/// let ntfs = Ntfs::new(&mut fs)?;
+/// # assert_eq!(ntfs.cluster_size(), 512);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SectorReader<R>
+where
+    R: Read + Seek,
+{
+    /// The inner reader stream.
+    inner: R,
+    /// The sector size set at creation.
+    sector_size: usize,
+    /// The current stream position as requested by the caller through `read` or `seek`.
+    /// The implementation will internally make sure to only read/seek on sector boundaries.
+    stream_position: u64,
+    /// The position of the inner reader, if it is known and on a sector boundary.
+    inner_position: Option<u64>,
+    /// The length of the inner reader, determined by the first seek via [`SeekFrom::End`].
+    len: Option<u64>,
+    /// This buffer is only part of the struct as a small performance optimization (keeping it allocated between reads).
+    temp_buf: Vec<u8>,
+}
+
+impl<R> SectorReader<R>
+where
+    R: Read + Seek,
+{
+    /// Creates a new `SectorReader` that accesses `inner` in units of `sector_size` bytes.
+    ///
+    /// The sector size must be a power of two.
+    /// The stream position starts at 0, independent of the current position of `inner`.
+    pub fn new(inner: R, sector_size: usize) -> io::Result<Self> {
+        if !sector_size.is_power_of_two() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "sector_size is not a power of two",
+            ));
+        }
+
+        Ok(Self {
+            inner,
+            sector_size,
+            stream_position: 0,
+            inner_position: None,
+            len: None,
+            temp_buf: Vec::new(),
+        })
+    }
+
+    fn align_down_to_sector_size(&self, n: u64) -> u64 {
+        n / self.sector_size as u64 * self.sector_size as u64
+    }
+
+    fn align_up_to_sector_size(&self, n: u64) -> u64 {
+        self.align_down_to_sector_size(n + self.sector_size as u64 - 1)
+    }
+
+    /// Returns a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner reader.
+    ///
+    /// The inner reader may be used freely, as `SectorReader` seeks it again before the next read.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.inner_position = None;
+        &mut self.inner
+    }
+
+    /// Consumes this `SectorReader` and returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Returns the sector size set at creation.
+    pub fn sector_size(&self) -> usize {
+        self.sector_size
+    }
+}
+
+impl<R> Read for SectorReader<R>
+where
+    R: Read + Seek,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // We can only read from a sector boundary, and `self.stream_position` specifies the position where the
+        // caller thinks we are.
+        // Align down to a sector boundary to determine the position where we have to start reading.
+        let aligned_position = self.align_down_to_sector_size(self.stream_position);
+        if self.inner_position != Some(aligned_position) {
+            self.inner.seek(SeekFrom::Start(aligned_position))?;
+            self.inner_position = Some(aligned_position);
+        }
+
+        // We have to read more bytes now to make up for the alignment difference.
+        // We can also only read in multiples of the sector size, so align up to the next sector boundary.
+        let start = (self.stream_position - aligned_position) as usize;
+        let end = start + buf.len();
+        let aligned_bytes_to_read = self.align_up_to_sector_size(end as u64) as usize;
+
+        // Perform the sector-sized read, continuing after short reads until the inner reader reports its end.
+        self.temp_buf.resize(aligned_bytes_to_read, 0);
+        let mut bytes_read = 0;
+
+        while bytes_read < aligned_bytes_to_read {
+            match self.inner.read(&mut self.temp_buf[bytes_read..]) {
+                Ok(0) => break,
+                Ok(n) => {
+                    bytes_read += n;
+
+                    // A partial sector can only be read at the end of the inner reader.
+                    // Don't continue reading from a position that is not on a sector boundary.
+                    if bytes_read % self.sector_size != 0 {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.inner_position = None;
+                    return Err(e);
+                }
+            }
+        }
+
+        // Forget the position after reading a partial sector to seek again before the next read.
+        self.inner_position = if bytes_read % self.sector_size == 0 {
+            Some(aligned_position + bytes_read as u64)
+        } else {
+            None
+        };
+
+        // Copy the actually requested bytes (as far as available) into the given buffer.
+        let end = usize::min(end, bytes_read);
+        let bytes_copied = end.saturating_sub(start);
+        buf[..bytes_copied].copy_from_slice(&self.temp_buf[start..start + bytes_copied]);
+
+        self.stream_position += bytes_copied as u64;
+        Ok(bytes_copied)
+    }
+}
+
+impl<R> Seek for SectorReader<R>
+where
+    R: Read + Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => {
+                let len = match self.len {
+                    Some(len) => len,
+                    None => {
+                        let len = self.inner.seek(SeekFrom::End(0))?;
+                        self.inner_position = None;
+                        self.len = Some(len);
+                        len
+                    }
+                };
+
+                if n >= 0 {
+                    len.checked_add(n as u64)
+                } else {
+                    len.checked_sub(n.wrapping_neg() as u64)
+                }
+            }
+            SeekFrom::Current(n) => {
+                if n >= 0 {
+                    self.stream_position.checked_add(n as u64)
+                } else {
+                    self.stream_position.checked_sub(n.wrapping_neg() as u64)
+                }
+            }
+        };
+
+        match new_pos {
+            Some(n) => {
+                // Make the caller believe that we seeked to the actually requested position.
+                // Our `read` implementation will seek the inner reader to the corresponding sector boundary
+                // if necessary.
+                self.stream_position = n;
+                Ok(self.stream_position)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use super::*;
+
+    const SECTOR_SIZE: usize = 512;
+
+    /// In-memory reader that rejects every read or seek not aligned to `SECTOR_SIZE`,
+    /// and returns at most a single sector per read.
+    struct AlignedReader {
+        inner: Cursor<Vec<u8>>,
+    }
+
+    impl AlignedReader {
+        fn new(len: usize) -> Self {
+            let data = (0..len).map(|i| (i % 251) as u8).collect();
+            let inner = Cursor::new(data);
+            Self { inner }
+        }
+
+        fn data(&self) -> &[u8] {
+            self.inner.get_ref()
+        }
+    }
+
+    impl Read for AlignedReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            assert_eq!(self.inner.position() % SECTOR_SIZE as u64, 0);
+            assert_eq!(buf.len() % SECTOR_SIZE, 0);
+
+            let bytes_to_read = usize::min(buf.len(), SECTOR_SIZE);
+            self.inner.read(&mut buf[..bytes_to_read])
+        }
+    }
+
+    impl Seek for AlignedReader {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            match pos {
+                SeekFrom::Start(n) => assert_eq!(n % SECTOR_SIZE as u64, 0),
+                SeekFrom::End(n) => assert_eq!(n, 0),
+                SeekFrom::Current(_) => panic!("SectorReader must only seek via SeekFrom::Start"),
+            }
+
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_read_unaligned() {
+        // The length is not a multiple of the sector size.
+        let len = 3 * SECTOR_SIZE + 100;
+        let mut sr = SectorReader::new(AlignedReader::new(len), SECTOR_SIZE).unwrap();
+        let data = sr.get_ref().data().to_vec();
+
+        // Read in odd chunks across sector boundaries.
+        let mut output = Vec::new();
+        let mut buf = [0u8; 333];
+        loop {
+            let bytes_read = sr.read(&mut buf).unwrap();
+            if bytes_read == 0 {
+                break;
+            }
+
+            output.extend_from_slice(&buf[..bytes_read]);
+        }
+        assert_eq!(output, data);
+
+        // Seek to arbitrary positions and read from there.
+        for (pos, expected_start) in [
+            (SeekFrom::Start(1000), 1000),
+            (SeekFrom::Current(-10), 1080),
+            (SeekFrom::End(-50), len - 50),
+        ] {
+            assert_eq!(sr.seek(pos).unwrap(), expected_start as u64);
+
+            let mut buf = [0u8; 90];
+            let bytes_read = sr.read(&mut buf).unwrap();
+            let expected_end = usize::min(expected_start + buf.len(), len);
+            assert_eq!(&buf[..bytes_read], &data[expected_start..expected_end]);
+        }
+
+        // Reading beyond the end returns no data.
+        sr.seek(SeekFrom::End(10)).unwrap();
+        assert_eq!(sr.read(&mut buf).unwrap(), 0);
+
+        assert!(sr.seek(SeekFrom::Current(-(len as i64) - 11)).is_err());
+    }
+
+    #[test]
+    fn test_buf_reader() {
+        let len = 20 * SECTOR_SIZE + 7;
+        let sr = SectorReader::new(AlignedReader::new(len), SECTOR_SIZE).unwrap();
+        let data = sr.get_ref().data().to_vec();
+        let mut reader = BufReader::with_capacity(4 * SECTOR_SIZE, sr);
+
+        let mut buf = [0u8; 10];
+        reader.seek(SeekFrom::Start(3000)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, data[3000..3010]);
+
+        reader.seek(SeekFrom::Current(5000)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, data[8010..8020]);
+
+        let mut output = Vec::new();
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        reader.read_to_end(&mut output).unwrap();
+        assert_eq!(output, data);
+
+        let sr = reader.into_inner();
+        assert_eq!(sr.sector_size(), SECTOR_SIZE);
+        assert_eq!(sr.into_inner().data(), data);
+    }
+
+    #[test]
+    fn test_invalid_sector_size() {
+        for sector_size in [0, 3, 1000] {
+            assert!(SectorReader::new(Cursor::new(Vec::<u8>::new()), sector_size).is_err());
+        }
+    }
+}
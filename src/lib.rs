@@ -9,6 +9,31 @@
 //!
 //! The crate is `no_std`-compatible and therefore usable from firmware level code up to user-mode applications.
 //!
+//! # Supported configurations
+//! * **std** (default): Everything below, plus `std::error::Error` for [`NtfsError`], conversions between
+//!   [`NtfsTime`] and `std::time::SystemTime`, and conversions of NTFS strings to `OsString` on Windows.
+//! * **no_std with an allocator** (`default-features = false`): The entire filesystem functionality.
+//!   Readers implement the [`io::Read`] and [`io::Seek`] traits of this crate, which don't require `std`.
+//!   This is what firmware code (e.g. UEFI applications) uses. A global allocator must be provided.
+//! * **no_std without an allocator** is not supported, because the crate depends on the `alloc` crate for
+//!   reading File Records and Index Records.
+//!
+//! The optional `time` feature adds conversions between [`NtfsTime`] and `time::OffsetDateTime` in every configuration.
+//! Likewise, the optional `chrono` feature adds conversions between [`NtfsTime`] and `chrono::DateTime<Utc>`.
+//!
+//! The optional `serde` feature implements `serde::Serialize` and `serde::Deserialize` for plain metadata types
+//! (like [`NtfsTime`], [`NtfsFileReference`], [`NtfsPosition`], and the various flags),
+//! and adds `NtfsFileName::to_owned_metadata` for serializing file names.
+//!
+//! The optional `write` feature adds in-place modification of File Records, e.g. `NtfsFile::update_standard_information`.
+//! The reader then also needs to implement [`io::Write`].
+//!
+//! The optional `async` feature (requires `std`) adds `*_async` variants of the main entry points
+//! (like `Ntfs::new_async`, `NtfsFile::data_async`, and `NtfsAttributeValue::read_async`) for readers
+//! implementing `futures_io::AsyncRead` and `futures_io::AsyncSeek`.
+//!
+//! [`NtfsPosition`]: crate::types::NtfsPosition
+//!
 //! # Getting started
 //! 1. Create an [`Ntfs`] structure from a reader by calling [`Ntfs::new`].
 //! 2. Retrieve the [`NtfsFile`] of the root directory via [`Ntfs::root_directory`].
@@ -42,34 +67,68 @@ extern crate alloc;
 #[macro_use]
 mod helpers;
 
+#[cfg(feature = "async")]
+mod async_io;
 mod attribute;
+mod attribute_definition;
 pub mod attribute_value;
 mod boot_sector;
+mod compression;
 mod error;
 mod file;
+#[cfg(feature = "std")]
+mod file_record_cache;
 mod file_reference;
+#[cfg(feature = "fuzz-internals")]
+#[doc(hidden)]
+pub mod fuzz;
 mod guid;
+mod health;
 mod index;
 mod index_entry;
 mod index_record;
 pub mod indexes;
+pub mod io;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod io_ext;
+mod log_file;
+mod mft_mirror;
 mod ntfs;
 mod record;
+pub mod recovery;
+mod resolved_name;
+pub mod scan;
+pub mod security;
+mod string;
 pub mod structured_values;
 mod time;
 mod traits;
 pub mod types;
 mod upcase_table;
+mod verify;
+pub mod walk;
+mod wof;
 
+#[cfg(feature = "async")]
+pub use crate::async_io::*;
 pub use crate::attribute::*;
+pub use crate::attribute_definition::*;
 pub use crate::error::*;
 pub use crate::file::*;
 pub use crate::file_reference::*;
 pub use crate::guid::*;
+pub use crate::health::*;
 pub use crate::index::*;
 pub use crate::index_entry::*;
 pub use crate::index_record::*;
+pub use crate::log_file::*;
+pub use crate::mft_mirror::*;
 pub use crate::ntfs::*;
+pub use crate::resolved_name::*;
+pub use crate::string::*;
 pub use crate::time::*;
 pub use crate::traits::*;
 pub use crate::upcase_table::*;
+pub use crate::verify::*;
+pub use crate::wof::*;
@@ -0,0 +1,348 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::mem;
+
+use alloc::vec;
+use byteorder::{ByteOrder, LittleEndian};
+use memoffset::{offset_of, span_of};
+
+use crate::attribute::NtfsAttributeType;
+use crate::attribute_value::NtfsAttributeValue;
+use crate::error::{NtfsError, Result};
+use crate::file::KnownNtfsFileRecordNumber;
+use crate::io::{Read, Seek, SeekFrom};
+use crate::ntfs::Ntfs;
+use crate::record::Record;
+use crate::traits::NtfsReadSeek;
+use crate::types::NtfsPosition;
+
+/// Signature of a $LogFile restart page.
+const RESTART_PAGE_SIGNATURE: &[u8] = b"RSTR";
+
+/// Signature found at the beginning of a $LogFile that has been emptied (filled with 0xFF).
+const EMPTY_LOG_FILE_SIGNATURE: &[u8] = &[0xff; 4];
+
+/// Smallest and largest system page size we accept for a restart page.
+const MIN_RESTART_PAGE_SIZE: u32 = 512;
+const MAX_RESTART_PAGE_SIZE: u32 = 65536;
+
+/// Restart area flag indicating that the volume has been cleanly unmounted.
+const RESTART_AREA_VOLUME_IS_CLEAN: u16 = 0x0002;
+
+#[repr(C, packed)]
+struct RestartPageHeader {
+    signature: [u8; 4],
+    update_sequence_offset: u16,
+    update_sequence_count: u16,
+    chkdsk_lsn: u64,
+    system_page_size: u32,
+    log_page_size: u32,
+    restart_area_offset: u16,
+    minor_version: i16,
+    major_version: i16,
+}
+
+#[repr(C, packed)]
+struct RestartAreaHeader {
+    current_lsn: u64,
+    log_clients: u16,
+    client_free_list: u16,
+    client_in_use_list: u16,
+    flags: u16,
+}
+
+/// State of the NTFS journal ($LogFile), as returned by [`Ntfs::log_file_status`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NtfsLogFileStatus {
+    /// The $LogFile has been emptied (filled with 0xFF) and contains no restart area.
+    ///
+    /// This is done by formatting tools like mkntfs and by drivers like ntfs-3g when unmounting cleanly.
+    Empty,
+    /// The $LogFile contains a restart area, which is described by the given [`NtfsLogFileRestartArea`].
+    RestartArea(NtfsLogFileRestartArea),
+}
+
+impl NtfsLogFileStatus {
+    /// Returns whether the $LogFile indicates a cleanly unmounted volume.
+    ///
+    /// This is the case if the $LogFile is empty or its restart area has the "volume is clean" flag set.
+    pub fn is_clean(&self) -> bool {
+        match self {
+            Self::Empty => true,
+            Self::RestartArea(restart_area) => restart_area.is_clean(),
+        }
+    }
+
+    pub(crate) fn read<T>(ntfs: &Ntfs, fs: &mut T) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let log_file = ntfs.file(fs, KnownNtfsFileRecordNumber::LogFile as u64)?;
+        let data_item = log_file.data(fs, "").ok_or(NtfsError::AttributeNotFound {
+            position: log_file.position(),
+            ty: NtfsAttributeType::Data,
+        })??;
+        let data_attribute = data_item.to_attribute()?;
+        let mut value = data_attribute.value(fs)?;
+
+        // The $LogFile begins with two restart pages, the second one directly following the first one.
+        let first_restart_area = match NtfsLogFileRestartArea::read(&mut value, fs, 0)? {
+            Some(restart_area) => restart_area,
+            None => return Ok(Self::Empty),
+        };
+        let second_offset = first_restart_area.system_page_size as u64;
+
+        // Windows alternates between both restart pages. The one with the higher LSN is the current one.
+        // A corrupted second restart page is no problem as long as the first one is fine.
+        let restart_area = match NtfsLogFileRestartArea::read(&mut value, fs, second_offset) {
+            Ok(Some(second_restart_area))
+                if second_restart_area.current_lsn > first_restart_area.current_lsn =>
+            {
+                second_restart_area
+            }
+            Err(e) if matches!(e.root_cause(), NtfsError::Io(_)) => return Err(e),
+            _ => first_restart_area,
+        };
+
+        Ok(Self::RestartArea(restart_area))
+    }
+}
+
+/// Information from a restart area of the NTFS journal ($LogFile).
+///
+/// Reference: <https://flatcap.github.io/linux-ntfs/ntfs/files/logfile.html>
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsLogFileRestartArea {
+    chkdsk_lsn: u64,
+    current_lsn: u64,
+    flags: u16,
+    position: NtfsPosition,
+    system_page_size: u32,
+}
+
+impl NtfsLogFileRestartArea {
+    /// Reads the restart page at `offset` of the $LogFile value.
+    ///
+    /// Returns `None` if the restart page has been emptied (filled with 0xFF).
+    fn read<T>(value: &mut NtfsAttributeValue, fs: &mut T, offset: u64) -> Result<Option<Self>>
+    where
+        T: Read + Seek,
+    {
+        value.seek(fs, SeekFrom::Start(offset))?;
+        let position = value.data_position();
+
+        let mut header = [0u8; mem::size_of::<RestartPageHeader>()];
+        value.read_exact(fs, &mut header)?;
+
+        let signature = &header[span_of!(RestartPageHeader, signature)];
+        if signature == EMPTY_LOG_FILE_SIGNATURE {
+            return Ok(None);
+        } else if signature != RESTART_PAGE_SIGNATURE {
+            return Err(NtfsError::InvalidLogFileRestartPageSignature {
+                position,
+                expected: RESTART_PAGE_SIGNATURE,
+                actual: signature.try_into().unwrap(),
+            });
+        }
+
+        let system_page_size =
+            LittleEndian::read_u32(&header[offset_of!(RestartPageHeader, system_page_size)..]);
+        if !system_page_size.is_power_of_two()
+            || !(MIN_RESTART_PAGE_SIZE..=MAX_RESTART_PAGE_SIZE).contains(&system_page_size)
+        {
+            return Err(NtfsError::InvalidLogFileRestartPageSize {
+                position,
+                size: system_page_size,
+            });
+        }
+
+        // Read the entire restart page and apply the fixups.
+        let mut data = vec![0u8; system_page_size as usize];
+        value.seek(fs, SeekFrom::Start(offset))?;
+        value.read_exact(fs, &mut data)?;
+
+        let mut record = Record::new(data, position);
+        record.fixup()?;
+        let data = record.data();
+
+        let chkdsk_lsn = LittleEndian::read_u64(&data[offset_of!(RestartPageHeader, chkdsk_lsn)..]);
+        let restart_area_offset =
+            LittleEndian::read_u16(&data[offset_of!(RestartPageHeader, restart_area_offset)..]);
+        let restart_area_start = restart_area_offset as usize;
+        let restart_area_end = restart_area_start + mem::size_of::<RestartAreaHeader>();
+        if restart_area_start < mem::size_of::<RestartPageHeader>() || restart_area_end > data.len()
+        {
+            return Err(NtfsError::InvalidLogFileRestartAreaOffset {
+                position,
+                offset: restart_area_offset,
+                page_size: system_page_size,
+            });
+        }
+
+        let restart_area = &data[restart_area_start..restart_area_end];
+        let current_lsn =
+            LittleEndian::read_u64(&restart_area[offset_of!(RestartAreaHeader, current_lsn)..]);
+        let flags = LittleEndian::read_u16(&restart_area[offset_of!(RestartAreaHeader, flags)..]);
+
+        Ok(Some(Self {
+            chkdsk_lsn,
+            current_lsn,
+            flags,
+            position,
+            system_page_size,
+        }))
+    }
+
+    /// Returns the Log File Sequence Number (LSN) of the last chkdsk run, as stored in the restart page.
+    ///
+    /// This is zero if chkdsk has never modified the journal.
+    pub fn chkdsk_lsn(&self) -> u64 {
+        self.chkdsk_lsn
+    }
+
+    /// Returns the current Log File Sequence Number (LSN), which is the LSN of the last written log record.
+    pub fn current_lsn(&self) -> u64 {
+        self.current_lsn
+    }
+
+    /// Returns whether the "volume is clean" flag of this restart area is set.
+    ///
+    /// NTFS drivers set this flag when the volume has been unmounted without any pending log records.
+    pub fn is_clean(&self) -> bool {
+        self.flags & RESTART_AREA_VOLUME_IS_CLEAN != 0
+    }
+
+    /// Returns the absolute position of the restart page containing this restart area within the filesystem, in bytes.
+    pub fn position(&self) -> NtfsPosition {
+        self.position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE_SIZE: usize = 4096;
+
+    /// Builds a restart page of 4096 bytes with the given values and applies the Update Sequence Array.
+    fn restart_page(current_lsn: u64, flags: u16) -> Vec<u8> {
+        const SECTOR_SIZE: usize = 512;
+        const USA_OFFSET: usize = 0x1e;
+        const RESTART_AREA_OFFSET: usize = 0x30;
+
+        let mut page = vec![0u8; PAGE_SIZE];
+        let usa_count = PAGE_SIZE / SECTOR_SIZE + 1;
+
+        page[0..4].copy_from_slice(RESTART_PAGE_SIGNATURE);
+        LittleEndian::write_u16(&mut page[4..], USA_OFFSET as u16);
+        LittleEndian::write_u16(&mut page[6..], usa_count as u16);
+        LittleEndian::write_u32(&mut page[0x10..], PAGE_SIZE as u32);
+        LittleEndian::write_u32(&mut page[0x14..], PAGE_SIZE as u32);
+        LittleEndian::write_u16(&mut page[0x18..], RESTART_AREA_OFFSET as u16);
+        LittleEndian::write_i16(&mut page[0x1a..], 1);
+        LittleEndian::write_i16(&mut page[0x1c..], 1);
+
+        let restart_area = &mut page[RESTART_AREA_OFFSET..];
+        LittleEndian::write_u64(&mut restart_area[0..], current_lsn);
+        LittleEndian::write_u16(&mut restart_area[8..], 1);
+        LittleEndian::write_u16(&mut restart_area[0x0a..], 0xffff);
+        LittleEndian::write_u16(&mut restart_area[0x0e..], flags);
+
+        // Move the last 2 bytes of each sector into the Update Sequence Array and replace them by the
+        // Update Sequence Number.
+        let update_sequence_number = [0x01, 0x00];
+        page[USA_OFFSET..USA_OFFSET + 2].copy_from_slice(&update_sequence_number);
+
+        for i in 1..usa_count {
+            let sector_end = i * SECTOR_SIZE - 2;
+            let usa_entry = USA_OFFSET + i * 2;
+            page.copy_within(sector_end..sector_end + 2, usa_entry);
+            page[sector_end..sector_end + 2].copy_from_slice(&update_sequence_number);
+        }
+
+        page
+    }
+
+    /// Returns the absolute byte position of the $LogFile data in testfs1, which is stored in a single Data Run.
+    fn log_file_position(testfs1: &mut crate::io::Cursor<Vec<u8>>) -> usize {
+        let ntfs = Ntfs::new(testfs1).unwrap();
+        let log_file = ntfs
+            .file(testfs1, KnownNtfsFileRecordNumber::LogFile as u64)
+            .unwrap();
+        let data_item = log_file.data(testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let data_value = data_attribute.value(testfs1).unwrap();
+        data_value.data_position().value().unwrap().get() as usize
+    }
+
+    #[test]
+    fn test_restart_pages() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let position = log_file_position(&mut testfs1);
+
+        // The second restart page is more recent and indicates a dirty volume.
+        let first_page = restart_page(100, RESTART_AREA_VOLUME_IS_CLEAN);
+        let second_page = restart_page(200, 0);
+        let data = testfs1.get_mut();
+        data[position..position + PAGE_SIZE].copy_from_slice(&first_page);
+        data[position + PAGE_SIZE..position + 2 * PAGE_SIZE].copy_from_slice(&second_page);
+
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let status = ntfs.log_file_status(&mut testfs1).unwrap();
+        let restart_area = match &status {
+            NtfsLogFileStatus::RestartArea(restart_area) => restart_area,
+            _ => panic!("Expected a restart area, got {status:?}"),
+        };
+        assert_eq!(restart_area.current_lsn(), 200);
+        assert_eq!(restart_area.chkdsk_lsn(), 0);
+        assert_eq!(
+            restart_area.position(),
+            NtfsPosition::new((position + PAGE_SIZE) as u64)
+        );
+        assert!(!status.is_clean());
+        assert_eq!(
+            ntfs.health_summary(&mut testfs1).unwrap().is_clean(),
+            Some(false)
+        );
+
+        // A corrupted second restart page makes us fall back to the first one.
+        testfs1.get_mut()[position + PAGE_SIZE..position + PAGE_SIZE + 4].copy_from_slice(b"BAAD");
+        let status = ntfs.log_file_status(&mut testfs1).unwrap();
+        let restart_area = match &status {
+            NtfsLogFileStatus::RestartArea(restart_area) => restart_area,
+            _ => panic!("Expected a restart area, got {status:?}"),
+        };
+        assert_eq!(restart_area.current_lsn(), 100);
+        assert!(status.is_clean());
+    }
+
+    #[test]
+    fn test_invalid_restart_page() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let position = log_file_position(&mut testfs1);
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        testfs1.get_mut()[position..position + 4].copy_from_slice(b"BAAD");
+        assert!(matches!(
+            ntfs.log_file_status(&mut testfs1),
+            Err(NtfsError::InvalidLogFileRestartPageSignature { actual, .. }) if &actual == b"BAAD"
+        ));
+
+        let mut page = restart_page(100, RESTART_AREA_VOLUME_IS_CLEAN);
+        LittleEndian::write_u32(&mut page[0x10..], 1000);
+        testfs1.get_mut()[position..position + PAGE_SIZE].copy_from_slice(&page);
+        assert!(matches!(
+            ntfs.log_file_status(&mut testfs1),
+            Err(NtfsError::InvalidLogFileRestartPageSize { size: 1000, .. })
+        ));
+
+        let mut page = restart_page(100, RESTART_AREA_VOLUME_IS_CLEAN);
+        LittleEndian::write_u16(&mut page[0x18..], 0x1000);
+        testfs1.get_mut()[position..position + PAGE_SIZE].copy_from_slice(&page);
+        assert!(matches!(
+            ntfs.log_file_status(&mut testfs1),
+            Err(NtfsError::InvalidLogFileRestartAreaOffset { offset: 0x1000, .. })
+        ));
+    }
+}
@@ -0,0 +1,185 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::vec::Vec;
+
+use crate::error::{NtfsError, Result};
+use crate::file::NtfsFile;
+use crate::io::{Read, Seek};
+use crate::ntfs::Ntfs;
+use crate::types::NtfsPosition;
+
+/// Minimum number of File Records at the beginning of the MFT that are mirrored in $MFTMirr.
+const MIN_MFT_MIRROR_RECORD_COUNT: u32 = 4;
+
+/// A File Record at the beginning of the MFT that doesn't match its copy in $MFTMirr,
+/// as returned by [`Ntfs::verify_mft_mirror`].
+///
+/// If neither [`mft_error`][Self::mft_error] nor [`mft_mirror_error`][Self::mft_mirror_error] is set,
+/// both File Records could be read, but their contents differ.
+#[derive(Debug)]
+pub struct NtfsMftMirrorMismatch {
+    file_record_number: u64,
+    mft_position: NtfsPosition,
+    mft_mirror_position: NtfsPosition,
+    mft_error: Option<NtfsError>,
+    mft_mirror_error: Option<NtfsError>,
+}
+
+impl NtfsMftMirrorMismatch {
+    pub(crate) fn verify<T>(ntfs: &Ntfs, fs: &mut T) -> Result<Vec<Self>>
+    where
+        T: Read + Seek,
+    {
+        let mut mismatches = Vec::new();
+
+        for file_record_number in 0..mirrored_record_count(ntfs) {
+            let offset = file_record_number * ntfs.file_record_size() as u64;
+            let mft_position = ntfs.mft_position() + offset;
+            let mft_mirror_position = ntfs.mft_mirror_position() + offset;
+
+            let mft_file = read_file(ntfs, fs, mft_position, file_record_number)?;
+            let mft_mirror_file = read_file(ntfs, fs, mft_mirror_position, file_record_number)?;
+
+            let (mft_error, mft_mirror_error) = match (mft_file, mft_mirror_file) {
+                (Ok(mft_file), Ok(mft_mirror_file)) => {
+                    if mft_file.record_data() == mft_mirror_file.record_data() {
+                        continue;
+                    }
+
+                    (None, None)
+                }
+                (mft_file, mft_mirror_file) => (mft_file.err(), mft_mirror_file.err()),
+            };
+
+            mismatches.push(Self {
+                file_record_number,
+                mft_position,
+                mft_mirror_position,
+                mft_error,
+                mft_mirror_error,
+            });
+        }
+
+        Ok(mismatches)
+    }
+
+    /// Returns `true` if both File Records could be read, but their contents differ.
+    pub fn contents_differ(&self) -> bool {
+        self.mft_error.is_none() && self.mft_mirror_error.is_none()
+    }
+
+    /// Returns the File Record Number of the mismatching File Record.
+    pub fn file_record_number(&self) -> u64 {
+        self.file_record_number
+    }
+
+    /// Returns the error that occurred while reading the File Record from the MFT, if any.
+    pub fn mft_error(&self) -> Option<&NtfsError> {
+        self.mft_error.as_ref()
+    }
+
+    /// Returns the error that occurred while reading the File Record from $MFTMirr, if any.
+    pub fn mft_mirror_error(&self) -> Option<&NtfsError> {
+        self.mft_mirror_error.as_ref()
+    }
+
+    /// Returns the absolute byte position of the File Record copy in $MFTMirr.
+    pub fn mft_mirror_position(&self) -> NtfsPosition {
+        self.mft_mirror_position
+    }
+
+    /// Returns the absolute byte position of the File Record in the MFT.
+    pub fn mft_position(&self) -> NtfsPosition {
+        self.mft_position
+    }
+}
+
+/// Returns the number of File Records mirrored in $MFTMirr.
+///
+/// This is at least 4, but more if a single cluster holds more File Records.
+fn mirrored_record_count(ntfs: &Ntfs) -> u64 {
+    let records_per_cluster = ntfs.cluster_size() / ntfs.file_record_size();
+    u64::from(records_per_cluster.max(MIN_MFT_MIRROR_RECORD_COUNT))
+}
+
+/// Reads a File Record via [`Ntfs::file_at_position`].
+///
+/// I/O errors are returned in the outer `Result`, all other errors in the inner `Result`.
+fn read_file<'n, T>(
+    ntfs: &'n Ntfs,
+    fs: &mut T,
+    position: NtfsPosition,
+    file_record_number: u64,
+) -> Result<core::result::Result<NtfsFile<'n>, NtfsError>>
+where
+    T: Read + Seek,
+{
+    match ntfs.file_at_position(fs, position, file_record_number) {
+        Ok(file) => Ok(Ok(file)),
+        Err(e) if matches!(e.root_cause(), NtfsError::Io(_)) => Err(e),
+        Err(e) => Ok(Err(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::KnownNtfsFileRecordNumber;
+
+    #[test]
+    fn test_verify_mft_mirror() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        assert_eq!(ntfs.mft_mirror_position().value().unwrap().get(), 1048064);
+
+        // testfs1 has a consistent $MFTMirr.
+        assert!(ntfs.verify_mft_mirror(&mut testfs1).unwrap().is_empty());
+
+        // Change a byte in the $LogFile File Record of the MFT (after its header).
+        let file_record_size = ntfs.file_record_size() as u64;
+        let mft_position = ntfs.mft_position().value().unwrap().get();
+        let log_file_position = mft_position + 2 * file_record_size;
+        testfs1.get_mut()[log_file_position as usize + 0x100] ^= 0xff;
+
+        // Corrupt the signature of the $Volume File Record in $MFTMirr.
+        let mft_mirror_position = ntfs.mft_mirror_position().value().unwrap().get();
+        let volume_mirror_position = mft_mirror_position + 3 * file_record_size;
+        testfs1.get_mut()[volume_mirror_position as usize..volume_mirror_position as usize + 4]
+            .copy_from_slice(b"BAAD");
+
+        let mismatches = ntfs.verify_mft_mirror(&mut testfs1).unwrap();
+        assert_eq!(mismatches.len(), 2);
+
+        let log_file_mismatch = &mismatches[0];
+        assert_eq!(
+            log_file_mismatch.file_record_number(),
+            KnownNtfsFileRecordNumber::LogFile as u64
+        );
+        assert_eq!(
+            log_file_mismatch.mft_position().value().unwrap().get(),
+            log_file_position
+        );
+        assert_eq!(
+            log_file_mismatch
+                .mft_mirror_position()
+                .value()
+                .unwrap()
+                .get(),
+            mft_mirror_position + 2 * file_record_size
+        );
+        assert!(log_file_mismatch.contents_differ());
+
+        let volume_mismatch = &mismatches[1];
+        assert_eq!(
+            volume_mismatch.file_record_number(),
+            KnownNtfsFileRecordNumber::Volume as u64
+        );
+        assert!(!volume_mismatch.contents_differ());
+        assert!(volume_mismatch.mft_error().is_none());
+        assert!(matches!(
+            volume_mismatch.mft_mirror_error(),
+            Some(NtfsError::InvalidFileSignature { .. })
+        ));
+    }
+}
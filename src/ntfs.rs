@@ -1,35 +1,280 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use binrw::io::{Read, Seek, SeekFrom};
+use core::fmt;
+use core::ops::{Range, RangeInclusive};
+
+use crate::io::{Read, Seek, SeekFrom};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
 use binrw::BinReaderExt;
 
-use crate::attribute::NtfsAttributeType;
+#[cfg(feature = "async")]
+use crate::async_io::{AsyncBlockCache, AsyncNtfsReadSeek};
+use crate::attribute::{NtfsAttribute, NtfsAttributeItem, NtfsAttributeType};
+use crate::attribute_definition::NtfsAttributeDefinition;
+use crate::attribute_value::{NtfsAttributeValue, NtfsDataRun};
 use crate::boot_sector::BootSector;
 use crate::error::{NtfsError, Result};
-use crate::file::{KnownNtfsFileRecordNumber, NtfsFile};
-use crate::structured_values::{NtfsVolumeInformation, NtfsVolumeName};
+use crate::file::{KnownNtfsFileRecordNumber, NtfsFile, NtfsFileFlags};
+#[cfg(feature = "std")]
+use crate::file_record_cache::FileRecordCache;
+use crate::guid::NtfsGuid;
+use crate::health::NtfsHealthSummary;
+use crate::log_file::NtfsLogFileStatus;
+use crate::mft_mirror::NtfsMftMirrorMismatch;
+use crate::resolved_name::NtfsResolvedName;
+use crate::structured_values::{NtfsAttributeList, NtfsVolumeInformation, NtfsVolumeName};
+use crate::time::NtfsTime;
 use crate::traits::NtfsReadSeek;
-use crate::types::NtfsPosition;
-use crate::upcase_table::UpcaseTable;
+use crate::types::{Lcn, NtfsPosition};
+use crate::upcase_table::{NtfsUpcaseInfo, NtfsUpcaseTableSource, UpcaseTable};
+use crate::verify::{NtfsVerificationReport, NtfsVerifyOptions};
+
+/// Determines how strictly on-disk structures are validated, set via [`Ntfs::set_validation_mode`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NtfsValidationMode {
+    /// Every inconsistency is reported as an error.
+    ///
+    /// This is the default.
+    Strict,
+    /// Inconsistencies that don't affect the bounds used for further parsing are tolerated.
+    /// They are reported to the handler set via [`Ntfs::set_warning_handler`] instead.
+    ///
+    /// This mode is useful for recovering data from damaged filesystems.
+    Lenient,
+}
+
+impl Default for NtfsValidationMode {
+    fn default() -> Self {
+        Self::Strict
+    }
+}
+
+/// Determines how reads from clusters marked as bad are handled, set via [`Ntfs::set_bad_cluster_policy`].
+///
+/// This only has an effect after the bad clusters have been read via [`Ntfs::read_bad_clusters`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NtfsBadClusterPolicy {
+    /// Reading from a bad cluster returns [`NtfsError::BadClusterRead`].
+    ///
+    /// This is the default.
+    Error,
+    /// Bad clusters are not read from the filesystem, but read as zeros.
+    ZeroFill,
+}
+
+impl Default for NtfsBadClusterPolicy {
+    fn default() -> Self {
+        Self::Error
+    }
+}
+
+/// File Records below this number are reserved for system files.
+///
+/// This covers all [`KnownNtfsFileRecordNumber`] entries and the File Records 12 to 15,
+/// which are reserved for future use.
+const FIRST_USER_FILE_RECORD_NUMBER: u64 = 16;
+
+/// Region of the filesystem an absolute byte position belongs to, as returned by [`Ntfs::classify_position`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NtfsRegion {
+    /// The boot sector area (covered by the $Boot file) or the backup boot sector at the end of the volume.
+    BootSector,
+    /// The Master File Table (MFT).
+    Mft,
+    /// The mirror copy of the first File Records of the Master File Table.
+    MftMirr,
+    /// Any other position, including positions outside the volume.
+    Other,
+}
+
+/// Byte ranges occupied by the system files checked in [`Ntfs::classify_position`].
+#[derive(Clone, Debug, Default)]
+struct SystemRegions {
+    boot_size: u64,
+    mft: Vec<Range<u64>>,
+    mft_mirr: Vec<Range<u64>>,
+}
+
+impl SystemRegions {
+    /// Returns whether `position` lies in one of the `ranges`, which must be sorted by their start.
+    fn contains(ranges: &[Range<u64>], position: u64) -> bool {
+        let index = ranges.partition_point(|range| range.start <= position);
+        index > 0 && position < ranges[index - 1].end
+    }
+}
+
+/// Size and Data Runs of the $DATA attribute of the MFT, cached by [`Ntfs::read_mft_data_runs`].
+#[derive(Clone, Debug)]
+struct MftDataRuns {
+    /// Size of the $DATA attribute value, in bytes.
+    data_size: u64,
+    /// The Data Runs without any information about bad clusters or the length of the reader,
+    /// which are added on every read (as they may change after caching).
+    data_runs: Vec<NtfsDataRun>,
+}
+
+impl MftDataRuns {
+    /// Reads the size and Data Runs of the $DATA attribute from File Record 0 of the MFT.
+    ///
+    /// Like [`Ntfs::read_file_record_data`], this assumes that the MFT does not have an Attribute List.
+    fn read<T>(ntfs: &Ntfs, fs: &mut T) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        // Always read File Record 0 from the filesystem, bypassing the File Record cache.
+        let mft_position = ntfs
+            .mft_position
+            .value()
+            .expect("mft_position is validated to be nonzero in Ntfs::new");
+        let mft = NtfsFile::new(
+            ntfs,
+            fs,
+            mft_position,
+            KnownNtfsFileRecordNumber::MFT as u64,
+        )?;
+
+        let mft_data_attribute =
+            mft.find_resident_attribute(NtfsAttributeType::Data, None, None)?;
+        if mft_data_attribute.is_resident() {
+            return Err(NtfsError::UnexpectedResidentAttribute {
+                position: mft_data_attribute.position(),
+            });
+        }
+
+        let data_runs = mft_data_attribute
+            .non_resident_value()?
+            .data_runs()
+            .map(|data_run| {
+                data_run.map(|data_run| {
+                    NtfsDataRun::new(data_run.data_position(), data_run.allocated_size())
+                })
+            })
+            .collect::<Result<Vec<NtfsDataRun>>>()?;
+
+        Ok(Self {
+            data_size: mft_data_attribute.value_length(),
+            data_runs,
+        })
+    }
+
+    /// Reads the raw bytes of the File Record with the given number (without applying any fixups).
+    ///
+    /// Returns the bytes along with the absolute byte position of the File Record.
+    fn read_file_record_data<T>(
+        &self,
+        ntfs: &Ntfs,
+        fs: &mut T,
+        file_record_number: u64,
+    ) -> Result<(Vec<u8>, NtfsPosition)>
+    where
+        T: Read + Seek,
+    {
+        let offset = file_record_number
+            .checked_mul(ntfs.file_record_size as u64)
+            .ok_or(NtfsError::InvalidFileRecordNumber { file_record_number })?;
+        let record_end = offset
+            .checked_add(ntfs.file_record_size as u64)
+            .ok_or(NtfsError::InvalidFileRecordNumber { file_record_number })?;
+        if record_end > self.data_size {
+            return Err(NtfsError::InvalidFileRecordNumber { file_record_number });
+        }
+
+        let mut data = vec![0; ntfs.file_record_size as usize];
+        let mut position = NtfsPosition::none();
+        let mut bytes_read = 0;
+        let mut data_run_start = 0u64;
+
+        // A File Record may span two Data Runs of the MFT (e.g. with a cluster size smaller than the
+        // File Record size).
+        for data_run in &self.data_runs {
+            let data_run_end = data_run_start.saturating_add(data_run.allocated_size());
+            let read_offset = offset + bytes_read as u64;
+
+            if read_offset < data_run_end {
+                let mut data_run = data_run
+                    .clone()
+                    .with_backing_size(ntfs)
+                    .with_bad_clusters(ntfs);
+                data_run.seek(fs, SeekFrom::Start(read_offset - data_run_start))?;
+
+                if bytes_read == 0 {
+                    position = data_run.data_position();
+                    if position.value().is_none() {
+                        return Err(NtfsError::InvalidFileRecordNumber { file_record_number });
+                    }
+                }
+
+                let bytes_to_read =
+                    usize::min(data.len() - bytes_read, data_run.remaining_len() as usize);
+                data_run
+                    .read_exact(fs, &mut data[bytes_read..bytes_read + bytes_to_read])
+                    .map_err(|e| {
+                        e.with_value_context(
+                            KnownNtfsFileRecordNumber::MFT as u64,
+                            NtfsAttributeType::Data as u32,
+                        )
+                    })?;
+                bytes_read += bytes_to_read;
+
+                if bytes_read == data.len() {
+                    return Ok((data, position));
+                }
+            }
+
+            data_run_start = data_run_end;
+        }
+
+        // The Data Runs don't cover the size of the $DATA attribute.
+        Err(NtfsError::InvalidFileRecordNumber { file_record_number })
+    }
+}
+
+/// Handler called for every inconsistency tolerated in [`NtfsValidationMode::Lenient`].
+pub type NtfsWarningHandler = Box<dyn Fn(&NtfsError) + Send + Sync>;
 
 /// Root structure describing an NTFS filesystem.
-#[derive(Debug)]
 pub struct Ntfs {
+    /// Clusters marked as bad in the $BadClus file, sorted and merged (only filled by [`Ntfs::read_bad_clusters`]).
+    bad_clusters: Vec<Range<Lcn>>,
+    /// How reads from `bad_clusters` are handled.
+    bad_cluster_policy: NtfsBadClusterPolicy,
+    /// Length of the reader, in bytes (only known after [`Ntfs::check_backing_size`]).
+    backing_size: Option<u64>,
     /// The size of a single cluster, in bytes. This is usually 4096.
     cluster_size: u32,
     /// The size of a single sector, in bytes. This is usually 512.
     sector_size: u16,
     /// Size of the filesystem, in bytes.
     size: u64,
+    /// Byte position of the filesystem within the reader, which is added to all absolute positions.
+    partition_offset: u64,
+    /// Whether the reader only contains a raw dump of the MFT (see [`Ntfs::new_from_mft_parameters`]).
+    mft_dump: bool,
     /// Absolute position of the Master File Table (MFT), in bytes.
     mft_position: NtfsPosition,
+    /// Absolute position of the mirror copy of the first MFT File Records ($MFTMirr), in bytes.
+    mft_mirror_position: NtfsPosition,
+    /// Size and Data Runs of the $DATA attribute of the MFT, to look up File Records without reading
+    /// File Record 0 every time (see [`Ntfs::read_mft_data_runs`]).
+    mft_data_runs: Option<MftDataRuns>,
+    /// Recently used File Records (only filled after [`Ntfs::set_file_record_cache_capacity`]).
+    #[cfg(feature = "std")]
+    file_record_cache: Option<std::sync::Mutex<FileRecordCache>>,
     /// Size of a single File Record, in bytes.
     file_record_size: u32,
     /// Serial number of the NTFS volume.
     serial_number: u64,
+    /// Byte ranges of system files (only required for [`Ntfs::classify_position`]).
+    system_regions: Option<SystemRegions>,
     /// Table of Unicode uppercase characters (only required for case-insensitive comparisons).
     upcase_table: Option<UpcaseTable>,
+    /// How strictly on-disk structures are validated.
+    validation_mode: NtfsValidationMode,
+    /// Handler for inconsistencies tolerated in [`NtfsValidationMode::Lenient`].
+    warning_handler: Option<NtfsWarningHandler>,
 }
 
 impl Ntfs {
@@ -37,13 +282,39 @@ impl Ntfs {
     ///
     /// The reader must cover the entire NTFS partition, not more and not less.
     /// It will be rewinded to the beginning before reading anything.
-    #[allow(clippy::seek_to_start_instead_of_rewind)]
+    ///
+    /// Use [`Ntfs::new_at_offset`] if the reader covers an entire disk instead.
     pub fn new<T>(fs: &mut T) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        Self::new_at_offset(fs, 0)
+    }
+
+    /// Async variant of [`Ntfs::new`] for readers implementing [`AsyncNtfsReadSeek`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn new_async<T>(fs: &mut T) -> Result<Self>
+    where
+        T: AsyncNtfsReadSeek,
+    {
+        let mut cache = AsyncBlockCache::new(fs).await?;
+        cache.run(|fs| Self::new(fs)).await
+    }
+
+    /// Creates a new [`Ntfs`] object from a reader whose NTFS partition begins at byte position `partition_offset`,
+    /// and validates its boot sector information.
+    ///
+    /// This is useful for readers covering an entire disk: All absolute byte positions (every [`NtfsPosition`]
+    /// returned by this crate) then include `partition_offset` and can be cross-referenced with other disk tools
+    /// without any adjustment.
+    /// Logical Cluster Numbers (LCNs) and [`Ntfs::size`] are still relative to the start of the partition.
+    pub fn new_at_offset<T>(fs: &mut T, partition_offset: u64) -> Result<Self>
     where
         T: Read + Seek,
     {
         // Read and validate the boot sector.
-        fs.seek(SeekFrom::Start(0))?;
+        fs.seek(SeekFrom::Start(partition_offset))?;
         let boot_sector = fs.read_le::<BootSector>()?;
         boot_sector.validate()?;
 
@@ -53,39 +324,378 @@ impl Ntfs {
         let total_sectors = bpb.total_sectors();
         let size = total_sectors
             .checked_mul(sector_size as u64)
+            .filter(|size| size.checked_add(partition_offset).is_some())
             .ok_or(NtfsError::TotalSectorsTooBig { total_sectors })?;
+        let bad_clusters = Vec::new();
+        let bad_cluster_policy = NtfsBadClusterPolicy::default();
+        let backing_size = None;
+        let mft_dump = false;
         let mft_position = NtfsPosition::none();
+        let mft_mirror_position = NtfsPosition::none();
+        let mft_data_runs = None;
+        #[cfg(feature = "std")]
+        let file_record_cache = None;
         let file_record_size = bpb.file_record_size()?;
         let serial_number = bpb.serial_number();
+        let system_regions = None;
         let upcase_table = None;
+        let validation_mode = NtfsValidationMode::default();
+        let warning_handler = None;
 
         let mut ntfs = Self {
+            bad_clusters,
+            bad_cluster_policy,
+            backing_size,
             cluster_size,
             sector_size,
             size,
+            partition_offset,
+            mft_dump,
             mft_position,
+            mft_mirror_position,
+            mft_data_runs,
+            #[cfg(feature = "std")]
+            file_record_cache,
             file_record_size,
             serial_number,
+            system_regions,
             upcase_table,
+            validation_mode,
+            warning_handler,
         };
         ntfs.mft_position = bpb.mft_lcn()?.position(&ntfs)?;
+        ntfs.validate_mft_position()?;
+        ntfs.mft_mirror_position = bpb
+            .mft_mirror_lcn()?
+            .position(&ntfs)
+            .map_err(|_| NtfsError::InvalidMftMirrorLcn)?;
+        ntfs.validate_mft_mirror_position()?;
+
+        // A damaged File Record 0 must not prevent recovery via `Ntfs::file_at_position`.
+        // Without cached Data Runs, `Ntfs::file` reads File Record 0 on every call and reports the error there.
+        ntfs.mft_data_runs = MftDataRuns::read(&ntfs, fs).ok();
 
         Ok(ntfs)
     }
 
+    /// Creates a new [`Ntfs`] object for a reader that only contains a raw dump of the Master File Table (MFT),
+    /// like an extracted $MFT file, instead of an entire NTFS partition.
+    ///
+    /// As there is no boot sector to take them from, the cluster size and File Record size of the original
+    /// filesystem must be given.
+    /// The cluster size must be a power of two between 512 bytes and 2 MiB, and the File Record size
+    /// a power of two between 1 KiB and 16 KiB (usually 1 KiB).
+    ///
+    /// [`Ntfs::file`] then reads File Record `n` at byte position `n * file_record_size` of the reader,
+    /// and [`records_summary`] iterates all File Records of the dump.
+    /// Resident attributes (including names, timestamps, and resident Attribute Lists) are fully usable.
+    /// Reading non-resident attribute values fails with [`NtfsError::VolumeDataUnavailable`], because their
+    /// data lives outside the MFT.
+    ///
+    /// All byte positions returned for this [`Ntfs`] are relative to the start of the dump.
+    /// This also means that File Record 0 has no position (see [`NtfsPosition::value`]).
+    ///
+    /// [`records_summary`]: crate::scan::records_summary
+    pub fn new_from_mft_parameters(cluster_size: u32, file_record_size: u32) -> Result<Self> {
+        const CLUSTER_SIZE_RANGE: RangeInclusive<u32> = 512..=2097152;
+        const FILE_RECORD_SIZE_RANGE: RangeInclusive<u32> = 1024..=16384;
+
+        if !cluster_size.is_power_of_two() || !CLUSTER_SIZE_RANGE.contains(&cluster_size) {
+            return Err(NtfsError::UnsupportedClusterSize {
+                min: *CLUSTER_SIZE_RANGE.start(),
+                max: *CLUSTER_SIZE_RANGE.end(),
+                actual: cluster_size,
+            });
+        }
+
+        if !file_record_size.is_power_of_two()
+            || !FILE_RECORD_SIZE_RANGE.contains(&file_record_size)
+        {
+            return Err(NtfsError::UnsupportedFileRecordSize {
+                min: *FILE_RECORD_SIZE_RANGE.start(),
+                max: *FILE_RECORD_SIZE_RANGE.end(),
+                actual: file_record_size,
+            });
+        }
+
+        Ok(Self {
+            bad_clusters: Vec::new(),
+            bad_cluster_policy: NtfsBadClusterPolicy::default(),
+            backing_size: None,
+            cluster_size,
+            sector_size: 512,
+            size: 0,
+            partition_offset: 0,
+            mft_dump: true,
+            mft_position: NtfsPosition::none(),
+            mft_mirror_position: NtfsPosition::none(),
+            mft_data_runs: None,
+            #[cfg(feature = "std")]
+            file_record_cache: None,
+            file_record_size,
+            serial_number: 0,
+            system_regions: None,
+            upcase_table: None,
+            validation_mode: NtfsValidationMode::default(),
+            warning_handler: None,
+        })
+    }
+
+    /// Returns all attribute types defined in the $AttrDef file of this filesystem
+    /// (see [`NtfsAttributeDefinition`]).
+    pub fn attribute_definitions<T>(&self, fs: &mut T) -> Result<Vec<NtfsAttributeDefinition>>
+    where
+        T: Read + Seek,
+    {
+        NtfsAttributeDefinition::read_all(self, fs)
+    }
+
+    /// Returns the [`NtfsBadClusterPolicy`] set via [`Ntfs::set_bad_cluster_policy`].
+    pub fn bad_cluster_policy(&self) -> NtfsBadClusterPolicy {
+        self.bad_cluster_policy
+    }
+
+    /// Returns the ranges of clusters marked as bad, sorted ascending and with adjacent ranges merged.
+    ///
+    /// This is empty unless [`Ntfs::read_bad_clusters`] has been called.
+    pub fn bad_clusters(&self) -> &[Range<Lcn>] {
+        &self.bad_clusters
+    }
+
+    /// Returns the length of the reader, in bytes, as determined by [`Ntfs::check_backing_size`].
+    ///
+    /// This is `None` if [`Ntfs::check_backing_size`] has not been called.
+    pub fn backing_size(&self) -> Option<u64> {
+        self.backing_size
+    }
+
+    /// Compares the size of the filesystem (see [`Ntfs::size`]) with the actual length of the reader,
+    /// and returns `true` if the reader is shorter, i.e. if it is a truncated image.
+    ///
+    /// The length of the reader is also remembered for subsequent reads:
+    /// Reading data of a Data Run beyond the end of the reader then fails with
+    /// [`NtfsError::BeyondBackingStorage`] instead of an I/O error.
+    /// Data Runs that are already in use are not affected.
+    ///
+    /// This check is optional, because it requires a reader that can seek to its end.
+    /// The reader is left at its end afterwards.
+    pub fn check_backing_size<T>(&mut self, fs: &mut T) -> Result<bool>
+    where
+        T: Read + Seek,
+    {
+        let backing_size = fs.seek(SeekFrom::End(0))?;
+        self.backing_size = Some(backing_size);
+
+        Ok(self.is_truncated() == Some(true))
+    }
+
+    /// Returns the region of the filesystem the given absolute byte position belongs to.
+    ///
+    /// The position is checked against the size of the $Boot file and the actual Data Runs of the
+    /// $MFT and $MFTMirr files, so this also works for a fragmented MFT.
+    /// A position without a value (see [`NtfsPosition::value`]) is classified as [`NtfsRegion::Other`].
+    ///
+    /// Returns [`NtfsError::SystemRegionsNotLoaded`] if [`Ntfs::read_system_regions`] has not been called.
+    pub fn classify_position(&self, position: NtfsPosition) -> Result<NtfsRegion> {
+        let system_regions = self
+            .system_regions
+            .as_ref()
+            .ok_or(NtfsError::SystemRegionsNotLoaded)?;
+
+        let position = match position.value() {
+            Some(position) => position.get(),
+            None => return Ok(NtfsRegion::Other),
+        };
+        let partition_position = match position.checked_sub(self.partition_offset) {
+            Some(partition_position) => partition_position,
+            None => return Ok(NtfsRegion::Other),
+        };
+
+        // The backup boot sector follows the last sector counted in the boot sector.
+        let is_backup_boot_sector = partition_position >= self.size
+            && partition_position - self.size < self.sector_size as u64;
+
+        let region = if is_backup_boot_sector || partition_position < system_regions.boot_size {
+            NtfsRegion::BootSector
+        } else if SystemRegions::contains(&system_regions.mft, position) {
+            NtfsRegion::Mft
+        } else if SystemRegions::contains(&system_regions.mft_mirr, position) {
+            NtfsRegion::MftMirr
+        } else {
+            NtfsRegion::Other
+        };
+
+        Ok(region)
+    }
+
+    /// Stores a copy of the given File Record in the File Record cache (if enabled).
+    #[cfg(feature = "std")]
+    fn cache_file(&self, file: &NtfsFile) {
+        if let Some(cache) = &self.file_record_cache {
+            let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+            cache.insert(file.file_record_number(), file.record().clone());
+        }
+    }
+
+    /// Returns the File Record with the given number from the File Record cache (if enabled and cached).
+    #[cfg(feature = "std")]
+    fn cached_file(&self, file_record_number: u64) -> Option<NtfsFile> {
+        let cache = self.file_record_cache.as_ref()?;
+        let record = cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(file_record_number)?;
+
+        Some(NtfsFile::from_record(self, record, file_record_number))
+    }
+
+    /// Removes all File Records from the cache enabled via [`Ntfs::set_file_record_cache_capacity`].
+    ///
+    /// Call this if the filesystem has been modified by other means than this crate.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn clear_file_record_cache(&self) {
+        if let Some(cache) = &self.file_record_cache {
+            cache.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        }
+    }
+
     /// Returns the size of a single cluster, in bytes.
     pub fn cluster_size(&self) -> u32 {
         self.cluster_size
     }
 
+    /// Returns the absolute byte position right after the last byte of this NTFS filesystem.
+    fn end_position(&self) -> u64 {
+        // `Ntfs::new_at_offset` has checked that this addition cannot overflow.
+        self.partition_offset + self.size
+    }
+
     /// Returns the [`NtfsFile`] for the given NTFS File Record Number.
     ///
     /// The first few NTFS files have fixed indexes and contain filesystem
     /// management information (see the [`KnownNtfsFileRecordNumber`] enum).
+    ///
+    /// This function also returns File Records that are not in use (i.e. belong to deleted files),
+    /// which is what recovery tools need.
+    /// Use [`Ntfs::file_in_use`] to only get File Records of existing files.
     pub fn file<'n, T>(&'n self, fs: &mut T, file_record_number: u64) -> Result<NtfsFile<'n>>
     where
         T: Read + Seek,
     {
+        #[cfg(feature = "std")]
+        if let Some(file) = self.cached_file(file_record_number) {
+            return Ok(file);
+        }
+
+        let (data, position) = if self.mft_dump {
+            self.read_mft_dump_record_data(fs, file_record_number)?
+        } else if let Some(mft_data_runs) = &self.mft_data_runs {
+            mft_data_runs.read_file_record_data(self, fs, file_record_number)?
+        } else {
+            let mft = self.mft(fs)?;
+            self.read_file_record_data(fs, &mft, file_record_number)?
+        };
+        let file = NtfsFile::from_data(self, data, position, file_record_number)?;
+
+        #[cfg(feature = "std")]
+        self.cache_file(&file);
+
+        Ok(file)
+    }
+
+    /// Async variant of [`Ntfs::file`] for readers implementing [`AsyncNtfsReadSeek`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn file_async<'n, T>(
+        &'n self,
+        fs: &mut T,
+        file_record_number: u64,
+    ) -> Result<NtfsFile<'n>>
+    where
+        T: AsyncNtfsReadSeek,
+    {
+        let mut cache = AsyncBlockCache::new(fs).await?;
+        cache.run(|fs| self.file(fs, file_record_number)).await
+    }
+
+    /// Returns the [`NtfsFile`] for the given NTFS File Record Number, but fails with
+    /// [`NtfsError::FileRecordNotInUse`] if the File Record is not in use.
+    ///
+    /// Contrary to [`Ntfs::file`], this never returns a File Record that has been freed after deleting its file.
+    /// This is what you want for regular filesystem browsing, where a stale directory entry may still
+    /// reference such a record.
+    /// [`NtfsIndexEntry::to_file`] uses this function.
+    ///
+    /// [`NtfsIndexEntry::to_file`]: crate::NtfsIndexEntry::to_file
+    pub fn file_in_use<'n, T>(&'n self, fs: &mut T, file_record_number: u64) -> Result<NtfsFile<'n>>
+    where
+        T: Read + Seek,
+    {
+        let file = self.file(fs, file_record_number)?;
+
+        if !file.flags().contains(NtfsFileFlags::IN_USE) {
+            return Err(NtfsError::FileRecordNotInUse { file_record_number });
+        }
+
+        Ok(file)
+    }
+
+    /// Returns the [`NtfsFile`] for the File Record at the given absolute byte position.
+    ///
+    /// Unlike [`Ntfs::file`], this doesn't look up the File Record via the MFT.
+    /// This is useful for recovery tools that need to read File Records from $MFTMirr or from an MFT
+    /// whose first File Record is damaged.
+    ///
+    /// The caller is responsible for passing a position that is actually the start of a File Record.
+    /// Only the signature, fixups, and sizes of the File Record are validated.
+    /// `file_record_number` is not checked against the File Record, but returned by
+    /// [`NtfsFile::file_record_number`] and used to look up extension records via the regular MFT.
+    pub fn file_at_position<'n, T>(
+        &'n self,
+        fs: &mut T,
+        position: NtfsPosition,
+        file_record_number: u64,
+    ) -> Result<NtfsFile<'n>>
+    where
+        T: Read + Seek,
+    {
+        let record_position = position
+            .value()
+            .ok_or(NtfsError::InvalidFileRecordPosition { position })?;
+        let record_end = record_position
+            .get()
+            .checked_add(self.file_record_size as u64)
+            .ok_or(NtfsError::InvalidFileRecordPosition { position })?;
+        if record_end > self.end_position() {
+            return Err(NtfsError::InvalidFileRecordPosition { position });
+        }
+
+        NtfsFile::new(self, fs, record_position, file_record_number)
+    }
+
+    /// Reads the raw bytes of the File Record with the given number (without applying any fixups)
+    /// via the $DATA attribute of the passed MFT File Record, or via the Data Runs cached by
+    /// [`Ntfs::read_mft_data_runs`] if available.
+    ///
+    /// Returns the bytes along with the absolute byte position of the File Record.
+    pub(crate) fn read_file_record_data<T>(
+        &self,
+        fs: &mut T,
+        mft: &NtfsFile,
+        file_record_number: u64,
+    ) -> Result<(Vec<u8>, NtfsPosition)>
+    where
+        T: Read + Seek,
+    {
+        if self.mft_dump {
+            return self.read_mft_dump_record_data(fs, file_record_number);
+        }
+
+        if let Some(mft_data_runs) = &self.mft_data_runs {
+            return mft_data_runs.read_file_record_data(self, fs, file_record_number);
+        }
+
         let offset = file_record_number
             .checked_mul(self.file_record_size as u64)
             .ok_or(NtfsError::InvalidFileRecordNumber { file_record_number })?;
@@ -93,123 +703,1769 @@ impl Ntfs {
         // The MFT may be split into multiple data runs, referenced by its $DATA attribute.
         // We therefore read it just like any other non-resident attribute value.
         // However, this code assumes that the MFT does not have an Attribute List!
-        //
-        // This unwrap is safe, because `self.mft_position` has been checked in `Ntfs::new`.
-        let mft = NtfsFile::new(self, fs, self.mft_position.value().unwrap(), 0)?;
         let mft_data_attribute =
             mft.find_resident_attribute(NtfsAttributeType::Data, None, None)?;
         let mut mft_data_value = mft_data_attribute.value(fs)?;
 
-        mft_data_value.seek(fs, SeekFrom::Start(offset))?;
-        let position = mft_data_value
-            .data_position()
-            .value()
+        let record_end = offset
+            .checked_add(self.file_record_size as u64)
             .ok_or(NtfsError::InvalidFileRecordNumber { file_record_number })?;
+        if record_end > mft_data_value.len() {
+            return Err(NtfsError::InvalidFileRecordNumber { file_record_number });
+        }
 
-        NtfsFile::new(self, fs, position, file_record_number)
-    }
+        mft_data_value.seek(fs, SeekFrom::Start(offset))?;
+        let position = mft_data_value.data_position();
+        if position.value().is_none() {
+            return Err(NtfsError::InvalidFileRecordNumber { file_record_number });
+        }
 
-    /// Returns the size of a File Record of this NTFS filesystem, in bytes.
-    pub fn file_record_size(&self) -> u32 {
-        self.file_record_size
-    }
+        // A File Record may span two Data Runs of the MFT (e.g. with a cluster size smaller than the
+        // File Record size), so we must read it through the MFT $DATA value and not directly from `position`.
+        let mut data = vec![0; self.file_record_size as usize];
+        mft_data_value.read_exact(fs, &mut data)?;
 
-    /// Returns the absolute byte position of the Master File Table (MFT).
-    ///
-    /// This [`NtfsPosition`] is guaranteed to be nonzero.
-    pub fn mft_position(&self) -> NtfsPosition {
-        self.mft_position
+        Ok((data, position))
     }
 
-    /// Reads the $UpCase file from the filesystem and stores it in this [`Ntfs`] object.
-    ///
-    /// This function only needs to be called if case-insensitive comparisons are later performed
-    /// (i.e. finding files).
-    pub fn read_upcase_table<T>(&mut self, fs: &mut T) -> Result<()>
+    /// Reads the raw bytes of a File Record from a reader that only contains a dump of the MFT
+    /// (see [`Ntfs::new_from_mft_parameters`]).
+    fn read_mft_dump_record_data<T>(
+        &self,
+        fs: &mut T,
+        file_record_number: u64,
+    ) -> Result<(Vec<u8>, NtfsPosition)>
     where
         T: Read + Seek,
     {
-        let upcase_table = UpcaseTable::read(self, fs)?;
-        self.upcase_table = Some(upcase_table);
-        Ok(())
+        let offset = file_record_number
+            .checked_mul(self.file_record_size as u64)
+            .ok_or(NtfsError::InvalidFileRecordNumber { file_record_number })?;
+
+        let mut data = vec![0; self.file_record_size as usize];
+        fs.seek(SeekFrom::Start(offset))?;
+        fs.read_exact(&mut data)?;
+
+        Ok((data, NtfsPosition::new(offset)))
     }
 
-    /// Returns the root directory of this NTFS volume as an [`NtfsFile`].
-    pub fn root_directory<'n, T>(&'n self, fs: &mut T) -> Result<NtfsFile<'n>>
+    /// Returns an [`NtfsHealthSummary`] describing whether this volume has been cleanly unmounted.
+    ///
+    /// It combines the $VOLUME_INFORMATION flags, the state of the $LogFile, a comparison of the MFT
+    /// with $MFTMirr, and the volume creation time.
+    /// Each of them is `None` if the underlying structure is corrupted, while the others are still reported.
+    /// Only I/O errors are returned as errors.
+    pub fn health_summary<T>(&self, fs: &mut T) -> Result<NtfsHealthSummary>
     where
         T: Read + Seek,
     {
-        self.file(fs, KnownNtfsFileRecordNumber::RootDirectory as u64)
+        NtfsHealthSummary::read(self, fs)
     }
 
-    /// Returns the size of a single sector in bytes.
-    pub fn sector_size(&self) -> u16 {
-        self.sector_size
+    /// Returns whether this [`Ntfs`] reads from a raw dump of the MFT instead of an entire NTFS partition
+    /// (see [`Ntfs::new_from_mft_parameters`]).
+    pub fn is_mft_dump(&self) -> bool {
+        self.mft_dump
     }
 
-    /// Returns the 64-bit serial number of this NTFS volume.
-    pub fn serial_number(&self) -> u64 {
-        self.serial_number
+    /// Returns whether the reader is shorter than the filesystem, as determined by [`Ntfs::check_backing_size`].
+    ///
+    /// This is `None` if [`Ntfs::check_backing_size`] has not been called.
+    pub fn is_truncated(&self) -> Option<bool> {
+        let backing_size = self.backing_size?;
+        Some(self.partition_offset + self.size > backing_size)
     }
 
-    /// Returns the partition size in bytes.
-    pub fn size(&self) -> u64 {
-        self.size
+    /// Returns whether the File Record with the given number is marked as in use in the $BITMAP attribute of the MFT.
+    ///
+    /// This is useful to check a File Record before reading it, e.g. during recovery.
+    /// Note that [`Ntfs::file_in_use`] checks the flags of the File Record itself instead.
+    ///
+    /// Returns [`NtfsError::InvalidFileRecordNumber`] if the number is not below [`Ntfs::mft_record_count`].
+    pub fn is_file_record_allocated<T>(&self, fs: &mut T, file_record_number: u64) -> Result<bool>
+    where
+        T: Read + Seek,
+    {
+        let mft = self.mft(fs)?;
+        if file_record_number >= self.mft_data_record_count(&mft, fs)? {
+            return Err(NtfsError::InvalidFileRecordNumber { file_record_number });
+        }
+
+        let bitmap_item = Self::mft_bitmap(&mft, fs)?;
+        let bitmap_attribute = bitmap_item.to_attribute()?;
+        let mut bitmap_value = bitmap_attribute.value(fs)?;
+
+        // Bytes beyond the initialized size of the $BITMAP attribute read as zeros.
+        let byte_offset = file_record_number / 8;
+        let initialized_size = u64::min(bitmap_attribute.initialized_size(), bitmap_value.len());
+        if byte_offset >= initialized_size {
+            return Ok(false);
+        }
+
+        let mut byte = [0u8];
+        bitmap_value.seek(fs, SeekFrom::Start(byte_offset))?;
+        bitmap_value.read_exact(fs, &mut byte)?;
+
+        Ok(byte[0] & (1 << (file_record_number % 8)) != 0)
     }
 
-    /// Returns the stored [`UpcaseTable`].
-    ///
-    /// # Panics
+    /// Returns whether the given File Record Number belongs to a system file.
     ///
-    /// Panics if [`read_upcase_table`][Ntfs::read_upcase_table] had not been called.
-    pub(crate) fn upcase_table(&self) -> &UpcaseTable {
-        self.upcase_table
-            .as_ref()
-            .expect("You need to call read_upcase_table first")
+    /// This is the case for all [`KnownNtfsFileRecordNumber`] entries and the File Records 12 to 15,
+    /// which are reserved for future system files.
+    /// Note that files in the $Extend directory (like $Quota or $UsnJrnl) have regular File Record Numbers
+    /// and are not covered by this check.
+    pub fn is_system_record(file_record_number: u64) -> bool {
+        file_record_number < FIRST_USER_FILE_RECORD_NUMBER
     }
 
-    /// Returns an [`NtfsVolumeInformation`] containing general information about
-    /// the volume, like the NTFS version.
-    pub fn volume_info<T>(&self, fs: &mut T) -> Result<NtfsVolumeInformation>
+    /// Returns the size of a File Record of this NTFS filesystem, in bytes.
+    pub fn file_record_size(&self) -> u32 {
+        self.file_record_size
+    }
+
+    /// Returns an [`NtfsLogFileStatus`] describing the state of the NTFS journal ($LogFile).
+    pub fn log_file_status<T>(&self, fs: &mut T) -> Result<NtfsLogFileStatus>
     where
         T: Read + Seek,
     {
-        let volume_file = self.file(fs, KnownNtfsFileRecordNumber::Volume as u64)?;
-        volume_file.find_resident_attribute_structured_value::<NtfsVolumeInformation>(None)
+        NtfsLogFileStatus::read(self, fs)
     }
 
-    /// Returns an [`NtfsVolumeName`] to read the volume name (also called volume label)
-    /// of this NTFS volume.
-    ///
-    /// Note that a volume may also have no label, which is why the return value is further
-    /// encapsulated in an `Option`.
-    pub fn volume_name<T>(&self, fs: &mut T) -> Option<Result<NtfsVolumeName>>
+    /// Returns the [`NtfsFile`] of the Master File Table (MFT), which is read directly from [`Ntfs::mft_position`].
+    fn mft<T>(&self, fs: &mut T) -> Result<NtfsFile>
     where
         T: Read + Seek,
     {
-        let volume_file = iter_try!(self.file(fs, KnownNtfsFileRecordNumber::Volume as u64));
+        let file_record_number = KnownNtfsFileRecordNumber::MFT as u64;
 
-        match volume_file.find_resident_attribute_structured_value::<NtfsVolumeName>(None) {
-            Ok(volume_name) => Some(Ok(volume_name)),
-            Err(NtfsError::AttributeNotFound { .. }) => None,
-            Err(e) => Some(Err(e)),
+        #[cfg(feature = "std")]
+        if let Some(mft) = self.cached_file(file_record_number) {
+            return Ok(mft);
         }
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        if self.mft_dump {
+            return self.file(fs, file_record_number);
+        }
 
-    #[test]
-    fn test_basics() {
-        let mut testfs1 = crate::helpers::tests::testfs1();
-        let ntfs = Ntfs::new(&mut testfs1).unwrap();
-        assert_eq!(ntfs.cluster_size(), 512);
+        let mft_position = self
+            .mft_position
+            .value()
+            .expect("mft_position is validated to be nonzero in Ntfs::new");
+        let mft = NtfsFile::new(self, fs, mft_position, file_record_number)?;
+
+        #[cfg(feature = "std")]
+        self.cache_file(&mft);
+
+        Ok(mft)
+    }
+
+    /// Returns the absolute byte position of the mirror copy of the first MFT File Records ($MFTMirr).
+    ///
+    /// It is taken from the BIOS Parameter Block, so it can be used even if the MFT is damaged.
+    pub fn mft_mirror_position(&self) -> NtfsPosition {
+        self.mft_mirror_position
+    }
+
+    /// Returns the absolute byte position of the Master File Table (MFT).
+    ///
+    /// This [`NtfsPosition`] is guaranteed to be nonzero, unless this [`Ntfs`] has been created by
+    /// [`Ntfs::new_from_mft_parameters`], where it has no value.
+    pub fn mft_position(&self) -> NtfsPosition {
+        self.mft_position
+    }
+
+    /// Returns the $BITMAP attribute of the given MFT File Record, which marks the File Records in use.
+    fn mft_bitmap<'n, 'f, T>(mft: &'f NtfsFile<'n>, fs: &mut T) -> Result<NtfsAttributeItem<'n, 'f>>
+    where
+        T: Read + Seek,
+    {
+        mft.stream(fs, NtfsAttributeType::Bitmap, "")
+            .ok_or(NtfsError::AttributeNotFound {
+                position: mft.position(),
+                ty: NtfsAttributeType::Bitmap,
+            })?
+    }
+
+    /// Returns the number of File Records covered by the $DATA attribute of the given MFT File Record.
+    fn mft_data_record_count<T>(&self, mft: &NtfsFile, fs: &mut T) -> Result<u64>
+    where
+        T: Read + Seek,
+    {
+        let data_item = mft.data(fs, "").ok_or(NtfsError::AttributeNotFound {
+            position: mft.position(),
+            ty: NtfsAttributeType::Data,
+        })??;
+        let data_attribute = data_item.to_attribute()?;
+
+        Ok(data_attribute.value_length() / self.file_record_size as u64)
+    }
+
+    /// Returns the total number of File Records in the Master File Table (MFT), whether in use or not.
+    ///
+    /// This is the size of the $DATA attribute of the MFT divided by the File Record size.
+    /// Valid File Record Numbers range from zero to this number (exclusive).
+    pub fn mft_record_count<T>(&self, fs: &mut T) -> Result<u64>
+    where
+        T: Read + Seek,
+    {
+        let mft = self.mft(fs)?;
+        self.mft_data_record_count(&mft, fs)
+    }
+
+    /// Returns the number of File Records in use, as marked in the $BITMAP attribute of the MFT.
+    ///
+    /// The $BITMAP attribute is usually larger than required.
+    /// Bits beyond [`Ntfs::mft_record_count`] are ignored.
+    pub fn mft_records_in_use<T>(&self, fs: &mut T) -> Result<u64>
+    where
+        T: Read + Seek,
+    {
+        let mft = self.mft(fs)?;
+        let mut remaining_records = self.mft_data_record_count(&mft, fs)?;
+        let bitmap_item = Self::mft_bitmap(&mft, fs)?;
+        let bitmap_attribute = bitmap_item.to_attribute()?;
+
+        let mut buf = vec![0u8; 4096];
+        let mut records_in_use = 0;
+
+        bitmap_attribute.for_each_value_chunk(fs, &mut buf, |chunk| {
+            for byte in chunk {
+                if remaining_records == 0 {
+                    break;
+                }
+
+                let bit_count = u64::min(remaining_records, 8);
+                let mask = ((1u16 << bit_count) - 1) as u8;
+                records_in_use += (byte & mask).count_ones() as u64;
+                remaining_records -= bit_count;
+            }
+
+            Ok::<(), NtfsError>(())
+        })?;
+
+        Ok(records_in_use)
+    }
+
+    /// Returns the byte position of this NTFS filesystem within the reader, as passed to [`Ntfs::new_at_offset`].
+    ///
+    /// This is zero for an [`Ntfs`] object created via [`Ntfs::new`].
+    pub fn partition_offset(&self) -> u64 {
+        self.partition_offset
+    }
+
+    /// Reads the clusters marked as bad from the $Bad stream of the $BadClus file
+    /// and stores them in this [`Ntfs`] object.
+    ///
+    /// Afterwards, reading a Data Run that covers one of these clusters is handled according to the
+    /// [`NtfsBadClusterPolicy`] set via [`Ntfs::set_bad_cluster_policy`].
+    /// This only applies to Data Runs that are read from their attributes after calling this function.
+    pub fn read_bad_clusters<T>(&mut self, fs: &mut T) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        let mut bad_clusters = Vec::new();
+
+        {
+            let file = self.file(fs, KnownNtfsFileRecordNumber::BadClus as u64)?;
+
+            // $Bad is a sparse stream spanning the entire volume.
+            // Each bad cluster is mapped into it at a VCN equal to its LCN, so all non-sparse
+            // Data Runs denote bad clusters.
+            // The stream may be split over multiple extents (connected attributes), which may also be stored
+            // in extension records.
+            // We walk all of them directly instead of reading the value, because only the allocated Data Runs matter.
+            let mut attribute_list = None;
+
+            for attribute in file.attributes_raw() {
+                let attribute = attribute?;
+                let ty = attribute.ty()?;
+
+                if ty == NtfsAttributeType::AttributeList {
+                    attribute_list = Some(attribute.structured_value::<T, NtfsAttributeList>(fs)?);
+                } else if ty == NtfsAttributeType::Data && attribute.name()? == "$Bad" {
+                    self.push_bad_clusters(&attribute, &mut bad_clusters)?;
+                }
+            }
+
+            // Extents in extension records are only referenced by the Attribute List.
+            if let Some(attribute_list) = attribute_list {
+                let mut entries = attribute_list.entries();
+
+                while let Some(entry) = entries.next(fs) {
+                    let entry = entry?;
+
+                    if entry.base_file_reference().file_record_number() == file.file_record_number()
+                        || entry.ty()? != NtfsAttributeType::Data
+                        || entry.name() != "$Bad"
+                    {
+                        continue;
+                    }
+
+                    let entry_file = entry.to_file(self, fs)?;
+                    let attribute = entry.to_attribute(&entry_file)?;
+                    self.push_bad_clusters(&attribute, &mut bad_clusters)?;
+                }
+            }
+        }
+
+        bad_clusters.sort_unstable_by_key(|range| range.start);
+
+        let mut merged_bad_clusters: Vec<Range<Lcn>> = Vec::with_capacity(bad_clusters.len());
+        for range in bad_clusters {
+            match merged_bad_clusters.last_mut() {
+                Some(last) if range.start <= last.end => last.end = Lcn::max(last.end, range.end),
+                _ => merged_bad_clusters.push(range),
+            }
+        }
+
+        self.bad_clusters = merged_bad_clusters;
+        Ok(())
+    }
+
+    /// Adds the LCN ranges of all allocated Data Runs of a single extent of the $Bad stream to `bad_clusters`.
+    fn push_bad_clusters(
+        &self,
+        attribute: &NtfsAttribute,
+        bad_clusters: &mut Vec<Range<Lcn>>,
+    ) -> Result<()> {
+        if attribute.is_resident() {
+            return Ok(());
+        }
+
+        let cluster_size = self.cluster_size as u64;
+
+        for data_run in attribute.non_resident_value()?.data_runs() {
+            let data_run = data_run?;
+
+            if let Some(position) = data_run.data_position().value() {
+                let start = (position.get() - self.partition_offset) / cluster_size;
+                let end = start + data_run.allocated_size() / cluster_size;
+                bad_clusters.push(Lcn::from(start)..Lcn::from(end));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the $UpCase file from the filesystem and stores it in this [`Ntfs`] object.
+    ///
+    /// This function only needs to be called if case-insensitive comparisons are later performed
+    /// (i.e. finding files).
+    ///
+    /// A truncated $UpCase file is tolerated: The missing characters are taken from
+    /// [`UpcaseTable::default_windows`] and [`Ntfs::upcase_table_source`] reports
+    /// [`NtfsUpcaseTableSource::Truncated`] afterwards.
+    /// A $UpCase file larger than the table size is rejected with [`NtfsError::InvalidUpcaseTableSize`].
+    pub fn read_upcase_table<T>(&mut self, fs: &mut T) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        let upcase_table = UpcaseTable::read(self, fs)?;
+        self.upcase_table = Some(upcase_table);
+        Ok(())
+    }
+
+    /// Async variant of [`Ntfs::read_upcase_table`] for readers implementing [`AsyncNtfsReadSeek`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn read_upcase_table_async<T>(&mut self, fs: &mut T) -> Result<()>
+    where
+        T: AsyncNtfsReadSeek,
+    {
+        let mut cache = AsyncBlockCache::new(fs).await?;
+        cache.run(|fs| self.read_upcase_table(fs)).await
+    }
+
+    /// Reads the Data Runs of the $DATA attribute of the MFT from File Record 0
+    /// and stores them in this [`Ntfs`] object.
+    ///
+    /// With these Data Runs, [`Ntfs::file`] translates a File Record Number to the position of the File Record
+    /// without reading File Record 0 every time.
+    /// [`Ntfs::new`] already calls this function, so it only needs to be called again if the MFT has been
+    /// extended or moved by other means than this crate.
+    /// The cached Data Runs are never invalidated otherwise.
+    ///
+    /// If File Record 0 cannot be read, the previously cached Data Runs are discarded and the error is returned.
+    /// [`Ntfs::file`] then reads File Record 0 on every call again.
+    pub fn read_mft_data_runs<T>(&mut self, fs: &mut T) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        self.mft_data_runs = None;
+
+        if !self.mft_dump {
+            let mft_data_runs = MftDataRuns::read(self, fs)?;
+            self.mft_data_runs = Some(mft_data_runs);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the size of the $Boot file and the Data Runs of the $MFT and $MFTMirr files
+    /// and stores them in this [`Ntfs`] object.
+    ///
+    /// This function only needs to be called if [`Ntfs::classify_position`] is used later.
+    pub fn read_system_regions<T>(&mut self, fs: &mut T) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        // The $Boot file always starts at LCN 0, which cannot be expressed as an `NtfsPosition`
+        // and would therefore be indistinguishable from a sparse Data Run.
+        let boot_file = self.file(fs, KnownNtfsFileRecordNumber::Boot as u64)?;
+        let boot_data_attribute =
+            boot_file.find_resident_attribute(NtfsAttributeType::Data, Some(""), None)?;
+        let boot_size = boot_data_attribute.value_length();
+
+        let system_regions = SystemRegions {
+            boot_size,
+            mft: self.read_data_ranges(fs, KnownNtfsFileRecordNumber::MFT as u64)?,
+            mft_mirr: self.read_data_ranges(fs, KnownNtfsFileRecordNumber::MFTMirr as u64)?,
+        };
+        self.system_regions = Some(system_regions);
+        Ok(())
+    }
+
+    /// Returns the byte ranges occupied by the unnamed $DATA attribute of the given system file,
+    /// sorted by their start.
+    ///
+    /// Like [`Ntfs::read_file_record_data`], this assumes that the file does not have an Attribute List.
+    fn read_data_ranges<T>(&self, fs: &mut T, file_record_number: u64) -> Result<Vec<Range<u64>>>
+    where
+        T: Read + Seek,
+    {
+        let file = self.file(fs, file_record_number)?;
+        let data_attribute =
+            file.find_resident_attribute(NtfsAttributeType::Data, Some(""), None)?;
+        let mut ranges = Vec::new();
+
+        if let NtfsAttributeValue::NonResident(value) = data_attribute.value(fs)? {
+            for data_run in value.data_runs() {
+                let data_run = data_run?;
+
+                // Sparse Data Runs don't occupy any space on the filesystem.
+                if let Some(position) = data_run.data_position().value() {
+                    let start = position.get();
+                    let end = start.saturating_add(data_run.allocated_size());
+                    ranges.push(start..end);
+                }
+            }
+        }
+
+        ranges.sort_unstable_by_key(|range| range.start);
+        Ok(ranges)
+    }
+
+    /// Resolves many File Record Numbers to the preferred name of each file and a reference to its parent directory
+    /// (see [`NtfsResolvedName`]).
+    ///
+    /// This is much faster than calling [`Ntfs::file`] and [`NtfsFile::name`] for each File Record Number,
+    /// e.g. when post-processing a USN journal.
+    /// Each requested File Record is read only once, and all File Records are read in the order of their
+    /// positions on the filesystem.
+    ///
+    /// The returned names are in the same order as `record_numbers`.
+    /// A name is `None` if the File Record Number is out of range, the File Record is not in use
+    /// (i.e. the file has been deleted), it is an extension record, or it has no $FILE_NAME attribute.
+    /// A [`NtfsFileNamespace::Win32`] name is preferred over a [`NtfsFileNamespace::Win32AndDos`] name,
+    /// which is preferred over all others.
+    ///
+    /// Errors reading or parsing an in-use File Record abort the entire lookup.
+    ///
+    /// [`NtfsFileNamespace::Win32`]: crate::structured_values::NtfsFileNamespace::Win32
+    /// [`NtfsFileNamespace::Win32AndDos`]: crate::structured_values::NtfsFileNamespace::Win32AndDos
+    pub fn resolve_names<T>(
+        &self,
+        fs: &mut T,
+        record_numbers: &[u64],
+    ) -> Result<Vec<Option<NtfsResolvedName>>>
+    where
+        T: Read + Seek,
+    {
+        NtfsResolvedName::resolve(self, fs, record_numbers)
+    }
+
+    /// Returns the root directory of this NTFS volume as an [`NtfsFile`].
+    pub fn root_directory<'n, T>(&'n self, fs: &mut T) -> Result<NtfsFile<'n>>
+    where
+        T: Read + Seek,
+    {
+        self.file(fs, KnownNtfsFileRecordNumber::RootDirectory as u64)
+    }
+
+    /// Sets how reads from clusters marked as bad are handled (see [`NtfsBadClusterPolicy`]).
+    pub fn set_bad_cluster_policy(&mut self, bad_cluster_policy: NtfsBadClusterPolicy) {
+        self.bad_cluster_policy = bad_cluster_policy;
+    }
+
+    /// Enables a cache of the `capacity` most recently used File Records, or disables it if `capacity` is zero.
+    ///
+    /// [`Ntfs::file`] (and therefore every function opening a File Record, like [`NtfsIndexEntry::to_file`])
+    /// consults this cache before reading from the filesystem.
+    /// This speeds up workloads that open the same File Records over and over again, e.g. iterating
+    /// the attributes of files with Attribute Lists, which opens the extension records for every attribute.
+    ///
+    /// The cache is disabled by default.
+    /// File Records modified via this crate are removed from the cache, but you need to call
+    /// [`Ntfs::clear_file_record_cache`] if the filesystem has been modified by other means.
+    /// Changing the capacity clears the cache.
+    ///
+    /// [`NtfsIndexEntry::to_file`]: crate::NtfsIndexEntry::to_file
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn set_file_record_cache_capacity(&mut self, capacity: usize) {
+        self.file_record_cache = if capacity > 0 {
+            Some(std::sync::Mutex::new(FileRecordCache::new(capacity)))
+        } else {
+            None
+        };
+    }
+
+    /// Stores the given [`UpcaseTable`] in this [`Ntfs`] object, replacing any previously stored table.
+    ///
+    /// This is an alternative to [`Ntfs::read_upcase_table`] when the $UpCase file of the filesystem
+    /// is missing or damaged.
+    /// [`UpcaseTable::default_windows`] provides a suitable replacement in that case.
+    pub fn set_upcase_table(&mut self, upcase_table: UpcaseTable) {
+        self.upcase_table = Some(upcase_table);
+    }
+
+    /// Sets how strictly on-disk structures are validated (see [`NtfsValidationMode`]).
+    pub fn set_validation_mode(&mut self, validation_mode: NtfsValidationMode) {
+        self.validation_mode = validation_mode;
+
+        // Cached File Records may have been accepted in a more lenient mode.
+        #[cfg(feature = "std")]
+        self.clear_file_record_cache();
+    }
+
+    /// Sets a handler that is called for every inconsistency tolerated in [`NtfsValidationMode::Lenient`].
+    ///
+    /// The inconsistency is passed as the [`NtfsError`] that would have been returned in
+    /// [`NtfsValidationMode::Strict`].
+    pub fn set_warning_handler<F>(&mut self, warning_handler: F)
+    where
+        F: Fn(&NtfsError) + Send + Sync + 'static,
+    {
+        self.warning_handler = Some(Box::new(warning_handler));
+    }
+
+    /// Returns the size of a single sector in bytes.
+    pub fn sector_size(&self) -> u16 {
+        self.sector_size
+    }
+
+    /// Returns the 64-bit serial number of this NTFS volume.
+    pub fn serial_number(&self) -> u64 {
+        self.serial_number
+    }
+
+    /// Returns the partition size in bytes.
+    ///
+    /// This does not include the partition offset passed to [`Ntfs::new_at_offset`].
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Removes the File Record with the given number from the File Record cache (if enabled and cached).
+    #[cfg(feature = "std")]
+    pub(crate) fn uncache_file_record(&self, file_record_number: u64) {
+        if let Some(cache) = &self.file_record_cache {
+            cache
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(file_record_number);
+        }
+    }
+
+    /// Returns an [`NtfsUpcaseInfo`] with information about the $UpCase table, read from the $Info data stream
+    /// of the $UpCase file.
+    ///
+    /// Filesystems created before Windows 8 have no such stream, which is why the return value is further
+    /// encapsulated in an `Option`.
+    pub fn upcase_info<T>(&self, fs: &mut T) -> Result<Option<NtfsUpcaseInfo>>
+    where
+        T: Read + Seek,
+    {
+        NtfsUpcaseInfo::read(self, fs)
+    }
+
+    /// Returns the [`UpcaseTable`] stored via [`Ntfs::read_upcase_table`] or [`Ntfs::set_upcase_table`],
+    /// or `None` if no table has been stored yet.
+    pub fn upcase_table(&self) -> Option<&UpcaseTable> {
+        self.upcase_table.as_ref()
+    }
+
+    /// Returns the stored [`UpcaseTable`], or [`NtfsError::UpcaseTableNotLoaded`] if neither
+    /// [`read_upcase_table`][Ntfs::read_upcase_table] nor [`set_upcase_table`][Ntfs::set_upcase_table]
+    /// had been called.
+    pub(crate) fn upcase_table_or_error(&self) -> Result<&UpcaseTable> {
+        self.upcase_table
+            .as_ref()
+            .ok_or(NtfsError::UpcaseTableNotLoaded)
+    }
+
+    /// Returns where the stored [`UpcaseTable`] comes from, or `None` if no table has been stored yet.
+    ///
+    /// Check this for [`NtfsUpcaseTableSource::Truncated`] after calling [`Ntfs::read_upcase_table`]
+    /// to find out whether parts of the table had to be generated.
+    pub fn upcase_table_source(&self) -> Option<NtfsUpcaseTableSource> {
+        self.upcase_table
+            .as_ref()
+            .map(|upcase_table| upcase_table.source())
+    }
+
+    /// Checks that the MFT begins within this NTFS volume and at least its first File Record fits into it.
+    fn validate_mft_position(&self) -> Result<()> {
+        let mft_position = self
+            .mft_position
+            .value()
+            .ok_or(NtfsError::InvalidMftLcn)?
+            .get();
+        let first_file_record_end = mft_position
+            .checked_add(self.file_record_size as u64)
+            .ok_or(NtfsError::InvalidMftLcn)?;
+
+        if first_file_record_end > self.end_position() {
+            return Err(NtfsError::InvalidMftLcn);
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the MFT Mirror begins within this NTFS volume and at least its first File Record fits into it.
+    fn validate_mft_mirror_position(&self) -> Result<()> {
+        let mft_mirror_position = self
+            .mft_mirror_position
+            .value()
+            .ok_or(NtfsError::InvalidMftMirrorLcn)?
+            .get();
+        let first_file_record_end = mft_mirror_position
+            .checked_add(self.file_record_size as u64)
+            .ok_or(NtfsError::InvalidMftMirrorLcn)?;
+
+        if first_file_record_end > self.end_position() {
+            return Err(NtfsError::InvalidMftMirrorLcn);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the [`NtfsValidationMode`] set via [`Ntfs::set_validation_mode`].
+    pub fn validation_mode(&self) -> NtfsValidationMode {
+        self.validation_mode
+    }
+
+    /// Handles an inconsistency that does not affect the bounds used for further parsing.
+    ///
+    /// Returns the passed error in [`NtfsValidationMode::Strict`].
+    /// In [`NtfsValidationMode::Lenient`], it is passed to the warning handler instead and parsing can continue.
+    pub(crate) fn tolerate(&self, error: NtfsError) -> Result<()> {
+        match self.validation_mode {
+            NtfsValidationMode::Strict => Err(error),
+            NtfsValidationMode::Lenient => {
+                if let Some(warning_handler) = &self.warning_handler {
+                    warning_handler(&error);
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Checks all in-use File Records of the MFT for inconsistencies, similar to a filesystem check.
+    ///
+    /// For every File Record, this checks the signature, fixups, and sizes, the length and alignment of
+    /// every attribute, the bounds of resident values, and the order of Attribute List entries.
+    /// Depending on `options`, it also checks that all Data Runs lie within the volume and that every
+    /// $FILE_NAME attribute references an in-use directory as its parent.
+    /// Indexes are not checked.
+    ///
+    /// Inconsistencies are collected in the returned [`NtfsVerificationReport`].
+    /// Only I/O errors and errors reading the MFT itself are returned as errors.
+    pub fn verify<T>(
+        &self,
+        fs: &mut T,
+        options: &NtfsVerifyOptions,
+    ) -> Result<NtfsVerificationReport>
+    where
+        T: Read + Seek,
+    {
+        NtfsVerificationReport::verify(self, fs, options)
+    }
+
+    /// Compares the first File Records of the MFT with their copies in $MFTMirr and returns all
+    /// File Records that don't match (see [`NtfsMftMirrorMismatch`]).
+    ///
+    /// Both copies are read from the positions given in the BIOS Parameter Block (see [`Ntfs::mft_position`]
+    /// and [`Ntfs::mft_mirror_position`]), so this also detects a damaged beginning of the MFT.
+    /// Their contents are compared after applying fixups.
+    /// An empty list means that the MFT and $MFTMirr are consistent.
+    ///
+    /// Only I/O errors are returned as errors.
+    /// File Records that fail to parse are reported as mismatches.
+    pub fn verify_mft_mirror<T>(&self, fs: &mut T) -> Result<Vec<NtfsMftMirrorMismatch>>
+    where
+        T: Read + Seek,
+    {
+        NtfsMftMirrorMismatch::verify(self, fs)
+    }
+
+    /// Returns the creation time of this NTFS volume.
+    ///
+    /// This is the creation time stored in the $STANDARD_INFORMATION attribute of the $Volume file,
+    /// which is set when the filesystem is formatted.
+    pub fn volume_creation_time<T>(&self, fs: &mut T) -> Result<NtfsTime>
+    where
+        T: Read + Seek,
+    {
+        let volume_file = self.file(fs, KnownNtfsFileRecordNumber::Volume as u64)?;
+        let standard_information = volume_file.info()?;
+        Ok(standard_information.creation_time())
+    }
+
+    /// Returns an [`NtfsVolumeInformation`] containing general information about
+    /// the volume, like the NTFS version.
+    pub fn volume_info<T>(&self, fs: &mut T) -> Result<NtfsVolumeInformation>
+    where
+        T: Read + Seek,
+    {
+        let volume_file = self.file(fs, KnownNtfsFileRecordNumber::Volume as u64)?;
+        volume_file.find_resident_attribute_structured_value::<NtfsVolumeInformation>(None)
+    }
+
+    /// Returns the [`NtfsGuid`] stored in the $OBJECT_ID attribute of the $Volume file.
+    ///
+    /// Windows uses it to track the volume (e.g. in the mount manager), but most volumes don't have one,
+    /// which is why the return value is further encapsulated in an `Option`.
+    pub fn volume_object_id<T>(&self, fs: &mut T) -> Option<Result<NtfsGuid>>
+    where
+        T: Read + Seek,
+    {
+        let volume_file = iter_try!(self.file(fs, KnownNtfsFileRecordNumber::Volume as u64));
+
+        match volume_file.object_id(fs) {
+            Ok(object_id) => Some(Ok(object_id.object_id().clone())),
+            Err(NtfsError::AttributeNotFound { .. }) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    /// Returns an [`NtfsVolumeName`] to read the volume name (also called volume label)
+    /// of this NTFS volume.
+    ///
+    /// Note that a volume may also have no label, which is why the return value is further
+    /// encapsulated in an `Option`.
+    pub fn volume_name<T>(&self, fs: &mut T) -> Option<Result<NtfsVolumeName>>
+    where
+        T: Read + Seek,
+    {
+        let volume_file = iter_try!(self.file(fs, KnownNtfsFileRecordNumber::Volume as u64));
+
+        match volume_file.find_resident_attribute_structured_value::<NtfsVolumeName>(None) {
+            Ok(volume_name) => Some(Ok(volume_name)),
+            Err(NtfsError::AttributeNotFound { .. }) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl fmt::Debug for Ntfs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ntfs")
+            .field("bad_clusters", &self.bad_clusters)
+            .field("bad_cluster_policy", &self.bad_cluster_policy)
+            .field("cluster_size", &self.cluster_size)
+            .field("sector_size", &self.sector_size)
+            .field("size", &self.size)
+            .field("partition_offset", &self.partition_offset)
+            .field("mft_dump", &self.mft_dump)
+            .field("mft_position", &self.mft_position)
+            .field("mft_mirror_position", &self.mft_mirror_position)
+            .field("mft_data_runs", &self.mft_data_runs)
+            .field("file_record_size", &self.file_record_size)
+            .field("serial_number", &self.serial_number)
+            .field("system_regions", &self.system_regions)
+            .field("upcase_table", &self.upcase_table)
+            .field("validation_mode", &self.validation_mode)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::tests::{
+        attribute_list_entry, attribute_offset, clear_in_use, encode_data_runs, file_reference,
+        insert_attribute, make_extension_record, patch_file_record_number, remove_attribute,
+        take_attribute_instance, AttributeBuilder,
+    };
+
+    #[test]
+    fn test_basics() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        assert_eq!(ntfs.cluster_size(), 512);
         assert_eq!(ntfs.sector_size(), 512);
         assert_eq!(ntfs.size(), 2096640);
     }
 
+    #[test]
+    fn test_check_backing_size() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        assert_eq!(ntfs.is_truncated(), None);
+        assert!(!ntfs.check_backing_size(&mut testfs1).unwrap());
+        assert_eq!(ntfs.backing_size(), Some(2097152));
+        assert_eq!(ntfs.is_truncated(), Some(false));
+
+        // The data of "1000-bytes-file" (File Record 66) occupies the two clusters at byte 1314304.
+        // Cut the image after the first one, which keeps all structures required to get there intact.
+        const CUT: u64 = 1314304 + 512;
+        let mut truncated = testfs1.into_inner();
+        truncated.truncate(CUT as usize);
+        let mut testfs1 = std::io::Cursor::new(truncated);
+
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        assert!(ntfs.check_backing_size(&mut testfs1).unwrap());
+        assert_eq!(ntfs.is_truncated(), Some(true));
+
+        let file = ntfs.file(&mut testfs1, 66).unwrap();
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+
+        // The first read returns everything up to the cut, the next one reports the missing data.
+        let mut buf = [0u8; 1000];
+        assert_eq!(data_value.read(&mut testfs1, &mut buf).unwrap(), 512);
+        let error = data_value.read(&mut testfs1, &mut buf).unwrap_err();
+        assert!(matches!(
+            error.root_cause(),
+            NtfsError::BeyondBackingStorage { position, available: CUT }
+                if position.value().unwrap().get() == CUT
+        ));
+
+        // The error tells which attribute value of which file could not be read.
+        assert!(matches!(
+            error,
+            NtfsError::ValueReadContext {
+                file_record_number: 66,
+                ty,
+                ..
+            } if ty == NtfsAttributeType::Data as u32
+        ));
+    }
+
+    #[test]
+    fn test_new_from_mft_parameters() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // Extract the MFT data into a separate buffer, like an $MFT file copied off a volume.
+        let mft = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::MFT as u64)
+            .unwrap();
+        let mft_data_item = mft.data(&mut testfs1, "").unwrap().unwrap();
+        let mft_data_attribute = mft_data_item.to_attribute().unwrap();
+        let mut mft_data = vec![0u8; mft_data_attribute.value_length() as usize];
+        mft_data_attribute
+            .value(&mut testfs1)
+            .unwrap()
+            .read_exact(&mut testfs1, &mut mft_data)
+            .unwrap();
+        let mut dump = std::io::Cursor::new(mft_data);
+
+        let dump_ntfs =
+            Ntfs::new_from_mft_parameters(ntfs.cluster_size(), ntfs.file_record_size()).unwrap();
+        assert!(dump_ntfs.is_mft_dump());
+        assert!(!ntfs.is_mft_dump());
+
+        // The dump yields the same files with the same names as the full volume.
+        let summaries = crate::scan::records_summary(&ntfs, &mut testfs1)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        let dump_summaries = crate::scan::records_summary(&dump_ntfs, &mut dump)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(dump_summaries.len(), summaries.len());
+
+        for (summary, dump_summary) in summaries.iter().zip(&dump_summaries) {
+            assert_eq!(
+                dump_summary.file_record_number(),
+                summary.file_record_number()
+            );
+            assert_eq!(
+                dump_summary
+                    .name()
+                    .map(|name| name.name().to_string_lossy()),
+                summary.name().map(|name| name.name().to_string_lossy())
+            );
+            assert_eq!(dump_summary.data_size(), summary.data_size());
+        }
+
+        // The File Record Number is derived from the position within the dump.
+        let file = dump_ntfs.file(&mut dump, 66).unwrap();
+        assert_eq!(file.file_record_number(), 66);
+        assert_eq!(file.position().value().unwrap().get(), 66 * 1024);
+        assert_eq!(
+            file.info().unwrap().creation_time(),
+            ntfs.file(&mut testfs1, 66)
+                .unwrap()
+                .info()
+                .unwrap()
+                .creation_time()
+        );
+
+        // Non-resident values cannot be read, because the volume data is missing.
+        let data_item = file.data(&mut dump, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        assert_eq!(data_attribute.value_length(), 1000);
+        let error = data_attribute
+            .value(&mut dump)
+            .and_then(|mut value| value.read(&mut dump, &mut [0u8; 16]))
+            .unwrap_err();
+        assert!(matches!(
+            error.root_cause(),
+            NtfsError::VolumeDataUnavailable { .. }
+        ));
+
+        // Resident values are fully usable.
+        let file = dump_ntfs.file(&mut dump, 65).unwrap();
+        let data_item = file.data(&mut dump, "").unwrap().unwrap();
+        let mut buf = [0u8; 5];
+        data_item
+            .to_attribute()
+            .unwrap()
+            .value(&mut dump)
+            .unwrap()
+            .read_exact(&mut dump, &mut buf)
+            .unwrap();
+        assert_eq!(&buf, b"12345");
+
+        // Only plausible parameters are accepted.
+        assert!(matches!(
+            Ntfs::new_from_mft_parameters(1000, 1024),
+            Err(NtfsError::UnsupportedClusterSize { actual: 1000, .. })
+        ));
+        assert!(matches!(
+            Ntfs::new_from_mft_parameters(4096, 512),
+            Err(NtfsError::UnsupportedFileRecordSize { actual: 512, .. })
+        ));
+    }
+
+    #[test]
+    fn test_new_at_offset() {
+        const PARTITION_OFFSET: u64 = 0x10_0000;
+
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        assert_eq!(ntfs.partition_offset(), 0);
+
+        // An offset of zero is the same as `Ntfs::new`.
+        let ntfs_at_zero = Ntfs::new_at_offset(&mut testfs1, 0).unwrap();
+        assert_eq!(ntfs_at_zero.mft_position(), ntfs.mft_position());
+        assert_eq!(ntfs_at_zero.size(), ntfs.size());
+
+        // Put testfs1 behind some unpartitioned space, like on a disk.
+        let mut disk = vec![0u8; PARTITION_OFFSET as usize];
+        disk.extend_from_slice(testfs1.get_ref());
+        let mut disk = std::io::Cursor::new(disk);
+
+        let mut shifted_ntfs = Ntfs::new_at_offset(&mut disk, PARTITION_OFFSET).unwrap();
+        shifted_ntfs.read_upcase_table(&mut disk).unwrap();
+        shifted_ntfs.read_bad_clusters(&mut disk).unwrap();
+        shifted_ntfs.read_system_regions(&mut disk).unwrap();
+        assert_eq!(shifted_ntfs.partition_offset(), PARTITION_OFFSET);
+        assert_eq!(shifted_ntfs.size(), ntfs.size());
+        assert_eq!(
+            shifted_ntfs.mft_position(),
+            ntfs.mft_position() + PARTITION_OFFSET
+        );
+        assert_eq!(
+            shifted_ntfs
+                .classify_position(shifted_ntfs.mft_position())
+                .unwrap(),
+            NtfsRegion::Mft
+        );
+        assert_eq!(
+            shifted_ntfs
+                .classify_position(NtfsPosition::new(PARTITION_OFFSET))
+                .unwrap(),
+            NtfsRegion::BootSector
+        );
+        assert_eq!(
+            shifted_ntfs
+                .classify_position(NtfsPosition::new(PARTITION_OFFSET - 1))
+                .unwrap(),
+            NtfsRegion::Other
+        );
+
+        // Positions are disk-absolute, and all data is read from the shifted partition.
+        let root_dir = shifted_ntfs.root_directory(&mut disk).unwrap();
+        let root_dir_index = root_dir.directory_index(&mut disk).unwrap();
+        let mut finder = root_dir_index.finder();
+        let entry = crate::indexes::NtfsFileNameIndex::find(
+            &mut finder,
+            &shifted_ntfs,
+            &mut disk,
+            "1000-bytes-file",
+        )
+        .unwrap()
+        .unwrap();
+        let file = entry.to_file(&shifted_ntfs, &mut disk).unwrap();
+        let unshifted_file = ntfs.file(&mut testfs1, file.file_record_number()).unwrap();
+        assert_eq!(
+            file.position(),
+            unshifted_file.position() + PARTITION_OFFSET
+        );
+
+        let data_item = file.data(&mut disk, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let data_value = data_attribute.value(&mut disk).unwrap();
+        let mut data_value_attached = data_value.attach(&mut disk);
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(&mut data_value_attached, &mut data).unwrap();
+        assert_eq!(data, b"12345".repeat(200));
+
+        let unshifted_data_item = unshifted_file.data(&mut testfs1, "").unwrap().unwrap();
+        let unshifted_data_attribute = unshifted_data_item.to_attribute().unwrap();
+        assert_eq!(
+            data_attribute.value(&mut disk).unwrap().data_position(),
+            unshifted_data_attribute
+                .value(&mut testfs1)
+                .unwrap()
+                .data_position()
+                + PARTITION_OFFSET
+        );
+    }
+
+    #[test]
+    fn test_bad_clusters() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+
+        // testfs1 has no bad clusters.
+        // Get the File Record and the first LCN of "1000-bytes-file", which occupies two clusters,
+        // as well as the location of the $Bad attribute.
+        let (
+            file_record_number,
+            first_lcn,
+            total_clusters,
+            data,
+            record_position,
+            attribute_offset,
+        ) = {
+            let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+            ntfs.read_upcase_table(&mut testfs1).unwrap();
+            ntfs.read_bad_clusters(&mut testfs1).unwrap();
+            assert!(ntfs.bad_clusters().is_empty());
+
+            let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+            let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+            let mut root_dir_finder = root_dir_index.finder();
+            let entry = crate::indexes::NtfsFileNameIndex::find(
+                &mut root_dir_finder,
+                &ntfs,
+                &mut testfs1,
+                "1000-bytes-file",
+            )
+            .unwrap()
+            .unwrap();
+            let file = entry.to_file(&ntfs, &mut testfs1).unwrap();
+            let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+            let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+            let data_run = data_attribute
+                .non_resident_value()
+                .unwrap()
+                .data_runs()
+                .next()
+                .unwrap()
+                .unwrap();
+            assert_eq!(data_run.allocated_size(), 1024);
+            let first_lcn = data_run.data_position().value().unwrap().get() / 512;
+
+            let mut data = vec![0u8; 1000];
+            data_value.read(&mut testfs1, &mut data).unwrap();
+
+            let bad_clus = ntfs
+                .file(&mut testfs1, KnownNtfsFileRecordNumber::BadClus as u64)
+                .unwrap();
+            let bad_attribute = bad_clus
+                .find_resident_attribute(NtfsAttributeType::Data, Some("$Bad"), None)
+                .unwrap();
+
+            (
+                file.file_record_number(),
+                first_lcn,
+                ntfs.size() / ntfs.cluster_size() as u64,
+                data,
+                bad_clus.position().value().unwrap().get() as usize,
+                bad_attribute.offset(),
+            )
+        };
+
+        // Mark the second cluster of "1000-bytes-file" and the following cluster as bad by mapping
+        // them into the otherwise sparse $Bad stream at a VCN equal to their LCN, using separate Data Runs.
+        let bad_lcn = first_lcn + 1;
+        let mut data_runs = Vec::new();
+        data_runs.push(0x02);
+        data_runs.extend_from_slice(&(bad_lcn as u16).to_le_bytes());
+        data_runs.extend_from_slice(&[0x21, 0x01]);
+        data_runs.extend_from_slice(&(bad_lcn as u16).to_le_bytes());
+        data_runs.extend_from_slice(&[0x11, 0x01, 0x01]);
+        data_runs.push(0x02);
+        data_runs.extend_from_slice(&((total_clusters - bad_lcn - 2) as u16).to_le_bytes());
+        data_runs.push(0x00);
+
+        crate::helpers::tests::patch_file_record(&mut testfs1, record_position, 1024, |record| {
+            // Grow the $Bad attribute by 16 bytes to make room for the new Data Runs.
+            let attribute_length = u32::from_le_bytes(
+                record[attribute_offset + 4..attribute_offset + 8]
+                    .try_into()
+                    .unwrap(),
+            ) as usize;
+            let new_attribute_length = attribute_length + 16;
+            let used_size = u32::from_le_bytes(record[0x18..0x1c].try_into().unwrap()) as usize;
+            record.copy_within(
+                attribute_offset + attribute_length..used_size,
+                attribute_offset + new_attribute_length,
+            );
+            record[0x18..0x1c].copy_from_slice(&(used_size as u32 + 16).to_le_bytes());
+            record[attribute_offset + 4..attribute_offset + 8]
+                .copy_from_slice(&(new_attribute_length as u32).to_le_bytes());
+
+            let data_runs_offset = attribute_offset
+                + u16::from_le_bytes(
+                    record[attribute_offset + 32..attribute_offset + 34]
+                        .try_into()
+                        .unwrap(),
+                ) as usize;
+            let data_runs_area =
+                &mut record[data_runs_offset..attribute_offset + new_attribute_length];
+            data_runs_area.fill(0);
+            data_runs_area[..data_runs.len()].copy_from_slice(&data_runs);
+        });
+
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_bad_clusters(&mut testfs1).unwrap();
+        assert_eq!(
+            ntfs.bad_clusters(),
+            [Lcn::from(bad_lcn)..Lcn::from(bad_lcn + 2)]
+        );
+
+        // By default, reading returns the data up to the bad cluster, and an error for the bad cluster.
+        let read_data = |ntfs: &Ntfs, testfs1: &mut std::io::Cursor<Vec<u8>>| {
+            let file = ntfs.file(testfs1, file_record_number).unwrap();
+            let data_item = file.data(testfs1, "").unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+            let mut data_value = data_attribute.value(testfs1).unwrap();
+
+            let mut buf = vec![0u8; 1000];
+            let first_result = data_value.read(testfs1, &mut buf);
+            let second_result = data_value.read(testfs1, &mut buf[512..]);
+            (buf, first_result, second_result)
+        };
+
+        let (buf, first_result, second_result) = read_data(&ntfs, &mut testfs1);
+        assert_eq!(first_result.unwrap(), 512);
+        assert_eq!(&buf[..512], &data[..512]);
+        let error = second_result.unwrap_err();
+        assert!(matches!(
+            *error.root_cause(),
+            NtfsError::BadClusterRead { lcn, position }
+                if lcn == Lcn::from(bad_lcn) && position == NtfsPosition::new(bad_lcn * 512)
+        ));
+
+        // With the zero-fill policy, the bad cluster reads as zeros.
+        ntfs.set_bad_cluster_policy(NtfsBadClusterPolicy::ZeroFill);
+        let (buf, first_result, _) = read_data(&ntfs, &mut testfs1);
+        assert_eq!(first_result.unwrap(), 1000);
+        assert_eq!(&buf[..512], &data[..512]);
+        assert!(buf[512..].iter().all(|&byte| byte == 0));
+        assert!(data[512..].iter().any(|&byte| byte != 0));
+    }
+
+    #[test]
+    fn test_bad_clusters_in_extents() {
+        const CLUSTER_SIZE: u64 = 512;
+
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let bad_clus_record_number = KnownNtfsFileRecordNumber::BadClus as u64;
+
+        // Get the first LCN of "1000-bytes-file" to have some allocated clusters to mark as bad.
+        let (first_lcn, total_clusters, bad_attribute_offset, base_reference, extension_references) = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            assert_eq!(ntfs.cluster_size() as u64, CLUSTER_SIZE);
+
+            let file = ntfs.file(&mut testfs1, 66).unwrap();
+            let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+            let data_run = data_attribute
+                .non_resident_value()
+                .unwrap()
+                .data_runs()
+                .next()
+                .unwrap()
+                .unwrap();
+            let first_lcn = data_run.data_position().value().unwrap().get() / CLUSTER_SIZE;
+
+            let bad_clus = ntfs.file(&mut testfs1, bad_clus_record_number).unwrap();
+            let bad_attribute = bad_clus
+                .find_resident_attribute(NtfsAttributeType::Data, Some("$Bad"), None)
+                .unwrap();
+
+            let extension_references = [100, 101].map(|file_record_number| {
+                let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+                (
+                    file_record_number,
+                    file_reference(file_record_number, file.sequence_number()),
+                )
+            });
+
+            (
+                first_lcn,
+                ntfs.size() / CLUSTER_SIZE,
+                bad_attribute.offset(),
+                file_reference(bad_clus_record_number, bad_clus.sequence_number()),
+                extension_references,
+            )
+        };
+
+        // Split $Bad into two extents in the extension records 100 and 101, each marking a single cluster as bad.
+        // The bad clusters are not adjacent, so they are reported as separate ranges.
+        let volume_size = total_clusters * CLUSTER_SIZE;
+        let extents = [
+            (
+                0,
+                first_lcn,
+                encode_data_runs(&[(first_lcn, None), (1, Some(first_lcn))]),
+            ),
+            (
+                first_lcn + 1,
+                total_clusters - 1,
+                encode_data_runs(&[
+                    (1, None),
+                    (1, Some(first_lcn + 2)),
+                    (total_clusters - first_lcn - 3, None),
+                ]),
+            ),
+        ];
+
+        let mut list = Vec::new();
+        for ((lowest_vcn, highest_vcn, data_runs), (file_record_number, reference)) in
+            extents.iter().zip(extension_references)
+        {
+            let mut attribute = AttributeBuilder::non_resident(
+                NtfsAttributeType::Data,
+                *lowest_vcn,
+                *highest_vcn,
+                data_runs,
+            )
+            .name("$Bad");
+            if *lowest_vcn == 0 {
+                attribute = attribute.sizes(volume_size, volume_size, volume_size);
+            }
+            let attribute = attribute.build();
+
+            patch_file_record_number(&mut testfs1, file_record_number, |record| {
+                make_extension_record(record, base_reference, &attribute, 1);
+            });
+            list.extend(attribute_list_entry(
+                NtfsAttributeType::Data,
+                "$Bad",
+                *lowest_vcn,
+                reference,
+                0,
+            ));
+        }
+
+        // Replace the $Bad attribute of $BadClus by an $ATTRIBUTE_LIST referencing the extents.
+        patch_file_record_number(&mut testfs1, bad_clus_record_number, |record| {
+            remove_attribute(record, bad_attribute_offset);
+
+            let offset = attribute_offset(record, NtfsAttributeType::StandardInformation);
+            let insert_offset = offset
+                + u32::from_le_bytes(record[offset + 4..offset + 8].try_into().unwrap()) as usize;
+            let attribute = AttributeBuilder::resident(NtfsAttributeType::AttributeList, &list)
+                .instance(take_attribute_instance(record))
+                .build();
+            insert_attribute(record, insert_offset, &attribute);
+        });
+
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_bad_clusters(&mut testfs1).unwrap();
+        assert_eq!(
+            ntfs.bad_clusters(),
+            [
+                Lcn::from(first_lcn)..Lcn::from(first_lcn + 1),
+                Lcn::from(first_lcn + 2)..Lcn::from(first_lcn + 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_classify_position() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        assert!(matches!(
+            ntfs.classify_position(ntfs.mft_position()),
+            Err(NtfsError::SystemRegionsNotLoaded)
+        ));
+        ntfs.read_system_regions(&mut testfs1).unwrap();
+
+        let classify = |position: u64| ntfs.classify_position(NtfsPosition::new(position)).unwrap();
+
+        // The $Boot file covers the first 8192 bytes, and the backup boot sector follows the volume.
+        assert_eq!(classify(1), NtfsRegion::BootSector);
+        assert_eq!(classify(8191), NtfsRegion::BootSector);
+        assert_eq!(classify(8192), NtfsRegion::Other);
+        assert_eq!(classify(ntfs.size()), NtfsRegion::BootSector);
+        assert_eq!(classify(ntfs.size() + 512), NtfsRegion::Other);
+
+        // The MFT of testfs1 is fragmented into 6 Data Runs.
+        // Check the boundaries of the first one and the gap to the second one.
+        assert_eq!(
+            classify(ntfs.mft_position().value().unwrap().get()),
+            NtfsRegion::Mft
+        );
+        assert_eq!(classify(278015), NtfsRegion::Mft);
+        assert_eq!(classify(278016), NtfsRegion::Other);
+        assert_eq!(classify(1348607), NtfsRegion::Other);
+        assert_eq!(classify(1348608), NtfsRegion::Mft);
+        assert_eq!(classify(1704447), NtfsRegion::Mft);
+        assert_eq!(classify(1704448), NtfsRegion::Other);
+
+        // File Records are located in the MFT, no matter which Data Run they are in.
+        for file_record_number in [0, 66, 255, 256, 580] {
+            let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+            assert_eq!(
+                ntfs.classify_position(file.position()).unwrap(),
+                NtfsRegion::Mft
+            );
+        }
+
+        // The $MFTMirr file occupies a single Data Run of 4096 bytes.
+        assert_eq!(classify(1048063), NtfsRegion::Other);
+        assert_eq!(classify(1048064), NtfsRegion::MftMirr);
+        assert_eq!(classify(1052159), NtfsRegion::MftMirr);
+        assert_eq!(classify(1052160), NtfsRegion::Other);
+
+        // File data is located elsewhere.
+        let file = ntfs.file(&mut testfs1, 66).unwrap();
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let data_value = data_attribute.value(&mut testfs1).unwrap();
+        assert_eq!(
+            ntfs.classify_position(data_value.data_position()).unwrap(),
+            NtfsRegion::Other
+        );
+
+        assert_eq!(
+            ntfs.classify_position(NtfsPosition::none()).unwrap(),
+            NtfsRegion::Other
+        );
+    }
+
+    #[test]
+    fn test_file_at_position() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // Read the $Volume File Record from the MFT and from $MFTMirr.
+        let volume_file = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::Volume as u64)
+            .unwrap();
+        let offset = 3 * ntfs.file_record_size() as u64;
+
+        for position in [
+            ntfs.mft_position() + offset,
+            ntfs.mft_mirror_position() + offset,
+        ] {
+            let file = ntfs
+                .file_at_position(
+                    &mut testfs1,
+                    position,
+                    KnownNtfsFileRecordNumber::Volume as u64,
+                )
+                .unwrap();
+            assert_eq!(file.position(), position);
+            assert_eq!(file.record_data(), volume_file.record_data());
+        }
+
+        // Positions where the File Record doesn't fit into the filesystem must be rejected.
+        for position in [
+            NtfsPosition::none(),
+            NtfsPosition::new(ntfs.size() - 512),
+            NtfsPosition::new(u64::MAX),
+        ] {
+            assert!(matches!(
+                ntfs.file_at_position(&mut testfs1, position, 0),
+                Err(NtfsError::InvalidFileRecordPosition { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_file_in_use() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+
+        {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let file = ntfs.file_in_use(&mut testfs1, 65).unwrap();
+            assert!(file.flags().contains(NtfsFileFlags::IN_USE));
+        }
+
+        // Mark "file-with-12345" as deleted, but keep its directory entry.
+        patch_file_record_number(&mut testfs1, 65, clear_in_use);
+
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // The permissive function still returns the File Record for forensic use.
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        assert!(!file.flags().contains(NtfsFileFlags::IN_USE));
+
+        assert!(matches!(
+            ntfs.file_in_use(&mut testfs1, 65),
+            Err(NtfsError::FileRecordNotInUse {
+                file_record_number: 65
+            })
+        ));
+
+        // A directory lookup must not return the deleted file.
+        let root_directory = ntfs.root_directory(&mut testfs1).unwrap();
+        let index = root_directory.directory_index(&mut testfs1).unwrap();
+        let mut finder = index.finder();
+        let entry = crate::indexes::NtfsFileNameIndex::find(
+            &mut finder,
+            &ntfs,
+            &mut testfs1,
+            "file-with-12345",
+        )
+        .unwrap()
+        .unwrap();
+        assert!(matches!(
+            entry.to_file(&ntfs, &mut testfs1),
+            Err(NtfsError::FileRecordNotInUse {
+                file_record_number: 65
+            })
+        ));
+    }
+
+    #[test]
+    fn test_mft_records_in_use() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // The MFT of testfs1 is 594944 bytes large, and 536 of its 581 File Records are in use.
+        let record_count = ntfs.mft_record_count(&mut testfs1).unwrap();
+        assert_eq!(record_count, 581);
+        assert_eq!(ntfs.mft_records_in_use(&mut testfs1).unwrap(), 536);
+
+        // Every File Record marked as allocated is in use.
+        let mut allocated_count = 0;
+        for file_record_number in 0..record_count {
+            if ntfs
+                .is_file_record_allocated(&mut testfs1, file_record_number)
+                .unwrap()
+            {
+                ntfs.file_in_use(&mut testfs1, file_record_number).unwrap();
+                allocated_count += 1;
+            }
+        }
+        assert_eq!(allocated_count, 536);
+
+        // File Records 16 to 23 are reserved, but not in use.
+        assert!(ntfs.is_file_record_allocated(&mut testfs1, 15).unwrap());
+        assert!(!ntfs.is_file_record_allocated(&mut testfs1, 16).unwrap());
+        assert!(matches!(
+            ntfs.is_file_record_allocated(&mut testfs1, record_count),
+            Err(NtfsError::InvalidFileRecordNumber { .. })
+        ));
+
+        // The 80 bytes of the $BITMAP attribute cover 640 File Records.
+        // Bits beyond the 581 File Records of the MFT must be ignored.
+        let bitmap_position = {
+            let mft = ntfs.mft(&mut testfs1).unwrap();
+            let bitmap_item = Ntfs::mft_bitmap(&mft, &mut testfs1).unwrap();
+            let bitmap_attribute = bitmap_item.to_attribute().unwrap();
+            assert_eq!(bitmap_attribute.value_length(), 80);
+
+            let bitmap_value = bitmap_attribute.value(&mut testfs1).unwrap();
+            bitmap_value.data_position().value().unwrap().get() as usize
+        };
+        let bitmap = &mut testfs1.get_mut()[bitmap_position..bitmap_position + 80];
+        assert_eq!(bitmap[72] & 0xe0, 0);
+        bitmap[72] |= 0xe0;
+        bitmap[73..].fill(0xff);
+
+        assert_eq!(ntfs.mft_records_in_use(&mut testfs1).unwrap(), 536);
+    }
+
+    #[test]
+    fn test_is_system_record() {
+        assert!(Ntfs::is_system_record(
+            KnownNtfsFileRecordNumber::MFT as u64
+        ));
+        assert!(Ntfs::is_system_record(
+            KnownNtfsFileRecordNumber::Extend as u64
+        ));
+        assert!(Ntfs::is_system_record(12));
+        assert!(Ntfs::is_system_record(15));
+        assert!(!Ntfs::is_system_record(16));
+        assert!(!Ntfs::is_system_record(64));
+    }
+
+    #[test]
+    fn test_file_spanning_data_runs() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // The first Data Run of the MFT in testfs1 is 261632 bytes long.
+        // Hence, the first half of File Record 255 is at the end of the first Data Run and the
+        // second half is at the beginning of the second Data Run.
+        let file = ntfs.file(&mut testfs1, 255).unwrap();
+        assert_eq!(file.file_record_number(), 255);
+        assert!(file.is_directory());
+
+        // File Records beyond the end of the MFT must be rejected.
+        let mft_size = 594944;
+        let file_record_number = mft_size / ntfs.file_record_size() as u64;
+        assert!(matches!(
+            ntfs.file(&mut testfs1, file_record_number),
+            Err(NtfsError::InvalidFileRecordNumber { .. })
+        ));
+    }
+
+    /// Wraps a reader and counts the reads starting at `position`.
+    struct PositionCountingReader<R> {
+        inner: R,
+        position: u64,
+        read_count: usize,
+    }
+
+    impl<R: Read + Seek> Read for PositionCountingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> crate::io::Result<usize> {
+            if self.inner.stream_position()? == self.position {
+                self.read_count += 1;
+            }
+
+            self.inner.read(buf)
+        }
+    }
+
+    impl<R: Seek> Seek for PositionCountingReader<R> {
+        fn seek(&mut self, pos: SeekFrom) -> crate::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_mft_data_runs_cache() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let (mft_position, expected) = {
+            // Read all File Records the old way, via the $DATA attribute of File Record 0.
+            let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+            ntfs.mft_data_runs = None;
+            let expected = (1..581)
+                .map(|file_record_number| {
+                    ntfs.file(&mut testfs1, file_record_number)
+                        .map(|file| (file.position(), file.record_data().to_vec()))
+                        .ok()
+                })
+                .collect::<Vec<_>>();
+            (ntfs.mft_position().value().unwrap().get(), expected)
+        };
+
+        // With the cached Data Runs, File Record 0 is only read once when creating the `Ntfs` object.
+        let mut testfs1 = PositionCountingReader {
+            inner: testfs1,
+            position: mft_position,
+            read_count: 0,
+        };
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        assert_eq!(testfs1.read_count, 1);
+
+        for (file_record_number, expected) in (1..581).zip(&expected) {
+            let file = ntfs
+                .file(&mut testfs1, file_record_number)
+                .map(|file| (file.position(), file.record_data().to_vec()))
+                .ok();
+            assert_eq!(&file, expected, "File Record {file_record_number}");
+        }
+        assert_eq!(testfs1.read_count, 1);
+
+        assert!(matches!(
+            ntfs.file(&mut testfs1, 581),
+            Err(NtfsError::InvalidFileRecordNumber { .. })
+        ));
+
+        // The Data Runs are only read again on request.
+        ntfs.read_mft_data_runs(&mut testfs1).unwrap();
+        assert_eq!(testfs1.read_count, 2);
+        ntfs.file(&mut testfs1, 66).unwrap();
+        assert_eq!(testfs1.read_count, 2);
+
+        // A damaged File Record 0 doesn't prevent creating the `Ntfs` object,
+        // but every lookup reports it.
+        testfs1.inner.get_mut()[mft_position as usize..mft_position as usize + 4]
+            .copy_from_slice(b"BAAD");
+        assert!(matches!(
+            ntfs.read_mft_data_runs(&mut testfs1),
+            Err(NtfsError::InvalidFileSignature { .. })
+        ));
+        assert!(matches!(
+            ntfs.file(&mut testfs1, 66),
+            Err(NtfsError::InvalidFileSignature { .. })
+        ));
+
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        assert!(matches!(
+            ntfs.file(&mut testfs1, 66),
+            Err(NtfsError::InvalidFileSignature { .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_mft_lcn() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+
+        // The MFT LCN is stored at byte 0x30 of the boot sector.
+        // testfs1 has 4095 clusters, so an MFT starting at LCN 4095 or beyond is outside the volume.
+        for mft_lcn in [4095u64, 4096, u64::MAX / 512] {
+            testfs1.get_mut()[0x30..0x38].copy_from_slice(&mft_lcn.to_le_bytes());
+            assert!(matches!(
+                Ntfs::new(&mut testfs1),
+                Err(NtfsError::InvalidMftLcn)
+            ));
+        }
+
+        // The first File Record of the MFT must fit entirely into the volume.
+        // This LCN is in the volume, but only leaves 512 bytes for the 1024-byte File Record.
+        testfs1.get_mut()[0x30..0x38].copy_from_slice(&4094u64.to_le_bytes());
+        assert!(matches!(
+            Ntfs::new(&mut testfs1),
+            Err(NtfsError::InvalidMftLcn)
+        ));
+
+        // An MFT LCN of zero has always been rejected.
+        testfs1.get_mut()[0x30..0x38].copy_from_slice(&0u64.to_le_bytes());
+        assert!(matches!(
+            Ntfs::new(&mut testfs1),
+            Err(NtfsError::InvalidMftLcn)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_boot_sector() {
+        fn new_with_patch<F>(f: F) -> Result<Ntfs>
+        where
+            F: FnOnce(&mut [u8]),
+        {
+            let mut testfs1 = crate::helpers::tests::testfs1();
+            f(testfs1.get_mut());
+            Ntfs::new(&mut testfs1)
+        }
+
+        // A boot sector of another filesystem with a valid signature.
+        let result = new_with_patch(|data| data[0x03..0x0b].copy_from_slice(b"MSDOS5.0"));
+        assert!(matches!(
+            result,
+            Err(NtfsError::InvalidBootSectorOemName { position, actual, .. })
+                if position.value().unwrap().get() == 0x03 && &actual == b"MSDOS5.0"
+        ));
+
+        // Nonzero values in the reserved fields.
+        for reserved_position in [0x0e, 0x14, 0x16, 0x20, 0x23] {
+            let result = new_with_patch(|data| data[reserved_position] = 0x01);
+            let expected_position = match reserved_position {
+                0x0e..=0x14 => 0x0e,
+                0x16..=0x17 => 0x16,
+                _ => 0x20,
+            };
+            assert!(matches!(
+                result,
+                Err(NtfsError::InvalidBootSectorReservedField { position })
+                    if position.value().unwrap().get() == expected_position
+            ));
+        }
+
+        // Invalid and valid media descriptors.
+        for media in [0x00, 0x12, 0xf7] {
+            let result = new_with_patch(|data| data[0x15] = media);
+            assert!(matches!(
+                result,
+                Err(NtfsError::InvalidMediaDescriptor { actual, .. }) if actual == media
+            ));
+        }
+        for media in [0xf0, 0xf8, 0xff] {
+            assert!(new_with_patch(|data| data[0x15] = media).is_ok());
+        }
+
+        // File Record sizes denoted by a cluster count that would result in huge allocations.
+        // testfs1 has 512-byte clusters, so 32 clusters are the largest accepted File Record size.
+        for (sectors_per_cluster, file_record_size_info) in [(0xf4, 127), (0xf4, 1), (0x01, 33)] {
+            let result = new_with_patch(|data| {
+                data[0x0d] = sectors_per_cluster;
+                data[0x40] = file_record_size_info;
+            });
+            assert!(matches!(
+                result,
+                Err(NtfsError::InvalidRecordSizeInfo { size_info, .. })
+                    if size_info == file_record_size_info as i8
+            ));
+        }
+
+        // A File Record size denoted by a cluster count that matches the usual size of 1024 bytes.
+        let ntfs = new_with_patch(|data| data[0x40] = 2).unwrap();
+        assert_eq!(ntfs.file_record_size(), 1024);
+
+        // A total sector count of zero.
+        let result = new_with_patch(|data| data[0x28..0x30].copy_from_slice(&0u64.to_le_bytes()));
+        assert!(matches!(result, Err(NtfsError::InvalidTotalSectors)));
+
+        // MFT Mirror LCNs that are zero, equal to the MFT LCN, or outside the volume.
+        for mft_mirror_lcn in [0u64, 0x20, 4094, 4095, u64::MAX / 512, u64::MAX] {
+            let result = new_with_patch(|data| {
+                data[0x38..0x40].copy_from_slice(&mft_mirror_lcn.to_le_bytes())
+            });
+            assert!(matches!(result, Err(NtfsError::InvalidMftMirrorLcn)));
+        }
+
+        // Garbage with a valid signature, similar to the fuzzer input that used to crash while reading
+        // the upcase table, must be rejected before anything beyond the boot sector is read.
+        let result = new_with_patch(|data| {
+            let mut seed = 0x2545_f491u32;
+            for byte in &mut data[..510] {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                *byte = seed as u8;
+            }
+        });
+        assert!(matches!(
+            result,
+            Err(NtfsError::InvalidBootSectorOemName { .. })
+        ));
+    }
+
+    #[test]
+    fn test_volume_creation_time() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let volume_creation_time = ntfs.volume_creation_time(&mut testfs1).unwrap();
+
+        // All system files have been created along with the volume.
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        assert_eq!(
+            volume_creation_time.nt_timestamp(),
+            root_dir.info().unwrap().creation_time().nt_timestamp()
+        );
+        assert!(volume_creation_time.nt_timestamp() > 0);
+    }
+
     #[test]
     fn test_volume_info() {
         let mut testfs1 = crate::helpers::tests::testfs1();
@@ -219,6 +2475,76 @@ mod tests {
         assert_eq!(volume_info.minor_version(), 1);
     }
 
+    #[test]
+    fn test_volume_object_id() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+
+        // testfs1 has no volume Object ID.
+        let (position, file_record_size, end_marker_offset, instance) = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            assert!(ntfs.volume_object_id(&mut testfs1).is_none());
+
+            let volume_file = ntfs
+                .file(&mut testfs1, KnownNtfsFileRecordNumber::Volume as u64)
+                .unwrap();
+            let attributes_length = volume_file
+                .attributes_raw()
+                .map(|attribute| attribute.unwrap().attribute_length() as usize)
+                .sum::<usize>();
+
+            (
+                volume_file.position().value().unwrap().get() as usize,
+                ntfs.file_record_size() as usize,
+                volume_file.first_attribute_offset() as usize + attributes_length,
+                volume_file.next_attribute_instance(),
+            )
+        };
+
+        // Insert a resident $OBJECT_ID attribute in front of the end marker of the $Volume File Record.
+        let guid = [
+            0x78, 0x56, 0x34, 0x12, 0x34, 0x12, 0x78, 0x56, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06,
+            0x07, 0x08,
+        ];
+        crate::helpers::tests::patch_file_record(
+            &mut testfs1,
+            position,
+            file_record_size,
+            |record| {
+                let mut attribute = vec![0u8; 24];
+                attribute[0..4]
+                    .copy_from_slice(&(NtfsAttributeType::ObjectId as u32).to_le_bytes());
+                attribute[4..8].copy_from_slice(&40u32.to_le_bytes());
+                attribute[10..12].copy_from_slice(&24u16.to_le_bytes());
+                attribute[14..16].copy_from_slice(&instance.to_le_bytes());
+                attribute[16..20].copy_from_slice(&16u32.to_le_bytes());
+                attribute[20..22].copy_from_slice(&24u16.to_le_bytes());
+                attribute.extend_from_slice(&guid);
+
+                let new_end_marker_offset = end_marker_offset + attribute.len();
+                record.copy_within(
+                    end_marker_offset..end_marker_offset + 4,
+                    new_end_marker_offset,
+                );
+                record[end_marker_offset..new_end_marker_offset].copy_from_slice(&attribute);
+
+                // Update the data size and next attribute instance of the File Record header.
+                record[0x18..0x1c]
+                    .copy_from_slice(&(new_end_marker_offset as u32 + 8).to_le_bytes());
+                record[0x28..0x2a].copy_from_slice(&(instance + 1).to_le_bytes());
+            },
+        );
+
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let object_id = ntfs.volume_object_id(&mut testfs1).unwrap().unwrap();
+        assert_eq!(object_id.data1, 0x12345678);
+        assert_eq!(object_id.data2, 0x1234);
+        assert_eq!(object_id.data3, 0x5678);
+        assert_eq!(
+            object_id.data4,
+            [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]
+        );
+    }
+
     #[test]
     fn test_volume_name() {
         let mut testfs1 = crate::helpers::tests::testfs1();
@@ -10,7 +10,7 @@ use memoffset::{offset_of, span_of};
 use crate::error::{NtfsError, Result};
 use crate::types::NtfsPosition;
 
-const NTFS_BLOCK_SIZE: usize = 512;
+pub(crate) const NTFS_BLOCK_SIZE: usize = 512;
 
 #[repr(C, packed)]
 pub(crate) struct RecordHeader {
@@ -24,11 +24,25 @@ pub(crate) struct RecordHeader {
 pub(crate) struct Record {
     data: Vec<u8>,
     position: NtfsPosition,
+    /// Absolute positions of all 512-byte blocks of a record that is not stored contiguously
+    /// (e.g. because it straddles two Data Runs).
+    /// Empty for contiguous records, whose blocks simply follow `position`.
+    block_positions: Vec<NtfsPosition>,
 }
 
 impl Record {
     pub(crate) fn new(data: Vec<u8>, position: NtfsPosition) -> Self {
-        Self { data, position }
+        Self {
+            data,
+            position,
+            block_positions: Vec::new(),
+        }
+    }
+
+    /// Sets the absolute positions of all 512-byte blocks of a record that is not stored contiguously.
+    pub(crate) fn with_block_positions(mut self, block_positions: Vec<NtfsPosition>) -> Self {
+        self.block_positions = block_positions;
+        self
     }
 
     pub(crate) fn data(&self) -> &[u8] {
@@ -52,6 +66,16 @@ impl Record {
             });
         }
 
+        // The array must cover every sector of the record.
+        // Otherwise, the record has been written for a different record size, and the trailing sectors
+        // would silently keep the Update Sequence Number (USN) instead of their actual bytes.
+        if sectors_end != self.data.len() {
+            return Err(NtfsError::InvalidUpdateSequenceCount {
+                position: self.position,
+                update_sequence_count: array_count + 1,
+            });
+        }
+
         // The Update Sequence Number (USN) is written to the last 2 bytes of each sector.
         let mut sector_position = NTFS_BLOCK_SIZE - mem::size_of::<u16>();
 
@@ -69,7 +93,7 @@ impl Record {
             let bytes_to_update = &mut self.data[sector_position..sector_position_end];
             if bytes_to_update != update_sequence_number {
                 return Err(NtfsError::UpdateSequenceNumberMismatch {
-                    position: self.position + array_position,
+                    position: self.position_at(sector_position),
                     expected: update_sequence_number,
                     actual: (&*bytes_to_update).try_into().unwrap(),
                 });
@@ -86,6 +110,65 @@ impl Record {
         Ok(())
     }
 
+    /// Returns the mutable bytes of this record (after fixup).
+    #[cfg(feature = "write")]
+    pub(crate) fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Reverses [`Record::fixup`] to get the record ready for being written back to the filesystem.
+    ///
+    /// The Update Sequence Number (USN) is incremented, and the last 2 bytes of each sector are saved to the
+    /// Update Sequence Array before being replaced by the new USN.
+    /// Call [`Record::fixup`] afterwards to get back the fixed-up view of the record.
+    #[cfg(feature = "write")]
+    pub(crate) fn unfixup(&mut self) -> Result<()> {
+        let mut update_sequence_number =
+            u16::from_le_bytes(self.update_sequence_number()?).wrapping_add(1);
+
+        // Windows skips the values 0 and 0xFFFF when incrementing the USN.
+        if update_sequence_number == 0 || update_sequence_number == 0xffff {
+            update_sequence_number = 1;
+        }
+        let update_sequence_number = update_sequence_number.to_le_bytes();
+
+        let array_count = self.update_sequence_array_count()?;
+        let array_start = self.update_sequence_array_start() as usize;
+        let array_end =
+            self.update_sequence_offset() as usize + self.update_sequence_size() as usize;
+        let sectors_end = array_count as usize * NTFS_BLOCK_SIZE;
+
+        if array_end > self.data.len() || sectors_end > self.data.len() {
+            return Err(NtfsError::UpdateSequenceArrayExceedsRecordSize {
+                position: self.position,
+                array_count,
+                record_size: self.data.len(),
+            });
+        }
+
+        let usn_start = self.update_sequence_offset() as usize;
+        self.data[usn_start..usn_start + mem::size_of::<u16>()]
+            .copy_from_slice(&update_sequence_number);
+
+        let mut array_position = array_start;
+        let mut sector_position = NTFS_BLOCK_SIZE - mem::size_of::<u16>();
+
+        while array_position < array_end {
+            // Save the actual 2 bytes at the end of the sector to the array and replace them by the USN.
+            self.data.copy_within(
+                sector_position..sector_position + mem::size_of::<u16>(),
+                array_position,
+            );
+            self.data[sector_position..sector_position + mem::size_of::<u16>()]
+                .copy_from_slice(&update_sequence_number);
+
+            array_position += mem::size_of::<u16>();
+            sector_position += NTFS_BLOCK_SIZE;
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn into_data(self) -> Vec<u8> {
         self.data
     }
@@ -96,10 +179,35 @@ impl Record {
         self.data.len() as u32
     }
 
+    pub(crate) fn logfile_sequence_number(&self) -> u64 {
+        let start = offset_of!(RecordHeader, logfile_sequence_number);
+        LittleEndian::read_u64(&self.data[start..])
+    }
+
     pub(crate) fn position(&self) -> NtfsPosition {
         self.position
     }
 
+    /// Returns the absolute position of the byte at `offset` of this record.
+    pub(crate) fn position_at(&self, offset: usize) -> NtfsPosition {
+        if self.block_positions.is_empty() {
+            return self.position + offset;
+        }
+
+        match self.block_positions.get(offset / NTFS_BLOCK_SIZE) {
+            Some(block_position) => *block_position + offset % NTFS_BLOCK_SIZE,
+            None => NtfsPosition::none(),
+        }
+    }
+
+    /// Returns the Update Sequence Number (USN) of a record that has been fixed up successfully.
+    pub(crate) fn fixed_up_update_sequence_number(&self) -> u16 {
+        let update_sequence_number = self
+            .update_sequence_number()
+            .expect("the USN range has been validated by Record::fixup");
+        u16::from_le_bytes(update_sequence_number)
+    }
+
     pub(crate) fn signature(&self) -> [u8; 4] {
         self.data[span_of!(RecordHeader, signature)]
             .try_into()
@@ -124,7 +232,7 @@ impl Record {
         self.update_sequence_offset() + mem::size_of::<u16>() as u16
     }
 
-    fn update_sequence_number(&self) -> Result<[u8; 2]> {
+    pub(crate) fn update_sequence_number(&self) -> Result<[u8; 2]> {
         let start = self.update_sequence_offset() as usize;
         let end = start + mem::size_of::<u16>();
         self.data
@@ -148,3 +256,100 @@ impl Record {
         update_sequence_count as u32 * mem::size_of::<u16>() as u32
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a record of two blocks with a valid Update Sequence Array.
+    fn two_block_record() -> Vec<u8> {
+        let mut data = vec![0u8; 2 * NTFS_BLOCK_SIZE];
+        data[4..6].copy_from_slice(&0x30u16.to_le_bytes());
+        data[6..8].copy_from_slice(&3u16.to_le_bytes());
+        data[0x30..0x32].copy_from_slice(&[0x12, 0x34]);
+        data[510..512].copy_from_slice(&[0x12, 0x34]);
+        data[1022..1024].copy_from_slice(&[0x12, 0x34]);
+        data
+    }
+
+    #[test]
+    fn test_position_at() {
+        let position = NtfsPosition::new(0x1000);
+
+        // The blocks of a contiguous record follow its position.
+        let record = Record::new(two_block_record(), position);
+        assert_eq!(record.position_at(0), position);
+        assert_eq!(record.position_at(600), NtfsPosition::new(0x1258));
+
+        // The second block of this record is stored elsewhere.
+        let record = Record::new(two_block_record(), position)
+            .with_block_positions(vec![position, NtfsPosition::new(0x8000)]);
+        assert_eq!(record.position_at(0), position);
+        assert_eq!(record.position_at(511), NtfsPosition::new(0x11ff));
+        assert_eq!(record.position_at(600), NtfsPosition::new(0x8058));
+        assert_eq!(record.position_at(1024), NtfsPosition::none());
+    }
+
+    #[test]
+    fn test_fixup_mismatch_position() {
+        let mut data = two_block_record();
+        data[1022] = 0;
+
+        let mut record = Record::new(data, NtfsPosition::new(0x1000))
+            .with_block_positions(vec![NtfsPosition::new(0x1000), NtfsPosition::new(0x8000)]);
+        let error = record.fixup().unwrap_err();
+        assert!(matches!(
+            error,
+            NtfsError::UpdateSequenceNumberMismatch {
+                expected: [0x12, 0x34],
+                actual: [0, 0x34],
+                ..
+            }
+        ));
+
+        // The mismatch is reported at the physical position of the last 2 bytes of the second block.
+        assert_eq!(error.position(), Some(NtfsPosition::new(0x81fe)));
+    }
+
+    #[test]
+    fn test_fixup_array_count_mismatch() {
+        // An Update Sequence Array for a single block doesn't cover a record of two blocks.
+        let mut data = two_block_record();
+        data[6..8].copy_from_slice(&2u16.to_le_bytes());
+
+        let mut record = Record::new(data, NtfsPosition::new(0x1000));
+        assert!(matches!(
+            record.fixup(),
+            Err(NtfsError::InvalidUpdateSequenceCount {
+                update_sequence_count: 2,
+                ..
+            })
+        ));
+    }
+
+    #[cfg(feature = "write")]
+    #[test]
+    fn test_unfixup() {
+        let mut record = Record::new(two_block_record(), NtfsPosition::new(0x1000));
+        record.data[0x32..0x34].copy_from_slice(&[0xaa, 0xbb]);
+        record.data[0x34..0x36].copy_from_slice(&[0xcc, 0xdd]);
+        record.fixup().unwrap();
+        assert_eq!(&record.data[510..512], &[0xaa, 0xbb]);
+        assert_eq!(&record.data[1022..1024], &[0xcc, 0xdd]);
+
+        // Modify the end of the second sector and write the record back.
+        record.data[1022..1024].copy_from_slice(&[0xee, 0xff]);
+        record.unfixup().unwrap();
+
+        // The USN has been incremented and replaces the last 2 bytes of each sector.
+        assert_eq!(&record.data[0x30..0x32], &[0x13, 0x34]);
+        assert_eq!(&record.data[510..512], &[0x13, 0x34]);
+        assert_eq!(&record.data[1022..1024], &[0x13, 0x34]);
+        assert_eq!(&record.data[0x32..0x36], &[0xaa, 0xbb, 0xee, 0xff]);
+
+        // Another fixup brings back the modified data.
+        record.fixup().unwrap();
+        assert_eq!(&record.data[510..512], &[0xaa, 0xbb]);
+        assert_eq!(&record.data[1022..1024], &[0xee, 0xff]);
+    }
+}
@@ -0,0 +1,390 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+//! Functions for finding deleted files whose metadata is still present in the Master File Table (MFT).
+//!
+//! When NTFS deletes a file, it only clears the in-use flag of its File Record and marks its clusters as free
+//! in the $Bitmap file.
+//! Until the File Record and the clusters are reused, the names, sizes, and Data Runs of the file survive.
+//! [`deleted_files`] finds such File Records and estimates whether their data can still be recovered.
+//!
+//! This module never writes to the filesystem.
+//! The remaining data of a deleted file can be read through the usual attribute value readers
+//! (see [`NtfsDeletedFile::file_record_number`] and [`Ntfs::file`]).
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::iter::FusedIterator;
+use core::ops::Range;
+
+use crate::io::{Read, Seek, SeekFrom};
+
+use crate::attribute::{NtfsAttribute, NtfsAttributeType};
+use crate::error::{NtfsError, Result};
+use crate::file::{KnownNtfsFileRecordNumber, NtfsFile, NtfsFileFlags};
+use crate::ntfs::Ntfs;
+use crate::structured_values::NtfsFileName;
+use crate::traits::NtfsReadSeek;
+
+/// Returns an iterator over an [`NtfsDeletedFile`] for each File Record of a deleted file.
+///
+/// A File Record is considered to belong to a deleted file if it is not in use, has a valid signature,
+/// is a base File Record, and still has at least one $FILE_NAME attribute.
+/// Records that have never been used are skipped, as are extension records.
+///
+/// Only the attributes stored in the base File Record are considered.
+/// The extension records of a deleted file are usually freed as well and cannot be trusted.
+pub fn deleted_files<'n, 'a, T>(
+    ntfs: &'n Ntfs,
+    fs: &'a mut T,
+) -> Result<NtfsDeletedFiles<'n, 'a, T>>
+where
+    T: Read + Seek,
+{
+    let mft = ntfs.file(fs, KnownNtfsFileRecordNumber::MFT as u64)?;
+    let bitmap = ntfs.file(fs, KnownNtfsFileRecordNumber::Bitmap as u64)?;
+    let file_record_count = ntfs.mft_record_count(fs)?;
+
+    Ok(NtfsDeletedFiles {
+        ntfs,
+        fs,
+        mft,
+        bitmap,
+        next_file_record_number: 0,
+        file_record_count,
+    })
+}
+
+/// Information about a deleted file, returned by [`deleted_files`].
+#[derive(Clone, Debug)]
+pub struct NtfsDeletedFile {
+    file_record_number: u64,
+    names: Vec<NtfsFileName>,
+    data_streams: Vec<NtfsDeletedDataStream>,
+}
+
+impl NtfsDeletedFile {
+    /// Returns all $DATA attributes (data streams) found in the File Record of the deleted file.
+    pub fn data_streams(&self) -> &[NtfsDeletedDataStream] {
+        &self.data_streams
+    }
+
+    /// Returns the File Record Number of the deleted file.
+    ///
+    /// Pass it to [`Ntfs::file`] to read the remaining data of the file.
+    pub fn file_record_number(&self) -> u64 {
+        self.file_record_number
+    }
+
+    /// Returns all $FILE_NAME attributes found in the File Record of the deleted file.
+    pub fn names(&self) -> &[NtfsFileName] {
+        &self.names
+    }
+}
+
+/// A $DATA attribute (data stream) of a deleted file, returned by [`NtfsDeletedFile::data_streams`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsDeletedDataStream {
+    name: String,
+    data_size: u64,
+    recoverability_hint: NtfsRecoverabilityHint,
+}
+
+impl NtfsDeletedDataStream {
+    /// Returns the logical size of the stream, in bytes.
+    pub fn data_size(&self) -> u64 {
+        self.data_size
+    }
+
+    /// Returns the name of the stream, which is empty for the default unnamed data stream.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns how likely the data of this stream can still be recovered.
+    pub fn recoverability_hint(&self) -> NtfsRecoverabilityHint {
+        self.recoverability_hint
+    }
+}
+
+/// Estimate whether the data of a deleted stream can still be recovered,
+/// returned by [`NtfsDeletedDataStream::recoverability_hint`].
+///
+/// It is determined by checking the clusters of all Data Runs against the $Bitmap file.
+/// Clusters that are still free have most likely not been overwritten, but this cannot be guaranteed.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NtfsRecoverabilityHint {
+    /// All clusters of the stream are still free (or the data is stored in the File Record itself).
+    Recoverable,
+    /// Some clusters of the stream have been allocated again and probably contain data of another file.
+    PartiallyOverwritten,
+    /// All clusters of the stream have been allocated again.
+    Overwritten,
+}
+
+/// Iterator over
+///   an [`NtfsDeletedFile`] for each File Record of a deleted file,
+///   returning a `Result<NtfsDeletedFile>` for each entry,
+///   implementing [`Iterator`] and [`FusedIterator`].
+///
+/// This iterator is returned from the [`deleted_files`] function.
+#[derive(Debug)]
+pub struct NtfsDeletedFiles<'n, 'a, T>
+where
+    T: Read + Seek,
+{
+    ntfs: &'n Ntfs,
+    fs: &'a mut T,
+    mft: NtfsFile<'n>,
+    bitmap: NtfsFile<'n>,
+    next_file_record_number: u64,
+    file_record_count: u64,
+}
+
+impl<'n, 'a, T> NtfsDeletedFiles<'n, 'a, T>
+where
+    T: Read + Seek,
+{
+    /// Returns the number of clusters in `lcns` that are marked as allocated in the $Bitmap file.
+    ///
+    /// Clusters beyond the end of the $Bitmap file do not exist and are counted as allocated.
+    fn allocated_cluster_count(&mut self, lcns: Range<u64>) -> Result<u64> {
+        let data_item = self
+            .bitmap
+            .data(self.fs, "")
+            .ok_or(NtfsError::AttributeNotFound {
+                position: self.bitmap.position(),
+                ty: NtfsAttributeType::Data,
+            })??;
+        let data_attribute = data_item.to_attribute()?;
+        let mut bitmap_value = data_attribute.value(self.fs)?;
+        let bitmap_length = bitmap_value.len();
+
+        let mut allocated_count = 0;
+        let mut lcn = lcns.start;
+        let mut buf = [0u8; 4096];
+        bitmap_value.seek(self.fs, SeekFrom::Start(lcn / 8))?;
+
+        while lcn < lcns.end {
+            let byte_offset = lcn / 8;
+            if byte_offset >= bitmap_length {
+                allocated_count += lcns.end - lcn;
+                break;
+            }
+
+            let end_byte_offset = u64::min((lcns.end + 7) / 8, bitmap_length);
+            let bytes_to_read = u64::min(buf.len() as u64, end_byte_offset - byte_offset) as usize;
+            bitmap_value.read_exact(self.fs, &mut buf[..bytes_to_read])?;
+
+            for byte in &buf[..bytes_to_read] {
+                let byte_end_lcn = u64::min((lcn / 8 + 1) * 8, lcns.end);
+
+                while lcn < byte_end_lcn {
+                    if byte & (1 << (lcn % 8)) != 0 {
+                        allocated_count += 1;
+                    }
+
+                    lcn += 1;
+                }
+            }
+        }
+
+        Ok(allocated_count)
+    }
+
+    fn data_stream(&mut self, attribute: &NtfsAttribute) -> Result<NtfsDeletedDataStream> {
+        let name = attribute.name()?.to_string_lossy();
+        let data_size = attribute.value_length();
+
+        let mut cluster_count = 0;
+        let mut allocated_count = 0;
+
+        if !attribute.is_resident() {
+            let cluster_size = self.ntfs.cluster_size() as u64;
+            let partition_offset = self.ntfs.partition_offset();
+
+            for data_run in attribute.to_metadata()?.data_runs() {
+                // Sparse Data Runs have no clusters that could have been overwritten.
+                let position = match data_run.data_position().value() {
+                    Some(position) => position.get(),
+                    None => continue,
+                };
+
+                // Data Runs are always cluster-aligned.
+                let start_lcn = (position - partition_offset) / cluster_size;
+                let run_cluster_count = data_run.allocated_size() / cluster_size;
+                cluster_count += run_cluster_count;
+                allocated_count +=
+                    self.allocated_cluster_count(start_lcn..start_lcn + run_cluster_count)?;
+            }
+        }
+
+        let recoverability_hint = if allocated_count == 0 {
+            NtfsRecoverabilityHint::Recoverable
+        } else if allocated_count < cluster_count {
+            NtfsRecoverabilityHint::PartiallyOverwritten
+        } else {
+            NtfsRecoverabilityHint::Overwritten
+        };
+
+        Ok(NtfsDeletedDataStream {
+            name,
+            data_size,
+            recoverability_hint,
+        })
+    }
+
+    /// Collects the names and data streams of the given File Record.
+    ///
+    /// Returns `None` if the File Record has no $FILE_NAME attribute.
+    fn deleted_file(&mut self, file: &NtfsFile) -> Result<Option<NtfsDeletedFile>> {
+        let mut names = Vec::new();
+        let mut data_streams = Vec::new();
+
+        for attribute in file.attributes_raw() {
+            let attribute = attribute?;
+
+            match attribute.ty()? {
+                NtfsAttributeType::FileName => {
+                    names.push(attribute.structured_value::<_, NtfsFileName>(self.fs)?);
+                }
+                NtfsAttributeType::Data if !attribute.is_continuation() => {
+                    data_streams.push(self.data_stream(&attribute)?);
+                }
+                _ => (),
+            }
+        }
+
+        if names.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(NtfsDeletedFile {
+            file_record_number: file.file_record_number(),
+            names,
+            data_streams,
+        }))
+    }
+}
+
+impl<'n, 'a, T> Iterator for NtfsDeletedFiles<'n, 'a, T>
+where
+    T: Read + Seek,
+{
+    type Item = Result<NtfsDeletedFile>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next_file_record_number < self.file_record_count {
+            let file_record_number = self.next_file_record_number;
+            self.next_file_record_number += 1;
+
+            let (data, position) =
+                iter_try!(self
+                    .ntfs
+                    .read_file_record_data(self.fs, &self.mft, file_record_number));
+
+            if NtfsFile::flags_from_data(&data).contains(NtfsFileFlags::IN_USE) {
+                continue;
+            }
+
+            // Records that have never been used may be entirely zeroed.
+            let file = match NtfsFile::from_data(self.ntfs, data, position, file_record_number) {
+                Ok(file) => file,
+                Err(NtfsError::InvalidFileSignature { .. }) => continue,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if !file.is_base_record() {
+                continue;
+            }
+
+            if let Some(deleted_file) = iter_try!(self.deleted_file(&file)) {
+                return Some(Ok(deleted_file));
+            }
+        }
+
+        None
+    }
+}
+
+impl<'n, 'a, T> FusedIterator for NtfsDeletedFiles<'n, 'a, T> where T: Read + Seek {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::tests::{clear_in_use, patch_file_record_number};
+    use crate::io::Cursor;
+
+    /// Marks "1000-bytes-file" (File Record 66) as not in use, like NTFS does when deleting it.
+    /// If `free_clusters` is set, its two clusters at LCN 2567 are also marked as free in the $Bitmap file.
+    fn delete_1000_bytes_file(testfs1: &mut Cursor<Vec<u8>>, free_clusters: bool) {
+        let bitmap_position = {
+            let ntfs = Ntfs::new(testfs1).unwrap();
+            let bitmap = ntfs
+                .file(testfs1, KnownNtfsFileRecordNumber::Bitmap as u64)
+                .unwrap();
+            let bitmap_data_item = bitmap.data(testfs1, "").unwrap().unwrap();
+            let bitmap_data_attribute = bitmap_data_item.to_attribute().unwrap();
+            let bitmap_data_run =
+                bitmap_data_attribute.to_metadata().unwrap().data_runs()[0].clone();
+
+            bitmap_data_run.data_position().value().unwrap().get() as usize
+        };
+
+        patch_file_record_number(testfs1, 66, clear_in_use);
+
+        if free_clusters {
+            // LCN 2567 and 2568 are bits 7 and 8 of the $Bitmap file.
+            let bitmap = &mut testfs1.get_mut()[bitmap_position + 2567 / 8..];
+            bitmap[0] &= !0x80;
+            bitmap[1] &= !0x01;
+        }
+    }
+
+    fn deleted_1000_bytes_file(testfs1: &mut Cursor<Vec<u8>>) -> NtfsDeletedFile {
+        let ntfs = Ntfs::new(testfs1).unwrap();
+        let deleted_files = deleted_files(&ntfs, testfs1)
+            .unwrap()
+            .collect::<Result<Vec<NtfsDeletedFile>>>()
+            .unwrap();
+
+        assert_eq!(deleted_files.len(), 1);
+        deleted_files.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn test_deleted_files() {
+        // Records that are not in use, but have no $FILE_NAME attribute, are not reported.
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        assert_eq!(deleted_files(&ntfs, &mut testfs1).unwrap().count(), 0);
+
+        // A deleted file whose clusters are still free is recoverable.
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        delete_1000_bytes_file(&mut testfs1, true);
+        let deleted_file = deleted_1000_bytes_file(&mut testfs1);
+        assert_eq!(deleted_file.file_record_number(), 66);
+        assert!(deleted_file
+            .names()
+            .iter()
+            .any(|name| name.name() == "1000-bytes-file"));
+        assert_eq!(
+            deleted_file.data_streams(),
+            &[NtfsDeletedDataStream {
+                name: String::new(),
+                data_size: 1000,
+                recoverability_hint: NtfsRecoverabilityHint::Recoverable,
+            }]
+        );
+
+        // If its clusters are still allocated, they have most likely been reused.
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        delete_1000_bytes_file(&mut testfs1, false);
+        let deleted_file = deleted_1000_bytes_file(&mut testfs1);
+        assert_eq!(deleted_file.data_streams().len(), 1);
+        assert_eq!(
+            deleted_file.data_streams()[0].recoverability_hint(),
+            NtfsRecoverabilityHint::Overwritten
+        );
+    }
+}
@@ -0,0 +1,286 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use nt_string::u16strle::U16StrLe;
+
+use crate::attribute::NtfsAttributeType;
+use crate::error::Result;
+use crate::file::{KnownNtfsFileRecordNumber, NtfsFile, NtfsFileFlags};
+use crate::file_reference::NtfsFileReference;
+use crate::io::{Read, Seek};
+use crate::ntfs::Ntfs;
+use crate::structured_values::{NtfsFileName, NtfsFileNamespace};
+
+/// The preferred name of a file along with a reference to its parent directory,
+/// as returned by [`Ntfs::resolve_names`].
+#[derive(Clone, Debug)]
+pub struct NtfsResolvedName {
+    file_name: NtfsFileName,
+    sequence_number: u16,
+}
+
+impl NtfsResolvedName {
+    pub(crate) fn resolve<T>(
+        ntfs: &Ntfs,
+        fs: &mut T,
+        record_numbers: &[u64],
+    ) -> Result<Vec<Option<Self>>>
+    where
+        T: Read + Seek,
+    {
+        let mft = ntfs.file(fs, KnownNtfsFileRecordNumber::MFT as u64)?;
+        let file_record_count = ntfs.mft_record_count(fs)?;
+
+        // Every File Record is read only once, no matter how often it has been requested.
+        let mut unique_record_numbers = record_numbers
+            .iter()
+            .copied()
+            .filter(|file_record_number| *file_record_number < file_record_count)
+            .collect::<Vec<u64>>();
+        unique_record_numbers.sort_unstable();
+        unique_record_numbers.dedup();
+
+        // Read the File Records in the order in which they are stored on the filesystem.
+        let mut read_order = disk_order(ntfs, &mft, &unique_record_numbers)?;
+        read_order.sort_unstable();
+
+        let mut unique_names = vec![None; unique_record_numbers.len()];
+        for (_, index) in read_order {
+            unique_names[index] = Self::read(ntfs, fs, &mft, unique_record_numbers[index])?;
+        }
+
+        // Return the names in the order they have been requested.
+        let names = record_numbers
+            .iter()
+            .map(|file_record_number| {
+                unique_record_numbers
+                    .binary_search(file_record_number)
+                    .ok()
+                    .and_then(|index| unique_names[index].clone())
+            })
+            .collect();
+
+        Ok(names)
+    }
+
+    fn read<T>(
+        ntfs: &Ntfs,
+        fs: &mut T,
+        mft: &NtfsFile,
+        file_record_number: u64,
+    ) -> Result<Option<Self>>
+    where
+        T: Read + Seek,
+    {
+        let (data, position) = ntfs.read_file_record_data(fs, mft, file_record_number)?;
+
+        // Check the flags before parsing the File Record, because records that are not in use
+        // may contain any garbage (or be entirely zeroed).
+        if !NtfsFile::flags_from_data(&data).contains(NtfsFileFlags::IN_USE) {
+            return Ok(None);
+        }
+
+        let file = NtfsFile::from_data(ntfs, data, position, file_record_number)?;
+        if !file.is_base_record() {
+            // Extension records don't have names of their own.
+            return Ok(None);
+        }
+
+        let mut best_file_name = None;
+        let mut has_attribute_list = false;
+
+        for attribute in file.attributes_raw() {
+            let attribute = attribute?;
+
+            match attribute.ty()? {
+                NtfsAttributeType::AttributeList => has_attribute_list = true,
+                NtfsAttributeType::FileName => {
+                    let file_name = attribute.structured_value::<_, NtfsFileName>(fs)?;
+                    pick_better(&mut best_file_name, file_name);
+                }
+                _ => (),
+            }
+        }
+
+        if best_file_name.is_none() && has_attribute_list {
+            // All $FILE_NAME attributes have been moved to extension records.
+            let mut iter = file.names();
+
+            while let Some(file_name) = iter.next(fs) {
+                pick_better(&mut best_file_name, file_name?);
+            }
+        }
+
+        Ok(best_file_name.map(|file_name| Self {
+            file_name,
+            sequence_number: file.sequence_number(),
+        }))
+    }
+
+    /// Returns the full $FILE_NAME attribute that has been picked as the preferred name.
+    pub fn file_name(&self) -> &NtfsFileName {
+        &self.file_name
+    }
+
+    /// Returns the preferred name of the file.
+    pub fn name(&self) -> U16StrLe<'_> {
+        self.file_name.name()
+    }
+
+    /// Returns a reference to the parent directory of the name returned by [`NtfsResolvedName::name`].
+    pub fn parent_directory_reference(&self) -> NtfsFileReference {
+        self.file_name.parent_directory_reference()
+    }
+
+    /// Returns the sequence number of the File Record when it was read.
+    ///
+    /// Compare it with the sequence number of an [`NtfsFileReference`] to check that the File Record
+    /// hasn't been reused for another file in the meantime.
+    pub fn sequence_number(&self) -> u16 {
+        self.sequence_number
+    }
+}
+
+/// Returns the absolute byte position of each File Record along with its index in `sorted_record_numbers`.
+fn disk_order(
+    ntfs: &Ntfs,
+    mft: &NtfsFile,
+    sorted_record_numbers: &[u64],
+) -> Result<Vec<(u64, usize)>> {
+    let file_record_size = ntfs.file_record_size() as u64;
+    let offsets = sorted_record_numbers
+        .iter()
+        .map(|file_record_number| file_record_number * file_record_size);
+
+    if ntfs.is_mft_dump() {
+        // The reader contains nothing but the MFT, so the offsets are the positions.
+        return Ok(offsets.zip(0..).collect());
+    }
+
+    // The MFT may be split into multiple Data Runs.
+    // As both the Data Runs and the offsets are in ascending order, a single pass over both is enough.
+    let mft_data_attribute = mft.find_resident_attribute(NtfsAttributeType::Data, None, None)?;
+    let metadata = mft_data_attribute.to_metadata()?;
+    let mut data_runs = metadata.data_runs().iter();
+    let mut data_run = data_runs.next();
+    let mut data_run_offset = 0;
+    let mut positions = Vec::with_capacity(sorted_record_numbers.len());
+
+    for (offset, index) in offsets.zip(0..) {
+        while let Some(run) = data_run {
+            if offset < data_run_offset + run.allocated_size() {
+                break;
+            }
+
+            data_run_offset += run.allocated_size();
+            data_run = data_runs.next();
+        }
+
+        // Anything we can't map is read last and fails with a proper error then.
+        let position = data_run
+            .and_then(|run| run.data_position().value())
+            .map(|position| position.get() + (offset - data_run_offset))
+            .unwrap_or(u64::MAX);
+        positions.push((position, index));
+    }
+
+    Ok(positions)
+}
+
+/// Replaces `best` by `file_name` if the latter has a more preferred namespace.
+///
+/// [`NtfsFileNamespace::Win32`] names are preferred over [`NtfsFileNamespace::Win32AndDos`] names,
+/// which are preferred over all others.
+/// Among names of the same rank, the first one is kept.
+fn pick_better(best: &mut Option<NtfsFileName>, file_name: NtfsFileName) {
+    let is_better = match best {
+        Some(best) => namespace_rank(file_name.namespace()) > namespace_rank(best.namespace()),
+        None => true,
+    };
+
+    if is_better {
+        *best = Some(file_name);
+    }
+}
+
+fn namespace_rank(namespace: NtfsFileNamespace) -> u8 {
+    match namespace {
+        NtfsFileNamespace::Dos | NtfsFileNamespace::Posix => 0,
+        NtfsFileNamespace::Win32AndDos => 1,
+        NtfsFileNamespace::Win32 => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::tests::{clear_in_use, patch_file_record_number};
+
+    #[test]
+    fn test_resolve_names() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file_record_count = ntfs.mft_record_count(&mut testfs1).unwrap();
+
+        // Request all File Records in descending order, some of them twice, and some that don't exist.
+        let mut record_numbers = (0..file_record_count).rev().collect::<Vec<u64>>();
+        record_numbers.extend([65, 5, 65, file_record_count, u64::MAX]);
+
+        let names = ntfs.resolve_names(&mut testfs1, &record_numbers).unwrap();
+        assert_eq!(names.len(), record_numbers.len());
+
+        // Compare against resolving each file on its own.
+        // Every file of testfs1 has at most a single name.
+        let mut resolved_count = 0;
+        for (file_record_number, name) in record_numbers.iter().zip(&names) {
+            let expected = if *file_record_number < file_record_count {
+                let file = ntfs.file(&mut testfs1, *file_record_number).unwrap();
+                if file.flags().contains(NtfsFileFlags::IN_USE) {
+                    file.name(&mut testfs1, None, None)
+                        .map(|file_name| (file_name.unwrap(), file.sequence_number()))
+                } else {
+                    None
+                }
+            } else {
+                None
+            };
+
+            match (name, expected) {
+                (Some(name), Some((file_name, sequence_number))) => {
+                    assert_eq!(name.name(), file_name.name());
+                    assert_eq!(
+                        name.parent_directory_reference(),
+                        file_name.parent_directory_reference()
+                    );
+                    assert_eq!(name.sequence_number(), sequence_number);
+                    resolved_count += 1;
+                }
+                (None, None) => (),
+                (name, expected) => panic!(
+                    "File Record {file_record_number}: resolved {name:?}, expected {expected:?}"
+                ),
+            }
+        }
+
+        // 532 named files in use, plus 3 duplicates.
+        assert_eq!(resolved_count, 532 + 3);
+        let root_name = names[names.len() - 4].as_ref().unwrap();
+        assert_eq!(root_name.name(), ".");
+        assert_eq!(
+            root_name.parent_directory_reference().file_record_number(),
+            KnownNtfsFileRecordNumber::RootDirectory as u64
+        );
+
+        // A deleted file can't be resolved anymore.
+        patch_file_record_number(&mut testfs1, 65, clear_in_use);
+
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let names = ntfs.resolve_names(&mut testfs1, &[66, 65, 66]).unwrap();
+        assert_eq!(names[0].as_ref().unwrap().name(), "1000-bytes-file");
+        assert!(names[1].is_none());
+        assert_eq!(names[2].as_ref().unwrap().name(), "1000-bytes-file");
+    }
+}
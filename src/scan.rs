@@ -0,0 +1,345 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+//! Functions for efficiently scanning all File Records of the Master File Table (MFT).
+//!
+//! Contrary to traversing the directory tree, an MFT scan visits every file exactly once in the order of their
+//! File Record Numbers, which makes it the fastest way to enumerate all files of a filesystem.
+
+use core::iter::FusedIterator;
+
+use crate::io::{Read, Seek};
+
+use crate::attribute::{NtfsAttribute, NtfsAttributeType};
+use crate::error::Result;
+use crate::file::{KnownNtfsFileRecordNumber, NtfsFile, NtfsFileFlags};
+use crate::file_reference::NtfsFileReference;
+use crate::ntfs::Ntfs;
+use crate::structured_values::{NtfsFileName, NtfsFileNamespace, NtfsStandardInformation};
+
+/// Returns an iterator over an [`NtfsRecordSummary`] for each in-use File Record of the filesystem.
+///
+/// Records that are not in use and extension records of other files (see [`NtfsAttributeList`] and
+/// [`NtfsFile::is_base_record`]) are skipped.
+///
+/// All fields of a summary are collected in a single pass over the attributes of the File Record.
+/// Attribute Lists are only traversed if a field cannot be found in the base File Record.
+/// This makes the iterator considerably faster than calling [`NtfsFile::info`], [`NtfsFile::name`], and
+/// [`NtfsFile::data`] for every file.
+///
+/// [`NtfsAttributeList`]: crate::structured_values::NtfsAttributeList
+pub fn records_summary<'n, 'a, T>(
+    ntfs: &'n Ntfs,
+    fs: &'a mut T,
+) -> Result<NtfsRecordSummaries<'n, 'a, T>>
+where
+    T: Read + Seek,
+{
+    let mft = ntfs.file(fs, KnownNtfsFileRecordNumber::MFT as u64)?;
+    let mft_data_length = mft
+        .find_resident_attribute(NtfsAttributeType::Data, None, None)?
+        .value_length();
+    let file_record_count = mft_data_length / ntfs.file_record_size() as u64;
+
+    Ok(NtfsRecordSummaries {
+        ntfs,
+        fs,
+        mft,
+        next_file_record_number: 0,
+        file_record_count,
+    })
+}
+
+/// Summary of the most commonly needed information about a file, returned by [`records_summary`].
+#[derive(Clone, Debug)]
+pub struct NtfsRecordSummary {
+    file_record_number: u64,
+    flags: NtfsFileFlags,
+    file_name: Option<NtfsFileName>,
+    standard_information: Option<NtfsStandardInformation>,
+    data_size: Option<u64>,
+}
+
+impl NtfsRecordSummary {
+    fn new(file: &NtfsFile) -> Self {
+        Self {
+            file_record_number: file.file_record_number(),
+            flags: file.flags(),
+            file_name: None,
+            standard_information: None,
+            data_size: None,
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.file_name.is_some() && self.standard_information.is_some() && self.data_size.is_some()
+    }
+
+    fn collect<T>(&mut self, fs: &mut T, attribute: &NtfsAttribute) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        match attribute.ty()? {
+            NtfsAttributeType::StandardInformation if self.standard_information.is_none() => {
+                self.standard_information = Some(attribute.structured_value(fs)?);
+            }
+            NtfsAttributeType::FileName => {
+                let file_name = attribute.structured_value::<_, NtfsFileName>(fs)?;
+                let is_better = match &self.file_name {
+                    Some(best) => {
+                        namespace_rank(file_name.namespace()) > namespace_rank(best.namespace())
+                    }
+                    None => true,
+                };
+
+                if is_better {
+                    self.file_name = Some(file_name);
+                }
+            }
+            NtfsAttributeType::Data if self.data_size.is_none() && attribute.name_length() == 0 => {
+                self.data_size = Some(attribute.value_length());
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Returns the size of the unnamed $DATA attribute (i.e. the file data), in bytes.
+    ///
+    /// Returns `None` if the file has no unnamed $DATA attribute (like all directories).
+    pub fn data_size(&self) -> Option<u64> {
+        self.data_size
+    }
+
+    /// Returns the NTFS File Record Number of this file.
+    pub fn file_record_number(&self) -> u64 {
+        self.file_record_number
+    }
+
+    /// Returns the flags of the File Record (see [`NtfsFile::flags`]).
+    pub fn flags(&self) -> NtfsFileFlags {
+        self.flags
+    }
+
+    /// Returns the $STANDARD_INFORMATION attribute of this file (with all timestamps and file attributes),
+    /// or `None` if the file has none.
+    pub fn info(&self) -> Option<&NtfsStandardInformation> {
+        self.standard_information.as_ref()
+    }
+
+    /// Returns whether this file is a directory.
+    pub fn is_directory(&self) -> bool {
+        self.flags.contains(NtfsFileFlags::IS_DIRECTORY)
+    }
+
+    /// Returns the best $FILE_NAME attribute of this file, or `None` if the file has none.
+    ///
+    /// Long names ([`NtfsFileNamespace::Win32`] and [`NtfsFileNamespace::Win32AndDos`]) are preferred over
+    /// [`NtfsFileNamespace::Posix`] names, which are preferred over [`NtfsFileNamespace::Dos`] names.
+    /// If a file has multiple names of the same rank (hard links), the first one is returned.
+    pub fn name(&self) -> Option<&NtfsFileName> {
+        self.file_name.as_ref()
+    }
+
+    /// Returns a reference to the parent directory of the name returned by [`NtfsRecordSummary::name`].
+    pub fn parent_directory_reference(&self) -> Option<NtfsFileReference> {
+        self.file_name
+            .as_ref()
+            .map(|file_name| file_name.parent_directory_reference())
+    }
+}
+
+fn namespace_rank(namespace: NtfsFileNamespace) -> u8 {
+    match namespace {
+        NtfsFileNamespace::Dos => 0,
+        NtfsFileNamespace::Posix => 1,
+        NtfsFileNamespace::Win32 | NtfsFileNamespace::Win32AndDos => 2,
+    }
+}
+
+/// Iterator over
+///   an [`NtfsRecordSummary`] for each in-use File Record,
+///   returning a `Result<NtfsRecordSummary>` for each entry,
+///   implementing [`Iterator`] and [`FusedIterator`].
+///
+/// This iterator is returned from the [`records_summary`] function.
+#[derive(Debug)]
+pub struct NtfsRecordSummaries<'n, 'a, T>
+where
+    T: Read + Seek,
+{
+    ntfs: &'n Ntfs,
+    fs: &'a mut T,
+    mft: NtfsFile<'n>,
+    next_file_record_number: u64,
+    file_record_count: u64,
+}
+
+impl<'n, 'a, T> NtfsRecordSummaries<'n, 'a, T>
+where
+    T: Read + Seek,
+{
+    fn summarize(&mut self, file: &NtfsFile) -> Result<NtfsRecordSummary> {
+        let mut summary = NtfsRecordSummary::new(file);
+        let mut has_attribute_list = false;
+
+        for attribute in file.attributes_raw() {
+            let attribute = attribute?;
+
+            if attribute.ty()? == NtfsAttributeType::AttributeList {
+                has_attribute_list = true;
+            } else {
+                summary.collect(self.fs, &attribute)?;
+            }
+        }
+
+        if has_attribute_list && !summary.is_complete() {
+            // Some fields may be stored in extension records, which are only reachable via the Attribute List.
+            // Traverse it, but only pick up what is still missing.
+            let missing_file_name = summary.file_name.is_none();
+            let mut iter = file.attributes();
+
+            while let Some(item) = iter.next(self.fs) {
+                let item = item?;
+                let attribute = item.to_attribute()?;
+
+                if attribute.ty()? == NtfsAttributeType::FileName && !missing_file_name {
+                    continue;
+                }
+
+                summary.collect(self.fs, &attribute)?;
+            }
+        }
+
+        Ok(summary)
+    }
+}
+
+impl<'n, 'a, T> Iterator for NtfsRecordSummaries<'n, 'a, T>
+where
+    T: Read + Seek,
+{
+    type Item = Result<NtfsRecordSummary>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next_file_record_number < self.file_record_count {
+            let file_record_number = self.next_file_record_number;
+            self.next_file_record_number += 1;
+
+            let (data, position) =
+                iter_try!(self
+                    .ntfs
+                    .read_file_record_data(self.fs, &self.mft, file_record_number));
+
+            // Check the flags before parsing the File Record, because records that are not in use
+            // may contain any garbage (or be entirely zeroed).
+            if !NtfsFile::flags_from_data(&data).contains(NtfsFileFlags::IN_USE) {
+                continue;
+            }
+
+            let file = iter_try!(NtfsFile::from_data(
+                self.ntfs,
+                data,
+                position,
+                file_record_number
+            ));
+
+            if !file.is_base_record() {
+                // This is an extension record, which is summarized as part of its base File Record.
+                continue;
+            }
+
+            return Some(self.summarize(&file));
+        }
+
+        None
+    }
+}
+
+impl<'n, 'a, T> FusedIterator for NtfsRecordSummaries<'n, 'a, T> where T: Read + Seek {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_summary() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        let summaries = records_summary(&ntfs, &mut testfs1)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert!(summaries.len() > 500);
+
+        // The summaries must contain the same values as returned by the individual convenience functions.
+        for summary in &summaries {
+            let file = ntfs
+                .file(&mut testfs1, summary.file_record_number())
+                .unwrap();
+            assert!(file.flags().contains(NtfsFileFlags::IN_USE));
+            assert_eq!(summary.flags(), file.flags());
+
+            let info = file.info().ok();
+            assert_eq!(
+                summary.info().map(|info| info.modification_time()),
+                info.map(|info| info.modification_time())
+            );
+
+            let data_size = file
+                .data(&mut testfs1, "")
+                .map(|data_item| data_item.unwrap().to_attribute().unwrap().value_length());
+            assert_eq!(summary.data_size(), data_size);
+
+            let name = [
+                NtfsFileNamespace::Win32,
+                NtfsFileNamespace::Win32AndDos,
+                NtfsFileNamespace::Posix,
+                NtfsFileNamespace::Dos,
+            ]
+            .into_iter()
+            .find_map(|namespace| file.name(&mut testfs1, Some(namespace), None))
+            .map(|file_name| file_name.unwrap());
+            assert_eq!(
+                summary
+                    .name()
+                    .map(|file_name| file_name.name().to_string_lossy()),
+                name.as_ref()
+                    .map(|file_name| file_name.name().to_string_lossy())
+            );
+            assert_eq!(
+                summary
+                    .parent_directory_reference()
+                    .map(|reference| reference.file_record_number()),
+                name.map(|file_name| file_name.parent_directory_reference().file_record_number())
+            );
+        }
+
+        // Check a few well-known files.
+        let root_dir = summaries
+            .iter()
+            .find(|summary| {
+                summary.file_record_number() == KnownNtfsFileRecordNumber::RootDirectory as u64
+            })
+            .unwrap();
+        assert!(root_dir.is_directory());
+        assert_eq!(root_dir.name().unwrap().name(), ".");
+        assert_eq!(root_dir.data_size(), None);
+
+        let file = summaries
+            .iter()
+            .find(|summary| summary.file_record_number() == 65)
+            .unwrap();
+        assert!(!file.is_directory());
+        assert_eq!(file.name().unwrap().name(), "file-with-12345");
+        assert_eq!(
+            file.parent_directory_reference()
+                .unwrap()
+                .file_record_number(),
+            KnownNtfsFileRecordNumber::RootDirectory as u64
+        );
+        assert_eq!(file.data_size(), Some(5));
+    }
+}
@@ -0,0 +1,968 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+//! Parser for Windows security descriptors.
+//!
+//! NTFS stores the security descriptors of all files in the $SDS data stream of the $Secure file
+//! (or in a $SECURITY_DESCRIPTOR attribute on old filesystems).
+//! They are stored in the self-relative format (`SECURITY_DESCRIPTOR_RELATIVE`), which can be parsed via
+//! [`NtfsSecurityDescriptor::parse`].
+//!
+//! The parser only works on byte slices and performs strict bounds checks, so it can be used for
+//! security descriptors from any source.
+//! It only decodes the structures and doesn't evaluate any access rights.
+
+use core::fmt;
+use core::iter::FusedIterator;
+use core::ops::Range;
+
+use arrayvec::ArrayVec;
+use bitflags::bitflags;
+use byteorder::{ByteOrder, LittleEndian};
+use enumn::N;
+
+use crate::error::{NtfsError, Result};
+use crate::guid::{NtfsGuid, GUID_SIZE};
+
+/// Size of the `SECURITY_DESCRIPTOR_RELATIVE` header.
+const SECURITY_DESCRIPTOR_HEADER_SIZE: usize = 20;
+
+/// The only revision of security descriptors.
+const SECURITY_DESCRIPTOR_REVISION: u8 = 1;
+
+/// Size of the fixed part of a SID (revision, sub authority count, identifier authority).
+const SID_HEADER_SIZE: usize = 8;
+
+/// Maximum number of sub authorities of a SID.
+const SID_MAX_SUB_AUTHORITIES: usize = 15;
+
+/// Size of an ACL header.
+const ACL_HEADER_SIZE: usize = 8;
+
+/// Size of an ACE header.
+const ACE_HEADER_SIZE: usize = 4;
+
+/// Size of an ACE header and the access mask following it.
+const ACE_MASK_END: usize = ACE_HEADER_SIZE + 4;
+
+bitflags! {
+    /// Flags returned by [`NtfsSecurityDescriptor::control`].
+    #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    pub struct NtfsSecurityDescriptorControl: u16 {
+        const OWNER_DEFAULTED = 0x0001;
+        const GROUP_DEFAULTED = 0x0002;
+        /// The security descriptor has a DACL (which may still be a null DACL).
+        const DACL_PRESENT = 0x0004;
+        const DACL_DEFAULTED = 0x0008;
+        /// The security descriptor has a SACL.
+        const SACL_PRESENT = 0x0010;
+        const SACL_DEFAULTED = 0x0020;
+        const DACL_AUTO_INHERIT_REQ = 0x0100;
+        const SACL_AUTO_INHERIT_REQ = 0x0200;
+        const DACL_AUTO_INHERITED = 0x0400;
+        const SACL_AUTO_INHERITED = 0x0800;
+        const DACL_PROTECTED = 0x1000;
+        const SACL_PROTECTED = 0x2000;
+        const RM_CONTROL_VALID = 0x4000;
+        /// The security descriptor is in the self-relative format.
+        const SELF_RELATIVE = 0x8000;
+    }
+}
+
+impl fmt::Display for NtfsSecurityDescriptorControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+bitflags! {
+    /// Flags returned by [`NtfsAce::flags`].
+    #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    pub struct NtfsAceFlags: u8 {
+        const OBJECT_INHERIT = 0x01;
+        const CONTAINER_INHERIT = 0x02;
+        const NO_PROPAGATE_INHERIT = 0x04;
+        const INHERIT_ONLY = 0x08;
+        /// The ACE has been inherited from a parent object.
+        const INHERITED = 0x10;
+        const SUCCESSFUL_ACCESS = 0x40;
+        const FAILED_ACCESS = 0x80;
+    }
+}
+
+impl fmt::Display for NtfsAceFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// Type of an [`NtfsAce`], returned by [`NtfsAce::ty`].
+#[derive(Clone, Copy, Debug, Eq, Hash, N, Ord, PartialEq, PartialOrd)]
+#[repr(u8)]
+pub enum NtfsAceType {
+    AccessAllowed = 0x00,
+    AccessDenied = 0x01,
+    SystemAudit = 0x02,
+    SystemAlarm = 0x03,
+    AccessAllowedCompound = 0x04,
+    AccessAllowedObject = 0x05,
+    AccessDeniedObject = 0x06,
+    SystemAuditObject = 0x07,
+    SystemAlarmObject = 0x08,
+    AccessAllowedCallback = 0x09,
+    AccessDeniedCallback = 0x0a,
+    AccessAllowedCallbackObject = 0x0b,
+    AccessDeniedCallbackObject = 0x0c,
+    SystemAuditCallback = 0x0d,
+    SystemAlarmCallback = 0x0e,
+    SystemAuditCallbackObject = 0x0f,
+    SystemAlarmCallbackObject = 0x10,
+    SystemMandatoryLabel = 0x11,
+    SystemResourceAttribute = 0x12,
+    SystemScopedPolicyId = 0x13,
+}
+
+impl NtfsAceType {
+    /// Returns whether this ACE type has the layout of an object ACE (with optional object type GUIDs).
+    fn is_object_ace(&self) -> bool {
+        matches!(
+            self,
+            Self::AccessAllowedObject
+                | Self::AccessDeniedObject
+                | Self::SystemAuditObject
+                | Self::SystemAlarmObject
+                | Self::AccessAllowedCallbackObject
+                | Self::AccessDeniedCallbackObject
+                | Self::SystemAuditCallbackObject
+                | Self::SystemAlarmCallbackObject
+        )
+    }
+}
+
+/// Returns the subslice of `data` in the given range, or an error if the range is out of bounds.
+fn subslice(data: &[u8], range: Range<usize>) -> Result<&[u8]> {
+    data.get(range.clone())
+        .ok_or(NtfsError::InvalidSecurityDescriptorRange {
+            range,
+            size: data.len(),
+        })
+}
+
+/// Returns the byte range of `length` bytes at byte offset `offset` of `data`, or an error if that range
+/// is out of bounds.
+///
+/// Offsets are read from the filesystem, so the end of the range is computed without overflowing.
+fn checked_range(data: &[u8], offset: usize, length: usize) -> Result<Range<usize>> {
+    let end = offset
+        .checked_add(length)
+        .ok_or(NtfsError::InvalidSecurityDescriptorRange {
+            range: offset..usize::MAX,
+            size: data.len(),
+        })?;
+    let range = offset..end;
+
+    subslice(data, range.clone())?;
+    Ok(range)
+}
+
+/// Returns the byte range of the SID at byte offset `offset` of `data`, after checking its bounds.
+fn sid_range(data: &[u8], offset: usize) -> Result<Range<usize>> {
+    let header = &data[checked_range(data, offset, SID_HEADER_SIZE)?];
+    let sub_authority_count = header[1];
+
+    if sub_authority_count as usize > SID_MAX_SUB_AUTHORITIES {
+        return Err(NtfsError::InvalidSidSubAuthorityCount {
+            count: sub_authority_count,
+        });
+    }
+
+    checked_range(
+        data,
+        offset,
+        SID_HEADER_SIZE + sub_authority_count as usize * 4,
+    )
+}
+
+/// Returns the byte range of the ACL at byte offset `offset` of `data`, after checking its bounds.
+fn acl_range(data: &[u8], offset: usize) -> Result<Range<usize>> {
+    let header = &data[checked_range(data, offset, ACL_HEADER_SIZE)?];
+    let acl_size = LittleEndian::read_u16(&header[2..]) as usize;
+
+    if acl_size < ACL_HEADER_SIZE {
+        return Err(NtfsError::InvalidSecurityDescriptorRange {
+            range: offset..offset + acl_size,
+            size: data.len(),
+        });
+    }
+
+    checked_range(data, offset, acl_size)
+}
+
+/// Header of a self-relative security descriptor, with the byte ranges of all structures it references.
+///
+/// This is shared between [`NtfsSecurityDescriptor`] and [`NtfsSecurityDescriptorAttribute`].
+///
+/// [`NtfsSecurityDescriptorAttribute`]: crate::structured_values::NtfsSecurityDescriptorAttribute
+#[derive(Clone, Debug)]
+pub(crate) struct SecurityDescriptorLayout {
+    pub(crate) revision: u8,
+    pub(crate) control: NtfsSecurityDescriptorControl,
+    pub(crate) owner: Option<Range<usize>>,
+    pub(crate) group: Option<Range<usize>>,
+    pub(crate) sacl: Option<Range<usize>>,
+    pub(crate) dacl: Option<Range<usize>>,
+}
+
+impl SecurityDescriptorLayout {
+    /// Parses the header of a self-relative security descriptor and checks the bounds of all structures it references.
+    pub(crate) fn parse(data: &[u8]) -> Result<Self> {
+        let header = subslice(data, 0..SECURITY_DESCRIPTOR_HEADER_SIZE)?;
+
+        let revision = header[0];
+        if revision != SECURITY_DESCRIPTOR_REVISION {
+            return Err(NtfsError::UnsupportedSecurityDescriptorRevision { revision });
+        }
+
+        let control =
+            NtfsSecurityDescriptorControl::from_bits_truncate(LittleEndian::read_u16(&header[2..]));
+        let owner_offset = LittleEndian::read_u32(&header[4..]) as usize;
+        let group_offset = LittleEndian::read_u32(&header[8..]) as usize;
+        let sacl_offset = LittleEndian::read_u32(&header[12..]) as usize;
+        let dacl_offset = LittleEndian::read_u32(&header[16..]) as usize;
+
+        // An offset of zero denotes a missing structure.
+        let owner = match owner_offset {
+            0 => None,
+            offset => Some(sid_range(data, offset)?),
+        };
+        let group = match group_offset {
+            0 => None,
+            offset => Some(sid_range(data, offset)?),
+        };
+
+        let sacl = match sacl_offset {
+            0 => None,
+            _ if !control.contains(NtfsSecurityDescriptorControl::SACL_PRESENT) => None,
+            offset => Some(acl_range(data, offset)?),
+        };
+        let dacl = match dacl_offset {
+            0 => None,
+            _ if !control.contains(NtfsSecurityDescriptorControl::DACL_PRESENT) => None,
+            offset => Some(acl_range(data, offset)?),
+        };
+
+        Ok(Self {
+            revision,
+            control,
+            owner,
+            group,
+            sacl,
+            dacl,
+        })
+    }
+}
+
+/// A Security Identifier (SID), which identifies a user, group, or computer account.
+///
+/// Its [`Display`](fmt::Display) implementation outputs the common string format (e.g. `S-1-5-32-544`).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct NtfsSid {
+    revision: u8,
+    identifier_authority: [u8; 6],
+    sub_authorities: ArrayVec<u32, SID_MAX_SUB_AUTHORITIES>,
+}
+
+impl NtfsSid {
+    /// Parses a SID from the beginning of the given bytes.
+    ///
+    /// Any bytes after the SID are ignored.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::parse(bytes, 0)
+    }
+
+    /// Parses a SID at byte offset `offset` of `data`.
+    fn parse(data: &[u8], offset: usize) -> Result<Self> {
+        let sid = &data[sid_range(data, offset)?];
+        let revision = sid[0];
+
+        let mut identifier_authority = [0u8; 6];
+        identifier_authority.copy_from_slice(&sid[2..8]);
+
+        let sub_authorities = sid[SID_HEADER_SIZE..]
+            .chunks_exact(4)
+            .map(LittleEndian::read_u32)
+            .collect();
+
+        Ok(Self {
+            revision,
+            identifier_authority,
+            sub_authorities,
+        })
+    }
+
+    /// Returns the identifier authority of this SID (e.g. 5 for `SECURITY_NT_AUTHORITY`).
+    pub fn identifier_authority(&self) -> u64 {
+        // The identifier authority is the only big-endian field.
+        self.identifier_authority
+            .iter()
+            .fold(0, |value, byte| (value << 8) | *byte as u64)
+    }
+
+    /// Returns the revision of this SID (always 1 in practice).
+    pub fn revision(&self) -> u8 {
+        self.revision
+    }
+
+    /// Returns the sub authorities of this SID, the last of which is the relative identifier (RID).
+    pub fn sub_authorities(&self) -> &[u32] {
+        &self.sub_authorities
+    }
+}
+
+impl fmt::Display for NtfsSid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "S-{}-", self.revision)?;
+
+        // Large identifier authorities are output in hexadecimal, just like Windows does.
+        let identifier_authority = self.identifier_authority();
+        if identifier_authority >= 1 << 32 {
+            write!(f, "{identifier_authority:#014x}")?;
+        } else {
+            write!(f, "{identifier_authority}")?;
+        }
+
+        for sub_authority in &self.sub_authorities {
+            write!(f, "-{sub_authority}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A Windows security descriptor in the self-relative format (`SECURITY_DESCRIPTOR_RELATIVE`).
+///
+/// It defines the owner and primary group of a file, as well as its access control lists:
+/// The discretionary ACL (DACL) controls access to the file and the system ACL (SACL) controls auditing.
+///
+/// Reference: <https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-dtyp/7d4dac05-9cef-4563-a058-f108abecce1d>
+#[derive(Clone, Debug)]
+pub struct NtfsSecurityDescriptor<'a> {
+    control: NtfsSecurityDescriptorControl,
+    owner: Option<NtfsSid>,
+    group: Option<NtfsSid>,
+    sacl: Option<NtfsAcl<'a>>,
+    dacl: Option<NtfsAcl<'a>>,
+}
+
+impl<'a> NtfsSecurityDescriptor<'a> {
+    /// Parses a self-relative security descriptor from the given bytes.
+    ///
+    /// This validates the header, the owner and group SIDs, and the headers of both ACLs.
+    /// The ACEs of each ACL are validated while iterating through them via [`NtfsAcl::aces`].
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let layout = SecurityDescriptorLayout::parse(data)?;
+
+        let owner = layout
+            .owner
+            .map(|range| NtfsSid::parse(data, range.start))
+            .transpose()?;
+        let group = layout
+            .group
+            .map(|range| NtfsSid::parse(data, range.start))
+            .transpose()?;
+        let sacl = layout
+            .sacl
+            .map(|range| NtfsAcl::parse(data, range.start))
+            .transpose()?;
+        let dacl = layout
+            .dacl
+            .map(|range| NtfsAcl::parse(data, range.start))
+            .transpose()?;
+
+        Ok(Self {
+            control: layout.control,
+            owner,
+            group,
+            sacl,
+            dacl,
+        })
+    }
+
+    /// Returns the control flags of this security descriptor.
+    pub fn control(&self) -> NtfsSecurityDescriptorControl {
+        self.control
+    }
+
+    /// Returns the discretionary ACL (DACL) of this security descriptor, which controls access to the file.
+    ///
+    /// Returns `None` if the security descriptor has no DACL or a null DACL.
+    /// Both cases have very different meanings, which is why you should check [`NtfsSecurityDescriptor::has_null_dacl`].
+    pub fn dacl(&self) -> Option<&NtfsAcl<'a>> {
+        self.dacl.as_ref()
+    }
+
+    /// Returns the SID of the primary group, or `None` if the security descriptor has no group.
+    pub fn group(&self) -> Option<&NtfsSid> {
+        self.group.as_ref()
+    }
+
+    /// Returns whether this security descriptor has a null DACL.
+    ///
+    /// A null DACL grants full access to everyone, whereas an empty DACL denies access to everyone.
+    pub fn has_null_dacl(&self) -> bool {
+        self.control
+            .contains(NtfsSecurityDescriptorControl::DACL_PRESENT)
+            && self.dacl.is_none()
+    }
+
+    /// Returns the SID of the owner, or `None` if the security descriptor has no owner.
+    pub fn owner(&self) -> Option<&NtfsSid> {
+        self.owner.as_ref()
+    }
+
+    /// Returns the system ACL (SACL) of this security descriptor, which controls auditing and
+    /// holds the mandatory integrity label.
+    ///
+    /// Returns `None` if the security descriptor has no SACL.
+    pub fn sacl(&self) -> Option<&NtfsAcl<'a>> {
+        self.sacl.as_ref()
+    }
+}
+
+/// An access control list (ACL), consisting of access control entries ([`NtfsAce`]).
+///
+/// Returned by [`NtfsSecurityDescriptor::dacl`] and [`NtfsSecurityDescriptor::sacl`].
+#[derive(Clone, Debug)]
+pub struct NtfsAcl<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> NtfsAcl<'a> {
+    /// Parses the ACL at byte offset `offset` of the security descriptor bytes `data`.
+    fn parse(data: &'a [u8], offset: usize) -> Result<Self> {
+        let data = &data[acl_range(data, offset)?];
+        Ok(Self { data, offset })
+    }
+
+    /// Returns the number of ACEs in this ACL, as stored in its header.
+    pub fn ace_count(&self) -> u16 {
+        LittleEndian::read_u16(&self.data[4..])
+    }
+
+    /// Returns an iterator over all ACEs of this ACL.
+    pub fn aces(&self) -> NtfsAces<'a> {
+        NtfsAces {
+            data: self.data,
+            acl_offset: self.offset,
+            offset: ACL_HEADER_SIZE,
+            remaining: self.ace_count(),
+        }
+    }
+
+    /// Returns the revision of this ACL (2, or 4 if it contains object ACEs).
+    pub fn revision(&self) -> u8 {
+        self.data[0]
+    }
+}
+
+/// Iterator over
+///   all ACEs of an [`NtfsAcl`],
+///   returning an [`NtfsAce`] for each entry,
+///   implementing [`Iterator`] and [`FusedIterator`].
+///
+/// This iterator is returned from the [`NtfsAcl::aces`] function.
+/// It stops after the first error.
+#[derive(Clone, Debug)]
+pub struct NtfsAces<'a> {
+    data: &'a [u8],
+    acl_offset: usize,
+    offset: usize,
+    remaining: u16,
+}
+
+impl<'a> NtfsAces<'a> {
+    fn parse_ace(&self) -> Result<NtfsAce> {
+        let ace_offset = self.acl_offset + self.offset;
+        let header = subslice(self.data, self.offset..self.offset + ACE_HEADER_SIZE)
+            .map_err(|_| self.range_error(ACE_HEADER_SIZE))?;
+        let ty = header[0];
+        let flags = NtfsAceFlags::from_bits_truncate(header[1]);
+        let size = LittleEndian::read_u16(&header[2..]);
+
+        if (size as usize) < ACE_HEADER_SIZE {
+            return Err(NtfsError::InvalidAceSize {
+                offset: ace_offset,
+                size,
+            });
+        }
+
+        let ace_data = subslice(self.data, self.offset..self.offset + size as usize)
+            .map_err(|_| self.range_error(size as usize))?;
+
+        let mut ace = NtfsAce {
+            ty,
+            flags,
+            size,
+            access_mask: None,
+            object_type: None,
+            inherited_object_type: None,
+            sid: None,
+        };
+
+        let ace_type = match NtfsAceType::n(ty) {
+            Some(NtfsAceType::AccessAllowedCompound) | None => {
+                // We don't know the layout of this ACE, so only return its header.
+                return Ok(ace);
+            }
+            Some(ace_type) => ace_type,
+        };
+
+        // All remaining ACE types begin with an access mask.
+        let invalid_size = NtfsError::InvalidAceSize {
+            offset: ace_offset,
+            size,
+        };
+        let mask = ace_data
+            .get(ACE_HEADER_SIZE..ACE_MASK_END)
+            .ok_or(invalid_size)?;
+        ace.access_mask = Some(LittleEndian::read_u32(mask));
+
+        let mut sid_offset = ACE_MASK_END;
+
+        if ace_type.is_object_ace() {
+            // Object ACEs have additional flags, which indicate the presence of up to two GUIDs.
+            const ACE_OBJECT_TYPE_PRESENT: u32 = 0x1;
+            const ACE_INHERITED_OBJECT_TYPE_PRESENT: u32 = 0x2;
+
+            let object_flags =
+                ace_data
+                    .get(sid_offset..sid_offset + 4)
+                    .ok_or(NtfsError::InvalidAceSize {
+                        offset: ace_offset,
+                        size,
+                    })?;
+            let object_flags = LittleEndian::read_u32(object_flags);
+            sid_offset += 4;
+
+            if object_flags & ACE_OBJECT_TYPE_PRESENT != 0 {
+                ace.object_type = Some(parse_guid(ace_data, sid_offset, ace_offset, size)?);
+                sid_offset += GUID_SIZE;
+            }
+
+            if object_flags & ACE_INHERITED_OBJECT_TYPE_PRESENT != 0 {
+                ace.inherited_object_type =
+                    Some(parse_guid(ace_data, sid_offset, ace_offset, size)?);
+                sid_offset += GUID_SIZE;
+            }
+        }
+
+        let sid = NtfsSid::parse(ace_data, sid_offset).map_err(|e| match e {
+            NtfsError::InvalidSecurityDescriptorRange { .. } => NtfsError::InvalidAceSize {
+                offset: ace_offset,
+                size,
+            },
+            e => e,
+        })?;
+        ace.sid = Some(sid);
+
+        Ok(ace)
+    }
+
+    fn range_error(&self, length: usize) -> NtfsError {
+        let start = self.acl_offset + self.offset;
+
+        NtfsError::InvalidSecurityDescriptorRange {
+            range: start..start + length,
+            size: self.acl_offset + self.data.len(),
+        }
+    }
+}
+
+impl<'a> Iterator for NtfsAces<'a> {
+    type Item = Result<NtfsAce>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        match self.parse_ace() {
+            Ok(ace) => {
+                self.offset += ace.size() as usize;
+                self.remaining -= 1;
+                Some(Ok(ace))
+            }
+            Err(e) => {
+                self.remaining = 0;
+                Some(Err(e))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining as usize))
+    }
+}
+
+impl<'a> FusedIterator for NtfsAces<'a> {}
+
+fn parse_guid(ace_data: &[u8], offset: usize, ace_offset: usize, size: u16) -> Result<NtfsGuid> {
+    let bytes = ace_data
+        .get(offset..offset + GUID_SIZE)
+        .ok_or(NtfsError::InvalidAceSize {
+            offset: ace_offset,
+            size,
+        })?;
+
+    let mut data4 = [0u8; 8];
+    data4.copy_from_slice(&bytes[8..]);
+
+    Ok(NtfsGuid {
+        data1: LittleEndian::read_u32(bytes),
+        data2: LittleEndian::read_u16(&bytes[4..]),
+        data3: LittleEndian::read_u16(&bytes[6..]),
+        data4,
+    })
+}
+
+/// A single access control entry (ACE) of an [`NtfsAcl`].
+#[derive(Clone, Debug)]
+pub struct NtfsAce {
+    ty: u8,
+    flags: NtfsAceFlags,
+    size: u16,
+    access_mask: Option<u32>,
+    object_type: Option<NtfsGuid>,
+    inherited_object_type: Option<NtfsGuid>,
+    sid: Option<NtfsSid>,
+}
+
+impl NtfsAce {
+    /// Returns the access mask of this ACE, or `None` if this ACE type is not supported.
+    pub fn access_mask(&self) -> Option<u32> {
+        self.access_mask
+    }
+
+    /// Returns the flags of this ACE.
+    pub fn flags(&self) -> NtfsAceFlags {
+        self.flags
+    }
+
+    /// Returns the GUID of the object type that inherits this object ACE, if present.
+    pub fn inherited_object_type(&self) -> Option<&NtfsGuid> {
+        self.inherited_object_type.as_ref()
+    }
+
+    /// Returns the GUID of the object type of this object ACE, if present.
+    pub fn object_type(&self) -> Option<&NtfsGuid> {
+        self.object_type.as_ref()
+    }
+
+    /// Returns the raw type of this ACE, which is also returned for unknown ACE types.
+    pub fn raw_ty(&self) -> u8 {
+        self.ty
+    }
+
+    /// Returns the SID this ACE applies to, or `None` if this ACE type is not supported.
+    pub fn sid(&self) -> Option<&NtfsSid> {
+        self.sid.as_ref()
+    }
+
+    /// Returns the size of this ACE in bytes.
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    /// Returns the type of this ACE, or `None` if it's an unknown type.
+    pub fn ty(&self) -> Option<NtfsAceType> {
+        NtfsAceType::n(self.ty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn aces(acl: &NtfsAcl) -> Vec<(NtfsAceType, NtfsAceFlags, u32, String)> {
+        acl.aces()
+            .map(|ace| {
+                let ace = ace.unwrap();
+                (
+                    ace.ty().unwrap(),
+                    ace.flags(),
+                    ace.access_mask().unwrap(),
+                    ace.sid().unwrap().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_admin_owned() {
+        // Security descriptor of the system files of testfs1, taken from its $Secure:$SDS stream.
+        let data = decode_hex(concat!(
+            "0100048048000000580000000000000014000000020034000200000000001400",
+            "8900120001010000000000051200000000001800890012000102000000000005",
+            "2000000020020000010200000000000520000000200200000102000000000005",
+            "2000000020020000",
+        ));
+
+        let sd = NtfsSecurityDescriptor::parse(&data).unwrap();
+        assert_eq!(
+            sd.control(),
+            NtfsSecurityDescriptorControl::SELF_RELATIVE
+                | NtfsSecurityDescriptorControl::DACL_PRESENT
+        );
+        assert_eq!(sd.owner().unwrap().to_string(), "S-1-5-32-544");
+        assert_eq!(sd.group().unwrap().to_string(), "S-1-5-32-544");
+        assert!(sd.sacl().is_none());
+        assert!(!sd.has_null_dacl());
+
+        let owner = sd.owner().unwrap();
+        assert_eq!(owner.revision(), 1);
+        assert_eq!(owner.identifier_authority(), 5);
+        assert_eq!(owner.sub_authorities(), [32, 544]);
+
+        let dacl = sd.dacl().unwrap();
+        assert_eq!(dacl.revision(), 2);
+        assert_eq!(dacl.ace_count(), 2);
+        assert_eq!(
+            aces(dacl),
+            [
+                (
+                    NtfsAceType::AccessAllowed,
+                    NtfsAceFlags::empty(),
+                    0x120089,
+                    "S-1-5-18".to_string()
+                ),
+                (
+                    NtfsAceType::AccessAllowed,
+                    NtfsAceFlags::empty(),
+                    0x120089,
+                    "S-1-5-32-544".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inherited_aces() {
+        // Typical security descriptor of a file created by a user in their profile directory,
+        // with a SACL holding a mandatory integrity label.
+        let data = decode_hex(concat!(
+            // Header
+            "01001484",
+            "14000000", // Owner
+            "30000000", // Group
+            "4c000000", // SACL
+            "68000000", // DACL
+            // Owner: S-1-5-21-1004336348-1177238915-681606530-1001
+            "010500000000000515000000dcf4dc3b833d2b46827da028e9030000",
+            // Group: S-1-5-21-1004336348-1177238915-681606530-513
+            "010500000000000515000000dcf4dc3b833d2b46827da02801020000",
+            // SACL with a mandatory label (S-1-16-4096, low integrity), "no write up"
+            "02001c0001000000",
+            "1100140001000000010100000000001000100000",
+            // DACL with three inherited ACEs
+            "0200580003000000",
+            "00101400ff011f00010100000000000512000000",
+            "00101800ff011f0001020000000000052000000020020000",
+            "00102400ff011f00010500000000000515000000dcf4dc3b833d2b46827da028e9030000",
+        ));
+
+        let sd = NtfsSecurityDescriptor::parse(&data).unwrap();
+        assert!(sd
+            .control()
+            .contains(NtfsSecurityDescriptorControl::DACL_AUTO_INHERITED));
+        assert_eq!(
+            sd.owner().unwrap().to_string(),
+            "S-1-5-21-1004336348-1177238915-681606530-1001"
+        );
+        assert_eq!(
+            sd.group().unwrap().to_string(),
+            "S-1-5-21-1004336348-1177238915-681606530-513"
+        );
+
+        let sacl = sd.sacl().unwrap();
+        assert_eq!(
+            aces(sacl),
+            [(
+                NtfsAceType::SystemMandatoryLabel,
+                NtfsAceFlags::empty(),
+                0x1,
+                "S-1-16-4096".to_string()
+            )]
+        );
+
+        let dacl = sd.dacl().unwrap();
+        assert_eq!(
+            aces(dacl),
+            [
+                (
+                    NtfsAceType::AccessAllowed,
+                    NtfsAceFlags::INHERITED,
+                    0x1f01ff,
+                    "S-1-5-18".to_string()
+                ),
+                (
+                    NtfsAceType::AccessAllowed,
+                    NtfsAceFlags::INHERITED,
+                    0x1f01ff,
+                    "S-1-5-32-544".to_string()
+                ),
+                (
+                    NtfsAceType::AccessAllowed,
+                    NtfsAceFlags::INHERITED,
+                    0x1f01ff,
+                    "S-1-5-21-1004336348-1177238915-681606530-1001".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_null_dacl() {
+        // DACL_PRESENT is set, but there is no DACL, which grants full access to everyone.
+        // There is also no group.
+        let data = decode_hex(concat!(
+            "01000480",
+            "14000000",
+            "00000000",
+            "00000000",
+            "00000000",
+            "0101000000000001",
+            "00000000",
+        ));
+
+        let sd = NtfsSecurityDescriptor::parse(&data).unwrap();
+        assert_eq!(sd.owner().unwrap().to_string(), "S-1-1-0");
+        assert!(sd.group().is_none());
+        assert!(sd.dacl().is_none());
+        assert!(sd.has_null_dacl());
+
+        // Without DACL_PRESENT, there is no DACL at all.
+        let mut data = data;
+        data[2] = 0x00;
+        let sd = NtfsSecurityDescriptor::parse(&data).unwrap();
+        assert!(sd.dacl().is_none());
+        assert!(!sd.has_null_dacl());
+    }
+
+    #[test]
+    fn test_object_ace() {
+        // An ACL with an ACCESS_ALLOWED_OBJECT ACE that has both GUIDs.
+        let data = decode_hex(concat!(
+            "01000480",
+            "00000000",
+            "00000000",
+            "00000000",
+            "14000000",
+            "0400400001000000",
+            "0502380000010000", // Type, flags (CONTAINER_INHERIT), size 0x38, access mask 0x100
+            "03000000",         // Both GUIDs present
+            "0b77c867f1440a41ab9af9b5446f13ee",
+            "00112233445566778899aabbccddeeff",
+            "010100000000000100000000",
+        ));
+
+        let sd = NtfsSecurityDescriptor::parse(&data).unwrap();
+        let dacl = sd.dacl().unwrap();
+        let ace = dacl.aces().next().unwrap().unwrap();
+        assert_eq!(ace.ty(), Some(NtfsAceType::AccessAllowedObject));
+        assert_eq!(ace.flags(), NtfsAceFlags::CONTAINER_INHERIT);
+        assert_eq!(ace.access_mask(), Some(0x100));
+        assert_eq!(ace.object_type().unwrap().data1, 0x67c8770b);
+        assert_eq!(ace.inherited_object_type().unwrap().data1, 0x33221100);
+        assert_eq!(ace.sid().unwrap().to_string(), "S-1-1-0");
+    }
+
+    #[test]
+    fn test_invalid() {
+        let valid = decode_hex(concat!(
+            "0100048048000000580000000000000014000000020034000200000000001400",
+            "8900120001010000000000051200000000001800890012000102000000000005",
+            "2000000020020000010200000000000520000000200200000102000000000005",
+            "2000000020020000",
+        ));
+
+        // Every truncation must be rejected cleanly.
+        for len in 0..valid.len() {
+            let result = NtfsSecurityDescriptor::parse(&valid[..len]).and_then(|sd| {
+                sd.dacl()
+                    .unwrap()
+                    .aces()
+                    .collect::<Result<Vec<_>>>()
+                    .map(|_| ())
+            });
+            assert!(result.is_err(), "Truncation to {len} bytes was accepted");
+        }
+
+        // Unsupported revision.
+        let mut data = valid.clone();
+        data[0] = 2;
+        assert!(matches!(
+            NtfsSecurityDescriptor::parse(&data),
+            Err(NtfsError::UnsupportedSecurityDescriptorRevision { revision: 2 })
+        ));
+
+        // Too many sub authorities in the owner SID.
+        let mut data = valid.clone();
+        data[0x49] = 16;
+        assert!(matches!(
+            NtfsSecurityDescriptor::parse(&data),
+            Err(NtfsError::InvalidSidSubAuthorityCount { count: 16 })
+        ));
+
+        // An owner offset beyond the data.
+        let mut data = valid.clone();
+        data[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            NtfsSecurityDescriptor::parse(&data),
+            Err(NtfsError::InvalidSecurityDescriptorRange { .. })
+        ));
+
+        // Ranges whose end overflows are rejected as well.
+        assert!(matches!(
+            sid_range(&valid, usize::MAX - 1),
+            Err(NtfsError::InvalidSecurityDescriptorRange { .. })
+        ));
+        assert!(matches!(
+            acl_range(&valid, usize::MAX - 1),
+            Err(NtfsError::InvalidSecurityDescriptorRange { .. })
+        ));
+
+        // An ACE that is too small for its SID.
+        let mut data = valid.clone();
+        data[0x1e] = 0x0c;
+        let sd = NtfsSecurityDescriptor::parse(&data).unwrap();
+        let mut aces = sd.dacl().unwrap().aces();
+        assert!(matches!(
+            aces.next(),
+            Some(Err(NtfsError::InvalidAceSize {
+                offset: 0x1c,
+                size: 0x0c
+            }))
+        ));
+        assert!(aces.next().is_none());
+
+        // An ACE that is smaller than its header.
+        let mut data = valid;
+        data[0x1e] = 0x02;
+        let sd = NtfsSecurityDescriptor::parse(&data).unwrap();
+        assert!(matches!(
+            sd.dacl().unwrap().aces().next(),
+            Some(Err(NtfsError::InvalidAceSize {
+                offset: 0x1c,
+                size: 0x02
+            }))
+        ));
+    }
+}
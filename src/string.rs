@@ -0,0 +1,273 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::char::{self, DecodeUtf16};
+use core::cmp::Ordering;
+use core::fmt::{self, Write};
+use core::iter::{FusedIterator, Map};
+use core::slice::ChunksExact;
+
+use nt_string::u16strle::U16StrLe;
+
+use crate::error::Result;
+use crate::ntfs::Ntfs;
+use crate::upcase_table::UpcaseOrd;
+
+#[cfg(all(feature = "std", windows))]
+use {alloc::vec::Vec, std::ffi::OsString, std::os::windows::ffi::OsStringExt};
+
+/// Additional functions for the UTF-16 strings returned by this crate (e.g. by [`NtfsFileName::name`]).
+///
+/// NTFS stores all names as little-endian UTF-16 strings, which are exposed as [`U16StrLe`] without any conversion.
+/// However, NTFS does not validate these strings, so they may contain unpaired surrogates.
+///
+/// [`NtfsFileName::name`]: crate::structured_values::NtfsFileName::name
+pub trait NtfsStringExt {
+    /// Returns an iterator over the characters of this string.
+    ///
+    /// Contrary to [`U16StrLe::to_string_lossy`], unpaired surrogates are not replaced,
+    /// but returned as `Err` with the offending UTF-16 code unit.
+    fn chars(&self) -> NtfsStringChars<'_>;
+
+    /// Returns a wrapper that displays this string safely for logs and terminals.
+    ///
+    /// Control characters, Unicode bidirectional formatting characters, line/paragraph separators,
+    /// and unpaired surrogates are escaped as `\u{...}`, and backslashes are escaped as `\\`.
+    /// The output is truncated to [`NtfsSanitizedDisplay::MAX_CHARS`] characters followed by "…".
+    ///
+    /// Use this whenever names parsed from an untrusted filesystem are printed.
+    /// The string itself is left untouched, so [`NtfsStringExt::chars`] still provides the exact characters.
+    fn display_sanitized(&self) -> NtfsSanitizedDisplay<'_>;
+
+    /// Returns whether this string equals `other` when comparing case-insensitively based on the
+    /// $UpCase table of the filesystem.
+    ///
+    /// This is the same comparison that [`NtfsFileNameIndex::find`] performs.
+    ///
+    /// Returns [`NtfsError::UpcaseTableNotLoaded`](crate::NtfsError::UpcaseTableNotLoaded) if [`read_upcase_table`][Ntfs::read_upcase_table] had not been
+    /// called on the passed [`Ntfs`] object.
+    ///
+    /// [`NtfsFileNameIndex::find`]: crate::indexes::NtfsFileNameIndex::find
+    fn eq_ignore_case(&self, other: &str, ntfs: &Ntfs) -> Result<bool>;
+
+    /// Returns the length of this string in UTF-16 code units.
+    ///
+    /// This is half the length in bytes returned by [`U16StrLe::len`].
+    fn len_utf16(&self) -> usize;
+
+    /// Copies this string to a new [`OsString`] without any lossy conversion.
+    #[cfg(all(feature = "std", windows))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "std", windows))))]
+    fn to_os_string(&self) -> OsString;
+}
+
+impl<'a> NtfsStringExt for U16StrLe<'a> {
+    fn chars(&self) -> NtfsStringChars<'_> {
+        NtfsStringChars::new(self.0)
+    }
+
+    fn display_sanitized(&self) -> NtfsSanitizedDisplay<'_> {
+        NtfsSanitizedDisplay { bytes: self.0 }
+    }
+
+    fn eq_ignore_case(&self, other: &str, ntfs: &Ntfs) -> Result<bool> {
+        Ok(self.upcase_cmp(ntfs, &other)? == Ordering::Equal)
+    }
+
+    fn len_utf16(&self) -> usize {
+        self.len() / 2
+    }
+
+    #[cfg(all(feature = "std", windows))]
+    fn to_os_string(&self) -> OsString {
+        let wide = self.u16_iter().collect::<Vec<u16>>();
+        OsString::from_wide(&wide)
+    }
+}
+
+/// Wrapper around a UTF-16 string that implements [`fmt::Display`] and [`fmt::Debug`] in a way that is safe
+/// for logs and terminals.
+///
+/// This structure is returned from the [`NtfsStringExt::display_sanitized`] function.
+#[derive(Clone, Copy)]
+pub struct NtfsSanitizedDisplay<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> NtfsSanitizedDisplay<'a> {
+    /// Maximum number of characters of the string that are output before it is truncated with "…".
+    pub const MAX_CHARS: usize = 128;
+
+    fn needs_escape(c: char) -> bool {
+        c.is_control()
+            || matches!(
+                c,
+                // Arabic Letter Mark, Left-to-Right Mark, Right-to-Left Mark
+                '\u{061c}' | '\u{200e}' | '\u{200f}'
+                // Line Separator, Paragraph Separator
+                | '\u{2028}' | '\u{2029}'
+                // Bidirectional embeddings and overrides
+                | '\u{202a}'..='\u{202e}'
+                // Bidirectional isolates
+                | '\u{2066}'..='\u{2069}'
+            )
+    }
+}
+
+impl<'a> fmt::Debug for NtfsSanitizedDisplay<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('"')?;
+        fmt::Display::fmt(self, f)?;
+        f.write_char('"')
+    }
+}
+
+impl<'a> fmt::Display for NtfsSanitizedDisplay<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, c) in NtfsStringChars::new(self.bytes).enumerate() {
+            if i == Self::MAX_CHARS {
+                return f.write_char('…');
+            }
+
+            match c {
+                Ok('\\') => f.write_str("\\\\")?,
+                Ok(c) if Self::needs_escape(c) => write!(f, "{}", c.escape_unicode())?,
+                Ok(c) => f.write_char(c)?,
+                Err(code_unit) => write!(f, "\\u{{{code_unit:x}}}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+type U16Iter<'a> = Map<ChunksExact<'a, u8>, fn(&[u8]) -> u16>;
+
+/// Iterator over
+///   all characters of a UTF-16 string,
+///   returning a `Result<char, u16>` for each character,
+///   implementing [`Iterator`] and [`FusedIterator`].
+///
+/// Unpaired surrogates are returned as `Err` with the offending UTF-16 code unit.
+///
+/// This iterator is returned from the [`NtfsStringExt::chars`] function.
+#[derive(Clone, Debug)]
+pub struct NtfsStringChars<'a> {
+    inner: DecodeUtf16<U16Iter<'a>>,
+}
+
+impl<'a> NtfsStringChars<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        let u16_iter: U16Iter<'a> = bytes
+            .chunks_exact(2)
+            .map(|two_bytes| u16::from_le_bytes([two_bytes[0], two_bytes[1]]));
+        let inner = char::decode_utf16(u16_iter);
+
+        Self { inner }
+    }
+}
+
+impl<'a> Iterator for NtfsStringChars<'a> {
+    type Item = Result<char, u16>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.inner.next()?;
+        Some(result.map_err(|e| e.unpaired_surrogate()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a> FusedIterator for NtfsStringChars<'a> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_bytes(code_units: &[u16]) -> Vec<u8> {
+        code_units.iter().flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_chars() {
+        // "A", an unpaired high surrogate, "B", a valid surrogate pair (U+1F600), and an unpaired low surrogate.
+        let bytes = to_bytes(&[0x41, 0xd800, 0x42, 0xd83d, 0xde00, 0xdc00]);
+        let string = U16StrLe(&bytes);
+
+        let chars = string.chars().collect::<Vec<_>>();
+        assert_eq!(
+            chars,
+            [Ok('A'), Err(0xd800), Ok('B'), Ok('\u{1f600}'), Err(0xdc00)]
+        );
+
+        // The lossy conversion hides the unpaired surrogates.
+        assert_eq!(string.to_string_lossy(), "A\u{fffd}B\u{1f600}\u{fffd}");
+
+        assert_eq!(string.len_utf16(), 6);
+        assert!(!string.is_empty());
+
+        let empty = U16StrLe(&[]);
+        assert_eq!(empty.chars().next(), None);
+        assert_eq!(empty.len_utf16(), 0);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_display_sanitized() {
+        let bytes = to_bytes(
+            &"a\nb\0c\u{202e}gpj.exe\\"
+                .encode_utf16()
+                .collect::<Vec<_>>(),
+        );
+        let string = U16StrLe(&bytes);
+        assert_eq!(
+            string.display_sanitized().to_string(),
+            r"a\u{a}b\u{0}c\u{202e}gpj.exe\\"
+        );
+        assert_eq!(
+            format!("{:?}", string.display_sanitized()),
+            r#""a\u{a}b\u{0}c\u{202e}gpj.exe\\""#
+        );
+
+        // Unpaired surrogates are escaped as well, and regular non-ASCII characters are kept.
+        let bytes = to_bytes(&[0x41, 0xd800, 0xe4, 0xd83d, 0xde00]);
+        let string = U16StrLe(&bytes);
+        assert_eq!(
+            string.display_sanitized().to_string(),
+            "A\\u{d800}ä\u{1f600}"
+        );
+
+        // Long strings are truncated, counting characters and not UTF-16 code units.
+        let long = "x".repeat(NtfsSanitizedDisplay::MAX_CHARS);
+        let bytes = to_bytes(&long.encode_utf16().collect::<Vec<_>>());
+        assert_eq!(U16StrLe(&bytes).display_sanitized().to_string(), long);
+
+        let bytes = to_bytes(&"\u{1f600}".repeat(255).encode_utf16().collect::<Vec<_>>());
+        let expected = "\u{1f600}".repeat(NtfsSanitizedDisplay::MAX_CHARS) + "…";
+        assert_eq!(U16StrLe(&bytes).display_sanitized().to_string(), expected);
+    }
+
+    #[test]
+    fn test_eq_ignore_case() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let bytes = to_bytes(&"Many_Subdirs".encode_utf16().collect::<Vec<_>>());
+        let string = U16StrLe(&bytes);
+
+        assert!(string.eq_ignore_case("many_subdirs", &ntfs).unwrap());
+        assert!(string.eq_ignore_case("MANY_SUBDIRS", &ntfs).unwrap());
+        assert!(!string.eq_ignore_case("many_subdir", &ntfs).unwrap());
+        assert!(!string.eq_ignore_case("many_subdirs2", &ntfs).unwrap());
+
+        // The comparison needs the $UpCase table.
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        assert!(matches!(
+            string.eq_ignore_case("many_subdirs", &ntfs),
+            Err(crate::error::NtfsError::UpcaseTableNotLoaded)
+        ));
+    }
+}
@@ -1,10 +1,11 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use core::iter::{self, FusedIterator};
 use core::mem;
 
+use crate::io::{Cursor, Read, Seek, SeekFrom};
 use arrayvec::ArrayVec;
-use binrw::io::{Cursor, Read, Seek, SeekFrom};
 use binrw::{BinRead, BinReaderExt};
 use nt_string::u16strle::U16StrLe;
 
@@ -109,6 +110,7 @@ impl<'n, 'f> NtfsStructuredValue<'n, 'f> for NtfsAttributeList<'n, 'f> {
 ///   returning an [`NtfsAttributeListEntry`] for each entry.
 ///
 /// This iterator is returned from the [`NtfsAttributeList::entries`] function.
+/// See [`NtfsAttributeListEntriesAttached`] for an iterator that implements [`Iterator`] and [`FusedIterator`].
 #[derive(Clone, Debug)]
 pub struct NtfsAttributeListEntries<'n, 'f> {
     attribute_list: NtfsAttributeList<'n, 'f>,
@@ -119,6 +121,15 @@ impl<'n, 'f> NtfsAttributeListEntries<'n, 'f> {
         Self { attribute_list }
     }
 
+    /// Returns a variant of this iterator that implements [`Iterator`] and [`FusedIterator`]
+    /// by mutably borrowing the filesystem reader.
+    pub fn attach<'a, T>(self, fs: &'a mut T) -> NtfsAttributeListEntriesAttached<'n, 'f, 'a, T>
+    where
+        T: Read + Seek,
+    {
+        NtfsAttributeListEntriesAttached::new(fs, self)
+    }
+
     /// See [`Iterator::next`].
     pub fn next<T>(&mut self, fs: &mut T) -> Option<Result<NtfsAttributeListEntry>>
     where
@@ -172,6 +183,50 @@ impl<'n, 'f> NtfsAttributeListEntries<'n, 'f> {
     }
 }
 
+/// Iterator over
+///   all entries of an [`NtfsAttributeList`] attribute,
+///   returning an [`NtfsAttributeListEntry`] for each entry,
+///   implementing [`Iterator`] and [`FusedIterator`].
+///
+/// This iterator is returned from the [`NtfsAttributeListEntries::attach`] function.
+/// Conceptually the same as [`NtfsAttributeListEntries`], but mutably borrows the filesystem
+/// to implement aforementioned traits.
+#[derive(Debug)]
+pub struct NtfsAttributeListEntriesAttached<'n, 'f, 'a, T: Read + Seek> {
+    fs: &'a mut T,
+    entries: NtfsAttributeListEntries<'n, 'f>,
+}
+
+impl<'n, 'f, 'a, T> NtfsAttributeListEntriesAttached<'n, 'f, 'a, T>
+where
+    T: Read + Seek,
+{
+    fn new(fs: &'a mut T, entries: NtfsAttributeListEntries<'n, 'f>) -> Self {
+        Self { fs, entries }
+    }
+
+    /// Consumes this iterator and returns the inner [`NtfsAttributeListEntries`].
+    pub fn detach(self) -> NtfsAttributeListEntries<'n, 'f> {
+        self.entries
+    }
+}
+
+impl<'n, 'f, 'a, T> Iterator for NtfsAttributeListEntriesAttached<'n, 'f, 'a, T>
+where
+    T: Read + Seek,
+{
+    type Item = Result<NtfsAttributeListEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next(self.fs)
+    }
+}
+
+impl<'n, 'f, 'a, T> FusedIterator for NtfsAttributeListEntriesAttached<'n, 'f, 'a, T> where
+    T: Read + Seek
+{
+}
+
 /// A single entry of an [`NtfsAttributeList`] attribute.
 #[derive(Clone, Debug)]
 pub struct NtfsAttributeListEntry {
@@ -181,7 +236,7 @@ pub struct NtfsAttributeListEntry {
 }
 
 impl NtfsAttributeListEntry {
-    fn new<T>(r: &mut T, position: NtfsPosition) -> Result<Self>
+    pub(crate) fn new<T>(r: &mut T, position: NtfsPosition) -> Result<Self>
     where
         T: Read + Seek,
     {
@@ -189,7 +244,7 @@ impl NtfsAttributeListEntry {
 
         let mut entry = Self {
             header,
-            name: ArrayVec::from([0u8; NAME_MAX_SIZE]),
+            name: ArrayVec::new(),
             position,
         };
         entry.validate_entry_and_name_length()?;
@@ -205,14 +260,27 @@ impl NtfsAttributeListEntry {
 
     /// Returns the instance number of this attribute list entry.
     ///
-    /// An instance number is unique within a single NTFS File Record.
-    ///
-    /// Multiple entries of the same type and instance number form a connected attribute,
-    /// meaning an attribute whose value is stretched over multiple attributes.
+    /// An instance number is only unique within a single NTFS File Record.
+    /// Hence, the fragments of a connected attribute stored in different File Records may have different
+    /// instance numbers, and unrelated attributes in different File Records may have equal ones.
     pub fn instance(&self) -> u16 {
         self.header.instance
     }
 
+    /// Returns whether this entry continues the connected attribute whose previous fragment is described
+    /// by `previous`.
+    ///
+    /// Following the NTFS rules, connected fragments are adjacent in the Attribute List,
+    /// share type and name, and have a strictly increasing lowest VCN.
+    pub(crate) fn is_connected_to(&self, previous: &Self) -> bool {
+        self.has_same_ty_and_name(previous) && self.header.lowest_vcn > previous.header.lowest_vcn
+    }
+
+    /// Returns whether this entry and `other` describe attributes of the same type and name.
+    pub(crate) fn has_same_ty_and_name(&self, other: &Self) -> bool {
+        self.header.ty == other.header.ty && self.name == other.name
+    }
+
     /// Returns the length of this attribute list entry, in bytes.
     pub fn list_entry_length(&self) -> u16 {
         self.header.list_entry_length
@@ -229,7 +297,7 @@ impl NtfsAttributeListEntry {
     }
 
     /// Gets the attribute name and returns it wrapped in a [`U16StrLe`].
-    pub fn name(&self) -> U16StrLe {
+    pub fn name(&self) -> U16StrLe<'_> {
         U16StrLe(&self.name)
     }
 
@@ -249,11 +317,13 @@ impl NtfsAttributeListEntry {
     where
         T: Read + Seek,
     {
-        debug_assert_eq!(self.name.len(), NAME_MAX_SIZE);
+        debug_assert!(self.name.is_empty());
 
+        // `name_length` is derived from an `u8` field and therefore never exceeds `NAME_MAX_SIZE`.
+        // Only zero-initialize the bytes we are going to read.
         let name_length = self.name_length();
-        r.read_exact(&mut self.name[..name_length])?;
-        self.name.truncate(name_length);
+        self.name.extend(iter::repeat(0).take(name_length));
+        r.read_exact(&mut self.name)?;
 
         Ok(())
     }
@@ -301,6 +371,7 @@ impl NtfsAttributeListEntry {
     fn validate_entry_and_name_length(&self) -> Result<()> {
         let total_size = ATTRIBUTE_LIST_ENTRY_HEADER_SIZE + self.name_length();
 
+        // This also guarantees that every entry advances the iterator.
         if total_size > self.list_entry_length() as usize {
             return Err(NtfsError::InvalidStructuredValueSize {
                 position: self.position(),
@@ -310,6 +381,101 @@ impl NtfsAttributeListEntry {
             });
         }
 
+        // Entries are aligned to 8-byte boundaries.
+        let aligned_length = (self.list_entry_length() as u64 + 7) & !7;
+        if aligned_length != self.list_entry_length() as u64 {
+            return Err(NtfsError::InvalidStructuredValueSize {
+                position: self.position(),
+                ty: NtfsAttributeType::AttributeList,
+                expected: aligned_length,
+                actual: self.list_entry_length() as u64,
+            });
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use super::*;
+
+    /// Returns a resident attribute list entry for an unnamed $DATA attribute with the given length field.
+    fn attribute_list_entry(list_entry_length: u16) -> Vec<u8> {
+        let mut entry = vec![0u8; (list_entry_length as usize).max(32)];
+        LittleEndian::write_u32(&mut entry[0..], NtfsAttributeType::Data as u32);
+        LittleEndian::write_u16(&mut entry[4..], list_entry_length);
+        entry[7] = ATTRIBUTE_LIST_ENTRY_HEADER_SIZE as u8;
+        entry
+    }
+
+    /// Collects the results of iterating over the given resident attribute list,
+    /// but stops after a few iterations to catch iterators that don't advance.
+    fn collect_entries(data: &[u8]) -> Vec<Result<NtfsAttributeListEntry>> {
+        let mut fs = Cursor::new(Vec::<u8>::new());
+        let attribute_list = NtfsAttributeList::Resident(data, NtfsPosition::new(0x1000));
+        let mut entries = attribute_list.entries();
+
+        let mut results = Vec::new();
+        while let Some(entry) = entries.next(&mut fs) {
+            let is_err = entry.is_err();
+            results.push(entry);
+
+            if is_err || results.len() == 10 {
+                break;
+            }
+        }
+
+        results
+    }
+
+    #[test]
+    fn test_valid_entries() {
+        let mut data = attribute_list_entry(32);
+        data.extend(attribute_list_entry(32));
+
+        let results = collect_entries(&data);
+        assert_eq!(results.len(), 2);
+
+        let second_entry = results[1].as_ref().unwrap();
+        assert_eq!(second_entry.list_entry_length(), 32);
+        assert_eq!(second_entry.ty().unwrap(), NtfsAttributeType::Data);
+        assert_eq!(second_entry.position(), NtfsPosition::new(0x1020));
+    }
+
+    #[test]
+    fn test_zero_length_entry() {
+        let mut data = attribute_list_entry(32);
+        data.extend(attribute_list_entry(0));
+
+        let results = collect_entries(&data);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1],
+            Err(NtfsError::InvalidStructuredValueSize {
+                expected: 0,
+                actual: 26,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_unaligned_entry() {
+        let data = attribute_list_entry(33);
+
+        let results = collect_entries(&data);
+        assert_eq!(results.len(), 1);
+        assert!(matches!(
+            results[0],
+            Err(NtfsError::InvalidStructuredValueSize {
+                expected: 40,
+                actual: 33,
+                ..
+            })
+        ));
+    }
+}
@@ -1,10 +1,10 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use core::mem;
+use core::{iter, mem};
 
+use crate::io::{Cursor, Read, Seek};
 use arrayvec::ArrayVec;
-use binrw::io::{Cursor, Read, Seek};
 use binrw::{BinRead, BinReaderExt};
 use enumn::N;
 use nt_string::u16strle::U16StrLe;
@@ -18,6 +18,9 @@ use crate::structured_values::{NtfsFileAttributeFlags, NtfsStructuredValue};
 use crate::time::NtfsTime;
 use crate::types::NtfsPosition;
 
+#[cfg(feature = "serde")]
+use alloc::string::String;
+
 /// Size of all [`FileNameHeader`] fields.
 const FILE_NAME_HEADER_SIZE: usize = 66;
 
@@ -39,7 +42,9 @@ struct FileNameHeader {
     allocated_size: u64,
     data_size: u64,
     file_attributes: u32,
-    reparse_point_tag: u32,
+    /// Union of the reparse point tag (for reparse points) and the packed size of extended attributes
+    /// in its lower 16 bits (for all other files).
+    reparse_point_tag_or_ea_size: u32,
     name_length: u8,
     namespace: u8,
 }
@@ -48,6 +53,7 @@ struct FileNameHeader {
 ///
 /// Reference: <https://flatcap.github.io/linux-ntfs/ntfs/concepts/filename_namespace.html>
 #[derive(Clone, Copy, Debug, Eq, N, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[repr(u8)]
 pub enum NtfsFileNamespace {
     /// A POSIX-compatible filename, which is case-sensitive and supports all Unicode
@@ -64,6 +70,49 @@ pub enum NtfsFileNamespace {
     Win32AndDos = 3,
 }
 
+impl NtfsFileNamespace {
+    /// Returns the [`NtfsFileNamespace`] for the given on-disk namespace value,
+    /// or `None` if it is not a known namespace.
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Self::n(value)
+    }
+}
+
+/// Owned copy of all fields of an [`NtfsFileName`], returned by [`NtfsFileName::to_owned_metadata`].
+///
+/// Contrary to [`NtfsFileName`], it stores the name as a [`String`] and can be serialized,
+/// e.g. for exporting file metadata to JSON.
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct NtfsFileNameMetadata {
+    /// See [`NtfsFileName::parent_directory_reference`].
+    pub parent_directory_reference: NtfsFileReference,
+    /// See [`NtfsFileName::name`].
+    /// Invalid UTF-16 sequences are replaced by U+FFFD REPLACEMENT CHARACTER.
+    pub name: String,
+    /// See [`NtfsFileName::namespace`].
+    pub namespace: NtfsFileNamespace,
+    /// See [`NtfsFileName::file_attributes`].
+    pub file_attributes: NtfsFileAttributeFlags,
+    /// See [`NtfsFileName::creation_time`].
+    pub creation_time: NtfsTime,
+    /// See [`NtfsFileName::modification_time`].
+    pub modification_time: NtfsTime,
+    /// See [`NtfsFileName::mft_record_modification_time`].
+    pub mft_record_modification_time: NtfsTime,
+    /// See [`NtfsFileName::access_time`].
+    pub access_time: NtfsTime,
+    /// See [`NtfsFileName::allocated_size`].
+    pub allocated_size: u64,
+    /// See [`NtfsFileName::data_size`].
+    pub data_size: u64,
+    /// See [`NtfsFileName::reparse_point_tag`].
+    pub reparse_point_tag: Option<u32>,
+    /// See [`NtfsFileName::packed_ea_size`].
+    pub packed_ea_size: Option<u16>,
+}
+
 /// Structure of a $FILE_NAME attribute.
 ///
 /// NTFS creates a $FILE_NAME attribute for every hard link.
@@ -79,6 +128,7 @@ pub enum NtfsFileNamespace {
 #[derive(Clone, Debug)]
 pub struct NtfsFileName {
     header: FileNameHeader,
+    namespace: NtfsFileNamespace,
     name: ArrayVec<u8, NAME_MAX_SIZE>,
 }
 
@@ -97,13 +147,19 @@ impl NtfsFileName {
         }
 
         let header = r.read_le::<FileNameHeader>()?;
+        let namespace = NtfsFileNamespace::from_u8(header.namespace).ok_or(
+            NtfsError::UnsupportedFileNamespace {
+                position,
+                actual: header.namespace,
+            },
+        )?;
 
         let mut file_name = Self {
             header,
-            name: ArrayVec::from([0u8; NAME_MAX_SIZE]),
+            namespace,
+            name: ArrayVec::new(),
         };
         file_name.validate_name_length(value_length, position)?;
-        file_name.validate_namespace(position)?;
         file_name.read_name(r)?;
 
         Ok(file_name)
@@ -186,6 +242,11 @@ impl NtfsFileName {
             .contains(NtfsFileAttributeFlags::IS_DIRECTORY)
     }
 
+    fn is_reparse_point(&self) -> bool {
+        self.file_attributes()
+            .contains(NtfsFileAttributeFlags::REPARSE_POINT)
+    }
+
     /// Returns the MFT record modification time stored in this $FILE_NAME record.
     ///
     /// **Note that NTFS only updates it when the file name is changed!**
@@ -207,7 +268,7 @@ impl NtfsFileName {
     }
 
     /// Gets the file name and returns it wrapped in a [`U16StrLe`].
-    pub fn name(&self) -> U16StrLe {
+    pub fn name(&self) -> U16StrLe<'_> {
         U16StrLe(&self.name)
     }
 
@@ -220,7 +281,21 @@ impl NtfsFileName {
 
     /// Returns the [`NtfsFileNamespace`] of this file name.
     pub fn namespace(&self) -> NtfsFileNamespace {
-        NtfsFileNamespace::n(self.header.namespace).unwrap()
+        self.namespace
+    }
+
+    /// Returns the packed size of the extended attributes of this file, in bytes,
+    /// or `None` if this file is a reparse point.
+    ///
+    /// The same field stores either the reparse point tag or the packed size of extended attributes,
+    /// depending on whether [`NtfsFileAttributeFlags::REPARSE_POINT`] is set.
+    /// See also [`NtfsFileName::reparse_point_tag`].
+    pub fn packed_ea_size(&self) -> Option<u16> {
+        if self.is_reparse_point() {
+            None
+        } else {
+            Some(self.header.reparse_point_tag_or_ea_size as u16)
+        }
     }
 
     /// Returns an [`NtfsFileReference`] for the directory where this file is located.
@@ -232,15 +307,51 @@ impl NtfsFileName {
     where
         T: Read + Seek,
     {
-        debug_assert_eq!(self.name.len(), NAME_MAX_SIZE);
+        debug_assert!(self.name.is_empty());
 
+        // `name_length` is derived from an `u8` field and therefore never exceeds `NAME_MAX_SIZE`.
+        // Only zero-initialize the bytes we are going to read.
         let name_length = self.name_length();
-        r.read_exact(&mut self.name[..name_length])?;
-        self.name.truncate(name_length);
+        self.name.extend(iter::repeat(0).take(name_length));
+        r.read_exact(&mut self.name)?;
 
         Ok(())
     }
 
+    /// Returns the reparse point tag of this file (like `IO_REPARSE_TAG_SYMLINK`),
+    /// or `None` if this file is not a reparse point.
+    ///
+    /// This allows identifying symbolic links and other special files directly from a directory index entry,
+    /// without reading the File Record and its $REPARSE_POINT attribute.
+    /// See also [`NtfsFileName::packed_ea_size`].
+    pub fn reparse_point_tag(&self) -> Option<u32> {
+        if self.is_reparse_point() {
+            Some(self.header.reparse_point_tag_or_ea_size)
+        } else {
+            None
+        }
+    }
+
+    /// Returns an [`NtfsFileNameMetadata`] structure with an owned copy of all fields of this $FILE_NAME attribute.
+    #[cfg(feature = "serde")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+    pub fn to_owned_metadata(&self) -> NtfsFileNameMetadata {
+        NtfsFileNameMetadata {
+            parent_directory_reference: self.parent_directory_reference(),
+            name: self.name().to_string_lossy(),
+            namespace: self.namespace(),
+            file_attributes: self.file_attributes(),
+            creation_time: self.creation_time(),
+            modification_time: self.modification_time(),
+            mft_record_modification_time: self.mft_record_modification_time(),
+            access_time: self.access_time(),
+            allocated_size: self.allocated_size(),
+            data_size: self.data_size(),
+            reparse_point_tag: self.reparse_point_tag(),
+            packed_ea_size: self.packed_ea_size(),
+        }
+    }
+
     fn validate_name_length(&self, data_size: u64, position: NtfsPosition) -> Result<()> {
         let total_size = (FILE_NAME_HEADER_SIZE + self.name_length()) as u64;
 
@@ -255,17 +366,6 @@ impl NtfsFileName {
 
         Ok(())
     }
-
-    fn validate_namespace(&self, position: NtfsPosition) -> Result<()> {
-        if NtfsFileNamespace::n(self.header.namespace).is_none() {
-            return Err(NtfsError::UnsupportedFileNamespace {
-                position,
-                actual: self.header.namespace,
-            });
-        }
-
-        Ok(())
-    }
 }
 
 impl<'n, 'f> NtfsStructuredValue<'n, 'f> for NtfsFileName {
@@ -300,6 +400,18 @@ mod tests {
     use crate::ntfs::Ntfs;
     use crate::time::tests::NT_TIMESTAMP_2021_01_01;
 
+    /// Returns a $FILE_NAME value for the name "a" with the given file attributes
+    /// and value of the reparse point tag / EA size union.
+    fn file_name_value(file_attributes: NtfsFileAttributeFlags, union_value: u32) -> Vec<u8> {
+        let mut value = vec![0u8; FILE_NAME_MIN_SIZE];
+        value[0x38..0x3c].copy_from_slice(&file_attributes.bits().to_le_bytes());
+        value[0x3c..0x40].copy_from_slice(&union_value.to_le_bytes());
+        value[0x40] = 1;
+        value[0x41] = NtfsFileNamespace::Win32AndDos as u8;
+        value[0x42] = b'a';
+        value
+    }
+
     #[test]
     fn test_file_name() {
         let mut testfs1 = crate::helpers::tests::testfs1();
@@ -333,6 +445,8 @@ mod tests {
         assert_eq!(allocated_size, file_name.data_size());
 
         assert_eq!(file_name.name_length(), 8);
+        assert_eq!(file_name.reparse_point_tag(), None);
+        assert_eq!(file_name.packed_ea_size(), Some(0));
 
         // Test various ways to compare the same string.
         assert_eq!(file_name.name(), "$MFT");
@@ -342,4 +456,110 @@ mod tests {
             U16StrLe(&[b'$', 0, b'M', 0, b'F', 0, b'T', 0])
         );
     }
+
+    #[test]
+    fn test_namespace() {
+        let position = NtfsPosition::new(0x1000);
+        let mut value = file_name_value(NtfsFileAttributeFlags::ARCHIVE, 0);
+
+        for namespace in [
+            NtfsFileNamespace::Posix,
+            NtfsFileNamespace::Win32,
+            NtfsFileNamespace::Dos,
+            NtfsFileNamespace::Win32AndDos,
+        ] {
+            assert_eq!(NtfsFileNamespace::from_u8(namespace as u8), Some(namespace));
+
+            value[0x41] = namespace as u8;
+            let file_name = NtfsFileName::key_from_slice(&value, position).unwrap();
+            assert_eq!(file_name.namespace(), namespace);
+        }
+
+        assert_eq!(NtfsFileNamespace::from_u8(7), None);
+        value[0x41] = 7;
+        assert!(matches!(
+            NtfsFileName::key_from_slice(&value, position),
+            Err(NtfsError::UnsupportedFileNamespace { actual: 7, .. })
+        ));
+    }
+
+    #[test]
+    fn test_name_lengths() {
+        let position = NtfsPosition::new(0x1000);
+
+        for name_length in [1, 2, 254, 255] {
+            let mut value = file_name_value(NtfsFileAttributeFlags::ARCHIVE, 0);
+            value.truncate(FILE_NAME_HEADER_SIZE);
+            value[0x40] = name_length;
+            let name = (0..name_length as u16)
+                .flat_map(|i| (u16::from(b'a') + i % 26).to_le_bytes())
+                .collect::<Vec<u8>>();
+            value.extend_from_slice(&name);
+
+            let file_name = NtfsFileName::key_from_slice(&value, position).unwrap();
+            assert_eq!(file_name.name_length(), name.len());
+            assert_eq!(file_name.name(), U16StrLe(&name));
+
+            // A value too short for the given name length is rejected.
+            value.pop();
+            assert!(NtfsFileName::key_from_slice(&value, position).is_err());
+        }
+    }
+
+    #[test]
+    fn test_reparse_point_tag_and_packed_ea_size() {
+        const IO_REPARSE_TAG_SYMLINK: u32 = 0xa000_000c;
+        let position = NtfsPosition::new(0x1000);
+
+        // For reparse points, the union contains the reparse point tag.
+        let value = file_name_value(
+            NtfsFileAttributeFlags::ARCHIVE | NtfsFileAttributeFlags::REPARSE_POINT,
+            IO_REPARSE_TAG_SYMLINK,
+        );
+        let file_name = NtfsFileName::key_from_slice(&value, position).unwrap();
+        assert_eq!(file_name.name(), "a");
+        assert_eq!(file_name.reparse_point_tag(), Some(IO_REPARSE_TAG_SYMLINK));
+        assert_eq!(file_name.packed_ea_size(), None);
+
+        // For all other files, its lower 16 bits contain the packed size of extended attributes.
+        let value = file_name_value(NtfsFileAttributeFlags::ARCHIVE, 0x48);
+        let file_name = NtfsFileName::key_from_slice(&value, position).unwrap();
+        assert_eq!(file_name.reparse_point_tag(), None);
+        assert_eq!(file_name.packed_ea_size(), Some(0x48));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let mft = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::MFT as u64)
+            .unwrap();
+        let file_name = mft.name(&mut testfs1, None, None).unwrap().unwrap();
+
+        let metadata = file_name.to_owned_metadata();
+        assert_eq!(metadata.name, "$MFT");
+        assert_eq!(
+            metadata.parent_directory_reference,
+            file_name.parent_directory_reference()
+        );
+
+        let json = serde_json::to_value(&metadata).unwrap();
+        assert_eq!(json["name"], "$MFT");
+        assert_eq!(json["namespace"], "Win32AndDos");
+        assert_eq!(json["file_attributes"], "HIDDEN | SYSTEM");
+        assert_eq!(
+            json["parent_directory_reference"]["file_record_number"],
+            KnownNtfsFileRecordNumber::RootDirectory as u64
+        );
+        assert_eq!(
+            json["creation_time"]["nt_timestamp"],
+            file_name.creation_time().nt_timestamp()
+        );
+        assert_eq!(json["reparse_point_tag"], serde_json::Value::Null);
+
+        let metadata2 = serde_json::from_value::<NtfsFileNameMetadata>(json).unwrap();
+        assert_eq!(metadata, metadata2);
+    }
 }
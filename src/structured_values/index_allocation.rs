@@ -3,12 +3,12 @@
 
 use core::iter::FusedIterator;
 
-use binrw::io::{Read, Seek, SeekFrom};
+use crate::io::{Read, Seek, SeekFrom};
 
 use crate::attribute::NtfsAttributeType;
 use crate::attribute_value::NtfsAttributeValue;
 use crate::error::{NtfsError, Result};
-use crate::index_record::NtfsIndexRecord;
+use crate::index_record::{validate_index_record_size, NtfsIndexRecord};
 use crate::ntfs::Ntfs;
 use crate::structured_values::NtfsStructuredValue;
 use crate::traits::NtfsReadSeek;
@@ -38,6 +38,8 @@ impl<'n, 'f> NtfsIndexAllocation<'n, 'f> {
     /// Returns the [`NtfsIndexRecord`] located at the given Virtual Cluster Number (VCN).
     ///
     /// The record is fully read, fixed up, and validated.
+    /// Its position is exact even if it straddles two Data Runs or connected attributes
+    /// (see [`NtfsIndexRecord::position_at`]).
     ///
     /// This function is usually called on the return value of [`NtfsIndexEntry::subnode_vcn`] to move further
     /// down in the B-tree.
@@ -52,25 +54,66 @@ impl<'n, 'f> NtfsIndexAllocation<'n, 'f> {
     where
         T: Read + Seek,
     {
-        // Seek to the byte offset of the given VCN.
-        let mut value = self.value.clone();
-        let offset = vcn.offset(self.ntfs)?;
-        value.seek(fs, SeekFrom::Current(offset))?;
+        self.record_from_vcn_with_bitmap(fs, index_record_size, vcn, None)
+    }
+
+    /// Like [`NtfsIndexAllocation::record_from_vcn`], but additionally checks the Index Record against
+    /// the $BITMAP attribute of the index (if given) before reading it.
+    ///
+    /// Returns [`NtfsError::IndexRecordNotAllocated`] if the Index Record is not marked as in use.
+    pub(crate) fn record_from_vcn_with_bitmap<T>(
+        &self,
+        fs: &mut T,
+        index_record_size: u32,
+        vcn: Vcn,
+        index_bitmap: Option<&[u8]>,
+    ) -> Result<NtfsIndexRecord>
+    where
+        T: Read + Seek,
+    {
+        validate_index_record_size(self.value.data_position(), index_record_size)?;
 
-        if value.stream_position() >= value.len() {
+        // The entire Index Record at the byte offset of the given VCN must be part of this attribute.
+        // Check this before reading (and allocating a buffer for) the record.
+        let offset = vcn.offset(self.ntfs)?;
+        let record_end = u64::try_from(offset)
+            .ok()
+            .and_then(|offset| offset.checked_add(index_record_size as u64));
+        if !matches!(record_end, Some(record_end) if record_end <= self.value.len()) {
             return Err(NtfsError::VcnOutOfBoundsInIndexAllocation {
                 position: self.value.data_position(),
                 vcn,
             });
         }
 
+        // A deallocated Index Record may still contain stale entries, so don't even read it.
+        // The offset is non-negative, because we have successfully checked `record_end` above.
+        if let Some(index_bitmap) = index_bitmap {
+            let record_number = offset as u64 / index_record_size as u64;
+            let is_allocated = usize::try_from(record_number / 8)
+                .ok()
+                .and_then(|byte_index| index_bitmap.get(byte_index))
+                .map_or(false, |byte| byte & (1 << (record_number % 8)) != 0);
+
+            if !is_allocated {
+                return Err(NtfsError::IndexRecordNotAllocated {
+                    position: self.value.data_position(),
+                    vcn,
+                });
+            }
+        }
+
+        // Seek to the byte offset of the given VCN.
+        let mut value = self.value.clone();
+        value.seek(fs, SeekFrom::Current(offset))?;
+
         // Get the record.
         let record = NtfsIndexRecord::new(fs, value, index_record_size)?;
 
         // Validate that the VCN in the record is the requested one.
         if record.vcn() != vcn {
             return Err(NtfsError::VcnMismatchInIndexAllocation {
-                position: self.value.data_position(),
+                position: record.position(),
                 expected: vcn,
                 actual: record.vcn(),
             });
@@ -205,3 +248,525 @@ where
 }
 
 impl<'n, 'f, 'a, T> FusedIterator for NtfsIndexRecordsAttached<'n, 'f, 'a, T> where T: Read + Seek {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::NtfsFile;
+    use crate::helpers::tests::{
+        attribute_list_entry, attribute_offset, encode_data_runs, file_reference,
+        first_attribute_offset, insert_attribute, make_extension_record, patch_file_record_number,
+        remove_attribute, take_attribute_instance, AttributeBuilder,
+    };
+    use crate::index_record::NtfsIndexNodeFlags;
+    use crate::structured_values::NtfsIndexRoot;
+    use crate::types::NtfsPosition;
+    use byteorder::{ByteOrder, LittleEndian};
+
+    fn many_subdirs<'n>(ntfs: &'n Ntfs, testfs1: &mut std::io::Cursor<Vec<u8>>) -> NtfsFile<'n> {
+        let file = ntfs.file(testfs1, 68).unwrap();
+        assert_eq!(
+            file.name(testfs1, None, None).unwrap().unwrap().name(),
+            "many_subdirs"
+        );
+        file
+    }
+
+    #[test]
+    fn test_invalid_index_record_size() {
+        for index_record_size in [0u32, 1000, 256, 0x20000] {
+            let mut testfs1 = crate::helpers::tests::testfs1();
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+            // Patch the Index Record size in the $INDEX_ROOT attribute of "many_subdirs".
+            // The position of a resident structured value is the position of its attribute.
+            let (record_position, attribute_offset) = {
+                let file = many_subdirs(&ntfs, &mut testfs1);
+                let index_root = file
+                    .find_resident_attribute_structured_value::<NtfsIndexRoot>(None)
+                    .unwrap();
+                let record_position = file.position().value().unwrap().get();
+                let attribute_offset =
+                    index_root.position().value().unwrap().get() - record_position;
+                (record_position as usize, attribute_offset as usize)
+            };
+            crate::helpers::tests::patch_file_record(
+                &mut testfs1,
+                record_position,
+                ntfs.file_record_size() as usize,
+                |record| {
+                    let value_offset = u16::from_le_bytes([
+                        record[attribute_offset + 20],
+                        record[attribute_offset + 21],
+                    ]) as usize;
+                    let start = attribute_offset + value_offset + 8;
+                    record[start..start + 4].copy_from_slice(&index_record_size.to_le_bytes());
+                },
+            );
+
+            let file = many_subdirs(&ntfs, &mut testfs1);
+            let error = file.directory_index(&mut testfs1).unwrap_err();
+            assert!(
+                matches!(error, NtfsError::InvalidIndexRecordSize { size, .. } if size == index_record_size),
+                "{error:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_records() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = many_subdirs(&ntfs, &mut testfs1);
+
+        let index_root = file
+            .find_resident_attribute_structured_value::<NtfsIndexRoot>(None)
+            .unwrap();
+        let index_record_size = index_root.index_record_size();
+
+        let mut iter = file.attributes();
+        let index_allocation_item = loop {
+            let item = iter.next(&mut testfs1).unwrap().unwrap();
+            let attribute = item.to_attribute().unwrap();
+            if attribute.ty().unwrap() == NtfsAttributeType::IndexAllocation {
+                break item;
+            }
+        };
+        let index_allocation_attribute = index_allocation_item.to_attribute().unwrap();
+        let index_allocation = index_allocation_attribute
+            .structured_value::<_, NtfsIndexAllocation>(&mut testfs1)
+            .unwrap();
+
+        let records = index_allocation
+            .records(index_record_size)
+            .attach(&mut testfs1)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        // The Index Records are returned in the order of their VCNs.
+        let clusters_per_record = (index_record_size / ntfs.cluster_size()) as i64;
+        assert_eq!(
+            records.len() as u64,
+            index_allocation.value.len() / index_record_size as u64
+        );
+
+        for (i, record) in records.iter().enumerate() {
+            assert_eq!(record.vcn(), Vcn::from(i as i64 * clusters_per_record));
+            assert_eq!(
+                record.has_subnodes(),
+                record.flags().contains(NtfsIndexNodeFlags::HAS_SUBNODES)
+            );
+            assert_eq!(record.index_used_size(), record.index_data_size());
+            assert_eq!(
+                record.slack().len() as u32,
+                record.index_allocated_size() - record.index_used_size()
+            );
+
+            // testfs1 has been created without journaling.
+            assert_eq!(record.lsn(), 0);
+            assert_ne!(record.update_sequence_number(), 0);
+
+            // Every block of the record is located where the value maps its offset to.
+            let record_offset = i as u64 * index_record_size as u64;
+            for offset in [0, 511, 512, index_record_size - 1] {
+                assert_eq!(
+                    record.position_at(offset),
+                    index_allocation
+                        .value
+                        .map_position(&mut testfs1, record_offset + offset as u64)
+                        .unwrap()
+                );
+            }
+            assert_eq!(record.position(), record.position_at(0));
+        }
+
+        // Only a single Index Record of "many_subdirs" is an inner node, all others are leaf nodes.
+        assert_eq!(records.iter().filter(|r| r.has_subnodes()).count(), 1);
+
+        // Entries that were moved to another Index Record when splitting a node linger in the slack space.
+        assert!(records
+            .iter()
+            .any(|record| record.slack().iter().any(|byte| *byte != 0)));
+    }
+
+    #[test]
+    fn test_record_from_vcn_bounds() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = many_subdirs(&ntfs, &mut testfs1);
+
+        let index_root = file
+            .find_resident_attribute_structured_value::<NtfsIndexRoot>(None)
+            .unwrap();
+        let index_record_size = index_root.index_record_size();
+        assert_eq!(index_record_size, 4096);
+
+        let mut iter = file.attributes();
+        let index_allocation_item = loop {
+            let item = iter.next(&mut testfs1).unwrap().unwrap();
+            let attribute = item.to_attribute().unwrap();
+            if attribute.ty().unwrap() == NtfsAttributeType::IndexAllocation {
+                break item;
+            }
+        };
+        let index_allocation_attribute = index_allocation_item.to_attribute().unwrap();
+        let index_allocation = index_allocation_attribute
+            .structured_value::<_, NtfsIndexAllocation>(&mut testfs1)
+            .unwrap();
+
+        // The first Index Record is at VCN 0 and the last one ends right at the end of the attribute.
+        let len = index_allocation.value.len();
+        let clusters_per_record = (index_record_size / ntfs.cluster_size()) as i64;
+        let last_vcn = len as i64 / ntfs.cluster_size() as i64 - clusters_per_record;
+        for vcn in [0, last_vcn] {
+            let record = index_allocation
+                .record_from_vcn(&mut testfs1, index_record_size, Vcn::from(vcn))
+                .unwrap();
+            assert_eq!(record.vcn(), Vcn::from(vcn));
+        }
+
+        // An Index Record must not exceed the end of the attribute, even partially.
+        for vcn in [
+            last_vcn + 1,
+            last_vcn + clusters_per_record,
+            -clusters_per_record,
+        ] {
+            assert!(matches!(
+                index_allocation.record_from_vcn(&mut testfs1, index_record_size, Vcn::from(vcn)),
+                Err(NtfsError::VcnOutOfBoundsInIndexAllocation { .. })
+            ));
+        }
+
+        // A VCN whose byte offset doesn't fit into 64 bits must be rejected.
+        assert!(matches!(
+            index_allocation.record_from_vcn(&mut testfs1, index_record_size, Vcn::from(i64::MAX)),
+            Err(NtfsError::VcnTooBig { .. })
+        ));
+
+        // Bogus Index Record sizes must be rejected before reading anything.
+        for index_record_size in [0, 4095, u32::MAX] {
+            assert!(matches!(
+                index_allocation.record_from_vcn(&mut testfs1, index_record_size, Vcn::from(0)),
+                Err(NtfsError::InvalidIndexRecordSize { .. })
+            ));
+
+            let mut records = index_allocation.records(index_record_size);
+            assert!(matches!(
+                records.next(&mut testfs1),
+                Some(Err(NtfsError::InvalidIndexRecordSize { .. }))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_record_size_mismatch() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = many_subdirs(&ntfs, &mut testfs1);
+
+        let index_root = file
+            .find_resident_attribute_structured_value::<NtfsIndexRoot>(None)
+            .unwrap();
+        let index_record_size = index_root.index_record_size();
+        assert_eq!(index_record_size, 4096);
+
+        let mut iter = file.attributes();
+        let index_allocation_item = loop {
+            let item = iter.next(&mut testfs1).unwrap().unwrap();
+            let attribute = item.to_attribute().unwrap();
+            if attribute.ty().unwrap() == NtfsAttributeType::IndexAllocation {
+                break item;
+            }
+        };
+        let index_allocation_attribute = index_allocation_item.to_attribute().unwrap();
+        let index_allocation = index_allocation_attribute
+            .structured_value::<_, NtfsIndexAllocation>(&mut testfs1)
+            .unwrap();
+
+        let record = index_allocation
+            .record_from_vcn(&mut testfs1, index_record_size, Vcn::from(0))
+            .unwrap();
+        let position = record.position().value().unwrap().get() as usize;
+        let original = testfs1.get_ref()[position..position + index_record_size as usize].to_vec();
+
+        // Let the Index Record look like it has been written for an Index Record size of 2048 bytes,
+        // i.e. with an Update Sequence Array for 4 sectors and an allocated size of 2048 bytes minus the header.
+        // Neither field lies at the end of a sector, so both are unaffected by the fixups.
+        let small_update_sequence_count = 5u16;
+        let small_allocated_size = 2048u32 - 24;
+
+        // The Update Sequence Array only covers the first half of the record.
+        let record_data = &mut testfs1.get_mut()[position..];
+        record_data[6..8].copy_from_slice(&small_update_sequence_count.to_le_bytes());
+        record_data[0x20..0x24].copy_from_slice(&small_allocated_size.to_le_bytes());
+        assert!(matches!(
+            index_allocation.record_from_vcn(&mut testfs1, index_record_size, Vcn::from(0)),
+            Err(NtfsError::InvalidUpdateSequenceCount {
+                update_sequence_count: 5,
+                ..
+            })
+        ));
+
+        // With a matching Update Sequence Array, the allocated size of the record is still checked.
+        let record_data = &mut testfs1.get_mut()[position..];
+        record_data[..original.len()].copy_from_slice(&original);
+        record_data[0x20..0x24].copy_from_slice(&small_allocated_size.to_le_bytes());
+        assert!(matches!(
+            index_allocation.record_from_vcn(&mut testfs1, index_record_size, Vcn::from(0)),
+            Err(NtfsError::InvalidIndexAllocatedSize {
+                expected: 4096,
+                actual: 2048,
+                ..
+            })
+        ));
+    }
+
+    /// VCN at which [`testfs1_with_split_index_allocation`] splits the $INDEX_ALLOCATION attribute.
+    /// This is in the middle of the first Index Record.
+    const SPLIT_VCN: usize = 4;
+
+    /// Returns testfs1 with the $INDEX_ALLOCATION attribute of "many_subdirs" split at [`SPLIT_VCN`] into two
+    /// connected attributes in the extension records 100 and 101.
+    ///
+    /// The clusters of the second half of the first Index Record are additionally relocated to the end of
+    /// the filesystem image, whose Logical Cluster Number is returned as well.
+    fn testfs1_with_split_index_allocation() -> (std::io::Cursor<Vec<u8>>, u64) {
+        const CLUSTER_SIZE: u64 = 512;
+
+        let mut testfs1 = crate::helpers::tests::testfs1();
+
+        let (base_reference, extension_references, mut lcns, sizes) = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            assert_eq!(ntfs.cluster_size() as u64, CLUSTER_SIZE);
+
+            let file = many_subdirs(&ntfs, &mut testfs1);
+            let base_reference = file_reference(68, file.sequence_number());
+            let extension_references = [100, 101].map(|file_record_number| {
+                let file = ntfs.file(&mut testfs1, file_record_number).unwrap();
+                file_reference(file_record_number, file.sequence_number())
+            });
+
+            let attribute = file
+                .attributes_raw()
+                .map(Result::unwrap)
+                .find(|attribute| attribute.ty().unwrap() == NtfsAttributeType::IndexAllocation)
+                .unwrap();
+            let sizes = (
+                attribute.allocated_size(),
+                attribute.value_length(),
+                attribute.initialized_size(),
+            );
+            let value = match attribute.value(&mut testfs1).unwrap() {
+                NtfsAttributeValue::NonResident(value) => value,
+                _ => panic!("$INDEX_ALLOCATION of many_subdirs should be non-resident"),
+            };
+
+            // The Logical Cluster Number of every cluster of the value.
+            let lcns = value
+                .data_runs()
+                .map(Result::unwrap)
+                .flat_map(|data_run| {
+                    let first_lcn = data_run.data_position().value().unwrap().get() / CLUSTER_SIZE;
+                    first_lcn..first_lcn + data_run.allocated_size() / CLUSTER_SIZE
+                })
+                .collect::<Vec<u64>>();
+            assert_eq!(lcns.len() as u64 * CLUSTER_SIZE, sizes.0);
+            assert!(lcns.len() >= 4 * SPLIT_VCN);
+
+            (base_reference, extension_references, lcns, sizes)
+        };
+
+        // Copy the clusters of the second half of the first Index Record to the end of the image.
+        let relocated_lcn = testfs1.get_ref().len() as u64 / CLUSTER_SIZE;
+        for (i, lcn) in lcns[SPLIT_VCN..2 * SPLIT_VCN].iter_mut().enumerate() {
+            let start = (*lcn * CLUSTER_SIZE) as usize;
+            let cluster = testfs1.get_ref()[start..start + CLUSTER_SIZE as usize].to_vec();
+            testfs1.get_mut().extend(cluster);
+            *lcn = relocated_lcn + i as u64;
+        }
+
+        // Encode the clusters as Data Runs, merging consecutive clusters.
+        let data_runs = |lcns: &[u64]| {
+            let mut data_runs = Vec::<(u64, Option<u64>)>::new();
+            for lcn in lcns {
+                match data_runs.last_mut() {
+                    Some((cluster_count, Some(first_lcn)))
+                        if *first_lcn + *cluster_count == *lcn =>
+                    {
+                        *cluster_count += 1
+                    }
+                    _ => data_runs.push((1, Some(*lcn))),
+                }
+            }
+            encode_data_runs(&data_runs)
+        };
+
+        // Only the first connected attribute stores the value sizes.
+        let (allocated_size, data_size, initialized_size) = sizes;
+        let attributes = [
+            AttributeBuilder::non_resident(
+                NtfsAttributeType::IndexAllocation,
+                0,
+                SPLIT_VCN as u64 - 1,
+                &data_runs(&lcns[..SPLIT_VCN]),
+            )
+            .name("$I30")
+            .sizes(allocated_size, data_size, initialized_size)
+            .build(),
+            AttributeBuilder::non_resident(
+                NtfsAttributeType::IndexAllocation,
+                SPLIT_VCN as u64,
+                lcns.len() as u64 - 1,
+                &data_runs(&lcns[SPLIT_VCN..]),
+            )
+            .name("$I30")
+            .build(),
+        ];
+
+        for (file_record_number, attribute) in [100, 101].iter().zip(&attributes) {
+            patch_file_record_number(&mut testfs1, *file_record_number, |record| {
+                make_extension_record(record, base_reference, attribute, 1);
+            });
+        }
+
+        let list = [0, SPLIT_VCN as u64]
+            .iter()
+            .zip(&extension_references)
+            .flat_map(|(lowest_vcn, reference)| {
+                attribute_list_entry(
+                    NtfsAttributeType::IndexAllocation,
+                    "$I30",
+                    *lowest_vcn,
+                    *reference,
+                    0,
+                )
+            })
+            .collect::<Vec<u8>>();
+
+        patch_file_record_number(&mut testfs1, 68, |record| {
+            let offset = attribute_offset(record, NtfsAttributeType::IndexAllocation);
+            remove_attribute(record, offset);
+
+            // The $ATTRIBUTE_LIST comes right after the $STANDARD_INFORMATION attribute.
+            let first_attribute_offset = first_attribute_offset(record);
+            assert_eq!(
+                attribute_offset(record, NtfsAttributeType::StandardInformation),
+                first_attribute_offset
+            );
+            let insert_offset = first_attribute_offset
+                + LittleEndian::read_u32(&record[first_attribute_offset + 4..]) as usize;
+
+            let attribute = AttributeBuilder::resident(NtfsAttributeType::AttributeList, &list)
+                .instance(take_attribute_instance(record))
+                .build();
+            insert_attribute(record, insert_offset, &attribute);
+        });
+
+        (testfs1, relocated_lcn)
+    }
+
+    fn index_allocation<'n, 'f>(
+        file: &'f NtfsFile<'n>,
+        fs: &mut std::io::Cursor<Vec<u8>>,
+    ) -> NtfsIndexAllocation<'n, 'f> {
+        let mut iter = file.attributes();
+        let index_allocation_item = loop {
+            let item = iter.next(fs).unwrap().unwrap();
+            let attribute = item.to_attribute().unwrap();
+            if attribute.ty().unwrap() == NtfsAttributeType::IndexAllocation {
+                break item;
+            }
+        };
+        let index_allocation_attribute = index_allocation_item.to_attribute().unwrap();
+        index_allocation_attribute
+            .structured_value::<_, NtfsIndexAllocation>(fs)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_record_spanning_connected_attributes() {
+        let mut original_testfs1 = crate::helpers::tests::testfs1();
+        let original_ntfs = Ntfs::new(&mut original_testfs1).unwrap();
+        let original_file = many_subdirs(&original_ntfs, &mut original_testfs1);
+        let index_record_size = original_file
+            .find_resident_attribute_structured_value::<NtfsIndexRoot>(None)
+            .unwrap()
+            .index_record_size();
+        assert_eq!(index_record_size, 4096);
+        let original_index_allocation = index_allocation(&original_file, &mut original_testfs1);
+        let original_records = original_index_allocation
+            .records(index_record_size)
+            .attach(&mut original_testfs1)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        let (mut testfs1, relocated_lcn) = testfs1_with_split_index_allocation();
+        let relocated_position = relocated_lcn * 512;
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = many_subdirs(&ntfs, &mut testfs1);
+        let index_allocation = index_allocation(&file, &mut testfs1);
+        assert!(matches!(
+            index_allocation.value,
+            NtfsAttributeValue::AttributeListNonResident(_)
+        ));
+
+        // The first Index Record starts in the first connected attribute and continues in the relocated
+        // clusters of the second one.
+        let record = index_allocation
+            .record_from_vcn(&mut testfs1, index_record_size, Vcn::from(0))
+            .unwrap();
+        let original_position = original_records[0].position().value().unwrap().get();
+        let split_offset = SPLIT_VCN as u32 * 512;
+        assert_eq!(record.position(), NtfsPosition::new(original_position));
+        assert_eq!(
+            record.position_at(split_offset - 1),
+            NtfsPosition::new(original_position + split_offset as u64 - 1)
+        );
+        assert_eq!(
+            record.position_at(split_offset),
+            NtfsPosition::new(relocated_position)
+        );
+        assert_eq!(
+            record.position_at(index_record_size - 1),
+            NtfsPosition::new(relocated_position + split_offset as u64 - 1)
+        );
+
+        // All Index Records read the same as without splitting, and are located where the value maps them.
+        let records = index_allocation
+            .records(index_record_size)
+            .attach(&mut testfs1)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(records.len(), original_records.len());
+        for (i, (record, original_record)) in records.iter().zip(&original_records).enumerate() {
+            assert_eq!(record.vcn(), original_record.vcn());
+            assert_eq!(record.index_used_size(), original_record.index_used_size());
+            assert_eq!(record.slack(), original_record.slack());
+
+            let record_offset = i as u64 * index_record_size as u64;
+            for offset in [0, split_offset - 1, split_offset, index_record_size - 1] {
+                assert_eq!(
+                    record.position_at(offset),
+                    index_allocation
+                        .value
+                        .map_position(&mut testfs1, record_offset + offset as u64)
+                        .unwrap()
+                );
+            }
+        }
+
+        // A fixup mismatch in the relocated clusters is reported at its actual position.
+        let corrupted_position = relocated_position + 512 + 510;
+        testfs1.get_mut()[corrupted_position as usize] ^= 0xff;
+        let error = index_allocation
+            .record_from_vcn(&mut testfs1, index_record_size, Vcn::from(0))
+            .unwrap_err();
+        assert!(
+            matches!(
+                error,
+                NtfsError::UpdateSequenceNumberMismatch { position, .. }
+                    if position == NtfsPosition::new(corrupted_position)
+            ),
+            "{error:?}"
+        );
+    }
+}
@@ -3,15 +3,16 @@
 
 use core::ops::Range;
 
-use binrw::io::{Read, Seek};
+use crate::io::{Read, Seek};
 use byteorder::{ByteOrder, LittleEndian};
+use enumn::N;
 use memoffset::offset_of;
 
 use crate::attribute::NtfsAttributeType;
 use crate::attribute_value::{NtfsAttributeValue, NtfsResidentAttributeValue};
 use crate::error::{NtfsError, Result};
-use crate::index_entry::{IndexNodeEntryRanges, NtfsIndexNodeEntries};
-use crate::index_record::{IndexNodeHeader, INDEX_NODE_HEADER_SIZE};
+use crate::index_entry::{IndexNodeEntryRanges, NodeData, NtfsIndexNodeEntries};
+use crate::index_record::{validate_index_record_size, IndexNodeHeader, INDEX_NODE_HEADER_SIZE};
 use crate::indexes::NtfsIndexEntryType;
 use crate::structured_values::{
     NtfsStructuredValue, NtfsStructuredValueFromResidentAttributeValue,
@@ -51,6 +52,32 @@ pub struct NtfsIndexRoot<'f> {
 
 const LARGE_INDEX_FLAG: u8 = 0x01;
 
+/// Rules for comparing the keys of an index, as stored in its [`NtfsIndexRoot`].
+///
+/// The collation rule tells which kind of keys an index holds.
+///
+/// Reference: <https://flatcap.github.io/linux-ntfs/ntfs/attributes/index_root.html>
+///
+/// [`NtfsFileName`]: crate::structured_values::NtfsFileName
+#[derive(Clone, Copy, Debug, Eq, N, PartialEq)]
+#[repr(u32)]
+pub enum NtfsCollationRule {
+    /// Keys are compared byte by byte.
+    Binary = 0x00,
+    /// Keys are [`NtfsFileName`]s compared by their upcased names (used for directories).
+    FileName = 0x01,
+    /// Keys are Unicode strings compared by their upcased characters.
+    UnicodeString = 0x02,
+    /// Keys are a single little-endian 32-bit unsigned integer (used for $Q of $Quota and $SII of $Secure).
+    NtofsUlong = 0x10,
+    /// Keys are Security Identifiers (used for $O of $Quota).
+    NtofsSid = 0x11,
+    /// Keys are a security descriptor hash followed by a security identifier (used for $SDH of $Secure).
+    NtofsSecurityHash = 0x12,
+    /// Keys are multiple little-endian 32-bit unsigned integers (used for $O of $ObjId and $R of $Reparse).
+    NtofsUlongs = 0x13,
+}
+
 impl<'f> NtfsIndexRoot<'f> {
     fn new(slice: &'f [u8], position: NtfsPosition) -> Result<Self> {
         if slice.len() < INDEX_ROOT_HEADER_SIZE + INDEX_NODE_HEADER_SIZE {
@@ -69,6 +96,11 @@ impl<'f> NtfsIndexRoot<'f> {
     }
 
     /// Returns an iterator over all top-level nodes of the B-tree.
+    ///
+    /// Only the entries stored in this Index Root are returned.
+    /// Subnodes in the $INDEX_ALLOCATION attribute are not traversed, which is what [`NtfsIndex`] is for.
+    ///
+    /// [`NtfsIndex`]: crate::NtfsIndex
     pub fn entries<E>(&self) -> Result<NtfsIndexNodeEntries<'f, E>>
     where
         E: NtfsIndexEntryType,
@@ -87,15 +119,44 @@ impl<'f> NtfsIndexRoot<'f> {
         (start..end, position)
     }
 
-    pub(crate) fn entry_ranges<E>(&self) -> IndexNodeEntryRanges<E>
+    /// Returns the type of the attribute indexed by this Index Root, or `None` if the index is not built
+    /// from an attribute.
+    ///
+    /// See [`NtfsIndexRoot::indexed_attribute_type`] for the raw value.
+    pub fn attribute_type(&self) -> Option<NtfsAttributeType> {
+        NtfsAttributeType::n(self.indexed_attribute_type())
+    }
+
+    /// Returns the number of clusters allocated for a single Index Record, as stored in this Index Root.
+    ///
+    /// If the Index Record size is smaller than the cluster size, this value is negative, and the
+    /// Index Record size is 2 to the power of its absolute value.
+    /// Use [`NtfsIndexRoot::index_record_size`] to get the Index Record size in bytes in either case.
+    pub fn clusters_per_index_record(&self) -> i8 {
+        let start = offset_of!(IndexRootHeader, clusters_per_index_record);
+        self.slice[start] as i8
+    }
+
+    /// Returns the rule for comparing the keys of this index.
+    pub fn collation_rule(&self) -> Result<NtfsCollationRule> {
+        let start = offset_of!(IndexRootHeader, collation_rule);
+        let collation_rule = LittleEndian::read_u32(&self.slice[start..]);
+
+        NtfsCollationRule::n(collation_rule).ok_or(NtfsError::UnsupportedCollationRule {
+            position: self.position,
+            actual: collation_rule,
+        })
+    }
+
+    pub(crate) fn entry_ranges<E>(&self) -> IndexNodeEntryRanges<'f, E>
     where
         E: NtfsIndexEntryType,
     {
         let (entries_range, position) = self.entries_range_and_position();
-        let entries_data = self.slice[entries_range].to_vec();
+        let entries_data = &self.slice[entries_range];
         let range = 0..entries_data.len();
 
-        IndexNodeEntryRanges::new(entries_data, range, position)
+        IndexNodeEntryRanges::new(NodeData::Borrowed(entries_data), range, position)
     }
 
     /// Returns the allocated size of this NTFS Index Root, in bytes.
@@ -121,6 +182,16 @@ impl<'f> NtfsIndexRoot<'f> {
         LittleEndian::read_u32(&self.slice[start..])
     }
 
+    /// Returns the type of the attribute indexed by this Index Root.
+    ///
+    /// This is [`NtfsAttributeType::FileName`] for directories.
+    /// Indexes that are not built from an attribute (like $SII or $O) store 0 here, which is not a valid
+    /// [`NtfsAttributeType`] and therefore returned as a raw value.
+    pub fn indexed_attribute_type(&self) -> u32 {
+        let start = offset_of!(IndexRootHeader, ty);
+        LittleEndian::read_u32(&self.slice[start..])
+    }
+
     /// Returns whether the index belonging to this Index Root is large enough
     /// to need an extra Index Allocation attribute.
     /// Otherwise, the entire index information is stored in this Index Root.
@@ -135,6 +206,8 @@ impl<'f> NtfsIndexRoot<'f> {
     }
 
     fn validate_sizes(&self) -> Result<()> {
+        validate_index_record_size(self.position, self.index_record_size())?;
+
         let (entries_range, _position) = self.entries_range_and_position();
 
         if entries_range.start >= self.slice.len() {
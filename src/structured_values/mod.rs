@@ -8,7 +8,9 @@ mod file_name;
 mod index_allocation;
 mod index_root;
 mod object_id;
+mod security_descriptor;
 mod standard_information;
+mod txf_data;
 mod volume_information;
 mod volume_name;
 
@@ -19,11 +21,13 @@ pub use file_name::*;
 pub use index_allocation::*;
 pub use index_root::*;
 pub use object_id::*;
+pub use security_descriptor::*;
 pub use standard_information::*;
+pub use txf_data::*;
 pub use volume_information::*;
 pub use volume_name::*;
 
-use binrw::io::{Read, Seek};
+use crate::io::{Read, Seek};
 use bitflags::bitflags;
 
 use crate::attribute::NtfsAttributeType;
@@ -40,6 +44,7 @@ bitflags! {
     ///
     /// [`NtfsAttribute`]: crate::attribute::NtfsAttribute
     #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
     pub struct NtfsFileAttributeFlags: u32 {
         /// File is marked read-only.
         const READ_ONLY = 0x0001;
@@ -1,7 +1,7 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use binrw::io::{Cursor, Read, Seek};
+use crate::io::{Cursor, Read, Seek};
 use binrw::BinReaderExt;
 
 use crate::attribute::NtfsAttributeType;
@@ -0,0 +1,261 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::io::{Read, Seek};
+
+use crate::attribute::NtfsAttributeType;
+use crate::attribute_value::NtfsAttributeValue;
+use crate::error::{NtfsError, Result};
+use crate::security::{self, NtfsSecurityDescriptorControl, SecurityDescriptorLayout};
+use crate::structured_values::NtfsStructuredValue;
+
+/// The largest possible self-relative security descriptor consists of the header, two SIDs with
+/// 15 sub authorities each, and two ACLs of the maximum size.
+const SECURITY_DESCRIPTOR_MAX_SIZE: usize = 20 + 2 * (8 + 15 * 4) + 2 * u16::MAX as usize;
+
+/// Structure of a $SECURITY_DESCRIPTOR attribute.
+///
+/// Filesystems created by NTFS versions before 3.0 store the security descriptor of every file in this attribute.
+/// Newer versions store them centrally in the $Secure file and only reference them by a Security ID
+/// (see [`NtfsStandardInformation::security_id`]), but a few special files may still have this attribute.
+///
+/// The attribute contains a security descriptor in the self-relative format.
+/// This structure gives access to its header and the raw bytes of the structures it references.
+/// Use [`NtfsSecurityDescriptorAttribute::parse`] to fully decode it into an [`NtfsSecurityDescriptor`].
+///
+/// A $SECURITY_DESCRIPTOR attribute can be resident or non-resident.
+///
+/// Reference: <https://flatcap.github.io/linux-ntfs/ntfs/attributes/security_descriptor.html>
+///
+/// [`NtfsSecurityDescriptor`]: crate::security::NtfsSecurityDescriptor
+/// [`NtfsStandardInformation::security_id`]: crate::structured_values::NtfsStandardInformation::security_id
+#[derive(Clone, Debug)]
+pub struct NtfsSecurityDescriptorAttribute {
+    data: Vec<u8>,
+    layout: SecurityDescriptorLayout,
+}
+
+impl NtfsSecurityDescriptorAttribute {
+    fn new<T>(r: &mut T, value: &NtfsAttributeValue) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let value_length = value.len();
+        if value_length > SECURITY_DESCRIPTOR_MAX_SIZE as u64 {
+            return Err(NtfsError::InvalidStructuredValueSize {
+                position: value.data_position(),
+                ty: NtfsAttributeType::SecurityDescriptor,
+                expected: SECURITY_DESCRIPTOR_MAX_SIZE as u64,
+                actual: value_length,
+            });
+        }
+
+        let mut data = vec![0u8; value_length as usize];
+        r.read_exact(&mut data)?;
+        let layout = SecurityDescriptorLayout::parse(&data)?;
+
+        Ok(Self { data, layout })
+    }
+
+    /// Returns the control flags of this security descriptor.
+    pub fn control(&self) -> NtfsSecurityDescriptorControl {
+        self.layout.control
+    }
+
+    /// Returns the raw bytes of the discretionary ACL (DACL), or `None` if the security descriptor has
+    /// no DACL or a null DACL.
+    pub fn dacl(&self) -> Option<&[u8]> {
+        self.slice(&self.layout.dacl)
+    }
+
+    /// Returns the raw bytes of the entire self-relative security descriptor.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the raw bytes of the SID of the primary group, or `None` if the security descriptor has no group.
+    ///
+    /// Use [`NtfsSid::from_bytes`] to decode it.
+    ///
+    /// [`NtfsSid::from_bytes`]: crate::security::NtfsSid::from_bytes
+    pub fn group(&self) -> Option<&[u8]> {
+        self.slice(&self.layout.group)
+    }
+
+    /// Returns the raw bytes of the SID of the owner, or `None` if the security descriptor has no owner.
+    ///
+    /// Use [`NtfsSid::from_bytes`] to decode it.
+    ///
+    /// [`NtfsSid::from_bytes`]: crate::security::NtfsSid::from_bytes
+    pub fn owner(&self) -> Option<&[u8]> {
+        self.slice(&self.layout.owner)
+    }
+
+    /// Fully decodes this security descriptor, including its SIDs and ACLs.
+    pub fn parse(&self) -> Result<security::NtfsSecurityDescriptor<'_>> {
+        security::NtfsSecurityDescriptor::parse(&self.data)
+    }
+
+    /// Returns the revision of this security descriptor (always 1).
+    pub fn revision(&self) -> u8 {
+        self.layout.revision
+    }
+
+    /// Returns the raw bytes of the system ACL (SACL), or `None` if the security descriptor has no SACL.
+    pub fn sacl(&self) -> Option<&[u8]> {
+        self.slice(&self.layout.sacl)
+    }
+
+    fn slice(&self, range: &Option<Range<usize>>) -> Option<&[u8]> {
+        range.clone().map(|range| &self.data[range])
+    }
+}
+
+impl<'n, 'f> NtfsStructuredValue<'n, 'f> for NtfsSecurityDescriptorAttribute {
+    const TY: NtfsAttributeType = NtfsAttributeType::SecurityDescriptor;
+
+    fn from_attribute_value<T>(fs: &mut T, value: NtfsAttributeValue<'n, 'f>) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let mut value_attached = value.clone().attach(fs);
+        Self::new(&mut value_attached, &value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::tests::add_resident_attribute;
+    use crate::ntfs::Ntfs;
+    use crate::security::NtfsSid;
+
+    /// Security descriptor of the system files of testfs1, taken from its $Secure:$SDS stream.
+    fn admin_owned() -> Vec<u8> {
+        let hex = concat!(
+            "0100048048000000580000000000000014000000020034000200000000001400",
+            "8900120001010000000000051200000000001800890012000102000000000005",
+            "2000000020020000010200000000000520000000200200000102000000000005",
+            "2000000020020000",
+        );
+
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn check(sd: &NtfsSecurityDescriptorAttribute) {
+        assert_eq!(sd.revision(), 1);
+        assert_eq!(
+            sd.control(),
+            NtfsSecurityDescriptorControl::SELF_RELATIVE
+                | NtfsSecurityDescriptorControl::DACL_PRESENT
+        );
+
+        let owner = NtfsSid::from_bytes(sd.owner().unwrap()).unwrap();
+        assert_eq!(owner.to_string(), "S-1-5-32-544");
+        assert_eq!(sd.owner().unwrap().len(), 16);
+        let group = NtfsSid::from_bytes(sd.group().unwrap()).unwrap();
+        assert_eq!(group.to_string(), "S-1-5-32-544");
+
+        assert!(sd.sacl().is_none());
+        assert_eq!(sd.dacl().unwrap().len(), 0x34);
+        assert_eq!(sd.parse().unwrap().dacl().unwrap().ace_count(), 2);
+    }
+
+    #[test]
+    fn test_resident() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        add_resident_attribute(
+            &mut testfs1,
+            65,
+            NtfsAttributeType::SecurityDescriptor,
+            "",
+            &admin_owned(),
+        );
+
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        let attribute = file
+            .find_resident_attribute(NtfsAttributeType::SecurityDescriptor, None, None)
+            .unwrap();
+        let sd = attribute
+            .structured_value::<_, NtfsSecurityDescriptorAttribute>(&mut testfs1)
+            .unwrap();
+        check(&sd);
+        assert_eq!(sd.data(), admin_owned());
+    }
+
+    #[test]
+    fn test_non_resident() {
+        // Turn the non-resident $DATA attribute of "1000-bytes-file" (File Record 66) into a
+        // $SECURITY_DESCRIPTOR attribute and store the security descriptor in its first cluster.
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let (record_position, attribute_offset, file_record_size, data_position) = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let file = ntfs.file(&mut testfs1, 66).unwrap();
+            let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+            let data_run = data_attribute
+                .non_resident_value()
+                .unwrap()
+                .data_runs()
+                .next()
+                .unwrap()
+                .unwrap();
+
+            let record_position = file.position().value().unwrap().get() as usize;
+            (
+                record_position,
+                data_attribute.position().value().unwrap().get() as usize - record_position,
+                ntfs.file_record_size() as usize,
+                data_run.data_position().value().unwrap().get() as usize,
+            )
+        };
+
+        let data = admin_owned();
+        testfs1.get_mut()[data_position..data_position + data.len()].copy_from_slice(&data);
+        crate::helpers::tests::patch_file_record(
+            &mut testfs1,
+            record_position,
+            file_record_size,
+            |record| {
+                let ty = NtfsAttributeType::SecurityDescriptor as u32;
+                record[attribute_offset..attribute_offset + 4].copy_from_slice(&ty.to_le_bytes());
+            },
+        );
+
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 66).unwrap();
+        let mut attributes = file.attributes();
+        let attribute = loop {
+            let item = attributes.next(&mut testfs1).unwrap().unwrap();
+            let attribute = item.to_attribute().unwrap();
+            if attribute.ty().unwrap() == NtfsAttributeType::SecurityDescriptor {
+                break attribute;
+            }
+        };
+        assert!(!attribute.is_resident());
+
+        // The value is 1000 bytes long, so the security descriptor is followed by garbage,
+        // which is ignored.
+        let sd = attribute
+            .structured_value::<_, NtfsSecurityDescriptorAttribute>(&mut testfs1)
+            .unwrap();
+        check(&sd);
+        assert_eq!(sd.data().len(), 1000);
+
+        // A truncated security descriptor is rejected.
+        let mut data = admin_owned();
+        data.truncate(0x50);
+        assert!(matches!(
+            SecurityDescriptorLayout::parse(&data),
+            Err(NtfsError::InvalidSecurityDescriptorRange { .. })
+        ));
+    }
+}
@@ -1,7 +1,7 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use binrw::io::{Cursor, Read, Seek};
+use crate::io::{Cursor, Read, Seek};
 use binrw::{BinRead, BinReaderExt};
 
 use crate::attribute::NtfsAttributeType;
@@ -146,6 +146,81 @@ impl NtfsStandardInformation {
     }
 }
 
+/// In-place modification of the resident $STANDARD_INFORMATION value of a File Record.
+///
+/// Passed to the closure of [`NtfsFile::update_standard_information`].
+/// Only fields that never change the size of any structure can be modified.
+///
+/// [`NtfsFile::update_standard_information`]: crate::NtfsFile::update_standard_information
+#[cfg(feature = "write")]
+#[cfg_attr(docsrs, doc(cfg(feature = "write")))]
+#[derive(Debug)]
+pub struct NtfsStandardInformationUpdate<'a> {
+    data: &'a mut [u8],
+}
+
+#[cfg(feature = "write")]
+impl<'a> NtfsStandardInformationUpdate<'a> {
+    const CREATION_TIME_OFFSET: usize = 0;
+    const MODIFICATION_TIME_OFFSET: usize = 8;
+    const MFT_RECORD_MODIFICATION_TIME_OFFSET: usize = 16;
+    const ACCESS_TIME_OFFSET: usize = 24;
+    const FILE_ATTRIBUTES_OFFSET: usize = 32;
+
+    /// Creates an update for the given resident value data, which must be validated to have at least
+    /// [`STANDARD_INFORMATION_SIZE_NTFS1`] bytes.
+    pub(crate) fn new(data: &'a mut [u8]) -> Self {
+        debug_assert!(data.len() >= STANDARD_INFORMATION_SIZE_NTFS1);
+        Self { data }
+    }
+
+    pub(crate) fn validate_size(position: NtfsPosition, value_length: usize) -> Result<()> {
+        if value_length < STANDARD_INFORMATION_SIZE_NTFS1 {
+            return Err(NtfsError::InvalidStructuredValueSize {
+                position,
+                ty: NtfsAttributeType::StandardInformation,
+                expected: STANDARD_INFORMATION_SIZE_NTFS1 as u64,
+                actual: value_length as u64,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn set_time(&mut self, offset: usize, time: NtfsTime) {
+        self.data[offset..offset + 8].copy_from_slice(&time.nt_timestamp().to_le_bytes());
+    }
+
+    /// Sets the time this file was last accessed.
+    pub fn set_access_time(&mut self, time: NtfsTime) {
+        self.set_time(Self::ACCESS_TIME_OFFSET, time);
+    }
+
+    /// Sets the time this file was created.
+    pub fn set_creation_time(&mut self, time: NtfsTime) {
+        self.set_time(Self::CREATION_TIME_OFFSET, time);
+    }
+
+    /// Sets the flags that a user can set for a file (Read-Only, Hidden, System, Archive, etc.).
+    ///
+    /// [`NtfsFileAttributeFlags::IS_DIRECTORY`] is only used by $FILE_NAME attributes and therefore ignored here.
+    pub fn set_file_attributes(&mut self, file_attributes: NtfsFileAttributeFlags) {
+        let file_attributes = file_attributes - NtfsFileAttributeFlags::IS_DIRECTORY;
+        let start = Self::FILE_ATTRIBUTES_OFFSET;
+        self.data[start..start + 4].copy_from_slice(&file_attributes.bits().to_le_bytes());
+    }
+
+    /// Sets the time the MFT record of this file was last modified.
+    pub fn set_mft_record_modification_time(&mut self, time: NtfsTime) {
+        self.set_time(Self::MFT_RECORD_MODIFICATION_TIME_OFFSET, time);
+    }
+
+    /// Sets the time this file was last modified.
+    pub fn set_modification_time(&mut self, time: NtfsTime) {
+        self.set_time(Self::MODIFICATION_TIME_OFFSET, time);
+    }
+}
+
 impl<'n, 'f> NtfsStructuredValue<'n, 'f> for NtfsStandardInformation {
     const TY: NtfsAttributeType = NtfsAttributeType::StandardInformation;
 
@@ -0,0 +1,171 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::io::{Cursor, Read, Seek};
+use binrw::{BinRead, BinReaderExt};
+
+use crate::attribute::NtfsAttributeType;
+use crate::attribute_value::{NtfsAttributeValue, NtfsResidentAttributeValue};
+use crate::error::{NtfsError, Result};
+use crate::file_reference::NtfsFileReference;
+use crate::structured_values::{
+    NtfsStructuredValue, NtfsStructuredValueFromResidentAttributeValue,
+};
+use crate::types::NtfsPosition;
+
+/// Size of all [`TxfDataHeader`] fields.
+const TXF_DATA_SIZE: usize = 56;
+
+#[derive(BinRead, Clone, Debug)]
+struct TxfDataHeader {
+    _unknown: [u8; 6],
+    rm_root_file_reference: NtfsFileReference,
+    usn_index: u64,
+    txid: u64,
+    data_lsn: u64,
+    metadata_lsn: u64,
+    directory_index_lsn: u64,
+    flags: u16,
+}
+
+/// Structure of a $TXF_DATA attribute.
+///
+/// This is a $LOGGED_UTILITY_STREAM attribute named "$TXF_DATA", which Transactional NTFS (TxF) adds to
+/// every file that has ever been part of a transaction.
+/// You can easily find it via [`NtfsFile::stream`].
+///
+/// A $TXF_DATA attribute is always resident.
+///
+/// Reference: <https://github.com/libyal/libfsntfs/blob/main/documentation/New%20Technologies%20File%20System%20(NTFS).asciidoc>
+///
+/// [`NtfsFile::stream`]: crate::NtfsFile::stream
+#[derive(Clone, Debug)]
+pub struct NtfsTxfData {
+    header: TxfDataHeader,
+}
+
+impl NtfsTxfData {
+    fn new<T>(r: &mut T, position: NtfsPosition, value_length: u64) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        if value_length < TXF_DATA_SIZE as u64 {
+            return Err(NtfsError::InvalidStructuredValueSize {
+                position,
+                ty: NtfsAttributeType::LoggedUtilityStream,
+                expected: TXF_DATA_SIZE as u64,
+                actual: value_length,
+            });
+        }
+
+        let header = r.read_le::<TxfDataHeader>()?;
+
+        Ok(Self { header })
+    }
+
+    /// Returns the Log File Sequence Number (LSN) of the last transactional change to the file data.
+    pub fn data_lsn(&self) -> u64 {
+        self.header.data_lsn
+    }
+
+    /// Returns the Log File Sequence Number (LSN) of the last transactional change to the directory index
+    /// entry of the file.
+    pub fn directory_index_lsn(&self) -> u64 {
+        self.header.directory_index_lsn
+    }
+
+    /// Returns the undocumented flags of this $TXF_DATA attribute.
+    pub fn flags(&self) -> u16 {
+        self.header.flags
+    }
+
+    /// Returns the Log File Sequence Number (LSN) of the last transactional change to the file metadata.
+    pub fn metadata_lsn(&self) -> u64 {
+        self.header.metadata_lsn
+    }
+
+    /// Returns an [`NtfsFileReference`] to the root of the Resource Manager (usually the `$Extend\$RmMetadata`
+    /// directory) that manages the transactions of this file.
+    pub fn rm_root_file_reference(&self) -> NtfsFileReference {
+        self.header.rm_root_file_reference
+    }
+
+    /// Returns the identifier of the transaction that last changed this file.
+    pub fn txid(&self) -> u64 {
+        self.header.txid
+    }
+
+    /// Returns the index into the Update Sequence Number (USN) journal associated to this file.
+    pub fn usn_index(&self) -> u64 {
+        self.header.usn_index
+    }
+}
+
+impl<'n, 'f> NtfsStructuredValue<'n, 'f> for NtfsTxfData {
+    const TY: NtfsAttributeType = NtfsAttributeType::LoggedUtilityStream;
+
+    fn from_attribute_value<T>(fs: &mut T, value: NtfsAttributeValue<'n, 'f>) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let position = value.data_position();
+        let value_length = value.len();
+
+        let mut value_attached = value.attach(fs);
+        Self::new(&mut value_attached, position, value_length)
+    }
+}
+
+impl<'n, 'f> NtfsStructuredValueFromResidentAttributeValue<'n, 'f> for NtfsTxfData {
+    fn from_resident_attribute_value(value: NtfsResidentAttributeValue<'f>) -> Result<Self> {
+        let position = value.data_position();
+        let value_length = value.len();
+
+        let mut cursor = Cursor::new(value.data());
+        Self::new(&mut cursor, position, value_length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use super::*;
+
+    #[test]
+    fn test_txf_data() {
+        let position = NtfsPosition::new(0x1000);
+
+        let mut data = [0u8; TXF_DATA_SIZE];
+        LittleEndian::write_u64(&mut data[6..], 0x0001_0000_0000_0027);
+        LittleEndian::write_u64(&mut data[14..], 0x1122);
+        LittleEndian::write_u64(&mut data[22..], 0x3344);
+        LittleEndian::write_u64(&mut data[30..], 0x5566);
+        LittleEndian::write_u64(&mut data[38..], 0x7788);
+        LittleEndian::write_u64(&mut data[46..], 0x99aa);
+        LittleEndian::write_u16(&mut data[54..], 0x2);
+
+        let value = NtfsResidentAttributeValue::new(&data, position);
+        let txf_data = NtfsTxfData::from_resident_attribute_value(value).unwrap();
+        assert_eq!(txf_data.rm_root_file_reference().file_record_number(), 0x27);
+        assert_eq!(txf_data.rm_root_file_reference().sequence_number(), 1);
+        assert_eq!(txf_data.usn_index(), 0x1122);
+        assert_eq!(txf_data.txid(), 0x3344);
+        assert_eq!(txf_data.data_lsn(), 0x5566);
+        assert_eq!(txf_data.metadata_lsn(), 0x7788);
+        assert_eq!(txf_data.directory_index_lsn(), 0x99aa);
+        assert_eq!(txf_data.flags(), 0x2);
+
+        // A truncated value is rejected.
+        let value = NtfsResidentAttributeValue::new(&data[..TXF_DATA_SIZE - 1], position);
+        assert!(matches!(
+            NtfsTxfData::from_resident_attribute_value(value),
+            Err(NtfsError::InvalidStructuredValueSize {
+                ty: NtfsAttributeType::LoggedUtilityStream,
+                expected: 56,
+                actual: 55,
+                ..
+            })
+        ));
+    }
+}
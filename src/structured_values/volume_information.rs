@@ -3,7 +3,7 @@
 
 use core::fmt;
 
-use binrw::io::{Cursor, Read, Seek};
+use crate::io::{Cursor, Read, Seek};
 use binrw::{BinRead, BinReaderExt};
 use bitflags::bitflags;
 
@@ -1,10 +1,10 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use core::mem;
+use core::{iter, mem};
 
+use crate::io::{Cursor, Read, Seek};
 use arrayvec::ArrayVec;
-use binrw::io::{Cursor, Read, Seek};
 use nt_string::u16strle::U16StrLe;
 
 use crate::attribute::NtfsAttributeType;
@@ -49,15 +49,15 @@ impl NtfsVolumeName {
 
         let value_length = value_length as usize;
 
-        let mut name = ArrayVec::from([0u8; VOLUME_NAME_MAX_SIZE]);
-        r.read_exact(&mut name[..value_length])?;
-        name.truncate(value_length);
+        let mut name = ArrayVec::new();
+        name.extend(iter::repeat(0).take(value_length));
+        r.read_exact(&mut name)?;
 
         Ok(Self { name })
     }
 
     /// Gets the volume name and returns it wrapped in a [`U16StrLe`].
-    pub fn name(&self) -> U16StrLe {
+    pub fn name(&self) -> U16StrLe<'_> {
         U16StrLe(&self.name)
     }
 
@@ -1,21 +1,31 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use core::time::Duration;
+
 use binrw::BinRead;
 use derive_more::From;
 
+#[cfg(any(feature = "chrono", feature = "time"))]
+use crate::error::NtfsError;
+
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+
 #[cfg(feature = "time")]
-use {crate::error::NtfsError, time::OffsetDateTime};
+use time::OffsetDateTime;
 
 #[cfg(feature = "std")]
 use std::time::{SystemTime, SystemTimeError};
 
+#[cfg(all(feature = "serde", feature = "time"))]
+use alloc::{format, string::String};
+
 /// Difference in 100-nanosecond intervals between the Windows/NTFS epoch (1601-01-01) and the Unix epoch (1970-01-01).
-#[cfg(any(feature = "time", feature = "std"))]
+#[cfg(any(feature = "chrono", feature = "std", feature = "time"))]
 const EPOCH_DIFFERENCE_IN_INTERVALS: u64 = 116_444_736_000_000_000;
 
 /// Number of 100-nanosecond intervals in a second.
-#[cfg(any(feature = "time", feature = "std"))]
 const INTERVALS_PER_SECOND: u64 = 10_000_000;
 
 /// An NTFS timestamp, used for expressing file times.
@@ -26,10 +36,154 @@ const INTERVALS_PER_SECOND: u64 = 10_000_000;
 pub struct NtfsTime(u64);
 
 impl NtfsTime {
+    /// Returns this timestamp plus the given duration, or `None` if the result is beyond the largest NT timestamp.
+    ///
+    /// The duration is truncated to the NT timestamp precision of 100 nanoseconds.
+    pub fn checked_add_duration(&self, duration: Duration) -> Option<Self> {
+        let intervals = duration_to_intervals(duration)?;
+        self.0.checked_add(intervals).map(Self)
+    }
+
+    /// Returns this timestamp minus the given duration, or `None` if the result is before January 1, 1601.
+    ///
+    /// The duration is truncated to the NT timestamp precision of 100 nanoseconds.
+    pub fn checked_sub_duration(&self, duration: Duration) -> Option<Self> {
+        let intervals = duration_to_intervals(duration)?;
+        self.0.checked_sub(intervals).map(Self)
+    }
+
+    /// Returns the current system time as an NT timestamp.
+    ///
+    /// Like NTFS, this doesn't know about leap seconds.
+    /// A system time outside the range of NT timestamps is clamped to the nearest representable value.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn now() -> Self {
+        let unix_epoch = Self(EPOCH_DIFFERENCE_IN_INTERVALS);
+
+        match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(duration) => unix_epoch
+                .checked_add_duration(duration)
+                .unwrap_or(Self(u64::MAX)),
+            Err(e) => unix_epoch
+                .checked_sub_duration(e.duration())
+                .unwrap_or(Self(0)),
+        }
+    }
+
     /// Returns the stored NT timestamp (number of 100-nanosecond intervals since January 1, 1601).
     pub fn nt_timestamp(&self) -> u64 {
         self.0
     }
+
+    /// Formats this timestamp as an ISO 8601 string in UTC with the full precision of 100 nanoseconds,
+    /// e.g. "2013-01-05T18:15:00.0000000Z".
+    ///
+    /// Years beyond 9999 are prefixed with a plus sign, as required by ISO 8601 for expanded years.
+    #[cfg(all(feature = "serde", feature = "time"))]
+    fn to_iso8601(self) -> Result<String, NtfsError> {
+        let dt = OffsetDateTime::try_from(self)?;
+        let sign = if dt.year() > 9999 { "+" } else { "" };
+
+        Ok(format!(
+            "{}{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:07}Z",
+            sign,
+            dt.year(),
+            u8::from(dt.month()),
+            dt.day(),
+            dt.hour(),
+            dt.minute(),
+            dt.second(),
+            dt.nanosecond() / 100
+        ))
+    }
+}
+
+/// Converts the given duration into 100-nanosecond intervals, or returns `None` if they don't fit into a `u64`.
+fn duration_to_intervals(duration: Duration) -> Option<u64> {
+    duration
+        .as_secs()
+        .checked_mul(INTERVALS_PER_SECOND)?
+        .checked_add(duration.subsec_nanos() as u64 / 100)
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<'de> serde::Deserialize<'de> for NtfsTime {
+    /// Deserializes a timestamp serialized by the [`serde::Serialize`] implementation.
+    ///
+    /// Only the `nt_timestamp` field is used, an `iso8601` field is ignored.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(serde::Deserialize)]
+        struct NtfsTimeFields {
+            nt_timestamp: u64,
+        }
+
+        let fields = NtfsTimeFields::deserialize(deserializer)?;
+        Ok(Self(fields.nt_timestamp))
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl serde::Serialize for NtfsTime {
+    /// Serializes the timestamp as a structure with the raw `nt_timestamp`.
+    ///
+    /// If the `time` feature is enabled, the structure additionally contains an `iso8601` field
+    /// with the timestamp formatted as an ISO 8601 string in UTC (e.g. "2013-01-05T18:15:00.0000000Z").
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let field_count = if cfg!(feature = "time") { 2 } else { 1 };
+        let mut state = serializer.serialize_struct("NtfsTime", field_count)?;
+        state.serialize_field("nt_timestamp", &self.0)?;
+        #[cfg(feature = "time")]
+        state.serialize_field(
+            "iso8601",
+            &self.to_iso8601().map_err(serde::ser::Error::custom)?,
+        )?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "chrono")]
+#[cfg_attr(docsrs, doc(cfg(feature = "chrono")))]
+impl TryFrom<DateTime<Utc>> for NtfsTime {
+    type Error = NtfsError;
+
+    fn try_from(dt: DateTime<Utc>) -> Result<Self, Self::Error> {
+        // The nanoseconds exceed a second to represent a leap second.
+        // NTFS doesn't know about leap seconds, so they just carry over into the next second.
+        let intervals_since_unix_epoch = dt.timestamp() as i128 * INTERVALS_PER_SECOND as i128
+            + dt.timestamp_subsec_nanos() as i128 / 100;
+        let intervals_since_windows_epoch =
+            intervals_since_unix_epoch + EPOCH_DIFFERENCE_IN_INTERVALS as i128;
+        let nt_timestamp =
+            u64::try_from(intervals_since_windows_epoch).map_err(|_| NtfsError::InvalidTime)?;
+
+        Ok(Self(nt_timestamp))
+    }
+}
+
+#[cfg(feature = "chrono")]
+#[cfg_attr(docsrs, doc(cfg(feature = "chrono")))]
+impl From<NtfsTime> for DateTime<Utc> {
+    fn from(nt: NtfsTime) -> DateTime<Utc> {
+        let intervals_since_windows_epoch = nt.nt_timestamp() as i128;
+        let intervals_since_unix_epoch =
+            intervals_since_windows_epoch - EPOCH_DIFFERENCE_IN_INTERVALS as i128;
+        let secs = intervals_since_unix_epoch.div_euclid(INTERVALS_PER_SECOND as i128);
+        let nanos = intervals_since_unix_epoch.rem_euclid(INTERVALS_PER_SECOND as i128) * 100;
+
+        // Every NT timestamp lies within the range supported by chrono (up to the year 262143).
+        DateTime::from_timestamp(secs as i64, nanos as u32).unwrap()
+    }
 }
 
 #[cfg(feature = "time")]
@@ -51,14 +205,22 @@ impl TryFrom<OffsetDateTime> for NtfsTime {
 
 #[cfg(feature = "time")]
 #[cfg_attr(docsrs, doc(cfg(feature = "time")))]
-impl From<NtfsTime> for OffsetDateTime {
-    fn from(nt: NtfsTime) -> OffsetDateTime {
+impl TryFrom<NtfsTime> for OffsetDateTime {
+    type Error = NtfsError;
+
+    fn try_from(nt: NtfsTime) -> Result<Self, Self::Error> {
+        // The nanoseconds are computed in i128, so this can't overflow.
+        // However, the `time` crate only supports years beyond 9999 with its "large-dates" feature.
         let intervals_since_windows_epoch = nt.nt_timestamp() as i128;
         let intervals_since_unix_epoch =
             intervals_since_windows_epoch - EPOCH_DIFFERENCE_IN_INTERVALS as i128;
         let nanos_since_unix_epoch = intervals_since_unix_epoch * 100;
 
-        OffsetDateTime::from_unix_timestamp_nanos(nanos_since_unix_epoch).unwrap()
+        OffsetDateTime::from_unix_timestamp_nanos(nanos_since_unix_epoch).map_err(|_| {
+            NtfsError::NtfsTimeOutOfRange {
+                nt_timestamp: nt.nt_timestamp(),
+            }
+        })
     }
 }
 
@@ -82,11 +244,54 @@ impl TryFrom<SystemTime> for NtfsTime {
 pub(crate) mod tests {
     use super::*;
 
+    #[cfg(feature = "chrono")]
+    use chrono::{TimeZone, Timelike};
+
     #[cfg(feature = "time")]
     use time::macros::datetime;
 
     pub(crate) const NT_TIMESTAMP_2021_01_01: u64 = 132539328000000000u64;
 
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono_datetime() {
+        let dt = Utc.with_ymd_and_hms(2013, 1, 5, 18, 15, 0).unwrap();
+        let nt = NtfsTime::try_from(dt).unwrap();
+        assert_eq!(nt.nt_timestamp(), 130018833000000000u64);
+        assert_eq!(DateTime::<Utc>::from(nt), dt);
+
+        // The full precision of 100 nanoseconds is preserved.
+        let nt = NtfsTime::from(130018833001234567u64);
+        let dt = DateTime::<Utc>::from(nt);
+        assert_eq!(dt.nanosecond(), 123_456_700);
+        assert_eq!(NtfsTime::try_from(dt).unwrap(), nt);
+
+        let dt = Utc.with_ymd_and_hms(1601, 1, 1, 0, 0, 0).unwrap();
+        let nt = NtfsTime::try_from(dt).unwrap();
+        assert_eq!(nt.nt_timestamp(), 0u64);
+        assert_eq!(DateTime::<Utc>::from(nt), dt);
+
+        let dt = Utc.with_ymd_and_hms(1600, 12, 31, 23, 59, 59).unwrap();
+        assert!(NtfsTime::try_from(dt).is_err());
+
+        let dt = Utc
+            .with_ymd_and_hms(9999, 12, 31, 23, 59, 59)
+            .unwrap()
+            .with_nanosecond(999_999_900)
+            .unwrap();
+        let nt = NtfsTime::try_from(dt).unwrap();
+        assert_eq!(nt.nt_timestamp(), 2650467743999999999u64);
+        assert_eq!(DateTime::<Utc>::from(nt), dt);
+
+        // The largest NT timestamp is still representable by chrono.
+        let nt = NtfsTime::from(u64::MAX);
+        let dt = DateTime::<Utc>::from(nt);
+        assert_eq!(NtfsTime::try_from(dt).unwrap(), nt);
+
+        let dt = Utc.with_ymd_and_hms(60056, 5, 29, 0, 0, 0).unwrap();
+        assert!(NtfsTime::try_from(dt).is_err());
+    }
+
     #[cfg(feature = "time")]
     #[test]
     fn test_offsetdatetime() {
@@ -94,7 +299,7 @@ pub(crate) mod tests {
         let nt = NtfsTime::try_from(dt).unwrap();
         assert_eq!(nt.nt_timestamp(), 130018833000000000u64);
 
-        let dt2 = OffsetDateTime::from(nt);
+        let dt2 = OffsetDateTime::try_from(nt).unwrap();
         assert_eq!(dt, dt2);
 
         let dt = datetime!(1601-01-01 0:00 UTC);
@@ -109,6 +314,87 @@ pub(crate) mod tests {
 
         let dt = datetime!(+60056-05-29 0:00 UTC);
         assert!(NtfsTime::try_from(dt).is_err());
+
+        // The full precision of 100 nanoseconds is preserved.
+        let nt = NtfsTime::from(130018833001234567u64);
+        let dt = OffsetDateTime::try_from(nt).unwrap();
+        assert_eq!(dt.nanosecond(), 123_456_700);
+        assert_eq!(NtfsTime::try_from(dt).unwrap(), nt);
+
+        let dt = datetime!(9999-12-31 23:59:59.9999999 UTC);
+        let nt = NtfsTime::try_from(dt).unwrap();
+        assert_eq!(nt.nt_timestamp(), 2650467743999999999u64);
+        assert_eq!(OffsetDateTime::try_from(nt).unwrap(), dt);
+
+        // The largest NT timestamp is still representable thanks to the "large-dates" feature of `time`.
+        let nt = NtfsTime::from(u64::MAX);
+        let dt = OffsetDateTime::try_from(nt).unwrap();
+        assert_eq!(dt.date(), datetime!(+60056-05-28 0:00 UTC).date());
+        assert_eq!(NtfsTime::try_from(dt).unwrap(), nt);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let nt = NtfsTime::from(130018833001234567u64);
+        let json = serde_json::to_value(nt).unwrap();
+        assert_eq!(json["nt_timestamp"], 130018833001234567u64);
+
+        #[cfg(feature = "time")]
+        assert_eq!(json["iso8601"], "2013-01-05T18:15:00.1234567Z");
+
+        let nt2 = serde_json::from_value::<NtfsTime>(json).unwrap();
+        assert_eq!(nt, nt2);
+
+        #[cfg(feature = "time")]
+        {
+            let nt = NtfsTime::try_from(datetime!(+60056-05-27 0:00 UTC)).unwrap();
+            assert_eq!(nt.to_iso8601().unwrap(), "+60056-05-27T00:00:00.0000000Z");
+        }
+    }
+
+    #[test]
+    fn test_checked_duration() {
+        let nt = NtfsTime::from(NT_TIMESTAMP_2021_01_01);
+        let day = Duration::from_secs(24 * 60 * 60);
+        assert_eq!(
+            nt.checked_add_duration(day).unwrap().nt_timestamp(),
+            NT_TIMESTAMP_2021_01_01 + 864_000_000_000
+        );
+        assert_eq!(
+            nt.checked_sub_duration(day).unwrap().nt_timestamp(),
+            NT_TIMESTAMP_2021_01_01 - 864_000_000_000
+        );
+
+        // Durations are truncated to 100 nanoseconds.
+        assert_eq!(
+            nt.checked_add_duration(Duration::from_nanos(199)),
+            Some(NtfsTime::from(NT_TIMESTAMP_2021_01_01 + 1))
+        );
+
+        // The range of NT timestamps is never left.
+        assert!(NtfsTime::from(u64::MAX)
+            .checked_add_duration(Duration::from_nanos(100))
+            .is_none());
+        assert!(NtfsTime::from(0)
+            .checked_sub_duration(Duration::from_nanos(100))
+            .is_none());
+        assert!(nt.checked_add_duration(Duration::MAX).is_none());
+        assert_eq!(
+            NtfsTime::from(u64::MAX).checked_add_duration(Duration::from_nanos(99)),
+            Some(NtfsTime::from(u64::MAX))
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_now() {
+        let before = NtfsTime::try_from(SystemTime::now()).unwrap();
+        let now = NtfsTime::now();
+        let after = NtfsTime::try_from(SystemTime::now()).unwrap();
+
+        assert!(now.nt_timestamp() > NT_TIMESTAMP_2021_01_01);
+        assert!(before <= now && now <= after);
     }
 
     #[cfg(feature = "std")]
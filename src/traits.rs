@@ -1,8 +1,10 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use binrw::io;
-use binrw::io::{Read, Seek, SeekFrom};
+use alloc::vec::Vec;
+
+use crate::io;
+use crate::io::{Read, Seek, SeekFrom};
 
 use crate::error::{NtfsError, Result};
 
@@ -13,6 +15,10 @@ use crate::error::{NtfsError, Result};
 /// If we held one, we could not read from two objects in alternation.
 pub trait NtfsReadSeek {
     /// See [`std::io::Read::read`].
+    ///
+    /// The attribute value readers of this crate leave their stream position unchanged if an error is returned,
+    /// even if parts of the value had already been read successfully.
+    /// Hence, a failed read can simply be retried.
     fn read<T>(&mut self, fs: &mut T, buf: &mut [u8]) -> Result<usize>
     where
         T: Read + Seek;
@@ -30,7 +36,7 @@ pub trait NtfsReadSeek {
                 Ok(n) => {
                     buf = &mut buf[n..];
                 }
-                Err(NtfsError::Io(e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if is_interrupted(&e) => {}
                 Err(e) => return Err(e),
             }
         }
@@ -45,6 +51,61 @@ pub trait NtfsReadSeek {
         }
     }
 
+    /// Reads the data at the given `offset` into `buf` and returns the number of bytes read.
+    ///
+    /// Unlike calling [`NtfsReadSeek::seek`] and [`NtfsReadSeek::read`], this restores the previous
+    /// stream position afterwards.
+    fn read_at<T>(&mut self, fs: &mut T, offset: u64, buf: &mut [u8]) -> Result<usize>
+    where
+        T: Read + Seek,
+    {
+        let stream_position = self.stream_position();
+        self.seek(fs, SeekFrom::Start(offset))?;
+        let result = self.read(fs, buf);
+        self.seek(fs, SeekFrom::Start(stream_position))?;
+        result
+    }
+
+    /// Reads all bytes until the end, but at most `limit` bytes, and appends them to `buf`.
+    /// Returns the number of bytes read.
+    ///
+    /// Like [`std::io::Read::take`] followed by [`std::io::Read::read_to_end`], this stops silently at the limit.
+    /// Check the stream position against the length of the value if you need to know whether the entire
+    /// value has been read.
+    fn read_to_end<T>(&mut self, fs: &mut T, buf: &mut Vec<u8>, limit: usize) -> Result<usize>
+    where
+        T: Read + Seek,
+    {
+        const CHUNK_SIZE: usize = 4096;
+        let start_len = buf.len();
+
+        loop {
+            let bytes_read = buf.len() - start_len;
+            let chunk_size = CHUNK_SIZE.min(limit - bytes_read);
+            if chunk_size == 0 {
+                break;
+            }
+
+            let chunk_start = buf.len();
+            buf.resize(chunk_start + chunk_size, 0);
+
+            match self.read(fs, &mut buf[chunk_start..]) {
+                Ok(0) => {
+                    buf.truncate(chunk_start);
+                    break;
+                }
+                Ok(n) => buf.truncate(chunk_start + n),
+                Err(e) if is_interrupted(&e) => buf.truncate(chunk_start),
+                Err(e) => {
+                    buf.truncate(chunk_start);
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(buf.len() - start_len)
+    }
+
     /// See [`std::io::Seek::seek`].
     fn seek<T>(&mut self, fs: &mut T, pos: SeekFrom) -> Result<u64>
     where
@@ -53,3 +114,101 @@ pub trait NtfsReadSeek {
     /// See [`std::io::Seek::stream_position`].
     fn stream_position(&self) -> u64;
 }
+
+/// Returns whether `error` is an interrupted I/O operation that should be retried,
+/// regardless of any [`NtfsError::ValueReadContext`] it has been wrapped in.
+fn is_interrupted(error: &NtfsError) -> bool {
+    matches!(error.root_cause(), NtfsError::Io(e) if e.kind() == io::ErrorKind::Interrupted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ntfs::Ntfs;
+
+    #[test]
+    fn test_read_exact() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // The "sparse-file" consists of a Data Run with data, a sparse Data Run, and another one with data.
+        let file = ntfs.file(&mut testfs1, 67).unwrap();
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+        assert_eq!(data_value.len(), 500005);
+
+        // The zero-filled sparse Data Run counts towards filling the buffer.
+        let mut buf = vec![0xccu8; 500005];
+        data_value.read_exact(&mut testfs1, &mut buf).unwrap();
+        assert_eq!(buf[..5], *b"12345");
+        assert!(buf[5..500000].iter().all(|b| *b == 0));
+        assert_eq!(buf[500000..], *b"11111");
+
+        // One more byte than the value has must fail.
+        data_value.seek(&mut testfs1, SeekFrom::Start(0)).unwrap();
+        let mut buf = vec![0u8; 500006];
+        let error = data_value.read_exact(&mut testfs1, &mut buf).unwrap_err();
+        assert!(matches!(error, NtfsError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_read_at_and_read_to_end() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // Check a resident value ("file-with-12345") and a non-resident one ("sparse-file").
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+        assert!(data_attribute.is_resident());
+
+        data_value.seek(&mut testfs1, SeekFrom::Start(1)).unwrap();
+        let mut buf = [0u8; 3];
+        assert_eq!(data_value.read_at(&mut testfs1, 3, &mut buf).unwrap(), 2);
+        assert_eq!(buf[..2], *b"45");
+        assert_eq!(data_value.stream_position(), 1);
+
+        let mut vec = b"x".to_vec();
+        assert_eq!(
+            data_value.read_to_end(&mut testfs1, &mut vec, 100).unwrap(),
+            4
+        );
+        assert_eq!(vec, b"x2345");
+
+        let file = ntfs.file(&mut testfs1, 67).unwrap();
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+        assert!(!data_attribute.is_resident());
+
+        let mut buf = [0xccu8; 10];
+        assert_eq!(
+            data_value.read_at(&mut testfs1, 499998, &mut buf).unwrap(),
+            7
+        );
+        assert_eq!(buf[..7], [0, 0, b'1', b'1', b'1', b'1', b'1']);
+        assert_eq!(data_value.stream_position(), 0);
+
+        // The limit is respected, also across Data Runs.
+        let mut vec = Vec::new();
+        assert_eq!(
+            data_value
+                .read_to_end(&mut testfs1, &mut vec, 10000)
+                .unwrap(),
+            10000
+        );
+        assert_eq!(data_value.stream_position(), 10000);
+        assert_eq!(vec[..5], *b"12345");
+
+        assert_eq!(
+            data_value
+                .read_to_end(&mut testfs1, &mut vec, usize::MAX)
+                .unwrap(),
+            490005
+        );
+        assert_eq!(vec.len(), 500005);
+        assert_eq!(vec[500000..], *b"11111");
+    }
+}
@@ -21,13 +21,19 @@ use crate::ntfs::Ntfs;
 /// position outside the valid range.
 /// Therefore, this structure internally uses an [`Option`] of a [`NonZeroU64`] to alternatively
 /// store a `None` value if no valid position can be given.
+///
+/// Positions are ordered by their value, with `None` being less than any valid position.
+/// Adding an offset never panics: An overflowing addition results in `None`.
 #[derive(Clone, Copy, Debug, Eq, From, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct NtfsPosition(Option<NonZeroU64>);
 
 impl NtfsPosition {
     const NONE_STR: &'static str = "<NONE>";
 
-    pub(crate) const fn new(position: u64) -> Self {
+    /// Creates a new position from the given absolute byte position.
+    /// A position of zero results in `None`.
+    pub const fn new(position: u64) -> Self {
         Self(NonZeroU64::new(position))
     }
 
@@ -35,6 +41,25 @@ impl NtfsPosition {
         Self(None)
     }
 
+    /// Adds `offset` to this position.
+    ///
+    /// Returns a `None` position if this position is `None` or the addition overflows.
+    pub fn checked_add(self, offset: u64) -> Self {
+        let new_value = self
+            .0
+            .and_then(|position| position.get().checked_add(offset))
+            .and_then(NonZeroU64::new);
+        Self(new_value)
+    }
+
+    /// Returns the stored position as a `u64`, or `None` if there is no valid position.
+    pub const fn get(&self) -> Option<u64> {
+        match self.0 {
+            Some(position) => Some(position.get()),
+            None => None,
+        }
+    }
+
     /// Returns the stored position, or `None` if there is no valid position.
     pub const fn value(&self) -> Option<NonZeroU64> {
         self.0
@@ -53,10 +78,7 @@ impl Add<u64> for NtfsPosition {
     type Output = Self;
 
     fn add(self, other: u64) -> Self {
-        let new_value = self
-            .0
-            .and_then(|position| NonZeroU64::new(position.get().wrapping_add(other)));
-        Self(new_value)
+        self.checked_add(other)
     }
 }
 
@@ -137,6 +159,12 @@ impl From<NonZeroU64> for NtfsPosition {
     }
 }
 
+impl From<NtfsPosition> for Option<u64> {
+    fn from(position: NtfsPosition) -> Self {
+        position.get()
+    }
+}
+
 /// A Logical Cluster Number (LCN).
 ///
 /// NTFS divides a filesystem into clusters of a given size (power of two), see [`Ntfs::cluster_size`].
@@ -157,6 +185,7 @@ impl From<NonZeroU64> for NtfsPosition {
     PartialOrd,
     UpperHex,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct Lcn(u64);
 
 impl Lcn {
@@ -172,10 +201,18 @@ impl Lcn {
     }
 
     /// Returns the absolute byte position of this LCN within the filesystem.
+    ///
+    /// This includes the partition offset passed to [`Ntfs::new_at_offset`].
+    /// Fails with [`NtfsError::VolumeDataUnavailable`] for an [`Ntfs`] created by [`Ntfs::new_from_mft_parameters`].
     pub fn position(&self, ntfs: &Ntfs) -> Result<NtfsPosition> {
+        if ntfs.is_mft_dump() {
+            return Err(NtfsError::VolumeDataUnavailable { lcn: *self });
+        }
+
         let value = self
             .0
             .checked_mul(ntfs.cluster_size() as u64)
+            .and_then(|offset| offset.checked_add(ntfs.partition_offset()))
             .ok_or(NtfsError::LcnTooBig { lcn: *self })?;
         Ok(NtfsPosition::new(value))
     }
@@ -207,6 +244,7 @@ impl Lcn {
     PartialOrd,
     UpperHex,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct Vcn(i64);
 
 impl Vcn {
@@ -222,3 +260,70 @@ impl Vcn {
         self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_conversions() {
+        assert_eq!(NtfsPosition::new(0), NtfsPosition::none());
+        assert_eq!(NtfsPosition::new(0).get(), None);
+        assert_eq!(NtfsPosition::new(4096).get(), Some(4096));
+        assert_eq!(Option::<u64>::from(NtfsPosition::new(4096)), Some(4096));
+        assert_eq!(Option::<u64>::from(NtfsPosition::none()), None);
+    }
+
+    #[test]
+    fn test_position_overflow() {
+        let position = NtfsPosition::new(u64::MAX - 1);
+        assert_eq!(position.checked_add(1).get(), Some(u64::MAX));
+        assert_eq!(position.checked_add(2), NtfsPosition::none());
+        assert_eq!(position + 2u64, NtfsPosition::none());
+        assert_eq!(position + usize::MAX, NtfsPosition::none());
+        assert_eq!(NtfsPosition::new(u64::MAX) + 1u16, NtfsPosition::none());
+
+        let mut position = NtfsPosition::new(u64::MAX);
+        position += 1u64;
+        assert_eq!(position, NtfsPosition::none());
+
+        // A `None` position stays `None`.
+        assert_eq!(NtfsPosition::none().checked_add(1), NtfsPosition::none());
+    }
+
+    #[test]
+    fn test_position_ordering() {
+        assert!(NtfsPosition::none() < NtfsPosition::new(1));
+        assert!(NtfsPosition::new(1) < NtfsPosition::new(2));
+        assert_eq!(
+            NtfsPosition::new(u64::MAX).max(NtfsPosition::none()),
+            NtfsPosition::new(u64::MAX)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let lcn = Lcn::from(0x1234);
+        let json = serde_json::to_string(&lcn).unwrap();
+        assert_eq!(json, "4660");
+        assert_eq!(serde_json::from_str::<Lcn>(&json).unwrap(), lcn);
+
+        let vcn = Vcn::from(-5);
+        let json = serde_json::to_string(&vcn).unwrap();
+        assert_eq!(json, "-5");
+        assert_eq!(serde_json::from_str::<Vcn>(&json).unwrap(), vcn);
+
+        for (position, expected_json) in [
+            (NtfsPosition::new(0x1000), "4096"),
+            (NtfsPosition::none(), "null"),
+        ] {
+            let json = serde_json::to_string(&position).unwrap();
+            assert_eq!(json, expected_json);
+            assert_eq!(
+                serde_json::from_str::<NtfsPosition>(&json).unwrap(),
+                position
+            );
+        }
+    }
+}
@@ -4,9 +4,10 @@
 use core::cmp::Ordering;
 use core::mem;
 
+use crate::io::{Cursor, Read, Seek};
 use alloc::vec;
 use alloc::vec::Vec;
-use binrw::io::{Read, Seek};
+use binrw::{BinRead, BinReaderExt};
 use nt_string::u16strle::U16StrLe;
 
 use crate::attribute::NtfsAttributeType;
@@ -21,19 +22,189 @@ const UPCASE_CHARACTER_COUNT: usize = 65536;
 /// Hence, the table has a size of 128 KiB.
 const UPCASE_TABLE_SIZE: u64 = (UPCASE_CHARACTER_COUNT * mem::size_of::<u16>()) as u64;
 
+/// Size of all [`UpcaseInfoData`] fields.
+const UPCASE_INFO_SIZE: u64 = 32;
+
+/// Polynomial of the CRC-64 stored in the $Info stream of $UpCase (in reversed bit order).
+const UPCASE_CRC64_POLYNOMIAL: u64 = 0x9a6c_9329_ac4b_c9b5;
+
+#[derive(BinRead, Clone, Debug)]
+struct UpcaseInfoData {
+    length: u32,
+    _reserved: u32,
+    crc64: u64,
+    os_major_version: u32,
+    os_minor_version: u32,
+    os_build: u32,
+    service_pack_major_version: u16,
+    service_pack_minor_version: u16,
+}
+
+/// Information about the $UpCase table, stored in the $Info data stream of the $UpCase file.
+///
+/// Windows 8 and later (as well as recent versions of mkntfs) write this stream when creating a filesystem.
+/// It records the Windows version that generated the table and a CRC-64 checksum of it.
+/// Compare the checksum with [`UpcaseTable::crc64`] to verify the table.
+///
+/// You can get this structure via [`Ntfs::upcase_info`].
+#[derive(Clone, Debug)]
+pub struct NtfsUpcaseInfo {
+    data: UpcaseInfoData,
+}
+
+impl NtfsUpcaseInfo {
+    /// Reads the $Info data stream of the $UpCase file, or returns `None` if the file has no such stream.
+    pub(crate) fn read<T>(ntfs: &Ntfs, fs: &mut T) -> Result<Option<Self>>
+    where
+        T: Read + Seek,
+    {
+        let upcase_file = ntfs.file(fs, KnownNtfsFileRecordNumber::UpCase as u64)?;
+        let mut iter = upcase_file.attributes();
+
+        while let Some(item) = iter.next(fs) {
+            let item = item?;
+            let attribute = item.to_attribute()?;
+
+            // This doesn't use `NtfsFile::data`, because looking up a named stream would require the $UpCase table
+            // to be read already.
+            if attribute.ty()? != NtfsAttributeType::Data || attribute.name()? != "$Info" {
+                continue;
+            }
+
+            if attribute.value_length() < UPCASE_INFO_SIZE {
+                return Err(NtfsError::InvalidStructuredValueSize {
+                    position: attribute.position(),
+                    ty: NtfsAttributeType::Data,
+                    expected: UPCASE_INFO_SIZE,
+                    actual: attribute.value_length(),
+                });
+            }
+
+            let mut value = attribute.value(fs)?;
+            let mut buf = [0u8; UPCASE_INFO_SIZE as usize];
+            value.read_exact(fs, &mut buf)?;
+
+            let mut cursor = Cursor::new(buf);
+            let data = cursor.read_le::<UpcaseInfoData>()?;
+
+            return Ok(Some(Self { data }));
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the CRC-64 checksum of the $UpCase table, as computed by [`UpcaseTable::crc64`].
+    pub fn crc64(&self) -> u64 {
+        self.data.crc64
+    }
+
+    /// Returns the length of the $Info structure in bytes, as stored in the structure itself.
+    pub fn length(&self) -> u32 {
+        self.data.length
+    }
+
+    /// Returns the build number of the Windows version that generated the $UpCase table.
+    pub fn os_build(&self) -> u32 {
+        self.data.os_build
+    }
+
+    /// Returns the major version of the Windows version that generated the $UpCase table.
+    pub fn os_major_version(&self) -> u32 {
+        self.data.os_major_version
+    }
+
+    /// Returns the minor version of the Windows version that generated the $UpCase table.
+    pub fn os_minor_version(&self) -> u32 {
+        self.data.os_minor_version
+    }
+
+    /// Returns the major version of the Service Pack of the Windows version that generated the $UpCase table.
+    pub fn service_pack_major_version(&self) -> u16 {
+        self.data.service_pack_major_version
+    }
+
+    /// Returns the minor version of the Service Pack of the Windows version that generated the $UpCase table.
+    pub fn service_pack_minor_version(&self) -> u16 {
+        self.data.service_pack_minor_version
+    }
+}
+
+/// Origin of an [`UpcaseTable`], returned by [`UpcaseTable::source`] and [`Ntfs::upcase_table_source`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NtfsUpcaseTableSource {
+    /// The entire table has been read from the $UpCase file of the filesystem.
+    Filesystem,
+    /// The $UpCase file of the filesystem is truncated.
+    /// Only the first `length` bytes of the table have been read from it,
+    /// the remaining characters are mapped as in [`UpcaseTable::default_windows`].
+    Truncated { length: u64 },
+    /// The table has been generated by [`UpcaseTable::default_windows`].
+    Generated,
+}
+
 /// Manages a table for converting characters to uppercase.
 /// This table is used for case-insensitive file name comparisons.
 ///
 /// NTFS stores such a table in the special $UpCase file on every filesystem.
 /// As this table is slightly different depending on the Windows version used for creating the filesystem,
-/// it is very important to always read the table from the filesystem itself.
-/// Hence, this table is not hardcoded into the crate.
+/// it is very important to always read the table from the filesystem itself via [`Ntfs::read_upcase_table`].
+///
+/// Only if the $UpCase file is missing or damaged, you may fall back to [`UpcaseTable::default_windows`]
+/// and install that table via [`Ntfs::set_upcase_table`].
 #[derive(Clone, Debug)]
-pub(crate) struct UpcaseTable {
+pub struct UpcaseTable {
     uppercase_characters: Vec<u16>,
+    source: NtfsUpcaseTableSource,
 }
 
 impl UpcaseTable {
+    /// Computes the CRC-64 checksum of this table, as stored in the $Info data stream of the $UpCase file
+    /// (see [`NtfsUpcaseInfo::crc64`]).
+    ///
+    /// The checksum is computed over the little-endian on-disk representation of the table.
+    pub fn crc64(&self) -> u64 {
+        let mut crc = u64::MAX;
+
+        for byte in self
+            .uppercase_characters
+            .iter()
+            .flat_map(|character| character.to_le_bytes())
+        {
+            crc ^= byte as u64;
+
+            for _ in 0..8 {
+                if crc & 1 != 0 {
+                    crc = (crc >> 1) ^ UPCASE_CRC64_POLYNOMIAL;
+                } else {
+                    crc >>= 1;
+                }
+            }
+        }
+
+        !crc
+    }
+
+    /// Generates an [`UpcaseTable`] resembling the one written by Windows when formatting a filesystem.
+    ///
+    /// The table is computed from the simple uppercase mappings of the Unicode Basic Multilingual Plane:
+    /// Every character with a single-character uppercase equivalent inside the BMP is mapped to it,
+    /// all other characters (including surrogates) are mapped to themselves.
+    /// This is the same approach that mkntfs takes.
+    ///
+    /// The mappings follow the Unicode version of the Rust standard library this crate is compiled with.
+    /// As the table stored by each Windows version differs slightly for characters added in newer Unicode versions,
+    /// prefer [`Ntfs::read_upcase_table`] whenever the $UpCase file of the filesystem is intact.
+    pub fn default_windows() -> Self {
+        let uppercase_characters = (0..UPCASE_CHARACTER_COUNT)
+            .map(default_uppercase_char)
+            .collect();
+
+        Self {
+            uppercase_characters,
+            source: NtfsUpcaseTableSource::Generated,
+        }
+    }
+
     /// Reads the $UpCase file from the given filesystem into a new [`UpcaseTable`] object.
     pub(crate) fn read<T>(ntfs: &Ntfs, fs: &mut T) -> Result<Self>
     where
@@ -49,103 +220,188 @@ impl UpcaseTable {
             })??;
 
         let data_attribute = data_item.to_attribute()?;
-        if data_attribute.value_length() != UPCASE_TABLE_SIZE {
+        let length = data_attribute.value_length();
+        if length > UPCASE_TABLE_SIZE {
             return Err(NtfsError::InvalidUpcaseTableSize {
                 expected: UPCASE_TABLE_SIZE,
-                actual: data_attribute.value_length(),
+                actual: length,
             });
         }
 
         // Read the entire raw data from the $DATA attribute.
         let mut data_value = data_attribute.value(fs)?;
-        let mut data = vec![0u8; UPCASE_TABLE_SIZE as usize];
+        let mut data = vec![0u8; length as usize];
         data_value.read_exact(fs, &mut data)?;
 
         // Store it in an array of `u16` uppercase characters.
         // Any endianness conversion is done here once, which makes `u16_to_uppercase` fast.
-        let uppercase_characters = data
+        let mut uppercase_characters = data
             .chunks_exact(2)
             .map(|two_bytes| u16::from_le_bytes(two_bytes.try_into().unwrap()))
-            .collect();
+            .collect::<Vec<u16>>();
+
+        // Generate the characters missing from a truncated table.
+        let source = if length == UPCASE_TABLE_SIZE {
+            NtfsUpcaseTableSource::Filesystem
+        } else {
+            NtfsUpcaseTableSource::Truncated { length }
+        };
+
+        let read_characters = uppercase_characters.len();
+        uppercase_characters
+            .extend((read_characters..UPCASE_CHARACTER_COUNT).map(default_uppercase_char));
 
         Ok(Self {
             uppercase_characters,
+            source,
         })
     }
 
+    /// Returns where this table comes from.
+    pub fn source(&self) -> NtfsUpcaseTableSource {
+        self.source
+    }
+
     /// Returns the uppercase variant of the given UCS-2 character (i.e. a Unicode character
     /// from the Basic Multilingual Plane) based on the stored conversion table.
     /// A character without an uppercase equivalent is returned as-is.
-    pub(crate) fn u16_to_uppercase(&self, character: u16) -> u16 {
+    pub fn uppercase_char(&self, character: u16) -> u16 {
         self.uppercase_characters[character as usize]
     }
+
+    /// Performs a case-insensitive ordering of two strings given as UTF-16 code units based on this table.
+    pub(crate) fn cmp_code_units<TI, OI>(&self, mut this_iter: TI, mut other_iter: OI) -> Ordering
+    where
+        TI: Iterator<Item = u16>,
+        OI: Iterator<Item = u16>,
+    {
+        loop {
+            match (this_iter.next(), other_iter.next()) {
+                (Some(this_code_unit), Some(other_code_unit)) => {
+                    // We have two UTF-16 code units to compare.
+                    let this_upper = self.uppercase_char(this_code_unit);
+                    let other_upper = self.uppercase_char(other_code_unit);
+
+                    if this_upper != other_upper {
+                        return this_upper.cmp(&other_upper);
+                    }
+                }
+                (Some(_), None) => {
+                    // `this_iter` is longer than `other_iter` but otherwise equal.
+                    return Ordering::Greater;
+                }
+                (None, Some(_)) => {
+                    // `other_iter` is longer than `this_iter` but otherwise equal.
+                    return Ordering::Less;
+                }
+                (None, None) => {
+                    // We made it to the end of both strings, so they must be equal.
+                    return Ordering::Equal;
+                }
+            }
+        }
+    }
+}
+
+/// Returns the uppercase variant of the `i`-th UCS-2 character, as generated by [`UpcaseTable::default_windows`].
+fn default_uppercase_char(i: usize) -> u16 {
+    let character = i as u16;
+    char::from_u32(character as u32)
+        .and_then(simple_uppercase)
+        .unwrap_or(character)
+}
+
+/// Returns the uppercase variant of `c` if it consists of a single character from the Basic Multilingual Plane.
+fn simple_uppercase(c: char) -> Option<u16> {
+    let mut uppercase = c.to_uppercase();
+    let upper = uppercase.next()?;
+
+    if uppercase.next().is_some() {
+        // Multi-character uppercase mappings (like "ß" -> "SS") cannot be represented in the table.
+        return None;
+    }
+
+    // Windows only maps characters that are the canonical lowercase form of their uppercase variant.
+    // This skips e.g. "µ" (MICRO SIGN), whose uppercase "Μ" lowercases to "μ" (GREEK SMALL LETTER MU),
+    // as well as titlecase characters like "ǅ".
+    let mut lowercase = upper.to_lowercase();
+    if lowercase.next() != Some(c) || lowercase.next().is_some() {
+        return None;
+    }
+
+    u16::try_from(upper as u32).ok()
 }
 
 /// Trait for a case-insensitive ordering with respect to the $UpCase table read from the filesystem.
 pub trait UpcaseOrd<Rhs> {
     /// Performs a case-insensitive ordering based on the $UpCase table read from the filesystem.
     ///
-    /// # Panics
-    ///
-    /// Panics if [`read_upcase_table`][Ntfs::read_upcase_table] had not been called on the passed [`Ntfs`] object.
-    fn upcase_cmp(&self, ntfs: &Ntfs, other: &Rhs) -> Ordering;
+    /// Returns [`NtfsError::UpcaseTableNotLoaded`] if [`read_upcase_table`][Ntfs::read_upcase_table] had not been
+    /// called on the passed [`Ntfs`] object.
+    fn upcase_cmp(&self, ntfs: &Ntfs, other: &Rhs) -> Result<Ordering>;
 }
 
 impl<'a, 'b> UpcaseOrd<U16StrLe<'a>> for U16StrLe<'b> {
-    fn upcase_cmp(&self, ntfs: &Ntfs, other: &U16StrLe<'a>) -> Ordering {
-        upcase_cmp_iter(self.u16_iter(), other.u16_iter(), ntfs)
+    fn upcase_cmp(&self, ntfs: &Ntfs, other: &U16StrLe<'a>) -> Result<Ordering> {
+        let upcase_table = ntfs.upcase_table_or_error()?;
+        Ok(upcase_table.cmp_code_units(self.u16_iter(), other.u16_iter()))
     }
 }
 
 impl<'a> UpcaseOrd<&str> for U16StrLe<'a> {
-    fn upcase_cmp(&self, ntfs: &Ntfs, other: &&str) -> Ordering {
-        upcase_cmp_iter(self.u16_iter(), other.encode_utf16(), ntfs)
+    fn upcase_cmp(&self, ntfs: &Ntfs, other: &&str) -> Result<Ordering> {
+        let upcase_table = ntfs.upcase_table_or_error()?;
+        Ok(upcase_table.cmp_code_units(self.u16_iter(), other.encode_utf16()))
     }
 }
 
 impl<'a> UpcaseOrd<U16StrLe<'a>> for &str {
-    fn upcase_cmp(&self, ntfs: &Ntfs, other: &U16StrLe<'a>) -> Ordering {
-        upcase_cmp_iter(self.encode_utf16(), other.u16_iter(), ntfs)
-    }
-}
-
-fn upcase_cmp_iter<TI, OI>(mut this_iter: TI, mut other_iter: OI, ntfs: &Ntfs) -> Ordering
-where
-    TI: Iterator<Item = u16>,
-    OI: Iterator<Item = u16>,
-{
-    let upcase_table = ntfs.upcase_table();
-
-    loop {
-        match (this_iter.next(), other_iter.next()) {
-            (Some(this_code_unit), Some(other_code_unit)) => {
-                // We have two UTF-16 code units to compare.
-                let this_upper = upcase_table.u16_to_uppercase(this_code_unit);
-                let other_upper = upcase_table.u16_to_uppercase(other_code_unit);
-
-                if this_upper != other_upper {
-                    return this_upper.cmp(&other_upper);
-                }
-            }
-            (Some(_), None) => {
-                // `this_iter` is longer than `other_iter` but otherwise equal.
-                return Ordering::Greater;
-            }
-            (None, Some(_)) => {
-                // `other_iter` is longer than `this_iter` but otherwise equal.
-                return Ordering::Less;
-            }
-            (None, None) => {
-                // We made it to the end of both strings, so they must be equal.
-                return Ordering::Equal;
-            }
-        }
+    fn upcase_cmp(&self, ntfs: &Ntfs, other: &U16StrLe<'a>) -> Result<Ordering> {
+        let upcase_table = ntfs.upcase_table_or_error()?;
+        Ok(upcase_table.cmp_code_units(self.encode_utf16(), other.u16_iter()))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::helpers::tests::patch_file_record_number;
+    use crate::indexes::{NtfsCaseSensitivity, NtfsFileNameIndex};
+
+    /// Sets the data size and initialized size of the unnamed $DATA attribute of the $UpCase file in testfs1.
+    fn testfs1_with_upcase_length(length: u64) -> std::io::Cursor<Vec<u8>> {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let attribute_offset = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let upcase_file = ntfs
+                .file(&mut testfs1, KnownNtfsFileRecordNumber::UpCase as u64)
+                .unwrap();
+            let attribute = upcase_file
+                .attributes_raw()
+                .map(Result::unwrap)
+                .find(|attribute| {
+                    attribute.ty().unwrap() == NtfsAttributeType::Data
+                        && attribute.name_length() == 0
+                })
+                .unwrap();
+            assert!(!attribute.is_resident());
+
+            attribute.offset()
+        };
+
+        patch_file_record_number(
+            &mut testfs1,
+            KnownNtfsFileRecordNumber::UpCase as u64,
+            |record| {
+                // Data size and initialized size of the non-resident attribute header.
+                let attribute = &mut record[attribute_offset..];
+                attribute[48..56].copy_from_slice(&length.to_le_bytes());
+                attribute[56..64].copy_from_slice(&length.to_le_bytes());
+            },
+        );
+
+        testfs1
+    }
 
     #[test]
     fn test_upcase_table() {
@@ -157,9 +413,139 @@ mod tests {
         // It makes no sense to check everything here.
         for (lowercase, uppercase) in (b'a'..=b'z').zip(b'A'..=b'Z') {
             assert_eq!(
-                upcase_table.u16_to_uppercase(lowercase as u16),
+                upcase_table.uppercase_char(lowercase as u16),
                 uppercase as u16
             );
         }
     }
+
+    #[test]
+    fn test_upcase_info() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // testfs1 has been created by mkntfs, which doesn't record any Windows version.
+        let upcase_info = ntfs.upcase_info(&mut testfs1).unwrap().unwrap();
+        assert_eq!(upcase_info.length(), 32);
+        assert_eq!(upcase_info.crc64(), 0xdadc_7e77_6b1b_690c);
+        assert_eq!(upcase_info.os_major_version(), 0);
+        assert_eq!(upcase_info.os_minor_version(), 0);
+
+        // The checksum must match the table we read.
+        let upcase_table = ntfs.upcase_table().unwrap();
+        assert_eq!(upcase_table.crc64(), upcase_info.crc64());
+    }
+
+    #[test]
+    fn test_default_windows() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let upcase_table = UpcaseTable::read(&ntfs, &mut testfs1).unwrap();
+        let default_table = UpcaseTable::default_windows();
+
+        // The generated table must match the real one for ASCII and Latin-1.
+        for character in 0..=0xffu16 {
+            assert_eq!(
+                default_table.uppercase_char(character),
+                upcase_table.uppercase_char(character),
+                "Mismatch for character {character:#06x}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_set_upcase_table() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        assert!(ntfs.upcase_table().is_none());
+
+        // Finding files works without reading $UpCase once the default table is installed.
+        ntfs.set_upcase_table(UpcaseTable::default_windows());
+        assert!(ntfs.upcase_table().is_some());
+
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut finder = root_dir_index.finder();
+        let entry = NtfsFileNameIndex::find(&mut finder, &ntfs, &mut testfs1, "MANY_SUBDIRS")
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.file_reference().file_record_number(), 68);
+    }
+
+    #[test]
+    fn test_truncated_upcase_table() {
+        let mut testfs1 = testfs1_with_upcase_length(1000);
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        assert_eq!(
+            ntfs.upcase_table_source(),
+            Some(NtfsUpcaseTableSource::Truncated { length: 1000 })
+        );
+
+        // The first 500 characters come from the filesystem, the remaining ones are generated.
+        let upcase_table = ntfs.upcase_table().unwrap();
+        assert_eq!(upcase_table.uppercase_char(b'a' as u16), b'A' as u16);
+        assert_eq!(upcase_table.uppercase_char(0x3b1), 0x391);
+
+        // Case-insensitive lookups of ASCII names still work.
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut finder = root_dir_index.finder();
+        let entry = NtfsFileNameIndex::find(&mut finder, &ntfs, &mut testfs1, "MANY_SUBDIRS")
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.file_reference().file_record_number(), 68);
+    }
+
+    #[test]
+    fn test_oversized_upcase_table() {
+        let mut testfs1 = testfs1_with_upcase_length(UPCASE_TABLE_SIZE + 2);
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        let error = ntfs.read_upcase_table(&mut testfs1).unwrap_err();
+        assert!(matches!(
+            error,
+            NtfsError::InvalidUpcaseTableSize {
+                expected: UPCASE_TABLE_SIZE,
+                actual: 131074,
+            }
+        ));
+        assert!(error.to_string().contains("131074 bytes"));
+        assert!(ntfs.upcase_table_source().is_none());
+    }
+
+    #[test]
+    fn test_upcase_table_not_loaded() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut finder = root_dir_index.finder();
+        assert!(matches!(
+            NtfsFileNameIndex::find(&mut finder, &ntfs, &mut testfs1, "many_subdirs"),
+            Some(Err(NtfsError::UpcaseTableNotLoaded))
+        ));
+
+        assert!(matches!(
+            root_dir.attribute_by_ty_and_name(
+                &mut testfs1,
+                NtfsAttributeType::IndexRoot,
+                Some("$I30"),
+                NtfsCaseSensitivity::Insensitive
+            ),
+            Err(NtfsError::UpcaseTableNotLoaded)
+        ));
+
+        // Looking up names case-sensitively or without a name doesn't need the table.
+        assert!(root_dir
+            .attribute_by_ty_and_name(
+                &mut testfs1,
+                NtfsAttributeType::IndexRoot,
+                Some("$I30"),
+                NtfsCaseSensitivity::Sensitive
+            )
+            .is_ok());
+    }
 }
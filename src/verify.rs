@@ -0,0 +1,657 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::attribute::{NtfsAttribute, NtfsAttributeType};
+use crate::attribute_value::NtfsAttributeValue;
+use crate::error::{NtfsError, NtfsErrorKind, Result};
+use crate::file::{KnownNtfsFileRecordNumber, NtfsFile, NtfsFileFlags};
+use crate::file_reference::NtfsFileReference;
+use crate::io::{Read, Seek};
+use crate::ntfs::Ntfs;
+use crate::structured_values::{NtfsAttributeList, NtfsAttributeListEntry, NtfsFileName};
+use crate::types::NtfsPosition;
+
+/// Options for [`Ntfs::verify`].
+///
+/// By default, all checks are enabled.
+#[derive(Clone, Debug)]
+pub struct NtfsVerifyOptions {
+    check_data_runs: bool,
+    check_parent_directories: bool,
+}
+
+impl NtfsVerifyOptions {
+    /// Creates options with all checks enabled.
+    pub fn new() -> Self {
+        Self {
+            check_data_runs: true,
+            check_parent_directories: true,
+        }
+    }
+
+    /// Returns whether the Data Runs of non-resident attributes are checked to lie within the volume.
+    pub fn check_data_runs(&self) -> bool {
+        self.check_data_runs
+    }
+
+    /// Returns whether the parent directory references of $FILE_NAME attributes are checked.
+    pub fn check_parent_directories(&self) -> bool {
+        self.check_parent_directories
+    }
+
+    /// Sets whether the Data Runs of non-resident attributes are checked to lie within the volume.
+    pub fn set_check_data_runs(&mut self, check_data_runs: bool) {
+        self.check_data_runs = check_data_runs;
+    }
+
+    /// Sets whether the parent directory references of $FILE_NAME attributes are checked.
+    ///
+    /// This check reads the File Record of every referenced parent directory (once per directory).
+    pub fn set_check_parent_directories(&mut self, check_parent_directories: bool) {
+        self.check_parent_directories = check_parent_directories;
+    }
+}
+
+impl Default for NtfsVerifyOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Severity of an [`NtfsVerificationFinding`].
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum NtfsFindingSeverity {
+    /// The inconsistency does not prevent reading the affected file, but a filesystem check would repair it.
+    Warning,
+    /// The affected structure cannot be read (entirely).
+    Error,
+}
+
+/// An inconsistency found by [`Ntfs::verify`].
+///
+/// The kind of inconsistency is described by the [`NtfsError`] that would be returned when accessing the
+/// affected structure.
+#[derive(Debug)]
+pub struct NtfsVerificationFinding {
+    file_record_number: u64,
+    position: NtfsPosition,
+    severity: NtfsFindingSeverity,
+    error: NtfsError,
+}
+
+impl NtfsVerificationFinding {
+    /// Returns the error describing this inconsistency.
+    pub fn error(&self) -> &NtfsError {
+        &self.error
+    }
+
+    /// Returns the File Record Number of the File Record containing the inconsistency.
+    pub fn file_record_number(&self) -> u64 {
+        self.file_record_number
+    }
+
+    /// Returns the category of this inconsistency (see [`NtfsError::kind`]).
+    pub fn kind(&self) -> NtfsErrorKind {
+        self.error.kind()
+    }
+
+    /// Returns the absolute position of the inconsistent structure within the filesystem, in bytes.
+    ///
+    /// This is the position reported by the error, or the position of the File Record if the error carries none.
+    pub fn position(&self) -> NtfsPosition {
+        self.position
+    }
+
+    /// Returns the severity of this inconsistency.
+    pub fn severity(&self) -> NtfsFindingSeverity {
+        self.severity
+    }
+}
+
+/// Result of [`Ntfs::verify`].
+#[derive(Debug)]
+pub struct NtfsVerificationReport {
+    checked_record_count: u64,
+    findings: Vec<NtfsVerificationFinding>,
+}
+
+impl NtfsVerificationReport {
+    pub(crate) fn verify<T>(ntfs: &Ntfs, fs: &mut T, options: &NtfsVerifyOptions) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let mft = ntfs.file(fs, KnownNtfsFileRecordNumber::MFT as u64)?;
+        let mft_data_length = mft
+            .find_resident_attribute(NtfsAttributeType::Data, None, None)?
+            .value_length();
+        let file_record_count = mft_data_length / ntfs.file_record_size() as u64;
+
+        let mut verifier = Verifier {
+            ntfs,
+            mft,
+            options,
+            parent_directories: BTreeMap::new(),
+            report: Self {
+                checked_record_count: 0,
+                findings: Vec::new(),
+            },
+        };
+
+        for file_record_number in 0..file_record_count {
+            verifier.verify_file_record(fs, file_record_number)?;
+        }
+
+        Ok(verifier.report)
+    }
+
+    /// Returns the number of in-use File Records that have been checked.
+    pub fn checked_record_count(&self) -> u64 {
+        self.checked_record_count
+    }
+
+    /// Returns all inconsistencies found, in the order of their File Record Numbers.
+    pub fn findings(&self) -> &[NtfsVerificationFinding] {
+        &self.findings
+    }
+
+    /// Returns `true` if no inconsistencies have been found.
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+struct Verifier<'n, 'o> {
+    ntfs: &'n Ntfs,
+    mft: NtfsFile<'n>,
+    options: &'o NtfsVerifyOptions,
+    /// Cache of the parent directories checked so far, mapping their File Record Number to the
+    /// Sequence Number if they are in-use directories.
+    parent_directories: BTreeMap<u64, Option<u16>>,
+    report: NtfsVerificationReport,
+}
+
+impl<'n, 'o> Verifier<'n, 'o> {
+    /// Records a finding for a failed check and returns `None`.
+    ///
+    /// I/O errors are no inconsistencies of the filesystem and abort the verification instead.
+    fn check<V>(
+        &mut self,
+        file_record_number: u64,
+        position: NtfsPosition,
+        severity: NtfsFindingSeverity,
+        result: Result<V>,
+    ) -> Result<Option<V>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if matches!(e.root_cause(), NtfsError::Io(_)) => Err(e),
+            Err(error) => {
+                let position = error.position().unwrap_or(position);
+                self.report.findings.push(NtfsVerificationFinding {
+                    file_record_number,
+                    position,
+                    severity,
+                    error,
+                });
+                Ok(None)
+            }
+        }
+    }
+
+    fn is_in_use_directory<T>(&mut self, fs: &mut T, reference: NtfsFileReference) -> Result<bool>
+    where
+        T: Read + Seek,
+    {
+        let file_record_number = reference.file_record_number();
+
+        let sequence_number = match self.parent_directories.get(&file_record_number) {
+            Some(sequence_number) => *sequence_number,
+            None => {
+                let sequence_number =
+                    match self
+                        .ntfs
+                        .read_file_record_data(fs, &self.mft, file_record_number)
+                    {
+                        Ok((data, position)) => {
+                            let flags = NtfsFile::flags_from_data(&data);
+                            if flags.contains(NtfsFileFlags::IN_USE | NtfsFileFlags::IS_DIRECTORY) {
+                                NtfsFile::from_data(self.ntfs, data, position, file_record_number)
+                                    .ok()
+                                    .map(|file| file.sequence_number())
+                            } else {
+                                None
+                            }
+                        }
+                        Err(e) if matches!(e.root_cause(), NtfsError::Io(_)) => return Err(e),
+                        Err(_) => None,
+                    };
+
+                self.parent_directories
+                    .insert(file_record_number, sequence_number);
+                sequence_number
+            }
+        };
+
+        Ok(sequence_number == Some(reference.sequence_number()))
+    }
+
+    fn verify_attribute<T>(
+        &mut self,
+        fs: &mut T,
+        file_record_number: u64,
+        attribute: &NtfsAttribute,
+    ) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        let position = attribute.position();
+
+        let length = attribute.attribute_length();
+        if length % 8 != 0 {
+            self.check::<()>(
+                file_record_number,
+                position,
+                NtfsFindingSeverity::Error,
+                Err(NtfsError::UnalignedAttributeLength { position, length }),
+            )?;
+        }
+
+        self.check(
+            file_record_number,
+            position,
+            NtfsFindingSeverity::Error,
+            attribute.name(),
+        )?;
+
+        let ty = match self.check(
+            file_record_number,
+            position,
+            NtfsFindingSeverity::Error,
+            attribute.ty(),
+        )? {
+            Some(ty) => ty,
+            None => return Ok(()),
+        };
+
+        // This also checks the bounds of resident values.
+        let value = match self.check(
+            file_record_number,
+            position,
+            NtfsFindingSeverity::Error,
+            attribute.value(fs),
+        )? {
+            Some(value) => value,
+            None => return Ok(()),
+        };
+
+        if self.options.check_data_runs {
+            if let NtfsAttributeValue::NonResident(value) = &value {
+                let mut data_runs = value.data_runs();
+
+                loop {
+                    let data_run_position = data_runs.position();
+                    let data_run = match data_runs.next() {
+                        Some(data_run) => data_run,
+                        None => break,
+                    };
+                    let data_run = match self.check(
+                        file_record_number,
+                        data_run_position,
+                        NtfsFindingSeverity::Error,
+                        data_run,
+                    )? {
+                        Some(data_run) => data_run,
+                        None => break,
+                    };
+
+                    // The Data Runs iterator guarantees that this addition cannot overflow.
+                    // Data Run positions include the partition offset, but the volume size doesn't.
+                    let start = match data_run.data_position().value() {
+                        Some(start) => start.get() - self.ntfs.partition_offset(),
+                        None => continue,
+                    };
+                    let end = start + data_run.allocated_size();
+
+                    if end > self.ntfs.size() {
+                        self.check::<()>(
+                            file_record_number,
+                            data_run_position,
+                            NtfsFindingSeverity::Error,
+                            Err(NtfsError::DataRunBeyondVolume {
+                                position: data_run_position,
+                                end,
+                                volume_size: self.ntfs.size(),
+                            }),
+                        )?;
+                    }
+                }
+            }
+        }
+
+        match ty {
+            NtfsAttributeType::AttributeList => {
+                self.verify_attribute_list(fs, file_record_number, attribute)
+            }
+            NtfsAttributeType::FileName if self.options.check_parent_directories => {
+                self.verify_parent_directory(fs, file_record_number, attribute)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn verify_attribute_list<T>(
+        &mut self,
+        fs: &mut T,
+        file_record_number: u64,
+        attribute: &NtfsAttribute,
+    ) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        let position = attribute.position();
+
+        let attribute_list = match self.check(
+            file_record_number,
+            position,
+            NtfsFindingSeverity::Error,
+            attribute.structured_value::<_, NtfsAttributeList>(fs),
+        )? {
+            Some(attribute_list) => attribute_list,
+            None => return Ok(()),
+        };
+
+        let mut entries = attribute_list.entries();
+        let mut previous_entry: Option<NtfsAttributeListEntry> = None;
+
+        while let Some(entry) = entries.next(fs) {
+            let entry = match self.check(
+                file_record_number,
+                position,
+                NtfsFindingSeverity::Error,
+                entry,
+            )? {
+                Some(entry) => entry,
+                None => break,
+            };
+
+            // Every entry with a nonzero lowest VCN continues the attribute of the previous entry.
+            // Its lowest VCN must be greater than that of the previous fragment.
+            if let Some(previous_entry) = &previous_entry {
+                if entry.lowest_vcn().value() != 0
+                    && entry.has_same_ty_and_name(previous_entry)
+                    && entry.lowest_vcn() <= previous_entry.lowest_vcn()
+                {
+                    self.check::<()>(
+                        file_record_number,
+                        entry.position(),
+                        NtfsFindingSeverity::Error,
+                        Err(NtfsError::InvalidAttributeListEntryOrder {
+                            position: entry.position(),
+                            lowest_vcn: entry.lowest_vcn(),
+                            previous_lowest_vcn: previous_entry.lowest_vcn(),
+                        }),
+                    )?;
+                }
+            }
+
+            previous_entry = Some(entry);
+        }
+
+        Ok(())
+    }
+
+    fn verify_file_record<T>(&mut self, fs: &mut T, file_record_number: u64) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        let result = self
+            .ntfs
+            .read_file_record_data(fs, &self.mft, file_record_number);
+        let (data, position) = match self.check(
+            file_record_number,
+            NtfsPosition::none(),
+            NtfsFindingSeverity::Error,
+            result,
+        )? {
+            Some(record) => record,
+            None => return Ok(()),
+        };
+
+        // Records that are not in use may contain any garbage.
+        if !NtfsFile::flags_from_data(&data).contains(NtfsFileFlags::IN_USE) {
+            return Ok(());
+        }
+
+        self.report.checked_record_count += 1;
+
+        // This checks the signature, fixups, and sizes of the File Record.
+        let file = match self.check(
+            file_record_number,
+            position,
+            NtfsFindingSeverity::Error,
+            NtfsFile::from_data(self.ntfs, data, position, file_record_number),
+        )? {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+
+        for attribute in file.attributes_raw() {
+            // The raw attributes iterator cannot continue after an error.
+            let attribute = match self.check(
+                file_record_number,
+                file.position(),
+                NtfsFindingSeverity::Error,
+                attribute,
+            )? {
+                Some(attribute) => attribute,
+                None => break,
+            };
+
+            self.verify_attribute(fs, file_record_number, &attribute)?;
+        }
+
+        Ok(())
+    }
+
+    fn verify_parent_directory<T>(
+        &mut self,
+        fs: &mut T,
+        file_record_number: u64,
+        attribute: &NtfsAttribute,
+    ) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        let position = attribute.position();
+
+        let file_name = match self.check(
+            file_record_number,
+            position,
+            NtfsFindingSeverity::Error,
+            attribute.structured_value::<_, NtfsFileName>(fs),
+        )? {
+            Some(file_name) => file_name,
+            None => return Ok(()),
+        };
+
+        let parent_directory_reference = file_name.parent_directory_reference();
+        if !self.is_in_use_directory(fs, parent_directory_reference)? {
+            self.check::<()>(
+                file_record_number,
+                position,
+                NtfsFindingSeverity::Warning,
+                Err(NtfsError::InvalidParentDirectoryReference {
+                    position,
+                    parent_file_record_number: parent_directory_reference.file_record_number(),
+                }),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::tests::patch_file_record;
+    use crate::indexes::NtfsFileNameIndex;
+    use std::io::Cursor;
+
+    /// Returns the File Record Number, File Record position, and offset of the given attribute within the
+    /// File Record of the "1000-bytes-file" of testfs1.
+    fn locate_attribute(
+        testfs1: &mut Cursor<Vec<u8>>,
+        ty: NtfsAttributeType,
+    ) -> (u64, usize, usize) {
+        let mut ntfs = Ntfs::new(testfs1).unwrap();
+        ntfs.read_upcase_table(testfs1).unwrap();
+        let root_dir = ntfs.root_directory(testfs1).unwrap();
+
+        let root_dir_index = root_dir.directory_index(testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, testfs1, "1000-bytes-file")
+                .unwrap()
+                .unwrap();
+        let file = entry.to_file(&ntfs, testfs1).unwrap();
+        let file_position = file.position().value().unwrap().get();
+
+        let attribute = file
+            .attributes_raw()
+            .map(Result::unwrap)
+            .find(|attribute| attribute.ty().unwrap() == ty)
+            .unwrap();
+        let attribute_position = attribute.position().value().unwrap().get();
+
+        (
+            file.file_record_number(),
+            file_position as usize,
+            (attribute_position - file_position) as usize,
+        )
+    }
+
+    fn verify(testfs1: &mut Cursor<Vec<u8>>) -> NtfsVerificationReport {
+        let ntfs = Ntfs::new(testfs1).unwrap();
+        ntfs.verify(testfs1, &NtfsVerifyOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_verify() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let report = verify(&mut testfs1);
+
+        assert!(report.is_clean(), "{:?}", report.findings());
+        assert!(report.checked_record_count() > 512);
+    }
+
+    #[test]
+    fn test_verify_data_run_beyond_volume() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let (file_record_number, file_position, attribute_offset) =
+            locate_attribute(&mut testfs1, NtfsAttributeType::Data);
+        let mut data_run_offset = 0;
+
+        // Let the first Data Run start at the highest LCN that fits into its LCN field.
+        patch_file_record(&mut testfs1, file_position, 1024, |record| {
+            let attribute = &mut record[attribute_offset..];
+            data_run_offset = u16::from_le_bytes([attribute[0x20], attribute[0x21]]) as usize;
+
+            let data_run = &mut attribute[data_run_offset..];
+            let cluster_count_byte_count = (data_run[0] & 0x0f) as usize;
+            let lcn_byte_count = (data_run[0] >> 4) as usize;
+            assert!(lcn_byte_count >= 2);
+
+            let lcn_start = 1 + cluster_count_byte_count;
+            let lcn_end = lcn_start + lcn_byte_count;
+            data_run[lcn_start..lcn_end - 1].fill(0xff);
+            data_run[lcn_end - 1] = 0x7f;
+        });
+
+        let report = verify(&mut testfs1);
+        let findings = report.findings();
+        assert_eq!(findings.len(), 1);
+
+        let finding = &findings[0];
+        assert_eq!(finding.file_record_number(), file_record_number);
+        assert_eq!(finding.severity(), NtfsFindingSeverity::Error);
+        assert_eq!(finding.kind(), NtfsErrorKind::CorruptAttribute);
+        assert_eq!(
+            finding.position(),
+            NtfsPosition::new((file_position + attribute_offset + data_run_offset) as u64)
+        );
+        let volume_size = Ntfs::new(&mut testfs1).unwrap().size();
+        assert!(matches!(
+            finding.error(),
+            NtfsError::DataRunBeyondVolume {
+                volume_size: size,
+                ..
+            } if *size == volume_size
+        ));
+
+        // The check can be disabled.
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let mut options = NtfsVerifyOptions::new();
+        options.set_check_data_runs(false);
+        let report = ntfs.verify(&mut testfs1, &options).unwrap();
+        assert!(report.is_clean());
+    }
+
+    #[test]
+    fn test_verify_fixup() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let (file_record_number, file_position, _) =
+            locate_attribute(&mut testfs1, NtfsAttributeType::Data);
+
+        // Overwrite the Update Sequence Number at the end of the first sector.
+        testfs1.get_mut()[file_position + 510] ^= 0xff;
+
+        let report = verify(&mut testfs1);
+        let findings = report.findings();
+        assert_eq!(findings.len(), 1);
+
+        let finding = &findings[0];
+        assert_eq!(finding.file_record_number(), file_record_number);
+        assert_eq!(finding.severity(), NtfsFindingSeverity::Error);
+        assert_eq!(finding.kind(), NtfsErrorKind::CorruptRecord);
+        assert!(matches!(
+            finding.error(),
+            NtfsError::UpdateSequenceNumberMismatch { .. }
+        ));
+    }
+
+    #[test]
+    fn test_verify_parent_directory() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let (file_record_number, file_position, attribute_offset) =
+            locate_attribute(&mut testfs1, NtfsAttributeType::FileName);
+
+        // Let the $FILE_NAME attribute reference the $MFT file (which is no directory) as its parent.
+        patch_file_record(&mut testfs1, file_position, 1024, |record| {
+            let attribute = &mut record[attribute_offset..];
+            let value_offset = u16::from_le_bytes([attribute[0x14], attribute[0x15]]) as usize;
+            attribute[value_offset..value_offset + 8].copy_from_slice(&[0, 0, 0, 0, 0, 0, 1, 0]);
+        });
+
+        let report = verify(&mut testfs1);
+        let findings = report.findings();
+        assert_eq!(findings.len(), 1);
+
+        let finding = &findings[0];
+        assert_eq!(finding.file_record_number(), file_record_number);
+        assert_eq!(finding.severity(), NtfsFindingSeverity::Warning);
+        assert_eq!(
+            finding.position(),
+            NtfsPosition::new((file_position + attribute_offset) as u64)
+        );
+        assert!(matches!(
+            finding.error(),
+            NtfsError::InvalidParentDirectoryReference {
+                parent_file_record_number: 0,
+                ..
+            }
+        ));
+    }
+}
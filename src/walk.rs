@@ -0,0 +1,682 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+//! Traversal of a directory tree.
+//!
+//! [`NtfsTreeWalker`] visits all files and directories below a directory in depth-first order,
+//! using an explicit stack instead of recursion.
+//! Contrary to an MFT scan (see the [`scan`](crate::scan) module), it provides the path structure of the tree.
+//!
+//! [`subtree_sizes`] builds on it to sum up the sizes of all files below a directory.
+
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::io::{Read, Seek};
+
+use crate::error::{NtfsError, Result};
+use crate::file::NtfsFile;
+use crate::file_reference::NtfsFileReference;
+use crate::index::NtfsIndexBitmapPolicy;
+use crate::indexes::NtfsFileNameFilter;
+use crate::ntfs::Ntfs;
+use crate::structured_values::{NtfsFileAttributeFlags, NtfsFileName};
+use crate::types::NtfsPosition;
+
+/// Default for [`NtfsTreeWalker::set_max_depth`].
+///
+/// NTFS paths are limited to 32767 UTF-16 code points, so a path of single-character directory names
+/// has a depth of at most 16384.
+pub const DEFAULT_MAX_DEPTH: usize = 16384;
+
+/// A file or directory visited by an [`NtfsTreeWalker`].
+#[derive(Clone, Debug)]
+pub struct NtfsTreeEntry {
+    depth: usize,
+    file_name: NtfsFileName,
+    file_reference: NtfsFileReference,
+    path: String,
+}
+
+impl NtfsTreeEntry {
+    /// Returns the depth of this entry relative to the root directory of the walk.
+    ///
+    /// Entries of the root directory have a depth of 1.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the [`NtfsFileName`] of this entry, as stored in the index of its parent directory.
+    pub fn file_name(&self) -> &NtfsFileName {
+        &self.file_name
+    }
+
+    /// Returns an [`NtfsFileReference`] to the File Record of this entry.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        self.file_reference
+    }
+
+    /// Returns whether this entry is a directory.
+    pub fn is_directory(&self) -> bool {
+        self.file_name.is_directory()
+    }
+
+    /// Returns the path of this entry relative to the root directory of the walk, with components separated
+    /// by backslashes (e.g. `dir\subdir\file.txt`).
+    ///
+    /// Any unpaired UTF-16 surrogates in the file names are replaced by U+FFFD REPLACEMENT CHARACTER.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Reads the File Record of this entry and returns it.
+    pub fn to_file<'n, T>(&self, ntfs: &'n Ntfs, fs: &mut T) -> Result<NtfsFile<'n>>
+    where
+        T: Read + Seek,
+    {
+        self.file_reference.to_file(ntfs, fs)
+    }
+}
+
+/// A directory whose entries are currently being visited.
+#[derive(Debug)]
+struct DirectoryState {
+    file_record_number: u64,
+    /// Remaining entries of this directory in reverse order.
+    entries: Vec<Result<NtfsTreeEntry>>,
+}
+
+/// A directory that has been returned by [`NtfsTreeWalker::next`] and is entered by the next call.
+#[derive(Debug)]
+struct PendingDirectory {
+    file_record_number: u64,
+    depth: usize,
+    path: String,
+}
+
+/// Iterator over
+///   all files and directories below a directory,
+///   returning an [`NtfsTreeEntry`] for each of them.
+///
+/// Entries are returned in depth-first order: Each directory is followed by its contents before
+/// its next sibling is returned.
+/// The entries of a single directory are returned in the order of its index.
+/// Each file is returned once per directory, DOS names are skipped (see [`NtfsFileNameFilter::SkipDosNames`]).
+/// The root directory itself is not returned.
+///
+/// The walker does not follow reparse points (like junctions or directory symbolic links),
+/// never enters a directory that is already being walked, and stops descending at
+/// the maximum depth (see [`NtfsTreeWalker::set_max_depth`]).
+/// This prevents endless loops even on corrupted filesystems.
+///
+/// Errors reading a directory or one of its entries are returned as separate items, and the walk
+/// continues with the next entry.
+/// This includes subnodes of a directory index that are not marked as in use, unless another
+/// [`NtfsIndexBitmapPolicy`] is set via [`NtfsTreeWalker::set_bitmap_policy`].
+#[derive(Debug)]
+pub struct NtfsTreeWalker<'n> {
+    ntfs: &'n Ntfs,
+    bitmap_policy: NtfsIndexBitmapPolicy,
+    max_depth: usize,
+    /// Root directory, until it has been entered by the first call to [`NtfsTreeWalker::next`].
+    root: Option<NtfsFile<'n>>,
+    /// Entry of the directory to be entered by the next call to [`NtfsTreeWalker::next`].
+    pending_directory: Option<PendingDirectory>,
+    stack: Vec<DirectoryState>,
+}
+
+impl<'n> NtfsTreeWalker<'n> {
+    /// Creates a walker for all files and directories below the given directory.
+    pub fn new(root: NtfsFile<'n>) -> Self {
+        Self {
+            ntfs: root.ntfs(),
+            bitmap_policy: NtfsIndexBitmapPolicy::default(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            root: Some(root),
+            pending_directory: None,
+            stack: Vec::new(),
+        }
+    }
+
+    fn enter_directory<T>(
+        &mut self,
+        fs: &mut T,
+        directory: NtfsFile,
+        depth: usize,
+        path: &str,
+    ) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        let file_record_number = directory.file_record_number();
+        let index = directory.directory_index(fs)?;
+        let mut iter = index.entries_deduplicated(NtfsFileNameFilter::SkipDosNames);
+        iter.set_bitmap_policy(self.bitmap_policy);
+
+        let mut entries = Vec::new();
+        while let Some(entry) = iter.next(fs) {
+            let entry = entry.and_then(|entry| {
+                // Only the last entry of an index node has no key, and the iterator never returns that one.
+                // A corrupted entry without a key may still show up though.
+                let file_name = entry.key().ok_or(NtfsError::ParseError {
+                    position: Some(entry.position()),
+                    reason: "index entry has no key",
+                    source: None,
+                })??;
+
+                let mut entry_path = String::from(path);
+                if !entry_path.is_empty() {
+                    entry_path.push('\\');
+                }
+                entry_path.push_str(&file_name.name().to_string_lossy());
+
+                Ok(NtfsTreeEntry {
+                    depth: depth + 1,
+                    file_name,
+                    file_reference: entry.file_reference(),
+                    path: entry_path,
+                })
+            });
+
+            // The "." entry of the root directory references the directory itself.
+            if matches!(&entry, Ok(entry) if entry.file_reference.file_record_number() == file_record_number)
+            {
+                continue;
+            }
+
+            // The index iterator cannot continue after an error, so this is the last entry.
+            let is_err = entry.is_err();
+            entries.push(entry);
+            if is_err {
+                break;
+            }
+        }
+
+        entries.reverse();
+        self.stack.push(DirectoryState {
+            file_record_number,
+            entries,
+        });
+
+        Ok(())
+    }
+
+    /// Returns the [`NtfsIndexBitmapPolicy`] set via [`NtfsTreeWalker::set_bitmap_policy`].
+    pub fn bitmap_policy(&self) -> NtfsIndexBitmapPolicy {
+        self.bitmap_policy
+    }
+
+    /// Returns the maximum depth of returned entries.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// See [`Iterator::next`].
+    pub fn next<T>(&mut self, fs: &mut T) -> Option<Result<NtfsTreeEntry>>
+    where
+        T: Read + Seek,
+    {
+        if let Some(root) = self.root.take() {
+            iter_try!(self.enter_directory(fs, root, 0, ""));
+        } else if let Some(pending) = self.pending_directory.take() {
+            let directory = iter_try!(self.ntfs.file(fs, pending.file_record_number));
+            iter_try!(self.enter_directory(fs, directory, pending.depth, &pending.path));
+        }
+
+        loop {
+            let directory = self.stack.last_mut()?;
+
+            let entry = match directory.entries.pop() {
+                Some(entry) => entry,
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+            let entry = iter_try!(entry);
+
+            if self.should_descend(&entry) {
+                self.pending_directory = Some(PendingDirectory {
+                    file_record_number: entry.file_reference.file_record_number(),
+                    depth: entry.depth,
+                    path: entry.path.clone(),
+                });
+            }
+
+            return Some(Ok(entry));
+        }
+    }
+
+    /// Sets the [`NtfsIndexBitmapPolicy`] for subnodes of directory indexes whose Index Record is not marked
+    /// as in use.
+    ///
+    /// It applies to all directories entered afterwards.
+    pub fn set_bitmap_policy(&mut self, bitmap_policy: NtfsIndexBitmapPolicy) {
+        self.bitmap_policy = bitmap_policy;
+    }
+
+    /// Sets the maximum depth of returned entries.
+    ///
+    /// Directories at this depth are still returned, but not entered.
+    /// A maximum depth of 1 only returns the entries of the root directory.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    fn should_descend(&self, entry: &NtfsTreeEntry) -> bool {
+        if !entry.is_directory() || entry.depth >= self.max_depth {
+            return false;
+        }
+
+        // Reparse points may point anywhere, including to a parent directory.
+        if entry
+            .file_name
+            .file_attributes()
+            .contains(NtfsFileAttributeFlags::REPARSE_POINT)
+        {
+            return false;
+        }
+
+        // A corrupted filesystem may reference a directory that is already being walked.
+        let file_record_number = entry.file_reference.file_record_number();
+        !self
+            .stack
+            .iter()
+            .any(|directory| directory.file_record_number == file_record_number)
+    }
+
+    /// Skips the contents of the directory that has been returned by the last call to [`NtfsTreeWalker::next`].
+    ///
+    /// This has no effect if the last returned entry was not a directory.
+    pub fn skip_subtree(&mut self) {
+        self.pending_directory = None;
+    }
+}
+
+/// Aggregated sizes of all files and directories below a directory, returned by [`subtree_sizes`].
+#[derive(Debug, Default)]
+pub struct NtfsSubtreeSizes {
+    files: u64,
+    directories: u64,
+    logical_bytes: u64,
+    allocated_bytes: u64,
+    errors: Vec<(NtfsPosition, NtfsError)>,
+}
+
+impl NtfsSubtreeSizes {
+    /// Returns the sum of the allocated sizes of all data streams, in bytes
+    /// (see [`NtfsStreamSizes::allocated_size`]).
+    ///
+    /// The sum saturates at [`u64::MAX`] if corrupted sizes would overflow it.
+    ///
+    /// [`NtfsStreamSizes::allocated_size`]: crate::NtfsStreamSizes::allocated_size
+    pub fn allocated_bytes(&self) -> u64 {
+        self.allocated_bytes
+    }
+
+    /// Returns the number of directories (including reparse points to directories).
+    pub fn directories(&self) -> u64 {
+        self.directories
+    }
+
+    /// Returns the errors that occurred while walking the tree or reading a file, along with the byte position
+    /// where each of them occurred (see [`NtfsError::position`]).
+    /// Errors without a position are paired with an [`NtfsPosition`] that has no value.
+    ///
+    /// Entries affected by an error are not counted.
+    pub fn errors(&self) -> &[(NtfsPosition, NtfsError)] {
+        &self.errors
+    }
+
+    /// Returns the number of files (including reparse points to files).
+    pub fn files(&self) -> u64 {
+        self.files
+    }
+
+    /// Returns the sum of the logical sizes of all data streams, in bytes.
+    ///
+    /// This includes alternate data streams.
+    /// Like [`NtfsSubtreeSizes::allocated_bytes`], the sum saturates at [`u64::MAX`].
+    pub fn logical_bytes(&self) -> u64 {
+        self.logical_bytes
+    }
+}
+
+/// Walks all files and directories below the directory `root` and sums up their numbers and sizes.
+///
+/// The following policies apply:
+///
+/// * A file with multiple hard links in the tree is only counted once, identified by its File Record Number.
+/// * Reparse points (like junctions or symbolic links) are counted, but not traversed (see [`NtfsTreeWalker`]).
+/// * All $DATA attributes of a file contribute to the sizes, including alternate data streams.
+///   The sizes are taken from the attribute headers (see [`NtfsFile::data_stream_sizes`]).
+/// * An error on an individual entry is recorded in [`NtfsSubtreeSizes::errors`] and the walk continues.
+///   This includes subnodes of directory indexes that are not marked as in use
+///   (see [`subtree_sizes_with_bitmap_policy`] to skip or read them instead).
+///
+/// This function only fails if `root` is not a directory.
+pub fn subtree_sizes<T>(root: &NtfsFile, fs: &mut T) -> Result<NtfsSubtreeSizes>
+where
+    T: Read + Seek,
+{
+    subtree_sizes_with_bitmap_policy(root, fs, NtfsIndexBitmapPolicy::default())
+}
+
+/// Like [`subtree_sizes`], but handles subnodes of directory indexes that are not marked as in use
+/// according to `bitmap_policy` (see [`NtfsTreeWalker::set_bitmap_policy`]).
+pub fn subtree_sizes_with_bitmap_policy<T>(
+    root: &NtfsFile,
+    fs: &mut T,
+    bitmap_policy: NtfsIndexBitmapPolicy,
+) -> Result<NtfsSubtreeSizes>
+where
+    T: Read + Seek,
+{
+    if !root.is_directory() {
+        return Err(NtfsError::NotADirectory {
+            position: root.position(),
+            index_entry_is_directory: None,
+        });
+    }
+
+    let ntfs = root.ntfs();
+    let mut sizes = NtfsSubtreeSizes::default();
+    let mut visited = BTreeSet::new();
+    let mut walker = NtfsTreeWalker::new(root.clone());
+    walker.set_bitmap_policy(bitmap_policy);
+
+    while let Some(entry) = walker.next(fs) {
+        let result = entry.and_then(|entry| {
+            if !visited.insert(entry.file_reference().file_record_number()) {
+                return Ok(());
+            }
+
+            let file = entry.to_file(ntfs, fs)?;
+            let all_stream_sizes = file.data_stream_sizes(fs)?;
+
+            for stream_sizes in all_stream_sizes {
+                // The sizes come from attribute headers, which may be corrupted.
+                sizes.logical_bytes = sizes.logical_bytes.saturating_add(stream_sizes.data_size());
+                sizes.allocated_bytes = sizes
+                    .allocated_bytes
+                    .saturating_add(stream_sizes.allocated_size());
+            }
+
+            if entry.is_directory() {
+                sizes.directories += 1;
+            } else {
+                sizes.files += 1;
+            }
+
+            Ok(())
+        });
+
+        if let Err(error) = result {
+            let position = error.position().unwrap_or_else(NtfsPosition::none);
+            sizes.errors.push((position, error));
+        }
+    }
+
+    Ok(sizes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attribute::NtfsAttributeType;
+    use crate::helpers::tests::patch_file_record_number;
+    use crate::types::Vcn;
+
+    /// Walks testfs1 from its root directory and returns the name and depth of each entry.
+    fn walk_testfs1<F>(configure: F) -> Vec<(String, usize)>
+    where
+        F: Fn(&mut NtfsTreeWalker, &NtfsTreeEntry),
+    {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let mut walker = NtfsTreeWalker::new(root_dir);
+
+        let mut entries = Vec::new();
+        while let Some(entry) = walker.next(&mut testfs1) {
+            let entry = entry.unwrap();
+            configure(&mut walker, &entry);
+            entries.push((entry.file_name().name().to_string_lossy(), entry.depth()));
+        }
+
+        entries
+    }
+
+    #[test]
+    fn test_walk() {
+        let entries = walk_testfs1(|_, _| {});
+        assert_eq!(entries.len(), 531);
+
+        // The root directory itself and its "." entry are not returned.
+        assert_eq!(entries[0], ("$AttrDef".to_string(), 1));
+        assert!(!entries.iter().any(|(name, _)| name == "."));
+
+        // The contents of a directory directly follow it.
+        let extend_index = entries
+            .iter()
+            .position(|(name, _)| name == "$Extend")
+            .unwrap();
+        assert_eq!(
+            entries[extend_index + 1..extend_index + 5],
+            [
+                ("$ObjId".to_string(), 2),
+                ("$Quota".to_string(), 2),
+                ("$Reparse".to_string(), 2),
+                ("$LogFile".to_string(), 1),
+            ]
+        );
+
+        // "many_subdirs" contains the subdirectories "1" to "512", which are returned in the order of the index.
+        let many_subdirs_index = entries
+            .iter()
+            .position(|(name, _)| name == "many_subdirs")
+            .unwrap();
+        let subdirs = &entries[many_subdirs_index + 1..many_subdirs_index + 513];
+        assert!(subdirs.iter().all(|(_, depth)| *depth == 2));
+        assert_eq!(
+            entries[many_subdirs_index + 513..],
+            [("sparse-file".to_string(), 1)]
+        );
+
+        let mut expected_names = (1..=512).map(|i| i.to_string()).collect::<Vec<_>>();
+        expected_names.sort();
+        let names = subdirs
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, expected_names);
+    }
+
+    #[test]
+    fn test_walk_paths() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let mut walker = NtfsTreeWalker::new(root_dir);
+
+        let mut paths = Vec::new();
+        while let Some(entry) = walker.next(&mut testfs1) {
+            paths.push(entry.unwrap().path().to_string());
+        }
+
+        // Paths are relative to the root directory of the walk.
+        assert_eq!(paths[0], "$AttrDef");
+        assert!(paths.iter().any(|path| path == "$Extend\\$ObjId"));
+        assert!(paths.iter().any(|path| path == "many_subdirs\\512"));
+        assert_eq!(paths.last().unwrap(), "sparse-file");
+
+        // Walking a subdirectory starts the paths at its entries.
+        let many_subdirs = ntfs.file(&mut testfs1, 68).unwrap();
+        let mut walker = NtfsTreeWalker::new(many_subdirs);
+        let entry = walker.next(&mut testfs1).unwrap().unwrap();
+        assert_eq!(entry.path(), "1");
+    }
+
+    #[test]
+    fn test_walk_max_depth_and_skip_subtree() {
+        let entries = walk_testfs1(|walker, _| walker.set_max_depth(1));
+        assert_eq!(entries.len(), 16);
+        assert!(entries.iter().all(|(_, depth)| *depth == 1));
+
+        let entries = walk_testfs1(|walker, entry| {
+            if entry.file_name().name() == "many_subdirs" {
+                walker.skip_subtree();
+            }
+        });
+        assert_eq!(entries.len(), 531 - 512);
+        assert_eq!(
+            entries[entries.len() - 2..],
+            [
+                ("many_subdirs".to_string(), 1),
+                ("sparse-file".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_subtree_sizes() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // The expected numbers have been summed up independently from the raw File Records of testfs1.
+        // Among others, they include both data streams of $BadClus and $UpCase.
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let sizes = subtree_sizes(&root_dir, &mut testfs1).unwrap();
+        assert!(sizes.errors().is_empty());
+        assert_eq!(sizes.files(), 17);
+        assert_eq!(sizes.directories(), 514);
+        assert_eq!(sizes.logical_bytes(), 3863598);
+        assert_eq!(sizes.allocated_bytes(), 3870248);
+
+        // "many_subdirs" (File Record 68) only contains empty directories.
+        let many_subdirs = ntfs.file(&mut testfs1, 68).unwrap();
+        let sizes = subtree_sizes(&many_subdirs, &mut testfs1).unwrap();
+        assert_eq!(sizes.files(), 0);
+        assert_eq!(sizes.directories(), 512);
+        assert_eq!(sizes.logical_bytes(), 0);
+
+        // Only directories can be the root of a subtree.
+        let file = ntfs.file(&mut testfs1, 66).unwrap();
+        assert!(matches!(
+            subtree_sizes(&file, &mut testfs1),
+            Err(NtfsError::NotADirectory { .. })
+        ));
+
+        // An unreadable File Record is recorded as an error, and the walk continues.
+        let file_position = file.position().value().unwrap().get();
+        let start = file_position as usize;
+        testfs1.get_mut()[start..start + 4].copy_from_slice(b"BAAD");
+
+        let sizes = subtree_sizes(&root_dir, &mut testfs1).unwrap();
+        assert_eq!(sizes.errors().len(), 1);
+        let (position, error) = &sizes.errors()[0];
+        assert_eq!(position.value().unwrap().get(), file_position);
+        assert!(matches!(error, NtfsError::InvalidFileSignature { .. }));
+        assert_eq!(sizes.files(), 16);
+        assert_eq!(sizes.logical_bytes(), 3863598 - 1000);
+        assert_eq!(sizes.allocated_bytes(), 3870248 - 1024);
+    }
+
+    /// Returns testfs1 with the Index Record at VCN 0x60 of "many_subdirs" (File Record 68) marked as
+    /// not in use in the $BITMAP attribute of its index.
+    ///
+    /// That Index Record contains the entry of the subdirectory "299".
+    fn testfs1_with_deallocated_index_record() -> std::io::Cursor<Vec<u8>> {
+        // Index Records of "many_subdirs" span 8 clusters.
+        const RECORD_NUMBER: usize = 0x60 / 8;
+
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let value_start = {
+            let ntfs = Ntfs::new(&mut testfs1).unwrap();
+            let many_subdirs = ntfs.file(&mut testfs1, 68).unwrap();
+            let attribute = many_subdirs
+                .find_resident_attribute(NtfsAttributeType::Bitmap, Some("$I30"), None)
+                .unwrap();
+            attribute.resident_value_range().unwrap().start
+        };
+
+        patch_file_record_number(&mut testfs1, 68, |record| {
+            let byte = &mut record[value_start + RECORD_NUMBER / 8];
+            let bit = 1 << (RECORD_NUMBER % 8);
+            assert_ne!(*byte & bit, 0);
+            *byte &= !bit;
+        });
+
+        testfs1
+    }
+
+    #[test]
+    fn test_walk_bitmap_policy() {
+        let mut testfs1 = testfs1_with_deallocated_index_record();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        let mut walk_many_subdirs = |bitmap_policy| {
+            let many_subdirs = ntfs.file(&mut testfs1, 68).unwrap();
+            let mut walker = NtfsTreeWalker::new(many_subdirs);
+            walker.set_bitmap_policy(bitmap_policy);
+            assert_eq!(walker.bitmap_policy(), bitmap_policy);
+
+            let mut paths = Vec::new();
+            let mut errors = Vec::new();
+            while let Some(entry) = walker.next(&mut testfs1) {
+                match entry {
+                    Ok(entry) => paths.push(entry.path().to_string()),
+                    Err(error) => errors.push(error),
+                }
+            }
+
+            (paths, errors)
+        };
+
+        // By default, the deallocated Index Record ends the walk of "many_subdirs" with an error.
+        let (paths, errors) = walk_many_subdirs(NtfsIndexBitmapPolicy::Error);
+        assert!(paths.len() < 512);
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            &errors[0],
+            NtfsError::IndexRecordNotAllocated { vcn, .. } if *vcn == Vcn::from(0x60)
+        ));
+
+        // Skipping it returns all subdirectories except for its entries.
+        let (paths, errors) = walk_many_subdirs(NtfsIndexBitmapPolicy::Skip);
+        assert!(errors.is_empty());
+        assert!(!paths.is_empty() && paths.len() < 512);
+        assert!(!paths.iter().any(|path| path == "299"));
+        assert!(paths.iter().any(|path| path == "512"));
+
+        // Ignoring the $BITMAP attribute returns all subdirectories.
+        let (paths, errors) = walk_many_subdirs(NtfsIndexBitmapPolicy::Ignore);
+        assert!(errors.is_empty());
+        assert_eq!(paths.len(), 512);
+
+        // `subtree_sizes` records the deallocated Index Record as an error,
+        // while `subtree_sizes_with_bitmap_policy` applies the given policy to its walk.
+        let many_subdirs = ntfs.file(&mut testfs1, 68).unwrap();
+        let sizes = subtree_sizes(&many_subdirs, &mut testfs1).unwrap();
+        assert_eq!(sizes.errors().len(), 1);
+
+        let sizes = subtree_sizes_with_bitmap_policy(
+            &many_subdirs,
+            &mut testfs1,
+            NtfsIndexBitmapPolicy::Skip,
+        )
+        .unwrap();
+        assert!(sizes.errors().is_empty());
+        assert!(sizes.directories() < 512);
+
+        let sizes = subtree_sizes_with_bitmap_policy(
+            &many_subdirs,
+            &mut testfs1,
+            NtfsIndexBitmapPolicy::Ignore,
+        )
+        .unwrap();
+        assert!(sizes.errors().is_empty());
+        assert_eq!(sizes.directories(), 512);
+    }
+}
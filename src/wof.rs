@@ -0,0 +1,489 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+//! Support for files compressed by the Windows Overlay Filter (WOF), e.g. via `compact.exe /EXE` or CompactOS.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, LittleEndian};
+use enumn::N;
+
+use crate::attribute::{NtfsAttributeItem, NtfsAttributeType};
+use crate::attribute_value::seek_contiguous;
+use crate::compression::{decompress_xpress_huffman, XPRESS_HUFFMAN_MAX_BLOCK_SIZE};
+use crate::error::{NtfsError, Result};
+use crate::io::{Read, Seek, SeekFrom};
+use crate::traits::NtfsReadSeek;
+use crate::types::NtfsPosition;
+
+/// Reparse point tag of files compressed by the Windows Overlay Filter (`IO_REPARSE_TAG_WOF`).
+pub(crate) const IO_REPARSE_TAG_WOF: u32 = 0x8000_0017;
+
+/// Name of the $DATA attribute that holds the compressed data of a WOF-compressed file.
+pub(crate) const WOF_COMPRESSED_DATA_STREAM_NAME: &str = "WofCompressedData";
+
+/// Size of the reparse tag, reparse data length, and reserved fields preceding every reparse data.
+const REPARSE_POINT_HEADER_SIZE: usize = 8;
+
+/// Size of the `WOF_EXTERNAL_INFO` and `FILE_PROVIDER_EXTERNAL_INFO_V1` structures following the reparse point header.
+const WOF_REPARSE_DATA_SIZE: usize = 16;
+
+/// WOF provider that stores the compressed data in an alternate data stream of the file itself.
+const WOF_PROVIDER_FILE: u32 = 2;
+
+/// Compression algorithms supported by the file provider of the Windows Overlay Filter (WOF).
+#[derive(Clone, Copy, Debug, Eq, N, PartialEq)]
+#[repr(u32)]
+pub enum NtfsWofAlgorithm {
+    Xpress4K = 0,
+    Lzx = 1,
+    Xpress8K = 2,
+    Xpress16K = 3,
+}
+
+impl NtfsWofAlgorithm {
+    /// Returns the number of uncompressed bytes that are compressed together as a single chunk.
+    pub fn chunk_size(&self) -> u32 {
+        match self {
+            Self::Xpress4K => 4096,
+            Self::Lzx => 32768,
+            Self::Xpress8K => 8192,
+            Self::Xpress16K => 16384,
+        }
+    }
+}
+
+/// Information about a file compressed by the Windows Overlay Filter (WOF), as returned by [`NtfsFile::wof_info`].
+///
+/// WOF-compressed files are marked by a reparse point with tag `IO_REPARSE_TAG_WOF`.
+/// Their unnamed $DATA attribute is sparse and only indicates the uncompressed size,
+/// while the actual compressed data is stored in a $DATA attribute named "WofCompressedData".
+///
+/// Reference: <https://github.com/ebiggers/ntfs-3g-system-compression>
+///
+/// [`NtfsFile::wof_info`]: crate::NtfsFile::wof_info
+#[derive(Clone, Debug)]
+pub struct NtfsWofInfo {
+    position: NtfsPosition,
+    version: u32,
+    provider: u32,
+    provider_version: u32,
+    algorithm: u32,
+}
+
+impl NtfsWofInfo {
+    /// Parses the given reparse point data, returning `None` if it doesn't belong to the Windows Overlay Filter.
+    pub(crate) fn from_reparse_point(data: &[u8], position: NtfsPosition) -> Result<Option<Self>> {
+        let expected = REPARSE_POINT_HEADER_SIZE + WOF_REPARSE_DATA_SIZE;
+
+        if data.len() < REPARSE_POINT_HEADER_SIZE {
+            return Err(NtfsError::InvalidStructuredValueSize {
+                position,
+                ty: NtfsAttributeType::ReparsePoint,
+                expected: REPARSE_POINT_HEADER_SIZE as u64,
+                actual: data.len() as u64,
+            });
+        }
+
+        if LittleEndian::read_u32(data) != IO_REPARSE_TAG_WOF {
+            return Ok(None);
+        }
+
+        if data.len() < expected {
+            return Err(NtfsError::InvalidStructuredValueSize {
+                position,
+                ty: NtfsAttributeType::ReparsePoint,
+                expected: expected as u64,
+                actual: data.len() as u64,
+            });
+        }
+
+        let data = &data[REPARSE_POINT_HEADER_SIZE..];
+
+        Ok(Some(Self {
+            position,
+            version: LittleEndian::read_u32(&data[0..]),
+            provider: LittleEndian::read_u32(&data[4..]),
+            provider_version: LittleEndian::read_u32(&data[8..]),
+            algorithm: LittleEndian::read_u32(&data[12..]),
+        }))
+    }
+
+    /// Returns the compression algorithm used for this file.
+    ///
+    /// This is only meaningful if [`NtfsWofInfo::provider`] indicates the file provider (2).
+    pub fn algorithm(&self) -> Result<NtfsWofAlgorithm> {
+        NtfsWofAlgorithm::n(self.algorithm).ok_or(NtfsError::UnsupportedWofAlgorithm {
+            position: self.position,
+            actual: self.algorithm,
+        })
+    }
+
+    /// Returns the absolute position of the reparse point data within the filesystem, in bytes.
+    pub fn position(&self) -> NtfsPosition {
+        self.position
+    }
+
+    /// Returns the WOF provider of this file (1 for WIM-backed files, 2 for individually compressed files).
+    pub fn provider(&self) -> u32 {
+        self.provider
+    }
+
+    /// Returns the version of the provider-specific information.
+    pub fn provider_version(&self) -> u32 {
+        self.provider_version
+    }
+
+    /// Returns the version of the general WOF information.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+/// The data of an unnamed $DATA attribute, as returned by [`NtfsFile::data_resolved`].
+///
+/// [`NtfsFile::data_resolved`]: crate::NtfsFile::data_resolved
+#[derive(Clone, Debug)]
+pub enum NtfsResolvedData<'n, 'f> {
+    /// The data is stored in the unnamed $DATA attribute as usual.
+    Plain(NtfsAttributeItem<'n, 'f>),
+    /// The file is compressed by the Windows Overlay Filter and read through an [`NtfsWofReader`].
+    WofCompressed(NtfsWofReader<'n, 'f>),
+}
+
+/// Reader for the uncompressed data of a file compressed by the Windows Overlay Filter (WOF).
+///
+/// The "WofCompressedData" attribute begins with a table of the offsets of all chunks but the first one,
+/// followed by the chunks themselves.
+/// Every chunk holds [`NtfsWofAlgorithm::chunk_size`] bytes of uncompressed data (less for the last one)
+/// and is stored uncompressed if compression didn't make it smaller.
+///
+/// The reader decompresses one chunk at a time and keeps the last decompressed chunk around for subsequent reads.
+/// Only the XPRESS algorithms are supported.
+#[derive(Clone, Debug)]
+pub struct NtfsWofReader<'n, 'f> {
+    compressed_item: NtfsAttributeItem<'n, 'f>,
+    chunk_size: u32,
+    /// Offsets of all chunks within the "WofCompressedData" attribute, followed by the end offset of the last chunk.
+    chunk_offsets: Vec<u64>,
+    data_size: u64,
+    stream_position: u64,
+    /// Index and uncompressed data of the last decompressed chunk.
+    cached_chunk: Option<(usize, Vec<u8>)>,
+}
+
+impl<'n, 'f> NtfsWofReader<'n, 'f> {
+    /// Creates a reader for `data_size` bytes of uncompressed data compressed according to `info`
+    /// into the "WofCompressedData" attribute `compressed_item`.
+    pub fn new<T>(
+        fs: &mut T,
+        info: &NtfsWofInfo,
+        compressed_item: NtfsAttributeItem<'n, 'f>,
+        data_size: u64,
+    ) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        if info.provider() != WOF_PROVIDER_FILE {
+            return Err(NtfsError::UnsupportedWofProvider {
+                position: info.position(),
+                actual: info.provider(),
+            });
+        }
+
+        let algorithm = info.algorithm()?;
+        if algorithm == NtfsWofAlgorithm::Lzx {
+            return Err(NtfsError::UnsupportedWofAlgorithm {
+                position: info.position(),
+                actual: algorithm as u32,
+            });
+        }
+
+        let chunk_size = algorithm.chunk_size();
+        debug_assert!(chunk_size as usize <= XPRESS_HUFFMAN_MAX_BLOCK_SIZE);
+
+        let compressed_attribute = compressed_item.to_attribute()?;
+        let mut compressed_value = compressed_attribute.value(fs)?;
+        let compressed_size = compressed_value.len();
+        let position = compressed_value.data_position();
+
+        // Files larger than 4 GiB use 64-bit table entries.
+        let chunk_count =
+            data_size / chunk_size as u64 + u64::from(data_size % chunk_size as u64 != 0);
+        let entry_size = if data_size > u32::MAX as u64 { 8 } else { 4 };
+        let table_size = chunk_count.saturating_sub(1) * entry_size;
+
+        // Check this before allocating anything based on the untrusted data size.
+        if table_size > compressed_size {
+            return Err(NtfsError::InvalidCompressedData {
+                position,
+                reason: "the chunk table exceeds the compressed data",
+            });
+        }
+
+        let mut table = vec![0u8; table_size as usize];
+        compressed_value.read_exact(fs, &mut table)?;
+
+        // The table offsets are relative to the end of the table, and the first chunk immediately follows it.
+        let mut chunk_offsets = Vec::with_capacity(chunk_count as usize + 1);
+        if chunk_count > 0 {
+            chunk_offsets.push(table_size);
+        }
+
+        for entry in table.chunks_exact(entry_size as usize) {
+            let offset = if entry_size == 8 {
+                LittleEndian::read_u64(entry)
+            } else {
+                LittleEndian::read_u32(entry) as u64
+            };
+
+            let offset =
+                offset
+                    .checked_add(table_size)
+                    .ok_or(NtfsError::InvalidCompressedData {
+                        position,
+                        reason: "a chunk offset is out of range",
+                    })?;
+            chunk_offsets.push(offset);
+        }
+
+        chunk_offsets.push(compressed_size);
+
+        if chunk_offsets.windows(2).any(|pair| pair[0] > pair[1]) {
+            return Err(NtfsError::InvalidCompressedData {
+                position,
+                reason: "the chunk offsets are not ascending or exceed the compressed data",
+            });
+        }
+
+        Ok(Self {
+            compressed_item,
+            chunk_size,
+            chunk_offsets,
+            data_size,
+            stream_position: 0,
+            cached_chunk: None,
+        })
+    }
+
+    /// Returns the uncompressed data of the chunk with the given index, decompressing it if necessary.
+    fn chunk<T>(&mut self, fs: &mut T, index: usize) -> Result<&[u8]>
+    where
+        T: Read + Seek,
+    {
+        if !matches!(&self.cached_chunk, Some((cached_index, _)) if *cached_index == index) {
+            let chunk_start = index as u64 * self.chunk_size as u64;
+            let uncompressed_size = u64::min(self.chunk_size as u64, self.data_size - chunk_start);
+            let uncompressed_size = uncompressed_size as usize;
+
+            let compressed_start = self.chunk_offsets[index];
+            let compressed_size = (self.chunk_offsets[index + 1] - compressed_start) as usize;
+
+            let compressed_attribute = self.compressed_item.to_attribute()?;
+            let mut compressed_value = compressed_attribute.value(fs)?;
+            compressed_value.seek(fs, SeekFrom::Start(compressed_start))?;
+            let position = compressed_value.data_position();
+
+            if compressed_size > uncompressed_size {
+                return Err(NtfsError::InvalidCompressedData {
+                    position,
+                    reason: "a chunk is larger than its uncompressed data",
+                });
+            }
+
+            let mut compressed = vec![0u8; compressed_size];
+            compressed_value.read_exact(fs, &mut compressed)?;
+
+            // Chunks that don't get smaller through compression are stored uncompressed.
+            let data = if compressed_size == uncompressed_size {
+                compressed
+            } else {
+                let mut data = vec![0u8; uncompressed_size];
+                decompress_xpress_huffman(&compressed, &mut data)
+                    .map_err(|reason| NtfsError::InvalidCompressedData { position, reason })?;
+                data
+            };
+
+            self.cached_chunk = Some((index, data));
+        }
+
+        Ok(&self.cached_chunk.as_ref().unwrap().1)
+    }
+
+    /// Returns `true` if the uncompressed data is empty.
+    pub fn is_empty(&self) -> bool {
+        self.data_size == 0
+    }
+
+    /// Returns the size of the uncompressed data, in bytes.
+    pub fn len(&self) -> u64 {
+        self.data_size
+    }
+}
+
+impl<'n, 'f> NtfsReadSeek for NtfsWofReader<'n, 'f> {
+    fn read<T>(&mut self, fs: &mut T, buf: &mut [u8]) -> Result<usize>
+    where
+        T: Read + Seek,
+    {
+        if self.stream_position >= self.data_size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let index = (self.stream_position / self.chunk_size as u64) as usize;
+        let offset_in_chunk = (self.stream_position % self.chunk_size as u64) as usize;
+
+        let chunk = self.chunk(fs, index)?;
+        let bytes_to_read = usize::min(buf.len(), chunk.len() - offset_in_chunk);
+        buf[..bytes_to_read]
+            .copy_from_slice(&chunk[offset_in_chunk..offset_in_chunk + bytes_to_read]);
+
+        self.stream_position += bytes_to_read as u64;
+        Ok(bytes_to_read)
+    }
+
+    fn seek<T>(&mut self, _fs: &mut T, pos: SeekFrom) -> Result<u64>
+    where
+        T: Read + Seek,
+    {
+        seek_contiguous(&mut self.stream_position, self.data_size, pos)
+    }
+
+    fn stream_position(&self) -> u64 {
+        self.stream_position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::tests::{fox_data, fox_data_compressed};
+    use crate::helpers::tests::add_resident_attribute;
+    use crate::ntfs::Ntfs;
+
+    /// Returns the value of a $REPARSE_POINT attribute marking a file as WOF-compressed with the given algorithm.
+    fn wof_reparse_point(algorithm: u32) -> Vec<u8> {
+        let mut data = vec![0u8; REPARSE_POINT_HEADER_SIZE + WOF_REPARSE_DATA_SIZE];
+        LittleEndian::write_u32(&mut data[0..], IO_REPARSE_TAG_WOF);
+        LittleEndian::write_u16(&mut data[4..], WOF_REPARSE_DATA_SIZE as u16);
+        LittleEndian::write_u32(&mut data[8..], 1);
+        LittleEndian::write_u32(&mut data[12..], WOF_PROVIDER_FILE);
+        LittleEndian::write_u32(&mut data[16..], 1);
+        LittleEndian::write_u32(&mut data[20..], algorithm);
+        data
+    }
+
+    /// Turns "file-with-12345" in testfs1 into a WOF-compressed file with the given algorithm and compressed data.
+    fn testfs1_with_wof(algorithm: u32, compressed_data: &[u8]) -> std::io::Cursor<Vec<u8>> {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        add_resident_attribute(
+            &mut testfs1,
+            65,
+            NtfsAttributeType::Data,
+            WOF_COMPRESSED_DATA_STREAM_NAME,
+            compressed_data,
+        );
+        add_resident_attribute(
+            &mut testfs1,
+            65,
+            NtfsAttributeType::ReparsePoint,
+            "",
+            &wof_reparse_point(algorithm),
+        );
+        testfs1
+    }
+
+    #[test]
+    fn test_wof_info() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        assert!(file.wof_info(&mut testfs1).is_none());
+        assert!(matches!(
+            file.data_resolved(&mut testfs1).unwrap().unwrap(),
+            NtfsResolvedData::Plain(_)
+        ));
+
+        let mut testfs1 = testfs1_with_wof(NtfsWofAlgorithm::Xpress8K as u32, b"12345");
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        let info = file.wof_info(&mut testfs1).unwrap().unwrap();
+        assert_eq!(info.version(), 1);
+        assert_eq!(info.provider(), WOF_PROVIDER_FILE);
+        assert_eq!(info.provider_version(), 1);
+        assert_eq!(info.algorithm().unwrap(), NtfsWofAlgorithm::Xpress8K);
+    }
+
+    #[test]
+    fn test_wof_reader() {
+        // Two chunks: The first one is compressed, the second one is stored uncompressed.
+        let fox_compressed = fox_data_compressed();
+        let mut compressed_data = (fox_compressed.len() as u32).to_le_bytes().to_vec();
+        compressed_data.extend_from_slice(&fox_compressed);
+        compressed_data.extend_from_slice(b"hello");
+
+        let mut testfs1 = testfs1_with_wof(NtfsWofAlgorithm::Xpress4K as u32, &compressed_data);
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        let info = file.wof_info(&mut testfs1).unwrap().unwrap();
+        let compressed_item = file
+            .data(&mut testfs1, WOF_COMPRESSED_DATA_STREAM_NAME)
+            .unwrap()
+            .unwrap();
+
+        let mut reader =
+            NtfsWofReader::new(&mut testfs1, &info, compressed_item.clone(), 4101).unwrap();
+        assert_eq!(reader.len(), 4101);
+
+        let mut buf = vec![0u8; 4101];
+        reader.read_exact(&mut testfs1, &mut buf).unwrap();
+        assert_eq!(buf[..4096], fox_data());
+        assert_eq!(buf[4096..], *b"hello");
+        assert_eq!(reader.read(&mut testfs1, &mut buf).unwrap(), 0);
+
+        // Seeking back into the first chunk decompresses it again.
+        reader.seek(&mut testfs1, SeekFrom::Start(4)).unwrap();
+        let mut buf = [0u8; 5];
+        reader.read_exact(&mut testfs1, &mut buf).unwrap();
+        assert_eq!(&buf, b"quick");
+
+        // A chunk table that doesn't fit into the compressed data is rejected.
+        assert!(matches!(
+            NtfsWofReader::new(&mut testfs1, &info, compressed_item.clone(), 1 << 30),
+            Err(NtfsError::InvalidCompressedData { .. })
+        ));
+
+        // A chunk offset beyond the compressed data is rejected.
+        assert!(matches!(
+            NtfsWofReader::new(&mut testfs1, &info, compressed_item, 4096 * 3),
+            Err(NtfsError::InvalidCompressedData { .. })
+        ));
+    }
+
+    #[test]
+    fn test_data_resolved() {
+        // "file-with-12345" has an unnamed $DATA attribute of 5 bytes, which fit into a single uncompressed chunk.
+        let mut testfs1 = testfs1_with_wof(NtfsWofAlgorithm::Xpress16K as u32, b"hello");
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+
+        let mut reader = match file.data_resolved(&mut testfs1).unwrap().unwrap() {
+            NtfsResolvedData::WofCompressed(reader) => reader,
+            NtfsResolvedData::Plain(_) => panic!("expected a WOF-compressed file"),
+        };
+        let mut buf = Vec::new();
+        reader
+            .read_to_end(&mut testfs1, &mut buf, usize::MAX)
+            .unwrap();
+        assert_eq!(buf, b"hello");
+
+        // LZX is recognized, but not supported.
+        let mut testfs1 = testfs1_with_wof(NtfsWofAlgorithm::Lzx as u32, b"hello");
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let file = ntfs.file(&mut testfs1, 65).unwrap();
+        assert!(matches!(
+            file.data_resolved(&mut testfs1).unwrap(),
+            Err(NtfsError::UnsupportedWofAlgorithm { actual: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,30 @@
+#!/bin/bash
+# Creates a manifest of all files and directories of an NTFS filesystem image as seen through ntfs-3g.
+# The manifest is used by tests/conformance.rs to compare the output of this crate against ntfs-3g.
+#
+# Usage: ./create-manifest.sh testfs1 > testfs1.manifest
+set -eu
+
+if [ "`whoami`" != "root" ]; then
+    echo Needs to be run as root! >&2
+    exit 1
+fi
+
+IMAGE="$1"
+
+mkdir mnt
+mount -t ntfs-3g -o loop,ro "$IMAGE" mnt
+cd mnt
+
+echo "# Recursive listing of $IMAGE as seen through ntfs-3g, generated by create-manifest.sh."
+echo "# Format: <File Record Number> TAB <d|f> TAB <data size> TAB <path>"
+
+# ntfs-3g reports the File Record Number as the inode number.
+# Directories are listed with a data size of 0, as they have no unnamed $DATA attribute.
+find . -mindepth 1 -printf '%i\t%y\t%s\t%P\n' \
+    | awk -F'\t' 'BEGIN { OFS = "\t" } { if ($2 == "d") $3 = 0; print }' \
+    | LC_ALL=C sort -t$'\t' -k4
+
+cd ..
+umount mnt
+rmdir mnt
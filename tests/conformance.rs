@@ -0,0 +1,226 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Compares the recursive directory listing of our test filesystems against manifests generated by ntfs-3g.
+//
+// These tests are only run if the `NTFS_CONFORMANCE_TESTS` environment variable is set,
+// because they need the manifest files next to the filesystem images in the `testdata` directory.
+// Run `testdata/create-manifest.sh` to (re)generate a manifest for a filesystem image.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Cursor, Read};
+
+use ntfs::structured_values::NtfsFileNamespace;
+use ntfs::{Ntfs, NtfsFile};
+
+/// ntfs-3g hides all files with a File Record Number below this value (the reserved system files).
+const FIRST_USER_FILE_RECORD_NUMBER: u64 = 16;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct ManifestEntry {
+    file_record_number: u64,
+    is_directory: bool,
+    size: u64,
+}
+
+impl fmt::Display for ManifestEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ty = if self.is_directory { 'd' } else { 'f' };
+        write!(f, "{}\t{}\t{}", self.file_record_number, ty, self.size)
+    }
+}
+
+fn parse_manifest(text: &str) -> BTreeMap<String, ManifestEntry> {
+    let mut manifest = BTreeMap::new();
+
+    for (i, line) in text.lines().enumerate() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields = line.splitn(4, '\t').collect::<Vec<_>>();
+        assert_eq!(
+            fields.len(),
+            4,
+            "Malformed manifest line {}: {line:?}",
+            i + 1
+        );
+
+        let file_record_number = fields[0].parse().unwrap();
+        let is_directory = match fields[1] {
+            "d" => true,
+            "f" => false,
+            ty => panic!("Unknown entry type {ty:?} in manifest line {}", i + 1),
+        };
+        let size = fields[2].parse().unwrap();
+        let path = fields[3].to_string();
+
+        let entry = ManifestEntry {
+            file_record_number,
+            is_directory,
+            size,
+        };
+        manifest.insert(path, entry);
+    }
+
+    manifest
+}
+
+fn unnamed_data_size(file: &NtfsFile, fs: &mut Cursor<Vec<u8>>) -> u64 {
+    match file.data(fs, "") {
+        Some(data_item) => {
+            let data_item = data_item.unwrap();
+            data_item.to_attribute().unwrap().value_length()
+        }
+        None => 0,
+    }
+}
+
+fn list_recursively(fs: &mut Cursor<Vec<u8>>) -> BTreeMap<String, ManifestEntry> {
+    let ntfs = Ntfs::new(fs).unwrap();
+    let root_dir = ntfs.root_directory(fs).unwrap();
+
+    let mut listing = BTreeMap::new();
+    let mut directories_to_visit = vec![(String::new(), root_dir)];
+
+    while let Some((dir_path, dir)) = directories_to_visit.pop() {
+        let index = dir.directory_index(fs).unwrap();
+        let mut iter = index.entries();
+
+        while let Some(entry) = iter.next(fs) {
+            let entry = entry.unwrap();
+            let file_name = entry.key().unwrap().unwrap();
+
+            // ntfs-3g only lists the long name of a file and skips system files.
+            if file_name.namespace() == NtfsFileNamespace::Dos {
+                continue;
+            }
+
+            let file_record_number = entry.file_reference().file_record_number();
+            if file_record_number < FIRST_USER_FILE_RECORD_NUMBER {
+                continue;
+            }
+
+            let name = file_name.name().to_string_lossy();
+            let path = if dir_path.is_empty() {
+                name
+            } else {
+                format!("{dir_path}/{name}")
+            };
+
+            let file = entry.to_file(&ntfs, fs).unwrap();
+            let is_directory = file.is_directory();
+            let size = if is_directory {
+                0
+            } else {
+                unnamed_data_size(&file, fs)
+            };
+
+            let manifest_entry = ManifestEntry {
+                file_record_number,
+                is_directory,
+                size,
+            };
+            listing.insert(path.clone(), manifest_entry);
+
+            if is_directory {
+                directories_to_visit.push((path, file));
+            }
+        }
+    }
+
+    listing
+}
+
+fn diff_listings(
+    expected: &BTreeMap<String, ManifestEntry>,
+    actual: &BTreeMap<String, ManifestEntry>,
+) -> Vec<String> {
+    let mut differences = Vec::new();
+
+    for (path, expected_entry) in expected {
+        match actual.get(path) {
+            Some(actual_entry) if actual_entry == expected_entry => (),
+            Some(actual_entry) => {
+                differences.push(format!("- {expected_entry}\t{path}"));
+                differences.push(format!("+ {actual_entry}\t{path}"));
+            }
+            None => differences.push(format!("- {expected_entry}\t{path}")),
+        }
+    }
+
+    for (path, actual_entry) in actual {
+        if !expected.contains_key(path) {
+            differences.push(format!("+ {actual_entry}\t{path}"));
+        }
+    }
+
+    differences
+}
+
+fn check_conformance(image_name: &str) {
+    let manifest_path = format!("testdata/{image_name}.manifest");
+    let manifest_text = fs::read_to_string(&manifest_path)
+        .unwrap_or_else(|e| panic!("Cannot read {manifest_path}: {e}"));
+    let expected = parse_manifest(&manifest_text);
+
+    let mut buffer = Vec::new();
+    File::open(format!("testdata/{image_name}"))
+        .unwrap()
+        .read_to_end(&mut buffer)
+        .unwrap();
+    let mut fs = Cursor::new(buffer);
+    let actual = list_recursively(&mut fs);
+
+    let differences = diff_listings(&expected, &actual);
+    assert!(
+        differences.is_empty(),
+        "Listing of {image_name} differs from {manifest_path} (- expected, + actual):\n{}",
+        differences.join("\n")
+    );
+}
+
+fn conformance_tests_enabled() -> bool {
+    env::var_os("NTFS_CONFORMANCE_TESTS").is_some()
+}
+
+#[test]
+fn test_conformance_testfs1() {
+    if !conformance_tests_enabled() {
+        eprintln!("Skipping, because NTFS_CONFORMANCE_TESTS is not set.");
+        return;
+    }
+
+    check_conformance("testfs1");
+}
+
+// Make sure that the comparison logic itself works, independent of any manifest file.
+#[test]
+fn test_diff_listings() {
+    let expected = parse_manifest("# comment\n64\td\t0\tdir\n65\tf\t5\tdir/file\n66\tf\t1\tgone\n");
+    let mut actual = expected.clone();
+    actual.remove("gone");
+    actual.get_mut("dir/file").unwrap().size = 6;
+    actual.insert(
+        "new".to_string(),
+        ManifestEntry {
+            file_record_number: 67,
+            is_directory: false,
+            size: 0,
+        },
+    );
+
+    let differences = diff_listings(&expected, &actual);
+    assert_eq!(
+        differences,
+        [
+            "- 65\tf\t5\tdir/file",
+            "+ 65\tf\t6\tdir/file",
+            "- 66\tf\t1\tgone",
+            "+ 67\tf\t0\tnew",
+        ]
+    );
+}
@@ -0,0 +1,138 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Checks that traversing an index allocates a buffer per Index Record, not per Index Entry,
+// and that the Index Root is borrowed from its File Record instead of being copied.
+//
+// This is a separate test binary, because it installs a global allocator that counts allocations.
+// Allocations are counted per thread, because the test harness runs the tests in parallel.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::thread::LocalKey;
+
+use ntfs::indexes::NtfsFileNameIndex;
+use ntfs::{KnownNtfsFileRecordNumber, Ntfs, UpcaseOrd};
+
+/// Allocations of at least this size are counted.
+/// This matches the Index Record size of testfs1, and is larger than any other allocation made during a traversal.
+const COUNTED_ALLOCATION_SIZE: usize = 4096;
+
+/// Size of the entries of the Index Root of the "$Extend" directory in testfs1.
+/// This directory is small enough to be stored entirely in its Index Root.
+const EXTEND_INDEX_ROOT_ENTRIES_SIZE: usize = 312;
+
+struct CountingAllocator;
+
+thread_local! {
+    // These must be const-initialized and not need dropping, so accessing them never allocates.
+    static LARGE_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    static INDEX_ROOT_SIZED_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count_allocation(counter: &'static LocalKey<Cell<usize>>) {
+    // The counter is unavailable while the thread is being torn down.
+    let _ = counter.try_with(|count| count.set(count.get() + 1));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.size() >= COUNTED_ALLOCATION_SIZE {
+            count_allocation(&LARGE_ALLOCATIONS);
+        }
+
+        if layout.size() == EXTEND_INDEX_ROOT_ENTRIES_SIZE {
+            count_allocation(&INDEX_ROOT_SIZED_ALLOCATIONS);
+        }
+
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn large_allocations() -> usize {
+    LARGE_ALLOCATIONS.with(Cell::get)
+}
+
+fn index_root_sized_allocations() -> usize {
+    INDEX_ROOT_SIZED_ALLOCATIONS.with(Cell::get)
+}
+
+fn testfs1() -> Cursor<Vec<u8>> {
+    let mut buffer = Vec::new();
+    File::open("testdata/testfs1")
+        .unwrap()
+        .read_to_end(&mut buffer)
+        .unwrap();
+    Cursor::new(buffer)
+}
+
+#[test]
+fn test_index_allocations() {
+    let mut testfs1 = testfs1();
+
+    let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+    ntfs.read_upcase_table(&mut testfs1).unwrap();
+    let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+    let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+    let mut root_dir_finder = root_dir_index.finder();
+    let entry = NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+        .unwrap()
+        .unwrap();
+    let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+    let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+
+    // The 512 entries of "many_subdirs" are stored in 21 Index Records.
+    const INDEX_RECORD_COUNT: usize = 21;
+
+    // Iterating all entries reads every Index Record exactly once.
+    let before = large_allocations();
+    let mut subdir_iter = subdir_index.entries();
+    let mut entry_count = 0;
+    while let Some(entry) = subdir_iter.next(&mut testfs1) {
+        entry.unwrap();
+        entry_count += 1;
+    }
+    assert_eq!(entry_count, 512);
+    assert_eq!(large_allocations() - before, INDEX_RECORD_COUNT);
+
+    // Seeking visits many entries on every node level, but must only read the Index Records on its path
+    // (an inner node and a leaf node below the Index Root).
+    let before = large_allocations();
+    let mut subdir_finder = subdir_index.finder();
+    subdir_finder
+        .seek_to(&mut testfs1, |file_name| {
+            "999".upcase_cmp(&ntfs, &file_name.name()).unwrap()
+        })
+        .unwrap();
+    assert_eq!(large_allocations() - before, 2);
+}
+
+#[test]
+fn test_index_root_not_copied() {
+    let mut testfs1 = testfs1();
+    let ntfs = Ntfs::new(&mut testfs1).unwrap();
+    let extend_dir = ntfs
+        .file(&mut testfs1, KnownNtfsFileRecordNumber::Extend as u64)
+        .unwrap();
+
+    // Neither creating the index nor iterating it copies the entries of the Index Root.
+    let before = index_root_sized_allocations();
+    let extend_dir_index = extend_dir.directory_index(&mut testfs1).unwrap();
+    let mut extend_dir_iter = extend_dir_index.entries();
+    let mut entry_count = 0;
+    while let Some(entry) = extend_dir_iter.next(&mut testfs1) {
+        entry.unwrap();
+        entry_count += 1;
+    }
+    assert_eq!(entry_count, 3);
+    assert_eq!(index_root_sized_allocations() - before, 0);
+}